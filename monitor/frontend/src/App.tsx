@@ -4,7 +4,7 @@ import { StreamCard } from "@/components/StreamCard";
 import { MetricsTable } from "@/components/MetricsTable";
 import { StatusIndicator } from "@/components/StatusIndicator";
 import { ConnectionBanner } from "@/components/ConnectionBanner";
-import { UptimeChart24h, RateChart } from "@/components/Charts";
+import { UptimeChart24h, RateChart, HydrationChart } from "@/components/Charts";
 import { DeltaCard } from "@/components/DeltaCard";
 import {
   StreamStats,
@@ -194,6 +194,7 @@ function App() {
                   rate={stats.rate_a || 0}
                   streak={stats.current_streak_a}
                   uptimeAllTime={stats.uptime_a_all_time}
+                  hydration={stats.hydration_a}
                   connected={stats.connected_a || false}
                 />
                 <StreamCard
@@ -204,6 +205,7 @@ function App() {
                   rate={stats.rate_b || 0}
                   streak={stats.current_streak_b}
                   uptimeAllTime={stats.uptime_b_all_time}
+                  hydration={stats.hydration_b}
                   connected={stats.connected_b || false}
                 />
                 <StreamCard
@@ -240,7 +242,7 @@ function App() {
                 <h2 className="monitor-section-title">Message-rate divergence across selected windows</h2>
                 <p className="monitor-section-copy">
                   Window controls update throughput and analytics together so trend interpretation stays
-                  consistent.
+                  consistent. Hydration percentiles appear for turbo streams that report enrichment time.
                 </p>
               </div>
             </div>
@@ -276,6 +278,14 @@ function App() {
               intervalSeconds={intervalSeconds}
               windowLabel={windowLabel}
             />
+
+            <HydrationChart
+              data={hourlyData}
+              streamAName={streamAName}
+              streamBName={streamBName}
+              renderState={historyRenderState}
+              windowLabel={windowLabel}
+            />
           </section>
 
           <section
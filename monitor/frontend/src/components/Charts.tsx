@@ -674,3 +674,159 @@ export function RateChart({
     />
   );
 }
+
+type HydrationChartProps = Omit<ChartProps, "baseline1Name" | "baseline2Name">;
+
+export function HydrationChart({
+  data,
+  streamAName,
+  streamBName,
+  renderState = "ready",
+  windowLabel = "24H",
+}: HydrationChartProps) {
+  const hasData = data.some(
+    (row) => row.stream_a_hydration_p50_ms !== null || row.stream_b_hydration_p50_ms !== null,
+  );
+  const title = `HYDRATION LATENCY ${windowLabel}`;
+  const palette = useMemo(() => getChartPalette(), []);
+
+  const chartContent = useMemo(() => {
+    if (!hasData) return null;
+
+    const labels = data.map((row) => formatHourLabel(row.hour));
+    const series = [
+      {
+        stream: streamAName,
+        color: palette.streamA,
+        values: data.map((row) => [
+          row.stream_a_hydration_p50_ms,
+          row.stream_a_hydration_p95_ms,
+          row.stream_a_hydration_p99_ms,
+        ]),
+      },
+      {
+        stream: streamBName,
+        color: palette.streamB,
+        values: data.map((row) => [
+          row.stream_b_hydration_p50_ms,
+          row.stream_b_hydration_p95_ms,
+          row.stream_b_hydration_p99_ms,
+        ]),
+      },
+    ];
+    const percentiles = [
+      { label: "p50", index: 0, dash: [] as number[] },
+      { label: "p95", index: 1, dash: palette.baselineDashed },
+      { label: "p99", index: 2, dash: [2, 4] },
+    ];
+    const datasets = series.flatMap(({ stream, color, values }) =>
+      percentiles.map(({ label, index, dash }) => ({
+        label: `${stream} ${label}`,
+        data: values.map((row) => row[index]),
+        borderColor: color,
+        backgroundColor: "transparent",
+        borderDash: dash,
+        tension: 0.12,
+        borderWidth: index === 0 ? 2 : 1.5,
+        pointRadius: index === 0 ? 2 : 1,
+        pointHoverRadius: 4,
+        pointHoverBorderWidth: 2,
+      })),
+    );
+    const latencyDomain = getAdaptiveDomain(
+      datasets.flatMap((dataset) =>
+        dataset.data.filter((value): value is number => value !== null),
+      ),
+      {
+        defaultMin: 0,
+        defaultMax: 100,
+        minPadding: 1,
+        minSpan: 10,
+        paddingRatio: 0.12,
+        flatPaddingRatio: 0.2,
+        clampMin: 0,
+      },
+    );
+
+    return {
+      chartData: { labels, datasets },
+      options: {
+        responsive: true,
+        maintainAspectRatio: false,
+        plugins: {
+          legend: {
+            position: "top" as const,
+            align: "end" as const,
+            labels: {
+              color: palette.textLight,
+              usePointStyle: true,
+              pointStyle: "rect" as const,
+              padding: 16,
+              font: terminalFont,
+            },
+          },
+          tooltip: {
+            backgroundColor: palette.tooltipBg,
+            titleColor: palette.textStrong,
+            bodyColor: palette.textLight,
+            borderColor: palette.grid,
+            borderWidth: 2,
+            cornerRadius: 0,
+            padding: 10,
+            titleFont: terminalFont,
+            bodyFont: terminalFont,
+          },
+        },
+        scales: {
+          x: {
+            ticks: { color: palette.text, maxTicksLimit: 12, font: terminalFont },
+            grid: { color: palette.grid, drawTicks: false },
+          },
+          y: {
+            type: "linear" as const,
+            afterBuildTicks: (scale: unknown) => {
+              const axis = scale as { ticks: Array<{ value: number | string }> };
+              sortNumericAxisTicks(axis.ticks);
+            },
+            ticks: {
+              color: palette.text,
+              callback: (v: number | string) =>
+                formatAdaptiveYAxisTick(v, latencyDomain.min, latencyDomain.max, "ms"),
+              font: terminalFont,
+            },
+            grid: { color: palette.grid, drawTicks: false },
+            min: latencyDomain.min,
+            max: latencyDomain.max,
+          },
+        },
+      },
+    };
+  }, [data, hasData, palette, streamAName, streamBName]);
+
+  if (!chartContent) {
+    return (
+      <ChartStateEmpty
+        title={title}
+        state={renderState === "ready" ? "no_data" : renderState}
+        heightClass="h-[236px]"
+      />
+    );
+  }
+
+  return (
+    <ChartCardShell
+      title={title}
+      renderState={renderState}
+      body={
+        <>
+          <ChartStateOverlay state={renderState} />
+          <div className="monitor-chart-frame relative h-[236px] w-full">
+            <Suspense fallback={<ChartLoader />}>
+              <Line data={chartContent.chartData} options={chartContent.options} />
+            </Suspense>
+          </div>
+        </>
+      }
+    />
+  );
+}
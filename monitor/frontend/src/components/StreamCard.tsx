@@ -2,6 +2,7 @@ import { memo } from "react";
 import { Info, Zap } from "lucide-react";
 import { cn } from "@/lib/utils";
 import { formatUptimePercent } from "@/lib/uptime";
+import type { LatencyPercentiles } from "@/hooks/useStream";
 
 interface StreamCardProps {
   streamId: "a" | "b" | "baseline-1" | "baseline-2";
@@ -12,6 +13,7 @@ interface StreamCardProps {
   streak?: number;
   uptime?: number;
   uptimeAllTime?: number;
+  hydration?: LatencyPercentiles | null;
   connected: boolean;
 }
 
@@ -49,6 +51,7 @@ export const StreamCard = memo(function StreamCard({
   rate,
   streak,
   uptimeAllTime,
+  hydration,
   connected,
 }: StreamCardProps) {
   const streamVariantClass =
@@ -134,6 +137,28 @@ export const StreamCard = memo(function StreamCard({
             )}
           </p>
         </div>
+
+        {hydration ? (
+          <div className="monitor-stream-metric">
+            <p className="monitor-stream-metric-label">
+              Hydration p50/p95
+              <button
+                type="button"
+                className="monitor-tooltip-trigger relative inline-flex cursor-pointer"
+                aria-label="More info about hydration time"
+              >
+                <Info className="h-2.5 w-2.5" aria-hidden="true" />
+                <span className="monitor-tooltip">
+                  Enrichment time reported by the turbo stream over the last 10 seconds.
+                </span>
+              </button>
+            </p>
+            <p className="monitor-stream-metric-value">
+              {hydration.p50_ms.toFixed(0)}/{hydration.p95_ms.toFixed(0)}
+              <span className="monitor-stream-metric-unit">ms</span>
+            </p>
+          </div>
+        ) : null}
       </div>
     </article>
   );
@@ -2,6 +2,12 @@ import { useState, useEffect, useRef, useCallback } from 'react'
 
 export type ConnectionStatus = 'connecting' | 'connected' | 'disconnected'
 
+export interface LatencyPercentiles {
+  p50_ms: number
+  p95_ms: number
+  p99_ms: number
+}
+
 export interface StreamStats {
   stream_a?: number
   stream_b?: number
@@ -19,6 +25,8 @@ export interface StreamStats {
   downtime_b?: number
   connected_a?: boolean
   connected_b?: boolean
+  hydration_a?: LatencyPercentiles | null
+  hydration_b?: LatencyPercentiles | null
   stream_a_name?: string
   stream_b_name?: string
   baseline_1_name?: string
@@ -74,6 +82,11 @@ function readObject(value: unknown): Record<string, unknown> | null {
   return value as Record<string, unknown>
 }
 
+function readOptionalNumber(value: unknown): number | null {
+  const parsed = readNumber(value, Number.NaN)
+  return Number.isFinite(parsed) ? parsed : null
+}
+
 function pickNumber(record: Record<string, unknown>, keys: string[]): number {
   for (const key of keys) {
     const value = readNumber(record[key], Number.NaN)
@@ -111,6 +124,12 @@ function normalizeUptimeRow(value: unknown): HourlyUptime | null {
     baseline_2_downtime_seconds: pickNumber(row, ['baseline_2_downtime_seconds']),
     baseline_1_messages: pickNumber(row, ['baseline_1_messages']),
     baseline_2_messages: pickNumber(row, ['baseline_2_messages']),
+    stream_a_hydration_p50_ms: readOptionalNumber(row.stream_a_hydration_p50_ms),
+    stream_a_hydration_p95_ms: readOptionalNumber(row.stream_a_hydration_p95_ms),
+    stream_a_hydration_p99_ms: readOptionalNumber(row.stream_a_hydration_p99_ms),
+    stream_b_hydration_p50_ms: readOptionalNumber(row.stream_b_hydration_p50_ms),
+    stream_b_hydration_p95_ms: readOptionalNumber(row.stream_b_hydration_p95_ms),
+    stream_b_hydration_p99_ms: readOptionalNumber(row.stream_b_hydration_p99_ms),
   }
 }
 
@@ -324,4 +343,11 @@ export interface HourlyUptime {
   baseline_2_downtime_seconds: number
   baseline_1_messages: number
   baseline_2_messages: number
+  // Hydration time percentiles; null when the stream sent no enriched records
+  stream_a_hydration_p50_ms: number | null
+  stream_a_hydration_p95_ms: number | null
+  stream_a_hydration_p99_ms: number | null
+  stream_b_hydration_p50_ms: number | null
+  stream_b_hydration_p95_ms: number | null
+  stream_b_hydration_p99_ms: number | null
 }
@@ -1,14 +1,18 @@
+use crate::access_log::TrustedProxies;
+use crate::stream::ReconnectPolicy;
 use anyhow::Result;
+use clap::Parser;
 use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Settings {
     pub stream_a_url: String,
     #[serde(default = "default_stream_a_name")]
     pub stream_a_name: String,
-    #[serde(default = "default_stream_b_name")]
     pub stream_b_url: String,
-
+    #[serde(default = "default_stream_b_name")]
     pub stream_b_name: String,
     #[serde(default = "default_bind")]
     pub bind_address: String,
@@ -16,6 +20,57 @@ pub struct Settings {
     pub database_url: String,
     #[serde(default = "default_stream_idle_timeout_seconds")]
     pub stream_idle_timeout_seconds: u64,
+    /// How often live stats are recomputed and pushed to dashboards
+    #[serde(default = "default_aggregation_interval_ms")]
+    pub aggregation_interval_ms: u64,
+    /// First reconnect delay, doubling per failed attempt
+    #[serde(default = "default_reconnect_initial_delay_ms")]
+    pub reconnect_initial_delay_ms: u64,
+    #[serde(default = "default_reconnect_max_delay_ms")]
+    pub reconnect_max_delay_ms: u64,
+    /// Fraction of each delay taken off at random, 0.0 to 1.0
+    #[serde(default = "default_reconnect_jitter")]
+    pub reconnect_jitter: f64,
+    /// Peers whose `x-forwarded-for` names the client in access logs, as
+    /// comma-separated addresses or CIDR ranges
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: String,
+}
+
+/// Command-line overrides. Each flag wins over `--config`, which wins over the
+/// environment, so several instances can share one `.env` and differ only in
+/// their profile file or flags.
+#[derive(Debug, Default, Parser)]
+#[command(name = "jetstream-monitor", version, about)]
+pub struct Cli {
+    /// Settings file (TOML, JSON or YAML, by extension) using the same keys
+    /// as the environment, lowercased
+    #[arg(long, short)]
+    pub config: Option<PathBuf>,
+    #[arg(long)]
+    pub stream_a_url: Option<String>,
+    #[arg(long)]
+    pub stream_a_name: Option<String>,
+    #[arg(long)]
+    pub stream_b_url: Option<String>,
+    #[arg(long)]
+    pub stream_b_name: Option<String>,
+    /// Address the dashboard and API listen on, e.g. 0.0.0.0:3001
+    #[arg(long)]
+    pub bind_address: Option<String>,
+    /// e.g. sqlite://monitor-b.db?mode=rwc
+    #[arg(long)]
+    pub database_url: Option<String>,
+    #[arg(long)]
+    pub stream_idle_timeout_seconds: Option<u64>,
+    #[arg(long)]
+    pub aggregation_interval_ms: Option<u64>,
+    #[arg(long)]
+    pub reconnect_initial_delay_ms: Option<u64>,
+    #[arg(long)]
+    pub reconnect_max_delay_ms: Option<u64>,
+    #[arg(long)]
+    pub reconnect_jitter: Option<f64>,
 }
 
 fn default_stream_a_name() -> String {
@@ -38,11 +93,35 @@ fn default_stream_idle_timeout_seconds() -> u64 {
     30
 }
 
+fn default_aggregation_interval_ms() -> u64 {
+    100
+}
+
+fn default_reconnect_initial_delay_ms() -> u64 {
+    1000
+}
+
+fn default_reconnect_max_delay_ms() -> u64 {
+    60_000
+}
+
+fn default_reconnect_jitter() -> f64 {
+    0.2
+}
+
+fn default_trusted_proxies() -> String {
+    "127.0.0.1,::1".to_string()
+}
+
 impl Settings {
     pub fn load() -> Result<Self> {
+        Self::load_with(&Cli::default())
+    }
+
+    pub fn load_with(cli: &Cli) -> Result<Self> {
         dotenvy::dotenv().ok();
 
-        let settings = config::Config::builder()
+        let mut builder = config::Config::builder()
             .set_default("bind_address", default_bind())?
             .set_default("database_url", default_database())?
             .set_default("stream_a_name", default_stream_a_name())?
@@ -51,9 +130,104 @@ impl Settings {
                 "stream_idle_timeout_seconds",
                 default_stream_idle_timeout_seconds(),
             )?
-            .add_source(config::Environment::default())
-            .build()?;
+            .set_default("aggregation_interval_ms", default_aggregation_interval_ms())?
+            .set_default(
+                "reconnect_initial_delay_ms",
+                default_reconnect_initial_delay_ms(),
+            )?
+            .set_default("reconnect_max_delay_ms", default_reconnect_max_delay_ms())?
+            .set_default("reconnect_jitter", default_reconnect_jitter())?
+            .set_default("trusted_proxies", default_trusted_proxies())?
+            .add_source(config::Environment::default());
+
+        if let Some(path) = &cli.config {
+            builder = builder.add_source(config::File::from(path.as_path()));
+        }
+
+        for (key, value) in [
+            ("stream_a_url", &cli.stream_a_url),
+            ("stream_a_name", &cli.stream_a_name),
+            ("stream_b_url", &cli.stream_b_url),
+            ("stream_b_name", &cli.stream_b_name),
+            ("bind_address", &cli.bind_address),
+            ("database_url", &cli.database_url),
+        ] {
+            builder = builder.set_override_option(key, value.clone())?;
+        }
+        builder = builder
+            .set_override_option(
+                "stream_idle_timeout_seconds",
+                cli.stream_idle_timeout_seconds,
+            )?
+            .set_override_option("aggregation_interval_ms", cli.aggregation_interval_ms)?
+            .set_override_option("reconnect_initial_delay_ms", cli.reconnect_initial_delay_ms)?
+            .set_override_option("reconnect_max_delay_ms", cli.reconnect_max_delay_ms)?
+            .set_override_option("reconnect_jitter", cli.reconnect_jitter)?;
+
+        let settings: Settings = builder.build()?.try_deserialize()?;
+        if settings.aggregation_interval_ms == 0 {
+            anyhow::bail!("aggregation_interval_ms must be greater than 0");
+        }
+        if settings.reconnect_max_delay_ms < settings.reconnect_initial_delay_ms {
+            anyhow::bail!(
+                "reconnect_max_delay_ms must not be less than reconnect_initial_delay_ms"
+            );
+        }
+        if !(0.0..=1.0).contains(&settings.reconnect_jitter) {
+            anyhow::bail!("reconnect_jitter must be between 0.0 and 1.0");
+        }
+        if let Err(e) = settings.trusted_proxies() {
+            anyhow::bail!("trusted_proxies: {e}");
+        }
+        Ok(settings)
+    }
+
+    /// `trusted_proxies`, parsed; checked by `load_with`.
+    pub fn trusted_proxies(&self) -> std::result::Result<TrustedProxies, String> {
+        self.trusted_proxies.parse()
+    }
+
+    pub fn reconnect_policy(&self) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(self.reconnect_initial_delay_ms),
+            max_delay: Duration::from_millis(self.reconnect_max_delay_ms),
+            jitter: self.reconnect_jitter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cli, Settings};
+
+    #[test]
+    fn flags_override_the_profile_file() {
+        let path =
+            std::env::temp_dir().join(format!("monitor-profile-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+stream_a_url = "ws://localhost:8080/api/v1/ws"
+stream_b_url = "ws://localhost:8081/api/v1/ws"
+stream_b_name = "Staging"
+database_url = "sqlite://staging.db?mode=rwc"
+"#,
+        )
+        .unwrap();
+
+        let settings = Settings::load_with(&Cli {
+            config: Some(path.clone()),
+            bind_address: Some("127.0.0.1:3002".to_string()),
+            stream_b_name: Some("Canary".to_string()),
+            ..Cli::default()
+        })
+        .unwrap();
+        std::fs::remove_file(&path).ok();
 
-        settings.try_deserialize().map_err(Into::into)
+        assert_eq!(settings.stream_a_url, "ws://localhost:8080/api/v1/ws");
+        assert_eq!(settings.stream_b_name, "Canary");
+        assert_eq!(settings.database_url, "sqlite://staging.db?mode=rwc");
+        assert_eq!(settings.bind_address, "127.0.0.1:3002");
+        assert_eq!(settings.aggregation_interval_ms, 100);
     }
 }
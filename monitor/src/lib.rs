@@ -1,3 +1,4 @@
+pub use jetstream_turbo_access_log as access_log;
 pub mod config;
 pub mod stats;
 pub mod storage;
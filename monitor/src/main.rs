@@ -1,6 +1,8 @@
 use anyhow::Result;
+use clap::Parser;
 use jetstream_monitor::{
-    config::Settings,
+    access_log,
+    config::{Cli, Settings},
     stats::{
         StatsAggregator, StreamStatsInternal, UptimeDetailedStats, UptimeMetricsSnapshot,
         UptimeTracker,
@@ -181,7 +183,7 @@ fn rollup_window(data: &[HourlyUptime]) -> WindowRollup {
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
-    let settings = Settings::load()?;
+    let settings = Settings::load_with(&Cli::parse())?;
     tracing::info!(
         "Loaded settings: stream_a={}, stream_b={}",
         settings.stream_a_url,
@@ -214,19 +216,29 @@ async fn main() -> Result<()> {
         settings.stream_b_name.clone(),
         BASELINE_1_NAME.to_string(),
         BASELINE_2_NAME.to_string(),
-    );
+    )
+    .with_interval(Duration::from_millis(settings.aggregation_interval_ms));
     let broadcast_tx = Arc::new(aggregator.sender());
 
     let stream_idle_timeout = Duration::from_secs(settings.stream_idle_timeout_seconds.max(1));
 
+    let reconnect_policy = settings.reconnect_policy();
+
+    // A and B continue from the stored lifetime totals rather than restarting at zero
     let client_a = StreamClient::new(settings.stream_a_url.clone(), StreamId::A)
-        .with_idle_timeout(stream_idle_timeout);
+        .with_idle_timeout(stream_idle_timeout)
+        .with_reconnect_policy(reconnect_policy)
+        .with_initial_count(lifetime_a);
     let client_b = StreamClient::new(settings.stream_b_url.clone(), StreamId::B)
-        .with_idle_timeout(stream_idle_timeout);
+        .with_idle_timeout(stream_idle_timeout)
+        .with_reconnect_policy(reconnect_policy)
+        .with_initial_count(lifetime_b);
     let client_baseline_1 = StreamClient::new(BASELINE_1_URL.to_string(), StreamId::Baseline1)
-        .with_idle_timeout(stream_idle_timeout);
+        .with_idle_timeout(stream_idle_timeout)
+        .with_reconnect_policy(reconnect_policy);
     let client_baseline_2 = StreamClient::new(BASELINE_2_URL.to_string(), StreamId::Baseline2)
-        .with_idle_timeout(stream_idle_timeout);
+        .with_idle_timeout(stream_idle_timeout)
+        .with_reconnect_policy(reconnect_policy);
 
     let stats_for_stream = Arc::clone(&stats_internal);
     let uptime_for_status: Arc<std::sync::RwLock<UptimeTracker>> = Arc::clone(&uptime_tracker);
@@ -239,25 +251,29 @@ async fn main() -> Result<()> {
 
         loop {
             tokio::select! {
-                Some(msg) = stream_a.next() => {
+                Some(mut msg) = stream_a.next() => {
                     let count = msg.count;
                     let delivery_latency_us = msg.delivery_latency_us;
+                    let hydration_times_ms = std::mem::take(&mut msg.hydration_times_ms);
                     stats_for_stream.write().unwrap().update(msg);
                     let mut tracker = uptime_for_status.write().unwrap();
                     tracker.record_total_count(StreamId::A, count);
                     if let Some(lat) = delivery_latency_us {
                         tracker.record_delivery_latency(StreamId::A, lat);
                     }
+                    tracker.record_hydration_times(StreamId::A, &hydration_times_ms);
                 }
-                Some(msg) = stream_b.next() => {
+                Some(mut msg) = stream_b.next() => {
                     let count = msg.count;
                     let delivery_latency_us = msg.delivery_latency_us;
+                    let hydration_times_ms = std::mem::take(&mut msg.hydration_times_ms);
                     stats_for_stream.write().unwrap().update(msg);
                     let mut tracker = uptime_for_status.write().unwrap();
                     tracker.record_total_count(StreamId::B, count);
                     if let Some(lat) = delivery_latency_us {
                         tracker.record_delivery_latency(StreamId::B, lat);
                     }
+                    tracker.record_hydration_times(StreamId::B, &hydration_times_ms);
                 }
                 Some(msg) = stream_b1.next() => {
                     uptime_for_status
@@ -368,6 +384,17 @@ async fn main() -> Result<()> {
                     tracing::error!("Failed to save hourly uptime: {}", e);
                 }
 
+                let (hydration_a, hydration_b) = uptime_for_storage
+                    .write()
+                    .unwrap()
+                    .take_interval_hydration();
+                if let Err(e) = storage_arc
+                    .save_hourly_hydration(chrono::Utc::now(), hydration_a, hydration_b)
+                    .await
+                {
+                    tracing::error!("Failed to save hourly hydration latency: {}", e);
+                }
+
                 if let Err(e) = storage_arc
                     .save_lifetime_totals(
                         current_snapshot.total_messages_a,
@@ -453,7 +480,7 @@ async fn main() -> Result<()> {
         Ok(response)
     }
 
-    let app = axum::Router::new()
+    let api = axum::Router::new()
         .route("/ws", axum::routing::get(websocket::ws_handler))
         .route("/api/history", axum::routing::get(get_history))
         .route("/api/uptime", axum::routing::get(get_uptime))
@@ -461,14 +488,18 @@ async fn main() -> Result<()> {
             "/api/uptime-detailed",
             axum::routing::get(get_uptime_detailed),
         )
-        .with_state((broadcast_tx, storage_for_api, uptime_for_api))
-        .layer(tower_http::trace::TraceLayer::new_for_http())
-        .fallback(serve_spa);
+        .with_state((broadcast_tx, storage_for_api, uptime_for_api));
+    let trusted_proxies = settings.trusted_proxies().map_err(anyhow::Error::msg)?;
+    let app = access_log::with_access_log(api, trusted_proxies).fallback(serve_spa);
 
     let listener = tokio::net::TcpListener::bind(&settings.bind_address).await?;
     tracing::info!("Listening on {}", settings.bind_address);
 
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -1,3 +1,4 @@
+use super::latency::{IntervalSamples, LatencyPercentiles, WindowedSamples};
 use crate::stream::{ConnectionStatus, StreamId, StreamMessage};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -38,6 +39,9 @@ pub struct StreamStats {
     pub live_latency_b_ms: f64,
     pub delivery_latency_a_ms: f64,
     pub delivery_latency_b_ms: f64,
+    /// Hydration time percentiles, `None` unless the stream is a turbo `/ws`
+    pub hydration_a: Option<LatencyPercentiles>,
+    pub hydration_b: Option<LatencyPercentiles>,
     pub mttr_a_ms: u64,
     pub mttr_b_ms: u64,
     pub current_streak_a: f64,
@@ -62,6 +66,7 @@ pub struct StatsAggregator {
     stream_b_name: String,
     baseline_1_name: String,
     baseline_2_name: String,
+    interval: std::time::Duration,
 }
 
 impl StatsAggregator {
@@ -78,9 +83,16 @@ impl StatsAggregator {
             stream_b_name,
             baseline_1_name,
             baseline_2_name,
+            interval: std::time::Duration::from_millis(100),
         }
     }
 
+    /// How often stats are recomputed and broadcast; 100ms by default.
+    pub fn with_interval(mut self, interval: std::time::Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
     pub fn subscribe(&self) -> broadcast::Receiver<StreamStats> {
         self.tx.subscribe()
     }
@@ -102,9 +114,10 @@ impl StatsAggregator {
         let baseline_1_name = self.baseline_1_name.clone();
         let baseline_2_name = self.baseline_2_name.clone();
         let counting_started_at = Utc::now();
+        let period = self.interval;
 
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_millis(100));
+            let mut interval = tokio::time::interval(period);
 
             loop {
                 interval.tick().await;
@@ -122,6 +135,8 @@ impl StatsAggregator {
                     live_latency_b,
                     delivery_latency_a,
                     delivery_latency_b,
+                    hydration_a,
+                    hydration_b,
                     mttr_a,
                     mttr_b,
                 ) = {
@@ -138,6 +153,8 @@ impl StatsAggregator {
                         up.get_connection_latency_b_ms(),
                         up.get_delivery_latency_a_ms(),
                         up.get_delivery_latency_b_ms(),
+                        up.get_hydration_a(),
+                        up.get_hydration_b(),
                         up.get_mttr_a_ms(),
                         up.get_mttr_b_ms(),
                     )
@@ -217,6 +234,8 @@ impl StatsAggregator {
                     live_latency_b_ms: live_latency_b,
                     delivery_latency_a_ms: delivery_latency_a,
                     delivery_latency_b_ms: delivery_latency_b,
+                    hydration_a,
+                    hydration_b,
                     mttr_a_ms: mttr_a,
                     mttr_b_ms: mttr_b,
                     current_streak_a: streak_a,
@@ -306,6 +325,10 @@ pub struct UptimeTracker {
     server_start_time: Instant,
     delivery_latency_samples_a: VecDeque<(Instant, u64)>,
     delivery_latency_samples_b: VecDeque<(Instant, u64)>,
+    hydration_live_a: WindowedSamples,
+    hydration_live_b: WindowedSamples,
+    hydration_interval_a: IntervalSamples,
+    hydration_interval_b: IntervalSamples,
     total_recovery_time_a_ms: u64,
     total_recovery_time_b_ms: u64,
     recovery_count_a: u64,
@@ -374,6 +397,10 @@ impl Default for UptimeTracker {
             server_start_time: Instant::now(),
             delivery_latency_samples_a: VecDeque::new(),
             delivery_latency_samples_b: VecDeque::new(),
+            hydration_live_a: WindowedSamples::new(Self::RATE_WINDOW),
+            hydration_live_b: WindowedSamples::new(Self::RATE_WINDOW),
+            hydration_interval_a: IntervalSamples::default(),
+            hydration_interval_b: IntervalSamples::default(),
             total_recovery_time_a_ms: 0,
             total_recovery_time_b_ms: 0,
             recovery_count_a: 0,
@@ -576,6 +603,42 @@ impl UptimeTracker {
         Self::avg_delivery_latency_ms(&self.delivery_latency_samples_b, Instant::now())
     }
 
+    pub fn record_hydration_times(&mut self, stream_id: StreamId, times_ms: &[u64]) {
+        if times_ms.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        match stream_id {
+            StreamId::A => {
+                self.hydration_live_a.record(now, times_ms);
+                self.hydration_interval_a.record(times_ms);
+            }
+            StreamId::B => {
+                self.hydration_live_b.record(now, times_ms);
+                self.hydration_interval_b.record(times_ms);
+            }
+            StreamId::Baseline1 | StreamId::Baseline2 => {}
+        }
+    }
+
+    pub fn get_hydration_a(&self) -> Option<LatencyPercentiles> {
+        self.hydration_live_a.percentiles(Instant::now())
+    }
+
+    pub fn get_hydration_b(&self) -> Option<LatencyPercentiles> {
+        self.hydration_live_b.percentiles(Instant::now())
+    }
+
+    /// Hydration percentiles since the previous call, for the hourly rollup.
+    pub fn take_interval_hydration(
+        &mut self,
+    ) -> (Option<LatencyPercentiles>, Option<LatencyPercentiles>) {
+        (
+            self.hydration_interval_a.take(),
+            self.hydration_interval_b.take(),
+        )
+    }
+
     pub fn get_mttr_a_ms(&self) -> u64 {
         if self.recovery_count_a > 0 {
             self.total_recovery_time_a_ms / self.recovery_count_a
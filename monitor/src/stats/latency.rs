@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Most samples held at once; past this the oldest (windowed) or every other
+/// (interval) sample is dropped, which keeps percentiles representative.
+const MAX_SAMPLES: usize = 50_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatencyPercentiles {
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+}
+
+impl LatencyPercentiles {
+    /// Nearest-rank percentiles, or `None` without samples.
+    pub fn from_samples(mut samples: Vec<u64>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let rank = |q: f64| {
+            let index = ((q * samples.len() as f64).ceil() as usize).saturating_sub(1);
+            samples[index.min(samples.len() - 1)] as f64
+        };
+        Some(Self {
+            p50_ms: rank(0.50),
+            p95_ms: rank(0.95),
+            p99_ms: rank(0.99),
+        })
+    }
+}
+
+/// Samples from the last `window`, for live percentiles.
+#[derive(Debug)]
+pub struct WindowedSamples {
+    window: Duration,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl WindowedSamples {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, now: Instant, values: &[u64]) {
+        self.samples
+            .extend(values.iter().map(|&value| (now, value)));
+        while self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        while let Some((t, _)) = self.samples.front() {
+            if now.duration_since(*t) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn percentiles(&self, now: Instant) -> Option<LatencyPercentiles> {
+        LatencyPercentiles::from_samples(
+            self.samples
+                .iter()
+                .filter(|(t, _)| now.duration_since(*t) <= self.window)
+                .map(|(_, value)| *value)
+                .collect(),
+        )
+    }
+}
+
+/// Samples since the last `take`, for per-interval percentiles. Once full,
+/// only every `stride`-th value is kept.
+#[derive(Debug)]
+pub struct IntervalSamples {
+    samples: Vec<u64>,
+    stride: u64,
+    seen: u64,
+}
+
+impl Default for IntervalSamples {
+    fn default() -> Self {
+        Self {
+            samples: Vec::new(),
+            stride: 1,
+            seen: 0,
+        }
+    }
+}
+
+impl IntervalSamples {
+    pub fn record(&mut self, values: &[u64]) {
+        for &value in values {
+            if self.seen.is_multiple_of(self.stride) {
+                self.samples.push(value);
+            }
+            self.seen += 1;
+            if self.samples.len() >= MAX_SAMPLES {
+                let mut index = 0;
+                self.samples.retain(|_| {
+                    index += 1;
+                    index % 2 == 1
+                });
+                self.stride *= 2;
+            }
+        }
+    }
+
+    pub fn take(&mut self) -> Option<LatencyPercentiles> {
+        LatencyPercentiles::from_samples(std::mem::take(self).samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IntervalSamples, LatencyPercentiles, WindowedSamples};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let percentiles = LatencyPercentiles::from_samples((1..=100).rev().collect()).unwrap();
+        assert_eq!(percentiles.p50_ms, 50.0);
+        assert_eq!(percentiles.p95_ms, 95.0);
+        assert_eq!(percentiles.p99_ms, 99.0);
+        assert!(LatencyPercentiles::from_samples(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn windowed_samples_expire_and_interval_samples_reset() {
+        let start = Instant::now();
+        let mut windowed = WindowedSamples::new(Duration::from_secs(10));
+        windowed.record(start, &[900, 900]);
+        windowed.record(start + Duration::from_secs(11), &[10, 20]);
+        let live = windowed
+            .percentiles(start + Duration::from_secs(11))
+            .unwrap();
+        assert_eq!(live.p99_ms, 20.0);
+
+        let mut interval = IntervalSamples::default();
+        let values: Vec<u64> = (0..200_000).map(|i| i % 100).collect();
+        interval.record(&values);
+        let hourly = interval.take().unwrap();
+        assert!((45.0..=55.0).contains(&hourly.p50_ms));
+        assert!(interval.take().is_none());
+    }
+}
@@ -1,6 +1,8 @@
 pub mod aggregator;
+pub mod latency;
 
 pub use aggregator::{
     StatsAggregator, StreamStats, StreamStatsInternal, UptimeDetailedStats, UptimeMetricsSnapshot,
     UptimeTracker,
 };
+pub use latency::LatencyPercentiles;
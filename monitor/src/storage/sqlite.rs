@@ -1,3 +1,4 @@
+use crate::stats::LatencyPercentiles;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::Serialize;
@@ -48,6 +49,19 @@ pub struct HourlyUptime {
     pub baseline_1_messages: i64,
     #[sqlx(default)]
     pub baseline_2_messages: i64,
+    /// Hydration time percentiles, null for hours without enriched records
+    #[sqlx(default)]
+    pub stream_a_hydration_p50_ms: Option<f64>,
+    #[sqlx(default)]
+    pub stream_a_hydration_p95_ms: Option<f64>,
+    #[sqlx(default)]
+    pub stream_a_hydration_p99_ms: Option<f64>,
+    #[sqlx(default)]
+    pub stream_b_hydration_p50_ms: Option<f64>,
+    #[sqlx(default)]
+    pub stream_b_hydration_p95_ms: Option<f64>,
+    #[sqlx(default)]
+    pub stream_b_hydration_p99_ms: Option<f64>,
     #[serde(skip_serializing)]
     pub metrics_contract_version: i64,
 }
@@ -137,6 +151,12 @@ impl Storage {
                 baseline_2_downtime_seconds INTEGER NOT NULL DEFAULT 0,
                 baseline_1_messages INTEGER NOT NULL DEFAULT 0,
                 baseline_2_messages INTEGER NOT NULL DEFAULT 0,
+                stream_a_hydration_p50_ms REAL,
+                stream_a_hydration_p95_ms REAL,
+                stream_a_hydration_p99_ms REAL,
+                stream_b_hydration_p50_ms REAL,
+                stream_b_hydration_p95_ms REAL,
+                stream_b_hydration_p99_ms REAL,
                 metrics_contract_version INTEGER NOT NULL DEFAULT 1,
                 updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
             )
@@ -284,6 +304,36 @@ impl Storage {
         .await
         .ok();
 
+        sqlx::query("ALTER TABLE hourly_uptime ADD COLUMN stream_a_hydration_p50_ms REAL")
+            .execute(&pool)
+            .await
+            .ok();
+
+        sqlx::query("ALTER TABLE hourly_uptime ADD COLUMN stream_a_hydration_p95_ms REAL")
+            .execute(&pool)
+            .await
+            .ok();
+
+        sqlx::query("ALTER TABLE hourly_uptime ADD COLUMN stream_a_hydration_p99_ms REAL")
+            .execute(&pool)
+            .await
+            .ok();
+
+        sqlx::query("ALTER TABLE hourly_uptime ADD COLUMN stream_b_hydration_p50_ms REAL")
+            .execute(&pool)
+            .await
+            .ok();
+
+        sqlx::query("ALTER TABLE hourly_uptime ADD COLUMN stream_b_hydration_p95_ms REAL")
+            .execute(&pool)
+            .await
+            .ok();
+
+        sqlx::query("ALTER TABLE hourly_uptime ADD COLUMN stream_b_hydration_p99_ms REAL")
+            .execute(&pool)
+            .await
+            .ok();
+
         sqlx::query(
             r#"
             CREATE TABLE IF NOT EXISTS lifetime_totals (
@@ -453,6 +503,48 @@ impl Storage {
         Ok(())
     }
 
+    /// Stores the hour's hydration percentiles alongside its uptime row.
+    pub async fn save_hourly_hydration(
+        &self,
+        hour: DateTime<Utc>,
+        stream_a: Option<LatencyPercentiles>,
+        stream_b: Option<LatencyPercentiles>,
+    ) -> Result<()> {
+        let hour_str = hour.format("%Y-%m-%d %H:00:00").to_string();
+
+        sqlx::query(
+            r#"
+            INSERT INTO hourly_uptime (
+                hour, stream_a_hydration_p50_ms, stream_a_hydration_p95_ms,
+                stream_a_hydration_p99_ms, stream_b_hydration_p50_ms,
+                stream_b_hydration_p95_ms, stream_b_hydration_p99_ms,
+                metrics_contract_version
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(hour) DO UPDATE SET
+                stream_a_hydration_p50_ms = excluded.stream_a_hydration_p50_ms,
+                stream_a_hydration_p95_ms = excluded.stream_a_hydration_p95_ms,
+                stream_a_hydration_p99_ms = excluded.stream_a_hydration_p99_ms,
+                stream_b_hydration_p50_ms = excluded.stream_b_hydration_p50_ms,
+                stream_b_hydration_p95_ms = excluded.stream_b_hydration_p95_ms,
+                stream_b_hydration_p99_ms = excluded.stream_b_hydration_p99_ms,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(&hour_str)
+        .bind(stream_a.map(|p| p.p50_ms))
+        .bind(stream_a.map(|p| p.p95_ms))
+        .bind(stream_a.map(|p| p.p99_ms))
+        .bind(stream_b.map(|p| p.p50_ms))
+        .bind(stream_b.map(|p| p.p95_ms))
+        .bind(stream_b.map(|p| p.p99_ms))
+        .bind(INTERVAL_UPTIME_CONTRACT_VERSION)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_uptime_since(&self, since: DateTime<Utc>) -> Result<Vec<HourlyUptime>> {
         let since_str = since.format("%Y-%m-%d %H:00:00").to_string();
 
@@ -468,6 +560,9 @@ impl Storage {
                    baseline_1_seconds, baseline_2_seconds,
                    baseline_1_downtime_seconds, baseline_2_downtime_seconds,
                    baseline_1_messages, baseline_2_messages,
+                   stream_a_hydration_p50_ms, stream_a_hydration_p95_ms,
+                   stream_a_hydration_p99_ms, stream_b_hydration_p50_ms,
+                   stream_b_hydration_p95_ms, stream_b_hydration_p99_ms,
                    metrics_contract_version
             FROM hourly_uptime
             WHERE hour < ?
@@ -491,6 +586,9 @@ impl Storage {
                    baseline_1_seconds, baseline_2_seconds,
                    baseline_1_downtime_seconds, baseline_2_downtime_seconds,
                    baseline_1_messages, baseline_2_messages,
+                   stream_a_hydration_p50_ms, stream_a_hydration_p95_ms,
+                   stream_a_hydration_p99_ms, stream_b_hydration_p50_ms,
+                   stream_b_hydration_p95_ms, stream_b_hydration_p99_ms,
                    metrics_contract_version
             FROM hourly_uptime
             WHERE hour >= ?
@@ -626,6 +724,7 @@ impl Storage {
 #[cfg(test)]
 mod tests {
     use super::{Storage, INTERVAL_UPTIME_CONTRACT_VERSION};
+    use crate::stats::LatencyPercentiles;
     use chrono::{Duration, Utc};
     use sqlx::SqlitePool;
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -737,6 +836,22 @@ mod tests {
             row.metrics_contract_version,
             INTERVAL_UPTIME_CONTRACT_VERSION
         );
+        assert_eq!(row.stream_a_hydration_p50_ms, None);
+
+        let percentiles = LatencyPercentiles {
+            p50_ms: 12.0,
+            p95_ms: 80.0,
+            p99_ms: 240.0,
+        };
+        storage
+            .save_hourly_hydration(recent_hour, Some(percentiles), None)
+            .await?;
+        let rows = storage
+            .get_uptime_since(Utc::now() - Duration::hours(24))
+            .await?;
+        assert_eq!(rows[0].stream_a_messages, 1200);
+        assert_eq!(rows[0].stream_a_hydration_p95_ms, Some(80.0));
+        assert_eq!(rows[0].stream_b_hydration_p95_ms, None);
 
         Ok(())
     }
@@ -1,6 +1,8 @@
 use chrono::Utc;
 use futures::{SinkExt, Stream, StreamExt};
 use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::time::sleep;
@@ -20,7 +22,7 @@ pub enum StreamId {
 
 #[cfg(test)]
 mod tests {
-    use super::{ConnectionStatus, StreamClient, StreamId};
+    use super::{ConnectionStatus, PayloadTimings, ReconnectPolicy, StreamClient, StreamId};
     use futures::{SinkExt, StreamExt};
     use std::time::Duration;
     use tokio::net::TcpListener;
@@ -35,6 +37,93 @@ mod tests {
             .expect("status stream ended")
     }
 
+    #[test]
+    fn delivery_latency_reads_jetstream_and_enriched_records() {
+        let time_us = chrono::Utc::now().timestamp_micros() as u64 - 1_000_000;
+        let jetstream = format!(r#"{{"did": "did:plc:a", "time_us": {time_us}}}"#);
+        let enriched = format!(
+            r#"{{"event_id": 1, "message": {{"time_us": {time_us}}}, "metrics": {{"ingest_lag_ms": 900}}}}"#
+        );
+        for text in [jetstream, enriched] {
+            let latency = PayloadTimings::parse(&text)
+                .and_then(|timings| timings.delivery_latency_us())
+                .unwrap();
+            assert!((1_000_000..10_000_000).contains(&latency));
+        }
+        assert!(PayloadTimings::parse(r#"{"message": {}}"#)
+            .and_then(|timings| timings.delivery_latency_us())
+            .is_none());
+    }
+
+    #[test]
+    fn hydration_time_is_read_from_enriched_records_only() {
+        let enriched = r#"{"message": {"time_us": 1}, "metrics": {"hydration_time_ms": 42}}"#;
+        let timings = PayloadTimings::parse(enriched).unwrap();
+        assert_eq!(timings.hydration_time_ms(), Some(42));
+
+        let jetstream = r#"{"did": "did:plc:a", "time_us": 1}"#;
+        let timings = PayloadTimings::parse(jetstream).unwrap();
+        assert_eq!(timings.hydration_time_ms(), None);
+    }
+
+    #[test]
+    fn reconnect_delay_backs_off_to_the_cap_with_jitter() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+        };
+        assert_eq!(policy.delay_with(0, 0.0), Duration::from_secs(1));
+        assert_eq!(policy.delay_with(2, 0.0), Duration::from_secs(4));
+        assert_eq!(policy.delay_with(30, 0.0), Duration::from_secs(10));
+        assert_eq!(policy.delay_with(30, 1.0), Duration::from_secs(5));
+        let delay = policy.delay(1);
+        assert!((Duration::from_secs(1)..=Duration::from_secs(2)).contains(&delay));
+    }
+
+    #[tokio::test]
+    async fn counts_continue_across_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind test websocket listener");
+        let addr = listener.local_addr().expect("read listener address");
+
+        tokio::spawn(async move {
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await.expect("accept test client");
+                let mut websocket = accept_async(stream)
+                    .await
+                    .expect("accept websocket handshake");
+                for _ in 0..3 {
+                    websocket
+                        .send(Message::Text(r#"{"time_us": 1}"#.into()))
+                        .await
+                        .expect("send message");
+                }
+                websocket.close(None).await.ok();
+            }
+        });
+
+        let client = StreamClient::new(format!("ws://{}", addr), StreamId::B)
+            .with_initial_count(100)
+            .with_reconnect_policy(ReconnectPolicy {
+                initial_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(10),
+                jitter: 0.0,
+            });
+        let mut messages = client.stream_counts();
+        let mut last = 0;
+        while last < 106 {
+            last = tokio::time::timeout(Duration::from_secs(2), messages.next())
+                .await
+                .expect("timed out waiting for counts")
+                .expect("message stream ended")
+                .count;
+            assert!(last > 100);
+        }
+        assert_eq!(last, 106);
+    }
+
     #[tokio::test]
     async fn stale_open_connection_is_marked_disconnected_after_idle_timeout() {
         let listener = TcpListener::bind("127.0.0.1:0")
@@ -69,15 +158,40 @@ mod tests {
 }
 
 #[derive(Deserialize)]
-struct TimeOnly {
+struct PayloadTimings {
+    time_us: Option<u64>,
+    /// Enriched turbo records nest the Jetstream event under `message`
+    message: Option<MessageTime>,
+    /// Only present on enriched turbo records
+    metrics: Option<RecordMetrics>,
+}
+
+#[derive(Deserialize)]
+struct MessageTime {
     time_us: Option<u64>,
 }
 
-fn extract_delivery_latency_us(text: &str) -> Option<u64> {
-    let parsed: TimeOnly = serde_json::from_str(text).ok()?;
-    let time_us = parsed.time_us?;
-    let now_us = Utc::now().timestamp_micros() as u64;
-    now_us.checked_sub(time_us)
+#[derive(Deserialize)]
+struct RecordMetrics {
+    hydration_time_ms: Option<u64>,
+}
+
+impl PayloadTimings {
+    fn parse(text: &str) -> Option<Self> {
+        serde_json::from_str(text).ok()
+    }
+
+    fn delivery_latency_us(&self) -> Option<u64> {
+        let time_us = self
+            .time_us
+            .or_else(|| self.message.as_ref().and_then(|message| message.time_us))?;
+        let now_us = Utc::now().timestamp_micros() as u64;
+        now_us.checked_sub(time_us)
+    }
+
+    fn hydration_time_ms(&self) -> Option<u64> {
+        self.metrics.as_ref()?.hydration_time_ms
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -85,6 +199,8 @@ pub struct StreamMessage {
     pub stream_id: StreamId,
     pub count: u64,
     pub delivery_latency_us: Option<u64>,
+    /// Hydration times of the enriched records received since the last update
+    pub hydration_times_ms: Vec<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -95,11 +211,50 @@ pub struct ConnectionStatus {
     pub connect_time_ms: Option<u64>,
 }
 
+/// Delay between reconnect attempts: doubling from `initial_delay` up to
+/// `max_delay`, with up to `jitter` of it taken off at random so streams that
+/// drop together don't reconnect in lockstep. A connection that delivered any
+/// messages starts the next backoff over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Fraction of the delay, 0.0 to 1.0
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before reconnect `attempt`, counting from 0.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        self.delay_with(attempt, random)
+    }
+
+    fn delay_with(&self, attempt: u32, random: f64) -> Duration {
+        let backoff = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt.min(16)))
+            .min(self.max_delay);
+        backoff.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * random)
+    }
+}
+
 pub struct StreamClient {
     url: String,
     stream_id: StreamId,
-    reconnect_delay: Duration,
+    reconnect_policy: ReconnectPolicy,
     idle_timeout: Duration,
+    initial_count: u64,
 }
 
 impl StreamClient {
@@ -107,8 +262,9 @@ impl StreamClient {
         Self {
             url,
             stream_id,
-            reconnect_delay: Duration::from_secs(5),
+            reconnect_policy: ReconnectPolicy::default(),
             idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            initial_count: 0,
         }
     }
 
@@ -117,103 +273,22 @@ impl StreamClient {
         self
     }
 
-    pub fn stream_counts(&self) -> impl Stream<Item = StreamMessage> {
-        let (tx, rx) = mpsc::unbounded_channel();
-        let url = self.url.clone();
-        let stream_id = self.stream_id;
-        let reconnect_delay = self.reconnect_delay;
-        let idle_timeout = self.idle_timeout;
-
-        tokio::spawn(async move {
-            let mut cumulative_count: u64 = 0;
-
-            loop {
-                info!(stream = ?stream_id, "Connecting to {}", url);
-
-                match connect_async(&url).await {
-                    Ok((ws_stream, _)) => {
-                        info!(stream = ?stream_id, "Connected successfully");
-                        let (mut write, mut read) = ws_stream.split();
-                        let mut count: u64 = 0;
-                        let mut last_send = Instant::now();
-                        let mut last_message = Instant::now();
-                        let update_interval = Duration::from_millis(100);
-                        let mut last_delivery_latency_us: Option<u64> = None;
-
-                        while let Ok(Some(msg_result)) =
-                            tokio::time::timeout(idle_timeout, read.next()).await
-                        {
-                            match msg_result {
-                                Ok(Message::Text(text)) => {
-                                    last_message = Instant::now();
-                                    count += 1;
-                                    last_delivery_latency_us = extract_delivery_latency_us(&text);
-                                    if last_send.elapsed() >= update_interval {
-                                        if tx
-                                            .send(StreamMessage {
-                                                stream_id,
-                                                count: cumulative_count.saturating_add(count),
-                                                delivery_latency_us: last_delivery_latency_us,
-                                            })
-                                            .is_err()
-                                        {
-                                            debug!(stream = ?stream_id, "Receiver dropped");
-                                            return;
-                                        }
-                                        last_send = Instant::now();
-                                    }
-                                }
-                                Ok(Message::Close(_)) => {
-                                    info!(stream = ?stream_id, "Connection closed by server");
-                                    break;
-                                }
-                                Ok(Message::Ping(payload)) => {
-                                    if let Err(e) = write.send(Message::Pong(payload)).await {
-                                        error!(stream = ?stream_id, "Failed to send WebSocket pong: {}", e);
-                                        break;
-                                    }
-                                    if last_message.elapsed() >= idle_timeout {
-                                        warn!(stream = ?stream_id, "No data messages received for {:?}; reconnecting", idle_timeout);
-                                        break;
-                                    }
-                                }
-                                Err(e) => {
-                                    error!(stream = ?stream_id, "WebSocket error: {}", e);
-                                    break;
-                                }
-                                _ => {
-                                    if last_message.elapsed() >= idle_timeout {
-                                        warn!(stream = ?stream_id, "No data messages received for {:?}; reconnecting", idle_timeout);
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-
-                        cumulative_count = cumulative_count.saturating_add(count);
-
-                        if tx
-                            .send(StreamMessage {
-                                stream_id,
-                                count: cumulative_count,
-                                delivery_latency_us: last_delivery_latency_us,
-                            })
-                            .is_err()
-                        {
-                            return;
-                        }
-                    }
-                    Err(e) => {
-                        error!(stream = ?stream_id, "Connection failed: {}", e);
-                    }
-                }
+    pub fn with_reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
 
-                warn!(stream = ?stream_id, "Reconnecting in {:?}...", reconnect_delay);
-                sleep(reconnect_delay).await;
-            }
-        });
+    /// Counts continue from `count`, e.g. the stored lifetime total, instead of
+    /// zero.
+    pub fn with_initial_count(mut self, count: u64) -> Self {
+        self.initial_count = count;
+        self
+    }
 
-        UnboundedReceiverStream::new(rx)
+    pub fn stream_counts(&self) -> impl Stream<Item = StreamMessage> {
+        let (tx_msg, rx_msg) = mpsc::unbounded_channel();
+        self.spawn(tx_msg, None);
+        UnboundedReceiverStream::new(rx_msg)
     }
 
     pub fn stream_with_status(
@@ -224,13 +299,33 @@ impl StreamClient {
     ) {
         let (tx_msg, rx_msg) = mpsc::unbounded_channel();
         let (tx_status, rx_status) = mpsc::unbounded_channel();
+        self.spawn(tx_msg, Some(tx_status));
+        (
+            UnboundedReceiverStream::new(rx_msg),
+            UnboundedReceiverStream::new(rx_status),
+        )
+    }
+
+    /// Connects and reconnects until the message receiver is dropped. Counts
+    /// are cumulative across connections.
+    fn spawn(
+        &self,
+        tx_msg: mpsc::UnboundedSender<StreamMessage>,
+        tx_status: Option<mpsc::UnboundedSender<ConnectionStatus>>,
+    ) {
         let url = self.url.clone();
         let stream_id = self.stream_id;
-        let reconnect_delay = self.reconnect_delay;
+        let reconnect_policy = self.reconnect_policy;
         let idle_timeout = self.idle_timeout;
+        let mut cumulative_count = self.initial_count;
 
         tokio::spawn(async move {
-            let mut cumulative_count: u64 = 0;
+            let send_status = |status: ConnectionStatus| {
+                if let Some(tx_status) = &tx_status {
+                    let _ = tx_status.send(status);
+                }
+            };
+            let mut attempt: u32 = 0;
 
             loop {
                 info!(stream = ?stream_id, "Connecting to {}", url);
@@ -241,7 +336,7 @@ impl StreamClient {
                         let connect_time_ms = connect_start.elapsed().as_millis() as u64;
                         info!(stream = ?stream_id, "Connected successfully in {}ms", connect_time_ms);
 
-                        let _ = tx_status.send(ConnectionStatus {
+                        send_status(ConnectionStatus {
                             stream_id,
                             connected: true,
                             connected_at: Some(connect_start),
@@ -254,6 +349,7 @@ impl StreamClient {
                         let mut last_message = Instant::now();
                         let update_interval = Duration::from_millis(100);
                         let mut last_delivery_latency_us: Option<u64> = None;
+                        let mut hydration_times_ms: Vec<u64> = Vec::new();
 
                         while let Ok(Some(msg_result)) =
                             tokio::time::timeout(idle_timeout, read.next()).await
@@ -262,13 +358,22 @@ impl StreamClient {
                                 Ok(Message::Text(text)) => {
                                     last_message = Instant::now();
                                     count += 1;
-                                    last_delivery_latency_us = extract_delivery_latency_us(&text);
+                                    let timings = PayloadTimings::parse(&text);
+                                    last_delivery_latency_us = timings
+                                        .as_ref()
+                                        .and_then(PayloadTimings::delivery_latency_us);
+                                    hydration_times_ms.extend(
+                                        timings.and_then(|timings| timings.hydration_time_ms()),
+                                    );
                                     if last_send.elapsed() >= update_interval {
                                         if tx_msg
                                             .send(StreamMessage {
                                                 stream_id,
                                                 count: cumulative_count.saturating_add(count),
                                                 delivery_latency_us: last_delivery_latency_us,
+                                                hydration_times_ms: std::mem::take(
+                                                    &mut hydration_times_ms,
+                                                ),
                                             })
                                             .is_err()
                                         {
@@ -306,12 +411,16 @@ impl StreamClient {
                         }
 
                         cumulative_count = cumulative_count.saturating_add(count);
+                        if count > 0 {
+                            attempt = 0;
+                        }
 
                         if tx_msg
                             .send(StreamMessage {
                                 stream_id,
                                 count: cumulative_count,
                                 delivery_latency_us: last_delivery_latency_us,
+                                hydration_times_ms: std::mem::take(&mut hydration_times_ms),
                             })
                             .is_err()
                         {
@@ -323,21 +432,18 @@ impl StreamClient {
                     }
                 }
 
-                let _ = tx_status.send(ConnectionStatus {
+                send_status(ConnectionStatus {
                     stream_id,
                     connected: false,
                     connected_at: None,
                     connect_time_ms: None,
                 });
 
-                warn!(stream = ?stream_id, "Reconnecting in {:?}...", reconnect_delay);
-                sleep(reconnect_delay).await;
+                let delay = reconnect_policy.delay(attempt);
+                attempt = attempt.saturating_add(1);
+                warn!(stream = ?stream_id, "Reconnecting in {:?} (attempt {})...", delay, attempt);
+                sleep(delay).await;
             }
         });
-
-        (
-            UnboundedReceiverStream::new(rx_msg),
-            UnboundedReceiverStream::new(rx_status),
-        )
     }
 }
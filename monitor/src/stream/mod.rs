@@ -1,3 +1,3 @@
 pub mod client;
 
-pub use client::{ConnectionStatus, StreamClient, StreamId, StreamMessage};
+pub use client::{ConnectionStatus, ReconnectPolicy, StreamClient, StreamId, StreamMessage};
@@ -0,0 +1,219 @@
+//! Request ids and structured access logs for the jetstream-turbo and monitor
+//! HTTP servers. Each request keeps the caller's `x-request-id` or gets a fresh
+//! UUID, which is echoed in the response and recorded on the request's tracing
+//! span so handler logs carry it too. The client address is read from
+//! `x-forwarded-for` only when the peer is a trusted proxy.
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, Request, Response};
+use axum::Router;
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceBuilder;
+use tower_http::classify::ServerErrorsFailureClass;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+use tracing::{info, info_span, warn, Span};
+
+pub const ACCESS_LOG_TARGET: &str = "jetstream_turbo.access";
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Peers whose `x-forwarded-for` header is believed, as addresses or CIDR ranges.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Arc<[IpNet]>);
+
+impl TrustedProxies {
+    /// A reverse proxy on the same host.
+    pub fn loopback() -> Self {
+        "127.0.0.1, ::1".parse().expect("valid loopback addresses")
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// A comma-separated list like `127.0.0.1, 10.0.0.0/8`; empty trusts no proxy.
+impl FromStr for TrustedProxies {
+    type Err = String;
+
+    fn from_str(list: &str) -> Result<Self, Self::Err> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("invalid trusted proxy address: {entry}"))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|nets| Self(nets.into()))
+    }
+}
+
+/// Wraps every route of `router` registered so far with request id assignment
+/// and access logging.
+pub fn with_access_log<S>(router: Router<S>, trusted_proxies: TrustedProxies) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(
+        ServiceBuilder::new()
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(move |request: &Request<Body>| {
+                        request_span(request, &trusted_proxies)
+                    })
+                    .on_request(())
+                    .on_response(log_response)
+                    .on_failure(log_failure),
+            )
+            .layer(PropagateRequestIdLayer::x_request_id()),
+    )
+}
+
+fn request_span<B>(request: &Request<B>, trusted_proxies: &TrustedProxies) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    info_span!(
+        "http_request",
+        request_id,
+        method = %request.method(),
+        path = request.uri().path(),
+        client = client_key(request, trusted_proxies),
+    )
+}
+
+fn log_response<B>(response: &Response<B>, latency: Duration, _span: &Span) {
+    info!(
+        target: ACCESS_LOG_TARGET,
+        status = response.status().as_u16(),
+        latency_ms = latency.as_secs_f64() * 1000.0,
+        "request completed"
+    );
+}
+
+fn log_failure(failure: ServerErrorsFailureClass, latency: Duration, _span: &Span) {
+    warn!(
+        target: ACCESS_LOG_TARGET,
+        failure = %failure,
+        latency_ms = latency.as_secs_f64() * 1000.0,
+        "request failed"
+    );
+}
+
+/// The peer address, or when the peer is a trusted proxy, the client it
+/// forwarded for.
+fn client_key<B>(request: &Request<B>, trusted_proxies: &TrustedProxies) -> String {
+    let Some(peer) = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+    else {
+        return "unknown".to_string();
+    };
+    if !trusted_proxies.contains(peer) {
+        return peer.to_string();
+    }
+    forwarded_for(request.headers(), trusted_proxies)
+        .unwrap_or(peer)
+        .to_string()
+}
+
+/// The nearest `x-forwarded-for` hop that isn't a trusted proxy itself. Hops
+/// further left were sent by the client and can't be believed.
+fn forwarded_for(headers: &HeaderMap, trusted_proxies: &TrustedProxies) -> Option<IpAddr> {
+    let mut client = None;
+    for hop in headers.get("x-forwarded-for")?.to_str().ok()?.rsplit(',') {
+        let ip = hop.trim().parse().ok()?;
+        client = Some(ip);
+        if !trusted_proxies.contains(ip) {
+            break;
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_request_ids_are_assigned_or_propagated() {
+        let app = with_access_log(
+            Router::new().route("/", get(|| async { "ok" })),
+            TrustedProxies::default(),
+        );
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(generated).is_ok());
+
+        let response = app
+            .oneshot(
+                Request::get("/")
+                    .header(REQUEST_ID_HEADER, "caller-id")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "caller-id");
+    }
+
+    #[test]
+    fn test_forwarded_for_is_only_believed_from_trusted_proxies() {
+        let trusted: TrustedProxies = "10.0.0.0/8, 192.0.2.1".parse().unwrap();
+        let mut request = Request::get("/").body(()).unwrap();
+        assert_eq!(client_key(&request, &trusted), "unknown");
+
+        request.headers_mut().insert(
+            "x-forwarded-for",
+            "198.51.100.9, 203.0.113.7, 10.0.0.1".parse().unwrap(),
+        );
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 50], 4000))));
+        assert_eq!(client_key(&request, &trusted), "203.0.113.50");
+
+        // Behind two trusted hops, the client is the first untrusted one from the right
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4000))));
+        assert_eq!(client_key(&request, &trusted), "203.0.113.7");
+
+        request
+            .headers_mut()
+            .insert("x-forwarded-for", "not an address".parse().unwrap());
+        assert_eq!(client_key(&request, &trusted), "192.0.2.1");
+    }
+
+    #[test]
+    fn test_trusted_proxies_parse_addresses_and_ranges() {
+        let trusted: TrustedProxies = " 127.0.0.1, ::1 ,10.0.0.0/8".parse().unwrap();
+        assert!(trusted.contains("10.20.30.40".parse().unwrap()));
+        assert!(trusted.contains("::1".parse().unwrap()));
+        assert!(!trusted.contains("127.0.0.2".parse().unwrap()));
+        assert!(TrustedProxies::loopback().contains("127.0.0.1".parse().unwrap()));
+        assert!(!""
+            .parse::<TrustedProxies>()
+            .unwrap()
+            .contains("127.0.0.1".parse().unwrap()));
+        assert!("10.0.0.300".parse::<TrustedProxies>().is_err());
+    }
+}
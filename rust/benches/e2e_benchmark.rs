@@ -0,0 +1,104 @@
+//! End-to-end throughput of the real orchestrator: a `TurboCharger` built with a
+//! mock message source and mocked Bluesky fetchers runs a fixed set of synthetic
+//! Jetstream messages through batching, hydration, on-disk SQLite, not_redis and
+//! the broadcast channel, exactly as in production. Criterion reports sustained
+//! records/sec.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use jetstream_turbo_rs::config::Settings;
+use jetstream_turbo_rs::models::jetstream::JetstreamMessage;
+use jetstream_turbo_rs::testing::{
+    create_message_batch, create_profile, MockMessageSource, MockPostFetcher, MockProfileFetcher,
+};
+use jetstream_turbo_rs::turbocharger::TurboChargerBuilder;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+const MESSAGES_PER_ITERATION: usize = 1000;
+
+/// `messages` moved `round` iterations forward in time, so each iteration stores new
+/// events instead of having the sinks drop them as duplicates.
+fn shifted(messages: &[JetstreamMessage], round: u64) -> Vec<JetstreamMessage> {
+    let offset = round * messages.len() as u64;
+    messages
+        .iter()
+        .cloned()
+        .map(|mut message| {
+            message.time_us = message.time_us.map(|time_us| time_us + offset);
+            message
+        })
+        .collect()
+}
+
+/// Builds a pipeline over `messages` and times one run of it. Building opens the
+/// stores and isn't timed; the run ends once the source is drained and every
+/// batch has been written.
+async fn run_pipeline(
+    settings: &Settings,
+    profiles: &Arc<MockProfileFetcher>,
+    messages: Vec<JetstreamMessage>,
+) -> Duration {
+    let turbocharger = TurboChargerBuilder::new(settings.clone())
+        .message_source(MockMessageSource::new(messages))
+        .fetchers(Arc::clone(profiles), Arc::new(MockPostFetcher::new()))
+        .build()
+        .await
+        .unwrap();
+    // Keep a subscriber attached so broadcast cost is included
+    let _subscription = turbocharger.subscribe(None);
+
+    let started = Instant::now();
+    // The mock source ending is reported as an error once everything is flushed
+    let _ = turbocharger.run().await;
+    started.elapsed()
+}
+
+fn bench_end_to_end_throughput(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let messages = create_message_batch(MESSAGES_PER_ITERATION);
+
+    let profiles = Arc::new(MockProfileFetcher::new());
+    rt.block_on(async {
+        for message in &messages {
+            profiles.add_profile(create_profile(&message.did)).await;
+        }
+    });
+
+    let mut group = c.benchmark_group("end_to_end");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(MESSAGES_PER_ITERATION as u64));
+
+    for sqlite_shards in [1, 4] {
+        let db_dir = TempDir::new().unwrap();
+        let settings = Settings {
+            db_dir: db_dir.path().to_string_lossy().into_owned(),
+            redis_url: String::new(),
+            sqlite_shards,
+            ..Default::default()
+        };
+
+        group.bench_with_input(
+            BenchmarkId::new("sqlite_shards", sqlite_shards),
+            &sqlite_shards,
+            |b, _| {
+                let mut round = 0;
+                b.iter_custom(|iters| {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        round += 1;
+                        let messages = shifted(&messages, round);
+                        elapsed += rt.block_on(run_pipeline(&settings, &profiles, messages));
+                    }
+                    elapsed
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_end_to_end_throughput);
+criterion_main!(benches);
@@ -1,8 +1,14 @@
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use jetstream_turbo_rs::client::JetstreamClient;
 use jetstream_turbo_rs::hydration::TurboCache;
 use jetstream_turbo_rs::models::bluesky::{BlueskyPost, BlueskyProfile};
-use jetstream_turbo_rs::models::enriched::{EnrichedRecord, HydratedMetadata, ProcessingMetrics};
-use jetstream_turbo_rs::models::jetstream::{CommitData, JetstreamMessage, MessageKind, OperationType};
+use jetstream_turbo_rs::models::enriched::{
+    EnrichedEventKind, EnrichedRecord, HydratedMetadata, ProcessingMetrics,
+    ENRICHED_RECORD_SCHEMA_VERSION,
+};
+use jetstream_turbo_rs::models::jetstream::{
+    CommitData, JetstreamMessage, MessageKind, OperationType,
+};
 use jetstream_turbo_rs::storage::{SQLitePragmaConfig, SQLiteStore};
 use serde_json::json;
 use std::sync::Arc;
@@ -10,12 +16,12 @@ use tempfile::TempDir;
 
 fn create_test_profile(i: usize) -> BlueskyProfile {
     BlueskyProfile {
-        did: format!("did:plc:test{}", i).into(),
-        handle: format!("user{}.bsky.social", i),
-        display_name: Some(format!("Test User {}", i)),
-        description: Some(format!("Description for user {}", i)),
-        avatar: Some(format!("https://avatar.example.com/{}", i)),
-        banner: Some(format!("https://banner.example.com/{}", i)),
+        did: format!("did:plc:test{i}").into(),
+        handle: format!("user{i}.bsky.social"),
+        display_name: Some(format!("Test User {i}")),
+        description: Some(format!("Description for user {i}")),
+        avatar: Some(format!("https://avatar.example.com/{i}")),
+        banner: Some(format!("https://banner.example.com/{i}")),
         followers_count: Some(1000 + i as u64),
         follows_count: Some(500 + i as u64),
         posts_count: Some(250 + i as u64),
@@ -27,10 +33,10 @@ fn create_test_profile(i: usize) -> BlueskyProfile {
 
 fn create_test_post(i: usize) -> BlueskyPost {
     BlueskyPost {
-        uri: format!("at://did:plc:test{}/app.bsky.feed.post/{}", i, i),
-        cid: format!("bafyrei{}", i),
+        uri: format!("at://did:plc:test{i}/app.bsky.feed.post/{i}"),
+        cid: format!("bafyrei{i}"),
         author: create_test_profile(i),
-        text: format!("Test post number {}", i),
+        text: format!("Test post number {i}"),
         created_at: chrono::Utc::now(),
         embed: None,
         reply: None,
@@ -44,21 +50,25 @@ fn create_test_post(i: usize) -> BlueskyPost {
 
 fn create_test_message(i: usize) -> JetstreamMessage {
     JetstreamMessage {
-        did: format!("did:plc:test{}", i),
+        did: format!("did:plc:test{i}"),
         time_us: Some(1640995200000000 + i as u64),
         seq: Some(i as u64),
         kind: MessageKind::Commit,
         commit: Some(CommitData {
-            rev: Some(format!("3x{}", i)),
+            rev: Some(format!("3x{i}")),
             operation_type: OperationType::Create,
             collection: Some("app.bsky.feed.post".to_string()),
-            rkey: Some(format!("{}", i)),
+            rkey: Some(format!("{i}")),
             record: Some(json!({
                 "text": format!("Hello world {}", i),
                 "createdAt": "2024-01-01T00:00:00.000Z"
             })),
-            cid: Some(format!("bafyrei{}", i)),
+            cid: Some(format!("bafyrei{i}")),
+            extra: Default::default(),
         }),
+        identity: None,
+        account: None,
+        extra: Default::default(),
     }
 }
 
@@ -80,7 +90,7 @@ fn bench_cache_operations(c: &mut Criterion) {
                 let profile = create_test_profile(0);
 
                 for i in 0..1000 {
-                    cache.set_user_profile(format!("did:plc:test{}", i), Arc::new(profile.clone()));
+                    cache.set_user_profile(format!("did:plc:test{i}"), Arc::new(profile.clone()));
                 }
             });
         });
@@ -92,7 +102,7 @@ fn bench_cache_operations(c: &mut Criterion) {
             let profile = create_test_profile(0);
 
             for i in 0..1000 {
-                cache.set_user_profile(format!("did:plc:test{}", i), Arc::new(profile.clone()));
+                cache.set_user_profile(format!("did:plc:test{i}"), Arc::new(profile.clone()));
             }
 
             cache
@@ -101,7 +111,7 @@ fn bench_cache_operations(c: &mut Criterion) {
         b.iter(|| {
             rt.block_on(async {
                 for i in 0..1000 {
-                    let _result = cache.get_user_profile(&format!("did:plc:test{}", i));
+                    let _result = cache.get_user_profile(&format!("did:plc:test{i}"));
                 }
             });
         });
@@ -114,7 +124,7 @@ fn bench_cache_operations(c: &mut Criterion) {
                 let post = create_test_post(0);
 
                 for i in 0..1000 {
-                    cache.set_post(format!("at://test/{}", i), Arc::new(post.clone()));
+                    cache.set_post(format!("at://test/{i}"), Arc::new(post.clone()));
                 }
             });
         });
@@ -126,7 +136,7 @@ fn bench_cache_operations(c: &mut Criterion) {
             let post = create_test_post(0);
 
             for i in 0..1000 {
-                cache.set_post(format!("at://test/{}", i), Arc::new(post.clone()));
+                cache.set_post(format!("at://test/{i}"), Arc::new(post.clone()));
             }
 
             cache
@@ -135,7 +145,7 @@ fn bench_cache_operations(c: &mut Criterion) {
         b.iter(|| {
             rt.block_on(async {
                 for i in 0..1000 {
-                    let _result = cache.get_post(&format!("at://test/{}", i));
+                    let _result = cache.get_post(&format!("at://test/{i}"));
                 }
             });
         });
@@ -147,7 +157,7 @@ fn bench_cache_operations(c: &mut Criterion) {
             let profile = create_test_profile(0);
 
             for i in 0..100 {
-                cache.set_user_profile(format!("did:plc:test{}", i), Arc::new(profile.clone()));
+                cache.set_user_profile(format!("did:plc:test{i}"), Arc::new(profile.clone()));
             }
 
             cache
@@ -155,7 +165,7 @@ fn bench_cache_operations(c: &mut Criterion) {
 
         b.iter(|| {
             rt.block_on(async {
-                let dids: Vec<String> = (0..100).map(|i| format!("did:plc:test{}", i)).collect();
+                let dids: Vec<String> = (0..100).map(|i| format!("did:plc:test{i}")).collect();
                 let _results = cache.get_user_profiles(&dids);
             });
         });
@@ -167,7 +177,7 @@ fn bench_cache_operations(c: &mut Criterion) {
             let post = create_test_post(0);
 
             for i in 0..100 {
-                cache.set_post(format!("at://test/{}", i), Arc::new(post.clone()));
+                cache.set_post(format!("at://test/{i}"), Arc::new(post.clone()));
             }
 
             cache
@@ -175,7 +185,7 @@ fn bench_cache_operations(c: &mut Criterion) {
 
         b.iter(|| {
             rt.block_on(async {
-                let uris: Vec<String> = (0..100).map(|i| format!("at://test/{}", i)).collect();
+                let uris: Vec<String> = (0..100).map(|i| format!("at://test/{i}")).collect();
                 let _results = cache.get_posts(&uris);
             });
         });
@@ -195,7 +205,7 @@ fn bench_cache_operations(c: &mut Criterion) {
 
                         for i in 0..(cache_size / 2) {
                             cache.set_user_profile(
-                                format!("did:plc:test{}", i),
+                                format!("did:plc:test{i}"),
                                 Arc::new(profile.clone()),
                             );
                         }
@@ -205,9 +215,9 @@ fn bench_cache_operations(c: &mut Criterion) {
 
                         for i in 0..cache_size {
                             let did = if i < (cache_size / 2) {
-                                format!("did:plc:test{}", i)
+                                format!("did:plc:test{i}")
                             } else {
-                                format!("did:plc:missing{}", i)
+                                format!("did:plc:missing{i}")
                             };
 
                             if cache.get_user_profile(&did).is_some() {
@@ -218,7 +228,7 @@ fn bench_cache_operations(c: &mut Criterion) {
                         }
 
                         let hit_rate = hits as f64 / (hits + misses) as f64;
-                        assert!(hit_rate >= 0.45 && hit_rate <= 0.55);
+                        assert!((0.45..=0.55).contains(&hit_rate));
                     });
                 });
             },
@@ -260,7 +270,9 @@ fn bench_serialization(c: &mut Criterion) {
     c.bench_function("serde_json_serialize_enriched_record", |b| {
         let message = create_test_message(0);
         let record = EnrichedRecord {
+            schema_version: ENRICHED_RECORD_SCHEMA_VERSION,
             message,
+            event: EnrichedEventKind::Record,
             hydrated_metadata: HydratedMetadata::default(),
             processed_at: chrono::Utc::now(),
             metrics: ProcessingMetrics {
@@ -269,6 +281,7 @@ fn bench_serialization(c: &mut Criterion) {
                 cache_hit_rate: 0.5,
                 cache_hits: 5,
                 cache_misses: 5,
+                ingest_lag_ms: 0,
             },
         };
         b.iter(|| {
@@ -277,6 +290,54 @@ fn bench_serialization(c: &mut Criterion) {
     });
 }
 
+/// Frame parsing at firehose scale: the public Jetstream peaks in the low thousands of
+/// events per second, so each iteration parses a 1,000-frame window.
+fn bench_frame_parsing(c: &mut Criterion) {
+    const FRAMES: usize = 1_000;
+    let frames: Vec<String> = (0..FRAMES)
+        .map(|i| serde_json::to_string(&create_test_message(i)).unwrap())
+        .collect();
+    let client = JetstreamClient::with_defaults(vec!["bench.bsky.network".to_string()]);
+
+    let mut group = c.benchmark_group("jetstream_frame_parse");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+
+    group.bench_function("serde_json", |b| {
+        b.iter_batched(
+            || frames.clone(),
+            |frames| {
+                for frame in frames {
+                    let _message: JetstreamMessage = serde_json::from_str(&frame).unwrap();
+                }
+            },
+            criterion::BatchSize::LargeInput,
+        );
+    });
+
+    group.bench_function("simd_json_owned", |b| {
+        b.iter_batched(
+            || frames.clone(),
+            |frames| {
+                for frame in frames {
+                    client.parse_owned(frame).unwrap();
+                }
+            },
+            criterion::BatchSize::LargeInput,
+        );
+    });
+
+    group.finish();
+}
+
+/// Moves every record past the previous round's events so repeated stores insert
+/// new rows instead of being dropped as duplicates.
+fn advance_time_us(records: &mut [EnrichedRecord]) {
+    let len = records.len() as u64;
+    for record in records {
+        record.message.time_us = record.message.time_us.map(|t| t + len);
+    }
+}
+
 fn bench_sqlite_operations(c: &mut Criterion) {
     let rt = tokio::runtime::Runtime::new().unwrap();
 
@@ -291,8 +352,10 @@ fn bench_sqlite_operations(c: &mut Criterion) {
         });
 
         let message = create_test_message(0);
-        let record = EnrichedRecord {
+        let mut record = EnrichedRecord {
+            schema_version: ENRICHED_RECORD_SCHEMA_VERSION,
             message,
+            event: EnrichedEventKind::Record,
             hydrated_metadata: HydratedMetadata::default(),
             processed_at: chrono::Utc::now(),
             metrics: ProcessingMetrics {
@@ -301,10 +364,13 @@ fn bench_sqlite_operations(c: &mut Criterion) {
                 cache_hit_rate: 0.5,
                 cache_hits: 5,
                 cache_misses: 5,
+                ingest_lag_ms: 0,
             },
         };
 
         b.iter(|| {
+            // A fresh time_us each round, otherwise repeats are deduplicated rather than inserted
+            record.message.time_us = record.message.time_us.map(|t| t + 1);
             rt.block_on(async {
                 let _id = store.store_record(&record).await.unwrap();
             });
@@ -321,11 +387,13 @@ fn bench_sqlite_operations(c: &mut Criterion) {
                 .unwrap()
         });
 
-        let records: Vec<EnrichedRecord> = (0..100)
+        let mut records: Vec<EnrichedRecord> = (0..100)
             .map(|i| {
                 let message = create_test_message(i);
                 EnrichedRecord {
+                    schema_version: ENRICHED_RECORD_SCHEMA_VERSION,
                     message,
+                    event: EnrichedEventKind::Record,
                     hydrated_metadata: HydratedMetadata::default(),
                     processed_at: chrono::Utc::now(),
                     metrics: ProcessingMetrics {
@@ -334,12 +402,14 @@ fn bench_sqlite_operations(c: &mut Criterion) {
                         cache_hit_rate: 0.5,
                         cache_hits: 5,
                         cache_misses: 5,
+                        ingest_lag_ms: 0,
                     },
                 }
             })
             .collect();
 
         b.iter(|| {
+            advance_time_us(&mut records);
             rt.block_on(async {
                 for record in &records {
                     let _id = store.store_record(record).await.unwrap();
@@ -364,11 +434,13 @@ fn bench_sqlite_operations(c: &mut Criterion) {
                         .unwrap()
                 });
 
-                let records: Vec<EnrichedRecord> = (0..batch_size)
+                let mut records: Vec<EnrichedRecord> = (0..batch_size)
                     .map(|i| {
                         let message = create_test_message(i);
                         EnrichedRecord {
+                            schema_version: ENRICHED_RECORD_SCHEMA_VERSION,
                             message,
+                            event: EnrichedEventKind::Record,
                             hydrated_metadata: HydratedMetadata::default(),
                             processed_at: chrono::Utc::now(),
                             metrics: ProcessingMetrics {
@@ -377,12 +449,14 @@ fn bench_sqlite_operations(c: &mut Criterion) {
                                 cache_hit_rate: 0.5,
                                 cache_hits: 5,
                                 cache_misses: 5,
+                                ingest_lag_ms: 0,
                             },
                         }
                     })
                     .collect();
 
                 b.iter(|| {
+                    advance_time_us(&mut records);
                     rt.block_on(async {
                         for record in &records {
                             let _id = store.store_record(record).await.unwrap();
@@ -433,8 +507,7 @@ fn bench_enriched_record_creation(c: &mut Criterion) {
 fn bench_batch_operations(c: &mut Criterion) {
     c.bench_function("batch_message_creation", |b| {
         b.iter(|| {
-            let messages: Vec<JetstreamMessage> =
-                (0..100).map(|i| create_test_message(i)).collect();
+            let messages: Vec<JetstreamMessage> = (0..100).map(create_test_message).collect();
             let _count = messages.len();
         });
     });
@@ -445,7 +518,9 @@ fn bench_batch_operations(c: &mut Criterion) {
                 .map(|i| {
                     let message = create_test_message(i);
                     EnrichedRecord {
+                        schema_version: ENRICHED_RECORD_SCHEMA_VERSION,
                         message,
+                        event: EnrichedEventKind::Record,
                         hydrated_metadata: HydratedMetadata::default(),
                         processed_at: chrono::Utc::now(),
                         metrics: ProcessingMetrics {
@@ -454,6 +529,7 @@ fn bench_batch_operations(c: &mut Criterion) {
                             cache_hit_rate: 0.5,
                             cache_hits: 5,
                             cache_misses: 5,
+                            ingest_lag_ms: 0,
                         },
                     }
                 })
@@ -471,9 +547,9 @@ fn bench_batch_operations(c: &mut Criterion) {
             |b, &batch_size| {
                 b.iter(|| {
                     let profiles: Vec<BlueskyProfile> =
-                        (0..batch_size).map(|i| create_test_profile(i)).collect();
+                        (0..batch_size).map(create_test_profile).collect();
                     let _arc_profiles: Vec<Arc<BlueskyProfile>> =
-                        profiles.into_iter().map(|p| Arc::new(p)).collect();
+                        profiles.into_iter().map(Arc::new).collect();
                 });
             },
         );
@@ -486,6 +562,7 @@ criterion_group!(
     benches,
     bench_cache_operations,
     bench_serialization,
+    bench_frame_parsing,
     bench_sqlite_operations,
     bench_enriched_record_creation,
     bench_batch_operations
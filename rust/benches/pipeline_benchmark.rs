@@ -78,14 +78,20 @@ fn bench_full_pipeline_single(c: &mut Criterion) {
                     build_hydrator(Arc::clone(&profile_fetcher), Arc::clone(&post_fetcher));
 
                 // Hydrate
-                let enriched = hydrator.hydrate_batch(vec![message]).await.unwrap();
+                let enriched: Vec<_> = hydrator
+                    .hydrate_batch(vec![message])
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(Arc::new)
+                    .collect();
                 // Store
                 record_store.store_batch(&enriched).await.unwrap();
                 // Publish
                 event_publisher.publish_batch(&enriched).await.unwrap();
                 // Broadcast
                 for record in &enriched {
-                    let _ = broadcast_sender.send(record.clone());
+                    let _ = broadcast_sender.send(Arc::clone(record));
                 }
                 enriched
             })
@@ -114,14 +120,20 @@ fn bench_full_pipeline_batch_25(c: &mut Criterion) {
                     build_hydrator(Arc::clone(&profile_fetcher), Arc::clone(&post_fetcher));
 
                 // Hydrate
-                let enriched = hydrator.hydrate_batch(messages).await.unwrap();
+                let enriched: Vec<_> = hydrator
+                    .hydrate_batch(messages)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(Arc::new)
+                    .collect();
                 // Store
                 record_store.store_batch(&enriched).await.unwrap();
                 // Publish
                 event_publisher.publish_batch(&enriched).await.unwrap();
                 // Broadcast
                 for record in &enriched {
-                    let _ = broadcast_sender.send(record.clone());
+                    let _ = broadcast_sender.send(Arc::clone(record));
                 }
                 enriched
             })
@@ -0,0 +1,46 @@
+//! Runs a recording through the pipeline offline and prints its digest.
+//!
+//! ```sh
+//! cargo run --example simulate --features testing -- session.ndjson [expected-digest]
+//! ```
+//!
+//! Exits non-zero when an expected digest is given and doesn't match, so CI
+//! can pin hydration and sink output to a checked-in recording.
+
+use jetstream_turbo_rs::testing::Simulation;
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let Some(recording) = args.next() else {
+        eprintln!("usage: simulate <recording> [expected-digest]");
+        return ExitCode::FAILURE;
+    };
+    let expected = args.next();
+
+    let report = match Simulation::from_recording(&recording) {
+        Ok(simulation) => simulation.run().await,
+        Err(e) => Err(e),
+    };
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("simulation failed: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!(
+        "{} messages, {} stored, {} published",
+        report.messages, report.stored, report.published
+    );
+    println!("{}", report.digest);
+    match expected {
+        Some(expected) if expected != report.digest => {
+            eprintln!("digest mismatch: expected {expected}");
+            ExitCode::FAILURE
+        }
+        _ => ExitCode::SUCCESS,
+    }
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+const AT_URI_PREFIX: &str = "at://";
+const MAX_RKEY_LEN: usize = 512;
+
+/// A record AT-URI: `at://<did>/<collection>/<rkey>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AtUri {
+    did: String,
+    collection: String,
+    rkey: String,
+}
+
+impl AtUri {
+    pub fn new(
+        did: impl Into<String>,
+        collection: impl Into<String>,
+        rkey: impl Into<String>,
+    ) -> Self {
+        Self {
+            did: did.into(),
+            collection: collection.into(),
+            rkey: rkey.into(),
+        }
+    }
+
+    /// Parses and validates a record AT-URI. Surrounding whitespace is ignored.
+    pub fn parse(uri: &str) -> Option<Self> {
+        let (did, collection, rkey) = Self::components(uri)?;
+        Some(Self::new(did, collection, rkey))
+    }
+
+    /// Borrowed `(did, collection, rkey)` of a valid record AT-URI, for hot paths that
+    /// only need one component.
+    pub fn components(uri: &str) -> Option<(&str, &str, &str)> {
+        let mut parts = uri.trim().strip_prefix(AT_URI_PREFIX)?.split('/');
+        let did = parts.next().filter(|did| is_valid_did(did))?;
+        let collection = parts.next().filter(|c| is_valid_nsid(c))?;
+        let rkey = parts.next().filter(|rkey| is_valid_rkey(rkey))?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some((did, collection, rkey))
+    }
+
+    pub fn is_valid(uri: &str) -> bool {
+        Self::components(uri).is_some()
+    }
+
+    pub fn did(&self) -> &str {
+        &self.did
+    }
+
+    pub fn collection(&self) -> &str {
+        &self.collection
+    }
+
+    pub fn rkey(&self) -> &str {
+        &self.rkey
+    }
+}
+
+impl fmt::Display for AtUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{AT_URI_PREFIX}{}/{}/{}",
+            self.did, self.collection, self.rkey
+        )
+    }
+}
+
+/// A string that isn't an `at://{did}/{collection}/{rkey}` URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidAtUri(pub String);
+
+impl fmt::Display for InvalidAtUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid AT-URI: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidAtUri {}
+
+impl FromStr for AtUri {
+    type Err = InvalidAtUri;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or_else(|| InvalidAtUri(s.to_string()))
+    }
+}
+
+impl Serialize for AtUri {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AtUri {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// `did:plc` and `did:web` are the only methods atproto accepts.
+fn is_valid_did(did: &str) -> bool {
+    did.strip_prefix("did:plc:")
+        .or_else(|| did.strip_prefix("did:web:"))
+        .is_some_and(|id| !id.is_empty() && !id.contains(['/', '?', '#']))
+}
+
+fn is_valid_nsid(collection: &str) -> bool {
+    collection.split('.').count() >= 3
+        && collection.split('.').all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+fn is_valid_rkey(rkey: &str) -> bool {
+    !rkey.is_empty()
+        && rkey.len() <= MAX_RKEY_LEN
+        && rkey != "."
+        && rkey != ".."
+        && rkey
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':' | '~'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_round_trip() {
+        let uri = AtUri::parse(" at://did:plc:abc123/app.bsky.feed.post/3kabc ").unwrap();
+        assert_eq!(uri.did(), "did:plc:abc123");
+        assert_eq!(uri.collection(), "app.bsky.feed.post");
+        assert_eq!(uri.rkey(), "3kabc");
+        assert_eq!(
+            uri.to_string(),
+            "at://did:plc:abc123/app.bsky.feed.post/3kabc"
+        );
+
+        let parsed: AtUri = uri.to_string().parse().unwrap();
+        assert_eq!(parsed, uri);
+
+        let json = serde_json::to_string(&uri).unwrap();
+        assert_eq!(json, r#""at://did:plc:abc123/app.bsky.feed.post/3kabc""#);
+        assert_eq!(serde_json::from_str::<AtUri>(&json).unwrap(), uri);
+    }
+
+    #[test]
+    fn test_rejects_malformed_uris() {
+        for uri in [
+            "",
+            "at://",
+            "at://did:plc:abc",
+            "at://did:plc:abc/app.bsky.feed.post",
+            "at://did:plc:abc/app.bsky.feed.post/",
+            "at://did:plc:abc/app.bsky.feed.post/3k/extra",
+            "at://did:example:abc/app.bsky.feed.post/3k",
+            "at://did:plc:abc/feed/3k",
+            "at://did:plc:abc/app.bsky.feed.post/..",
+            "https://bsky.app/profile/abc",
+        ] {
+            assert!(!AtUri::is_valid(uri), "{uri} should be rejected");
+        }
+        assert!(AtUri::is_valid(
+            "at://did:web:example.com/app.bsky.feed.post/self"
+        ));
+        assert!("at://did:plc:abc".parse::<AtUri>().is_err());
+    }
+}
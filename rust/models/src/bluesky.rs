@@ -51,11 +51,15 @@ pub struct BlueskyPost {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum Embed {
     Images(ImagesEmbed),
+    Video(VideoEmbed),
     External(ExternalEmbed),
-    Record(RecordEmbed),
+    ExternalView(ExternalViewEmbed),
+    // Must precede `Record`: both carry a `record` field and untagged matching is first-wins.
     RecordWithMedia(Box<RecordWithMediaEmbed>),
+    Record(RecordEmbed),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -68,6 +72,36 @@ pub struct Image {
     pub thumb: String,
     pub fullsize: String,
     pub alt: String,
+    #[serde(
+        default,
+        rename = "aspectRatio",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub aspect_ratio: Option<AspectRatio>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// `app.bsky.embed.video#view`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VideoEmbed {
+    pub cid: String,
+    /// HLS playlist URL
+    pub playlist: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    #[serde(
+        default,
+        rename = "aspectRatio",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub aspect_ratio: Option<AspectRatio>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -78,6 +112,41 @@ pub struct ExternalEmbed {
     pub thumb: Option<String>,
 }
 
+impl ExternalEmbed {
+    /// GIFs picked in the Bluesky composer are external embeds pointing at Tenor.
+    pub fn is_gif(&self) -> bool {
+        let without_query = self.uri.split(['?', '#']).next().unwrap_or_default();
+        self.uri.starts_with("https://media.tenor.com/")
+            || without_query.to_ascii_lowercase().ends_with(".gif")
+    }
+}
+
+/// `app.bsky.embed.external#view`, which nests the link card under `external`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExternalViewEmbed {
+    pub external: ExternalEmbed,
+}
+
+impl Embed {
+    /// Link card for external embeds in either the flat or the `#view` shape.
+    pub fn external(&self) -> Option<&ExternalEmbed> {
+        match self {
+            Embed::External(external) => Some(external),
+            Embed::ExternalView(view) => Some(&view.external),
+            Embed::RecordWithMedia(embed) => embed.media.external(),
+            Embed::Images(_) | Embed::Video(_) | Embed::Record(_) => None,
+        }
+    }
+
+    pub fn video(&self) -> Option<&VideoEmbed> {
+        match self {
+            Embed::Video(video) => Some(video),
+            Embed::RecordWithMedia(embed) => embed.media.video(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RecordEmbed {
     pub record: RecordRef,
@@ -85,7 +154,7 @@ pub struct RecordEmbed {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RecordWithMediaEmbed {
-    pub record: RecordRef,
+    pub record: RecordEmbed,
     pub media: Box<Embed>,
 }
 
@@ -271,4 +340,68 @@ mod tests {
         assert_eq!(profile.handle, "test.bsky.social");
         assert_eq!(profile.display_name, Some("Test User".to_string()));
     }
+
+    #[test]
+    fn test_video_and_external_embed_views() {
+        let video: Embed = serde_json::from_value(serde_json::json!({
+            "$type": "app.bsky.embed.video#view",
+            "cid": "bafkreivideo",
+            "playlist": "https://video.bsky.app/watch/did%3Aplc%3Aabc/bafkreivideo/playlist.m3u8",
+            "thumbnail": "https://video.bsky.app/watch/did%3Aplc%3Aabc/bafkreivideo/thumbnail.jpg",
+            "aspectRatio": {"width": 1080, "height": 1920}
+        }))
+        .unwrap();
+        let video = video.video().expect("video embed");
+        assert!(video.playlist.ends_with("playlist.m3u8"));
+        assert_eq!(
+            video.aspect_ratio,
+            Some(AspectRatio {
+                width: 1080,
+                height: 1920
+            })
+        );
+
+        let gif: Embed = serde_json::from_value(serde_json::json!({
+            "$type": "app.bsky.embed.external#view",
+            "external": {
+                "uri": "https://media.tenor.com/abc/party.gif?hh=200&ww=200",
+                "title": "Party",
+                "description": "Alt: dancing"
+            }
+        }))
+        .unwrap();
+        assert!(matches!(gif, Embed::ExternalView(_)));
+        assert!(gif.external().unwrap().is_gif());
+
+        let quote_with_video: Embed = serde_json::from_value(serde_json::json!({
+            "$type": "app.bsky.embed.recordWithMedia#view",
+            "record": {
+                "$type": "app.bsky.embed.record#view",
+                "record": {"uri": "at://did:plc:abc/app.bsky.feed.post/3k", "cid": "bafyquote"}
+            },
+            "media": {
+                "$type": "app.bsky.embed.video#view",
+                "cid": "bafkreivideo",
+                "playlist": "https://video.bsky.app/playlist.m3u8"
+            }
+        }))
+        .unwrap();
+        assert!(matches!(quote_with_video, Embed::RecordWithMedia(_)));
+        assert!(quote_with_video.video().is_some());
+        assert!(quote_with_video.external().is_none());
+    }
+
+    #[test]
+    fn test_quote_embed_is_a_plain_record_variant() {
+        let quote: Embed = serde_json::from_value(serde_json::json!({
+            "$type": "app.bsky.embed.record",
+            "record": {"uri": "at://did:plc:abc/app.bsky.feed.post/3k", "cid": "bafyquote"}
+        }))
+        .unwrap();
+        let Embed::Record(RecordEmbed { record }) = quote else {
+            panic!("expected a record embed, got {quote:?}");
+        };
+        assert_eq!(record.uri, "at://did:plc:abc/app.bsky.feed.post/3k");
+        assert!(Embed::Record(RecordEmbed { record }).external().is_none());
+    }
 }
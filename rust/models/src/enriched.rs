@@ -0,0 +1,1010 @@
+use crate::{
+    at_uri::AtUri,
+    bluesky::{AspectRatio, BlueskyProfile},
+    jetstream::JetstreamMessage,
+    text::normalize_hashtag,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+fn serialize_arc_str<S>(value: &Arc<str>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(value)
+}
+
+/// Serialized shape version written with every `EnrichedRecord`. Bump this whenever the shape
+/// changes incompatibly and add a step to `upgrade_enriched_record_json`.
+pub const ENRICHED_RECORD_SCHEMA_VERSION: u32 = 2;
+/// Version assumed for rows and payloads written before records were versioned.
+pub const LEGACY_SCHEMA_VERSION: u32 = 1;
+
+fn legacy_schema_version() -> u32 {
+    LEGACY_SCHEMA_VERSION
+}
+
+/// What downstream consumers should do with an enriched record.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EnrichedEventKind {
+    /// A created or updated record carrying hydrated metadata
+    #[default]
+    Record,
+    /// A delete commit; carries no record and is never hydrated
+    Delete,
+}
+
+/// Wire shape used when records are sent to WebSocket subscribers.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// The full `EnrichedRecord`
+    #[default]
+    Enriched,
+    /// The original Jetstream event with hydrated data under `JETSTREAM_EXTENSION_KEY`,
+    /// so unmodified Jetstream consumers can read the stream
+    Jetstream,
+}
+
+impl OutputFormat {
+    pub fn encode(self, record: &EnrichedRecord) -> serde_json::Result<String> {
+        match self {
+            OutputFormat::Enriched => serde_json::to_string(record),
+            OutputFormat::Jetstream => serde_json::to_string(&record.jetstream_envelope()),
+        }
+    }
+
+    /// `encode` with the broadcast event id as a top-level `event_id` key, which
+    /// WebSocket clients send back as `last_id` to resume after a reconnect.
+    pub fn encode_event(
+        self,
+        record: &EnrichedRecord,
+        event_id: u64,
+    ) -> serde_json::Result<String> {
+        #[derive(Serialize)]
+        struct WithEventId<T> {
+            event_id: u64,
+            #[serde(flatten)]
+            event: T,
+        }
+
+        match self {
+            OutputFormat::Enriched => serde_json::to_string(&WithEventId {
+                event_id,
+                event: record,
+            }),
+            OutputFormat::Jetstream => serde_json::to_string(&WithEventId {
+                event_id,
+                event: record.jetstream_envelope(),
+            }),
+        }
+    }
+}
+
+/// Key holding the hydrated data in the Jetstream-compatible envelope. Jetstream
+/// consumers ignore unknown top-level keys, so it rides along untouched.
+pub const JETSTREAM_EXTENSION_KEY: &str = "turbo";
+
+/// A Jetstream event with the enrichment attached; see `OutputFormat::Jetstream`.
+#[derive(Debug, Serialize)]
+pub struct JetstreamEnvelope<'a> {
+    #[serde(flatten)]
+    pub message: &'a JetstreamMessage,
+    #[serde(rename = "turbo")]
+    pub extension: TurboExtension<'a>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TurboExtension<'a> {
+    pub schema_version: u32,
+    pub event: EnrichedEventKind,
+    pub hydrated_metadata: &'a HydratedMetadata,
+    pub processed_at: DateTime<Utc>,
+    pub metrics: &'a ProcessingMetrics,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrichedRecord {
+    /// Shape version of this record, see `ENRICHED_RECORD_SCHEMA_VERSION`
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
+    /// Original jetstream message
+    pub message: JetstreamMessage,
+    /// Event type, so consumers can tell deletes apart without inspecting the commit
+    #[serde(default)]
+    pub event: EnrichedEventKind,
+    /// Hydrated metadata including profiles and referenced content
+    #[serde(default)]
+    pub hydrated_metadata: HydratedMetadata,
+    /// Processing timestamp
+    pub processed_at: DateTime<Utc>,
+    /// Processing metrics
+    pub metrics: ProcessingMetrics,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HydratedMetadata {
+    /// Author profile information
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_profile: Option<Arc<BlueskyProfile>>,
+    /// Profiles of mentioned users
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mentioned_profiles: Vec<Arc<BlueskyProfile>>,
+    /// Referenced posts (replies, quotes)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub referenced_posts: Vec<ReferencedPost>,
+    /// Extracted hashtags
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hashtags: Vec<String>,
+    /// Extracted URLs, with link previews when `URL_UNFURLING` is on
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<UrlEntry>,
+    /// Extracted mentions
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<Mention>,
+    /// Embedded images and videos with their alt text and aspect ratios
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media: Option<MediaSummary>,
+    /// Content language detection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detected_language: Option<String>,
+    /// Moderation label values applied to the author, the record, or posts it references
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// Labels that matched the configured label filter
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub flagged_labels: Vec<String>,
+    /// Lexicon violations found when validation runs in tag mode
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub validation_errors: Vec<String>,
+    /// Heuristic bot/spam likelihood in `[0, 1]`, when `SPAM_SCORING` is on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spam_score: Option<f32>,
+    /// Sentiment of the post text in `[-1, 1]` (`analytics` feature)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sentiment: Option<f32>,
+    /// Cashtags mentioned in the post text (`analytics` feature)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tickers: Vec<String>,
+    /// Domains of linked and embedded URLs (`analytics` feature)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+    /// Historical record loaded by backfill rather than received live
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub backfill: bool,
+    /// Hydrated while degraded under API pressure, with some referenced posts
+    /// or the author profile left out
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+    /// Source URL to object-storage copy for mirrored avatars and images (`s3` feature)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub mirrored_blobs: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferencedPost {
+    pub uri: String,
+    pub cid: String,
+    pub text: String,
+    #[serde(serialize_with = "serialize_arc_str")]
+    pub author_did: Arc<str>,
+    pub author_handle: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub reply_count: Option<u64>,
+    pub like_count: Option<u64>,
+    pub repost_count: Option<u64>,
+}
+
+/// A link from the post text. Plain links serialize as bare strings, as they
+/// did before unfurling existed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UrlEntry {
+    Link(String),
+    Unfurled(UnfurledUrl),
+}
+
+impl UrlEntry {
+    pub fn url(&self) -> &str {
+        match self {
+            Self::Link(url) => url,
+            Self::Unfurled(unfurled) => &unfurled.url,
+        }
+    }
+}
+
+/// OpenGraph metadata fetched from a linked page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnfurledUrl {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// Images and videos embedded in a record, directly or alongside a quote.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaSummary {
+    pub image_count: u32,
+    pub video_count: u32,
+    /// Items with no alt text, or only whitespace
+    pub missing_alt_count: u32,
+    pub items: Vec<MediaItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaItem {
+    pub kind: MediaKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<AspectRatio>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Image,
+    Video,
+}
+
+impl MediaSummary {
+    /// Reads the record's `app.bsky.embed.images` or `app.bsky.embed.video`
+    /// embed, or the media half of a `recordWithMedia`. `None` without media.
+    pub fn from_record(record: &serde_json::Value) -> Option<Self> {
+        let embed = record.get("embed")?;
+        let media = embed.get("media").unwrap_or(embed);
+
+        let items: Vec<MediaItem> = match media.get("$type").and_then(|t| t.as_str())? {
+            "app.bsky.embed.images" => media
+                .get("images")
+                .and_then(|images| images.as_array())
+                .into_iter()
+                .flatten()
+                .map(|image| MediaItem::from_embed(MediaKind::Image, image, image.get("image")))
+                .collect(),
+            "app.bsky.embed.video" => vec![MediaItem::from_embed(
+                MediaKind::Video,
+                media,
+                media.get("video"),
+            )],
+            _ => return None,
+        };
+        if items.is_empty() {
+            return None;
+        }
+
+        let count = |kind| items.iter().filter(|item| item.kind == kind).count() as u32;
+        Some(Self {
+            image_count: count(MediaKind::Image),
+            video_count: count(MediaKind::Video),
+            missing_alt_count: items.iter().filter(|item| item.alt.is_none()).count() as u32,
+            items,
+        })
+    }
+}
+
+impl MediaItem {
+    /// `entry` holds the alt text and aspect ratio, `blob` the uploaded file.
+    fn from_embed(
+        kind: MediaKind,
+        entry: &serde_json::Value,
+        blob: Option<&serde_json::Value>,
+    ) -> Self {
+        Self {
+            kind,
+            alt: entry
+                .get("alt")
+                .and_then(|alt| alt.as_str())
+                .map(str::trim)
+                .filter(|alt| !alt.is_empty())
+                .map(str::to_string),
+            aspect_ratio: entry
+                .get("aspectRatio")
+                .and_then(|ratio| serde_json::from_value(ratio.clone()).ok()),
+            mime_type: blob
+                .and_then(|blob| blob.get("mimeType")?.as_str())
+                .map(str::to_string),
+            size_bytes: blob.and_then(|blob| blob.get("size")?.as_u64()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mention {
+    #[serde(serialize_with = "serialize_arc_str")]
+    pub did: Arc<str>,
+    pub handle: Option<String>,
+    pub display_name: Option<String>,
+    pub start_byte: u32,
+    pub end_byte: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ProcessingMetrics {
+    /// Time taken to hydrate this record
+    pub hydration_time_ms: u64,
+    /// Number of API calls made
+    pub api_calls_count: u32,
+    /// Cache hit rate for this record
+    pub cache_hit_rate: f64,
+    /// Number of items fetched from cache vs API
+    pub cache_hits: u32,
+    pub cache_misses: u32,
+    /// How far behind real time the event was when it was hydrated
+    #[serde(default)]
+    pub ingest_lag_ms: u64,
+}
+
+impl EnrichedRecord {
+    #[inline(always)]
+    pub fn new(message: JetstreamMessage) -> Self {
+        let event = if message.is_delete_operation() {
+            EnrichedEventKind::Delete
+        } else {
+            EnrichedEventKind::Record
+        };
+
+        Self {
+            schema_version: ENRICHED_RECORD_SCHEMA_VERSION,
+            message,
+            event,
+            hydrated_metadata: HydratedMetadata {
+                author_profile: None,
+                mentioned_profiles: Vec::new(),
+                referenced_posts: Vec::new(),
+                hashtags: Vec::new(),
+                urls: Vec::new(),
+                mentions: Vec::new(),
+                media: None,
+                detected_language: None,
+                labels: Vec::new(),
+                flagged_labels: Vec::new(),
+                validation_errors: Vec::new(),
+                spam_score: None,
+                sentiment: None,
+                tickers: Vec::new(),
+                domains: Vec::new(),
+                backfill: false,
+                partial: false,
+                mirrored_blobs: BTreeMap::new(),
+            },
+            processed_at: Utc::now(),
+            metrics: ProcessingMetrics {
+                hydration_time_ms: 0,
+                api_calls_count: 0,
+                cache_hit_rate: 0.0,
+                cache_hits: 0,
+                cache_misses: 0,
+                ingest_lag_ms: 0,
+            },
+        }
+    }
+
+    /// Deserializes a record written by any earlier schema version.
+    pub fn from_json_value(mut value: serde_json::Value) -> serde_json::Result<Self> {
+        upgrade_enriched_record_json(&mut value);
+        serde_json::from_value(value)
+    }
+
+    pub fn jetstream_envelope(&self) -> JetstreamEnvelope<'_> {
+        JetstreamEnvelope {
+            message: &self.message,
+            extension: TurboExtension {
+                schema_version: self.schema_version,
+                event: self.event,
+                hydrated_metadata: &self.hydrated_metadata,
+                processed_at: self.processed_at,
+                metrics: &self.metrics,
+            },
+        }
+    }
+
+    #[inline(always)]
+    pub fn get_at_uri(&self) -> Option<String> {
+        self.message.extract_at_uri()
+    }
+
+    pub fn at_uri(&self) -> Option<AtUri> {
+        self.message.at_uri()
+    }
+
+    /// Stable identity of the source event, `blake3(did, rkey, time_us)` truncated to
+    /// 128 bits. Sinks use it to drop events replayed after a cursor resume. `None`
+    /// without a `time_us`, since such events can't be told apart from their replays.
+    pub fn idempotency_key(&self) -> Option<String> {
+        let time_us = self.message.time_us?;
+        let rkey = self
+            .message
+            .commit
+            .as_ref()
+            .and_then(|commit| commit.rkey.as_deref())
+            .unwrap_or_default();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.get_did().as_bytes());
+        hasher.update(&[0]);
+        hasher.update(rkey.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(&time_us.to_be_bytes());
+        Some(hasher.finalize().to_hex()[..32].to_string())
+    }
+
+    #[inline(always)]
+    pub fn is_delete(&self) -> bool {
+        self.event == EnrichedEventKind::Delete
+    }
+
+    #[inline(always)]
+    pub fn get_did(&self) -> &str {
+        self.message.extract_did()
+    }
+
+    #[inline(always)]
+    pub fn get_text(&self) -> Option<&str> {
+        self.message
+            .commit
+            .as_ref()
+            .and_then(|c| c.record.as_ref())
+            .and_then(|r| r.get("text").and_then(|v| v.as_str()))
+    }
+
+    /// Sets `metrics.ingest_lag_ms` to the time since the event's `time_us`.
+    pub fn record_ingest_lag(&mut self) {
+        if let Some(time_us) = self.message.time_us {
+            let now_us = Utc::now().timestamp_micros().max(0) as u64;
+            self.metrics.ingest_lag_ms = now_us.saturating_sub(time_us) / 1000;
+        }
+    }
+
+    #[inline(always)]
+    pub fn calculate_cache_hit_rate(&mut self) {
+        let total = self.metrics.cache_hits + self.metrics.cache_misses;
+        self.metrics.cache_hit_rate = if total > 0 {
+            self.metrics.cache_hits as f64 / total as f64
+        } else {
+            0.0
+        };
+    }
+}
+
+impl HydratedMetadata {
+    pub fn add_mentioned_profile(&mut self, profile: Arc<BlueskyProfile>) {
+        if !self.mentioned_profiles.iter().any(|p| p.did == profile.did) {
+            self.mentioned_profiles.push(profile);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.author_profile.is_none()
+            && self.mentioned_profiles.is_empty()
+            && self.referenced_posts.is_empty()
+            && self.hashtags.is_empty()
+            && self.urls.is_empty()
+            && self.mentions.is_empty()
+            && self.media.is_none()
+            && self.detected_language.is_none()
+            && self.labels.is_empty()
+            && self.flagged_labels.is_empty()
+            && self.validation_errors.is_empty()
+            && self.spam_score.is_none()
+            && self.sentiment.is_none()
+            && self.tickers.is_empty()
+            && self.domains.is_empty()
+    }
+
+    pub fn add_referenced_post(&mut self, post: ReferencedPost) {
+        if !self.referenced_posts.iter().any(|p| p.uri == post.uri) {
+            self.referenced_posts.push(post);
+        }
+    }
+
+    pub fn extract_content_features(&mut self, text: &str, record: &Option<serde_json::Value>) {
+        // Reset arrays
+        self.hashtags.clear();
+        self.urls.clear();
+        self.mentions.clear();
+
+        // Extract from facets
+        if let Some(record) = record {
+            if let Some(facets) = record.get("facets").and_then(|f| f.as_array()) {
+                for facet in facets {
+                    let range = facet_byte_range(text, facet.get("index"));
+
+                    if let Some(features) = facet.get("features").and_then(|f| f.as_array()) {
+                        for feature in features {
+                            let feature_type =
+                                feature.get("$type").and_then(|t| t.as_str()).unwrap_or("");
+                            match feature_type {
+                                "app.bsky.richtext.facet#tag" => {
+                                    // Prefer the tag value itself; indices are only a fallback
+                                    let tag = feature
+                                        .get("tag")
+                                        .and_then(|t| t.as_str())
+                                        .filter(|t| !t.is_empty())
+                                        .or_else(|| range.map(|(start, end)| &text[start..end]));
+                                    if let Some(tag) = tag.and_then(normalize_hashtag) {
+                                        self.hashtags.push(tag);
+                                    }
+                                }
+                                "app.bsky.richtext.facet#link" => {
+                                    if let Some(uri) = feature.get("uri").and_then(|u| u.as_str()) {
+                                        if uri.starts_with("http") {
+                                            self.urls.push(UrlEntry::Link(uri.to_string()));
+                                        }
+                                    }
+                                }
+                                "app.bsky.richtext.facet#mention" => {
+                                    if let Some(did) = feature.get("did").and_then(|d| d.as_str()) {
+                                        let (start, end) = range.unwrap_or((0, 0));
+                                        self.mentions.push(Mention {
+                                            did: did.into(),
+                                            handle: None,
+                                            display_name: None,
+                                            start_byte: start as u32,
+                                            end_byte: end as u32,
+                                        });
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rewrites a serialized `EnrichedRecord` in place so it matches
+/// `ENRICHED_RECORD_SCHEMA_VERSION`, applying each version's step in order. Unknown future
+/// versions are left untouched.
+pub fn upgrade_enriched_record_json(value: &mut serde_json::Value) {
+    let Some(object) = value.as_object_mut() else {
+        return;
+    };
+
+    let version = object
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+        .unwrap_or(LEGACY_SCHEMA_VERSION);
+
+    // v1 payloads predate the `event` field; deletes were only visible in the commit.
+    if version < 2 && !object.contains_key("event") {
+        let is_delete = object
+            .get("message")
+            .and_then(|m| m.get("commit"))
+            .and_then(|c| c.get("operation"))
+            .and_then(|o| o.as_str())
+            == Some("delete");
+        let event = if is_delete { "delete" } else { "record" };
+        object.insert("event".to_string(), event.into());
+    }
+
+    object.insert(
+        "schema_version".to_string(),
+        version.max(ENRICHED_RECORD_SCHEMA_VERSION).into(),
+    );
+}
+
+/// Validates a facet `index` against `text`, returning a non-empty byte range that lies on
+/// UTF-8 character boundaries. Offsets past the end are clamped and offsets inside a multibyte
+/// character are widened to cover the whole character.
+fn facet_byte_range(text: &str, index: Option<&serde_json::Value>) -> Option<(usize, usize)> {
+    let index = index?;
+    let start = usize::try_from(index.get("byteStart")?.as_u64()?).ok()?;
+    let end = usize::try_from(index.get("byteEnd")?.as_u64()?).ok()?;
+
+    let end = end.min(text.len());
+    if start >= end {
+        return None;
+    }
+
+    let mut start = start;
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = end;
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jetstream::{CommitData, MessageKind, OperationType};
+    use serde_json::json;
+
+    #[test]
+    fn test_enriched_record_creation() {
+        let message = JetstreamMessage {
+            did: "did:plc:test".to_string(),
+            time_us: Some(1640995200000000),
+            seq: Some(12345),
+            kind: MessageKind::Commit,
+            commit: Some(CommitData {
+                rev: Some("test-rev".to_string()),
+                operation_type: OperationType::Create,
+                collection: Some("app.bsky.feed.post".to_string()),
+                rkey: Some("test123".to_string()),
+                record: Some(json!({"text": "Hello world"})),
+                cid: Some("bafyrei".to_string()),
+                extra: Default::default(),
+            }),
+            identity: None,
+            account: None,
+            extra: Default::default(),
+        };
+
+        let enriched = EnrichedRecord::new(message);
+        assert_eq!(enriched.get_did(), "did:plc:test");
+        assert_eq!(enriched.get_text(), Some("Hello world"));
+    }
+
+    #[test]
+    fn test_idempotency_key_identifies_replayed_events() {
+        use crate::fixtures::create_post_message;
+
+        let original = EnrichedRecord::new(create_post_message(1));
+        let replayed = EnrichedRecord::new(create_post_message(1));
+        let key = original.idempotency_key().unwrap();
+        assert_eq!(key.len(), 32);
+        assert_eq!(replayed.idempotency_key(), Some(key.clone()));
+
+        let mut edited = create_post_message(1);
+        edited.time_us = edited.time_us.map(|time_us| time_us + 1);
+        assert_ne!(EnrichedRecord::new(edited).idempotency_key(), Some(key));
+
+        let mut untimed = create_post_message(1);
+        untimed.time_us = None;
+        assert!(EnrichedRecord::new(untimed).idempotency_key().is_none());
+    }
+
+    #[test]
+    fn test_cache_hit_rate_calculation() {
+        let mut enriched = EnrichedRecord::new(JetstreamMessage {
+            did: "did:plc:test".to_string(),
+            time_us: Some(1640995200000000),
+            seq: Some(12345),
+            kind: MessageKind::Commit,
+            commit: Some(CommitData {
+                rev: Some("test-rev".to_string()),
+                operation_type: OperationType::Create,
+                collection: Some("app.bsky.feed.post".to_string()),
+                rkey: Some("test123".to_string()),
+                record: Some(json!({"text": "Hello"})),
+                cid: Some("bafyrei".to_string()),
+                extra: Default::default(),
+            }),
+            identity: None,
+            account: None,
+            extra: Default::default(),
+        });
+
+        enriched.metrics.cache_hits = 8;
+        enriched.metrics.cache_misses = 2;
+        enriched.calculate_cache_hit_rate();
+
+        assert_eq!(enriched.metrics.cache_hit_rate, 0.8);
+    }
+
+    #[test]
+    fn test_empty_hydrated_metadata_serializes_compactly() {
+        let message = JetstreamMessage {
+            did: "did:plc:test".to_string(),
+            time_us: Some(1640995200000000),
+            seq: Some(12345),
+            kind: MessageKind::Commit,
+            commit: Some(CommitData {
+                rev: Some("test-rev".to_string()),
+                operation_type: OperationType::Create,
+                collection: Some("app.bsky.feed.post".to_string()),
+                rkey: Some("test123".to_string()),
+                record: Some(json!({"text": "Hello world"})),
+                cid: Some("bafyrei".to_string()),
+                extra: Default::default(),
+            }),
+            identity: None,
+            account: None,
+            extra: Default::default(),
+        };
+
+        let enriched = EnrichedRecord::new(message);
+        let json = serde_json::to_string(&enriched).unwrap();
+
+        assert!(json.contains("\"hydrated_metadata\":{}"));
+        assert!(!json.contains("\"mentioned_profiles\""));
+        assert!(!json.contains("\"referenced_posts\""));
+        assert!(!json.contains("\"hashtags\""));
+        assert!(!json.contains("\"urls\""));
+        assert!(!json.contains("\"mentions\""));
+    }
+
+    #[test]
+    fn test_hydrated_metadata_defaults_when_fields_are_missing() {
+        let enriched: EnrichedRecord = serde_json::from_value(json!({
+            "message": {
+                "did": "did:plc:test",
+                "time_us": 1640995200000000_u64,
+                "seq": 12345_u64,
+                "kind": "commit",
+                "commit": {
+                    "rev": "test-rev",
+                    "operation": "create",
+                    "collection": "app.bsky.feed.post",
+                    "rkey": "test123",
+                    "record": {"text": "Hello world"},
+                    "cid": "bafyrei"
+                }
+            },
+            "hydrated_metadata": {},
+            "processed_at": "2024-01-01T00:00:00Z",
+            "metrics": {
+                "hydration_time_ms": 0,
+                "api_calls_count": 0,
+                "cache_hit_rate": 0.0,
+                "cache_hits": 0,
+                "cache_misses": 0
+            }
+        }))
+        .unwrap();
+
+        assert!(enriched.hydrated_metadata.is_empty());
+        assert_eq!(enriched.event, EnrichedEventKind::Record);
+    }
+
+    #[test]
+    fn test_delete_commit_becomes_delete_event() {
+        let message = JetstreamMessage {
+            did: "did:plc:test".to_string(),
+            time_us: Some(1640995200000000),
+            seq: None,
+            kind: MessageKind::Commit,
+            commit: Some(CommitData {
+                rev: Some("test-rev".to_string()),
+                operation_type: OperationType::Delete,
+                collection: Some("app.bsky.feed.post".to_string()),
+                rkey: Some("test123".to_string()),
+                record: None,
+                cid: None,
+                extra: Default::default(),
+            }),
+            identity: None,
+            account: None,
+            extra: Default::default(),
+        };
+
+        let enriched = EnrichedRecord::new(message);
+        assert!(enriched.is_delete());
+        assert_eq!(
+            enriched.get_at_uri().as_deref(),
+            Some("at://did:plc:test/app.bsky.feed.post/test123")
+        );
+
+        let json = serde_json::to_string(&enriched).unwrap();
+        assert!(json.contains("\"event\":\"delete\""));
+    }
+
+    #[test]
+    fn test_content_features_with_emoji_heavy_text() {
+        let text = "🎉🎉 launch day #Rust🦀 and #日本語 with @alice.test 🚀";
+        let rust_start = text.find("#Rust").unwrap();
+        let rust_end = rust_start + "#Rust🦀".len();
+        let jp_start = text.find("#日本語").unwrap();
+        let jp_end = jp_start + "#日本語".len();
+        let mention_start = text.find("@alice").unwrap();
+        let mention_end = mention_start + "@alice.test".len();
+
+        let record = Some(json!({
+            "text": text,
+            "facets": [
+                {
+                    // byteEnd lands inside the crab emoji; no tag value to fall back on
+                    "index": {"byteStart": rust_start, "byteEnd": rust_end - 1},
+                    "features": [{"$type": "app.bsky.richtext.facet#tag"}]
+                },
+                {
+                    "index": {"byteStart": jp_start, "byteEnd": jp_end},
+                    "features": [{"$type": "app.bsky.richtext.facet#tag", "tag": "日本語"}]
+                },
+                {
+                    "index": {"byteStart": mention_start, "byteEnd": mention_end},
+                    "features": [{"$type": "app.bsky.richtext.facet#mention", "did": "did:plc:alice"}]
+                },
+                {
+                    // Reversed and out-of-range indices are ignored rather than panicking
+                    "index": {"byteStart": 500, "byteEnd": 3},
+                    "features": [{"$type": "app.bsky.richtext.facet#tag"}]
+                }
+            ]
+        }));
+
+        let mut metadata = HydratedMetadata::default();
+        metadata.extract_content_features(text, &record);
+
+        assert_eq!(metadata.hashtags, vec!["rust🦀", "日本語"]);
+        assert_eq!(metadata.mentions.len(), 1);
+        assert_eq!(metadata.mentions[0].start_byte as usize, mention_start);
+        assert_eq!(metadata.mentions[0].end_byte as usize, mention_end);
+    }
+
+    #[test]
+    fn test_media_summary_reads_images_and_quoted_video() {
+        let images = MediaSummary::from_record(&json!({
+            "text": "Two pictures",
+            "embed": {
+                "$type": "app.bsky.embed.images",
+                "images": [
+                    {
+                        "alt": "A crab on a rock",
+                        "aspectRatio": {"width": 4, "height": 3},
+                        "image": {"$type": "blob", "mimeType": "image/jpeg", "size": 1234}
+                    },
+                    {"alt": "  ", "image": {"$type": "blob", "mimeType": "image/png"}}
+                ]
+            }
+        }))
+        .unwrap();
+        assert_eq!((images.image_count, images.video_count), (2, 0));
+        assert_eq!(images.missing_alt_count, 1);
+        assert_eq!(
+            images.items[0],
+            MediaItem {
+                kind: MediaKind::Image,
+                alt: Some("A crab on a rock".to_string()),
+                aspect_ratio: Some(AspectRatio {
+                    width: 4,
+                    height: 3
+                }),
+                mime_type: Some("image/jpeg".to_string()),
+                size_bytes: Some(1234),
+            }
+        );
+
+        let video = MediaSummary::from_record(&json!({
+            "embed": {
+                "$type": "app.bsky.embed.recordWithMedia",
+                "record": {"$type": "app.bsky.embed.record", "record": {}},
+                "media": {
+                    "$type": "app.bsky.embed.video",
+                    "alt": "Screen recording",
+                    "video": {"$type": "blob", "mimeType": "video/mp4", "size": 99}
+                }
+            }
+        }))
+        .unwrap();
+        assert_eq!((video.image_count, video.video_count), (0, 1));
+        assert_eq!(video.items[0].alt.as_deref(), Some("Screen recording"));
+
+        assert!(MediaSummary::from_record(&json!({"text": "No media"})).is_none());
+        assert!(MediaSummary::from_record(&json!({
+            "embed": {"$type": "app.bsky.embed.external", "external": {}}
+        }))
+        .is_none());
+    }
+
+    #[test]
+    fn test_facet_byte_range_snaps_to_char_boundaries() {
+        let text = "a🦀b";
+        // 🦀 occupies bytes 1..5
+        assert_eq!(
+            facet_byte_range(text, Some(&json!({"byteStart": 2, "byteEnd": 3}))),
+            Some((1, 5))
+        );
+        assert_eq!(
+            facet_byte_range(text, Some(&json!({"byteStart": 5, "byteEnd": 99}))),
+            Some((5, 6))
+        );
+        assert_eq!(
+            facet_byte_range(text, Some(&json!({"byteStart": 6, "byteEnd": 6}))),
+            None
+        );
+        assert_eq!(facet_byte_range(text, Some(&json!({"byteStart": 1}))), None);
+        assert_eq!(facet_byte_range(text, None), None);
+    }
+
+    #[test]
+    fn test_legacy_payload_upgrades_to_current_schema() {
+        let legacy = json!({
+            "message": {
+                "did": "did:plc:test",
+                "kind": "commit",
+                "commit": {
+                    "operation": "delete",
+                    "collection": "app.bsky.feed.post",
+                    "rkey": "test123"
+                }
+            },
+            "processed_at": "2024-01-01T00:00:00Z",
+            "metrics": {
+                "hydration_time_ms": 0,
+                "api_calls_count": 0,
+                "cache_hit_rate": 0.0,
+                "cache_hits": 0,
+                "cache_misses": 0
+            }
+        });
+
+        let plain: EnrichedRecord = serde_json::from_value(legacy.clone()).unwrap();
+        assert_eq!(plain.schema_version, LEGACY_SCHEMA_VERSION);
+
+        let upgraded = EnrichedRecord::from_json_value(legacy).unwrap();
+        assert_eq!(upgraded.schema_version, ENRICHED_RECORD_SCHEMA_VERSION);
+        assert!(upgraded.is_delete());
+    }
+
+    #[test]
+    fn test_new_records_carry_current_schema_version() {
+        let enriched = EnrichedRecord::new(JetstreamMessage {
+            did: "did:plc:test".to_string(),
+            time_us: None,
+            seq: None,
+            kind: MessageKind::Identity,
+            commit: None,
+            identity: None,
+            account: None,
+            extra: Default::default(),
+        });
+        let value = serde_json::to_value(&enriched).unwrap();
+        assert_eq!(value["schema_version"], ENRICHED_RECORD_SCHEMA_VERSION);
+
+        let round_trip = EnrichedRecord::from_json_value(value).unwrap();
+        assert_eq!(round_trip.schema_version, ENRICHED_RECORD_SCHEMA_VERSION);
+        assert_eq!(round_trip.event, EnrichedEventKind::Record);
+    }
+
+    #[test]
+    fn test_jetstream_envelope_reads_as_jetstream_message() {
+        let mut enriched = EnrichedRecord::new(crate::fixtures::create_post_message(7));
+        enriched.hydrated_metadata.hashtags = vec!["rust".to_string()];
+
+        let json = OutputFormat::Jetstream.encode(&enriched).unwrap();
+        let message: JetstreamMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(message.did, enriched.message.did);
+        assert_eq!(
+            message.commit.unwrap().rkey,
+            enriched.message.commit.unwrap().rkey
+        );
+        // Consumers that don't know the extension see it as an unknown field
+        assert!(message.extra.contains_key(JETSTREAM_EXTENSION_KEY));
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["kind"], "commit");
+        assert_eq!(
+            value[JETSTREAM_EXTENSION_KEY]["hydrated_metadata"]["hashtags"],
+            json!(["rust"])
+        );
+        assert_eq!(value[JETSTREAM_EXTENSION_KEY]["event"], "record");
+    }
+
+    #[test]
+    fn test_encode_event_adds_event_id_to_either_format() {
+        let enriched = EnrichedRecord::new(crate::fixtures::create_post_message(7));
+        for format in [OutputFormat::Enriched, OutputFormat::Jetstream] {
+            let json = format.encode_event(&enriched, 42).unwrap();
+            let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(value["event_id"], 42);
+
+            // Otherwise the event is exactly what `encode` produces
+            value.as_object_mut().unwrap().remove("event_id");
+            let plain: serde_json::Value =
+                serde_json::from_str(&format.encode(&enriched).unwrap()).unwrap();
+            assert_eq!(value, plain);
+        }
+    }
+}
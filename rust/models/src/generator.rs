@@ -0,0 +1,597 @@
+//! Seeded generator of realistic Jetstream traffic, and canned Bluesky API
+//! responses to hydrate it against. The same seed always yields the same
+//! messages, so tests can assert on exact output without hand-writing JSON.
+//!
+//! ```
+//! use jetstream_turbo_models::generator::{get_profiles_response, MessageGenerator};
+//!
+//! let mut generator = MessageGenerator::new(7).with_users(50);
+//! let messages = generator.batch(100);
+//! let body = get_profiles_response(&[messages[0].did.as_str()]);
+//! assert_eq!(body["profiles"][0]["did"], messages[0].did.as_str());
+//! ```
+
+use crate::fixtures::create_profile;
+use crate::jetstream::{CommitData, IdentityData, JetstreamMessage, MessageKind, OperationType};
+use chrono::{DateTime, SecondsFormat};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+
+const POST_COLLECTION: &str = "app.bsky.feed.post";
+const LIKE_COLLECTION: &str = "app.bsky.feed.like";
+/// Start of generated time; matches the fixtures.
+const START_TIME_US: u64 = 1770949213790196;
+/// Posts kept around for replies, quotes, likes and deletes to point at.
+const RECENT_POSTS: usize = 1000;
+
+const TOPICS: &[&str] = &[
+    "rust",
+    "bluesky",
+    "atproto",
+    "coffee",
+    "hiking",
+    "music",
+    "books",
+    "cats",
+    "cooking",
+    "photography",
+];
+const SENTENCES: &[&str] = &[
+    "Just shipped a new feature and it feels great.",
+    "The sunset tonight was absolutely beautiful.",
+    "Great coffee, good book, perfect morning.",
+    "Working from home today with the cat on my desk.",
+    "Sometimes the simplest solutions are the best ones.",
+    "Finally got the sourdough to rise properly!",
+    "Nothing beats all tests passing on the first try.",
+    "Rainy day coding session with lo-fi beats.",
+];
+const DOMAINS: &[&str] = &["example.com", "blog.example.org", "news.example.net"];
+
+/// Relative weights of the event types `MessageGenerator::next_message` picks
+/// from. A zero weight leaves that type out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventMix {
+    pub posts: u32,
+    pub replies: u32,
+    pub quotes: u32,
+    pub likes: u32,
+    pub deletes: u32,
+    pub identities: u32,
+}
+
+impl Default for EventMix {
+    /// Roughly the shape of the live firehose for these types.
+    fn default() -> Self {
+        Self {
+            posts: 40,
+            replies: 15,
+            quotes: 5,
+            likes: 35,
+            deletes: 3,
+            identities: 2,
+        }
+    }
+}
+
+/// A post the generator created, for later events to reference.
+#[derive(Debug, Clone)]
+struct GeneratedPost {
+    did: String,
+    rkey: String,
+    cid: String,
+}
+
+impl GeneratedPost {
+    fn uri(&self) -> String {
+        format!("at://{}/{}/{}", self.did, POST_COLLECTION, self.rkey)
+    }
+
+    fn strong_ref(&self) -> Value {
+        json!({ "uri": self.uri(), "cid": self.cid })
+    }
+}
+
+pub struct MessageGenerator {
+    /// splitmix64 state
+    state: u64,
+    seq: u64,
+    time_us: u64,
+    users: usize,
+    mix: EventMix,
+    recent_posts: VecDeque<GeneratedPost>,
+}
+
+impl MessageGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+            seq: 0,
+            time_us: START_TIME_US,
+            users: 100,
+            mix: EventMix::default(),
+            recent_posts: VecDeque::new(),
+        }
+    }
+
+    /// Number of distinct accounts events are spread over; at least 1.
+    pub fn with_users(mut self, users: usize) -> Self {
+        self.users = users.max(1);
+        self
+    }
+
+    pub fn with_mix(mut self, mix: EventMix) -> Self {
+        self.mix = mix;
+        self
+    }
+
+    /// DID of generated account `index`; `create_profile` gives its profile.
+    pub fn user_did(index: usize) -> String {
+        format!("did:plc:gen{index:06}")
+    }
+
+    /// The next event, picked by the configured mix.
+    pub fn next_message(&mut self) -> JetstreamMessage {
+        let mix = self.mix;
+        let weights = [
+            mix.posts,
+            mix.replies,
+            mix.quotes,
+            mix.likes,
+            mix.deletes,
+            mix.identities,
+        ];
+        let total: u64 = weights.iter().map(|&w| u64::from(w)).sum();
+        if total == 0 {
+            return self.post();
+        }
+        let mut pick = self.below(total);
+        let mut kind = 0;
+        for (i, &weight) in weights.iter().enumerate() {
+            if pick < u64::from(weight) {
+                kind = i;
+                break;
+            }
+            pick -= u64::from(weight);
+        }
+        match kind {
+            0 => self.post(),
+            1 => self.reply(),
+            2 => self.quote(),
+            3 => self.like(),
+            4 => self.delete(),
+            _ => self.identity(),
+        }
+    }
+
+    pub fn batch(&mut self, count: usize) -> Vec<JetstreamMessage> {
+        (0..count).map(|_| self.next_message()).collect()
+    }
+
+    /// A post whose text carries a hashtag facet, and often a mention and a link.
+    pub fn post(&mut self) -> JetstreamMessage {
+        let (did, record) = self.post_record();
+        self.post_commit(did, record)
+    }
+
+    /// A reply to a recent post, or to an older one the stream never saw.
+    pub fn reply(&mut self) -> JetstreamMessage {
+        let parent = self.target_post();
+        let (did, mut record) = self.post_record();
+        record["reply"] = json!({ "root": parent.strong_ref(), "parent": parent.strong_ref() });
+        self.post_commit(did, record)
+    }
+
+    /// A post embedding a recent post as a quote.
+    pub fn quote(&mut self) -> JetstreamMessage {
+        let quoted = self.target_post();
+        let (did, mut record) = self.post_record();
+        record["embed"] = json!({
+            "$type": "app.bsky.embed.record",
+            "record": quoted.strong_ref(),
+        });
+        self.post_commit(did, record)
+    }
+
+    /// A like of a recent post.
+    pub fn like(&mut self) -> JetstreamMessage {
+        let subject = self.target_post();
+        let did = self.random_user();
+        let time_us = self.advance();
+        let rkey = self.rkey();
+        let cid = self.cid();
+        let record = json!({
+            "$type": LIKE_COLLECTION,
+            "subject": subject.strong_ref(),
+            "createdAt": created_at(time_us),
+        });
+        commit(
+            did,
+            time_us,
+            self.seq,
+            LIKE_COLLECTION,
+            rkey,
+            Some(record),
+            Some(cid),
+        )
+    }
+
+    /// Deletion of a recent post, which later events no longer reference.
+    pub fn delete(&mut self) -> JetstreamMessage {
+        let post = if self.recent_posts.is_empty() {
+            self.older_post()
+        } else {
+            let index = self.below(self.recent_posts.len() as u64) as usize;
+            self.recent_posts
+                .remove(index)
+                .expect("index is within recent posts")
+        };
+        let time_us = self.advance();
+        let mut message = commit(
+            post.did,
+            time_us,
+            self.seq,
+            POST_COLLECTION,
+            post.rkey,
+            None,
+            None,
+        );
+        if let Some(commit) = message.commit.as_mut() {
+            commit.operation_type = OperationType::Delete;
+        }
+        message
+    }
+
+    /// A handle change for one of the accounts.
+    pub fn identity(&mut self) -> JetstreamMessage {
+        let did = self.random_user();
+        let time_us = self.advance();
+        let handle = format!("{}.bsky.social", handle_stem(&did));
+        JetstreamMessage {
+            did: did.clone(),
+            time_us: Some(time_us),
+            seq: Some(self.seq),
+            kind: MessageKind::Identity,
+            commit: None,
+            identity: Some(IdentityData {
+                did,
+                handle: Some(handle),
+                seq: Some(self.seq),
+                time: Some(created_at(time_us)),
+                extra: Default::default(),
+            }),
+            account: None,
+            extra: Default::default(),
+        }
+    }
+
+    fn post_record(&mut self) -> (String, Value) {
+        let did = self.random_user();
+        let topic = TOPICS[self.below(TOPICS.len() as u64) as usize];
+        let mut text = SENTENCES[self.below(SENTENCES.len() as u64) as usize].to_string();
+        let mut facets = Vec::new();
+
+        text.push(' ');
+        let tag = format!("#{topic}");
+        facets.push(facet(
+            &text,
+            &tag,
+            json!({"$type": "app.bsky.richtext.facet#tag", "tag": topic}),
+        ));
+        text.push_str(&tag);
+
+        if self.below(3) == 0 {
+            let mentioned = self.random_user();
+            let mention = format!("@{}.bsky.social", handle_stem(&mentioned));
+            text.push_str(" cc ");
+            facets.push(facet(
+                &text,
+                &mention,
+                json!({"$type": "app.bsky.richtext.facet#mention", "did": mentioned}),
+            ));
+            text.push_str(&mention);
+        }
+        if self.below(4) == 0 {
+            let domain = DOMAINS[self.below(DOMAINS.len() as u64) as usize];
+            let link = format!("https://{domain}/{topic}/{}", self.seq + 1);
+            text.push(' ');
+            facets.push(facet(
+                &text,
+                &link,
+                json!({"$type": "app.bsky.richtext.facet#link", "uri": link}),
+            ));
+            text.push_str(&link);
+        }
+
+        let record = json!({
+            "$type": POST_COLLECTION,
+            "text": text,
+            "facets": facets,
+            "langs": ["en"],
+        });
+        (did, record)
+    }
+
+    /// Stamps `record` with its time and records the post for later events.
+    fn post_commit(&mut self, did: String, mut record: Value) -> JetstreamMessage {
+        let time_us = self.advance();
+        record["createdAt"] = Value::String(created_at(time_us));
+        let post = GeneratedPost {
+            did,
+            rkey: self.rkey(),
+            cid: self.cid(),
+        };
+        if self.recent_posts.len() == RECENT_POSTS {
+            self.recent_posts.pop_front();
+        }
+        self.recent_posts.push_back(post.clone());
+        commit(
+            post.did,
+            time_us,
+            self.seq,
+            POST_COLLECTION,
+            post.rkey,
+            Some(record),
+            Some(post.cid),
+        )
+    }
+
+    fn target_post(&mut self) -> GeneratedPost {
+        if self.recent_posts.is_empty() {
+            return self.older_post();
+        }
+        let index = self.below(self.recent_posts.len() as u64) as usize;
+        self.recent_posts[index].clone()
+    }
+
+    /// A post from before the generated stream started.
+    fn older_post(&mut self) -> GeneratedPost {
+        GeneratedPost {
+            did: self.random_user(),
+            rkey: format!("3jzfcijpj2z{:02}", self.below(100)),
+            cid: self.cid(),
+        }
+    }
+
+    fn random_user(&mut self) -> String {
+        Self::user_did(self.below(self.users as u64) as usize)
+    }
+
+    /// Moves time and sequence forward for the next event.
+    fn advance(&mut self) -> u64 {
+        self.seq += 1;
+        self.time_us += 1 + self.below(2_000);
+        self.time_us
+    }
+
+    fn rkey(&self) -> String {
+        format!("3m{:011x}", self.time_us ^ (self.seq << 44))
+    }
+
+    fn cid(&mut self) -> String {
+        format!("bafyrei{:016x}{:016x}", self.next_u64(), self.next_u64())
+    }
+
+    /// Uniform enough for test data in `0..bound`.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound.max(1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Iterator for MessageGenerator {
+    type Item = JetstreamMessage;
+
+    fn next(&mut self) -> Option<JetstreamMessage> {
+        Some(self.next_message())
+    }
+}
+
+/// Body of `app.bsky.actor.getProfiles` for `dids`, as `create_profile` would
+/// describe each one.
+pub fn get_profiles_response<S: AsRef<str>>(dids: &[S]) -> Value {
+    let profiles: Vec<Value> = dids.iter().map(|did| profile_view(did.as_ref())).collect();
+    json!({ "profiles": profiles })
+}
+
+/// Body of `app.bsky.feed.getPosts` for `uris`, each post authored by the DID
+/// in its URI. URIs that don't parse are left out, as the API leaves out posts
+/// it can't find.
+pub fn get_posts_response<S: AsRef<str>>(uris: &[S]) -> Value {
+    let posts: Vec<Value> = uris
+        .iter()
+        .filter_map(|uri| {
+            let uri = uri.as_ref();
+            let did = uri.strip_prefix("at://")?.split('/').next()?;
+            let digest = blake3::hash(uri.as_bytes()).to_hex();
+            Some(json!({
+                "uri": uri,
+                "cid": format!("bafyrei{}", &digest[..32]),
+                "author": profile_view(did),
+                "record": {
+                    "$type": POST_COLLECTION,
+                    "text": format!("Post {}", &digest[..8]),
+                    "createdAt": created_at(START_TIME_US),
+                },
+                "replyCount": 1,
+                "repostCount": 2,
+                "likeCount": 3,
+                "indexedAt": created_at(START_TIME_US),
+            }))
+        })
+        .collect();
+    json!({ "posts": posts })
+}
+
+/// `app.bsky.actor.defs#profileViewDetailed` for `did`.
+fn profile_view(did: &str) -> Value {
+    let profile = create_profile(did);
+    json!({
+        "did": did,
+        "handle": profile.handle,
+        "displayName": profile.display_name,
+        "description": profile.description,
+        "avatar": profile.avatar,
+        "followersCount": profile.followers_count,
+        "followsCount": profile.follows_count,
+        "postsCount": profile.posts_count,
+    })
+}
+
+fn commit(
+    did: String,
+    time_us: u64,
+    seq: u64,
+    collection: &str,
+    rkey: String,
+    record: Option<Value>,
+    cid: Option<String>,
+) -> JetstreamMessage {
+    JetstreamMessage {
+        did,
+        time_us: Some(time_us),
+        seq: Some(seq),
+        kind: MessageKind::Commit,
+        commit: Some(CommitData {
+            rev: Some(format!("3m{time_us:011x}")),
+            operation_type: OperationType::Create,
+            collection: Some(collection.to_string()),
+            rkey: Some(rkey),
+            record,
+            cid,
+            extra: Default::default(),
+        }),
+        identity: None,
+        account: None,
+        extra: Default::default(),
+    }
+}
+
+/// A facet covering `span` once it's appended to `text`.
+fn facet(text: &str, span: &str, feature: Value) -> Value {
+    json!({
+        "index": {"byteStart": text.len(), "byteEnd": text.len() + span.len()},
+        "features": [feature],
+    })
+}
+
+/// The part of a DID `create_profile` builds the handle from.
+fn handle_stem(did: &str) -> &str {
+    did.strip_prefix("did:plc:").unwrap_or("unknown")
+}
+
+fn created_at(time_us: u64) -> String {
+    DateTime::from_timestamp_micros(time_us as i64)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluesky::{GetPostsBulkResponse, GetProfilesResponse};
+    use crate::records::TypedRecord;
+
+    #[test]
+    fn test_same_seed_generates_same_messages() {
+        let first = serde_json::to_value(MessageGenerator::new(42).batch(200)).unwrap();
+        let second = serde_json::to_value(MessageGenerator::new(42).batch(200)).unwrap();
+        let other = serde_json::to_value(MessageGenerator::new(43).batch(200)).unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_mix_covers_every_event_type() {
+        let messages = MessageGenerator::new(1).with_users(10).batch(500);
+        let count = |pred: &dyn Fn(&JetstreamMessage) -> bool| {
+            messages.iter().filter(|message| pred(message)).count()
+        };
+        let record = |message: &JetstreamMessage, field: &str| {
+            message
+                .commit
+                .as_ref()
+                .and_then(|commit| commit.record.as_ref())
+                .is_some_and(|record| record.get(field).is_some())
+        };
+
+        assert!(count(&|m| record(m, "reply")) > 0);
+        assert!(count(&|m| record(m, "embed")) > 0);
+        assert!(count(&|m| m.kind == MessageKind::Identity) > 0);
+        assert!(
+            count(&|m| m
+                .commit
+                .as_ref()
+                .is_some_and(|c| c.operation_type == OperationType::Delete))
+                > 0
+        );
+        let likes: Vec<_> = messages
+            .iter()
+            .filter_map(|m| m.commit.as_ref()?.typed_record())
+            .filter(|record| matches!(record, TypedRecord::Like(_)))
+            .collect();
+        assert!(!likes.is_empty());
+        assert!(messages
+            .windows(2)
+            .all(|pair| pair[0].time_us < pair[1].time_us));
+        assert!(messages
+            .iter()
+            .all(|m| m.did.starts_with("did:plc:gen00000")));
+    }
+
+    #[test]
+    fn test_facets_point_at_their_spans() {
+        for message in MessageGenerator::new(9)
+            .with_mix(EventMix {
+                posts: 1,
+                replies: 0,
+                quotes: 0,
+                likes: 0,
+                deletes: 0,
+                identities: 0,
+            })
+            .take(50)
+        {
+            let record = message.commit.unwrap().record.unwrap();
+            let text = record["text"].as_str().unwrap();
+            for facet in record["facets"].as_array().unwrap() {
+                let start = facet["index"]["byteStart"].as_u64().unwrap() as usize;
+                let end = facet["index"]["byteEnd"].as_u64().unwrap() as usize;
+                let span = &text[start..end];
+                let feature = &facet["features"][0];
+                match feature["$type"].as_str().unwrap() {
+                    "app.bsky.richtext.facet#tag" => {
+                        assert_eq!(span, format!("#{}", feature["tag"].as_str().unwrap()))
+                    }
+                    "app.bsky.richtext.facet#link" => assert_eq!(span, feature["uri"]),
+                    _ => assert!(span.starts_with('@')),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_canned_responses_parse_as_api_responses() {
+        let dids = ["did:plc:gen000001", "did:plc:gen000002"];
+        let profiles: GetProfilesResponse =
+            serde_json::from_value(get_profiles_response(&dids)).unwrap();
+        assert_eq!(profiles.profiles.len(), 2);
+        assert_eq!(profiles.profiles[1].handle, "gen000002.bsky.social");
+
+        let uris = [
+            "at://did:plc:gen000001/app.bsky.feed.post/3mabc",
+            "not a uri",
+        ];
+        let posts: GetPostsBulkResponse =
+            serde_json::from_value(get_posts_response(&uris)).unwrap();
+        assert_eq!(posts.posts.len(), 1);
+        assert_eq!(posts.posts[0].author.did.as_ref(), "did:plc:gen000001");
+    }
+}
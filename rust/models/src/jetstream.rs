@@ -0,0 +1,769 @@
+use crate::at_uri::AtUri;
+use crate::records::{TypedRecord, POST_COLLECTION};
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::fmt;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageKind {
+    Commit,
+    Identity,
+    Account,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl Serialize for MessageKind {
+    #[inline(always)]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            MessageKind::Commit => "commit",
+            MessageKind::Identity => "identity",
+            MessageKind::Account => "account",
+            MessageKind::Unknown => "unknown",
+        })
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationType {
+    Create,
+    Update,
+    Delete,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl OperationType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationType::Create => "create",
+            OperationType::Update => "update",
+            OperationType::Delete => "delete",
+            OperationType::Unknown => "unknown",
+        }
+    }
+}
+
+impl Serialize for OperationType {
+    #[inline(always)]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// A Jetstream event. Parsing is tolerant of upstream schema changes: fields this
+/// model doesn't know are kept in `extra` (and re-serialized unchanged), and a missing
+/// or unrecognized `kind` becomes `MessageKind::Unknown` instead of failing the frame.
+///
+/// The event types deserialize through hand-written map visitors rather than
+/// `#[serde(flatten)]`, which would buffer every frame into serde's generic
+/// content tree before matching a single field.
+#[derive(Debug, Clone, Serialize)]
+pub struct JetstreamMessage {
+    pub did: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    pub kind: MessageKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<CommitData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<IdentityData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<AccountData>,
+    /// Fields not modeled above, preserved as received
+    #[serde(flatten, skip_serializing_if = "Map::is_empty")]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    #[serde(rename = "operation")]
+    pub operation_type: OperationType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rkey: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>,
+    #[serde(flatten, skip_serializing_if = "Map::is_empty")]
+    pub extra: Map<String, Value>,
+}
+
+impl CommitData {
+    /// Typed view of `record` for likes, reposts, follows, blocks and list items.
+    pub fn typed_record(&self) -> Option<TypedRecord> {
+        TypedRecord::parse(self.collection.as_deref()?, self.record.as_ref()?)
+    }
+}
+
+/// Payload of a `kind: "identity"` event (handle or DID document change).
+#[derive(Debug, Clone, Serialize)]
+pub struct IdentityData {
+    pub did: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    #[serde(flatten, skip_serializing_if = "Map::is_empty")]
+    pub extra: Map<String, Value>,
+}
+
+/// Payload of a `kind: "account"` event (activation, deactivation, takedown).
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountData {
+    pub did: String,
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    #[serde(flatten, skip_serializing_if = "Map::is_empty")]
+    pub extra: Map<String, Value>,
+}
+
+/// An object key, borrowed from the input whenever the deserializer allows, so
+/// matching known fields doesn't allocate.
+struct Key<'de>(Cow<'de, str>);
+
+impl<'de> Deserialize<'de> for Key<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = Key<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a field name")
+            }
+
+            fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Key<'de>, E> {
+                Ok(Key(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Key<'de>, E> {
+                Ok(Key(Cow::Owned(v.to_string())))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Key<'de>, E> {
+                Ok(Key(Cow::Owned(v)))
+            }
+        }
+
+        deserializer.deserialize_str(KeyVisitor)
+    }
+}
+
+/// Accepts a number or a numeric string; any other shape becomes `None` rather than
+/// failing the whole message.
+struct LenientU64(Option<u64>);
+
+impl<'de> Deserialize<'de> for LenientU64 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(LenientU64(match Value::deserialize(deserializer)? {
+            Value::Number(n) => n.as_u64(),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }))
+    }
+}
+
+/// Implements `Deserialize` for an event struct as a single pass over its map:
+/// each listed key is read straight into its field, anything else lands in
+/// `extra`, and missing fields fall back to their defaults.
+macro_rules! deserialize_event {
+    (
+        $ty:ident, $expecting:literal,
+        required { $($req:ident: $req_ty:ty = $req_key:literal),* $(,)? },
+        optional { $($opt:ident: $opt_ty:ty = $opt_key:literal),* $(,)? },
+        lenient { $($len:ident = $len_key:literal),* $(,)? }
+    ) => {
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct EventVisitor;
+
+                impl<'de> Visitor<'de> for EventVisitor {
+                    type Value = $ty;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str($expecting)
+                    }
+
+                    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<$ty, A::Error> {
+                        $(let mut $req: Option<$req_ty> = None;)*
+                        $(let mut $opt: Option<$opt_ty> = None;)*
+                        $(let mut $len: Option<u64> = None;)*
+                        let mut extra = Map::new();
+
+                        while let Some(Key(key)) = map.next_key()? {
+                            match key.as_ref() {
+                                $($req_key => $req = Some(map.next_value()?),)*
+                                $($opt_key => $opt = map.next_value()?,)*
+                                $($len_key => $len = map.next_value::<LenientU64>()?.0,)*
+                                _ => {
+                                    extra.insert(key.into_owned(), map.next_value()?);
+                                }
+                            }
+                        }
+
+                        Ok($ty {
+                            $($req: $req.ok_or_else(|| de::Error::missing_field($req_key))?,)*
+                            $($opt: $opt.unwrap_or_default(),)*
+                            $($len,)*
+                            extra,
+                        })
+                    }
+                }
+
+                deserializer.deserialize_map(EventVisitor)
+            }
+        }
+    };
+}
+
+deserialize_event!(
+    JetstreamMessage, "a Jetstream event",
+    required { did: String = "did" },
+    optional {
+        kind: MessageKind = "kind",
+        commit: Option<CommitData> = "commit",
+        identity: Option<IdentityData> = "identity",
+        account: Option<AccountData> = "account",
+    },
+    lenient { time_us = "time_us", seq = "seq" }
+);
+
+deserialize_event!(
+    CommitData, "a commit payload",
+    required {},
+    optional {
+        rev: Option<String> = "rev",
+        operation_type: OperationType = "operation",
+        collection: Option<String> = "collection",
+        rkey: Option<String> = "rkey",
+        record: Option<Value> = "record",
+        cid: Option<String> = "cid",
+    },
+    lenient {}
+);
+
+deserialize_event!(
+    IdentityData, "an identity payload",
+    required { did: String = "did" },
+    optional {
+        handle: Option<String> = "handle",
+        time: Option<String> = "time",
+    },
+    lenient { seq = "seq" }
+);
+
+deserialize_event!(
+    AccountData, "an account payload",
+    required { did: String = "did", active: bool = "active" },
+    optional {
+        status: Option<String> = "status",
+        time: Option<String> = "time",
+    },
+    lenient { seq = "seq" }
+);
+
+impl JetstreamMessage {
+    #[inline(always)]
+    pub fn extract_at_uri(&self) -> Option<String> {
+        if let Some(commit) = &self.commit {
+            if let (Some(collection), Some(rkey)) = (&commit.collection, &commit.rkey) {
+                let mut uri = String::with_capacity(
+                    "at://".len() + self.did.len() + collection.len() + rkey.len() + 2,
+                );
+                uri.push_str("at://");
+                uri.push_str(&self.did);
+                uri.push('/');
+                uri.push_str(collection);
+                uri.push('/');
+                uri.push_str(rkey);
+                return Some(uri);
+            }
+        }
+        None
+    }
+
+    /// Parsed AT-URI of the commit's record, for routing on collection or rkey.
+    pub fn at_uri(&self) -> Option<AtUri> {
+        let commit = self.commit.as_ref()?;
+        match (&commit.collection, &commit.rkey) {
+            (Some(collection), Some(rkey)) => Some(AtUri::new(&self.did, collection, rkey)),
+            _ => None,
+        }
+    }
+
+    /// Dotted names of fields this model doesn't recognize, e.g. `commit.foo`.
+    pub fn unknown_fields(&self) -> Vec<String> {
+        let nested = [
+            ("commit", self.commit.as_ref().map(|c| &c.extra)),
+            ("identity", self.identity.as_ref().map(|i| &i.extra)),
+            ("account", self.account.as_ref().map(|a| &a.extra)),
+        ];
+        let mut fields: Vec<String> = self.extra.keys().cloned().collect();
+        for (prefix, extra) in nested {
+            fields.extend(
+                extra
+                    .into_iter()
+                    .flat_map(Map::keys)
+                    .map(|k| format!("{prefix}.{k}")),
+            );
+        }
+        fields
+    }
+
+    #[inline(always)]
+    pub fn extract_did(&self) -> &str {
+        &self.did
+    }
+
+    pub fn is_create_operation(&self) -> bool {
+        if let Some(commit) = &self.commit {
+            return commit.operation_type == OperationType::Create;
+        }
+        false
+    }
+
+    pub fn typed_record(&self) -> Option<TypedRecord> {
+        self.commit.as_ref().and_then(CommitData::typed_record)
+    }
+
+    pub fn is_delete_operation(&self) -> bool {
+        if let Some(commit) = &self.commit {
+            return commit.operation_type == OperationType::Delete;
+        }
+        false
+    }
+
+    pub fn extract_mentioned_dids(&self) -> Vec<&str> {
+        let mut mentioned_dids = Vec::new();
+
+        if let Some(commit) = &self.commit {
+            if let Some(record) = &commit.record {
+                // Extract from reply references
+                if let Some(reply) = record.get("reply") {
+                    if let Some(parent) = reply.get("parent") {
+                        if let Some(uri) = parent.get("uri").and_then(|u| u.as_str()) {
+                            if let Some((did, _, _)) = AtUri::components(uri) {
+                                mentioned_dids.push(did);
+                            }
+                        }
+                    }
+                    if let Some(root) = reply.get("root") {
+                        if let Some(uri) = root.get("uri").and_then(|u| u.as_str()) {
+                            if let Some((did, _, _)) = AtUri::components(uri) {
+                                mentioned_dids.push(did);
+                            }
+                        }
+                    }
+                }
+
+                // Extract from facets (mentions)
+                if let Some(facets) = record.get("facets").and_then(|f| f.as_array()) {
+                    for facet in facets {
+                        if let Some(features) = facet.get("features").and_then(|f| f.as_array()) {
+                            for feature in features {
+                                // Check for mention features
+                                if let Some(did) = feature.get("did").and_then(|d| d.as_str()) {
+                                    mentioned_dids.push(did);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Extract from embeds (quotes)
+                if let Some(uri) = record
+                    .get("embed")
+                    .and_then(quoted_record_uri)
+                    .and_then(|u| u.as_str())
+                {
+                    if let Some((did, _, _)) = AtUri::components(uri) {
+                        mentioned_dids.push(did);
+                    }
+                }
+            }
+        }
+
+        mentioned_dids.retain(|did| did.starts_with("did:plc:"));
+        mentioned_dids.dedup();
+        mentioned_dids
+    }
+
+    /// CIDs of images embedded in the record, directly or alongside a quote.
+    pub fn extract_image_blob_cids(&self) -> Vec<String> {
+        let Some(embed) = self
+            .commit
+            .as_ref()
+            .and_then(|commit| commit.record.as_ref())
+            .and_then(|record| record.get("embed"))
+        else {
+            return Vec::new();
+        };
+
+        let images = embed
+            .get("images")
+            .or_else(|| embed.get("media").and_then(|media| media.get("images")))
+            .and_then(|images| images.as_array());
+        images
+            .into_iter()
+            .flatten()
+            .filter_map(|image| image.pointer("/image/ref/$link")?.as_str())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// AT-URIs of the posts this record points at: its reply parent and root,
+    /// and the quoted post of a record embed, with or without media. Quotes of
+    /// feeds, lists and other non-post records are left out, since they can't be
+    /// fetched as posts.
+    pub fn extract_post_uris(&self) -> Vec<String> {
+        let Some(record) = self
+            .commit
+            .as_ref()
+            .and_then(|commit| commit.record.as_ref())
+        else {
+            return Vec::new();
+        };
+
+        let reply = record.get("reply");
+        let candidates = [
+            reply.and_then(|reply| reply.pointer("/parent/uri")),
+            reply.and_then(|reply| reply.pointer("/root/uri")),
+            record.get("embed").and_then(quoted_record_uri),
+        ];
+
+        let mut uris: Vec<String> = Vec::with_capacity(candidates.len());
+        for uri in candidates.into_iter().flatten().filter_map(Value::as_str) {
+            let is_post = AtUri::components(uri)
+                .is_some_and(|(_, collection, _)| collection == POST_COLLECTION);
+            if is_post && !uris.iter().any(|seen| seen == uri) {
+                uris.push(uri.to_string());
+            }
+        }
+        uris
+    }
+}
+
+/// URI of the record quoted by an `app.bsky.embed.record` embed, or by the
+/// record half of an `app.bsky.embed.recordWithMedia` one.
+fn quoted_record_uri(embed: &Value) -> Option<&Value> {
+    let record = embed.get("record")?;
+    record.get("uri").or_else(|| record.pointer("/record/uri"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jetstream_message_parsing() {
+        let json_str = r#"
+        {
+            "did": "did:plc:test",
+            "time_us": 1770949213790196,
+            "kind": "commit",
+            "commit": {
+                "rev": "3mepgzgimkv23",
+                "operation": "create",
+                "collection": "app.bsky.feed.post",
+                "rkey": "3mepgzgiatv23",
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "createdAt": "2026-02-13T02:20:02.89585500Z",
+                    "text": "Hello world"
+                },
+                "cid": "bafyreiassbuahzdwy64xwlefqcwh6zk4stb4lhht24oozhxn3fhzomrxg4"
+            }
+        }
+        "#;
+
+        let message: JetstreamMessage = serde_json::from_str(json_str).unwrap();
+        assert_eq!(message.did, "did:plc:test");
+        assert!(message.is_create_operation());
+        assert_eq!(
+            message.extract_at_uri(),
+            Some("at://did:plc:test/app.bsky.feed.post/3mepgzgiatv23".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_are_kept_at_every_level() {
+        let json_str = r#"
+        {
+            "did": "did:plc:test",
+            "time_us": "1770949213790196",
+            "kind": "commit",
+            "region": "us-east",
+            "commit": {
+                "operation": "create",
+                "collection": "app.bsky.feed.post",
+                "rkey": "3mepgzgiatv23",
+                "record": {"text": "Hello world"},
+                "prev": null
+            },
+            "account": {"did": "did:plc:test", "active": true, "reason": "migrated"},
+            "tag\u0073": ["a"]
+        }
+        "#;
+
+        let message: JetstreamMessage = serde_json::from_str(json_str).unwrap();
+        assert_eq!(message.time_us, Some(1770949213790196));
+        assert_eq!(
+            message.unknown_fields(),
+            vec!["region", "tags", "commit.prev", "account.reason"]
+        );
+
+        let round_trip: Value = serde_json::to_value(&message).unwrap();
+        assert_eq!(round_trip["region"], "us-east");
+        assert_eq!(round_trip["tags"], serde_json::json!(["a"]));
+        assert!(round_trip["commit"]["prev"].is_null());
+        assert!(round_trip["commit"].get("prev").is_some());
+        assert_eq!(round_trip["account"]["reason"], "migrated");
+
+        let missing_did = r#"{"time_us": 1, "kind": "commit"}"#;
+        let error = serde_json::from_str::<JetstreamMessage>(missing_did).unwrap_err();
+        assert!(error.to_string().contains("missing field `did`"));
+    }
+
+    #[test]
+    fn test_extract_mentioned_dids() {
+        let json_str = r#"
+        {
+            "did": "did:plc:author",
+            "time_us": 1770949213800000,
+            "kind": "commit",
+            "commit": {
+                "operation": "create",
+                "collection": "app.bsky.feed.post",
+                "rkey": "test123",
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "text": "Test",
+                    "reply": {
+                        "parent": {
+                            "cid": "bafyrei...",
+                            "uri": "at://did:plc:parent123/app.bsky.feed.post/parent456"
+                        },
+                        "root": {
+                            "cid": "bafyrei...",
+                            "uri": "at://did:plc:root789/app.bsky.feed.post/root000"
+                        }
+                    }
+                }
+            }
+        }
+        "#;
+
+        let message: JetstreamMessage = serde_json::from_str(json_str).unwrap();
+        let mentioned = message.extract_mentioned_dids();
+        assert!(mentioned.contains(&"did:plc:parent123"));
+        assert!(mentioned.contains(&"did:plc:root789"));
+    }
+
+    #[test]
+    fn test_extract_post_uris_from_reply_and_quote_with_media() {
+        let json_str = r#"
+        {
+            "did": "did:plc:eygmaihciaxprqvxpfvl6flk",
+            "time_us": 1725911162329308,
+            "kind": "commit",
+            "commit": {
+                "rev": "3l3qo2vutsw2b",
+                "operation": "create",
+                "collection": "app.bsky.feed.post",
+                "rkey": "3l3qo2vuowo2b",
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "createdAt": "2024-09-09T19:46:02.102Z",
+                    "langs": ["en"],
+                    "text": "same, look at this",
+                    "reply": {
+                        "parent": {
+                            "cid": "bafyreidc6sydkkbchcyg62v77wbhzvb2mvytlmsychqgwf2xojjtirmzj4",
+                            "uri": "at://did:plc:wa7b35aakoll7hugkrjtf3xf/app.bsky.feed.post/3l3qkdsjtjm2s"
+                        },
+                        "root": {
+                            "cid": "bafyreidc6sydkkbchcyg62v77wbhzvb2mvytlmsychqgwf2xojjtirmzj4",
+                            "uri": "at://did:plc:wa7b35aakoll7hugkrjtf3xf/app.bsky.feed.post/3l3qkdsjtjm2s"
+                        }
+                    },
+                    "embed": {
+                        "$type": "app.bsky.embed.recordWithMedia",
+                        "media": {
+                            "$type": "app.bsky.embed.images",
+                            "images": [{
+                                "alt": "",
+                                "image": {
+                                    "$type": "blob",
+                                    "ref": {"$link": "bafkreifjl7ltjc4b5dxp5epxfhxomvfuxxkwivscjrgmi2qmuiq3ah4vky"},
+                                    "mimeType": "image/jpeg",
+                                    "size": 301766
+                                }
+                            }]
+                        },
+                        "record": {
+                            "$type": "app.bsky.embed.record",
+                            "record": {
+                                "cid": "bafyreih4ggrqcxspbvhjvjhoqvnbpirnspxgbfykm5dvtmoofvr72blzju",
+                                "uri": "at://did:plc:z72i7hdynmk6r22z27h6tvur/app.bsky.feed.post/3l3pte3p2e325"
+                            }
+                        }
+                    }
+                },
+                "cid": "bafyreiblfugmp3wqbrkmkqfqykb4rqqbgfaqkzayzxppk7syyfnfgz3xwi"
+            }
+        }
+        "#;
+
+        let message: JetstreamMessage = serde_json::from_str(json_str).unwrap();
+        assert_eq!(
+            message.extract_post_uris(),
+            vec![
+                "at://did:plc:wa7b35aakoll7hugkrjtf3xf/app.bsky.feed.post/3l3qkdsjtjm2s",
+                "at://did:plc:z72i7hdynmk6r22z27h6tvur/app.bsky.feed.post/3l3pte3p2e325",
+            ]
+        );
+        assert!(message
+            .extract_mentioned_dids()
+            .contains(&"did:plc:z72i7hdynmk6r22z27h6tvur"));
+    }
+
+    #[test]
+    fn test_extract_post_uris_skips_quoted_feeds() {
+        let json_str = r#"
+        {
+            "did": "did:plc:author",
+            "time_us": 1725911162329308,
+            "kind": "commit",
+            "commit": {
+                "operation": "create",
+                "collection": "app.bsky.feed.post",
+                "rkey": "3l3qo2vuowo2b",
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "createdAt": "2024-09-09T19:46:02.102Z",
+                    "text": "new feed just dropped",
+                    "embed": {
+                        "$type": "app.bsky.embed.record",
+                        "record": {
+                            "cid": "bafyreie3ccw3zfjsjwmrfjqb3xa3mtqo5zv6xgqxpfjrdq3aqmkdmnxlhu",
+                            "uri": "at://did:plc:z72i7hdynmk6r22z27h6tvur/app.bsky.feed.generator/whats-hot"
+                        }
+                    }
+                }
+            }
+        }
+        "#;
+
+        let message: JetstreamMessage = serde_json::from_str(json_str).unwrap();
+        assert!(message.extract_post_uris().is_empty());
+    }
+
+    #[test]
+    fn test_extract_image_blob_cids() {
+        let json_str = r#"
+        {
+            "did": "did:plc:author",
+            "time_us": 1770949213800000,
+            "kind": "commit",
+            "commit": {
+                "operation": "create",
+                "collection": "app.bsky.feed.post",
+                "rkey": "test123",
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "text": "Quoting with a picture",
+                    "embed": {
+                        "$type": "app.bsky.embed.recordWithMedia",
+                        "record": {"$type": "app.bsky.embed.record", "record": {}},
+                        "media": {
+                            "$type": "app.bsky.embed.images",
+                            "images": [{
+                                "alt": "",
+                                "image": {
+                                    "$type": "blob",
+                                    "ref": {"$link": "bafkreiimage"},
+                                    "mimeType": "image/jpeg",
+                                    "size": 1234
+                                }
+                            }]
+                        }
+                    }
+                }
+            }
+        }
+        "#;
+
+        let message: JetstreamMessage = serde_json::from_str(json_str).unwrap();
+        assert_eq!(message.extract_image_blob_cids(), vec!["bafkreiimage"]);
+    }
+
+    #[test]
+    fn test_identity_and_account_events_parse() {
+        let identity_json = r#"
+        {
+            "did": "did:plc:ident",
+            "time_us": 1725516665234703,
+            "kind": "identity",
+            "identity": {
+                "did": "did:plc:ident",
+                "handle": "ident.bsky.social",
+                "seq": 1409752997,
+                "time": "2024-09-05T06:11:04.870Z"
+            }
+        }
+        "#;
+        let message: JetstreamMessage = serde_json::from_str(identity_json).unwrap();
+        assert_eq!(message.kind, MessageKind::Identity);
+        assert!(message.commit.is_none());
+        let identity = message.identity.as_ref().unwrap();
+        assert_eq!(identity.handle.as_deref(), Some("ident.bsky.social"));
+        assert_eq!(message.extract_at_uri(), None);
+
+        let account_json = r#"
+        {
+            "did": "did:plc:acct",
+            "time_us": 1725516665333808,
+            "kind": "account",
+            "account": {
+                "active": false,
+                "did": "did:plc:acct",
+                "seq": 1409753013,
+                "status": "deactivated",
+                "time": "2024-09-05T06:11:04.870Z"
+            }
+        }
+        "#;
+        let message: JetstreamMessage = serde_json::from_str(account_json).unwrap();
+        assert_eq!(message.kind, MessageKind::Account);
+        let account = message.account.as_ref().unwrap();
+        assert!(!account.active);
+        assert_eq!(account.status.as_deref(), Some("deactivated"));
+
+        let round_trip = serde_json::to_string(&message).unwrap();
+        assert!(round_trip.contains(r#""account":{"did":"did:plc:acct","active":false"#));
+        assert!(!round_trip.contains("commit"));
+    }
+}
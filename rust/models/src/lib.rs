@@ -0,0 +1,21 @@
+//! The types jetstream-turbo reads from Jetstream and emits on its Redis
+//! stream and WebSocket: `JetstreamMessage`, `EnrichedRecord` and the Bluesky
+//! records they carry. No networking or storage dependencies, so the crate
+//! also builds for `wasm32-unknown-unknown` and clients can deserialize the
+//! server's output with the same definitions.
+
+pub mod at_uri;
+pub mod bluesky;
+pub mod enriched;
+pub mod jetstream;
+pub mod records;
+pub mod text;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod fixtures;
+#[cfg(any(test, feature = "testing"))]
+pub mod generator;
+
+pub use at_uri::{AtUri, InvalidAtUri};
+pub use enriched::EnrichedRecord;
+pub use jetstream::JetstreamMessage;
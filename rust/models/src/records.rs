@@ -0,0 +1,167 @@
+use crate::at_uri::AtUri;
+use serde::{Deserialize, Serialize};
+
+pub const POST_COLLECTION: &str = "app.bsky.feed.post";
+pub const LIKE_COLLECTION: &str = "app.bsky.feed.like";
+pub const REPOST_COLLECTION: &str = "app.bsky.feed.repost";
+pub const FOLLOW_COLLECTION: &str = "app.bsky.graph.follow";
+pub const BLOCK_COLLECTION: &str = "app.bsky.graph.block";
+pub const LIST_ITEM_COLLECTION: &str = "app.bsky.graph.listitem";
+
+/// `com.atproto.repo.strongRef`: a record URI pinned to a specific CID.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StrongRef {
+    pub uri: String,
+    pub cid: String,
+}
+
+impl StrongRef {
+    pub fn did(&self) -> Option<&str> {
+        AtUri::components(&self.uri).map(|(did, _, _)| did)
+    }
+
+    pub fn at_uri(&self) -> Option<AtUri> {
+        AtUri::parse(&self.uri)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LikeRecord {
+    pub subject: StrongRef,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RepostRecord {
+    pub subject: StrongRef,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FollowRecord {
+    /// DID of the followed account
+    pub subject: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BlockRecord {
+    /// DID of the blocked account
+    pub subject: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ListItemRecord {
+    /// DID of the account added to the list
+    pub subject: String,
+    /// AT-URI of the `app.bsky.graph.list` record
+    pub list: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+/// Typed view over the common non-post collections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypedRecord {
+    Like(LikeRecord),
+    Repost(RepostRecord),
+    Follow(FollowRecord),
+    Block(BlockRecord),
+    ListItem(ListItemRecord),
+}
+
+impl TypedRecord {
+    /// Parses `record` according to `collection`. Returns `None` for collections without a
+    /// typed model or records that don't match the lexicon shape.
+    pub fn parse(collection: &str, record: &serde_json::Value) -> Option<Self> {
+        match collection {
+            LIKE_COLLECTION => LikeRecord::deserialize(record).ok().map(Self::Like),
+            REPOST_COLLECTION => RepostRecord::deserialize(record).ok().map(Self::Repost),
+            FOLLOW_COLLECTION => FollowRecord::deserialize(record).ok().map(Self::Follow),
+            BLOCK_COLLECTION => BlockRecord::deserialize(record).ok().map(Self::Block),
+            LIST_ITEM_COLLECTION => ListItemRecord::deserialize(record).ok().map(Self::ListItem),
+            _ => None,
+        }
+    }
+
+    /// DID of the account this record points at.
+    pub fn subject_did(&self) -> Option<&str> {
+        match self {
+            TypedRecord::Like(like) => like.subject.did(),
+            TypedRecord::Repost(repost) => repost.subject.did(),
+            TypedRecord::Follow(follow) => Some(&follow.subject),
+            TypedRecord::Block(block) => Some(&block.subject),
+            TypedRecord::ListItem(item) => Some(&item.subject),
+        }
+    }
+
+    /// AT-URI of the record this record points at, for likes and reposts.
+    pub fn subject_uri(&self) -> Option<&str> {
+        match self {
+            TypedRecord::Like(like) => Some(&like.subject.uri),
+            TypedRecord::Repost(repost) => Some(&repost.subject.uri),
+            TypedRecord::Follow(_) | TypedRecord::Block(_) | TypedRecord::ListItem(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_like_and_follow() {
+        let like = TypedRecord::parse(
+            LIKE_COLLECTION,
+            &json!({
+                "$type": "app.bsky.feed.like",
+                "subject": {
+                    "uri": "at://did:plc:author/app.bsky.feed.post/3k2a",
+                    "cid": "bafyreilike"
+                },
+                "createdAt": "2024-01-01T00:00:00.000Z"
+            }),
+        )
+        .unwrap();
+        assert!(matches!(like, TypedRecord::Like(_)));
+        assert_eq!(like.subject_did(), Some("did:plc:author"));
+        assert_eq!(
+            like.subject_uri(),
+            Some("at://did:plc:author/app.bsky.feed.post/3k2a")
+        );
+
+        let follow = TypedRecord::parse(
+            FOLLOW_COLLECTION,
+            &json!({
+                "$type": "app.bsky.graph.follow",
+                "subject": "did:plc:followed",
+                "createdAt": "2024-01-01T00:00:00.000Z"
+            }),
+        )
+        .unwrap();
+        assert_eq!(follow.subject_did(), Some("did:plc:followed"));
+        assert_eq!(follow.subject_uri(), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_or_malformed() {
+        assert!(TypedRecord::parse(POST_COLLECTION, &json!({"text": "hi"})).is_none());
+        assert!(TypedRecord::parse(LIKE_COLLECTION, &json!({"subject": "did:plc:x"})).is_none());
+
+        let item = TypedRecord::parse(
+            LIST_ITEM_COLLECTION,
+            &json!({
+                "subject": "did:plc:member",
+                "list": "at://did:plc:owner/app.bsky.graph.list/3abc",
+                "createdAt": "2024-01-01T00:00:00.000Z"
+            }),
+        );
+        assert!(matches!(item, Some(TypedRecord::ListItem(ref i)) if i.list.ends_with("3abc")));
+    }
+}
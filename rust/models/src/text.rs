@@ -0,0 +1,89 @@
+//! Unicode-aware normalization and word segmentation for hashtags and keyword
+//! matching, so `#Ｒｕｓｔ`, `#RUST` and `#rust` are the same tag and text in
+//! scripts written without spaces can still be searched by word.
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// `text` in NFKC with full case folding: fullwidth and compatibility forms
+/// become their plain equivalents and case differences disappear, including
+/// ones plain lowercasing misses such as `ß` and `ss`.
+pub fn normalize(text: &str) -> String {
+    let composed: String = text.nfkc().collect();
+    caseless::default_case_fold_str(&composed).nfkc().collect()
+}
+
+/// A hashtag as stored and compared: normalized, without the leading `#`.
+/// `None` when nothing is left.
+pub fn normalize_hashtag(tag: &str) -> Option<String> {
+    let tag = normalize(tag);
+    let tag = tag.trim_start_matches('#');
+    (!tag.is_empty()).then(|| tag.to_string())
+}
+
+/// The words of `text` by Unicode word boundaries (UAX #29), skipping spaces
+/// and punctuation. Each Han ideograph and hiragana character is a word on
+/// its own, so CJK text without spaces still splits.
+pub fn words(text: &str) -> Vec<&str> {
+    text.unicode_words().collect()
+}
+
+/// Whether the words of `phrase` occur consecutively in `text_words`, the
+/// `words` of already normalized text. Whole words only, so `rust` doesn't
+/// match `trust`, but a CJK phrase matches inside a longer run of ideographs.
+pub fn contains_phrase(text_words: &[&str], phrase: &str) -> bool {
+    let phrase = normalize(phrase);
+    contains_words(text_words, &words(&phrase))
+}
+
+/// `contains_phrase` for a phrase already normalized and split with `words`,
+/// so a phrase checked against many texts is only normalized once.
+pub fn contains_words<S: AsRef<str>>(text_words: &[&str], phrase_words: &[S]) -> bool {
+    !phrase_words.is_empty()
+        && text_words.windows(phrase_words.len()).any(|window| {
+            window
+                .iter()
+                .zip(phrase_words)
+                .all(|(word, want)| *word == want.as_ref())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_folds_width_and_case() {
+        assert_eq!(normalize("Ｒｕｓｔ"), "rust");
+        assert_eq!(normalize("STRASSE"), normalize("straße"));
+        assert_eq!(normalize("ΌΣΟΣ"), normalize("όσος"));
+        // Halfwidth katakana composes to the fullwidth form
+        assert_eq!(normalize("ﾗｰﾒﾝ"), "ラーメン");
+        assert_eq!(normalize_hashtag("＃日本語").as_deref(), Some("日本語"));
+        assert_eq!(normalize_hashtag("#"), None);
+    }
+
+    fn contains(text: &str, phrase: &str) -> bool {
+        let text = normalize(text);
+        contains_phrase(&words(&text), phrase)
+    }
+
+    #[test]
+    fn test_phrases_match_whole_words_in_any_script() {
+        assert!(contains("Learning Rust today", "rust"));
+        assert!(!contains("In crabs we trust", "rust"));
+        assert!(contains("the BORROW, checker", "borrow checker"));
+
+        // Ideographs are words on their own, so a phrase matches mid-sentence
+        assert!(contains("今日は日本語を勉強しました", "日本語"));
+        assert!(contains("我喜欢学习中文", "中文"));
+        assert!(!contains("我喜欢学习中文", "英文"));
+
+        // Right-to-left scripts split on spaces like any other
+        assert!(contains("אני אוהב ראסט מאוד", "ראסט"));
+        assert!(contains("أحب لغة البرمجة", "البرمجة"));
+        assert!(!contains("أحب لغة البرمجة", "برمجة"));
+
+        assert!(!contains("anything", " "));
+    }
+}
@@ -0,0 +1,363 @@
+//! Python consumer for a jetstream-turbo server's enriched record stream.
+//!
+//! ```python
+//! from jetstream_turbo import Consumer
+//!
+//! for record in Consumer("ws://localhost:8080/api/v1/ws"):
+//!     print(record.did, record.text)
+//! ```
+//!
+//! A thin wrapper over the Rust `client::TurboConsumer`, driven on a
+//! single-threaded runtime, so the connection handling lives in one place.
+//! Records are handed to Python as objects with the commonly used fields as
+//! attributes and `to_dict()` for everything else. The consumer reconnects
+//! with `last_id`, so records broadcast while it was away are replayed,
+//! backing off from 1s to 30s between attempts. A server that rejects the
+//! request (an unknown stream, say) or a URL that can't be connected to
+//! raises `ConnectionError` instead of being retried. Frames that aren't
+//! records are skipped. The server's Redis stream lives in its own process,
+//! so the WebSocket is the way in from outside it.
+
+use futures::{Stream, StreamExt};
+use jetstream_turbo_rs::client::{ConsumedRecord, TurboConsumer};
+use jetstream_turbo_rs::models::{enriched::EnrichedRecord, TurboResult};
+use pyo3::exceptions::{PyConnectionError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyString};
+use serde_json::Value;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use url::Url;
+
+/// How long a wait for the next record blocks before checking for Ctrl-C.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type Records = Pin<Box<dyn Stream<Item = TurboResult<ConsumedRecord>> + Send>>;
+
+/// One record from the stream.
+#[pyclass(module = "jetstream_turbo", name = "EnrichedRecord", frozen)]
+struct PyEnrichedRecord {
+    event_id: Option<u64>,
+    record: EnrichedRecord,
+}
+
+#[pymethods]
+impl PyEnrichedRecord {
+    /// Broadcast id; pass it as `last_id` to resume after this record.
+    #[getter]
+    fn event_id(&self) -> Option<u64> {
+        self.event_id
+    }
+
+    #[getter]
+    fn did(&self) -> &str {
+        self.record.get_did()
+    }
+
+    #[getter]
+    fn at_uri(&self) -> Option<String> {
+        self.record.get_at_uri()
+    }
+
+    #[getter]
+    fn collection(&self) -> Option<&str> {
+        self.record.message.commit.as_ref()?.collection.as_deref()
+    }
+
+    /// `create`, `update` or `delete` for commits.
+    #[getter]
+    fn operation(&self) -> Option<&'static str> {
+        Some(self.record.message.commit.as_ref()?.operation_type.as_str())
+    }
+
+    #[getter]
+    fn text(&self) -> Option<&str> {
+        self.record.get_text()
+    }
+
+    #[getter]
+    fn time_us(&self) -> Option<u64> {
+        self.record.message.time_us
+    }
+
+    #[getter]
+    fn author_handle(&self) -> Option<&str> {
+        self.record
+            .hydrated_metadata
+            .author_profile
+            .as_ref()
+            .map(|profile| profile.handle.as_str())
+    }
+
+    /// The record as the server sent it.
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.to_json()?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "EnrichedRecord(event_id={:?}, at_uri={:?})",
+            self.event_id,
+            self.record.get_at_uri()
+        )
+    }
+}
+
+impl PyEnrichedRecord {
+    fn to_json(&self) -> PyResult<Value> {
+        let mut json =
+            serde_json::to_value(&self.record).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        if let (Some(event_id), Value::Object(map)) = (self.event_id, &mut json) {
+            map.insert("event_id".to_string(), event_id.into());
+        }
+        Ok(json)
+    }
+}
+
+impl From<ConsumedRecord> for PyEnrichedRecord {
+    fn from(consumed: ConsumedRecord) -> Self {
+        Self {
+            event_id: consumed.event_id,
+            record: consumed.record,
+        }
+    }
+}
+
+/// Iterates over records from `/api/v1/ws` or a named `/api/v1/ws/{stream}`.
+#[pyclass(module = "jetstream_turbo", unsendable)]
+struct Consumer {
+    endpoint: Endpoint,
+    last_id: Option<u64>,
+    reconnect: bool,
+    runtime: Runtime,
+    records: Option<Records>,
+}
+
+#[pymethods]
+impl Consumer {
+    #[new]
+    #[pyo3(signature = (url, last_id=None, reconnect=true))]
+    fn new(url: &str, last_id: Option<u64>, reconnect: bool) -> PyResult<Self> {
+        let endpoint = Endpoint::parse(url).map_err(PyValueError::new_err)?;
+        // Fail on a bad server URL here rather than on the first read
+        endpoint
+            .consumer()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            endpoint,
+            last_id,
+            reconnect,
+            runtime,
+            records: None,
+        })
+    }
+
+    /// Id of the last record returned, for resuming in a later process.
+    #[getter]
+    fn last_id(&self) -> Option<u64> {
+        self.last_id
+    }
+
+    /// Drops the connection; iterating again reconnects after `last_id`.
+    fn close(&mut self) {
+        self.records = None;
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Blocks until the next record. Ends when the server closes the
+    /// connection and `reconnect` is off; raises `ConnectionError` when the
+    /// server rejects the request, or on any failure with `reconnect` off.
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyEnrichedRecord>> {
+        if self.records.is_none() {
+            let records = self.subscribe()?;
+            self.records = Some(records);
+        }
+        loop {
+            py.check_signals()?;
+            let runtime = &self.runtime;
+            let records = self.records.as_mut().expect("subscribed above");
+            let next = py.allow_threads(|| {
+                runtime
+                    .block_on(async { tokio::time::timeout(POLL_INTERVAL, records.next()).await })
+            });
+            match next {
+                Ok(Some(Ok(consumed))) => {
+                    self.last_id = consumed.event_id.or(self.last_id);
+                    return Ok(Some(consumed.into()));
+                }
+                Ok(Some(Err(e))) => {
+                    self.records = None;
+                    return Err(PyConnectionError::new_err(e.to_string()));
+                }
+                Ok(None) => {
+                    self.records = None;
+                    return Ok(None);
+                }
+                Err(_elapsed) => {}
+            }
+        }
+    }
+}
+
+impl Consumer {
+    /// Starts a subscription on the runtime, after `last_id` if there is one.
+    fn subscribe(&self) -> PyResult<Records> {
+        let mut consumer = self
+            .endpoint
+            .consumer()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        if let Some(last_id) = self.last_id {
+            consumer = consumer.with_last_id(last_id);
+        }
+        if !self.reconnect {
+            consumer = consumer.without_reconnect();
+        }
+        let _guard = self.runtime.enter();
+        Ok(Box::pin(consumer.subscribe()))
+    }
+}
+
+/// A `/api/v1/ws[/{stream}]` URL split into what `TurboConsumer` takes.
+#[derive(Debug, PartialEq)]
+struct Endpoint {
+    server_url: Url,
+    stream: Option<String>,
+    batch_ms: Option<u64>,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> Result<Self, String> {
+        let url = Url::parse(url).map_err(|e| e.to_string())?;
+        if !matches!(url.scheme(), "ws" | "wss") {
+            return Err("url must be ws:// or wss://".to_string());
+        }
+        let segments: Vec<&str> = url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let ws = segments
+            .windows(3)
+            .rposition(|window| window == ["api", "v1", "ws"])
+            .ok_or_else(|| "url must point at /api/v1/ws or /api/v1/ws/{stream}".to_string())?;
+        let stream = match &segments[ws + 3..] {
+            [] => None,
+            [stream] => Some(stream.to_string()),
+            _ => return Err("url must point at /api/v1/ws or /api/v1/ws/{stream}".to_string()),
+        };
+
+        let mut server_url = url.clone();
+        server_url.set_path(&segments[..ws].join("/"));
+        let batch_ms = url
+            .query_pairs()
+            .find(|(key, _)| key == "batch_ms")
+            .map(|(_, value)| value.parse::<u64>())
+            .transpose()
+            .map_err(|e| format!("invalid batch_ms: {e}"))?;
+
+        Ok(Self {
+            server_url,
+            stream,
+            batch_ms,
+        })
+    }
+
+    fn consumer(&self) -> TurboResult<TurboConsumer> {
+        let mut consumer = TurboConsumer::new(self.server_url.as_str())?;
+        if let Some(stream) = &self.stream {
+            consumer = consumer.with_stream(stream.clone());
+        }
+        if let Some(batch_ms) = self.batch_ms {
+            consumer = consumer.with_batch_ms(batch_ms);
+        }
+        Ok(consumer)
+    }
+}
+
+fn to_python(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any().unbind(),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => u.into_pyobject(py)?.into_any().unbind(),
+            (None, Some(i)) => i.into_pyobject(py)?.into_any().unbind(),
+            (None, None) => PyFloat::new(py, n.as_f64().unwrap_or(f64::NAN))
+                .into_any()
+                .unbind(),
+        },
+        Value::String(s) => PyString::new(py, s).into_any().unbind(),
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| to_python(py, item))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items)?.into_any().unbind()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, to_python(py, value)?)?;
+            }
+            dict.into_any().unbind()
+        }
+    })
+}
+
+#[pymodule]
+fn jetstream_turbo(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Consumer>()?;
+    m.add_class::<PyEnrichedRecord>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jetstream_turbo_rs::testing::fixtures::create_post_message;
+
+    #[test]
+    fn test_to_json_adds_the_event_id() {
+        let record = PyEnrichedRecord {
+            event_id: Some(7),
+            record: EnrichedRecord::new(create_post_message(1)),
+        };
+        let json = record.to_json().unwrap();
+        assert_eq!(json["event_id"], 7);
+        assert_eq!(json["message"]["did"], "did:plc:user0001");
+        assert_eq!(record.did(), "did:plc:user0001");
+    }
+
+    #[test]
+    fn test_endpoint_maps_the_url_onto_the_rust_consumer() {
+        let endpoint =
+            Endpoint::parse("ws://localhost:8080/api/v1/ws?batch_ms=100&format=json&last_id=1")
+                .unwrap();
+        assert_eq!(endpoint.stream, None);
+        assert_eq!(endpoint.batch_ms, Some(100));
+        let consumer = endpoint.consumer().unwrap().with_last_id(42);
+        assert_eq!(
+            consumer.connect_url().as_str(),
+            "ws://localhost:8080/api/v1/ws?format=enriched&batch_ms=100&last_id=42"
+        );
+
+        let endpoint = Endpoint::parse("wss://example.com/turbo/api/v1/ws/posts").unwrap();
+        assert_eq!(endpoint.stream.as_deref(), Some("posts"));
+        assert_eq!(
+            endpoint.consumer().unwrap().connect_url().as_str(),
+            "wss://example.com/turbo/api/v1/ws/posts?format=enriched"
+        );
+
+        assert!(Endpoint::parse("http://localhost:8080/api/v1/ws").is_err());
+        assert!(Endpoint::parse("ws://localhost:8080/stream").is_err());
+        assert!(Endpoint::parse("ws://localhost:8080/api/v1/ws/a/b").is_err());
+        assert!(Endpoint::parse("ws://localhost:8080/api/v1/ws?batch_ms=soon").is_err());
+    }
+}
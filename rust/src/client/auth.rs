@@ -1,7 +1,8 @@
+use crate::client::bluesky::{rate_limit_delay, rate_limited_error};
+use crate::client::http::HttpPolicy;
 use crate::models::errors::{TurboError, TurboResult};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
 use tracing::{error, info, trace, warn};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -27,8 +28,7 @@ pub struct BlueskyAuthClient {
     handle: String,
     app_password: String,
     api_base_url: String,
-    max_retries: u32,
-    retry_delay: Duration,
+    policy: HttpPolicy,
 }
 
 impl BlueskyAuthClient {
@@ -41,21 +41,22 @@ impl BlueskyAuthClient {
         app_password: String,
         api_base_url: String,
     ) -> TurboResult<Self> {
-        let http_client = Client::builder()
-            .timeout(Duration::from_secs(10))
-            .user_agent("jetstream-turbo/0.1.0")
-            .build()?;
-
+        let policy = HttpPolicy::AUTH;
         Ok(Self {
-            http_client,
+            http_client: policy.client_builder().build()?,
             handle,
             app_password,
             api_base_url,
-            max_retries: 3,
-            retry_delay: Duration::from_millis(500),
+            policy,
         })
     }
 
+    pub fn with_http_policy(mut self, policy: HttpPolicy) -> TurboResult<Self> {
+        self.http_client = policy.client_builder().build()?;
+        self.policy = policy;
+        Ok(self)
+    }
+
     /// Authenticate with Bluesky and get a session token
     pub async fn authenticate(&self) -> TurboResult<AuthResponse> {
         let url = format!("{}/com.atproto.server.createSession", self.api_base_url);
@@ -111,8 +112,16 @@ impl BlueskyAuthClient {
                         ));
                     }
                     reqwest::StatusCode::TOO_MANY_REQUESTS => {
-                        warn!("Rate limited during authentication, waiting before retry");
-                        tokio::time::sleep(self.retry_delay * 2).await;
+                        let rate_limited =
+                            rate_limited_error("com.atproto.server.createSession", resp.headers());
+                        if attempt >= self.policy.max_retries {
+                            return Err(rate_limited);
+                        }
+                        let wait_time = rate_limit_delay(&rate_limited, attempt, &self.policy);
+                        warn!("{}, retrying in {:?}", rate_limited, wait_time);
+                        tokio::time::sleep(wait_time).await;
+                        attempt += 1;
+                        continue;
                     }
                     status => {
                         let error_text = resp.text().await.unwrap_or_default();
@@ -124,20 +133,17 @@ impl BlueskyAuthClient {
                 },
                 Err(e) => {
                     error!("HTTP request failed: {}", e);
-                    if attempt >= self.max_retries {
+                    if attempt >= self.policy.max_retries {
                         return Err(TurboError::HttpRequest(e));
                     }
                 }
             }
 
             attempt += 1;
-            if attempt <= self.max_retries {
-                trace!(
-                    "Retry attempt {} in {}ms",
-                    attempt,
-                    self.retry_delay.as_millis()
-                );
-                tokio::time::sleep(self.retry_delay.saturating_mul(attempt)).await;
+            if attempt <= self.policy.max_retries {
+                let delay = self.policy.retry_delay(attempt);
+                trace!("Retry attempt {} in {}ms", attempt, delay.as_millis());
+                tokio::time::sleep(delay).await;
             }
         }
     }
@@ -218,6 +224,7 @@ impl BlueskyAuthClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
     use wiremock::matchers::{method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
@@ -249,8 +256,11 @@ mod tests {
             handle: "test.bsky.social".to_string(),
             app_password: "test-password".to_string(),
             api_base_url: mock_server.uri(),
-            max_retries: 3,
-            retry_delay: Duration::from_millis(100),
+            policy: HttpPolicy {
+                max_retries: 3,
+                retry_base_delay: Duration::from_millis(100),
+                ..HttpPolicy::AUTH
+            },
         };
 
         let result = client.authenticate().await.unwrap();
@@ -274,8 +284,11 @@ mod tests {
             handle: "test.bsky.social".to_string(),
             app_password: "wrong-password".to_string(),
             api_base_url: mock_server.uri(),
-            max_retries: 3,
-            retry_delay: Duration::from_millis(100),
+            policy: HttpPolicy {
+                max_retries: 3,
+                retry_base_delay: Duration::from_millis(100),
+                ..HttpPolicy::AUTH
+            },
         };
 
         let result = client.authenticate().await;
@@ -285,4 +298,38 @@ mod tests {
             TurboError::PermissionDenied(_)
         ));
     }
+
+    #[tokio::test]
+    async fn test_auth_rate_limited_surfaces_retry_after() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/com.atproto.server.createSession"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let client = BlueskyAuthClient {
+            http_client: Client::builder()
+                .build()
+                .expect("Failed to build test HTTP client"),
+            handle: "test.bsky.social".to_string(),
+            app_password: "test-password".to_string(),
+            api_base_url: mock_server.uri(),
+            policy: HttpPolicy {
+                max_retries: 2,
+                retry_base_delay: Duration::from_millis(100),
+                ..HttpPolicy::AUTH
+            },
+        };
+
+        let error = client.authenticate().await.unwrap_err();
+        assert!(matches!(
+            &error,
+            TurboError::RateLimited { endpoint, .. } if endpoint == "com.atproto.server.createSession"
+        ));
+        assert_eq!(error.retry_after(), Some(Duration::ZERO));
+        assert!(error.is_retryable());
+    }
 }
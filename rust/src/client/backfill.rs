@@ -0,0 +1,339 @@
+use crate::client::bluesky::{rate_limit_delay, rate_limited_error};
+use crate::client::http::HttpPolicy;
+use crate::client::PlcClient;
+use crate::models::{
+    at_uri::AtUri,
+    errors::{TurboError, TurboResult},
+    jetstream::{CommitData, JetstreamMessage, MessageKind, OperationType},
+};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::Arc;
+use tracing::{trace, warn};
+
+/// Page size for `com.atproto.repo.listRecords`; 100 is the lexicon maximum.
+const LIST_RECORDS_LIMIT: u32 = 100;
+const LIST_RECORDS_ENDPOINT: &str = "com.atproto.repo.listRecords";
+const GET_RECORD_ENDPOINT: &str = "com.atproto.repo.getRecord";
+
+#[derive(Debug, Deserialize)]
+pub struct ListRecordsPage {
+    #[serde(default)]
+    pub cursor: Option<String>,
+    pub records: Vec<ListedRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListedRecord {
+    pub uri: String,
+    pub cid: String,
+    pub value: Value,
+}
+
+impl ListedRecord {
+    /// Shapes the record as the create commit Jetstream would have delivered, so it
+    /// can go through the regular hydration pipeline.
+    pub fn into_message(self) -> Option<JetstreamMessage> {
+        let uri = AtUri::parse(&self.uri)?;
+        let time_us = self
+            .value
+            .get("createdAt")
+            .and_then(Value::as_str)
+            .and_then(|created_at| chrono::DateTime::parse_from_rfc3339(created_at).ok())
+            .and_then(|created_at| u64::try_from(created_at.timestamp_micros()).ok());
+
+        Some(JetstreamMessage {
+            did: uri.did().to_string(),
+            seq: None,
+            time_us,
+            kind: MessageKind::Commit,
+            commit: Some(CommitData {
+                rev: None,
+                operation_type: OperationType::Create,
+                collection: Some(uri.collection().to_string()),
+                rkey: Some(uri.rkey().to_string()),
+                record: Some(self.value),
+                cid: Some(self.cid),
+                extra: Default::default(),
+            }),
+            identity: None,
+            account: None,
+            extra: Default::default(),
+        })
+    }
+}
+
+/// Pages through existing repo records so a new deployment can load history
+/// alongside the live stream.
+pub struct BackfillClient {
+    http_client: Client,
+    api_base_url: String,
+    policy: HttpPolicy,
+    pds_resolver: Option<Arc<PlcClient>>,
+}
+
+impl BackfillClient {
+    pub fn new(api_base_url: String) -> TurboResult<Self> {
+        let policy = HttpPolicy::BACKFILL;
+        Ok(Self {
+            http_client: policy.client_builder().build()?,
+            api_base_url: api_base_url.trim_end_matches('/').to_string(),
+            policy,
+            pds_resolver: None,
+        })
+    }
+
+    /// Reads each repo from the PDS its DID document lists. `api_base_url` is only
+    /// used for DIDs the directory can't resolve.
+    pub fn with_pds_resolver(mut self, resolver: Arc<PlcClient>) -> Self {
+        self.pds_resolver = Some(resolver);
+        self
+    }
+
+    pub fn with_http_policy(mut self, policy: HttpPolicy) -> TurboResult<Self> {
+        self.http_client = policy.client_builder().build()?;
+        self.policy = policy;
+        Ok(self)
+    }
+
+    /// One page of `collection` records in `did`'s repo, starting after `cursor`.
+    pub async fn list_records(
+        &self,
+        did: &str,
+        collection: &str,
+        cursor: Option<&str>,
+    ) -> TurboResult<ListRecordsPage> {
+        let limit = LIST_RECORDS_LIMIT.to_string();
+        let mut query = vec![
+            ("repo", did),
+            ("collection", collection),
+            ("limit", limit.as_str()),
+        ];
+        if let Some(cursor) = cursor {
+            query.push(("cursor", cursor));
+        }
+
+        let response = self
+            .get_with_retry(did, LIST_RECORDS_ENDPOINT, &query)
+            .await?;
+        match response.status() {
+            StatusCode::OK => {
+                let page: ListRecordsPage = response.json().await?;
+                trace!(
+                    "Listed {} {} records for {}",
+                    page.records.len(),
+                    collection,
+                    did
+                );
+                Ok(page)
+            }
+            status => {
+                let error_text = response.text().await.unwrap_or_default();
+                Err(TurboError::InvalidApiResponse(format!(
+                    "{LIST_RECORDS_ENDPOINT} for {did} returned {status}: {error_text}"
+                )))
+            }
+        }
+    }
+
+    /// The record at `uri`, or `None` if its repo doesn't have it.
+    pub async fn get_record(&self, uri: &AtUri) -> TurboResult<Option<ListedRecord>> {
+        let query = [
+            ("repo", uri.did()),
+            ("collection", uri.collection()),
+            ("rkey", uri.rkey()),
+        ];
+        let response = self
+            .get_with_retry(uri.did(), GET_RECORD_ENDPOINT, &query)
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(Some(response.json().await?)),
+            StatusCode::NOT_FOUND => Ok(None),
+            status => {
+                let error_text = response.text().await.unwrap_or_default();
+                // XRPC reports a missing record as a 400 with a RecordNotFound error
+                if status == StatusCode::BAD_REQUEST && error_text.contains("RecordNotFound") {
+                    return Ok(None);
+                }
+                Err(TurboError::InvalidApiResponse(format!(
+                    "{GET_RECORD_ENDPOINT} for {uri} returned {status}: {error_text}"
+                )))
+            }
+        }
+    }
+
+    /// XRPC base URL of the service hosting `did`'s repo.
+    async fn repo_base_url(&self, did: &str) -> Cow<'_, str> {
+        let Some(resolver) = &self.pds_resolver else {
+            return Cow::Borrowed(&self.api_base_url);
+        };
+        match resolver.pds_endpoint(did).await {
+            Ok(Some(endpoint)) => Cow::Owned(format!("{}/xrpc", endpoint.trim_end_matches('/'))),
+            Ok(None) => Cow::Borrowed(&self.api_base_url),
+            Err(e) => {
+                warn!("Failed to resolve the PDS of {}: {}", did, e);
+                Cow::Borrowed(&self.api_base_url)
+            }
+        }
+    }
+
+    /// Sends a GET to `endpoint` on the PDS hosting `did`, retrying rate-limited
+    /// responses; any other response is returned for the caller to interpret.
+    async fn get_with_retry(
+        &self,
+        did: &str,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> TurboResult<reqwest::Response> {
+        let url = format!("{}/{}", self.repo_base_url(did).await, endpoint);
+        let mut attempt = 0;
+        loop {
+            let response = self.http_client.get(&url).query(query).send().await?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+            let rate_limited = rate_limited_error(endpoint, response.headers());
+            if attempt >= self.policy.max_retries {
+                return Err(rate_limited);
+            }
+            let wait_time = rate_limit_delay(&rate_limited, attempt, &self.policy);
+            warn!("{}, retrying in {:?}", rate_limited, wait_time);
+            tokio::time::sleep(wait_time).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_list_records_pages_into_create_messages() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.repo.listRecords"))
+            .and(query_param("repo", "did:plc:backfill"))
+            .and(query_param("collection", "app.bsky.feed.post"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "cursor": "3kabc",
+                "records": [{
+                    "uri": "at://did:plc:backfill/app.bsky.feed.post/3kabc",
+                    "cid": "bafyreiabc",
+                    "value": {
+                        "$type": "app.bsky.feed.post",
+                        "text": "from the archive",
+                        "createdAt": "2024-01-02T03:04:05.000Z"
+                    }
+                }]
+            })))
+            .mount(&server)
+            .await;
+
+        let client = BackfillClient::new(format!("{}/xrpc/", server.uri())).unwrap();
+        let page = client
+            .list_records("did:plc:backfill", "app.bsky.feed.post", None)
+            .await
+            .unwrap();
+        assert_eq!(page.cursor.as_deref(), Some("3kabc"));
+
+        let message = page
+            .records
+            .into_iter()
+            .next()
+            .unwrap()
+            .into_message()
+            .unwrap();
+        assert_eq!(message.did, "did:plc:backfill");
+        assert_eq!(message.time_us, Some(1_704_164_645_000_000));
+        assert_eq!(
+            message.extract_at_uri().as_deref(),
+            Some("at://did:plc:backfill/app.bsky.feed.post/3kabc")
+        );
+        assert!(!message.is_delete_operation());
+    }
+
+    #[tokio::test]
+    async fn test_get_record_treats_record_not_found_as_missing() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.repo.getRecord"))
+            .and(query_param("rkey", "3kabc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "uri": "at://did:plc:lookup/app.bsky.feed.post/3kabc",
+                "cid": "bafyreiabc",
+                "value": {"$type": "app.bsky.feed.post", "text": "found"}
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.repo.getRecord"))
+            .and(query_param("rkey", "3kmissing"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "RecordNotFound",
+                "message": "Could not locate record"
+            })))
+            .mount(&server)
+            .await;
+
+        let client = BackfillClient::new(format!("{}/xrpc", server.uri())).unwrap();
+        let found = AtUri::parse("at://did:plc:lookup/app.bsky.feed.post/3kabc").unwrap();
+        let record = client.get_record(&found).await.unwrap().unwrap();
+        assert_eq!(record.value["text"], "found");
+
+        let missing = AtUri::parse("at://did:plc:lookup/app.bsky.feed.post/3kmissing").unwrap();
+        assert!(client.get_record(&missing).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reads_each_repo_from_its_pds() {
+        let directory = MockServer::start().await;
+        let pds = MockServer::start().await;
+        let fallback = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/did:plc:hosted/data"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "did": "did:plc:hosted",
+                "alsoKnownAs": ["at://hosted.example.com"],
+                "services": {
+                    "atproto_pds": {
+                        "type": "AtprotoPersonalDataServer",
+                        "endpoint": format!("{}/", pds.uri())
+                    }
+                }
+            })))
+            .mount(&directory)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/did:plc:unknown/data"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&directory)
+            .await;
+        for (server, did) in [(&pds, "did:plc:hosted"), (&fallback, "did:plc:unknown")] {
+            Mock::given(method("GET"))
+                .and(path("/xrpc/com.atproto.repo.listRecords"))
+                .and(query_param("repo", did))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "records": []
+                })))
+                .expect(1)
+                .mount(server)
+                .await;
+        }
+
+        let resolver = Arc::new(PlcClient::new(directory.uri()).unwrap());
+        let client = BackfillClient::new(format!("{}/xrpc", fallback.uri()))
+            .unwrap()
+            .with_pds_resolver(resolver);
+        for did in ["did:plc:hosted", "did:plc:unknown"] {
+            client
+                .list_records(did, "app.bsky.feed.post", None)
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Aggregate request budget for the Bluesky API that survives restarts. The
+//! per-second limiter paces requests within a process, but starts fresh on
+//! every restart; this token bucket is saved to disk and restored on startup,
+//! so a run of quick redeploys spends from the same budget instead of each
+//! getting a full one.
+
+use crate::models::errors::TurboResult;
+use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, trace, warn};
+
+/// How often spent budget is written out; a crash forgets at most this much.
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct SavedBudget {
+    tokens: f64,
+    updated_at_ms: u64,
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    saved: SavedBudget,
+    last_save: Option<Instant>,
+}
+
+/// Allows `capacity` requests per `window`, refilled continuously.
+#[derive(Debug)]
+pub struct RequestBudget {
+    capacity: f64,
+    refill_per_ms: f64,
+    state: Mutex<BudgetState>,
+    path: Option<PathBuf>,
+    /// Serializes writes to `path` and holds the `updated_at_ms` last written
+    written_at_ms: tokio::sync::Mutex<u64>,
+}
+
+impl RequestBudget {
+    /// A full budget that isn't persisted.
+    pub fn new(capacity: u32, window: Duration) -> Self {
+        let capacity = f64::from(capacity.max(1));
+        Self {
+            capacity,
+            refill_per_ms: capacity / window.as_millis().max(1) as f64,
+            state: Mutex::new(BudgetState {
+                saved: SavedBudget {
+                    tokens: capacity,
+                    updated_at_ms: unix_millis(),
+                },
+                last_save: None,
+            }),
+            path: None,
+            written_at_ms: tokio::sync::Mutex::new(0),
+        }
+    }
+
+    /// Persists the budget to `path`, restoring what a previous process left
+    /// there (plus what has refilled since). A missing or unreadable file
+    /// starts from a full budget.
+    pub fn with_state_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        match std::fs::read(&path) {
+            Ok(contents) => match serde_json::from_slice::<SavedBudget>(&contents) {
+                Ok(mut saved) => {
+                    self.refill(&mut saved, unix_millis());
+                    info!(
+                        "Restored Bluesky request budget: {:.0}/{:.0} requests available",
+                        saved.tokens, self.capacity
+                    );
+                    self.state
+                        .get_mut()
+                        .expect("request budget lock poisoned")
+                        .saved = saved;
+                }
+                Err(e) => warn!(
+                    "Ignoring unreadable request budget {}: {}",
+                    path.display(),
+                    e
+                ),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to read request budget {}: {}", path.display(), e),
+        }
+        self.path = Some(path);
+        self
+    }
+
+    /// Requests that could be made right now.
+    pub fn available(&self) -> f64 {
+        let mut state = self.lock();
+        self.refill(&mut state.saved, unix_millis());
+        state.saved.tokens
+    }
+
+    /// Waits until the budget allows another request, then spends it.
+    pub async fn until_ready(&self) {
+        let due_save = loop {
+            let wait = {
+                let mut state = self.lock();
+                self.refill(&mut state.saved, unix_millis());
+                if state.saved.tokens >= 1.0 {
+                    state.saved.tokens -= 1.0;
+                    gauge!("jetstream_turbo_api_budget_available").set(state.saved.tokens);
+                    break self.take_due_save(&mut state);
+                }
+                Duration::from_millis(
+                    ((1.0 - state.saved.tokens) / self.refill_per_ms).ceil() as u64
+                )
+            };
+            counter!("jetstream_turbo_api_budget_waits_total").increment(1);
+            trace!("Bluesky request budget exhausted, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        };
+        if let Some(saved) = due_save {
+            if let Err(e) = self.write(saved).await {
+                warn!("Failed to save request budget: {}", e);
+            }
+        }
+    }
+
+    /// Writes the current budget out now.
+    pub async fn save(&self) -> TurboResult<()> {
+        let saved = {
+            let mut state = self.lock();
+            self.refill(&mut state.saved, unix_millis());
+            state.last_save = Some(Instant::now());
+            state.saved
+        };
+        self.write(saved).await
+    }
+
+    /// The budget to write out if the last save was long enough ago, marking
+    /// it saved so concurrent requests don't write it too.
+    fn take_due_save(&self, state: &mut BudgetState) -> Option<SavedBudget> {
+        if self.path.is_none()
+            || state
+                .last_save
+                .is_some_and(|last_save| last_save.elapsed() < SAVE_INTERVAL)
+        {
+            return None;
+        }
+        state.last_save = Some(Instant::now());
+        Some(state.saved)
+    }
+
+    async fn write(&self, saved: SavedBudget) -> TurboResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut written_at_ms = self.written_at_ms.lock().await;
+        // A save that queued behind a newer one has nothing to add
+        if saved.updated_at_ms < *written_at_ms {
+            return Ok(());
+        }
+        // Write then rename, so a crash mid-write leaves the previous budget
+        let temp_path = path.with_extension("tmp");
+        tokio::fs::write(&temp_path, serde_json::to_vec(&saved)?).await?;
+        tokio::fs::rename(&temp_path, path).await?;
+        *written_at_ms = saved.updated_at_ms;
+        Ok(())
+    }
+
+    fn refill(&self, saved: &mut SavedBudget, now_ms: u64) {
+        let elapsed_ms = now_ms.saturating_sub(saved.updated_at_ms);
+        saved.tokens = (saved.tokens + elapsed_ms as f64 * self.refill_per_ms).min(self.capacity);
+        saved.updated_at_ms = saved.updated_at_ms.max(now_ms);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BudgetState> {
+        self.state.lock().expect("request budget lock poisoned")
+    }
+}
+
+impl Drop for RequestBudget {
+    /// Saves the budget on shutdown. Nothing else can be using it by now, so
+    /// this writes synchronously.
+    fn drop(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut saved = self.lock().saved;
+        self.refill(&mut saved, unix_millis());
+        let temp_path = path.with_extension("tmp");
+        let result = serde_json::to_vec(&saved)
+            .map_err(std::io::Error::from)
+            .and_then(|contents| std::fs::write(&temp_path, contents))
+            .and_then(|()| std::fs::rename(&temp_path, path));
+        if let Err(e) = result {
+            warn!("Failed to save request budget: {}", e);
+        }
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_spent_budget_is_restored_after_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("budget.json");
+
+        let budget = RequestBudget::new(5, Duration::from_secs(3600)).with_state_file(&path);
+        assert!(budget.available() >= 5.0);
+        budget.until_ready().await;
+        // The first request is saved without waiting for a shutdown
+        assert!(path.exists());
+        for _ in 0..3 {
+            budget.until_ready().await;
+        }
+        budget.save().await.unwrap();
+
+        let restarted = RequestBudget::new(5, Duration::from_secs(3600)).with_state_file(&path);
+        assert!((1.0..1.1).contains(&restarted.available()));
+
+        // Budget refills for the time the process was down
+        let an_hour_ago = SavedBudget {
+            tokens: 0.0,
+            updated_at_ms: unix_millis() - 3_600_000,
+        };
+        std::fs::write(&path, serde_json::to_vec(&an_hour_ago).unwrap()).unwrap();
+        let restarted = RequestBudget::new(5, Duration::from_secs(3600)).with_state_file(&path);
+        assert_eq!(restarted.available(), 5.0);
+    }
+
+    #[tokio::test]
+    async fn test_requests_wait_once_the_budget_is_spent() {
+        let budget = RequestBudget::new(1, Duration::from_millis(50));
+        budget.until_ready().await;
+
+        let started = Instant::now();
+        budget.until_ready().await;
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,401 @@
+//! Raw Jetstream frame capture and replay. Captures are zstd-compressed files of
+//! `<receive time in µs>\t<frame>` lines, rotated by size, so a live session can be
+//! fed back through the pipeline later for reproducible testing or offline runs.
+
+use crate::client::jetstream::{parse_message, MessageSource};
+use crate::models::{
+    errors::{TurboError, TurboResult},
+    jetstream::JetstreamMessage,
+};
+use crate::telemetry::{DropCounters, DropReason};
+use futures::{Stream, StreamExt};
+use metrics::counter;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, info, warn};
+
+const CAPTURE_EXTENSION: &str = "ndjson.zst";
+const CAPTURE_CHANNEL_CAPACITY: usize = 10_000;
+const CAPTURE_ZSTD_LEVEL: i32 = 3;
+/// Frames between zstd flushes, bounding what an unclean shutdown can lose.
+const CAPTURE_FLUSH_EVERY: u64 = 1_000;
+
+/// Writes raw frames to rotating capture files on a dedicated thread. Frames are
+/// dropped (and counted) rather than slowing the websocket reader if the writer
+/// falls behind.
+pub struct FrameCapture {
+    tx: Option<mpsc::Sender<(u64, String)>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl FrameCapture {
+    pub fn start(dir: impl Into<PathBuf>, rotate_bytes: u64) -> TurboResult<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        info!("Capturing raw Jetstream frames to {}", dir.display());
+
+        let (tx, rx) = mpsc::channel(CAPTURE_CHANNEL_CAPACITY);
+        let writer = std::thread::Builder::new()
+            .name("frame-capture".to_string())
+            .spawn(move || CaptureWriter::new(dir, rotate_bytes.max(1)).run(rx))?;
+
+        Ok(Self {
+            tx: Some(tx),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn record(&self, frame: &str) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        if tx.try_send((unix_micros(), frame.to_string())).is_err() {
+            counter!("jetstream_turbo_capture_dropped_total").increment(1);
+        }
+    }
+
+    /// Stops accepting frames and waits for the current file to be finished.
+    pub fn close(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.tx.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// The capture is usually shared with the websocket reader, so dropping the last
+/// handle has to finish the zstd frame too; otherwise the file is left unreadable.
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+struct CaptureWriter {
+    dir: PathBuf,
+    rotate_bytes: u64,
+    encoder: Option<zstd::Encoder<'static, BufWriter<File>>>,
+    file_bytes: u64,
+    files_opened: u64,
+    frames_since_flush: u64,
+}
+
+impl CaptureWriter {
+    fn new(dir: PathBuf, rotate_bytes: u64) -> Self {
+        Self {
+            dir,
+            rotate_bytes,
+            encoder: None,
+            file_bytes: 0,
+            files_opened: 0,
+            frames_since_flush: 0,
+        }
+    }
+
+    fn run(mut self, mut rx: mpsc::Receiver<(u64, String)>) {
+        while let Some((received_us, frame)) = rx.blocking_recv() {
+            if let Err(e) = self.write(received_us, &frame) {
+                error!("Frame capture write failed: {}", e);
+                self.encoder = None;
+            }
+        }
+        if let Err(e) = self.finish_file() {
+            error!("Failed to finish capture file: {}", e);
+        }
+    }
+
+    fn write(&mut self, received_us: u64, frame: &str) -> std::io::Result<()> {
+        if self.encoder.is_none() {
+            // The per-run file index keeps names unique when rotating within one microsecond
+            let path = self.dir.join(format!(
+                "jetstream-{received_us:020}-{:06}.{CAPTURE_EXTENSION}",
+                self.files_opened
+            ));
+            let file = BufWriter::new(File::create(&path)?);
+            self.encoder = Some(zstd::Encoder::new(file, CAPTURE_ZSTD_LEVEL)?);
+            self.file_bytes = 0;
+            self.files_opened += 1;
+            info!("Opened capture file {}", path.display());
+        }
+
+        let encoder = self.encoder.as_mut().expect("encoder opened above");
+        let line = format!("{received_us}\t{frame}\n");
+        encoder.write_all(line.as_bytes())?;
+        self.file_bytes += line.len() as u64;
+        self.frames_since_flush += 1;
+        if self.frames_since_flush >= CAPTURE_FLUSH_EVERY {
+            encoder.flush()?;
+            self.frames_since_flush = 0;
+        }
+
+        if self.file_bytes >= self.rotate_bytes {
+            self.finish_file()?;
+        }
+        Ok(())
+    }
+
+    fn finish_file(&mut self) -> std::io::Result<()> {
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish()?.flush()?;
+        }
+        self.frames_since_flush = 0;
+        Ok(())
+    }
+}
+
+/// Replays capture files through the pipeline. `speed` scales the original
+/// inter-frame timing (2.0 replays twice as fast); 0 replays as fast as possible.
+/// Once every file has been read the stream stays open without yielding more, so
+/// the pipeline doesn't restart and replay the capture a second time.
+pub struct ReplaySource {
+    path: PathBuf,
+    speed: f64,
+    channel_capacity: usize,
+    drops: DropCounters,
+}
+
+impl ReplaySource {
+    pub fn new(path: impl Into<PathBuf>, speed: f64) -> Self {
+        Self {
+            path: path.into(),
+            speed: speed.max(0.0),
+            channel_capacity: CAPTURE_CHANNEL_CAPACITY,
+            drops: DropCounters::new(),
+        }
+    }
+
+    pub fn with_channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = capacity.max(1);
+        self
+    }
+}
+
+impl MessageSource for ReplaySource {
+    fn drop_counters(&self) -> Option<&DropCounters> {
+        Some(&self.drops)
+    }
+
+    async fn stream_messages(
+        &self,
+    ) -> TurboResult<Pin<Box<dyn Stream<Item = TurboResult<JetstreamMessage>> + Send>>> {
+        let files = capture_files(&self.path)?;
+        if files.is_empty() {
+            return Err(TurboError::InvalidMessage(format!(
+                "no capture files found at {}",
+                self.path.display()
+            )));
+        }
+
+        let (tx, rx) = mpsc::channel(self.channel_capacity);
+        let speed = self.speed;
+        let drops = self.drops.clone();
+        tokio::task::spawn_blocking(move || replay_files(files, speed, drops, tx));
+
+        Ok(Box::pin(
+            ReceiverStream::new(rx).chain(futures::stream::pending()),
+        ))
+    }
+}
+
+fn replay_files(
+    files: Vec<PathBuf>,
+    speed: f64,
+    drops: DropCounters,
+    tx: mpsc::Sender<TurboResult<JetstreamMessage>>,
+) {
+    let started = Instant::now();
+    let mut first_received_us = None;
+    let mut frames = 0u64;
+
+    for path in &files {
+        info!("Replaying capture file {}", path.display());
+        let reader = match File::open(path).and_then(zstd::Decoder::new) {
+            Ok(decoder) => BufReader::new(decoder),
+            Err(e) => {
+                warn!("Skipping capture file {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                // A capture cut off by an unclean shutdown ends mid-frame
+                Err(e) => {
+                    warn!("Capture file {} ends early: {}", path.display(), e);
+                    break;
+                }
+            };
+            let Some((received_us, frame)) = split_capture_line(&line) else {
+                continue;
+            };
+
+            if speed > 0.0 {
+                let first = *first_received_us.get_or_insert(received_us);
+                let offset = Duration::from_micros(received_us.saturating_sub(first));
+                let due = offset.div_f64(speed);
+                if let Some(wait) = due.checked_sub(started.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+
+            match parse_message(frame.to_string()) {
+                Ok(message) => {
+                    if tx.blocking_send(Ok(message)).is_err() {
+                        return;
+                    }
+                    frames += 1;
+                }
+                Err(e) => {
+                    warn!("Skipping unparseable captured frame: {}", e);
+                    drops.record(DropReason::ParseError, 1);
+                }
+            }
+        }
+    }
+
+    info!(
+        "Replay complete: {} frames from {} files in {:?}",
+        frames,
+        files.len(),
+        started.elapsed()
+    );
+}
+
+/// Reads every frame of a capture into memory, skipping frames that don't
+/// parse, for callers that want the whole recording rather than a live replay.
+pub fn read_capture(path: &Path) -> TurboResult<Vec<JetstreamMessage>> {
+    let files = capture_files(path)?;
+    if files.is_empty() {
+        return Err(TurboError::InvalidMessage(format!(
+            "no capture files found at {}",
+            path.display()
+        )));
+    }
+
+    let mut messages = Vec::new();
+    for path in &files {
+        let reader = BufReader::new(zstd::Decoder::new(File::open(path)?)?);
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                warn!("Capture file {} ends early", path.display());
+                break;
+            };
+            if let Some(message) = split_capture_line(&line)
+                .and_then(|(_, frame)| parse_message(frame.to_string()).ok())
+            {
+                messages.push(message);
+            }
+        }
+    }
+    Ok(messages)
+}
+
+/// Splits a capture line into its receive time and frame.
+fn split_capture_line(line: &str) -> Option<(u64, &str)> {
+    let (received_us, frame) = line.split_once('\t')?;
+    Some((received_us.parse().ok()?, frame))
+}
+
+/// A single capture file, or every capture file in a directory in capture order.
+fn capture_files(path: &Path) -> TurboResult<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with(CAPTURE_EXTENSION))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn unix_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+
+    #[tokio::test]
+    async fn test_captured_frames_replay_in_order_across_rotated_files() {
+        let dir = std::env::temp_dir().join(format!("test_capture_{}", uuid::Uuid::new_v4()));
+        // Small enough that every frame rotates into its own file
+        let capture = FrameCapture::start(&dir, 1).unwrap();
+        let frames: Vec<String> = (0..3)
+            .map(|index| serde_json::to_string(&create_post_message(index)).unwrap())
+            .collect();
+        for frame in &frames {
+            capture.record(frame);
+        }
+        capture.record("not json");
+        capture.close();
+
+        assert_eq!(capture_files(&dir).unwrap().len(), 4);
+
+        let replay = ReplaySource::new(&dir, 0.0);
+        let messages: Vec<JetstreamMessage> = replay
+            .stream_messages()
+            .await
+            .unwrap()
+            .take(3)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        let seqs: Vec<Option<u64>> = messages.iter().map(|message| message.seq).collect();
+        assert_eq!(
+            seqs,
+            (0..3)
+                .map(|index| create_post_message(index).seq)
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(read_capture(&dir).unwrap().len(), 3);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_dropping_the_last_handle_finishes_the_capture_file() {
+        let dir = std::env::temp_dir().join(format!("test_capture_{}", uuid::Uuid::new_v4()));
+        let capture = std::sync::Arc::new(FrameCapture::start(&dir, u64::MAX).unwrap());
+        let frames: Vec<String> = (0..3)
+            .map(|index| serde_json::to_string(&create_post_message(index)).unwrap())
+            .collect();
+        for frame in &frames {
+            capture.record(frame);
+        }
+        drop(capture);
+
+        let files = capture_files(&dir).unwrap();
+        assert_eq!(files.len(), 1);
+        // Decoding the whole file fails if the zstd frame was never finished
+        let decoded = zstd::stream::decode_all(File::open(&files[0]).unwrap()).unwrap();
+        let decoded = String::from_utf8(decoded).unwrap();
+        let captured: Vec<&str> = decoded
+            .lines()
+            .map(|line| split_capture_line(line).unwrap().1)
+            .collect();
+        assert_eq!(captured, frames);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,389 @@
+//! Typed client for a jetstream-turbo server's own output, so Rust services
+//! downstream of one don't reimplement the connection handling. Connects to
+//! `/api/v1/ws` or a named `/api/v1/ws/{stream}`, asks for enriched records,
+//! and reconnects with `last_id` after a drop or a server restart so records
+//! broadcast in between are replayed.
+
+use crate::client::http::{connect_websocket, user_agent_header, DEFAULT_USER_AGENT};
+use crate::client::jetstream::DEFAULT_CHANNEL_CAPACITY;
+use crate::models::{enriched::EnrichedRecord, errors::TurboError, TurboResult};
+use futures::{Stream, StreamExt};
+use reqwest::header::HeaderValue;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tracing::{info, trace, warn};
+use url::Url;
+
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// One record from the server.
+#[derive(Debug, Clone)]
+pub struct ConsumedRecord {
+    /// Broadcast id; pass it to `TurboConsumer::with_last_id` to resume after this record
+    pub event_id: Option<u64>,
+    pub record: EnrichedRecord,
+}
+
+pub struct TurboConsumer {
+    server_url: Url,
+    stream: Option<String>,
+    batch_ms: Option<u64>,
+    reconnect: bool,
+    reconnect_delay: Duration,
+    channel_capacity: usize,
+    user_agent: HeaderValue,
+    last_id: Arc<Mutex<Option<u64>>>,
+}
+
+impl TurboConsumer {
+    /// `server_url` is the server's base URL, e.g. `ws://localhost:8080` or
+    /// `https://turbo.example.com`; `http(s)` is read as `ws(s)`.
+    pub fn new(server_url: &str) -> TurboResult<Self> {
+        let mut url = Url::parse(server_url)
+            .map_err(|e| TurboError::InvalidMessage(format!("invalid server URL: {e}")))?;
+        let scheme = match url.scheme() {
+            "ws" | "http" => "ws",
+            "wss" | "https" => "wss",
+            other => {
+                return Err(TurboError::InvalidMessage(format!(
+                    "server URL must be ws, wss, http or https, not {other}"
+                )))
+            }
+        };
+        url.set_scheme(scheme)
+            .map_err(|_| TurboError::InvalidMessage(format!("invalid server URL: {url}")))?;
+        url.set_query(None);
+        url.set_fragment(None);
+
+        Ok(Self {
+            server_url: url,
+            stream: None,
+            batch_ms: None,
+            reconnect: true,
+            reconnect_delay: Duration::from_secs(1),
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            user_agent: HeaderValue::from_static(DEFAULT_USER_AGENT),
+            last_id: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Reads one of the server's `OUTPUT_STREAMS` (or `watchlist`), whose
+    /// filters the server applies, instead of every record.
+    pub fn with_stream(mut self, stream: impl Into<String>) -> Self {
+        self.stream = Some(stream.into());
+        self
+    }
+
+    /// Has the server coalesce records into arrays flushed every `batch_ms`.
+    pub fn with_batch_ms(mut self, batch_ms: u64) -> Self {
+        self.batch_ms = Some(batch_ms).filter(|ms| *ms > 0);
+        self
+    }
+
+    /// Starts after `event_id`, e.g. the `last_id` saved by an earlier process.
+    pub fn with_last_id(self, event_id: u64) -> Self {
+        *self.last_id.lock().expect("consumer state lock poisoned") = Some(event_id);
+        self
+    }
+
+    /// Ends the stream when the connection drops instead of reconnecting.
+    pub fn without_reconnect(mut self) -> Self {
+        self.reconnect = false;
+        self
+    }
+
+    /// First delay before reconnecting, doubled per failed attempt up to 30s.
+    pub fn with_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.reconnect_delay = delay;
+        self
+    }
+
+    pub fn with_channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> TurboResult<Self> {
+        self.user_agent = user_agent_header(user_agent)?;
+        Ok(self)
+    }
+
+    /// Id of the last record received, for resuming in a later process.
+    pub fn last_id(&self) -> Option<u64> {
+        *self.last_id.lock().expect("consumer state lock poisoned")
+    }
+
+    /// The URL the next connection is made to.
+    pub fn connect_url(&self) -> Url {
+        connect_url(
+            &self.server_url,
+            self.stream.as_deref(),
+            self.batch_ms,
+            self.last_id(),
+        )
+    }
+
+    /// Connects and yields records until the stream is dropped. Connection
+    /// failures are retried; an `Err` is only yielded when the server rejects
+    /// the request (an unknown stream, say) or, without reconnecting, when the
+    /// connection fails, and the stream ends after it.
+    pub fn subscribe(&self) -> impl Stream<Item = TurboResult<ConsumedRecord>> + Send + Unpin {
+        let (tx, rx) = mpsc::channel(self.channel_capacity);
+        let server_url = self.server_url.clone();
+        let stream = self.stream.clone();
+        let batch_ms = self.batch_ms;
+        let reconnect = self.reconnect;
+        let reconnect_delay = self.reconnect_delay;
+        let user_agent = self.user_agent.clone();
+        let last_id = Arc::clone(&self.last_id);
+
+        tokio::spawn(async move {
+            let mut delay = reconnect_delay;
+            loop {
+                let resume_from = *last_id.lock().expect("consumer state lock poisoned");
+                let url = connect_url(&server_url, stream.as_deref(), batch_ms, resume_from);
+                match connect_websocket(url.as_str(), &user_agent).await {
+                    Ok((mut socket, _)) => {
+                        info!("Connected to {}", url);
+                        delay = reconnect_delay;
+                        loop {
+                            let message = tokio::select! {
+                                message = socket.next() => message,
+                                _ = tx.closed() => return,
+                            };
+                            match message {
+                                Some(Ok(Message::Text(text))) => {
+                                    for record in decode_frame(&text) {
+                                        if let Some(event_id) = record.event_id {
+                                            *last_id
+                                                .lock()
+                                                .expect("consumer state lock poisoned") =
+                                                Some(event_id);
+                                        }
+                                        if tx.send(Ok(record)).await.is_err() {
+                                            return;
+                                        }
+                                    }
+                                }
+                                Some(Ok(Message::Close(frame))) => {
+                                    info!("Server closed the connection: {:?}", frame);
+                                    break;
+                                }
+                                Some(Ok(_)) => {}
+                                Some(Err(e)) => {
+                                    warn!("Connection to {} failed: {}", url, e);
+                                    break;
+                                }
+                                None => break,
+                            }
+                        }
+                    }
+                    Err(e) if is_rejection(&e) || !reconnect => {
+                        let _ = tx
+                            .send(Err(TurboError::WebSocketConnection(format!(
+                                "{url} refused the connection: {e}"
+                            ))))
+                            .await;
+                        return;
+                    }
+                    Err(e) => warn!("Failed to connect to {}: {}", url, e),
+                }
+
+                if !reconnect {
+                    return;
+                }
+                tokio::select! {
+                    _ = sleep(delay) => {}
+                    _ = tx.closed() => return,
+                }
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+}
+
+fn connect_url(
+    server_url: &Url,
+    stream: Option<&str>,
+    batch_ms: Option<u64>,
+    last_id: Option<u64>,
+) -> Url {
+    let mut url = server_url.clone();
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments.pop_if_empty().extend(["api", "v1", "ws"]);
+        if let Some(stream) = stream {
+            segments.push(stream);
+        }
+    }
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("format", "enriched");
+        if let Some(batch_ms) = batch_ms {
+            query.append_pair("batch_ms", &batch_ms.to_string());
+        }
+        if let Some(last_id) = last_id {
+            query.append_pair("last_id", &last_id.to_string());
+        }
+    }
+    url
+}
+
+/// The server answered the handshake with a client error, so retrying the
+/// same request won't help.
+fn is_rejection(error: &tungstenite::Error) -> bool {
+    match error {
+        tungstenite::Error::Http(response) => response.status().is_client_error(),
+        tungstenite::Error::Url(_) => true,
+        _ => false,
+    }
+}
+
+/// Records in a frame: one, or an array of them with `batch_ms` set. Frames
+/// that aren't records are skipped.
+fn decode_frame(text: &str) -> Vec<ConsumedRecord> {
+    let values = match serde_json::from_str(text) {
+        Ok(Value::Array(values)) => values,
+        Ok(value) => vec![value],
+        Err(e) => {
+            trace!("Skipping frame that isn't JSON: {}", e);
+            return Vec::new();
+        }
+    };
+    values
+        .into_iter()
+        .filter_map(|value| {
+            let event_id = value.get("event_id").and_then(Value::as_u64);
+            match EnrichedRecord::from_json_value(value) {
+                Ok(record) => Some(ConsumedRecord { event_id, record }),
+                Err(e) => {
+                    trace!("Skipping frame that isn't a record: {}", e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::enriched::OutputFormat;
+    use crate::testing::fixtures::create_post_message;
+    use futures::SinkExt;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+    use tokio_tungstenite::tungstenite::http;
+
+    fn event(index: usize, event_id: u64) -> String {
+        let record = EnrichedRecord::new(create_post_message(index));
+        OutputFormat::Enriched
+            .encode_event(&record, event_id)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resumes_after_the_last_record_received() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (requests_tx, mut requests) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for connection in 0..2 {
+                let (tcp, _) = listener.accept().await.unwrap();
+                let requests_tx = requests_tx.clone();
+                let mut socket = tokio_tungstenite::accept_hdr_async(
+                    tcp,
+                    move |request: &Request, response: Response| {
+                        let user_agent = request.headers()[http::header::USER_AGENT].clone();
+                        requests_tx
+                            .send((request.uri().to_string(), user_agent))
+                            .unwrap();
+                        Ok(response)
+                    },
+                )
+                .await
+                .unwrap();
+                if connection == 0 {
+                    // A coalesced batch and a frame that isn't a record, then a restart
+                    let batch = format!("[{},{}]", event(1, 1), event(2, 2));
+                    socket.send(Message::Text(batch)).await.unwrap();
+                    let error = r#"{"error":"not a record"}"#.to_string();
+                    socket.send(Message::Text(error)).await.unwrap();
+                    socket.close(None).await.unwrap();
+                } else {
+                    socket.send(Message::Text(event(3, 3))).await.unwrap();
+                    std::future::pending::<()>().await;
+                }
+            }
+        });
+
+        let consumer = TurboConsumer::new(&format!("http://{addr}/"))
+            .unwrap()
+            .with_stream("posts")
+            .with_batch_ms(100)
+            .with_reconnect_delay(Duration::from_millis(10))
+            .with_user_agent("feed-indexer/1.0")
+            .unwrap();
+        let records: Vec<ConsumedRecord> = consumer
+            .subscribe()
+            .take(3)
+            .map(|record| record.unwrap())
+            .collect()
+            .await;
+
+        let ids: Vec<_> = records.iter().map(|record| record.event_id).collect();
+        assert_eq!(ids, vec![Some(1), Some(2), Some(3)]);
+        assert_eq!(records[2].record.get_did(), "did:plc:user0003");
+        assert_eq!(consumer.last_id(), Some(3));
+
+        let (first, user_agent) = requests.recv().await.unwrap();
+        assert_eq!(first, "/api/v1/ws/posts?format=enriched&batch_ms=100");
+        assert_eq!(user_agent, "feed-indexer/1.0");
+        let (second, _) = requests.recv().await.unwrap();
+        assert_eq!(
+            second,
+            "/api/v1/ws/posts?format=enriched&batch_ms=100&last_id=2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_stream_ends_with_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let _ = tokio_tungstenite::accept_hdr_async(
+                tcp,
+                |_: &Request, _: Response| -> Result<Response, ErrorResponse> {
+                    Err(http::Response::builder()
+                        .status(http::StatusCode::NOT_FOUND)
+                        .body(Some("Unknown output stream: nope".to_string()))
+                        .unwrap())
+                },
+            )
+            .await;
+        });
+
+        let consumer = TurboConsumer::new(&format!("ws://{addr}"))
+            .unwrap()
+            .with_stream("nope")
+            .with_last_id(7);
+        assert_eq!(
+            consumer.connect_url().as_str(),
+            format!("ws://{addr}/api/v1/ws/nope?format=enriched&last_id=7")
+        );
+        let mut records = consumer.subscribe();
+        assert!(matches!(
+            records.next().await,
+            Some(Err(TurboError::WebSocketConnection(_)))
+        ));
+        assert!(records.next().await.is_none());
+        assert!(TurboConsumer::new("ftp://example.com").is_err());
+    }
+}
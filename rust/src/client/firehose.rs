@@ -0,0 +1,526 @@
+use crate::client::http::{connect_websocket, user_agent_header, DEFAULT_USER_AGENT};
+use crate::client::jetstream::{
+    wait_for_pause_state, ConnectionState, DropLogState, MessageSource, DEFAULT_CHANNEL_CAPACITY,
+    DROP_LOG_INTERVAL,
+};
+use crate::models::{
+    errors::TurboError,
+    jetstream::{
+        AccountData, CommitData, IdentityData, JetstreamMessage, MessageKind, OperationType,
+    },
+    TurboResult,
+};
+use crate::telemetry::{DropCounters, DropReason};
+use crate::utils::dagcbor::{read_car_blocks, CborValue, Decoder};
+use futures::{Stream, StreamExt};
+use reqwest::header::HeaderValue;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::sleep;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, info, trace, warn};
+
+/// Consumes `com.atproto.sync.subscribeRepos` directly from a relay or PDS and
+/// converts each repo operation into the same `JetstreamMessage` the Jetstream
+/// client produces.
+pub struct FirehoseClient {
+    endpoints: Vec<String>,
+    wanted_collections: Vec<String>,
+    max_reconnect_attempts: u32,
+    reconnect_delay: Duration,
+    channel_capacity: usize,
+    cursor: Arc<AtomicU64>,
+    paused: watch::Sender<bool>,
+    drops: DropCounters,
+    user_agent: HeaderValue,
+}
+
+impl FirehoseClient {
+    pub fn new(endpoints: Vec<String>, wanted_collections: String) -> Self {
+        Self {
+            endpoints,
+            wanted_collections: wanted_collections
+                .split(',')
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect(),
+            max_reconnect_attempts: 10,
+            reconnect_delay: Duration::from_secs(5),
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            cursor: Arc::new(AtomicU64::new(0)),
+            paused: watch::channel(false).0,
+            drops: DropCounters::new(),
+            user_agent: HeaderValue::from_static(DEFAULT_USER_AGENT),
+        }
+    }
+
+    /// `User-Agent` sent with the WebSocket handshake.
+    pub fn with_user_agent(mut self, user_agent: &str) -> TurboResult<Self> {
+        self.user_agent = user_agent_header(user_agent)?;
+        Ok(self)
+    }
+
+    pub fn with_channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = capacity;
+        self
+    }
+
+    /// Sequence number of the last frame received; reconnects resume from here.
+    pub fn cursor(&self) -> Option<u64> {
+        Some(self.cursor.load(Ordering::Relaxed)).filter(|seq| *seq > 0)
+    }
+
+    pub fn decode_frame(&self, frame: &[u8]) -> TurboResult<Vec<JetstreamMessage>> {
+        decode_frame(frame, &self.wanted_collections, &self.drops)
+    }
+}
+
+impl MessageSource for FirehoseClient {
+    fn drop_counters(&self) -> Option<&DropCounters> {
+        Some(&self.drops)
+    }
+
+    fn set_paused(&self, paused: bool) -> bool {
+        self.paused.send_replace(paused);
+        true
+    }
+
+    fn connection_state(&self) -> Option<ConnectionState> {
+        self.paused.borrow().then_some(ConnectionState::Paused)
+    }
+
+    async fn stream_messages(
+        &self,
+    ) -> TurboResult<Pin<Box<dyn Stream<Item = TurboResult<JetstreamMessage>> + Send>>> {
+        let (tx, rx) = mpsc::channel(self.channel_capacity);
+
+        let endpoints = self.endpoints.clone();
+        let wanted_collections = self.wanted_collections.clone();
+        let max_reconnect_attempts = self.max_reconnect_attempts;
+        let reconnect_delay = self.reconnect_delay;
+        let user_agent = self.user_agent.clone();
+        let cursor = Arc::clone(&self.cursor);
+        let drops = self.drops.clone();
+        let mut paused = self.paused.subscribe();
+
+        tokio::spawn(async move {
+            let mut current_endpoint = 0;
+            let mut reconnect_attempts = 0;
+            let mut drop_log_state = DropLogState::new();
+            let mut drop_log_interval = tokio::time::interval(DROP_LOG_INTERVAL);
+
+            drop_log_interval.tick().await;
+
+            loop {
+                if *paused.borrow() {
+                    info!(
+                        "Ingestion paused; will resume from seq {}",
+                        cursor.load(Ordering::Relaxed)
+                    );
+                    wait_for_pause_state(&mut paused, false).await;
+                    info!("Ingestion resumed");
+                }
+
+                let endpoint = &endpoints[current_endpoint];
+                let mut url = format!("wss://{endpoint}/xrpc/com.atproto.sync.subscribeRepos");
+                let resume_from = cursor.load(Ordering::Relaxed);
+                if resume_from > 0 {
+                    url.push_str(&format!("?cursor={resume_from}"));
+                }
+
+                info!("Connecting to firehose endpoint: {}", endpoint);
+
+                match connect_websocket(&url, &user_agent).await {
+                    Ok((ws_stream, _)) => {
+                        info!("Successfully connected to {}", endpoint);
+                        reconnect_attempts = 0;
+
+                        let (_, mut read) = ws_stream.split();
+
+                        loop {
+                            tokio::select! {
+                                _ = wait_for_pause_state(&mut paused, true) => {
+                                    info!("Disconnecting from {} to pause ingestion", endpoint);
+                                    break;
+                                }
+                                _ = drop_log_interval.tick() => {
+                                    if let Some((dropped_since_last_log, dropped_total)) =
+                                        drop_log_state.take_snapshot()
+                                    {
+                                        warn!(
+                                            dropped_since_last_log,
+                                            dropped_total,
+                                            channel_capacity = tx.max_capacity(),
+                                            endpoint,
+                                            "Firehose input channel saturated; dropping messages"
+                                        );
+                                    }
+                                }
+                                msg_result = read.next() => {
+                                    let Some(msg_result) = msg_result else {
+                                        break;
+                                    };
+
+                                    match msg_result {
+                                        Ok(Message::Binary(frame)) => {
+                                            let messages =
+                                                match decode_frame(&frame, &wanted_collections, &drops) {
+                                                    Ok(messages) => messages,
+                                                    Err(e) => {
+                                                        warn!("Failed to decode firehose frame: {}", e);
+                                                        drops.record(DropReason::ParseError, 1);
+                                                        continue;
+                                                    }
+                                                };
+                                            for message in messages {
+                                                if let Some(seq) = message.seq {
+                                                    cursor.fetch_max(seq, Ordering::Relaxed);
+                                                }
+                                                match tx.try_send(Ok(message)) {
+                                                    Ok(()) => {
+                                                        if let Some(dropped_total) =
+                                                            drop_log_state.mark_recovered()
+                                                        {
+                                                            info!(
+                                                                dropped_total,
+                                                                endpoint,
+                                                                "Firehose input channel recovered"
+                                                            );
+                                                        }
+                                                    }
+                                                    Err(mpsc::error::TrySendError::Full(_)) => {
+                                                        drop_log_state.record_drop();
+                                                        drops.record(DropReason::ChannelFull, 1);
+                                                    }
+                                                    Err(mpsc::error::TrySendError::Closed(_)) => {
+                                                        info!("Receiver dropped, stopping stream");
+                                                        return;
+                                                    }
+                                                }
+                                            }
+                                        }
+                                        Ok(Message::Close(_)) => {
+                                            info!("WebSocket connection closed by server");
+                                            break;
+                                        }
+                                        Ok(_) => {
+                                            trace!("Ignoring non-binary firehose message");
+                                        }
+                                        Err(e) => {
+                                            error!("WebSocket error: {}", e);
+                                            break;
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to connect to {}: {}", endpoint, e);
+
+                        reconnect_attempts += 1;
+                        if reconnect_attempts >= max_reconnect_attempts {
+                            error!("Max reconnection attempts reached");
+                            let err = Err(TurboError::WebSocketConnection(format!(
+                                "Failed to connect after {max_reconnect_attempts} attempts"
+                            )));
+                            match tx.try_send(err) {
+                                Ok(()) | Err(mpsc::error::TrySendError::Full(_)) => {}
+                                Err(mpsc::error::TrySendError::Closed(_)) => return,
+                            }
+                            break;
+                        }
+                    }
+                }
+
+                if *paused.borrow() {
+                    continue;
+                }
+                current_endpoint = (current_endpoint + 1) % endpoints.len();
+                if endpoints.len() == 1 {
+                    sleep(reconnect_delay).await;
+                } else {
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+        });
+
+        Ok(Box::pin(ReceiverStream::new(rx)))
+    }
+}
+
+/// Decodes one event-stream frame (a DAG-CBOR header followed by a DAG-CBOR body).
+/// Commits expand to one message per repo operation; frame types that have no
+/// Jetstream equivalent yield no messages.
+pub fn decode_frame(
+    frame: &[u8],
+    wanted_collections: &[String],
+    drops: &DropCounters,
+) -> TurboResult<Vec<JetstreamMessage>> {
+    let mut decoder = Decoder::new(frame);
+    let header = decoder.decode()?;
+    let body = decoder.decode()?;
+
+    if header.get("op").and_then(CborValue::as_i64) == Some(-1) {
+        let error = body.get("error").and_then(CborValue::as_str).unwrap_or("");
+        let message = body
+            .get("message")
+            .and_then(CborValue::as_str)
+            .unwrap_or("");
+        return Err(TurboError::WebSocketConnection(format!(
+            "Firehose error frame: {error} {message}"
+        )));
+    }
+
+    let seq = body.get("seq").and_then(CborValue::as_u64);
+    let did = body
+        .get("repo")
+        .or_else(|| body.get("did"))
+        .and_then(CborValue::as_str)
+        .unwrap_or_default()
+        .to_string();
+    if did.is_empty() {
+        return Ok(Vec::new());
+    }
+    let time_us = body
+        .get("time")
+        .and_then(CborValue::as_str)
+        .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+        .and_then(|time| u64::try_from(time.timestamp_micros()).ok());
+    let optional_str = |key: &str| body.get(key).and_then(CborValue::as_str).map(String::from);
+
+    let message = |kind| JetstreamMessage {
+        did: did.clone(),
+        time_us,
+        seq,
+        kind,
+        commit: None,
+        identity: None,
+        account: None,
+        extra: Default::default(),
+    };
+
+    match header.get("t").and_then(CborValue::as_str) {
+        Some("#commit") => decode_commit(&body, wanted_collections, drops, message),
+        Some("#identity") => Ok(vec![JetstreamMessage {
+            identity: Some(IdentityData {
+                did: did.clone(),
+                handle: optional_str("handle"),
+                seq,
+                time: optional_str("time"),
+                extra: Default::default(),
+            }),
+            ..message(MessageKind::Identity)
+        }]),
+        Some("#account") => Ok(vec![JetstreamMessage {
+            account: Some(AccountData {
+                did: did.clone(),
+                active: body
+                    .get("active")
+                    .and_then(CborValue::as_bool)
+                    .unwrap_or(true),
+                status: optional_str("status"),
+                seq,
+                time: optional_str("time"),
+                extra: Default::default(),
+            }),
+            ..message(MessageKind::Account)
+        }]),
+        other => {
+            trace!("Ignoring firehose frame type {:?}", other);
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn decode_commit(
+    body: &CborValue<'_>,
+    wanted_collections: &[String],
+    drops: &DropCounters,
+    message: impl Fn(MessageKind) -> JetstreamMessage,
+) -> TurboResult<Vec<JetstreamMessage>> {
+    let rev = body
+        .get("rev")
+        .and_then(CborValue::as_str)
+        .map(String::from);
+    let blocks = match body.get("blocks").and_then(CborValue::as_bytes) {
+        Some(car) if !car.is_empty() => read_car_blocks(car)?,
+        _ => Default::default(),
+    };
+
+    let mut messages = Vec::new();
+    for op in body.get("ops").and_then(CborValue::as_array).unwrap_or(&[]) {
+        let Some((collection, rkey)) = op
+            .get("path")
+            .and_then(CborValue::as_str)
+            .and_then(|path| path.split_once('/'))
+        else {
+            drops.record(DropReason::MissingCollection, 1);
+            continue;
+        };
+        if !is_wanted_collection(collection, wanted_collections) {
+            continue;
+        }
+
+        let operation_type = match op.get("action").and_then(CborValue::as_str) {
+            Some("create") => OperationType::Create,
+            Some("update") => OperationType::Update,
+            Some("delete") => OperationType::Delete,
+            _ => OperationType::Unknown,
+        };
+        let cid = op.get("cid").and_then(CborValue::as_link).map(String::from);
+        let record = match cid.as_deref().and_then(|cid| blocks.get(cid)) {
+            Some(block) => Some(Decoder::new(block).decode()?.to_json()),
+            None => None,
+        };
+
+        messages.push(JetstreamMessage {
+            commit: Some(CommitData {
+                rev: rev.clone(),
+                operation_type,
+                collection: Some(collection.to_string()),
+                rkey: Some(rkey.to_string()),
+                record,
+                cid,
+                extra: Default::default(),
+            }),
+            ..message(MessageKind::Commit)
+        });
+    }
+    Ok(messages)
+}
+
+/// Matches Jetstream's `wantedCollections` semantics, including `prefix.*` wildcards.
+/// An empty list accepts everything.
+fn is_wanted_collection(collection: &str, wanted_collections: &[String]) -> bool {
+    wanted_collections.is_empty()
+        || wanted_collections
+            .iter()
+            .any(|wanted| match wanted.strip_suffix('*') {
+                Some(prefix) => collection.starts_with(prefix),
+                None => collection == wanted,
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(out: &mut Vec<u8>, s: &str) {
+        assert!(s.len() < 24);
+        out.push(0x60 | s.len() as u8);
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    fn cid_bytes() -> Vec<u8> {
+        let mut cid = vec![0x01, 0x71, 0x12, 0x20];
+        cid.extend_from_slice(&[0x2a; 32]);
+        cid
+    }
+
+    fn commit_frame(collection: &str) -> Vec<u8> {
+        let cid = cid_bytes();
+
+        // Record block: {"text": "hello"}
+        let mut record = vec![0xa1];
+        text(&mut record, "text");
+        text(&mut record, "hello");
+
+        let mut car = vec![0x01, 0xa0];
+        car.push((cid.len() + record.len()) as u8);
+        car.extend_from_slice(&cid);
+        car.extend_from_slice(&record);
+
+        // Header: {"op": 1, "t": "#commit"}
+        let mut frame = vec![0xa2];
+        text(&mut frame, "op");
+        frame.push(0x01);
+        text(&mut frame, "t");
+        text(&mut frame, "#commit");
+
+        // Body: {"seq", "repo", "rev", "time", "blocks", "ops"}
+        frame.push(0xa6);
+        text(&mut frame, "seq");
+        frame.extend_from_slice(&[0x19, 0x30, 0x39]);
+        text(&mut frame, "repo");
+        text(&mut frame, "did:plc:firehose");
+        text(&mut frame, "rev");
+        text(&mut frame, "3kabc");
+        text(&mut frame, "time");
+        let time = "2024-09-05T06:11:04.870Z";
+        frame.extend_from_slice(&[0x78, time.len() as u8]);
+        frame.extend_from_slice(time.as_bytes());
+        text(&mut frame, "blocks");
+        frame.extend_from_slice(&[0x58, car.len() as u8]);
+        frame.extend_from_slice(&car);
+        text(&mut frame, "ops");
+        frame.push(0x81);
+        frame.push(0xa3);
+        text(&mut frame, "action");
+        text(&mut frame, "create");
+        text(&mut frame, "path");
+        let path = format!("{collection}/3kpost");
+        frame.extend_from_slice(&[0x78, path.len() as u8]);
+        frame.extend_from_slice(path.as_bytes());
+        text(&mut frame, "cid");
+        frame.extend_from_slice(&[0xd8, 42, 0x58, (cid.len() + 1) as u8, 0x00]);
+        frame.extend_from_slice(&cid);
+        frame
+    }
+
+    #[test]
+    fn test_decode_commit_frame_into_jetstream_message() {
+        let client = FirehoseClient::new(
+            vec!["bsky.network".to_string()],
+            "app.bsky.feed.post".to_string(),
+        );
+        let messages = client
+            .decode_frame(&commit_frame("app.bsky.feed.post"))
+            .unwrap();
+
+        assert_eq!(messages.len(), 1);
+        let message = &messages[0];
+        assert_eq!(message.did, "did:plc:firehose");
+        assert_eq!(message.seq, Some(12345));
+        assert_eq!(message.time_us, Some(1_725_516_664_870_000));
+        assert!(message.is_create_operation());
+        assert_eq!(
+            message.extract_at_uri().as_deref(),
+            Some("at://did:plc:firehose/app.bsky.feed.post/3kpost")
+        );
+        let commit = message.commit.as_ref().unwrap();
+        assert_eq!(commit.rev.as_deref(), Some("3kabc"));
+        assert!(commit.cid.as_deref().unwrap().starts_with("bafyrei"));
+        assert_eq!(commit.record.as_ref().unwrap()["text"], "hello");
+
+        let filtered = client
+            .decode_frame(&commit_frame("app.bsky.feed.like"))
+            .unwrap();
+        assert!(filtered.is_empty());
+    }
+
+    #[test]
+    fn test_wanted_collection_wildcards() {
+        let wanted = vec!["app.bsky.graph.*".to_string()];
+        assert!(is_wanted_collection("app.bsky.graph.follow", &wanted));
+        assert!(!is_wanted_collection("app.bsky.feed.post", &wanted));
+        assert!(is_wanted_collection("app.bsky.feed.post", &[]));
+    }
+
+    #[test]
+    fn test_decode_error_frame() {
+        // {"op": -1} {"error": "FutureCursor"}
+        let mut frame = vec![0xa1];
+        text(&mut frame, "op");
+        frame.push(0x20);
+        frame.push(0xa1);
+        text(&mut frame, "error");
+        text(&mut frame, "FutureCursor");
+        assert!(decode_frame(&frame, &[], &DropCounters::new()).is_err());
+    }
+}
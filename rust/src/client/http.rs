@@ -0,0 +1,251 @@
+//! Timeouts and retry policy for the outbound HTTP clients. Each client has
+//! its own defaults; `HTTP_*` settings override them for every client and
+//! `HTTP_CLIENT_OVERRIDES` for one, for deployments on slow or flaky links.
+//!
+//! Every outbound request, WebSocket handshakes included, is built here so
+//! they all carry the same `User-Agent`.
+
+use crate::models::errors::{TurboError, TurboResult};
+use reqwest::header::HeaderValue;
+use reqwest::{Client, ClientBuilder};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Response;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// Identifies requests unless `HTTP_USER_AGENT` replaces it.
+pub const DEFAULT_USER_AGENT: &str = concat!("jetstream-turbo/", env!("CARGO_PKG_VERSION"));
+
+/// `product` (or `DEFAULT_USER_AGENT`) followed by the operator's contact URL
+/// as a `(+url)` comment, so the services this instance calls know whom to
+/// reach about its traffic, as AT Protocol operators ask of crawlers.
+pub fn user_agent(product: Option<&str>, contact_url: Option<&str>) -> String {
+    let product = product.unwrap_or(DEFAULT_USER_AGENT);
+    match contact_url {
+        Some(url) => format!("{product} (+{url})"),
+        None => product.to_string(),
+    }
+}
+
+/// The starting point of every outbound HTTP client.
+pub fn client_builder(user_agent: &str) -> ClientBuilder {
+    Client::builder().user_agent(user_agent)
+}
+
+/// Fails on characters a header can't carry.
+pub fn user_agent_header(user_agent: &str) -> TurboResult<HeaderValue> {
+    HeaderValue::from_str(user_agent)
+        .map_err(|_| TurboError::InvalidMessage(format!("invalid user agent: {user_agent:?}")))
+}
+
+/// Opens a WebSocket to `url`, sending `user_agent` with the handshake.
+pub(crate) async fn connect_websocket(
+    url: &str,
+    user_agent: &HeaderValue,
+) -> Result<
+    (
+        WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
+        Response,
+    ),
+    tokio_tungstenite::tungstenite::Error,
+> {
+    let mut request = url.into_client_request()?;
+    request
+        .headers_mut()
+        .insert(reqwest::header::USER_AGENT, user_agent.clone());
+    tokio_tungstenite::connect_async(request).await
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpPolicy {
+    pub connect_timeout: Duration,
+    /// Whole request, including the body
+    pub request_timeout: Duration,
+    pub max_retries: u32,
+    pub retry_base_delay: Duration,
+    /// Longest computed backoff; a server's `Retry-After` is still honored
+    pub max_backoff: Duration,
+    pub user_agent: Cow<'static, str>,
+}
+
+impl HttpPolicy {
+    /// Profile and post hydration
+    pub const BLUESKY: HttpPolicy = HttpPolicy {
+        connect_timeout: Duration::from_secs(10),
+        request_timeout: Duration::from_secs(30),
+        max_retries: 3,
+        retry_base_delay: Duration::from_millis(200),
+        max_backoff: Duration::from_secs(30),
+        user_agent: Cow::Borrowed(DEFAULT_USER_AGENT),
+    };
+
+    /// Session creation
+    pub const AUTH: HttpPolicy = HttpPolicy {
+        connect_timeout: Duration::from_secs(10),
+        request_timeout: Duration::from_secs(10),
+        max_retries: 3,
+        retry_base_delay: Duration::from_millis(500),
+        max_backoff: Duration::from_secs(30),
+        user_agent: Cow::Borrowed(DEFAULT_USER_AGENT),
+    };
+
+    /// Repo backfill and record lookups
+    pub const BACKFILL: HttpPolicy = HttpPolicy {
+        connect_timeout: Duration::from_secs(10),
+        request_timeout: Duration::from_secs(30),
+        max_retries: 3,
+        retry_base_delay: Duration::from_millis(500),
+        max_backoff: Duration::from_secs(30),
+        user_agent: Cow::Borrowed(DEFAULT_USER_AGENT),
+    };
+
+    pub fn with_overrides(self, overrides: &HttpPolicyOverrides) -> Self {
+        let millis =
+            |value: Option<u64>, default: Duration| value.map_or(default, Duration::from_millis);
+        Self {
+            connect_timeout: millis(overrides.connect_timeout_ms, self.connect_timeout),
+            request_timeout: millis(overrides.request_timeout_ms, self.request_timeout),
+            max_retries: overrides.max_retries.unwrap_or(self.max_retries),
+            retry_base_delay: millis(overrides.retry_base_delay_ms, self.retry_base_delay),
+            max_backoff: millis(overrides.max_backoff_ms, self.max_backoff),
+            user_agent: self.user_agent,
+        }
+    }
+
+    pub fn with_user_agent(mut self, user_agent: impl Into<Cow<'static, str>>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// A client builder with this policy's timeouts and user agent.
+    pub fn client_builder(&self) -> ClientBuilder {
+        client_builder(&self.user_agent)
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.request_timeout)
+    }
+
+    /// Exponential backoff before retry `attempt`, capped at `max_backoff`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.retry_base_delay
+            .saturating_mul(2u32.pow(attempt.min(16)))
+            .min(self.max_backoff)
+    }
+
+    /// Linear delay before retry `attempt` after a transport error, capped at
+    /// `max_backoff`.
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        self.retry_base_delay
+            .saturating_mul(attempt)
+            .min(self.max_backoff)
+    }
+
+    pub fn validate(&self) -> TurboResult<()> {
+        if self.connect_timeout.is_zero() || self.request_timeout.is_zero() {
+            return Err(config_error("HTTP timeouts must be greater than 0"));
+        }
+        if self.max_backoff < self.retry_base_delay {
+            return Err(config_error(
+                "HTTP max backoff must not be less than the retry base delay",
+            ));
+        }
+        user_agent_header(&self.user_agent)
+            .map_err(|_| config_error(format!("invalid user agent: {:?}", self.user_agent)))?;
+        Ok(())
+    }
+}
+
+fn config_error(message: impl Into<String>) -> TurboError {
+    TurboError::Configuration(config::ConfigError::Message(message.into()))
+}
+
+/// Policy fields to replace; unset ones keep the client's default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpPolicyOverrides {
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub request_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    #[serde(default)]
+    pub retry_base_delay_ms: Option<u64>,
+    #[serde(default)]
+    pub max_backoff_ms: Option<u64>,
+}
+
+/// Overrides for a single client, applied over the shared `HTTP_*` ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpClientOverrides {
+    #[serde(default)]
+    pub bluesky: HttpPolicyOverrides,
+    #[serde(default)]
+    pub auth: HttpPolicyOverrides,
+    #[serde(default)]
+    pub backfill: HttpPolicyOverrides,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_layer_over_client_defaults() {
+        let shared = HttpPolicyOverrides {
+            request_timeout_ms: Some(60_000),
+            max_retries: Some(5),
+            ..Default::default()
+        };
+        let clients: HttpClientOverrides =
+            serde_json::from_str(r#"{"auth": {"max_retries": 1, "max_backoff_ms": 1000}}"#)
+                .unwrap();
+
+        let auth = HttpPolicy::AUTH
+            .with_overrides(&shared)
+            .with_overrides(&clients.auth);
+        assert_eq!(auth.request_timeout, Duration::from_secs(60));
+        assert_eq!(auth.max_retries, 1);
+        assert_eq!(auth.retry_base_delay, Duration::from_millis(500));
+        assert_eq!(auth.backoff(0), Duration::from_millis(500));
+        assert_eq!(auth.backoff(4), Duration::from_secs(1));
+        assert_eq!(auth.retry_delay(3), Duration::from_secs(1));
+
+        let bluesky = HttpPolicy::BLUESKY
+            .with_overrides(&shared)
+            .with_overrides(&clients.bluesky);
+        assert_eq!(bluesky.max_retries, 5);
+        assert_eq!(bluesky.connect_timeout, Duration::from_secs(10));
+        assert!(bluesky.validate().is_ok());
+        assert!(matches!(
+            HttpPolicy::BLUESKY
+                .with_overrides(&HttpPolicyOverrides {
+                    max_backoff_ms: Some(10),
+                    ..Default::default()
+                })
+                .validate(),
+            Err(TurboError::Configuration(_))
+        ));
+        assert!(serde_json::from_str::<HttpClientOverrides>(r#"{"graze": {}}"#).is_err());
+    }
+
+    #[test]
+    fn test_user_agent_carries_operator_contact() {
+        assert_eq!(user_agent(None, None), DEFAULT_USER_AGENT);
+        assert_eq!(
+            user_agent(Some("feedgen/2.1"), Some("https://example.com/contact")),
+            "feedgen/2.1 (+https://example.com/contact)"
+        );
+        let policy = HttpPolicy::BACKFILL
+            .with_user_agent(user_agent(None, Some("mailto:ops@example.com")))
+            .with_overrides(&HttpPolicyOverrides::default());
+        assert!(policy.user_agent.ends_with(" (+mailto:ops@example.com)"));
+        assert!(policy.validate().is_ok());
+        assert!(matches!(
+            HttpPolicy::AUTH.with_user_agent("line\nbreak").validate(),
+            Err(TurboError::Configuration(_))
+        ));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::client::{
+    ConnectionState, FirehoseClient, JetstreamClient, MessageSource, ReplaySource,
+};
+use crate::models::{jetstream::JetstreamMessage, TurboResult};
+use crate::telemetry::DropCounters;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+
+/// Which upstream the pipeline reads repo events from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IngestMode {
+    /// JSON events from a Jetstream instance
+    #[default]
+    Jetstream,
+    /// Raw `com.atproto.sync.subscribeRepos` frames from a relay
+    Firehose,
+    /// Frames previously written by `CAPTURE_DIR`, read back from disk
+    Replay,
+}
+
+/// The production message source, selected at startup by `IngestMode`.
+pub enum IngestSource {
+    Jetstream(JetstreamClient),
+    Firehose(FirehoseClient),
+    Replay(ReplaySource),
+}
+
+impl MessageSource for IngestSource {
+    async fn stream_messages(
+        &self,
+    ) -> TurboResult<Pin<Box<dyn Stream<Item = TurboResult<JetstreamMessage>> + Send>>> {
+        match self {
+            IngestSource::Jetstream(client) => client.stream_messages().await,
+            IngestSource::Firehose(client) => client.stream_messages().await,
+            IngestSource::Replay(source) => source.stream_messages().await,
+        }
+    }
+
+    fn drop_counters(&self) -> Option<&DropCounters> {
+        match self {
+            IngestSource::Jetstream(client) => client.drop_counters(),
+            IngestSource::Firehose(client) => client.drop_counters(),
+            IngestSource::Replay(source) => source.drop_counters(),
+        }
+    }
+
+    fn connection_state(&self) -> Option<ConnectionState> {
+        match self {
+            IngestSource::Jetstream(client) => client.connection_state(),
+            IngestSource::Firehose(client) => client.connection_state(),
+            IngestSource::Replay(source) => source.connection_state(),
+        }
+    }
+
+    fn set_paused(&self, paused: bool) -> bool {
+        match self {
+            IngestSource::Jetstream(client) => client.set_paused(paused),
+            IngestSource::Firehose(client) => client.set_paused(paused),
+            IngestSource::Replay(source) => source.set_paused(paused),
+        }
+    }
+}
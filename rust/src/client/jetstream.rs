@@ -1,11 +1,20 @@
+use crate::client::capture::FrameCapture;
+use crate::client::http::{connect_websocket, user_agent_header, DEFAULT_USER_AGENT};
+use crate::client::probe::{EndpointProber, EndpointSelector, DEFAULT_PROBE_TIMEOUT};
 use crate::models::{errors::TurboError, jetstream::JetstreamMessage, TurboResult};
+use crate::telemetry::{DropCounters, DropReason, UnknownFieldTracker};
 use futures::{Stream, StreamExt};
+use metrics::gauge;
+use reqwest::header::HeaderValue;
+use std::collections::BTreeMap;
 use std::pin::Pin;
-use std::time::Duration;
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
 use tokio::time::sleep;
 use tokio_stream::wrappers::ReceiverStream;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_tungstenite::tungstenite::Message;
 use tracing::{error, info, trace, warn};
 
 pub trait MessageSource {
@@ -14,20 +23,51 @@ pub trait MessageSource {
     ) -> impl std::future::Future<
         Output = TurboResult<Pin<Box<dyn Stream<Item = TurboResult<JetstreamMessage>> + Send>>>,
     > + Send;
+
+    /// Messages the source discarded before handing them over, for sources
+    /// that count them.
+    fn drop_counters(&self) -> Option<&DropCounters> {
+        None
+    }
+
+    /// Whether the upstream connection is currently up, for sources that hold one.
+    fn connection_state(&self) -> Option<ConnectionState> {
+        None
+    }
+
+    /// Disconnects from upstream while paused and reconnects from the saved
+    /// cursor on resume. Returns false for sources that can't pause.
+    fn set_paused(&self, _paused: bool) -> bool {
+        false
+    }
 }
 
-const DEFAULT_CHANNEL_CAPACITY: usize = 10_000;
-const DROP_LOG_INTERVAL: Duration = Duration::from_secs(30);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// Down (or not yet up) for this long
+    Disconnected(Duration),
+    /// Disconnected on purpose by `set_paused`
+    Paused,
+}
+
+pub(crate) const DEFAULT_CHANNEL_CAPACITY: usize = 10_000;
+pub(crate) const DROP_LOG_INTERVAL: Duration = Duration::from_secs(30);
+const PARSE_ERROR_PREVIEW_BYTES: usize = 200;
+const DEFAULT_PARSE_WORKERS: usize = 2;
+/// How far before the last seen event a reconnect resumes, so frames lost with the
+/// old socket are replayed. The sinks drop the resulting duplicates.
+const CURSOR_REWIND: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
-struct DropLogState {
+pub(crate) struct DropLogState {
     dropped_since_last_log: u64,
     dropped_total: u64,
     in_backpressure: bool,
 }
 
 impl DropLogState {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             dropped_since_last_log: 0,
             dropped_total: 0,
@@ -35,13 +75,13 @@ impl DropLogState {
         }
     }
 
-    fn record_drop(&mut self) {
+    pub(crate) fn record_drop(&mut self) {
         self.dropped_since_last_log += 1;
         self.dropped_total += 1;
         self.in_backpressure = true;
     }
 
-    fn take_snapshot(&mut self) -> Option<(u64, u64)> {
+    pub(crate) fn take_snapshot(&mut self) -> Option<(u64, u64)> {
         if self.dropped_since_last_log == 0 {
             return None;
         }
@@ -53,7 +93,7 @@ impl DropLogState {
         Some((dropped_since_last_log, dropped_total))
     }
 
-    fn mark_recovered(&mut self) -> Option<u64> {
+    pub(crate) fn mark_recovered(&mut self) -> Option<u64> {
         if !self.in_backpressure {
             return None;
         }
@@ -69,6 +109,16 @@ pub struct JetstreamClient {
     max_reconnect_attempts: u32,
     reconnect_delay: Duration,
     channel_capacity: usize,
+    parse_workers: usize,
+    unknown_fields: Arc<UnknownFieldTracker>,
+    drops: DropCounters,
+    capture: Option<Arc<FrameCapture>>,
+    probe_interval: Option<Duration>,
+    cursor: Arc<AtomicU64>,
+    /// When the connection last went down; `None` while connected
+    disconnected_since: Arc<Mutex<Option<Instant>>>,
+    paused: watch::Sender<bool>,
+    user_agent: HeaderValue,
 }
 
 impl JetstreamClient {
@@ -79,9 +129,24 @@ impl JetstreamClient {
             max_reconnect_attempts: 10,
             reconnect_delay: Duration::from_secs(5),
             channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            parse_workers: DEFAULT_PARSE_WORKERS,
+            unknown_fields: Arc::new(UnknownFieldTracker::new()),
+            drops: DropCounters::new(),
+            capture: None,
+            probe_interval: None,
+            cursor: Arc::new(AtomicU64::new(0)),
+            disconnected_since: Arc::new(Mutex::new(Some(Instant::now()))),
+            paused: watch::channel(false).0,
+            user_agent: HeaderValue::from_static(DEFAULT_USER_AGENT),
         }
     }
 
+    /// `User-Agent` sent with the WebSocket handshake.
+    pub fn with_user_agent(mut self, user_agent: &str) -> TurboResult<Self> {
+        self.user_agent = user_agent_header(user_agent)?;
+        Ok(self)
+    }
+
     pub fn with_defaults(endpoints: Vec<String>) -> Self {
         Self::new(endpoints, "app.bsky.feed.post".to_string())
     }
@@ -91,12 +156,96 @@ impl JetstreamClient {
         self
     }
 
+    /// Number of frames parsed concurrently off the reader task.
+    pub fn with_parse_workers(mut self, workers: usize) -> Self {
+        self.parse_workers = workers.max(1);
+        self
+    }
+
+    /// Writes every received frame to capture files for later replay.
+    pub fn with_capture(mut self, capture: FrameCapture) -> Self {
+        self.capture = Some(Arc::new(capture));
+        self
+    }
+
+    /// Probes every endpoint before connecting and connects to the fastest
+    /// healthy one, re-probing on reconnect once the ranking is older than `interval`.
+    pub fn with_endpoint_probing(mut self, interval: Duration) -> Self {
+        self.probe_interval = Some(interval);
+        self
+    }
+
+    /// `time_us` of the newest event received; reconnects resume shortly before it.
+    pub fn cursor(&self) -> Option<u64> {
+        Some(self.cursor.load(Ordering::Relaxed)).filter(|time_us| *time_us > 0)
+    }
+
+    /// Starts the first connection from `time_us` (less the rewind) instead of
+    /// live, e.g. from the newest stored event after a restart.
+    pub fn resume_from(&self, time_us: u64) {
+        self.cursor.fetch_max(time_us, Ordering::Relaxed);
+    }
+
     pub fn parse_message(&self, text: &str) -> TurboResult<JetstreamMessage> {
+        parse_message(text.to_string())
+    }
+
+    /// Parses a frame in place, reusing the buffer handed over by the websocket.
+    pub fn parse_owned(&self, text: String) -> TurboResult<JetstreamMessage> {
         parse_message(text)
     }
+
+    /// Unrecognized payload fields seen so far, with counts.
+    pub fn unknown_fields(&self) -> Vec<(String, u64)> {
+        self.unknown_fields.snapshot()
+    }
+}
+
+/// Records a connection going up or down, keeping the original time across
+/// repeated failures so the outage is measured from when it began.
+fn set_connected(disconnected_since: &Mutex<Option<Instant>>, connected: bool) {
+    let mut since = disconnected_since
+        .lock()
+        .expect("connection state lock poisoned");
+    if connected {
+        *since = None;
+    } else if since.is_none() {
+        *since = Some(Instant::now());
+    }
+}
+
+/// Resolves once the pause flag equals `paused`. Never resolves if the flag's
+/// owner is gone, since it can no longer change.
+pub(crate) async fn wait_for_pause_state(paused_rx: &mut watch::Receiver<bool>, paused: bool) {
+    if paused_rx.wait_for(|state| *state == paused).await.is_err() {
+        std::future::pending::<()>().await;
+    }
 }
 
 impl MessageSource for JetstreamClient {
+    fn drop_counters(&self) -> Option<&DropCounters> {
+        Some(&self.drops)
+    }
+
+    fn set_paused(&self, paused: bool) -> bool {
+        self.paused.send_replace(paused);
+        true
+    }
+
+    fn connection_state(&self) -> Option<ConnectionState> {
+        if *self.paused.borrow() {
+            return Some(ConnectionState::Paused);
+        }
+        let since = *self
+            .disconnected_since
+            .lock()
+            .expect("connection state lock poisoned");
+        Some(match since {
+            None => ConnectionState::Connected,
+            Some(since) => ConnectionState::Disconnected(since.elapsed()),
+        })
+    }
+
     async fn stream_messages(
         &self,
     ) -> TurboResult<Pin<Box<dyn Stream<Item = TurboResult<JetstreamMessage>> + Send>>> {
@@ -107,9 +256,30 @@ impl MessageSource for JetstreamClient {
         let wanted_collections = self.wanted_collections.clone();
         let max_reconnect_attempts = self.max_reconnect_attempts;
         let reconnect_delay = self.reconnect_delay;
+        let user_agent = self.user_agent.clone();
+        let capture = self.capture.clone();
+        let probe_interval = self.probe_interval;
+        let cursor = Arc::clone(&self.cursor);
+        let disconnected_since = Arc::clone(&self.disconnected_since);
+        let mut paused = self.paused.subscribe();
+        let (raw_tx, raw_rx) = mpsc::channel(self.channel_capacity);
+        let parse_queue_depth = gauge!("jetstream_turbo_parse_queue_depth");
+        tokio::spawn(parse_frames(
+            raw_rx,
+            tx.clone(),
+            self.parse_workers,
+            Arc::clone(&self.unknown_fields),
+            self.drops.clone(),
+            Arc::clone(&cursor),
+        ));
+        let drops = self.drops.clone();
 
         tokio::spawn(async move {
-            let mut current_endpoint = 0;
+            let mut selector = EndpointSelector::new(
+                endpoints.clone(),
+                probe_interval.map(|_| EndpointProber::new(DEFAULT_PROBE_TIMEOUT)),
+                probe_interval.unwrap_or_default(),
+            );
             let mut reconnect_attempts = 0;
             let mut drop_log_state = DropLogState::new();
             let mut drop_log_interval = tokio::time::interval(DROP_LOG_INTERVAL);
@@ -117,22 +287,47 @@ impl MessageSource for JetstreamClient {
             drop_log_interval.tick().await;
 
             loop {
-                let endpoint = &endpoints[current_endpoint];
-                let url =
+                if *paused.borrow() {
+                    info!(
+                        "Ingestion paused; will resume from cursor {}",
+                        cursor.load(Ordering::Relaxed)
+                    );
+                    wait_for_pause_state(&mut paused, false).await;
+                    info!("Ingestion resumed");
+                    // Time spent paused doesn't count against the disconnect threshold
+                    *disconnected_since
+                        .lock()
+                        .expect("connection state lock poisoned") = Some(Instant::now());
+                }
+
+                let endpoint = &selector.next_endpoint().await;
+                let mut url =
                     format!("wss://{endpoint}/subscribe?wantedCollections={wanted_collections}");
+                let resume_from = cursor.load(Ordering::Relaxed);
+                if resume_from > 0 {
+                    let rewound = resume_from.saturating_sub(CURSOR_REWIND.as_micros() as u64);
+                    url.push_str(&format!("&cursor={rewound}"));
+                    info!("Resuming from cursor {}", rewound);
+                }
 
                 info!("Connecting to Jetstream endpoint: {}", endpoint);
 
-                match connect_async(&url).await {
+                match connect_websocket(&url, &user_agent).await {
                     Ok((ws_stream, _)) => {
                         info!("Successfully connected to {}", endpoint);
                         reconnect_attempts = 0; // Reset on successful connection
+                        selector.mark_connected();
+                        set_connected(&disconnected_since, true);
 
                         let (_, mut read) = ws_stream.split();
 
                         // Process messages
                         loop {
                             tokio::select! {
+                                _ = wait_for_pause_state(&mut paused, true) => {
+                                    info!("Disconnecting from {} to pause ingestion", endpoint);
+                                    break;
+                                }
                                 _ = drop_log_interval.tick() => {
                                     if let Some((dropped_since_last_log, dropped_total)) =
                                         drop_log_state.take_snapshot()
@@ -140,7 +335,7 @@ impl MessageSource for JetstreamClient {
                                         warn!(
                                             dropped_since_last_log,
                                             dropped_total,
-                                            channel_capacity = tx.max_capacity(),
+                                            channel_capacity = raw_tx.max_capacity(),
                                             endpoint,
                                             "Jetstream input channel saturated; dropping messages"
                                         );
@@ -154,34 +349,32 @@ impl MessageSource for JetstreamClient {
                                     match msg_result {
                                 Ok(Message::Text(text)) => {
                                     trace!("Received message: {}", text);
-                                    match parse_message(&text) {
-                                        Ok(message) => match tx.try_send(Ok(message)) {
-                                            Ok(()) => {
-                                                if let Some(dropped_total) =
-                                                    drop_log_state.mark_recovered()
-                                                {
-                                                    info!(
-                                                        dropped_total,
-                                                        endpoint,
-                                                        "Jetstream input channel recovered"
-                                                    );
-                                                }
-                                            }
-                                            Err(mpsc::error::TrySendError::Full(_)) => {
-                                                drop_log_state.record_drop();
-                                            }
-                                            Err(mpsc::error::TrySendError::Closed(_)) => {
-                                                info!("Receiver dropped, stopping stream");
-                                                return;
-                                            }
-                                        },
-                                        Err(e) => {
-                                            warn!(
-                                                "Failed to parse message: {:?}. Raw: {}",
-                                                e,
-                                                &text[..text.len().min(200)]
+                                    if let Some(capture) = &capture {
+                                        capture.record(&text);
+                                    }
+                                    // Hand the frame to the parse workers so this task only does socket I/O
+                                    match raw_tx.try_send(text) {
+                                        Ok(()) => {
+                                            parse_queue_depth.set(
+                                                (raw_tx.max_capacity() - raw_tx.capacity()) as f64,
                                             );
-                                            // Continue processing other messages
+                                            if let Some(dropped_total) =
+                                                drop_log_state.mark_recovered()
+                                            {
+                                                info!(
+                                                    dropped_total,
+                                                    endpoint,
+                                                    "Jetstream input channel recovered"
+                                                );
+                                            }
+                                        }
+                                        Err(mpsc::error::TrySendError::Full(_)) => {
+                                            drop_log_state.record_drop();
+                                            drops.record(DropReason::ChannelFull, 1);
+                                        }
+                                        Err(mpsc::error::TrySendError::Closed(_)) => {
+                                            info!("Receiver dropped, stopping stream");
+                                            return;
                                         }
                                     }
                                 }
@@ -229,8 +422,12 @@ impl MessageSource for JetstreamClient {
                     }
                 }
 
+                set_connected(&disconnected_since, false);
+                if *paused.borrow() {
+                    continue;
+                }
+
                 // Try next endpoint or wait before retry
-                current_endpoint = (current_endpoint + 1) % endpoints.len();
                 if endpoints.len() == 1 {
                     info!(
                         "Waiting {} seconds before reconnection attempt",
@@ -247,13 +444,99 @@ impl MessageSource for JetstreamClient {
     }
 }
 
-fn parse_message(text: &str) -> TurboResult<JetstreamMessage> {
+/// Parses raw frames on `workers` long-lived tasks and forwards the results in
+/// arrival order. Each worker numbers the frame it takes from the queue so the
+/// results can be put back in order. Waits for the consumer when it falls
+/// behind, which fills the raw frame queue and makes the reader drop frames
+/// instead of stalling the socket.
+async fn parse_frames(
+    raw_rx: mpsc::Receiver<String>,
+    tx: mpsc::Sender<TurboResult<JetstreamMessage>>,
+    workers: usize,
+    unknown_fields: Arc<UnknownFieldTracker>,
+    drops: DropCounters,
+    cursor: Arc<AtomicU64>,
+) {
+    let workers = workers.max(1);
+    // The frame counter sits under the queue's lock so numbers follow arrival order
+    let queue = Arc::new(tokio::sync::Mutex::new((raw_rx, 0u64)));
+    let (parsed_tx, mut parsed_rx) = mpsc::channel(workers);
+    for _ in 0..workers {
+        let queue = Arc::clone(&queue);
+        let parsed_tx = parsed_tx.clone();
+        tokio::spawn(async move {
+            loop {
+                let (seq, text) = {
+                    let mut queue = queue.lock().await;
+                    let (raw_rx, next_seq) = &mut *queue;
+                    let Some(text) = raw_rx.recv().await else {
+                        return;
+                    };
+                    let seq = *next_seq;
+                    *next_seq += 1;
+                    (seq, text)
+                };
+                let parsed = std::panic::catch_unwind(|| parse_frame(text)).unwrap_or_else(|_| {
+                    error!("Parse worker panicked on frame {}", seq);
+                    None
+                });
+                if parsed_tx.send((seq, parsed)).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    drop(parsed_tx);
+
+    let mut pending = BTreeMap::new();
+    let mut next_seq = 0u64;
+    while let Some((seq, parsed)) = parsed_rx.recv().await {
+        pending.insert(seq, parsed);
+        while let Some(parsed) = pending.remove(&next_seq) {
+            next_seq += 1;
+            let Some(message) = parsed else {
+                // Already logged by the worker
+                drops.record(DropReason::ParseError, 1);
+                continue;
+            };
+            unknown_fields.observe(&message);
+            if let Some(time_us) = message.time_us {
+                cursor.fetch_max(time_us, Ordering::Relaxed);
+            }
+            if tx.send(Ok(message)).await.is_err() {
+                info!("Receiver dropped, stopping parse workers");
+                return;
+            }
+        }
+    }
+}
+
+/// `parse_message`, logging the head of any frame that fails to parse.
+fn parse_frame(text: String) -> Option<JetstreamMessage> {
+    // simd-json parses in place, so keep a copy of the head for error logs
+    let mut preview = [0u8; PARSE_ERROR_PREVIEW_BYTES];
+    let preview_len = text.len().min(PARSE_ERROR_PREVIEW_BYTES);
+    preview[..preview_len].copy_from_slice(&text.as_bytes()[..preview_len]);
+    match parse_message(text) {
+        Ok(message) => Some(message),
+        Err(e) => {
+            warn!(
+                "Failed to parse message: {:?}. Raw: {}",
+                e,
+                String::from_utf8_lossy(&preview[..preview_len])
+            );
+            None
+        }
+    }
+}
+
+pub(crate) fn parse_message(text: String) -> TurboResult<JetstreamMessage> {
     // Use simd-json for faster parsing (2-4x faster than serde_json)
-    // simd-json requires mutable input and uses unsafe SIMD operations internally
-    // The library handles safety internally through careful validation
-    let mut text = text.to_string();
+    // simd-json parses in place, so take ownership of the frame's bytes rather than
+    // copying them; going through bytes also avoids the unsafe `from_str` entry point
+    let mut bytes = text.into_bytes();
     let message: JetstreamMessage =
-        unsafe { simd_json::from_str(&mut text).map_err(TurboError::JsonDeserialization)? };
+        simd_json::serde::from_slice(&mut bytes).map_err(TurboError::JsonDeserialization)?;
 
     // Validate required fields
     if message.did.is_empty() {
@@ -319,6 +602,40 @@ mod tests {
         let message = result.unwrap();
         assert_eq!(message.did, "did:plc:test");
         assert_eq!(message.seq, Some(12345));
+
+        let owned = client.parse_owned(valid_json.to_string()).unwrap();
+        assert_eq!(owned.did, message.did);
+        assert!(client.parse_owned("{\"did\": ".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_preserved_not_fatal() {
+        let client = JetstreamClient::with_defaults(vec!["test.bsky.network".to_string()]);
+
+        let drifted = r#"
+        {
+            "did": "did:plc:test",
+            "seq": "12345",
+            "time_us": 1640995200000000,
+            "kind": "sync",
+            "region": "us-east",
+            "commit": {
+                "operation": "create",
+                "collection": "app.bsky.feed.post",
+                "rkey": "test",
+                "prevData": {"$link": "bafyprev"}
+            }
+        }
+        "#;
+
+        let message = client.parse_message(drifted).unwrap();
+        assert_eq!(message.kind, crate::models::jetstream::MessageKind::Unknown);
+        assert_eq!(message.seq, Some(12345));
+        assert_eq!(message.unknown_fields(), vec!["region", "commit.prevData"]);
+
+        let round_trip = serde_json::to_value(&message).unwrap();
+        assert_eq!(round_trip["region"], "us-east");
+        assert_eq!(round_trip["commit"]["prevData"]["$link"], "bafyprev");
     }
 
     #[test]
@@ -347,6 +664,44 @@ mod tests {
         assert!(matches!(result.unwrap_err(), TurboError::InvalidMessage(_)));
     }
 
+    #[tokio::test]
+    async fn test_parse_frames_preserves_order_and_skips_bad_frames() {
+        let (raw_tx, raw_rx) = mpsc::channel(16);
+        let (tx, mut rx) = mpsc::channel(16);
+        let cursor = Arc::new(AtomicU64::new(0));
+        let drops = DropCounters::new();
+        let worker = tokio::spawn(parse_frames(
+            raw_rx,
+            tx,
+            4,
+            Arc::new(UnknownFieldTracker::new()),
+            drops.clone(),
+            Arc::clone(&cursor),
+        ));
+
+        for seq in 0..10 {
+            let frame = if seq == 3 {
+                "{\"did\": ".to_string()
+            } else {
+                format!(
+                    r#"{{"did": "did:plc:test", "seq": {seq}, "time_us": {}, "kind": "identity"}}"#,
+                    1000 + seq
+                )
+            };
+            raw_tx.send(frame).await.unwrap();
+        }
+        drop(raw_tx);
+        worker.await.unwrap();
+
+        let mut seqs = Vec::new();
+        while let Some(message) = rx.recv().await {
+            seqs.push(message.unwrap().seq.unwrap());
+        }
+        assert_eq!(seqs, vec![0, 1, 2, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(cursor.load(Ordering::Relaxed), 1009);
+        assert_eq!(drops.get(DropReason::ParseError), 1);
+    }
+
     #[test]
     fn test_drop_log_state_tracks_drops_and_recovery() {
         let mut state = DropLogState::new();
@@ -1,8 +1,32 @@
 pub mod auth;
+pub mod backfill;
 pub mod bluesky;
+pub mod budget;
+pub mod capture;
+pub mod consumer;
+pub mod firehose;
+pub mod http;
+pub mod ingest;
 pub mod jetstream;
+pub mod pause;
+pub mod plc;
 pub mod pool;
+pub mod probe;
+pub mod session;
 
 pub use auth::BlueskyAuthClient;
-pub use bluesky::{BlueskyClient, PostFetcher, ProfileFetcher};
-pub use jetstream::{JetstreamClient, MessageSource};
+pub use backfill::BackfillClient;
+pub use bluesky::{
+    BatchFillStats, BlueskyClient, CollectorBatchStats, PostFetcher, ProfileFetcher,
+};
+pub use budget::RequestBudget;
+pub use capture::{read_capture, FrameCapture, ReplaySource};
+pub use consumer::{ConsumedRecord, TurboConsumer};
+pub use firehose::FirehoseClient;
+pub use http::{HttpClientOverrides, HttpPolicy, HttpPolicyOverrides};
+pub use ingest::{IngestMode, IngestSource};
+pub use jetstream::{ConnectionState, JetstreamClient, MessageSource};
+pub use pause::HydrationPause;
+pub use plc::{DidDocument, PlcClient};
+pub use probe::{EndpointProbe, EndpointProber, EndpointSelector};
+pub use session::{SessionCredential, SessionStore, SESSION_REFRESH_MARGIN};
@@ -0,0 +1,133 @@
+//! Pause shared by every Bluesky request loop after a 429. Collectors that are
+//! rate limited together would otherwise each sleep their own backoff and
+//! retry at once, tripping the limit again; instead a 429 pauses all of them
+//! until the longest `Retry-After` seen has passed.
+
+use crate::telemetry::ErrorReporter;
+use metrics::{counter, histogram};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::warn;
+
+#[derive(Default)]
+pub struct HydrationPause {
+    until: Mutex<Option<Instant>>,
+    pauses: AtomicU64,
+    error_reporter: Option<ErrorReporter>,
+}
+
+impl HydrationPause {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports every pause started or extended as a `HydrationPause` alert.
+    pub fn with_error_reporter(mut self, error_reporter: ErrorReporter) -> Self {
+        self.error_reporter = Some(error_reporter);
+        self
+    }
+
+    /// Pauses requests for at least `wait` from now. A pause already running
+    /// longer is left alone. Returns whether the pause was started or extended.
+    pub fn extend(&self, endpoint: &str, wait: Duration) -> bool {
+        let now = Instant::now();
+        let resume_at = now + wait;
+        {
+            let mut until = self.until.lock().expect("hydration pause lock poisoned");
+            if until.is_some_and(|until| until >= resume_at) {
+                return false;
+            }
+            *until = Some(resume_at);
+        }
+        self.pauses.fetch_add(1, Ordering::Relaxed);
+        counter!("jetstream_turbo_hydration_pauses_total").increment(1);
+        histogram!("jetstream_turbo_hydration_pause_seconds").record(wait.as_secs_f64());
+        let message = format!(
+            "Rate limited on {}, pausing hydration requests for {:.1}s",
+            endpoint,
+            wait.as_secs_f64()
+        );
+        warn!("{}", message);
+        if let Some(error_reporter) = &self.error_reporter {
+            let mut ctx = HashMap::new();
+            ctx.insert("component", "hydration");
+            ctx.insert("operation", "rate_limit_pause");
+            ctx.insert("endpoint", endpoint);
+            error_reporter.capture_alert("HydrationPause", &message, ctx);
+        }
+        true
+    }
+
+    /// Time left on the current pause, if one is running.
+    pub fn remaining(&self) -> Option<Duration> {
+        let until = (*self.until.lock().expect("hydration pause lock poisoned"))?;
+        let remaining = until.saturating_duration_since(Instant::now());
+        (!remaining.is_zero()).then_some(remaining)
+    }
+
+    /// Waits out the pause, including any extensions made while waiting.
+    pub async fn until_resumed(&self) {
+        while let Some(remaining) = self.remaining() {
+            tokio::time::sleep(remaining).await;
+        }
+    }
+
+    /// Pauses started or extended since startup.
+    pub fn pause_count(&self) -> u64 {
+        self.pauses.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pause_keeps_the_longest_retry_after() {
+        let pause = HydrationPause::new();
+        assert!(pause.remaining().is_none());
+
+        assert!(pause.extend("app.bsky.actor.getProfiles", Duration::from_millis(80)));
+        assert!(!pause.extend("app.bsky.feed.getPosts", Duration::from_millis(10)));
+        assert_eq!(pause.pause_count(), 1);
+        assert!(pause.remaining().unwrap() > Duration::from_millis(40));
+
+        let started = std::time::Instant::now();
+        pause.until_resumed().await;
+        assert!(started.elapsed() >= Duration::from_millis(60));
+        assert!(pause.remaining().is_none());
+    }
+
+    #[cfg(feature = "posthog")]
+    #[tokio::test]
+    async fn test_pauses_are_reported_as_alerts() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let posthog = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/batch/"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&posthog)
+            .await;
+        let reporter = ErrorReporter::new(Some("phc_test".to_string()), Some(posthog.uri())).await;
+        let pause = HydrationPause::new().with_error_reporter(reporter.clone());
+
+        assert!(pause.extend("app.bsky.actor.getProfiles", Duration::from_secs(30)));
+        // Shorter than the running pause, so not a new alert
+        assert!(!pause.extend("app.bsky.feed.getPosts", Duration::from_secs(1)));
+        assert!(reporter.flush_with_timeout(Duration::from_secs(1)).await);
+
+        let requests = posthog.received_requests().await.unwrap();
+        let flushed: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        let events = flushed["batch"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        let properties = &events[0]["properties"];
+        assert_eq!(properties["$exception_type"], "HydrationPause");
+        assert_eq!(properties["component"], "hydration");
+        assert_eq!(properties["endpoint"], "app.bsky.actor.getProfiles");
+    }
+}
@@ -0,0 +1,256 @@
+use crate::client::bluesky::rate_limited_error;
+use crate::client::http::{client_builder, DEFAULT_USER_AGENT};
+use crate::models::bluesky::BlueskyProfile;
+use crate::models::errors::{TurboError, TurboResult};
+use futures::StreamExt;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use moka::sync::Cache as MokaCache;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{trace, warn};
+
+/// plc.directory asks clients to stay well under its global limit.
+const PLC_REQUESTS_PER_SECOND: u32 = 10;
+const PLC_CONCURRENT_RESOLUTIONS: usize = 8;
+const DID_DOCUMENT_CACHE_SIZE: u64 = 100_000;
+const DID_DOCUMENT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The parts of a DID document the pipeline uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DidDocument {
+    pub did: String,
+    /// Handle claimed in `alsoKnownAs`, without the `at://` prefix. Not verified
+    /// against the handle's own DNS or well-known record.
+    pub handle: Option<String>,
+    pub pds_endpoint: Option<String>,
+    pub rotation_keys: Vec<String>,
+}
+
+impl DidDocument {
+    /// A handle-only profile for authors the profile API returned nothing for.
+    pub fn handle_profile(&self) -> Option<BlueskyProfile> {
+        let handle = self.handle.clone()?;
+        Some(BlueskyProfile {
+            did: Arc::from(self.did.as_str()),
+            handle,
+            display_name: None,
+            description: None,
+            avatar: None,
+            banner: None,
+            followers_count: None,
+            follows_count: None,
+            posts_count: None,
+            indexed_at: None,
+            created_at: None,
+            labels: None,
+        })
+    }
+}
+
+/// Shape of `GET /{did}/data`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlcData {
+    did: String,
+    #[serde(default)]
+    rotation_keys: Vec<String>,
+    #[serde(default)]
+    also_known_as: Vec<String>,
+    #[serde(default)]
+    services: HashMap<String, PlcService>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlcService {
+    endpoint: String,
+}
+
+impl From<PlcData> for DidDocument {
+    fn from(data: PlcData) -> Self {
+        Self {
+            handle: data
+                .also_known_as
+                .iter()
+                .find_map(|aka| aka.strip_prefix("at://"))
+                .map(str::to_string),
+            pds_endpoint: data
+                .services
+                .get("atproto_pds")
+                .map(|service| service.endpoint.clone()),
+            rotation_keys: data.rotation_keys,
+            did: data.did,
+        }
+    }
+}
+
+/// Resolves `did:plc` identities against a PLC directory. Documents (and
+/// not-found results) are cached, and requests are rate limited.
+pub struct PlcClient {
+    http_client: Client,
+    directory_url: String,
+    rate_limiter: DefaultDirectRateLimiter,
+    cache: MokaCache<String, Option<Arc<DidDocument>>>,
+}
+
+impl PlcClient {
+    pub fn new(directory_url: String) -> TurboResult<Self> {
+        Ok(Self {
+            http_client: Self::http_client(DEFAULT_USER_AGENT)?,
+            directory_url: directory_url.trim_end_matches('/').to_string(),
+            rate_limiter: RateLimiter::direct(Quota::per_second(
+                NonZeroU32::new(PLC_REQUESTS_PER_SECOND).expect("non-zero rate"),
+            )),
+            cache: MokaCache::builder()
+                .max_capacity(DID_DOCUMENT_CACHE_SIZE)
+                .time_to_live(DID_DOCUMENT_TTL)
+                .build(),
+        })
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> TurboResult<Self> {
+        self.http_client = Self::http_client(user_agent)?;
+        Ok(self)
+    }
+
+    fn http_client(user_agent: &str) -> TurboResult<Client> {
+        Ok(client_builder(user_agent)
+            .timeout(Duration::from_secs(10))
+            .build()?)
+    }
+
+    /// Whether `did` has been looked up, including DIDs the directory didn't know.
+    pub fn has_resolved(&self, did: &str) -> bool {
+        self.cache.contains_key(did)
+    }
+
+    /// A previously resolved document, without making a request.
+    pub fn cached(&self, did: &str) -> Option<Arc<DidDocument>> {
+        self.cache.get(did).flatten()
+    }
+
+    /// The document for `did`, or `None` if it isn't a `did:plc` identity or the
+    /// directory doesn't know it (including tombstoned DIDs).
+    pub async fn resolve(&self, did: &str) -> TurboResult<Option<Arc<DidDocument>>> {
+        if !did.starts_with("did:plc:") {
+            return Ok(None);
+        }
+        if let Some(cached) = self.cache.get(did) {
+            return Ok(cached);
+        }
+
+        self.rate_limiter.until_ready().await;
+        let url = format!("{}/{}/data", self.directory_url, did);
+        let response = self.http_client.get(&url).send().await?;
+
+        let document = match response.status() {
+            StatusCode::OK => {
+                let data: PlcData = response.json().await?;
+                Some(Arc::new(DidDocument::from(data)))
+            }
+            StatusCode::NOT_FOUND | StatusCode::GONE => None,
+            StatusCode::TOO_MANY_REQUESTS => {
+                return Err(rate_limited_error("plc.directory", response.headers()));
+            }
+            status => {
+                let error_text = response.text().await.unwrap_or_default();
+                return Err(TurboError::InvalidApiResponse(format!(
+                    "PLC directory returned {status} for {did}: {error_text}"
+                )));
+            }
+        };
+
+        trace!("Resolved {} via PLC directory: {:?}", did, document);
+        self.cache.insert(did.to_string(), document.clone());
+        Ok(document)
+    }
+
+    /// The PDS hosting `did`'s repo, as listed in its DID document.
+    pub async fn pds_endpoint(&self, did: &str) -> TurboResult<Option<String>> {
+        Ok(self
+            .resolve(did)
+            .await?
+            .and_then(|document| document.pds_endpoint.clone()))
+    }
+
+    /// Resolves `dids` into the cache, a few at a time. Failures are logged and
+    /// left uncached so a later call retries them. Returns how many resolved.
+    pub async fn resolve_many(&self, dids: &[String]) -> usize {
+        futures::stream::iter(dids.iter().cloned())
+            .map(|did| async move {
+                match self.resolve(&did).await {
+                    Ok(document) => document.is_some(),
+                    Err(e) => {
+                        warn!("Failed to resolve {} via PLC directory: {}", did, e);
+                        false
+                    }
+                }
+            })
+            .buffer_unordered(PLC_CONCURRENT_RESOLUTIONS)
+            .filter(|resolved| futures::future::ready(*resolved))
+            .count()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_resolves_and_caches_did_documents() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/did:plc:alice/data"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "did": "did:plc:alice",
+                "verificationMethods": {"atproto": "did:key:zQ3shsigning"},
+                "rotationKeys": ["did:key:zQ3shrotation"],
+                "alsoKnownAs": ["at://alice.example.com"],
+                "services": {
+                    "atproto_pds": {
+                        "type": "AtprotoPersonalDataServer",
+                        "endpoint": "https://pds.example.com"
+                    }
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/did:plc:gone/data"))
+            .respond_with(ResponseTemplate::new(410))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = PlcClient::new(server.uri()).unwrap();
+        let dids = vec!["did:plc:alice".to_string(), "did:plc:gone".to_string()];
+        assert_eq!(client.resolve_many(&dids).await, 1);
+
+        // Both answers are served from the cache from here on
+        let document = client.resolve("did:plc:alice").await.unwrap().unwrap();
+        assert_eq!(document.handle.as_deref(), Some("alice.example.com"));
+        assert_eq!(
+            document.pds_endpoint.as_deref(),
+            Some("https://pds.example.com")
+        );
+        assert_eq!(document.rotation_keys, vec!["did:key:zQ3shrotation"]);
+        assert!(client.resolve("did:plc:gone").await.unwrap().is_none());
+        assert_eq!(client.cached("did:plc:alice"), Some(document.clone()));
+        let profile = document.handle_profile().unwrap();
+        assert_eq!(&*profile.did, "did:plc:alice");
+        assert_eq!(profile.handle, "alice.example.com");
+
+        assert!(client
+            .resolve("did:web:example.com")
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,213 @@
+//! Measures TCP + TLS handshake time to each Jetstream host so the client can
+//! connect to the fastest healthy one first.
+
+use futures::future::join_all;
+use metrics::gauge;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+use tracing::{info, trace};
+
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+const DEFAULT_TLS_PORT: u16 = 443;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointProbe {
+    pub endpoint: String,
+    /// Handshake time, or `None` if the endpoint couldn't be reached
+    pub latency: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl EndpointProbe {
+    pub fn is_healthy(&self) -> bool {
+        self.latency.is_some()
+    }
+}
+
+pub struct EndpointProber {
+    connector: TlsConnector,
+    timeout: Duration,
+}
+
+impl EndpointProber {
+    pub fn new(timeout: Duration) -> Self {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        // Pick the provider explicitly so probing works without a process-wide default
+        let config = ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::aws_lc_rs::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .expect("aws-lc-rs supports the default protocol versions")
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+        Self {
+            connector: TlsConnector::from(Arc::new(config)),
+            timeout,
+        }
+    }
+
+    /// Times a TCP connect and TLS handshake to `endpoint` (`host` or `host:port`).
+    pub async fn probe(&self, endpoint: &str) -> EndpointProbe {
+        let start = Instant::now();
+        let result = match tokio::time::timeout(self.timeout, self.handshake(endpoint)).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("timed out after {:?}", self.timeout)),
+        };
+
+        let probe = match result {
+            Ok(()) => EndpointProbe {
+                endpoint: endpoint.to_string(),
+                latency: Some(start.elapsed()),
+                error: None,
+            },
+            Err(error) => EndpointProbe {
+                endpoint: endpoint.to_string(),
+                latency: None,
+                error: Some(error),
+            },
+        };
+        gauge!("jetstream_turbo_endpoint_latency_ms", "endpoint" => endpoint.to_string()).set(
+            probe
+                .latency
+                .map_or(-1.0, |latency| latency.as_secs_f64() * 1000.0),
+        );
+        trace!("Probed {}: {:?}", endpoint, probe);
+        probe
+    }
+
+    async fn handshake(&self, endpoint: &str) -> Result<(), String> {
+        let (host, port) = match endpoint.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| "invalid port")?),
+            None => (endpoint, DEFAULT_TLS_PORT),
+        };
+        let server_name = ServerName::try_from(host.to_string()).map_err(|e| e.to_string())?;
+        let tcp = TcpStream::connect((host, port))
+            .await
+            .map_err(|e| e.to_string())?;
+        self.connector
+            .connect(server_name, tcp)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Probes every endpoint concurrently and returns them fastest first, with
+    /// unreachable ones last in their configured order.
+    pub async fn rank(&self, endpoints: &[String]) -> Vec<EndpointProbe> {
+        let mut probes = join_all(endpoints.iter().map(|endpoint| self.probe(endpoint))).await;
+        // Stable sort keeps the configured order among unreachable endpoints
+        probes.sort_by_key(|probe| probe.latency.unwrap_or(Duration::MAX));
+
+        let summary: Vec<String> = probes
+            .iter()
+            .map(|probe| match probe.latency {
+                Some(latency) => format!("{} {}ms", probe.endpoint, latency.as_millis()),
+                None => format!("{} unreachable", probe.endpoint),
+            })
+            .collect();
+        info!("Jetstream endpoint latency: {}", summary.join(", "));
+        probes
+    }
+}
+
+/// Connection order for the Jetstream endpoints, re-ranked by probing once the
+/// previous ranking is older than the re-probe interval.
+pub struct EndpointSelector {
+    endpoints: Vec<String>,
+    prober: Option<EndpointProber>,
+    reprobe_interval: Duration,
+    ranked: Vec<String>,
+    ranked_at: Option<Instant>,
+    next: usize,
+}
+
+impl EndpointSelector {
+    /// Without a prober (or with a single endpoint) endpoints are used in the
+    /// configured order.
+    pub fn new(
+        endpoints: Vec<String>,
+        prober: Option<EndpointProber>,
+        reprobe_interval: Duration,
+    ) -> Self {
+        let prober = prober.filter(|_| endpoints.len() > 1);
+        Self {
+            ranked: endpoints.clone(),
+            endpoints,
+            prober,
+            reprobe_interval,
+            ranked_at: None,
+            next: 0,
+        }
+    }
+
+    /// The endpoint to connect to next. A fresh ranking starts from its fastest
+    /// endpoint; otherwise this moves on from the last one handed out.
+    pub async fn next_endpoint(&mut self) -> String {
+        if let Some(prober) = &self.prober {
+            let stale = self
+                .ranked_at
+                .is_none_or(|ranked_at| ranked_at.elapsed() >= self.reprobe_interval);
+            if stale {
+                let probes = prober.rank(&self.endpoints).await;
+                self.ranked = probes.into_iter().map(|probe| probe.endpoint).collect();
+                self.ranked_at = Some(Instant::now());
+                self.next = 0;
+            }
+        }
+
+        let endpoint = self.ranked[self.next % self.ranked.len()].clone();
+        self.next = (self.next + 1) % self.ranked.len();
+        endpoint
+    }
+
+    /// With probing on, the next reconnect starts over from the fastest endpoint
+    /// instead of moving past the one that just worked.
+    pub fn mark_connected(&mut self) {
+        if self.prober.is_some() {
+            self.next = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_unreachable_endpoints_rank_last() {
+        // Accepts TCP but never completes a TLS handshake
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent = format!("localhost:{}", listener.local_addr().unwrap().port());
+        // Nothing listens here once the listener is dropped
+        let closed = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            format!("localhost:{}", listener.local_addr().unwrap().port())
+        };
+
+        let prober = EndpointProber::new(Duration::from_millis(200));
+        let probes = prober.rank(&[silent.clone(), closed.clone()]).await;
+        assert!(probes.iter().all(|probe| !probe.is_healthy()));
+        assert_eq!(probes[0].endpoint, silent);
+        assert!(probes[0].error.as_deref().unwrap().contains("timed out"));
+
+        let mut selector = EndpointSelector::new(
+            vec![silent.clone(), closed.clone()],
+            Some(prober),
+            Duration::from_secs(300),
+        );
+        assert_eq!(selector.next_endpoint().await, silent);
+        assert_eq!(selector.next_endpoint().await, closed);
+        selector.mark_connected();
+        assert_eq!(selector.next_endpoint().await, silent);
+    }
+}
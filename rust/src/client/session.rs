@@ -0,0 +1,469 @@
+use crate::client::auth::AuthResponse;
+use crate::client::BlueskyAuthClient;
+use crate::models::errors::{TurboError, TurboResult};
+use chrono::{DateTime, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{error, info, trace, warn};
+
+/// Separator between the access token and the PDS domain in a session string.
+const DOMAIN_SEPARATOR: &str = ":::";
+
+/// How long before the session expires it is renewed.
+pub const SESSION_REFRESH_MARGIN: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// A parsed session string: `token` or `token:::domain`. Requests made with the
+/// credential go to the XRPC endpoint of its domain when one is present.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionCredential {
+    token: String,
+    domain: Option<String>,
+}
+
+impl SessionCredential {
+    /// A credential for a bare access token, as issued by `createSession`.
+    pub fn from_token(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+            domain: None,
+        }
+    }
+
+    pub fn parse(session_string: &str) -> TurboResult<Self> {
+        let malformed = |reason: &str| {
+            TurboError::PermissionDenied(format!("Malformed session string: {reason}"))
+        };
+
+        let (token, domain) = match session_string.split_once(DOMAIN_SEPARATOR) {
+            Some((token, domain)) => (token, Some(domain)),
+            None => (session_string, None),
+        };
+
+        if token.is_empty() {
+            return Err(malformed("missing token"));
+        }
+        if token.chars().any(char::is_whitespace) {
+            return Err(malformed("token contains whitespace"));
+        }
+
+        let domain = match domain {
+            None => None,
+            Some(domain) if domain.contains(DOMAIN_SEPARATOR) => {
+                return Err(malformed("more than one ':::' separator"));
+            }
+            Some("") => return Err(malformed("empty domain after ':::'")),
+            Some(domain) => {
+                let host = domain
+                    .strip_prefix("https://")
+                    .or_else(|| domain.strip_prefix("http://"))
+                    .unwrap_or(domain);
+                if host.is_empty() || host.contains('/') || host.chars().any(char::is_whitespace) {
+                    return Err(malformed(&format!("invalid domain '{domain}'")));
+                }
+                Some(domain.to_string())
+            }
+        };
+
+        Ok(Self {
+            token: token.to_string(),
+            domain,
+        })
+    }
+
+    /// Parses every entry, failing on the first malformed one.
+    pub fn parse_all(session_strings: &[String]) -> TurboResult<Vec<Self>> {
+        session_strings
+            .iter()
+            .map(|session| Self::parse(session))
+            .collect()
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    /// When the token stops being accepted, from the `exp` claim of the access
+    /// JWT. The signature isn't checked; this is only for scheduling refreshes.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        #[derive(serde::Deserialize)]
+        struct Claims {
+            exp: i64,
+        }
+
+        let payload = self.token.split('.').nth(1)?;
+        let payload = BASE64URL_NOPAD
+            .decode(payload.trim_end_matches('=').as_bytes())
+            .ok()?;
+        let claims: Claims = serde_json::from_slice(&payload).ok()?;
+        DateTime::from_timestamp(claims.exp, 0)
+    }
+
+    /// XRPC base URL for this credential's domain, or `default` when it has none.
+    pub fn api_base_url(&self, default: &str) -> String {
+        match self.domain.as_deref() {
+            Some(domain) if domain.starts_with("http://") || domain.starts_with("https://") => {
+                format!("{}/xrpc", domain.trim_end_matches('/'))
+            }
+            Some(domain) => format!("https://{domain}/xrpc"),
+            None => default.to_string(),
+        }
+    }
+}
+
+// Keep tokens out of logs
+impl fmt::Debug for SessionCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionCredential")
+            .field("token", &"<redacted>")
+            .field("domain", &self.domain)
+            .finish()
+    }
+}
+
+/// The client's sessions and the tokens that renew them. Every refresh goes
+/// through `refresh`, one at a time: callers that see an expired session while
+/// a refresh is running wait for it and share its outcome, rather than racing
+/// it with a refresh of their own that would revoke the token it just used.
+pub struct SessionStore {
+    sessions: RwLock<Vec<SessionCredential>>,
+    refresh_jwt: RwLock<Option<String>>,
+    expires_at: RwLock<Option<DateTime<Utc>>>,
+    auth_client: Option<Arc<BlueskyAuthClient>>,
+    /// Bumped whenever the sessions are replaced or a refresh attempt ends
+    generation: AtomicU64,
+    /// Held for the whole of a refresh. Records the generation a failed
+    /// attempt ended at, with its error, so callers that waited on it fail too.
+    last_failure: Mutex<Option<(u64, String)>>,
+}
+
+impl SessionStore {
+    pub fn new(
+        sessions: Vec<SessionCredential>,
+        auth_client: Option<Arc<BlueskyAuthClient>>,
+    ) -> Self {
+        let expires_at = expiry_of(&sessions, None);
+        Self {
+            sessions: RwLock::new(sessions),
+            refresh_jwt: RwLock::new(None),
+            expires_at: RwLock::new(expires_at),
+            auth_client,
+            generation: AtomicU64::new(0),
+            last_failure: Mutex::new(None),
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// The session to use for the next request, with the generation to pass
+    /// to `refresh` if it turns out to have expired.
+    pub async fn current(&self) -> TurboResult<(SessionCredential, u64)> {
+        let generation = self.generation();
+        let sessions = self.sessions.read().await;
+        let session = sessions.first().cloned().ok_or_else(|| {
+            TurboError::PermissionDenied("No valid session strings available".to_string())
+        })?;
+        Ok((session, generation))
+    }
+
+    pub async fn len(&self) -> usize {
+        self.sessions.read().await.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.sessions.read().await.is_empty()
+    }
+
+    pub async fn refresh_jwt(&self) -> Option<String> {
+        self.refresh_jwt.read().await.clone()
+    }
+
+    /// When the current session expires, if that's known.
+    pub async fn expires_at(&self) -> Option<DateTime<Utc>> {
+        *self.expires_at.read().await
+    }
+
+    /// Swaps in sessions issued outside `refresh`, e.g. at startup. The expiry
+    /// is read from the first session's token, falling back to `expires_at`
+    /// (RFC 3339, as some servers send it) when that isn't a JWT.
+    pub async fn replace(
+        &self,
+        sessions: Vec<SessionCredential>,
+        refresh_jwt: Option<String>,
+        expires_at: Option<String>,
+    ) {
+        self.store(sessions, refresh_jwt, expires_at).await;
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Renews the sessions unless that already happened since `seen` (a
+    /// generation from `current`), in which case this shares the outcome of
+    /// that refresh instead of starting another.
+    pub async fn refresh(&self, seen: u64) -> TurboResult<()> {
+        let mut last_failure = self.last_failure.lock().await;
+        let generation = self.generation();
+        if generation != seen {
+            trace!("Session already refreshed by another caller");
+            return match &*last_failure {
+                Some((failed_at, error)) if *failed_at == generation => {
+                    Err(TurboError::ExpiredToken(error.clone()))
+                }
+                _ => Ok(()),
+            };
+        }
+
+        let result = self.refresh_with_fallback().await;
+        let generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        *last_failure = result
+            .as_ref()
+            .err()
+            .map(|error| (generation, error.to_string()));
+        result
+    }
+
+    async fn refresh_with_fallback(&self) -> TurboResult<()> {
+        let Some(auth_client) = &self.auth_client else {
+            return Err(TurboError::ExpiredToken(
+                "No auth client available for re-authentication".to_string(),
+            ));
+        };
+
+        if let Some(refresh_jwt) = self.refresh_jwt().await {
+            match auth_client.refresh_session(&refresh_jwt).await {
+                Ok(auth_response) => {
+                    self.store_auth_response(auth_response).await;
+                    info!("Session refreshed successfully");
+                    return Ok(());
+                }
+                Err(TurboError::ExpiredToken(_)) => {
+                    warn!("Refresh token expired, re-authenticating with credentials");
+                }
+                Err(e) => {
+                    error!("Session refresh failed: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+
+        match auth_client.authenticate().await {
+            Ok(auth_response) => {
+                self.store_auth_response(auth_response).await;
+                info!("Re-authenticated successfully");
+                Ok(())
+            }
+            Err(e) => {
+                error!("Re-authentication failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    async fn store_auth_response(&self, auth_response: AuthResponse) {
+        self.store(
+            vec![SessionCredential::from_token(auth_response.access_jwt)],
+            Some(auth_response.refresh_jwt),
+            auth_response.expires_at,
+        )
+        .await;
+    }
+
+    async fn store(
+        &self,
+        sessions: Vec<SessionCredential>,
+        refresh_jwt: Option<String>,
+        expires_at: Option<String>,
+    ) {
+        let count = sessions.len();
+        let expires_at = expiry_of(&sessions, expires_at.as_deref());
+        *self.sessions.write().await = sessions;
+        info!("Refreshed {} session strings", count);
+
+        if let Some(refresh_jwt) = refresh_jwt {
+            *self.refresh_jwt.write().await = Some(refresh_jwt);
+        }
+        match expires_at {
+            Some(expires_at) => info!("Session expires at: {}", expires_at),
+            None => warn!("Session expiry unknown; it will be refreshed on every check"),
+        }
+        *self.expires_at.write().await = expires_at;
+    }
+}
+
+/// Expiry of the session that `current` hands out: its JWT `exp` claim, or
+/// else the expiry the server reported alongside it.
+fn expiry_of(sessions: &[SessionCredential], reported: Option<&str>) -> Option<DateTime<Utc>> {
+    sessions
+        .first()
+        .and_then(SessionCredential::expires_at)
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(reported?)
+                .ok()
+                .map(|expires_at| expires_at.with_timezone(&Utc))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_token_and_domain() {
+        let credential = SessionCredential::parse("abc.def:::bsky.social").unwrap();
+        assert_eq!(credential.token(), "abc.def");
+        assert_eq!(credential.domain(), Some("bsky.social"));
+        assert_eq!(
+            credential.api_base_url("https://fallback/xrpc"),
+            "https://bsky.social/xrpc"
+        );
+
+        let bare = SessionCredential::parse("abc.def").unwrap();
+        assert_eq!(bare.domain(), None);
+        assert_eq!(
+            bare.api_base_url("https://fallback/xrpc"),
+            "https://fallback/xrpc"
+        );
+
+        let local = SessionCredential::parse("abc:::http://127.0.0.1:2583").unwrap();
+        assert_eq!(local.api_base_url(""), "http://127.0.0.1:2583/xrpc");
+        assert!(!format!("{local:?}").contains("abc"));
+    }
+
+    #[test]
+    fn test_rejects_malformed_session_strings() {
+        for malformed in [
+            "",
+            ":::bsky.social",
+            "token:::",
+            "token:::bsky.social:::extra",
+            "token:::bsky.social/xrpc",
+            "tok en:::bsky.social",
+        ] {
+            let error = SessionCredential::parse(malformed).unwrap_err();
+            assert!(
+                error.to_string().contains("Malformed session string"),
+                "{malformed:?}: {error}"
+            );
+        }
+    }
+
+    fn jwt_expiring_at(exp: i64) -> String {
+        let encode = |json: serde_json::Value| BASE64URL_NOPAD.encode(json.to_string().as_bytes());
+        format!(
+            "{}.{}.signature",
+            encode(serde_json::json!({"typ": "at+jwt", "alg": "ES256K"})),
+            encode(serde_json::json!({
+                "scope": "com.atproto.appPass",
+                "sub": "did:plc:test",
+                "iat": exp - 7200,
+                "exp": exp,
+                "aud": "did:web:bsky.social",
+            }))
+        )
+    }
+
+    #[tokio::test]
+    async fn test_expiry_comes_from_the_access_jwt() {
+        let credential = SessionCredential::from_token(jwt_expiring_at(1_775_000_000));
+        assert_eq!(
+            credential.expires_at(),
+            DateTime::from_timestamp(1_775_000_000, 0)
+        );
+        assert_eq!(SessionCredential::from_token("opaque").expires_at(), None);
+
+        let store = SessionStore::new(vec![credential.clone()], None);
+        assert_eq!(store.expires_at().await, credential.expires_at());
+
+        // The claim wins over the reported expiry, which is only a fallback
+        store
+            .replace(
+                vec![credential.clone()],
+                None,
+                Some("2026-04-04T00:00:00.000Z".to_string()),
+            )
+            .await;
+        assert_eq!(store.expires_at().await, credential.expires_at());
+        store
+            .replace(
+                vec![SessionCredential::from_token("opaque")],
+                None,
+                Some("2026-04-04T00:00:00.000Z".to_string()),
+            )
+            .await;
+        assert_eq!(
+            store.expires_at().await,
+            DateTime::from_timestamp(1_775_260_800, 0)
+        );
+        store
+            .replace(vec![SessionCredential::from_token("opaque")], None, None)
+            .await;
+        assert_eq!(store.expires_at().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_refreshes_share_one_request() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/com.atproto.server.refreshSession"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "accessJwt": "fresh_access",
+                        "refreshJwt": "fresh_refresh",
+                        "handle": "test.bsky.social",
+                        "did": "did:plc:test",
+                    }))
+                    .set_delay(std::time::Duration::from_millis(50)),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let auth_client = BlueskyAuthClient::with_api_url(
+            "test.bsky.social".to_string(),
+            "app-password".to_string(),
+            mock_server.uri(),
+        )
+        .unwrap();
+
+        let store = SessionStore::new(Vec::new(), Some(Arc::new(auth_client)));
+        store
+            .replace(
+                vec![SessionCredential::from_token("stale_access")],
+                Some("stale_refresh".to_string()),
+                None,
+            )
+            .await;
+        let (_, seen) = store.current().await.unwrap();
+
+        let results = futures::future::join_all((0..8).map(|_| store.refresh(seen))).await;
+        assert!(results.iter().all(Result::is_ok));
+        let (session, generation) = store.current().await.unwrap();
+        assert_eq!(session, SessionCredential::from_token("fresh_access"));
+        assert_eq!(generation, seen + 1);
+        assert_eq!(store.refresh_jwt().await.as_deref(), Some("fresh_refresh"));
+
+        let unauthenticated = SessionStore::new(Vec::new(), None);
+        assert!(unauthenticated.current().await.is_err());
+        assert!(unauthenticated
+            .refresh(0)
+            .await
+            .unwrap_err()
+            .is_expired_token());
+        // A caller that waited on that failed attempt fails with it
+        assert!(unauthenticated
+            .refresh(0)
+            .await
+            .unwrap_err()
+            .is_expired_token());
+    }
+}
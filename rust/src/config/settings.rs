@@ -1,4 +1,15 @@
+use crate::client::http::user_agent;
+use crate::client::{HttpClientOverrides, HttpPolicy, HttpPolicyOverrides, IngestMode};
+use crate::hydration::{LabelFilterMode, PrivacyMode, ValidationMode};
+use crate::models::enriched::OutputFormat;
+use crate::storage::file_sink::STDOUT_PATH;
+use crate::storage::{DeleteMode, PayloadEncoding, ProjectionPart};
+use crate::turbocharger::streams::OutputStreamConfig;
+use crate::turbocharger::watchlist::WATCHLIST_STREAM;
+#[cfg(feature = "chaos")]
+use crate::utils::chaos::ChaosConfig;
 use anyhow::Result;
+use jetstream_turbo_access_log::TrustedProxies;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -12,18 +23,107 @@ pub struct Settings {
     pub stream_name: String,
 
     // Jetstream Configuration
+    #[serde(default)]
+    pub ingest_mode: IngestMode,
+    #[serde(default = "default_firehose_hosts")]
+    pub firehose_hosts: Vec<String>,
     #[serde(default = "default_jetstream_hosts")]
     pub jetstream_hosts: Vec<String>,
+    /// Seconds between endpoint latency probes; 0 connects in configured order
+    #[serde(default = "default_jetstream_probe_interval_secs")]
+    pub jetstream_probe_interval_secs: u64,
+    /// Start Jetstream from the newest stored event rather than live after a restart
+    #[serde(default = "default_true")]
+    pub jetstream_resume_from_store: bool,
     #[serde(default = "default_wanted_collections")]
     pub wanted_collections: String,
+    #[serde(default = "default_backfill_api_url")]
+    pub backfill_api_url: String,
+    /// Records that lookups may fetch from their repos per minute when they
+    /// aren't stored, across all clients; 0 turns hydrate-on-miss off
+    #[serde(default = "default_hydrate_on_miss_per_minute")]
+    pub hydrate_on_miss_per_minute: u32,
+    #[serde(default)]
+    pub capture_dir: Option<String>,
+    #[serde(default = "default_capture_rotate_mb")]
+    pub capture_rotate_mb: u64,
+    #[serde(default)]
+    pub replay_path: Option<String>,
+    #[serde(default = "default_replay_speed")]
+    pub replay_speed: f64,
+
+    // Moderation Configuration
+    #[serde(default)]
+    pub accepted_labelers: Vec<String>,
+    #[serde(default)]
+    pub filtered_labels: Vec<String>,
+    #[serde(default)]
+    pub label_filter_mode: LabelFilterMode,
+    /// Where records of authors labeled `!no-unauthenticated` are left out
+    #[serde(default)]
+    pub author_privacy: PrivacyMode,
+    #[serde(default)]
+    pub record_validation: ValidationMode,
+    #[serde(default)]
+    pub spam_scoring: bool,
+    /// Records scoring at or above this are dropped; implies `spam_scoring`
+    #[serde(default)]
+    pub spam_drop_threshold: Option<f32>,
+
+    // Identity Configuration
+    #[serde(default)]
+    pub plc_directory_url: Option<String>,
+
+    // Pseudonymization Configuration
+    /// Secret for hashing DIDs and handles; identities are stored as-is when unset
+    #[serde(default)]
+    pub pseudonymize_key: Option<String>,
+
+    // Link Preview Configuration
+    #[serde(default)]
+    pub url_unfurling: bool,
+    #[serde(default = "default_unfurl_requests_per_second")]
+    pub unfurl_requests_per_second: u32,
+
+    // Blob Mirroring Configuration (`s3` feature)
+    #[serde(default)]
+    pub blob_mirror_bucket: Option<String>,
+    #[serde(default)]
+    pub blob_mirror_region: Option<String>,
+    #[serde(default)]
+    pub blob_mirror_endpoint: Option<String>,
+    #[serde(default)]
+    pub blob_mirror_public_url: Option<String>,
+    #[serde(default = "default_blob_mirror_prefix")]
+    pub blob_mirror_prefix: String,
 
     // Redis Configuration
     pub redis_url: String,
     pub stream_name_redis: String,
     pub trim_maxlen: Option<usize>,
+    #[serde(default)]
+    pub redis_payload_encoding: PayloadEncoding,
+    /// Publish delete events to the main stream so consumers can drop their copies
+    #[serde(default = "default_true")]
+    pub redis_publish_deletes: bool,
+    /// Run without Redis: the main stream, output streams and watchlist reach
+    /// WebSocket clients only, plus `standalone_output_file` if set
+    #[serde(default)]
+    pub standalone: bool,
+    /// NDJSON file the main stream is appended to in standalone mode; `-`
+    /// writes it to stdout, with logs moved to stderr
+    #[serde(default)]
+    pub standalone_output_file: Option<String>,
+    /// Serve the stored records over HTTP and WebSocket without ingesting:
+    /// no Jetstream, no Bluesky session and no Redis, with SQLite opened
+    /// read-only. Bluesky credentials aren't required.
+    #[serde(default)]
+    pub serve_only: bool,
 
     // Storage Configuration
     pub db_dir: String,
+    /// Minutes between moves of rolled-up records into rotated
+    /// `jetstream_<unix>.db` files; 0 keeps everything in the live database
     pub rotation_minutes: u64,
 
     // Cleanup Configuration
@@ -39,13 +139,101 @@ pub struct Settings {
     pub sqlite_cache_size_kib: u32,
     pub sqlite_mmap_size_mb: u64,
     pub sqlite_journal_size_limit_mb: u64,
+    #[serde(default)]
+    pub delete_mode: DeleteMode,
+    /// What happens to stored records of accounts taken down or deleted
+    #[serde(default)]
+    pub account_removal_mode: DeleteMode,
+    #[serde(default = "default_sqlite_shards")]
+    pub sqlite_shards: usize,
+    /// Parts of each record left out of SQLite and the main Redis stream
+    #[serde(default)]
+    pub record_projection: Vec<ProjectionPart>,
+
+    // Archive Compaction Configuration
+    #[serde(default)]
+    pub compaction_interval_minutes: u64,
+    /// Upload archives zstd-compressed; local archives stay uncompressed
+    #[serde(default = "default_true")]
+    pub compaction_compress: bool,
+    #[serde(default)]
+    pub archive_bucket: Option<String>,
+    #[serde(default = "default_archive_prefix")]
+    pub archive_prefix: String,
 
     // HTTP Server Configuration
     pub http_port: u16,
+    /// Serve on this unix socket instead of `http_port`
+    #[serde(default)]
+    pub http_unix_socket: Option<String>,
+    /// PEM certificate chain and private key; when both are set the server speaks HTTPS/WSS
+    #[serde(default)]
+    pub tls_cert_path: Option<String>,
+    #[serde(default)]
+    pub tls_key_path: Option<String>,
+    /// Bearer token for `/api/v1/admin`; the admin routes are refused when unset
+    #[serde(default)]
+    pub admin_token: Option<String>,
+    /// Peers whose `x-forwarded-for` names the client in access logs, as
+    /// comma-separated addresses or CIDR ranges
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: String,
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    #[serde(default)]
+    pub output_streams: Vec<OutputStreamConfig>,
+    /// DIDs and handles whose posts go to the watchlist stream
+    #[serde(default)]
+    pub watchlist: Vec<String>,
+    /// Defaults to `{redis stream}:watchlist`
+    #[serde(default)]
+    pub watchlist_redis_stream: Option<String>,
+    #[serde(default)]
+    pub watchlist_webhook_url: Option<String>,
+    /// DIDs whose events are dropped before hydration
+    #[serde(default)]
+    pub did_blocklist: Vec<String>,
+    /// File of blocked DIDs, one per line, re-read when it changes
+    #[serde(default)]
+    pub did_blocklist_file: Option<String>,
+    /// When set, only these DIDs' events are processed
+    #[serde(default)]
+    pub did_allowlist: Vec<String>,
+    #[serde(default)]
+    pub did_allowlist_file: Option<String>,
 
     // Channel Configuration
     #[serde(default = "default_channel_capacity")]
     pub channel_capacity: usize,
+    #[serde(default = "default_parse_workers")]
+    pub parse_workers: usize,
+    #[serde(default = "default_broadcast_capacity")]
+    pub broadcast_capacity: usize,
+    /// Seconds to let buffered records reach the sinks, and WebSocket clients
+    /// receive queued records and close, on shutdown
+    #[serde(default = "default_shutdown_drain_timeout_secs")]
+    pub shutdown_drain_timeout_secs: u64,
+
+    // Memory Configuration
+    #[serde(default)]
+    pub memory_soft_limit_mb: Option<u64>,
+
+    // Health Configuration (0 disables a check)
+    #[serde(default = "default_health_max_message_age_secs")]
+    pub health_max_message_age_secs: u64,
+    #[serde(default = "default_health_max_disconnected_secs")]
+    pub health_max_disconnected_secs: u64,
+    #[serde(default = "default_health_min_session_ttl_secs")]
+    pub health_min_session_ttl_secs: u64,
+    /// Fraction of failed sink writes over the last few minutes; 1.0 disables
+    #[serde(default = "default_health_max_sink_error_rate")]
+    pub health_max_sink_error_rate: f64,
+    /// A SQLite or Redis batch write slower than this counts as slow; 0 disables alerts
+    #[serde(default = "default_slow_write_threshold_ms")]
+    pub slow_write_threshold_ms: u64,
+    /// Consecutive slow batches on one sink before a telemetry alert
+    #[serde(default = "default_slow_write_batches")]
+    pub slow_write_batches: u32,
 
     // Performance Configuration
     pub batch_size: usize,
@@ -54,14 +242,47 @@ pub struct Settings {
     pub profile_batch_wait_ms: u64,
     pub post_batch_wait_ms: u64,
     pub max_concurrent_requests: usize,
+    /// Bluesky API requests allowed per window, saved across restarts in
+    /// `db_dir`; 0 disables
+    #[serde(default = "default_api_request_budget")]
+    pub api_request_budget: u32,
+    #[serde(default = "default_api_request_budget_window_secs")]
+    pub api_request_budget_window_secs: u64,
+    /// Skip post fetches, then all fetches, while the Bluesky API is rate
+    /// limiting or slower than `degradation_latency_ms` on average
+    #[serde(default = "default_true")]
+    pub adaptive_degradation: bool,
+    /// 0 leaves rate limiting as the only trigger
+    #[serde(default = "default_degradation_latency_ms")]
+    pub degradation_latency_ms: u64,
+    /// Seconds without pressure before each step back up
+    #[serde(default = "default_degradation_recovery_secs")]
+    pub degradation_recovery_secs: u64,
     pub cache_size_users: usize,
     pub cache_size_posts: usize,
 
     // Retry Configuration
+    /// Never read; kept so existing configs and callers still compile.
+    #[deprecated(note = "ignored; use `http.max_retries` or `http_clients`")]
     pub max_retries: u32,
+    #[deprecated(note = "ignored; use `http.retry_base_delay_ms` or `http_clients`")]
     #[serde(skip)]
     pub retry_base_delay: Duration,
 
+    // HTTP Client Configuration
+    /// Timeouts and retries for every outbound client, over each one's defaults
+    #[serde(default)]
+    pub http: HttpPolicyOverrides,
+    /// Per-client overrides on top of `http`
+    #[serde(default)]
+    pub http_clients: HttpClientOverrides,
+    /// Replaces the `jetstream-turbo/{version}` product in the user agent
+    #[serde(default)]
+    pub http_user_agent: Option<String>,
+    /// Appended to the user agent so upstream operators can reach this one
+    #[serde(default)]
+    pub operator_contact_url: Option<String>,
+
     // Metrics Configuration
     pub statsd_host: Option<String>,
     pub statsd_port: Option<u16>,
@@ -69,21 +290,58 @@ pub struct Settings {
     // PostHog Configuration
     pub posthog_api_key: Option<String>,
     pub posthog_host: Option<String>,
+
+    /// Faults injected into the Redis, SQLite and Bluesky client paths
+    #[cfg(feature = "chaos")]
+    #[serde(default)]
+    pub chaos: ChaosConfig,
 }
 
 impl Default for Settings {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
             bluesky_handle: String::new(),
             bluesky_app_password: String::new(),
             stream_name: String::new(),
+            ingest_mode: IngestMode::Jetstream,
+            firehose_hosts: default_firehose_hosts(),
             jetstream_hosts: default_jetstream_hosts(),
+            jetstream_probe_interval_secs: default_jetstream_probe_interval_secs(),
+            jetstream_resume_from_store: true,
             wanted_collections: default_wanted_collections(),
+            backfill_api_url: default_backfill_api_url(),
+            hydrate_on_miss_per_minute: default_hydrate_on_miss_per_minute(),
+            capture_dir: None,
+            capture_rotate_mb: default_capture_rotate_mb(),
+            replay_path: None,
+            replay_speed: default_replay_speed(),
+            accepted_labelers: Vec::new(),
+            filtered_labels: Vec::new(),
+            label_filter_mode: LabelFilterMode::Flag,
+            author_privacy: PrivacyMode::Off,
+            record_validation: ValidationMode::Off,
+            spam_scoring: false,
+            spam_drop_threshold: None,
+            plc_directory_url: None,
+            pseudonymize_key: None,
+            url_unfurling: false,
+            unfurl_requests_per_second: default_unfurl_requests_per_second(),
+            blob_mirror_bucket: None,
+            blob_mirror_region: None,
+            blob_mirror_endpoint: None,
+            blob_mirror_public_url: None,
+            blob_mirror_prefix: default_blob_mirror_prefix(),
             redis_url: "redis://localhost:6379".to_string(),
             stream_name_redis: "hydrated_jetstream".to_string(),
             trim_maxlen: Some(100),
+            redis_payload_encoding: PayloadEncoding::Json,
+            redis_publish_deletes: true,
+            standalone: false,
+            standalone_output_file: None,
+            serve_only: false,
             db_dir: "data_store".to_string(),
-            rotation_minutes: 1,
+            rotation_minutes: 0,
             // 8 GB RAM / 40 GB disk baseline:
             // tuned for higher throughput while still bounding growth.
             max_db_size_mb: 20 * 1024,
@@ -98,28 +356,85 @@ impl Default for Settings {
             sqlite_cache_size_kib: 64 * 1024,
             sqlite_mmap_size_mb: 256,
             sqlite_journal_size_limit_mb: 512,
+            delete_mode: DeleteMode::Keep,
+            account_removal_mode: DeleteMode::Keep,
+            record_projection: Vec::new(),
+            sqlite_shards: default_sqlite_shards(),
+            compaction_interval_minutes: 0,
+            compaction_compress: true,
+            archive_bucket: None,
+            archive_prefix: default_archive_prefix(),
             http_port: 8080,
+            http_unix_socket: None,
+            tls_cert_path: None,
+            tls_key_path: None,
+            admin_token: None,
+            trusted_proxies: default_trusted_proxies(),
+            output_format: OutputFormat::Enriched,
+            output_streams: Vec::new(),
+            watchlist: Vec::new(),
+            watchlist_redis_stream: None,
+            watchlist_webhook_url: None,
+            did_blocklist: Vec::new(),
+            did_blocklist_file: None,
+            did_allowlist: Vec::new(),
+            did_allowlist_file: None,
             channel_capacity: default_channel_capacity(),
+            parse_workers: default_parse_workers(),
+            broadcast_capacity: default_broadcast_capacity(),
+            shutdown_drain_timeout_secs: default_shutdown_drain_timeout_secs(),
+            memory_soft_limit_mb: None,
+            health_max_message_age_secs: default_health_max_message_age_secs(),
+            health_max_disconnected_secs: default_health_max_disconnected_secs(),
+            health_min_session_ttl_secs: default_health_min_session_ttl_secs(),
+            health_max_sink_error_rate: default_health_max_sink_error_rate(),
+            slow_write_threshold_ms: default_slow_write_threshold_ms(),
+            slow_write_batches: default_slow_write_batches(),
             batch_size: 10,
             profile_batch_size: 25,
             post_batch_size: 25,
             profile_batch_wait_ms: 150,
             post_batch_wait_ms: 300,
             max_concurrent_requests: 6,
+            api_request_budget: default_api_request_budget(),
+            api_request_budget_window_secs: default_api_request_budget_window_secs(),
+            adaptive_degradation: true,
+            degradation_latency_ms: default_degradation_latency_ms(),
+            degradation_recovery_secs: default_degradation_recovery_secs(),
             cache_size_users: 50_000,
             cache_size_posts: 40_000,
             max_retries: 3,
             retry_base_delay: Duration::from_millis(100),
+            http: HttpPolicyOverrides::default(),
+            http_clients: HttpClientOverrides::default(),
+            http_user_agent: None,
+            operator_contact_url: None,
             statsd_host: None,
             statsd_port: None,
             posthog_api_key: None,
             posthog_host: None,
+            #[cfg(feature = "chaos")]
+            chaos: ChaosConfig::default(),
         }
     }
 }
 
 impl Settings {
     pub fn from_env() -> Result<Self> {
+        let settings = Self::load_env()?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// `from_env` with `serve_only` set, as for `--serve-only`.
+    pub fn from_env_serve_only() -> Result<Self> {
+        let mut settings = Self::load_env()?;
+        settings.serve_only = true;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn load_env() -> Result<Self> {
         dotenvy::dotenv().ok();
 
         let mut builder = config::Config::builder()
@@ -143,11 +458,146 @@ impl Settings {
             builder = builder.set_override("wanted_collections", collections)?;
         }
 
+        if let Ok(url) = std::env::var("BACKFILL_API_URL") {
+            builder = builder.set_override("backfill_api_url", url)?;
+        }
+
+        if let Ok(per_minute) = std::env::var("HYDRATE_ON_MISS_PER_MINUTE") {
+            builder = builder.set_override("hydrate_on_miss_per_minute", per_minute)?;
+        }
+
         if let Ok(hosts) = std::env::var("JETSTREAM_HOSTS") {
             let hosts: Vec<String> = serde_json::from_str(&hosts)?;
             builder = builder.set_override("jetstream_hosts", hosts)?;
         }
 
+        if let Ok(interval) = std::env::var("JETSTREAM_PROBE_INTERVAL_SECS") {
+            builder = builder.set_override("jetstream_probe_interval_secs", interval)?;
+        }
+
+        if let Ok(resume) = std::env::var("JETSTREAM_RESUME_FROM_STORE") {
+            builder = builder.set_override("jetstream_resume_from_store", resume)?;
+        }
+
+        if let Ok(ingest_mode) = std::env::var("INGEST_MODE") {
+            builder = builder.set_override("ingest_mode", ingest_mode)?;
+        }
+
+        if let Ok(hosts) = std::env::var("FIREHOSE_HOSTS") {
+            let hosts: Vec<String> = serde_json::from_str(&hosts)?;
+            builder = builder.set_override("firehose_hosts", hosts)?;
+        }
+
+        if let Ok(capture_dir) = std::env::var("CAPTURE_DIR") {
+            builder = builder.set_override("capture_dir", capture_dir)?;
+        }
+
+        if let Ok(capture_rotate_mb) = std::env::var("CAPTURE_ROTATE_MB") {
+            builder = builder.set_override("capture_rotate_mb", capture_rotate_mb)?;
+        }
+
+        if let Ok(replay_path) = std::env::var("REPLAY_PATH") {
+            builder = builder.set_override("replay_path", replay_path)?;
+        }
+
+        if let Ok(replay_speed) = std::env::var("REPLAY_SPEED") {
+            builder = builder.set_override("replay_speed", replay_speed)?;
+        }
+
+        if let Ok(labelers) = std::env::var("ACCEPTED_LABELERS") {
+            builder = builder.set_override("accepted_labelers", split_list(&labelers))?;
+        }
+
+        if let Ok(labels) = std::env::var("FILTERED_LABELS") {
+            builder = builder.set_override("filtered_labels", split_list(&labels))?;
+        }
+
+        if let Ok(watchlist) = std::env::var("WATCHLIST") {
+            builder = builder.set_override("watchlist", split_list(&watchlist))?;
+        }
+
+        if let Ok(stream) = std::env::var("WATCHLIST_REDIS_STREAM") {
+            builder = builder.set_override("watchlist_redis_stream", stream)?;
+        }
+
+        if let Ok(url) = std::env::var("WATCHLIST_WEBHOOK_URL") {
+            builder = builder.set_override("watchlist_webhook_url", url)?;
+        }
+
+        if let Ok(dids) = std::env::var("DID_BLOCKLIST") {
+            builder = builder.set_override("did_blocklist", split_list(&dids))?;
+        }
+
+        if let Ok(path) = std::env::var("DID_BLOCKLIST_FILE") {
+            builder = builder.set_override("did_blocklist_file", path)?;
+        }
+
+        if let Ok(dids) = std::env::var("DID_ALLOWLIST") {
+            builder = builder.set_override("did_allowlist", split_list(&dids))?;
+        }
+
+        if let Ok(path) = std::env::var("DID_ALLOWLIST_FILE") {
+            builder = builder.set_override("did_allowlist_file", path)?;
+        }
+
+        if let Ok(mode) = std::env::var("LABEL_FILTER_MODE") {
+            builder = builder.set_override("label_filter_mode", mode)?;
+        }
+
+        if let Ok(mode) = std::env::var("AUTHOR_PRIVACY") {
+            builder = builder.set_override("author_privacy", mode)?;
+        }
+
+        if let Ok(mode) = std::env::var("RECORD_VALIDATION") {
+            builder = builder.set_override("record_validation", mode)?;
+        }
+
+        if let Ok(enabled) = std::env::var("SPAM_SCORING") {
+            builder = builder.set_override("spam_scoring", enabled)?;
+        }
+
+        if let Ok(threshold) = std::env::var("SPAM_DROP_THRESHOLD") {
+            if !threshold.trim().is_empty() {
+                builder = builder.set_override("spam_drop_threshold", threshold)?;
+            }
+        }
+
+        if let Ok(plc_directory_url) = std::env::var("PLC_DIRECTORY_URL") {
+            builder = builder.set_override("plc_directory_url", plc_directory_url)?;
+        }
+
+        if let Ok(key) = std::env::var("PSEUDONYMIZE_KEY") {
+            builder = builder.set_override("pseudonymize_key", key)?;
+        }
+
+        if let Ok(enabled) = std::env::var("URL_UNFURLING") {
+            builder = builder.set_override("url_unfurling", enabled)?;
+        }
+
+        if let Ok(rate) = std::env::var("UNFURL_REQUESTS_PER_SECOND") {
+            builder = builder.set_override("unfurl_requests_per_second", rate)?;
+        }
+
+        if let Ok(bucket) = std::env::var("BLOB_MIRROR_BUCKET") {
+            builder = builder.set_override("blob_mirror_bucket", bucket)?;
+        }
+
+        if let Ok(region) = std::env::var("BLOB_MIRROR_REGION") {
+            builder = builder.set_override("blob_mirror_region", region)?;
+        }
+
+        if let Ok(endpoint) = std::env::var("BLOB_MIRROR_ENDPOINT") {
+            builder = builder.set_override("blob_mirror_endpoint", endpoint)?;
+        }
+
+        if let Ok(public_url) = std::env::var("BLOB_MIRROR_PUBLIC_URL") {
+            builder = builder.set_override("blob_mirror_public_url", public_url)?;
+        }
+
+        if let Ok(prefix) = std::env::var("BLOB_MIRROR_PREFIX") {
+            builder = builder.set_override("blob_mirror_prefix", prefix)?;
+        }
+
         // Cleanup Configuration
         if let Ok(max_db_size_mb) = std::env::var("MAX_DB_SIZE_MB") {
             builder = builder.set_override("max_db_size_mb", max_db_size_mb)?;
@@ -199,11 +649,89 @@ impl Settings {
                 .set_override("sqlite_journal_size_limit_mb", sqlite_journal_size_limit_mb)?;
         }
 
+        if let Ok(delete_mode) = std::env::var("DELETE_MODE") {
+            builder = builder.set_override("delete_mode", delete_mode)?;
+        }
+
+        if let Ok(mode) = std::env::var("ACCOUNT_REMOVAL_MODE") {
+            builder = builder.set_override("account_removal_mode", mode)?;
+        }
+
+        if let Ok(projection) = std::env::var("RECORD_PROJECTION") {
+            builder = builder.set_override("record_projection", split_list(&projection))?;
+        }
+
+        if let Ok(sqlite_shards) = std::env::var("SQLITE_SHARDS") {
+            builder = builder.set_override("sqlite_shards", sqlite_shards)?;
+        }
+
+        if let Ok(rotation_minutes) = std::env::var("ROTATION_MINUTES") {
+            builder = builder.set_override("rotation_minutes", rotation_minutes)?;
+        }
+
+        if let Ok(compaction_interval) = std::env::var("COMPACTION_INTERVAL_MINUTES") {
+            builder = builder.set_override("compaction_interval_minutes", compaction_interval)?;
+        }
+
+        if let Ok(compaction_compress) = std::env::var("COMPACTION_COMPRESS") {
+            builder = builder.set_override("compaction_compress", compaction_compress)?;
+        }
+
+        if let Ok(archive_bucket) = std::env::var("ARCHIVE_BUCKET") {
+            builder = builder.set_override("archive_bucket", archive_bucket)?;
+        }
+
+        if let Ok(archive_prefix) = std::env::var("ARCHIVE_PREFIX") {
+            builder = builder.set_override("archive_prefix", archive_prefix)?;
+        }
+
         // Resource knobs with explicit env names for operability in .env files.
         if let Ok(max_concurrent_requests) = std::env::var("MAX_CONCURRENT_REQUESTS") {
             builder = builder.set_override("max_concurrent_requests", max_concurrent_requests)?;
         }
 
+        if let Ok(budget) = std::env::var("API_REQUEST_BUDGET") {
+            builder = builder.set_override("api_request_budget", budget)?;
+        }
+
+        if let Ok(window) = std::env::var("API_REQUEST_BUDGET_WINDOW_SECS") {
+            builder = builder.set_override("api_request_budget_window_secs", window)?;
+        }
+
+        if let Ok(enabled) = std::env::var("ADAPTIVE_DEGRADATION") {
+            builder = builder.set_override("adaptive_degradation", enabled)?;
+        }
+
+        if let Ok(latency) = std::env::var("DEGRADATION_LATENCY_MS") {
+            builder = builder.set_override("degradation_latency_ms", latency)?;
+        }
+
+        if let Ok(recovery) = std::env::var("DEGRADATION_RECOVERY_SECS") {
+            builder = builder.set_override("degradation_recovery_secs", recovery)?;
+        }
+
+        for (var, key) in [
+            ("HTTP_CONNECT_TIMEOUT_MS", "http.connect_timeout_ms"),
+            ("HTTP_REQUEST_TIMEOUT_MS", "http.request_timeout_ms"),
+            ("HTTP_MAX_RETRIES", "http.max_retries"),
+            ("HTTP_RETRY_BASE_DELAY_MS", "http.retry_base_delay_ms"),
+            ("HTTP_MAX_BACKOFF_MS", "http.max_backoff_ms"),
+        ] {
+            if let Ok(value) = std::env::var(var) {
+                if !value.trim().is_empty() {
+                    builder = builder.set_override(key, value)?;
+                }
+            }
+        }
+
+        if let Ok(user_agent) = std::env::var("HTTP_USER_AGENT") {
+            builder = builder.set_override("http_user_agent", user_agent)?;
+        }
+
+        if let Ok(contact_url) = std::env::var("OPERATOR_CONTACT_URL") {
+            builder = builder.set_override("operator_contact_url", contact_url)?;
+        }
+
         if let Ok(cache_size_users) = std::env::var("CACHE_SIZE_USERS") {
             builder = builder.set_override("cache_size_users", cache_size_users)?;
         }
@@ -216,10 +744,94 @@ impl Settings {
             builder = builder.set_override("channel_capacity", channel_capacity)?;
         }
 
+        if let Ok(http_unix_socket) = std::env::var("HTTP_UNIX_SOCKET") {
+            builder = builder.set_override("http_unix_socket", http_unix_socket)?;
+        }
+
+        if let Ok(tls_cert_path) = std::env::var("TLS_CERT_PATH") {
+            builder = builder.set_override("tls_cert_path", tls_cert_path)?;
+        }
+
+        if let Ok(tls_key_path) = std::env::var("TLS_KEY_PATH") {
+            builder = builder.set_override("tls_key_path", tls_key_path)?;
+        }
+
+        if let Ok(admin_token) = std::env::var("ADMIN_TOKEN") {
+            builder = builder.set_override("admin_token", admin_token)?;
+        }
+
+        if let Ok(trusted_proxies) = std::env::var("TRUSTED_PROXIES") {
+            builder = builder.set_override("trusted_proxies", trusted_proxies)?;
+        }
+
+        if let Ok(output_format) = std::env::var("OUTPUT_FORMAT") {
+            builder = builder.set_override("output_format", output_format)?;
+        }
+
+        if let Ok(broadcast_capacity) = std::env::var("BROADCAST_CAPACITY") {
+            builder = builder.set_override("broadcast_capacity", broadcast_capacity)?;
+        }
+
+        if let Ok(drain_timeout) = std::env::var("SHUTDOWN_DRAIN_TIMEOUT_SECS") {
+            builder = builder.set_override("shutdown_drain_timeout_secs", drain_timeout)?;
+        }
+
+        if let Ok(soft_limit) = std::env::var("MEMORY_SOFT_LIMIT_MB") {
+            builder = builder.set_override("memory_soft_limit_mb", soft_limit)?;
+        }
+
+        if let Ok(max_age) = std::env::var("HEALTH_MAX_MESSAGE_AGE_SECS") {
+            builder = builder.set_override("health_max_message_age_secs", max_age)?;
+        }
+
+        if let Ok(max_disconnected) = std::env::var("HEALTH_MAX_DISCONNECTED_SECS") {
+            builder = builder.set_override("health_max_disconnected_secs", max_disconnected)?;
+        }
+
+        if let Ok(min_ttl) = std::env::var("HEALTH_MIN_SESSION_TTL_SECS") {
+            builder = builder.set_override("health_min_session_ttl_secs", min_ttl)?;
+        }
+
+        if let Ok(max_rate) = std::env::var("HEALTH_MAX_SINK_ERROR_RATE") {
+            builder = builder.set_override("health_max_sink_error_rate", max_rate)?;
+        }
+
+        if let Ok(threshold) = std::env::var("SLOW_WRITE_THRESHOLD_MS") {
+            builder = builder.set_override("slow_write_threshold_ms", threshold)?;
+        }
+
+        if let Ok(batches) = std::env::var("SLOW_WRITE_BATCHES") {
+            builder = builder.set_override("slow_write_batches", batches)?;
+        }
+
+        if let Ok(parse_workers) = std::env::var("PARSE_WORKERS") {
+            builder = builder.set_override("parse_workers", parse_workers)?;
+        }
+
         if let Ok(trim_maxlen) = std::env::var("TRIM_MAXLEN") {
             builder = builder.set_override("trim_maxlen", trim_maxlen)?;
         }
 
+        if let Ok(encoding) = std::env::var("REDIS_PAYLOAD_ENCODING") {
+            builder = builder.set_override("redis_payload_encoding", encoding)?;
+        }
+
+        if let Ok(publish_deletes) = std::env::var("REDIS_PUBLISH_DELETES") {
+            builder = builder.set_override("redis_publish_deletes", publish_deletes)?;
+        }
+
+        if let Ok(standalone) = std::env::var("STANDALONE") {
+            builder = builder.set_override("standalone", standalone)?;
+        }
+
+        if let Ok(output_file) = std::env::var("STANDALONE_OUTPUT_FILE") {
+            builder = builder.set_override("standalone_output_file", output_file)?;
+        }
+
+        if let Ok(serve_only) = std::env::var("SERVE_ONLY") {
+            builder = builder.set_override("serve_only", serve_only)?;
+        }
+
         if let Ok(posthog_api_key) = std::env::var("POSTHOG_API_KEY") {
             builder = builder.set_override("posthog_api_key", posthog_api_key)?;
         }
@@ -232,13 +844,92 @@ impl Settings {
         let mut settings: Settings = settings.try_deserialize()?;
         settings.posthog_api_key = normalize_optional_setting(settings.posthog_api_key);
         settings.posthog_host = normalize_optional_setting(settings.posthog_host);
-
-        // Validate required settings
-        settings.validate()?;
+        settings.capture_dir = normalize_optional_setting(settings.capture_dir);
+        settings.replay_path = normalize_optional_setting(settings.replay_path);
+        settings.standalone_output_file =
+            normalize_optional_setting(settings.standalone_output_file);
+        settings.plc_directory_url = normalize_optional_setting(settings.plc_directory_url);
+        settings.http_user_agent = normalize_optional_setting(settings.http_user_agent);
+        settings.operator_contact_url = normalize_optional_setting(settings.operator_contact_url);
+        settings.pseudonymize_key = normalize_optional_setting(settings.pseudonymize_key);
+        settings.blob_mirror_bucket = normalize_optional_setting(settings.blob_mirror_bucket);
+        settings.archive_bucket = normalize_optional_setting(settings.archive_bucket);
+        settings.http_unix_socket = normalize_optional_setting(settings.http_unix_socket);
+        settings.tls_cert_path = normalize_optional_setting(settings.tls_cert_path);
+        settings.tls_key_path = normalize_optional_setting(settings.tls_key_path);
+        settings.admin_token = normalize_optional_setting(settings.admin_token);
+        settings.watchlist_redis_stream =
+            normalize_optional_setting(settings.watchlist_redis_stream);
+        settings.watchlist_webhook_url = normalize_optional_setting(settings.watchlist_webhook_url);
+        settings.did_blocklist_file = normalize_optional_setting(settings.did_blocklist_file);
+        settings.did_allowlist_file = normalize_optional_setting(settings.did_allowlist_file);
+        settings.blob_mirror_region = normalize_optional_setting(settings.blob_mirror_region);
+        settings.blob_mirror_endpoint = normalize_optional_setting(settings.blob_mirror_endpoint);
+        settings.blob_mirror_public_url =
+            normalize_optional_setting(settings.blob_mirror_public_url);
+
+        // Nested stream configs don't map onto config overrides, so parse them directly
+        if let Ok(output_streams) = std::env::var("OUTPUT_STREAMS") {
+            if !output_streams.trim().is_empty() {
+                settings.output_streams = serde_json::from_str(&output_streams)?;
+            }
+        }
+        if let Ok(overrides) = std::env::var("HTTP_CLIENT_OVERRIDES") {
+            if !overrides.trim().is_empty() {
+                settings.http_clients = serde_json::from_str(&overrides)?;
+            }
+        }
+        #[cfg(feature = "chaos")]
+        if let Ok(chaos) = std::env::var("CHAOS") {
+            if !chaos.trim().is_empty() {
+                settings.chaos = serde_json::from_str(&chaos)?;
+            }
+        }
 
         Ok(settings)
     }
 
+    /// Hydration client policy: its defaults, then `http`, then its override.
+    pub fn bluesky_http_policy(&self) -> HttpPolicy {
+        HttpPolicy::BLUESKY
+            .with_overrides(&self.http)
+            .with_overrides(&self.http_clients.bluesky)
+            .with_user_agent(self.user_agent())
+    }
+
+    pub fn auth_http_policy(&self) -> HttpPolicy {
+        HttpPolicy::AUTH
+            .with_overrides(&self.http)
+            .with_overrides(&self.http_clients.auth)
+            .with_user_agent(self.user_agent())
+    }
+
+    pub fn backfill_http_policy(&self) -> HttpPolicy {
+        HttpPolicy::BACKFILL
+            .with_overrides(&self.http)
+            .with_overrides(&self.http_clients.backfill)
+            .with_user_agent(self.user_agent())
+    }
+
+    /// `User-Agent` of every outbound request and WebSocket handshake.
+    pub fn user_agent(&self) -> String {
+        user_agent(
+            self.http_user_agent.as_deref(),
+            self.operator_contact_url.as_deref(),
+        )
+    }
+
+    /// Whether enriched records are written to stdout, which then carries
+    /// nothing else.
+    pub fn writes_records_to_stdout(&self) -> bool {
+        self.standalone && self.standalone_output_file.as_deref() == Some(STDOUT_PATH)
+    }
+
+    /// `trusted_proxies`, parsed; checked by `validate`.
+    pub fn trusted_proxies(&self) -> std::result::Result<TrustedProxies, String> {
+        self.trusted_proxies.parse()
+    }
+
     fn validate(&self) -> Result<()> {
         if self.stream_name.is_empty() {
             anyhow::bail!(
@@ -249,7 +940,7 @@ impl Settings {
             );
         }
 
-        if self.bluesky_handle.is_empty() {
+        if self.bluesky_handle.is_empty() && !self.serve_only {
             anyhow::bail!(
                 "BLUESKY_HANDLE environment variable is required\n\n\
                 To set up:\n\
@@ -259,7 +950,7 @@ impl Settings {
             );
         }
 
-        if self.bluesky_app_password.is_empty() {
+        if self.bluesky_app_password.is_empty() && !self.serve_only {
             anyhow::bail!(
                 "BLUESKY_APP_PASSWORD environment variable is required\n\n\
                 To set up:\n\
@@ -274,10 +965,82 @@ impl Settings {
             anyhow::bail!("batch_size must be greater than 0");
         }
 
+        if let Err(e) = self.trusted_proxies() {
+            anyhow::bail!("TRUSTED_PROXIES: {e}");
+        }
+
+        if self.ingest_mode == IngestMode::Replay && self.replay_path.is_none() {
+            anyhow::bail!("REPLAY_PATH is required when INGEST_MODE=replay");
+        }
+
+        if self.standalone_output_file.is_some() && !self.standalone {
+            anyhow::bail!("STANDALONE_OUTPUT_FILE requires STANDALONE=true");
+        }
+
+        let mut stream_names = std::collections::HashSet::new();
+        for stream in &self.output_streams {
+            if stream.name.is_empty() || !stream_names.insert(stream.name.as_str()) {
+                anyhow::bail!("OUTPUT_STREAMS names must be non-empty and unique");
+            }
+            if stream.name == WATCHLIST_STREAM {
+                anyhow::bail!(
+                    "OUTPUT_STREAMS name '{WATCHLIST_STREAM}' is reserved for the watchlist"
+                );
+            }
+        }
+
+        #[cfg(feature = "chaos")]
+        for (target, faults) in [
+            ("redis", self.chaos.redis),
+            ("sqlite", self.chaos.sqlite),
+            ("bluesky", self.chaos.bluesky),
+        ] {
+            if !(0.0..=1.0).contains(&faults.failure_rate)
+                || !(0.0..=1.0).contains(&faults.latency_rate)
+            {
+                anyhow::bail!("CHAOS {target} rates must be between 0.0 and 1.0");
+            }
+        }
+
+        if self.tls_cert_path.is_some() != self.tls_key_path.is_some() {
+            anyhow::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together");
+        }
+
+        if self.http_unix_socket.is_some() && self.tls_cert_path.is_some() {
+            anyhow::bail!("TLS is not supported on HTTP_UNIX_SOCKET; terminate TLS at the proxy");
+        }
+
+        if self.replay_speed < 0.0 {
+            anyhow::bail!("replay_speed must not be negative");
+        }
+
+        if self.url_unfurling && self.unfurl_requests_per_second == 0 {
+            anyhow::bail!("unfurl_requests_per_second must be greater than 0");
+        }
+
+        if self
+            .spam_drop_threshold
+            .is_some_and(|threshold| !(0.0..=1.0).contains(&threshold))
+        {
+            anyhow::bail!("spam_drop_threshold must be between 0.0 and 1.0");
+        }
+
+        if !(0.0..=1.0).contains(&self.health_max_sink_error_rate) {
+            anyhow::bail!("health_max_sink_error_rate must be between 0.0 and 1.0");
+        }
+
+        if self.slow_write_batches == 0 {
+            anyhow::bail!("slow_write_batches must be greater than 0");
+        }
+
         if self.max_concurrent_requests == 0 {
             anyhow::bail!("max_concurrent_requests must be greater than 0");
         }
 
+        if self.api_request_budget > 0 && self.api_request_budget_window_secs == 0 {
+            anyhow::bail!("api_request_budget_window_secs must be greater than 0");
+        }
+
         if self.cache_size_users == 0 || self.cache_size_posts == 0 {
             anyhow::bail!("cache_size_users and cache_size_posts must be greater than 0");
         }
@@ -298,6 +1061,16 @@ impl Settings {
             anyhow::bail!("sqlite_journal_size_limit_mb must be greater than 0");
         }
 
+        for (client, policy) in [
+            ("bluesky", self.bluesky_http_policy()),
+            ("auth", self.auth_http_policy()),
+            ("backfill", self.backfill_http_policy()),
+        ] {
+            policy
+                .validate()
+                .map_err(|e| anyhow::anyhow!("{client} client: {e}"))?;
+        }
+
         Ok(())
     }
 }
@@ -313,10 +1086,120 @@ fn default_jetstream_hosts() -> Vec<String> {
     ]
 }
 
+fn default_health_max_message_age_secs() -> u64 {
+    120
+}
+
+fn default_health_max_disconnected_secs() -> u64 {
+    60
+}
+
+fn default_health_min_session_ttl_secs() -> u64 {
+    300
+}
+
+fn default_health_max_sink_error_rate() -> f64 {
+    0.5
+}
+
+fn default_slow_write_threshold_ms() -> u64 {
+    1000
+}
+
+fn default_slow_write_batches() -> u32 {
+    5
+}
+
+/// The AppView's documented limit of 3000 requests per five minutes
+fn default_api_request_budget() -> u32 {
+    3000
+}
+
+fn default_api_request_budget_window_secs() -> u64 {
+    300
+}
+
+fn default_degradation_latency_ms() -> u64 {
+    2000
+}
+
+fn default_degradation_recovery_secs() -> u64 {
+    30
+}
+
+fn default_jetstream_probe_interval_secs() -> u64 {
+    300
+}
+
+fn default_firehose_hosts() -> Vec<String> {
+    vec!["bsky.network".to_string()]
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn default_sqlite_shards() -> usize {
+    1
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_archive_prefix() -> String {
+    "archives".to_string()
+}
+
 fn default_channel_capacity() -> usize {
     10_000
 }
 
+fn default_parse_workers() -> usize {
+    2
+}
+
+fn default_broadcast_capacity() -> usize {
+    crate::turbocharger::broadcast::DEFAULT_BROADCAST_CAPACITY
+}
+
+fn default_shutdown_drain_timeout_secs() -> u64 {
+    10
+}
+
+fn default_backfill_api_url() -> String {
+    "https://bsky.social/xrpc".to_string()
+}
+
+fn default_trusted_proxies() -> String {
+    "127.0.0.1,::1".to_string()
+}
+
+fn default_hydrate_on_miss_per_minute() -> u32 {
+    60
+}
+
+fn default_capture_rotate_mb() -> u64 {
+    256
+}
+
+fn default_replay_speed() -> f64 {
+    1.0
+}
+
+fn default_unfurl_requests_per_second() -> u32 {
+    5
+}
+
+fn default_blob_mirror_prefix() -> String {
+    "blobs".to_string()
+}
+
 fn default_wanted_collections() -> String {
     "app.bsky.feed.post".to_string()
 }
@@ -353,8 +1236,10 @@ mod tests {
 
     #[test]
     fn test_validation_missing_required_fields() {
-        let mut settings = Settings::default();
-        settings.stream_name = "".to_string();
+        let mut settings = Settings {
+            stream_name: "".to_string(),
+            ..Settings::default()
+        };
 
         assert!(settings.validate().is_err());
 
@@ -367,6 +1252,11 @@ mod tests {
         settings.bluesky_app_password = "".to_string();
 
         assert!(settings.validate().is_err());
+
+        // Serving stored data needs no Bluesky session
+        settings.bluesky_handle = "".to_string();
+        settings.serve_only = true;
+        assert!(settings.validate().is_ok());
     }
 
     #[test]
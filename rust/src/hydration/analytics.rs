@@ -0,0 +1,225 @@
+//! Lightweight text analytics for consumers that would otherwise post-process the
+//! stream: a lexicon-based sentiment score plus cashtag and link-domain extraction.
+
+use crate::models::enriched::EnrichedRecord;
+use serde_json::Value;
+
+const POSITIVE_WORDS: &[&str] = &[
+    "amazing",
+    "awesome",
+    "beautiful",
+    "best",
+    "better",
+    "bullish",
+    "congrats",
+    "cool",
+    "enjoy",
+    "excellent",
+    "excited",
+    "fantastic",
+    "fun",
+    "glad",
+    "good",
+    "great",
+    "happy",
+    "incredible",
+    "love",
+    "loved",
+    "nice",
+    "perfect",
+    "thanks",
+    "win",
+    "wonderful",
+];
+
+const NEGATIVE_WORDS: &[&str] = &[
+    "angry",
+    "awful",
+    "bad",
+    "bearish",
+    "boring",
+    "broken",
+    "crash",
+    "disappointed",
+    "fail",
+    "hate",
+    "horrible",
+    "lose",
+    "mad",
+    "sad",
+    "scam",
+    "sucks",
+    "terrible",
+    "tired",
+    "ugly",
+    "upset",
+    "worse",
+    "worst",
+    "wrong",
+];
+
+const NEGATIONS: &[&str] = &[
+    "not", "no", "never", "dont", "don't", "isnt", "isn't", "cant",
+];
+
+/// Normalization constant for `score / sqrt(score^2 + alpha)`, as in VADER.
+const SENTIMENT_ALPHA: f32 = 15.0;
+const MAX_TICKER_LEN: usize = 5;
+
+/// Fills the analytics fields of `record` from its post text and facets.
+pub fn enrich(record: &mut EnrichedRecord) {
+    let Some(text) = record.get_text() else {
+        return;
+    };
+    let sentiment = sentiment_score(text);
+    let tickers = extract_tickers(text);
+    let domains = record
+        .message
+        .commit
+        .as_ref()
+        .and_then(|commit| commit.record.as_ref())
+        .map(extract_domains)
+        .unwrap_or_default();
+
+    let metadata = &mut record.hydrated_metadata;
+    metadata.sentiment = sentiment;
+    metadata.tickers = tickers;
+    metadata.domains = domains;
+}
+
+/// Sentiment in `[-1, 1]`, or `None` when the text has no sentiment-bearing words.
+/// A negation directly before a word flips its polarity.
+pub fn sentiment_score(text: &str) -> Option<f32> {
+    let mut score = 0.0f32;
+    let mut hits = 0;
+    let mut negate = false;
+
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+    {
+        let word = word.to_lowercase();
+        let polarity = if POSITIVE_WORDS.contains(&word.as_str()) {
+            1.0
+        } else if NEGATIVE_WORDS.contains(&word.as_str()) {
+            -1.0
+        } else {
+            negate = NEGATIONS.contains(&word.as_str());
+            continue;
+        };
+        score += if negate { -polarity } else { polarity };
+        hits += 1;
+        negate = false;
+    }
+
+    (hits > 0).then(|| score / (score * score + SENTIMENT_ALPHA).sqrt())
+}
+
+/// Cashtags such as `$AAPL`, uppercased and deduplicated. Dollar amounts like `$5`
+/// are not tickers.
+pub fn extract_tickers(text: &str) -> Vec<String> {
+    let mut tickers: Vec<String> = Vec::new();
+    for (index, _) in text.match_indices('$') {
+        let preceded_by_word = text[..index]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric());
+        if preceded_by_word {
+            continue;
+        }
+        let symbol: String = text[index + 1..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        let is_ticker = (1..=MAX_TICKER_LEN).contains(&symbol.len())
+            && symbol.chars().all(|c| c.is_ascii_alphabetic());
+        if is_ticker {
+            let symbol = symbol.to_ascii_uppercase();
+            if !tickers.contains(&symbol) {
+                tickers.push(symbol);
+            }
+        }
+    }
+    tickers
+}
+
+/// Hosts of link facets and external embeds, lowercased with any `www.` stripped.
+pub fn extract_domains(record: &Value) -> Vec<String> {
+    let facet_links = record
+        .get("facets")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|facet| facet.get("features").and_then(Value::as_array))
+        .flatten()
+        .filter(|feature| {
+            feature.get("$type").and_then(Value::as_str) == Some("app.bsky.richtext.facet#link")
+        })
+        .filter_map(|feature| feature.get("uri").and_then(Value::as_str));
+    let external = record
+        .get("embed")
+        .and_then(|embed| embed.get("external"))
+        .and_then(|external| external.get("uri"))
+        .and_then(Value::as_str);
+
+    let mut domains: Vec<String> = Vec::new();
+    for uri in facet_links.chain(external) {
+        let Some(host) = url::Url::parse(uri)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+        else {
+            continue;
+        };
+        let host = host.strip_prefix("www.").map(String::from).unwrap_or(host);
+        if !domains.contains(&host) {
+            domains.push(host);
+        }
+    }
+    domains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+    use serde_json::json;
+
+    #[test]
+    fn test_sentiment_score_handles_negation() {
+        assert!(sentiment_score("What a great day, love it").unwrap() > 0.4);
+        assert!(sentiment_score("this is terrible").unwrap() < 0.0);
+        assert!(sentiment_score("not good at all").unwrap() < 0.0);
+        assert_eq!(sentiment_score("the meeting is at noon"), None);
+    }
+
+    #[test]
+    fn test_extracts_tickers_and_domains() {
+        assert_eq!(
+            extract_tickers("$aapl and $TSLA up, $TSLA again; paid $5 for US$ coffee"),
+            vec!["AAPL", "TSLA"]
+        );
+
+        let record = json!({
+            "text": "read this",
+            "facets": [{
+                "index": {"byteStart": 0, "byteEnd": 4},
+                "features": [
+                    {"$type": "app.bsky.richtext.facet#link", "uri": "https://www.Example.com/a"},
+                    {"$type": "app.bsky.richtext.facet#tag", "tag": "news"}
+                ]
+            }],
+            "embed": {
+                "$type": "app.bsky.embed.external",
+                "external": {"uri": "https://blog.example.org/post", "title": "", "description": ""}
+            }
+        });
+        assert_eq!(
+            extract_domains(&record),
+            vec!["example.com", "blog.example.org"]
+        );
+
+        let mut enriched = EnrichedRecord::new(create_post_message(0));
+        enrich(&mut enriched);
+        assert!(enriched.hydrated_metadata.tickers.is_empty());
+    }
+}
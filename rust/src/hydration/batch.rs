@@ -1,80 +1,408 @@
-use crate::client::{PostFetcher, ProfileFetcher};
-use crate::hydration::Hydrator;
-use crate::models::{enriched::EnrichedRecord, jetstream::JetstreamMessage, TurboResult};
-use futures::StreamExt;
+//! Coalesces lookups from concurrent callers into batched API requests. Keys
+//! queue until a batch fills or the oldest has waited `max_wait`, then one
+//! request goes out for all of them. A key that is already queued or in flight
+//! is joined rather than fetched again. The profile and post collectors in
+//! `BlueskyClient` are built on this, as can be any other batched endpoint.
+
+use crate::models::errors::{TurboError, TurboResult};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use metrics::counter;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tracing::{info, trace};
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchConfig {
+    /// Keys per request
+    pub batch_size: usize,
+    /// Longest a queued key waits for its batch to fill
+    pub max_wait: Duration,
+}
+
+/// Fetches one batch. Results line up with the keys; a missing entry or
+/// `None` means the key wasn't found.
+type BatchFetch<K, V> =
+    Arc<dyn Fn(Vec<K>) -> BoxFuture<'static, TurboResult<Vec<Option<V>>>> + Send + Sync>;
+
+type Waiter<V> = oneshot::Sender<TurboResult<Option<V>>>;
 
-pub struct BatchProcessor<P, Po> {
-    hydrator: Hydrator<P, Po>,
-    batch_size: usize,
-    max_wait_time: Duration,
+struct BatchState<K, V> {
+    pending: Vec<K>,
+    /// Callers waiting on each queued or in-flight key
+    waiters: HashMap<K, Vec<Waiter<V>>>,
+    /// When the oldest pending key was queued
+    oldest: Option<Instant>,
 }
 
-impl<P, Po> BatchProcessor<P, Po>
+pub struct BatchProcessor<K, V> {
+    name: &'static str,
+    config: BatchConfig,
+    fetch: BatchFetch<K, V>,
+    state: Arc<Mutex<BatchState<K, V>>>,
+    fill: Arc<BatchFillCounters>,
+}
+
+impl<K, V> BatchProcessor<K, V>
 where
-    P: ProfileFetcher + Send + Sync + 'static,
-    Po: PostFetcher + Send + Sync + 'static,
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Clone + Send + 'static,
 {
-    pub fn new(hydrator: Hydrator<P, Po>, batch_size: usize, max_wait_time: Duration) -> Self {
+    /// `name` labels logs and metrics, e.g. "Profile".
+    pub fn new<F, Fut>(name: &'static str, config: BatchConfig, fetch: F) -> Self
+    where
+        F: Fn(Vec<K>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = TurboResult<Vec<Option<V>>>> + Send + 'static,
+    {
+        let config = BatchConfig {
+            batch_size: config.batch_size.max(1),
+            ..config
+        };
         Self {
-            hydrator,
-            batch_size,
-            max_wait_time,
+            name,
+            config,
+            fetch: Arc::new(move |keys| fetch(keys).boxed()),
+            state: Arc::new(Mutex::new(BatchState {
+                pending: Vec::new(),
+                waiters: HashMap::new(),
+                oldest: None,
+            })),
+            fill: Arc::new(BatchFillCounters::new(config.batch_size.max(1))),
         }
     }
 
-    pub async fn process_stream<S>(&self, mut stream: S) -> TurboResult<Vec<EnrichedRecord>>
-    where
-        S: futures::Stream<Item = TurboResult<JetstreamMessage>> + Unpin,
-    {
-        let mut buffer = Vec::with_capacity(self.batch_size);
-        let mut results = Vec::new();
-        let mut last_flush = tokio::time::Instant::now();
-
-        while let Some(item) = stream.next().await {
-            match item {
-                Ok(message) => {
-                    buffer.push(message);
-
-                    // Flush if batch is full or max wait time reached
-                    if buffer.len() >= self.batch_size || last_flush.elapsed() >= self.max_wait_time
-                    {
-                        let batch = std::mem::take(&mut buffer);
-                        match self.hydrator.hydrate_batch(batch).await {
-                            Ok(processed) => {
-                                trace!("Processed batch of {} records", processed.len());
-                                results.extend(processed);
-                            }
-                            Err(e) => return Err(e),
+    pub fn config(&self) -> BatchConfig {
+        self.config
+    }
+
+    /// Values for `keys`, in the same order. Returns once every key has been
+    /// fetched, by this call's batches or by ones it joined.
+    pub async fn fetch(&self, keys: &[K]) -> TurboResult<Vec<Option<V>>> {
+        let receivers: Vec<_> = {
+            let mut state = self.lock();
+            let receivers = keys
+                .iter()
+                .map(|key| self.enqueue(&mut state, key.clone()))
+                .collect();
+            while state.pending.len() >= self.config.batch_size {
+                let batch: Vec<K> = state.pending.drain(..self.config.batch_size).collect();
+                self.dispatch(batch);
+            }
+            if state.pending.is_empty() {
+                state.oldest = None;
+            }
+            receivers
+        };
+
+        while let Some(deadline) = self.linger_deadline(keys) {
+            tokio::time::sleep_until(deadline).await;
+            self.flush_expired();
+        }
+
+        let mut results = Vec::with_capacity(receivers.len());
+        for receiver in receivers {
+            results.push(receiver.await.unwrap_or_else(|_| {
+                Err(TurboError::HydrationFailed(format!(
+                    "{} batch was dropped",
+                    self.name
+                )))
+            })?);
+        }
+        self.log_partial_percentage();
+        Ok(results)
+    }
+
+    /// How full the batches sent so far have been.
+    pub fn stats(&self) -> CollectorBatchStats {
+        self.fill.stats()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BatchState<K, V>> {
+        self.state.lock().expect("batch state lock poisoned")
+    }
+
+    fn enqueue(
+        &self,
+        state: &mut BatchState<K, V>,
+        key: K,
+    ) -> oneshot::Receiver<TurboResult<Option<V>>> {
+        let (sender, receiver) = oneshot::channel();
+        match state.waiters.get_mut(&key) {
+            Some(waiters) => {
+                waiters.push(sender);
+                counter!("jetstream_turbo_batch_keys_coalesced_total", "batch" => self.name)
+                    .increment(1);
+            }
+            None => {
+                state.waiters.insert(key.clone(), vec![sender]);
+                state.pending.push(key);
+                state.oldest.get_or_insert_with(Instant::now);
+            }
+        }
+        receiver
+    }
+
+    /// When to flush the pending batch, if any of `keys` are still in it.
+    fn linger_deadline(&self, keys: &[K]) -> Option<Instant> {
+        let state = self.lock();
+        if !keys.iter().any(|key| state.pending.contains(key)) {
+            return None;
+        }
+        state.oldest.map(|oldest| oldest + self.config.max_wait)
+    }
+
+    fn flush_expired(&self) {
+        let mut state = self.lock();
+        let expired = state
+            .oldest
+            .is_some_and(|oldest| oldest + self.config.max_wait <= Instant::now());
+        if !expired {
+            return;
+        }
+        let pending = std::mem::take(&mut state.pending);
+        state.oldest = None;
+        for batch in pending.chunks(self.config.batch_size) {
+            self.dispatch(batch.to_vec());
+        }
+    }
+
+    /// Sends `batch` on its own task, so it completes for every waiter even
+    /// if the caller that flushed it goes away.
+    fn dispatch(&self, batch: Vec<K>) {
+        self.fill.record(batch.len());
+        counter!("jetstream_turbo_batch_requests_total", "batch" => self.name).increment(1);
+        if batch.len() < self.config.batch_size {
+            counter!("jetstream_turbo_batch_partial_total", "batch" => self.name).increment(1);
+        }
+        info!(
+            "{} batch capacity: {}/{} ({:.0}%)",
+            self.name,
+            batch.len(),
+            self.config.batch_size,
+            batch.len() as f64 / self.config.batch_size as f64 * 100.0
+        );
+
+        let fetch = Arc::clone(&self.fetch);
+        let state = Arc::clone(&self.state);
+        let name = self.name;
+        tokio::spawn(async move {
+            let result = AssertUnwindSafe(fetch(batch.clone()))
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| {
+                    Err(TurboError::HydrationFailed(format!(
+                        "{name} batch fetch panicked"
+                    )))
+                });
+            let mut state = state.lock().expect("batch state lock poisoned");
+            match result {
+                Ok(values) => {
+                    let mut values = values.into_iter();
+                    for key in batch {
+                        let value = values.next().flatten();
+                        for waiter in state.waiters.remove(&key).unwrap_or_default() {
+                            let _ = waiter.send(Ok(value.clone()));
+                        }
+                    }
+                }
+                Err(error) => {
+                    let shared = share_error(&error);
+                    let mut original = Some(error);
+                    for key in batch {
+                        for waiter in state.waiters.remove(&key).unwrap_or_default() {
+                            let error = original.take().unwrap_or_else(|| share_error(&shared));
+                            let _ = waiter.send(Err(error));
                         }
-                        last_flush = tokio::time::Instant::now();
                     }
                 }
-                Err(e) => return Err(e),
             }
+        });
+    }
+
+    fn log_partial_percentage(&self) {
+        let stats = self.fill.stats();
+        let (total, partial) = (stats.batches_total, stats.batches_partial);
+        if total > 0 && total.is_multiple_of(10) {
+            let pct = (partial as f64 / total as f64) * 100.0;
+            info!(
+                "{} batch partial rate: {:.1}% ({}/{})",
+                self.name, pct, partial, total
+            );
         }
+    }
+}
 
-        // Process remaining items in buffer
-        if !buffer.is_empty() {
-            match self.hydrator.hydrate_batch(buffer).await {
-                Ok(processed) => {
-                    trace!("Processed final batch of {} records", processed.len());
-                    results.extend(processed);
-                }
-                Err(e) => return Err(e),
-            }
+/// Copy of a batch's error for the callers after the first. Kinds callers
+/// act on are kept; anything else is carried as its message.
+fn share_error(error: &TurboError) -> TurboError {
+    match error {
+        TurboError::RateLimited {
+            endpoint,
+            retry_after,
+        } => TurboError::RateLimited {
+            endpoint: endpoint.clone(),
+            retry_after: *retry_after,
+        },
+        TurboError::ExpiredToken(message) => TurboError::ExpiredToken(message.clone()),
+        TurboError::PermissionDenied(message) => TurboError::PermissionDenied(message.clone()),
+        TurboError::InvalidApiResponse(message) => TurboError::InvalidApiResponse(message.clone()),
+        TurboError::HydrationFailed(message) => TurboError::HydrationFailed(message.clone()),
+        #[cfg(feature = "chaos")]
+        TurboError::InjectedFault(source) => TurboError::InjectedFault(source),
+        other => TurboError::HydrationFailed(other.to_string()),
+    }
+}
+
+/// Batch fill counters for one processor, readable without touching its queue.
+#[derive(Debug)]
+struct BatchFillCounters {
+    capacity: usize,
+    batches_total: AtomicU64,
+    batches_partial: AtomicU64,
+    items_total: AtomicU64,
+}
+
+impl BatchFillCounters {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            batches_total: AtomicU64::new(0),
+            batches_partial: AtomicU64::new(0),
+            items_total: AtomicU64::new(0),
         }
+    }
 
-        info!("Total processed {} records", results.len());
-        Ok(results)
+    fn record(&self, batch_len: usize) {
+        self.batches_total.fetch_add(1, Ordering::Relaxed);
+        self.items_total
+            .fetch_add(batch_len as u64, Ordering::Relaxed);
+        if batch_len < self.capacity {
+            self.batches_partial.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
-    pub fn get_batch_size(&self) -> usize {
-        self.batch_size
+    fn stats(&self) -> CollectorBatchStats {
+        let batches_total = self.batches_total.load(Ordering::Relaxed);
+        let items_total = self.items_total.load(Ordering::Relaxed);
+        let slots = batches_total as f64 * self.capacity as f64;
+        CollectorBatchStats {
+            batch_capacity: self.capacity,
+            batches_total,
+            batches_partial: self.batches_partial.load(Ordering::Relaxed),
+            avg_fill_pct: if slots > 0.0 {
+                items_total as f64 / slots * 100.0
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectorBatchStats {
+    pub batch_capacity: usize,
+    pub batches_total: u64,
+    pub batches_partial: u64,
+    /// Mean items per batch as a percentage of `batch_capacity`
+    pub avg_fill_pct: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Batches = Arc<Mutex<Vec<Vec<u32>>>>;
+
+    fn recording_processor(
+        batch_size: usize,
+        max_wait: Duration,
+    ) -> (BatchProcessor<u32, u32>, Batches) {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&batches);
+        let processor = BatchProcessor::new(
+            "Test",
+            BatchConfig {
+                batch_size,
+                max_wait,
+            },
+            move |keys: Vec<u32>| {
+                recorded.lock().unwrap().push(keys.clone());
+                async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    Ok(keys
+                        .into_iter()
+                        .map(|key| (key % 2 == 1).then_some(key * 10))
+                        .collect())
+                }
+            },
+        );
+        (processor, batches)
     }
 
-    pub fn set_batch_size(&mut self, batch_size: usize) {
-        self.batch_size = batch_size;
+    #[tokio::test]
+    async fn test_concurrent_callers_share_batches_and_keys() {
+        let (processor, batches) = recording_processor(4, Duration::from_millis(50));
+
+        let (a, b, c) = tokio::join!(
+            processor.fetch(&[1, 2]),
+            processor.fetch(&[2, 3]),
+            processor.fetch(&[5, 7, 3, 9]),
+        );
+        assert_eq!(a.unwrap(), vec![Some(10), None]);
+        assert_eq!(b.unwrap(), vec![None, Some(30)]);
+        assert_eq!(c.unwrap(), vec![Some(50), Some(70), Some(30), Some(90)]);
+
+        let batches = batches.lock().unwrap().clone();
+        assert_eq!(batches, vec![vec![1, 2, 3, 5], vec![7, 9]]);
+        let stats = processor.stats();
+        assert_eq!(stats.batches_total, 2);
+        assert_eq!(stats.batches_partial, 1);
+        assert!((stats.avg_fill_pct - 75.0).abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_partial_batch_waits_for_max_wait() {
+        let (processor, batches) = recording_processor(4, Duration::from_millis(40));
+
+        let started = std::time::Instant::now();
+        assert_eq!(processor.fetch(&[1]).await.unwrap(), vec![Some(10)]);
+        assert!(started.elapsed() >= Duration::from_millis(40));
+
+        let (processor, _) = recording_processor(2, Duration::from_secs(60));
+        let full = tokio::time::timeout(Duration::from_secs(5), processor.fetch(&[1, 3, 5, 7]));
+        assert_eq!(full.await.unwrap().unwrap().len(), 4);
+        assert_eq!(batches.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch_fails_every_waiting_caller() {
+        let processor: BatchProcessor<u32, u32> = BatchProcessor::new(
+            "Test",
+            BatchConfig {
+                batch_size: 10,
+                max_wait: Duration::from_millis(10),
+            },
+            |_keys: Vec<u32>| async {
+                Err(TurboError::RateLimited {
+                    endpoint: "test".to_string(),
+                    retry_after: Some(Duration::from_secs(3)),
+                })
+            },
+        );
+
+        let (a, b) = tokio::join!(processor.fetch(&[1, 2]), processor.fetch(&[2]));
+        for result in [a, b] {
+            let error = result.unwrap_err();
+            assert!(matches!(error, TurboError::RateLimited { .. }));
+            assert_eq!(error.retry_after(), Some(Duration::from_secs(3)));
+        }
     }
 }
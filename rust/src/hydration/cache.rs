@@ -6,26 +6,155 @@ use std::sync::Arc;
 use std::time::Duration;
 use tracing::{instrument, trace};
 
-#[derive(Clone)]
-pub struct TurboCache {
+/// Where `TurboCache` keeps profiles and posts. The default is an in-process
+/// moka cache; implement this to keep them elsewhere, e.g. in a cache shared
+/// between instances. `TurboCache` counts hits and misses around it.
+pub trait CacheBackend: Send + Sync {
+    fn get_profile(&self, did: &str) -> Option<Arc<BlueskyProfile>>;
+    fn insert_profile(&self, did: String, profile: Arc<BlueskyProfile>);
+    fn remove_profile(&self, did: &str);
+    fn get_post(&self, uri: &str) -> Option<Arc<BlueskyPost>>;
+    fn insert_post(&self, uri: String, post: Arc<BlueskyPost>);
+
+    /// Checked before fetching, without counting as a lookup.
+    fn contains_profile(&self, did: &str) -> bool {
+        self.get_profile(did).is_some()
+    }
+
+    fn contains_post(&self, uri: &str) -> bool {
+        self.get_post(uri).is_some()
+    }
+
+    /// `(profiles, posts)` currently held.
+    fn entry_counts(&self) -> (u64, u64);
+    /// `(profiles, posts)` held at most.
+    fn capacity_limits(&self) -> (usize, usize);
+    fn clear(&self);
+}
+
+/// The default backend: bounded moka caches with a five-minute TTL.
+struct MokaBackend {
     user_cache: MokaCache<String, Arc<BlueskyProfile>, RandomState>,
     post_cache: MokaCache<String, Arc<BlueskyPost>, RandomState>,
     user_capacity: usize,
     post_capacity: usize,
+}
+
+impl MokaBackend {
+    /// Evictions are counted into `metrics`.
+    fn new(user_cache_size: usize, post_cache_size: usize, metrics: &Arc<CacheMetrics>) -> Self {
+        let user_metrics = Arc::clone(metrics);
+        let user_cache = MokaCache::builder()
+            .max_capacity(user_cache_size as u64)
+            .time_to_live(Duration::from_secs(300))
+            .eviction_listener(move |_k, _v, _cause| {
+                user_metrics.cache_evictions.fetch_add(1, Ordering::Relaxed);
+            })
+            .build_with_hasher(RandomState::default());
+
+        let post_metrics = Arc::clone(metrics);
+        let post_cache = MokaCache::builder()
+            .max_capacity(post_cache_size as u64)
+            .time_to_live(Duration::from_secs(300))
+            .eviction_listener(move |_k, _v, _cause| {
+                post_metrics.cache_evictions.fetch_add(1, Ordering::Relaxed);
+            })
+            .build_with_hasher(RandomState::default());
+
+        Self {
+            user_cache,
+            post_cache,
+            user_capacity: user_cache_size,
+            post_capacity: post_cache_size,
+        }
+    }
+}
+
+impl CacheBackend for MokaBackend {
+    fn get_profile(&self, did: &str) -> Option<Arc<BlueskyProfile>> {
+        self.user_cache.get(did)
+    }
+
+    fn insert_profile(&self, did: String, profile: Arc<BlueskyProfile>) {
+        self.user_cache.insert(did, profile);
+    }
+
+    fn remove_profile(&self, did: &str) {
+        self.user_cache.invalidate(did);
+    }
+
+    fn get_post(&self, uri: &str) -> Option<Arc<BlueskyPost>> {
+        self.post_cache.get(uri)
+    }
+
+    fn insert_post(&self, uri: String, post: Arc<BlueskyPost>) {
+        self.post_cache.insert(uri, post);
+    }
+
+    fn contains_profile(&self, did: &str) -> bool {
+        self.user_cache.contains_key(did)
+    }
+
+    fn contains_post(&self, uri: &str) -> bool {
+        self.post_cache.contains_key(uri)
+    }
+
+    fn entry_counts(&self) -> (u64, u64) {
+        (self.user_cache.entry_count(), self.post_cache.entry_count())
+    }
+
+    fn capacity_limits(&self) -> (usize, usize) {
+        (self.user_capacity, self.post_capacity)
+    }
+
+    fn clear(&self) {
+        self.user_cache.invalidate_all();
+        self.post_cache.invalidate_all();
+    }
+}
+
+#[derive(Clone)]
+pub struct TurboCache {
+    backend: Arc<dyn CacheBackend>,
     metrics: Arc<CacheMetrics>,
 }
 
+/// Hit/miss counters shared by every clone of the cache. Updates are relaxed atomic
+/// adds, so the lookup path never takes a lock.
 #[derive(Debug, Default)]
 pub struct CacheMetrics {
     pub user_hits: AtomicU64,
     pub user_misses: AtomicU64,
     pub post_hits: AtomicU64,
     pub post_misses: AtomicU64,
+    /// Never incremented by the cache; kept so existing readers still compile.
+    #[deprecated(note = "always 0; use `CacheMetrics::snapshot().total_requests`")]
     pub total_requests: AtomicU64,
     pub cache_evictions: AtomicU64,
 }
 
+impl CacheMetrics {
+    /// Point-in-time copy of the counters. Fields are loaded individually, so a
+    /// snapshot taken under load may be off by in-flight lookups.
+    pub fn snapshot(&self) -> CacheMetricsSnapshot {
+        let user_hits = self.user_hits.load(Ordering::Relaxed);
+        let user_misses = self.user_misses.load(Ordering::Relaxed);
+        let post_hits = self.post_hits.load(Ordering::Relaxed);
+        let post_misses = self.post_misses.load(Ordering::Relaxed);
+
+        CacheMetricsSnapshot {
+            user_hits,
+            user_misses,
+            post_hits,
+            post_misses,
+            total_requests: user_hits + user_misses + post_hits + post_misses,
+            cache_evictions: self.cache_evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
 impl Clone for CacheMetrics {
+    #[allow(deprecated)]
     fn clone(&self) -> Self {
         Self {
             user_hits: AtomicU64::new(self.user_hits.load(Ordering::Relaxed)),
@@ -41,44 +170,31 @@ impl Clone for CacheMetrics {
 impl TurboCache {
     pub fn new(user_cache_size: usize, post_cache_size: usize) -> Self {
         let metrics = Arc::new(CacheMetrics::default());
-
-        let user_metrics = Arc::clone(&metrics);
-        let user_cache = MokaCache::builder()
-            .max_capacity(user_cache_size as u64)
-            .time_to_live(Duration::from_secs(300))
-            .eviction_listener(move |_k, _v, _cause| {
-                user_metrics.cache_evictions.fetch_add(1, Ordering::Relaxed);
-            })
-            .build_with_hasher(RandomState::default());
-
-        let post_metrics = Arc::clone(&metrics);
-        let post_cache = MokaCache::builder()
-            .max_capacity(post_cache_size as u64)
-            .time_to_live(Duration::from_secs(300))
-            .eviction_listener(move |_k, _v, _cause| {
-                post_metrics.cache_evictions.fetch_add(1, Ordering::Relaxed);
-            })
-            .build_with_hasher(RandomState::default());
-
+        let backend = MokaBackend::new(user_cache_size, post_cache_size, &metrics);
         Self {
-            user_cache,
-            post_cache,
-            user_capacity: user_cache_size,
-            post_capacity: post_cache_size,
+            backend: Arc::new(backend),
             metrics,
         }
     }
 
+    /// A cache kept in `backend` instead of the built-in moka one.
+    pub fn with_backend(backend: Arc<dyn CacheBackend>) -> Self {
+        Self {
+            backend,
+            metrics: Arc::new(CacheMetrics::default()),
+        }
+    }
+
     pub fn get_entry_counts(&self) -> (u64, u64) {
-        (self.user_cache.entry_count(), self.post_cache.entry_count())
+        self.backend.entry_counts()
     }
 
     pub fn get_capacity_limits(&self) -> (usize, usize) {
-        (self.user_capacity, self.post_capacity)
+        self.backend.capacity_limits()
     }
 
     pub fn get_user_profile(&self, did: &str) -> Option<Arc<BlueskyProfile>> {
-        if let Some(profile) = self.user_cache.get(did) {
+        if let Some(profile) = self.backend.get_profile(did) {
             self.metrics.user_hits.fetch_add(1, Ordering::Relaxed);
             return Some(profile);
         }
@@ -92,7 +208,7 @@ impl TurboCache {
         let mut hits = 0_u64;
 
         for did in dids {
-            match self.user_cache.get(did) {
+            match self.backend.get_profile(did) {
                 Some(profile) => {
                     hits += 1;
                     profiles.push(Some(profile));
@@ -116,12 +232,17 @@ impl TurboCache {
     }
 
     pub fn set_user_profile(&self, did: String, profile: Arc<BlueskyProfile>) {
-        self.user_cache.insert(did.clone(), profile);
+        self.backend.insert_profile(did.clone(), profile);
         trace!("Cached user profile: {}", did);
     }
 
+    pub fn remove_user_profile(&self, did: &str) {
+        self.backend.remove_profile(did);
+        trace!("Evicted user profile: {}", did);
+    }
+
     pub fn get_post(&self, uri: &str) -> Option<Arc<BlueskyPost>> {
-        if let Some(post) = self.post_cache.get(uri) {
+        if let Some(post) = self.backend.get_post(uri) {
             self.metrics.post_hits.fetch_add(1, Ordering::Relaxed);
             return Some(post);
         }
@@ -135,7 +256,7 @@ impl TurboCache {
         let mut hits = 0_u64;
 
         for uri in uris {
-            match self.post_cache.get(uri) {
+            match self.backend.get_post(uri) {
                 Some(post) => {
                     hits += 1;
                     posts.push(Some(post));
@@ -159,7 +280,7 @@ impl TurboCache {
     }
 
     pub fn set_post(&self, uri: String, post: Arc<BlueskyPost>) {
-        self.post_cache.insert(uri.clone(), post);
+        self.backend.insert_post(uri.clone(), post);
         trace!("Cached post: {}", uri);
     }
 
@@ -167,7 +288,7 @@ impl TurboCache {
     pub fn check_user_profiles_cached(&self, dids: &[String]) -> Vec<bool> {
         tracing::Span::current().record("count", dids.len());
         dids.iter()
-            .map(|did| self.user_cache.contains_key(did))
+            .map(|did| self.backend.contains_profile(did))
             .collect()
     }
 
@@ -175,51 +296,21 @@ impl TurboCache {
     pub fn check_posts_cached(&self, uris: &[String]) -> Vec<bool> {
         tracing::Span::current().record("count", uris.len());
         uris.iter()
-            .map(|uri| self.post_cache.contains_key(uri))
+            .map(|uri| self.backend.contains_post(uri))
             .collect()
     }
 
     pub fn get_metrics(&self) -> CacheMetricsSnapshot {
-        let user_hits = self.metrics.user_hits.load(Ordering::Relaxed);
-        let user_misses = self.metrics.user_misses.load(Ordering::Relaxed);
-        let post_hits = self.metrics.post_hits.load(Ordering::Relaxed);
-        let post_misses = self.metrics.post_misses.load(Ordering::Relaxed);
-
-        CacheMetricsSnapshot {
-            user_hits,
-            user_misses,
-            post_hits,
-            post_misses,
-            total_requests: user_hits + user_misses + post_hits + post_misses,
-            cache_evictions: self.metrics.cache_evictions.load(Ordering::Relaxed),
-        }
+        self.metrics.snapshot()
     }
 
     pub fn clear(&self) {
-        self.user_cache.invalidate_all();
-        self.post_cache.invalidate_all();
+        self.backend.clear();
         trace!("Cleared all caches");
     }
 
     pub fn get_hit_rates(&self) -> (f64, f64) {
-        let user_hits = self.metrics.user_hits.load(Ordering::Relaxed);
-        let user_misses = self.metrics.user_misses.load(Ordering::Relaxed);
-        let post_hits = self.metrics.post_hits.load(Ordering::Relaxed);
-        let post_misses = self.metrics.post_misses.load(Ordering::Relaxed);
-
-        let user_hit_rate = if user_hits + user_misses > 0 {
-            user_hits as f64 / (user_hits + user_misses) as f64
-        } else {
-            0.0
-        };
-
-        let post_hit_rate = if post_hits + post_misses > 0 {
-            post_hits as f64 / (post_hits + post_misses) as f64
-        } else {
-            0.0
-        };
-
-        (user_hit_rate, post_hit_rate)
+        self.metrics.snapshot().hit_rates()
     }
 }
 
@@ -233,6 +324,23 @@ pub struct CacheMetricsSnapshot {
     pub cache_evictions: u64,
 }
 
+impl CacheMetricsSnapshot {
+    /// `(user, post)` hit rates, 0.0 when there have been no lookups.
+    pub fn hit_rates(&self) -> (f64, f64) {
+        let rate = |hits: u64, misses: u64| {
+            if hits + misses > 0 {
+                hits as f64 / (hits + misses) as f64
+            } else {
+                0.0
+            }
+        };
+        (
+            rate(self.user_hits, self.user_misses),
+            rate(self.post_hits, self.post_misses),
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -347,4 +455,27 @@ mod tests {
         assert_eq!(user_hit_rate, 1.0 / 3.0);
         assert_eq!(post_hit_rate, 0.0);
     }
+
+    #[test]
+    fn test_metrics_count_concurrent_lookups_without_loss() {
+        let cache = TurboCache::new(10, 10);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let cache = cache.clone();
+                scope.spawn(move || {
+                    for _ in 0..1000 {
+                        cache.get_user_profile("did:plc:missing");
+                        cache.get_posts(&["at://did:plc:missing/app.bsky.feed.post/1".to_string()]);
+                    }
+                });
+            }
+        });
+
+        let snapshot = cache.get_metrics();
+        assert_eq!(snapshot.user_misses, 4000);
+        assert_eq!(snapshot.post_misses, 4000);
+        assert_eq!(snapshot.total_requests, 8000);
+        assert_eq!(snapshot.hit_rates(), (0.0, 0.0));
+    }
 }
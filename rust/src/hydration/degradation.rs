@@ -0,0 +1,240 @@
+//! Adaptive hydration degradation. When the Bluesky API starts rate limiting
+//! or its latency climbs past a threshold, hydration steps down one level at a
+//! time, first skipping referenced-post fetches and then serving profiles from
+//! the cache alone, so the pipeline keeps pace with the firehose instead of
+//! queueing behind a struggling API. Once the API has been quiet for the
+//! recovery period it steps back up, again one level at a time.
+
+use metrics::{counter, gauge};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Weight of the newest fetch in the latency average.
+const LATENCY_EWMA_ALPHA: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DegradationLevel {
+    #[default]
+    Full,
+    /// Referenced posts are hydrated from the cache only
+    SkipPosts,
+    /// Profiles are too; nothing is fetched
+    CacheOnly,
+}
+
+impl DegradationLevel {
+    fn from_u8(level: u8) -> Self {
+        match level {
+            0 => Self::Full,
+            1 => Self::SkipPosts,
+            _ => Self::CacheOnly,
+        }
+    }
+
+    fn escalated(self) -> Self {
+        Self::from_u8(self as u8 + 1)
+    }
+
+    fn recovered(self) -> Self {
+        Self::from_u8((self as u8).saturating_sub(1))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DegradationConfig {
+    /// Average fetch latency that counts as pressure; zero leaves only rate
+    /// limiting as a trigger
+    pub latency_threshold: Duration,
+    /// How long the API has to go without pressure before stepping back up
+    pub recovery_period: Duration,
+    /// Minimum time between steps down, so one slow burst doesn't skip
+    /// straight to cache-only
+    pub escalation_interval: Duration,
+}
+
+impl Default for DegradationConfig {
+    fn default() -> Self {
+        Self {
+            latency_threshold: Duration::from_secs(2),
+            recovery_period: Duration::from_secs(30),
+            escalation_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct DegradationStats {
+    pub level: DegradationLevel,
+    /// Times hydration has stepped down since startup
+    pub escalations: u64,
+    /// Records hydrated with something left out because of degradation
+    pub partial_records: u64,
+    pub latency_ewma_ms: Option<f64>,
+}
+
+#[derive(Debug)]
+struct DegradationState {
+    latency_ewma_ms: Option<f64>,
+    last_pressure: Option<Instant>,
+    last_change: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub struct Degradation {
+    config: DegradationConfig,
+    level: AtomicU8,
+    escalations: AtomicU64,
+    partial_records: AtomicU64,
+    state: Mutex<DegradationState>,
+}
+
+impl Degradation {
+    pub fn new(config: DegradationConfig) -> Self {
+        Self {
+            config,
+            level: AtomicU8::new(DegradationLevel::Full as u8),
+            escalations: AtomicU64::new(0),
+            partial_records: AtomicU64::new(0),
+            state: Mutex::new(DegradationState {
+                latency_ewma_ms: None,
+                last_pressure: None,
+                last_change: None,
+            }),
+        }
+    }
+
+    pub fn level(&self) -> DegradationLevel {
+        DegradationLevel::from_u8(self.level.load(Ordering::Relaxed))
+    }
+
+    /// Records the outcome of one API fetch.
+    pub fn observe_fetch(&self, elapsed: Duration, rate_limited: bool) {
+        let mut state = self.lock();
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        let average = state.latency_ewma_ms.map_or(elapsed_ms, |average| {
+            average + LATENCY_EWMA_ALPHA * (elapsed_ms - average)
+        });
+        state.latency_ewma_ms = Some(average);
+
+        let slow = !self.config.latency_threshold.is_zero()
+            && average > self.config.latency_threshold.as_secs_f64() * 1000.0;
+        if !rate_limited && !slow {
+            return;
+        }
+        let now = Instant::now();
+        state.last_pressure = Some(now);
+
+        let level = self.level();
+        let settled = state
+            .last_change
+            .is_none_or(|changed| changed.elapsed() >= self.config.escalation_interval);
+        if level == DegradationLevel::CacheOnly || !settled {
+            return;
+        }
+        let level = level.escalated();
+        self.set_level(level);
+        state.last_change = Some(now);
+        self.escalations.fetch_add(1, Ordering::Relaxed);
+        counter!("jetstream_turbo_degradation_escalations_total").increment(1);
+        warn!(
+            "Bluesky API under pressure (rate limited: {}, average latency {:.0}ms), degrading hydration to {:?}",
+            rate_limited, average, level
+        );
+    }
+
+    /// Steps back up a level once the API has gone a recovery period without
+    /// pressure. Called ahead of each fetch, since at `CacheOnly` there are no
+    /// fetches left to observe.
+    pub fn maybe_recover(&self) -> DegradationLevel {
+        let level = self.level();
+        if level == DegradationLevel::Full {
+            return level;
+        }
+        let mut state = self.lock();
+        let quiet =
+            |at: Option<Instant>| at.is_none_or(|at| at.elapsed() >= self.config.recovery_period);
+        if !quiet(state.last_pressure) || !quiet(state.last_change) {
+            return level;
+        }
+        let level = level.recovered();
+        self.set_level(level);
+        state.last_change = Some(Instant::now());
+        // Latency from before the step says little about the API now
+        state.latency_ewma_ms = None;
+        info!(
+            "Bluesky API pressure eased, restoring hydration to {:?}",
+            level
+        );
+        level
+    }
+
+    pub fn record_partial(&self, records: usize) {
+        if records > 0 {
+            self.partial_records
+                .fetch_add(records as u64, Ordering::Relaxed);
+            counter!("jetstream_turbo_partially_hydrated_records_total").increment(records as u64);
+        }
+    }
+
+    pub fn stats(&self) -> DegradationStats {
+        DegradationStats {
+            level: self.level(),
+            escalations: self.escalations.load(Ordering::Relaxed),
+            partial_records: self.partial_records.load(Ordering::Relaxed),
+            latency_ewma_ms: self.lock().latency_ewma_ms,
+        }
+    }
+
+    fn set_level(&self, level: DegradationLevel) {
+        self.level.store(level as u8, Ordering::Relaxed);
+        gauge!("jetstream_turbo_degradation_level").set(level as u8 as f64);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DegradationState> {
+        self.state.lock().expect("degradation lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pressure_steps_down_and_quiet_steps_back_up() {
+        let degradation = Degradation::new(DegradationConfig {
+            latency_threshold: Duration::from_millis(100),
+            recovery_period: Duration::from_millis(30),
+            escalation_interval: Duration::from_millis(10),
+        });
+
+        degradation.observe_fetch(Duration::from_millis(20), false);
+        assert_eq!(degradation.level(), DegradationLevel::Full);
+
+        degradation.observe_fetch(Duration::from_millis(20), true);
+        assert_eq!(degradation.level(), DegradationLevel::SkipPosts);
+        // Too soon after the last step to take another
+        degradation.observe_fetch(Duration::from_millis(20), true);
+        assert_eq!(degradation.level(), DegradationLevel::SkipPosts);
+
+        std::thread::sleep(Duration::from_millis(15));
+        for _ in 0..5 {
+            degradation.observe_fetch(Duration::from_millis(500), false);
+        }
+        assert_eq!(degradation.level(), DegradationLevel::CacheOnly);
+        assert_eq!(degradation.maybe_recover(), DegradationLevel::CacheOnly);
+
+        std::thread::sleep(Duration::from_millis(35));
+        assert_eq!(degradation.maybe_recover(), DegradationLevel::SkipPosts);
+        assert_eq!(degradation.maybe_recover(), DegradationLevel::SkipPosts);
+        std::thread::sleep(Duration::from_millis(35));
+        assert_eq!(degradation.maybe_recover(), DegradationLevel::Full);
+
+        let stats = degradation.stats();
+        assert_eq!(stats.escalations, 2);
+        assert_eq!(stats.latency_ewma_ms, None);
+    }
+}
@@ -1,14 +1,47 @@
-use crate::client::{PostFetcher, ProfileFetcher};
+use crate::client::{PlcClient, PostFetcher, ProfileFetcher};
+use crate::hydration::degradation::{Degradation, DegradationLevel, DegradationStats};
+use crate::hydration::fetcher::DataFetcher;
+use crate::hydration::moderation::{self, LabelPolicy};
+use crate::hydration::pseudonymize::Pseudonymizer;
+use crate::hydration::spam::SpamScorer;
+use crate::hydration::unfurl::UrlUnfurler;
+use crate::hydration::validation::{LexiconValidator, ValidationMode};
 use crate::hydration::TurboCache;
-use crate::models::{enriched::EnrichedRecord, jetstream::JetstreamMessage, TurboResult};
+use crate::models::{
+    at_uri::AtUri,
+    bluesky::BlueskyProfile,
+    enriched::{EnrichedRecord, MediaSummary},
+    errors::TurboError,
+    jetstream::JetstreamMessage,
+    TurboResult,
+};
+use crate::telemetry::{DropCounters, DropReason};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
-use tracing::{info, trace};
+use tokio::sync::Semaphore;
+use tracing::{info, trace, warn};
+
+/// Background PLC lookups allowed at once. The directory is rate limited, so more
+/// would only queue; DIDs left out are picked up when they're seen again.
+const BACKGROUND_RESOLUTIONS: usize = 2;
 
 pub struct Hydrator<P, Po> {
     cache: TurboCache,
     profile_fetcher: Arc<P>,
     post_fetcher: Arc<Po>,
+    label_policy: Option<Arc<LabelPolicy>>,
+    validator: Option<Arc<LexiconValidator>>,
+    spam_scorer: Option<Arc<SpamScorer>>,
+    url_unfurler: Option<Arc<UrlUnfurler>>,
+    pseudonymizer: Option<Arc<Pseudonymizer>>,
+    did_resolver: Option<Arc<PlcClient>>,
+    background_resolutions: Arc<Semaphore>,
+    #[cfg(feature = "s3")]
+    blob_mirror: Option<Arc<crate::storage::BlobMirror>>,
+    skip_post_fetches: Arc<AtomicBool>,
+    degradation: Option<Arc<Degradation>>,
+    drops: DropCounters,
 }
 
 impl<P, Po> Clone for Hydrator<P, Po> {
@@ -17,10 +50,29 @@ impl<P, Po> Clone for Hydrator<P, Po> {
             cache: self.cache.clone(),
             profile_fetcher: Arc::clone(&self.profile_fetcher),
             post_fetcher: Arc::clone(&self.post_fetcher),
+            label_policy: self.label_policy.clone(),
+            validator: self.validator.clone(),
+            spam_scorer: self.spam_scorer.clone(),
+            url_unfurler: self.url_unfurler.clone(),
+            pseudonymizer: self.pseudonymizer.clone(),
+            did_resolver: self.did_resolver.clone(),
+            background_resolutions: Arc::clone(&self.background_resolutions),
+            #[cfg(feature = "s3")]
+            blob_mirror: self.blob_mirror.clone(),
+            skip_post_fetches: Arc::clone(&self.skip_post_fetches),
+            degradation: self.degradation.clone(),
+            drops: self.drops.clone(),
         }
     }
 }
 
+impl<F: DataFetcher> Hydrator<F, F> {
+    /// Hydrates with one fetcher for both profiles and posts.
+    pub fn from_fetcher(cache: TurboCache, fetcher: Arc<F>) -> Self {
+        Self::new(cache, Arc::clone(&fetcher), fetcher)
+    }
+}
+
 impl<P, Po> Hydrator<P, Po>
 where
     P: ProfileFetcher + Send + Sync + 'static,
@@ -31,20 +83,150 @@ where
             cache,
             profile_fetcher,
             post_fetcher,
+            label_policy: None,
+            validator: None,
+            spam_scorer: None,
+            url_unfurler: None,
+            pseudonymizer: None,
+            did_resolver: None,
+            background_resolutions: Arc::new(Semaphore::new(BACKGROUND_RESOLUTIONS)),
+            #[cfg(feature = "s3")]
+            blob_mirror: None,
+            skip_post_fetches: Arc::default(),
+            degradation: None,
+            drops: DropCounters::new(),
         }
     }
 
+    /// Flags or drops hydrated records carrying any of the policy's labels.
+    pub fn with_label_policy(mut self, policy: LabelPolicy) -> Self {
+        self.label_policy = (!policy.is_empty()).then(|| Arc::new(policy));
+        self
+    }
+
+    /// Tags or drops hydrated records that don't match their bundled lexicon.
+    pub fn with_validator(mut self, validator: LexiconValidator) -> Self {
+        self.validator = (validator.mode() != ValidationMode::Off).then(|| Arc::new(validator));
+        self
+    }
+
+    /// Attaches a `spam_score` to hydrated records and drops those at or above
+    /// the scorer's threshold.
+    pub fn with_spam_scorer(mut self, scorer: SpamScorer) -> Self {
+        self.spam_scorer = Some(Arc::new(scorer));
+        self
+    }
+
+    /// Attaches link previews to posts that link out without an embed card.
+    pub fn with_url_unfurler(mut self, unfurler: UrlUnfurler) -> Self {
+        self.url_unfurler = Some(Arc::new(unfurler));
+        self
+    }
+
+    /// Replaces identities in hydrated records with pseudonyms. Runs last, so
+    /// every sink and live consumer only sees pseudonymized records.
+    pub fn with_pseudonymizer(mut self, pseudonymizer: Pseudonymizer) -> Self {
+        self.pseudonymizer = Some(Arc::new(pseudonymizer));
+        self
+    }
+
+    /// Falls back to the DID document's handle for authors and mentions the
+    /// profile API returns nothing for. Batches don't wait on the directory:
+    /// unresolved DIDs are looked up in the background and hydrated from the
+    /// next record that references them.
+    pub fn with_did_resolver(mut self, resolver: Arc<PlcClient>) -> Self {
+        self.did_resolver = Some(resolver);
+        self
+    }
+
+    /// Mirrors avatars and images of hydrated records to object storage.
+    #[cfg(feature = "s3")]
+    pub fn with_blob_mirror(mut self, mirror: Arc<crate::storage::BlobMirror>) -> Self {
+        self.blob_mirror = Some(mirror);
+        self
+    }
+
+    /// Degrades hydration when the Bluesky API is rate limiting or slow:
+    /// referenced posts and then profiles are served from the cache alone,
+    /// and records missing either are marked `partial`.
+    pub fn with_degradation(mut self, degradation: Arc<Degradation>) -> Self {
+        self.degradation = Some(degradation);
+        self
+    }
+
+    /// Counts records dropped by the label policy, validation and spam
+    /// scoring, and references skipped for invalid URIs, into `drops`.
+    pub fn with_drop_counters(mut self, drops: DropCounters) -> Self {
+        self.drops = drops;
+        self
+    }
+
+    pub fn drop_counters(&self) -> &DropCounters {
+        &self.drops
+    }
+
+    /// While set, `prefetch_batch` fetches only profiles; referenced posts are
+    /// hydrated from the cache alone. Shared by every clone of this hydrator.
+    pub fn set_skip_post_fetches(&self, skip: bool) {
+        self.skip_post_fetches.store(skip, Ordering::Relaxed);
+    }
+
+    /// Records that failed lexicon validation since startup.
+    pub fn schema_drift_count(&self) -> u64 {
+        self.validator
+            .as_ref()
+            .map_or(0, |validator| validator.schema_drift_count())
+    }
+
+    /// Current degradation level and totals, when adaptive degradation is on.
+    pub fn degradation_stats(&self) -> Option<DegradationStats> {
+        self.degradation
+            .as_ref()
+            .map(|degradation| degradation.stats())
+    }
+
+    fn degradation_level(&self) -> DegradationLevel {
+        self.degradation
+            .as_ref()
+            .map_or(DegradationLevel::Full, |degradation| degradation.level())
+    }
+
+    /// Times one fetch for the degradation controller.
+    async fn observe_fetch<T>(
+        &self,
+        fetch: impl std::future::Future<Output = TurboResult<T>>,
+    ) -> TurboResult<T> {
+        let Some(degradation) = &self.degradation else {
+            return fetch.await;
+        };
+        let started = Instant::now();
+        let result = fetch.await;
+        degradation.observe_fetch(
+            started.elapsed(),
+            matches!(result, Err(TurboError::RateLimited { .. })),
+        );
+        result
+    }
+
     pub async fn hydrate_message(&self, message: JetstreamMessage) -> TurboResult<EnrichedRecord> {
         let start_time = Instant::now();
 
         // Extract needed fields as owned data before consuming the message
         let author_did = message.extract_did().to_string();
         let at_uri = message.extract_at_uri();
-        let mentioned_dids = message
+        let mut mentioned_dids = message
             .extract_mentioned_dids()
             .into_iter()
             .map(|s| s.to_string())
             .collect::<Vec<_>>();
+        if let Some(subject_did) = message
+            .typed_record()
+            .as_ref()
+            .and_then(|typed| typed.subject_did())
+        {
+            mentioned_dids.push(subject_did.to_string());
+        }
+        let post_uris = message.extract_post_uris();
 
         tracing::Span::current().record("did", &author_did);
         if let Some(ref uri) = at_uri {
@@ -54,6 +236,15 @@ where
         // Consume the message without cloning
         let mut enriched = EnrichedRecord::new(message);
 
+        // Deletes carry no record, so there is nothing to hydrate
+        if enriched.is_delete() {
+            trace!("Passing through delete event for DID: {}", author_did);
+            enriched.record_ingest_lag();
+            return Ok(enriched);
+        }
+
+        let level = self.degradation_level();
+
         // Hydrate author profile if this message has an at-uri (i.e., is a post)
         if at_uri.is_some() {
             let mut author_profile = self.cache.get_user_profile(author_did.as_str());
@@ -61,10 +252,12 @@ where
             let hit = author_profile.is_some();
             tracing::Span::current().record("cache_hit", hit);
 
-            if !hit {
+            if !hit && level < DegradationLevel::CacheOnly {
                 let profiles = self
-                    .profile_fetcher
-                    .bulk_fetch_profiles(&[author_did.to_string()])
+                    .observe_fetch(
+                        self.profile_fetcher
+                            .bulk_fetch_profiles(&[author_did.to_string()]),
+                    )
                     .await?;
 
                 if let Some(profile) = profiles.into_iter().next().flatten() {
@@ -78,6 +271,45 @@ where
             enriched.hydrated_metadata.author_profile = author_profile;
         }
 
+        // Collect moderation labels from the author, the record itself and referenced posts
+        let metadata = &mut enriched.hydrated_metadata;
+        if let Some(labels) = metadata
+            .author_profile
+            .as_ref()
+            .and_then(|profile| profile.labels.as_deref())
+        {
+            moderation::push_active_labels(labels, &mut metadata.labels);
+        }
+        if let Some(record) = enriched
+            .message
+            .commit
+            .as_ref()
+            .and_then(|commit| commit.record.as_ref())
+        {
+            moderation::push_self_labels(record, &mut metadata.labels);
+        }
+        for uri in &post_uris {
+            if let Some(labels) = self
+                .cache
+                .get_post(uri)
+                .and_then(|post| post.labels.clone())
+            {
+                moderation::push_active_labels(&labels, &mut metadata.labels);
+            }
+        }
+
+        // Hashtags, links and mentions from the post's facets, and its media
+        if let Some(commit) = enriched.message.commit.as_ref() {
+            let text = commit
+                .record
+                .as_ref()
+                .and_then(|record| record.get("text")?.as_str())
+                .unwrap_or_default();
+            let metadata = &mut enriched.hydrated_metadata;
+            metadata.extract_content_features(text, &commit.record);
+            metadata.media = commit.record.as_ref().and_then(MediaSummary::from_record);
+        }
+
         // Process mentions
         for did in &mentioned_dids {
             if let Some(profile) = self.cache.get_user_profile(did) {
@@ -85,8 +317,22 @@ where
             }
         }
 
+        // Only degradation is blamed for what's missing; a full-level miss is
+        // just a post or profile the API doesn't have
+        if level > DegradationLevel::Full {
+            let metadata = &mut enriched.hydrated_metadata;
+            metadata.partial = (at_uri.is_some() && metadata.author_profile.is_none())
+                || post_uris
+                    .iter()
+                    .any(|uri| self.cache.get_post(uri).is_none());
+        }
+
+        #[cfg(feature = "analytics")]
+        crate::hydration::analytics::enrich(&mut enriched);
+
         // Update metrics
         enriched.metrics.hydration_time_ms = start_time.elapsed().as_millis() as u64;
+        enriched.record_ingest_lag();
 
         trace!("Hydrated message for DID: {}", author_did);
         Ok(enriched)
@@ -96,15 +342,23 @@ where
         &self,
         messages: Vec<JetstreamMessage>,
     ) -> TurboResult<Vec<EnrichedRecord>> {
-        let start_time = Instant::now();
+        if let Err(e) = self.prefetch_batch(&messages).await {
+            warn!("Prefetch failed, hydrating from cache only: {}", e);
+        }
+        Ok(self.hydrate_prefetched(messages).await)
+    }
 
+    /// Fetches uncached profiles and posts referenced by `messages` into the cache.
+    /// Rate-limit errors are returned so the caller can schedule a retry; other fetch
+    /// failures leave the affected entries uncached.
+    pub async fn prefetch_batch(&self, messages: &[JetstreamMessage]) -> TurboResult<()> {
         let message_count = messages.len();
         tracing::Span::current().record("message_count", message_count);
 
         let mut unique_dids = std::collections::HashSet::new();
         let mut unique_uris = std::collections::HashSet::new();
 
-        for message in &messages {
+        for message in messages.iter().filter(|m| !m.is_delete_operation()) {
             unique_dids.insert(message.extract_did().to_string());
             for did in message.extract_mentioned_dids() {
                 unique_dids.insert(did.to_string());
@@ -112,7 +366,19 @@ where
             for uri in message.extract_post_uris() {
                 unique_uris.insert(uri);
             }
+            if let Some(typed) = message.typed_record() {
+                if let Some(did) = typed.subject_did() {
+                    unique_dids.insert(did.to_string());
+                }
+                if let Some(uri) = typed.subject_uri() {
+                    unique_uris.insert(uri.to_string());
+                }
+            }
         }
+        let referenced = unique_uris.len();
+        unique_uris.retain(|uri| AtUri::is_valid(uri));
+        self.drops
+            .record(DropReason::InvalidUri, referenced - unique_uris.len());
 
         let unique_dids_count = unique_dids.len();
         let unique_uris_count = unique_uris.len();
@@ -144,46 +410,198 @@ where
             .map(|(_, uri)| uri)
             .collect();
 
+        let level = self
+            .degradation
+            .as_ref()
+            .map_or(DegradationLevel::Full, |degradation| {
+                degradation.maybe_recover()
+            });
+
         // Fetch profiles and posts sequentially to avoid rate limiting
         let profiles_result = async {
-            if uncached_dids.is_empty() {
+            if uncached_dids.is_empty() || level >= DegradationLevel::CacheOnly {
                 return Ok(vec![]);
             }
-            self.profile_fetcher
-                .bulk_fetch_profiles(&uncached_dids)
+            self.observe_fetch(self.profile_fetcher.bulk_fetch_profiles(&uncached_dids))
                 .await
         }
         .await;
 
         let posts_result = async {
-            if uncached_uris.is_empty() {
+            if uncached_uris.is_empty()
+                || self.skip_post_fetches.load(Ordering::Relaxed)
+                || level >= DegradationLevel::SkipPosts
+            {
                 return Ok(vec![]);
             }
-            self.post_fetcher.bulk_fetch_posts(&uncached_uris).await
+            self.observe_fetch(self.post_fetcher.bulk_fetch_posts(&uncached_uris))
+                .await
         }
         .await;
 
         let api_fetch_time = cache_check_start.elapsed().as_millis() as u64 - cache_check_time;
         tracing::Span::current().record("api_fetch_time_ms", api_fetch_time);
 
-        if let Ok(profiles) = profiles_result {
-            for (did, maybe_profile) in uncached_dids.iter().zip(profiles) {
-                if let Some(profile) = maybe_profile {
-                    self.cache.set_user_profile(did.clone(), Arc::new(profile));
+        let mut rate_limited = None;
+
+        match profiles_result {
+            Ok(profiles) => {
+                let mut missing_dids = Vec::new();
+                for (did, maybe_profile) in uncached_dids.iter().zip(profiles) {
+                    match maybe_profile {
+                        Some(profile) => {
+                            self.cache.set_user_profile(did.clone(), Arc::new(profile))
+                        }
+                        None => missing_dids.push(did.clone()),
+                    }
                 }
+                self.resolve_missing_profiles_in_background(missing_dids);
             }
+            Err(e @ TurboError::RateLimited { .. }) => rate_limited = Some(e),
+            Err(e) => trace!("Profile prefetch failed: {}", e),
         }
 
-        if let Ok(posts) = posts_result {
-            for (uri, maybe_post) in uncached_uris.iter().zip(posts) {
-                if let Some(post) = maybe_post {
-                    self.cache.set_post(uri.clone(), Arc::new(post));
+        match posts_result {
+            Ok(posts) => {
+                for (uri, maybe_post) in uncached_uris.iter().zip(posts) {
+                    if let Some(post) = maybe_post {
+                        self.cache.set_post(uri.clone(), Arc::new(post));
+                    }
                 }
             }
+            Err(e @ TurboError::RateLimited { .. }) => rate_limited = Some(e),
+            Err(e) => trace!("Post prefetch failed: {}", e),
+        }
+
+        rate_limited.map_or(Ok(()), Err)
+    }
+
+    /// The cached profile for `did`, fetched and cached first on a miss.
+    pub async fn get_profile(&self, did: &str) -> TurboResult<Option<Arc<BlueskyProfile>>> {
+        if let Some(profile) = self.cache.get_user_profile(did) {
+            return Ok(Some(profile));
+        }
+
+        let dids = [did.to_string()];
+        match self.profile_fetcher.bulk_fetch_profiles(&dids).await?.pop() {
+            Some(Some(profile)) => self
+                .cache
+                .set_user_profile(did.to_string(), Arc::new(profile)),
+            _ => self.resolve_missing_profiles(&dids).await,
+        }
+        Ok(self.cache.get_user_profile(did))
+    }
+
+    /// Caches handle-only profiles for `dids` from DID documents resolved earlier,
+    /// and resolves the others on a background task so the batch isn't held
+    /// behind the directory's rate limit. If every background slot is busy they
+    /// are left for a later sighting.
+    fn resolve_missing_profiles_in_background(&self, dids: Vec<String>) {
+        let Some(resolver) = &self.did_resolver else {
+            return;
+        };
+        let (resolved, unresolved): (Vec<String>, Vec<String>) =
+            dids.into_iter().partition(|did| resolver.has_resolved(did));
+        cache_handle_profiles(&self.cache, resolver, &resolved);
+        if unresolved.is_empty() {
+            return;
+        }
+        let Ok(permit) = Arc::clone(&self.background_resolutions).try_acquire_owned() else {
+            trace!(
+                "PLC resolution busy; deferring {} DIDs to a later batch",
+                unresolved.len()
+            );
+            return;
+        };
+
+        let cache = self.cache.clone();
+        let resolver = Arc::clone(resolver);
+        tokio::spawn(async move {
+            let _permit = permit;
+            resolver.resolve_many(&unresolved).await;
+            cache_handle_profiles(&cache, &resolver, &unresolved);
+        });
+    }
+
+    /// Caches handle-only profiles from the DID resolver for `dids`.
+    async fn resolve_missing_profiles(&self, dids: &[String]) {
+        let Some(resolver) = &self.did_resolver else {
+            return;
+        };
+        if dids.is_empty() {
+            return;
         }
 
+        resolver.resolve_many(dids).await;
+        cache_handle_profiles(&self.cache, resolver, dids);
+    }
+
+    /// Hydrates `messages` from whatever `prefetch_batch` left in the cache and
+    /// applies the label policy, lexicon validation, spam scoring, link
+    /// unfurling and pseudonymization.
+    pub async fn hydrate_prefetched(&self, messages: Vec<JetstreamMessage>) -> Vec<EnrichedRecord> {
+        let start_time = Instant::now();
+
         let hydrate_start = Instant::now();
-        let results = self.hydrate_messages(messages).await;
+        let mut results = self.hydrate_messages(messages).await;
+        if let Some(degradation) = &self.degradation {
+            degradation.record_partial(
+                results
+                    .iter()
+                    .filter(|record| record.hydrated_metadata.partial)
+                    .count(),
+            );
+        }
+        let mut before = results.len();
+        if let Some(policy) = &self.label_policy {
+            let matched = policy.apply(&mut results);
+            self.drops
+                .record(DropReason::LabelPolicy, before - results.len());
+            before = results.len();
+            if matched > 0 {
+                trace!(
+                    "Label policy ({:?}) matched {} records",
+                    policy.mode(),
+                    matched
+                );
+            }
+        }
+        if let Some(validator) = &self.validator {
+            let invalid = validator.apply(&mut results);
+            self.drops
+                .record(DropReason::InvalidRecord, before - results.len());
+            before = results.len();
+            if invalid > 0 {
+                trace!(
+                    "Lexicon validation ({:?}) rejected {} records",
+                    validator.mode(),
+                    invalid
+                );
+            }
+        }
+        if let Some(scorer) = &self.spam_scorer {
+            let dropped = scorer.apply(&mut results);
+            self.drops.record(DropReason::Spam, before - results.len());
+            if dropped > 0 {
+                trace!(
+                    "Spam scoring (threshold {:?}) dropped {} records",
+                    scorer.drop_threshold(),
+                    dropped
+                );
+            }
+        }
+        if let Some(unfurler) = &self.url_unfurler {
+            let unfurled = unfurler.apply(&mut results);
+            trace!("Unfurled {} links", unfurled);
+        }
+        #[cfg(feature = "s3")]
+        if let Some(mirror) = &self.blob_mirror {
+            let mirrored = mirror.mirror_records(&mut results);
+            trace!("Mirrored {} blobs", mirrored);
+        }
+        if let Some(pseudonymizer) = &self.pseudonymizer {
+            pseudonymizer.apply(&mut results);
+        }
         let hydrate_time = hydrate_start.elapsed().as_millis() as u64;
         tracing::Span::current().record("hydrate_time_ms", hydrate_time);
 
@@ -196,7 +614,7 @@ where
             total_time
         );
 
-        Ok(results)
+        results
     }
 
     async fn hydrate_messages(&self, messages: Vec<JetstreamMessage>) -> Vec<EnrichedRecord> {
@@ -209,6 +627,7 @@ where
                 Ok(enriched) => results.push(enriched),
                 Err(e) => {
                     trace!("Failed to hydrate message: {}", e);
+                    self.drops.record(DropReason::HydrationFailed, 1);
                 }
             }
         }
@@ -219,3 +638,63 @@ where
         &self.cache
     }
 }
+
+/// Caches handle-only profiles for `dids` from documents `resolver` already holds.
+fn cache_handle_profiles(cache: &TurboCache, resolver: &PlcClient, dids: &[String]) {
+    for did in dids {
+        if let Some(profile) = resolver.cached(did).and_then(|doc| doc.handle_profile()) {
+            cache.set_user_profile(did.clone(), Arc::new(profile));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{create_post_message, MockPostFetcher, MockProfileFetcher};
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_prefetch_resolves_unknown_authors_in_the_background() {
+        let directory = MockServer::start().await;
+        let message = create_post_message(0);
+        let did = message.did.clone();
+        Mock::given(method("GET"))
+            .and(path(format!("/{did}/data")))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "did": did,
+                        "alsoKnownAs": ["at://resolved.example.com"],
+                    }))
+                    .set_delay(Duration::from_millis(500)),
+            )
+            .expect(1)
+            .mount(&directory)
+            .await;
+
+        let hydrator = Hydrator::new(
+            TurboCache::new(100, 100),
+            Arc::new(MockProfileFetcher::new()),
+            Arc::new(MockPostFetcher::new()),
+        )
+        .with_did_resolver(Arc::new(PlcClient::new(directory.uri()).unwrap()));
+
+        let started = Instant::now();
+        hydrator.prefetch_batch(&[message]).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert!(hydrator.get_cache().get_user_profile(&did).is_none());
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while hydrator.get_cache().get_user_profile(&did).is_none() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let profile = hydrator.get_cache().get_user_profile(&did).unwrap();
+        assert_eq!(profile.handle, "resolved.example.com");
+    }
+}
@@ -1,9 +1,25 @@
+#[cfg(feature = "analytics")]
+pub mod analytics;
 pub mod batch;
 pub mod cache;
+pub mod degradation;
 pub mod fetcher;
 pub mod hydrator;
+pub mod moderation;
+pub mod privacy;
+pub mod pseudonymize;
+pub mod spam;
+pub mod unfurl;
+pub mod validation;
 
-pub use batch::BatchProcessor;
-pub use cache::TurboCache;
+pub use batch::{BatchConfig, BatchProcessor};
+pub use cache::{CacheBackend, TurboCache};
+pub use degradation::{Degradation, DegradationConfig, DegradationLevel, DegradationStats};
 pub use fetcher::DataFetcher;
 pub use hydrator::Hydrator;
+pub use moderation::{LabelFilterMode, LabelPolicy};
+pub use privacy::{AuthorPrivacy, PrivacyMode, PrivacyStats};
+pub use pseudonymize::Pseudonymizer;
+pub use spam::SpamScorer;
+pub use unfurl::UrlUnfurler;
+pub use validation::{LexiconValidator, ValidationMode};
@@ -0,0 +1,197 @@
+use crate::models::bluesky::Label;
+use crate::models::enriched::EnrichedRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing::trace;
+
+/// What to do with a record that carries one of the filtered labels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelFilterMode {
+    /// Keep the record and list the matching labels in `flagged_labels`
+    #[default]
+    Flag,
+    /// Remove the record before it reaches any sink
+    Drop,
+}
+
+/// Appends the values of labels that are in effect, i.e. not negated by a later
+/// label with the same source and value. Duplicates are skipped.
+pub fn push_active_labels(labels: &[Label], out: &mut Vec<String>) {
+    for label in labels.iter().filter(|l| !l.neg.unwrap_or(false)) {
+        let negated = labels
+            .iter()
+            .any(|n| n.neg.unwrap_or(false) && n.src == label.src && n.val == label.val);
+        if !negated && !out.contains(&label.val) {
+            out.push(label.val.clone());
+        }
+    }
+}
+
+/// Appends the values of a record's self-labels (`com.atproto.label.defs#selfLabels`).
+pub fn push_self_labels(record: &serde_json::Value, out: &mut Vec<String>) {
+    let values = record
+        .get("labels")
+        .and_then(|labels| labels.get("values"))
+        .and_then(|values| values.as_array());
+
+    for val in values
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.get("val").and_then(|val| val.as_str()))
+    {
+        if !out.iter().any(|existing| existing == val) {
+            out.push(val.to_string());
+        }
+    }
+}
+
+/// Label values that should be flagged or dropped before records reach sinks.
+#[derive(Debug, Clone, Default)]
+pub struct LabelPolicy {
+    labels: HashSet<String>,
+    mode: LabelFilterMode,
+}
+
+impl LabelPolicy {
+    pub fn new(labels: impl IntoIterator<Item = String>, mode: LabelFilterMode) -> Self {
+        Self {
+            labels: labels
+                .into_iter()
+                .map(|label| label.trim().to_string())
+                .filter(|label| !label.is_empty())
+                .collect(),
+            mode,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn mode(&self) -> LabelFilterMode {
+        self.mode
+    }
+
+    /// Filtered labels present on `record`, in the order they were attached.
+    pub fn matching_labels(&self, record: &EnrichedRecord) -> Vec<String> {
+        record
+            .hydrated_metadata
+            .labels
+            .iter()
+            .filter(|label| self.labels.contains(label.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// Flags or drops records carrying a filtered label according to the policy mode.
+    /// Returns the number of records that matched.
+    pub fn apply(&self, records: &mut Vec<EnrichedRecord>) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+
+        let mut matched = 0;
+        match self.mode {
+            LabelFilterMode::Flag => {
+                for record in records.iter_mut() {
+                    let flagged = self.matching_labels(record);
+                    if !flagged.is_empty() {
+                        matched += 1;
+                        record.hydrated_metadata.flagged_labels = flagged;
+                    }
+                }
+            }
+            LabelFilterMode::Drop => {
+                records.retain(|record| {
+                    let keep = self.matching_labels(record).is_empty();
+                    if !keep {
+                        matched += 1;
+                        trace!(
+                            "Dropping labeled record: {:?}",
+                            record.get_at_uri().unwrap_or_default()
+                        );
+                    }
+                    keep
+                });
+            }
+        }
+        matched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn label(val: &str, neg: Option<bool>) -> Label {
+        Label {
+            src: "did:plc:labeler".to_string(),
+            uri: "did:plc:author".to_string(),
+            val: val.to_string(),
+            cts: Utc::now(),
+            neg,
+        }
+    }
+
+    fn labeled_record(index: usize, labels: &[&str]) -> EnrichedRecord {
+        let mut record = EnrichedRecord::new(create_post_message(index));
+        record.hydrated_metadata.labels = labels.iter().map(|l| l.to_string()).collect();
+        record
+    }
+
+    #[test]
+    fn test_active_and_self_labels_are_collected() {
+        let mut out = Vec::new();
+        push_active_labels(
+            &[
+                label("porn", None),
+                label("spam", Some(false)),
+                label("spam", None),
+                label("rude", None),
+                label("rude", Some(true)),
+            ],
+            &mut out,
+        );
+        assert_eq!(out, vec!["porn", "spam"]);
+
+        push_self_labels(
+            &json!({
+                "text": "hi",
+                "labels": {
+                    "$type": "com.atproto.label.defs#selfLabels",
+                    "values": [{"val": "nudity"}, {"val": "porn"}]
+                }
+            }),
+            &mut out,
+        );
+        assert_eq!(out, vec!["porn", "spam", "nudity"]);
+    }
+
+    #[test]
+    fn test_policy_flags_or_drops_matching_records() {
+        let flag = LabelPolicy::new(
+            vec!["!hide".to_string(), " porn ".to_string(), String::new()],
+            LabelFilterMode::Flag,
+        );
+        let mut records = vec![
+            labeled_record(0, &["porn", "spam"]),
+            labeled_record(1, &[]),
+            labeled_record(2, &["!hide"]),
+        ];
+        assert_eq!(flag.apply(&mut records), 2);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].hydrated_metadata.flagged_labels, vec!["porn"]);
+        assert!(records[1].hydrated_metadata.flagged_labels.is_empty());
+
+        let drop = LabelPolicy::new(vec!["porn".to_string()], LabelFilterMode::Drop);
+        assert_eq!(drop.apply(&mut records), 1);
+        assert_eq!(records.len(), 2);
+
+        let empty = LabelPolicy::default();
+        assert_eq!(empty.apply(&mut records), 0);
+    }
+}
@@ -0,0 +1,217 @@
+//! Authorship privacy. Authors can ask, with the `!no-unauthenticated` self
+//! label on their profile or on individual records, that their content not be
+//! shown to logged-out viewers. The WebSocket feeds and the record, thread and
+//! profile endpoints are exactly that, so the privacy mode can keep these
+//! authors out of them, and optionally out of every sink.
+
+use crate::hydration::moderation;
+use crate::models::bluesky::BlueskyProfile;
+use crate::models::enriched::EnrichedRecord;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Label asking that content not be shown to logged-out viewers.
+pub const NO_UNAUTHENTICATED: &str = "!no-unauthenticated";
+
+/// Where records of authors asking for privacy are left out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyMode {
+    /// Treated like any other record
+    #[default]
+    Off,
+    /// Stored and published to Redis, but not served over the WebSocket feeds
+    /// or the record, thread and profile endpoints
+    Public,
+    /// Dropped before any sink
+    All,
+}
+
+/// Whether `profile` carries an active `!no-unauthenticated` label.
+pub fn is_restricted_profile(profile: &BlueskyProfile) -> bool {
+    let mut labels = Vec::new();
+    moderation::push_active_labels(profile.labels.as_deref().unwrap_or_default(), &mut labels);
+    labels.iter().any(|label| label == NO_UNAUTHENTICATED)
+}
+
+/// Whether the record's author, or the record itself, asks not to be shown to
+/// logged-out viewers.
+pub fn is_restricted(record: &EnrichedRecord) -> bool {
+    if record
+        .hydrated_metadata
+        .author_profile
+        .as_deref()
+        .is_some_and(is_restricted_profile)
+    {
+        return true;
+    }
+    let mut labels = Vec::new();
+    if let Some(value) = record
+        .message
+        .commit
+        .as_ref()
+        .and_then(|commit| commit.record.as_ref())
+    {
+        moderation::push_self_labels(value, &mut labels);
+    }
+    labels.iter().any(|label| label == NO_UNAUTHENTICATED)
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PrivacyStats {
+    pub mode: PrivacyMode,
+    /// Records kept out of the WebSocket feeds and lookup endpoints
+    pub public_suppressed: u64,
+    /// Records dropped before any sink (`all` mode)
+    pub internal_suppressed: u64,
+}
+
+/// Applies the privacy mode and counts what it suppressed.
+#[derive(Debug, Default)]
+pub struct AuthorPrivacy {
+    mode: PrivacyMode,
+    public_suppressed: AtomicU64,
+    internal_suppressed: AtomicU64,
+}
+
+impl AuthorPrivacy {
+    pub fn new(mode: PrivacyMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn mode(&self) -> PrivacyMode {
+        self.mode
+    }
+
+    /// Drops restricted records in `all` mode, before they reach any sink.
+    pub fn apply_internal(&self, records: &mut Vec<EnrichedRecord>) -> usize {
+        if self.mode != PrivacyMode::All {
+            return 0;
+        }
+        let before = records.len();
+        records.retain(|record| !is_restricted(record));
+        let suppressed = before - records.len();
+        self.count(&self.internal_suppressed, "internal", suppressed);
+        suppressed
+    }
+
+    /// The records that may be served to public consumers.
+    pub fn public_records(&self, records: &[Arc<EnrichedRecord>]) -> Vec<Arc<EnrichedRecord>> {
+        if self.mode == PrivacyMode::Off {
+            return records.to_vec();
+        }
+        let public: Vec<Arc<EnrichedRecord>> = records
+            .iter()
+            .filter(|record| !is_restricted(record))
+            .cloned()
+            .collect();
+        self.count(
+            &self.public_suppressed,
+            "public",
+            records.len() - public.len(),
+        );
+        public
+    }
+
+    /// Whether a single looked-up record may be served publicly.
+    pub fn allows_record(&self, record: &EnrichedRecord) -> bool {
+        let allowed = self.mode == PrivacyMode::Off || !is_restricted(record);
+        if !allowed {
+            self.count(&self.public_suppressed, "public", 1);
+        }
+        allowed
+    }
+
+    /// Whether a looked-up profile may be served publicly.
+    pub fn allows_profile(&self, profile: &BlueskyProfile) -> bool {
+        let allowed = self.mode == PrivacyMode::Off || !is_restricted_profile(profile);
+        if !allowed {
+            self.count(&self.public_suppressed, "public", 1);
+        }
+        allowed
+    }
+
+    pub fn stats(&self) -> PrivacyStats {
+        PrivacyStats {
+            mode: self.mode,
+            public_suppressed: self.public_suppressed.load(Ordering::Relaxed),
+            internal_suppressed: self.internal_suppressed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn count(&self, counter: &AtomicU64, scope: &'static str, suppressed: usize) {
+        if suppressed > 0 {
+            counter.fetch_add(suppressed as u64, Ordering::Relaxed);
+            counter!("jetstream_turbo_privacy_suppressed_total", "scope" => scope)
+                .increment(suppressed as u64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bluesky::Label;
+    use crate::testing::fixtures::{create_post_message, create_profile};
+    use chrono::Utc;
+
+    fn restricted_profile(did: &str) -> BlueskyProfile {
+        let mut profile = create_profile(did);
+        profile.labels = Some(vec![Label {
+            src: did.to_string(),
+            uri: format!("at://{did}/app.bsky.actor.profile/self"),
+            val: NO_UNAUTHENTICATED.to_string(),
+            cts: Utc::now(),
+            neg: None,
+        }]);
+        profile
+    }
+
+    #[test]
+    fn test_restricted_authors_and_records_are_suppressed_by_mode() {
+        let mut private_author = EnrichedRecord::new(create_post_message(1));
+        private_author.hydrated_metadata.author_profile =
+            Some(Arc::new(restricted_profile(private_author.get_did())));
+        let mut private_record = create_post_message(2);
+        private_record
+            .commit
+            .as_mut()
+            .unwrap()
+            .record
+            .as_mut()
+            .unwrap()["labels"] = serde_json::json!({
+            "$type": "com.atproto.label.defs#selfLabels",
+            "values": [{"val": NO_UNAUTHENTICATED}]
+        });
+        let private_record = EnrichedRecord::new(private_record);
+        let public = EnrichedRecord::new(create_post_message(3));
+        assert!(is_restricted(&private_author));
+        assert!(is_restricted(&private_record));
+        assert!(!is_restricted(&public));
+
+        let records = vec![private_author, private_record, public];
+        let shared: Vec<_> = records.iter().cloned().map(Arc::new).collect();
+
+        let off = AuthorPrivacy::new(PrivacyMode::Off);
+        assert_eq!(off.public_records(&shared).len(), 3);
+        assert!(off.allows_profile(&restricted_profile("did:plc:private")));
+
+        let public_only = AuthorPrivacy::new(PrivacyMode::Public);
+        assert_eq!(public_only.apply_internal(&mut records.clone()), 0);
+        assert_eq!(public_only.public_records(&shared).len(), 1);
+        assert!(!public_only.allows_record(&records[0]));
+        assert!(!public_only.allows_profile(&restricted_profile("did:plc:private")));
+        assert_eq!(public_only.stats().public_suppressed, 4);
+
+        let all = AuthorPrivacy::new(PrivacyMode::All);
+        let mut records = records;
+        assert_eq!(all.apply_internal(&mut records), 2);
+        assert_eq!(records.len(), 1);
+        assert_eq!(all.stats().internal_suppressed, 2);
+    }
+}
@@ -0,0 +1,306 @@
+//! Pseudonymization for deployments that must not store identities. Every DID
+//! is replaced by a keyed hash shaped like a `did:plc`, wherever it appears
+//! (AT-URIs, record fields, CDN URLs), handles become `<hash>.pseudonym.invalid`,
+//! and display names, bios, avatars and banners are removed. The same DID maps
+//! to the same pseudonym for a given key, so threads, follows and per-author
+//! analysis still line up. Post text is kept as written.
+
+use crate::models::bluesky::{BlueskyProfile, Label};
+use crate::models::enriched::{EnrichedRecord, UrlEntry};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const KEY_CONTEXT: &str = "jetstream-turbo pseudonymize v1";
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+/// Same length as real `did:plc` identifiers
+const PSEUDONYM_CHARS: usize = 24;
+const HANDLE_CHARS: usize = 16;
+const PSEUDONYM_HANDLE_SUFFIX: &str = ".pseudonym.invalid";
+
+pub struct Pseudonymizer {
+    key: [u8; 32],
+}
+
+impl Pseudonymizer {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            key: blake3::derive_key(KEY_CONTEXT, secret.as_bytes()),
+        }
+    }
+
+    pub fn apply(&self, records: &mut [EnrichedRecord]) {
+        for record in records {
+            self.pseudonymize(record);
+        }
+    }
+
+    /// The pseudonym of a DID; also usable to find a known account's records.
+    pub fn did(&self, did: &str) -> String {
+        format!("did:plc:{}", self.encoded_hash(did, PSEUDONYM_CHARS))
+    }
+
+    /// Pseudonymous handle of the account `did`.
+    fn handle(&self, did: &str) -> String {
+        format!(
+            "{}{}",
+            self.encoded_hash(did, HANDLE_CHARS),
+            PSEUDONYM_HANDLE_SUFFIX
+        )
+    }
+
+    fn encoded_hash(&self, did: &str, chars: usize) -> String {
+        let hash = blake3::keyed_hash(&self.key, did.as_bytes());
+        let bytes = hash.as_bytes();
+        (0..chars)
+            .map(|i| {
+                // Five bits per character, read across byte boundaries
+                let bit = i * 5;
+                let window = u16::from_be_bytes([bytes[bit / 8], bytes[bit / 8 + 1]]);
+                let index = (window >> (11 - bit % 8)) & 0x1f;
+                BASE32_ALPHABET[index as usize] as char
+            })
+            .collect()
+    }
+
+    fn pseudonymize(&self, record: &mut EnrichedRecord) {
+        let message = &mut record.message;
+        let did = std::mem::take(&mut message.did);
+        message.did = self.did(&did);
+        if let Some(commit) = &mut message.commit {
+            if let Some(value) = &mut commit.record {
+                self.pseudonymize_value(value);
+            }
+        }
+        if let Some(identity) = &mut message.identity {
+            if identity.handle.is_some() {
+                identity.handle = Some(self.handle(&identity.did));
+            }
+            identity.did = self.did(&identity.did);
+        }
+        if let Some(account) = &mut message.account {
+            account.did = self.did(&account.did);
+        }
+
+        let metadata = &mut record.hydrated_metadata;
+        if let Some(profile) = &metadata.author_profile {
+            metadata.author_profile = Some(Arc::new(self.profile(profile)));
+        }
+        for profile in &mut metadata.mentioned_profiles {
+            *profile = Arc::new(self.profile(profile));
+        }
+        for post in &mut metadata.referenced_posts {
+            if post.author_handle.is_some() {
+                post.author_handle = Some(self.handle(&post.author_did));
+            }
+            post.author_did = self.did(&post.author_did).into();
+            post.uri = self.replace_dids(&post.uri).into_owned();
+        }
+        for mention in &mut metadata.mentions {
+            if mention.handle.is_some() {
+                mention.handle = Some(self.handle(&mention.did));
+            }
+            mention.display_name = None;
+            mention.did = self.did(&mention.did).into();
+        }
+        for entry in &mut metadata.urls {
+            match entry {
+                UrlEntry::Link(url) => *url = self.replace_dids(url).into_owned(),
+                UrlEntry::Unfurled(preview) => {
+                    preview.url = self.replace_dids(&preview.url).into_owned();
+                }
+            }
+        }
+        // Avatar and image URLs embed the owner's DID
+        metadata.mirrored_blobs = std::mem::take(&mut metadata.mirrored_blobs)
+            .into_iter()
+            .map(|(source, mirror)| (self.replace_dids(&source).into_owned(), mirror))
+            .collect::<BTreeMap<_, _>>();
+    }
+
+    fn profile(&self, profile: &BlueskyProfile) -> BlueskyProfile {
+        BlueskyProfile {
+            did: self.did(&profile.did).into(),
+            handle: self.handle(&profile.did),
+            display_name: None,
+            description: None,
+            avatar: None,
+            banner: None,
+            followers_count: profile.followers_count,
+            follows_count: profile.follows_count,
+            posts_count: profile.posts_count,
+            indexed_at: profile.indexed_at,
+            created_at: profile.created_at,
+            // Self-labels carry the account's DID as both source and subject
+            labels: profile.labels.as_ref().map(|labels| {
+                labels
+                    .iter()
+                    .map(|label| Label {
+                        src: self.replace_dids(&label.src).into_owned(),
+                        uri: self.replace_dids(&label.uri).into_owned(),
+                        ..label.clone()
+                    })
+                    .collect()
+            }),
+        }
+    }
+
+    fn pseudonymize_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => {
+                if let Cow::Owned(replaced) = self.replace_dids(text) {
+                    *text = replaced;
+                }
+            }
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(|item| self.pseudonymize_value(item)),
+            Value::Object(fields) => fields
+                .values_mut()
+                .for_each(|field| self.pseudonymize_value(field)),
+            _ => {}
+        }
+    }
+
+    /// Replaces every `did:plc` and `did:web` identifier inside `text`.
+    fn replace_dids<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.contains("did:") {
+            return Cow::Borrowed(text);
+        }
+        let mut replaced = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("did:") {
+            replaced.push_str(&rest[..start]);
+            let candidate = &rest[start..];
+            let length = did_length(candidate);
+            if length == 0 {
+                replaced.push_str("did:");
+                rest = &candidate[4..];
+            } else {
+                replaced.push_str(&self.did(&candidate[..length]));
+                rest = &candidate[length..];
+            }
+        }
+        replaced.push_str(rest);
+        Cow::Owned(replaced)
+    }
+}
+
+/// Length of the DID `text` starts with, or 0 if it doesn't start with one.
+fn did_length(text: &str) -> usize {
+    let (prefix, is_id_char): (&str, fn(char) -> bool) = if text.starts_with("did:plc:") {
+        ("did:plc:", |c| c.is_ascii_alphanumeric())
+    } else if text.starts_with("did:web:") {
+        ("did:web:", |c| {
+            c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '%' | ':')
+        })
+    } else {
+        return 0;
+    };
+    let id_length: usize = text[prefix.len()..]
+        .chars()
+        .take_while(|&c| is_id_char(c))
+        .map(char::len_utf8)
+        .sum();
+    // A trailing colon separates the DID from what follows
+    let id = text[prefix.len()..prefix.len() + id_length].trim_end_matches(':');
+    if id.is_empty() {
+        0
+    } else {
+        prefix.len() + id.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::at_uri::AtUri;
+    use crate::models::enriched::Mention;
+    use crate::testing::fixtures::create_post_message;
+
+    #[test]
+    fn test_identities_are_replaced_consistently() {
+        let pseudonymizer = Pseudonymizer::new("research-key");
+        let author = "did:plc:user0001";
+        let other = "did:plc:ewvi7nxzyoun6zhxrhs64oiz";
+
+        let mut message = create_post_message(1);
+        let fields = message.commit.as_mut().unwrap().record.as_mut().unwrap();
+        fields["reply"] = serde_json::json!({
+            "root": {"uri": format!("at://{other}/app.bsky.feed.post/3k"), "cid": "bafyroot"},
+            "parent": {"uri": format!("at://{other}/app.bsky.feed.post/3k"), "cid": "bafyroot"},
+        });
+        let mut record = EnrichedRecord::new(message);
+        let profile: BlueskyProfile = serde_json::from_value(serde_json::json!({
+            "did": author,
+            "handle": "user1.bsky.social",
+            "displayName": "User One",
+            "avatar": format!("https://cdn.bsky.app/img/avatar/plain/{author}/bafyavatar@jpeg"),
+            "followersCount": 3,
+            "labels": [{
+                "src": author,
+                "uri": format!("at://{author}/app.bsky.actor.profile/self"),
+                "val": "!no-unauthenticated",
+                "cts": "2024-01-01T00:00:00Z"
+            }]
+        }))
+        .unwrap();
+        record.hydrated_metadata.author_profile = Some(Arc::new(profile));
+        record.hydrated_metadata.mentions = vec![Mention {
+            did: other.into(),
+            handle: Some("other.bsky.social".to_string()),
+            display_name: Some("Other".to_string()),
+            start_byte: 0,
+            end_byte: 6,
+        }];
+        let mut records = vec![record];
+        pseudonymizer.apply(&mut records);
+
+        let record = &records[0];
+        let pseudonym = pseudonymizer.did(author);
+        assert_ne!(pseudonym, author);
+        assert_eq!(pseudonym, Pseudonymizer::new("research-key").did(author));
+        assert_ne!(pseudonym, Pseudonymizer::new("other-key").did(author));
+        assert_eq!(record.get_did(), pseudonym);
+        assert!(AtUri::is_valid(&record.get_at_uri().unwrap()));
+
+        let profile = record.hydrated_metadata.author_profile.as_ref().unwrap();
+        assert_eq!(&*profile.did, pseudonym);
+        assert!(profile.handle.ends_with(".pseudonym.invalid"));
+        assert!(profile.display_name.is_none() && profile.avatar.is_none());
+        assert_eq!(profile.followers_count, Some(3));
+        let label = &profile.labels.as_ref().unwrap()[0];
+        assert_eq!(label.src, pseudonym);
+        assert_eq!(label.val, "!no-unauthenticated");
+        assert_eq!(
+            &*record.hydrated_metadata.mentions[0].did,
+            pseudonymizer.did(other)
+        );
+        assert!(record.hydrated_metadata.mentions[0].display_name.is_none());
+
+        // No field of the output may still hold an original identity
+        let serialized = serde_json::to_string(record).unwrap();
+        for identity in [author, other, "user1.bsky.social", "User One", "Other\""] {
+            assert!(!serialized.contains(identity), "{identity} leaked");
+        }
+        assert!(serialized.contains(&format!(
+            "at://{}/app.bsky.feed.post/3k",
+            pseudonymizer.did(other)
+        )));
+    }
+
+    #[test]
+    fn test_dids_are_found_inside_longer_strings() {
+        let pseudonymizer = Pseudonymizer::new("key");
+        let web = pseudonymizer.did("did:web:example.com");
+        assert_eq!(
+            pseudonymizer.replace_dids("see did:web:example.com: and did:nope"),
+            format!("see {web}: and did:nope")
+        );
+        assert!(matches!(
+            pseudonymizer.replace_dids("no identifiers"),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+//! Heuristic bot and spam scoring. Each record gets a `spam_score` in `[0, 1]`
+//! from the author's cached profile (account age, follower ratio, lifetime
+//! posting rate) and from how often the same post text was seen recently.
+
+use crate::models::bluesky::BlueskyProfile;
+use crate::models::enriched::EnrichedRecord;
+use chrono::{DateTime, Utc};
+use metrics::counter;
+use moka::sync::Cache as MokaCache;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::trace;
+
+const ACCOUNT_AGE_WEIGHT: f32 = 0.25;
+const FOLLOW_RATIO_WEIGHT: f32 = 0.25;
+const POSTING_RATE_WEIGHT: f32 = 0.2;
+const DUPLICATE_TEXT_WEIGHT: f32 = 0.3;
+
+/// How long a post text is remembered for duplicate detection.
+const DUPLICATE_WINDOW: Duration = Duration::from_secs(10 * 60);
+const DUPLICATE_CAPACITY: u64 = 100_000;
+/// Short texts like "gm" repeat naturally and aren't counted.
+const MIN_DUPLICATE_TEXT_CHARS: usize = 20;
+
+pub struct SpamScorer {
+    drop_threshold: Option<f32>,
+    recent_texts: MokaCache<u64, Arc<AtomicU32>>,
+    hasher: ahash::RandomState,
+}
+
+impl SpamScorer {
+    /// Records scoring at or above `drop_threshold` are removed before the sinks.
+    pub fn new(drop_threshold: Option<f32>) -> Self {
+        Self {
+            drop_threshold,
+            recent_texts: MokaCache::builder()
+                .max_capacity(DUPLICATE_CAPACITY)
+                .time_to_live(DUPLICATE_WINDOW)
+                .build(),
+            hasher: ahash::RandomState::new(),
+        }
+    }
+
+    pub fn drop_threshold(&self) -> Option<f32> {
+        self.drop_threshold
+    }
+
+    /// Scores every record and drops those at or above the threshold. Returns
+    /// the number dropped.
+    pub fn apply(&self, records: &mut Vec<EnrichedRecord>) -> usize {
+        let now = Utc::now();
+        let mut dropped = 0;
+        records.retain_mut(|record| {
+            let Some(score) = self.score(record, now) else {
+                return true;
+            };
+            record.hydrated_metadata.spam_score = Some(score);
+            if self
+                .drop_threshold
+                .is_some_and(|threshold| score >= threshold)
+            {
+                dropped += 1;
+                trace!(
+                    "Dropping record with spam score {:.2}: {:?}",
+                    score,
+                    record.get_at_uri().unwrap_or_default()
+                );
+                return false;
+            }
+            true
+        });
+        if dropped > 0 {
+            counter!("jetstream_turbo_spam_dropped_total").increment(dropped as u64);
+        }
+        dropped
+    }
+
+    /// Deletes and non-commit events aren't scored.
+    fn score(&self, record: &EnrichedRecord, now: DateTime<Utc>) -> Option<f32> {
+        if record.is_delete() || record.message.commit.is_none() {
+            return None;
+        }
+        let profile_score = record
+            .hydrated_metadata
+            .author_profile
+            .as_deref()
+            .map_or(0.0, |profile| profile_score(profile, now));
+        let duplicate_score = record
+            .get_text()
+            .map_or(0.0, |text| duplicate_score(self.seen_count(text)));
+        let score = profile_score + DUPLICATE_TEXT_WEIGHT * duplicate_score;
+        // Two decimals is plenty for a heuristic and keeps payloads tidy
+        Some((score.clamp(0.0, 1.0) * 100.0).round() / 100.0)
+    }
+
+    /// How many times `text` was seen within the window, including this time.
+    fn seen_count(&self, text: &str) -> u32 {
+        let normalized = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if normalized.chars().count() < MIN_DUPLICATE_TEXT_CHARS {
+            return 1;
+        }
+        let key = self.hasher.hash_one(normalized.to_lowercase());
+        self.recent_texts
+            .get_with(key, || Arc::new(AtomicU32::new(0)))
+            .fetch_add(1, Ordering::Relaxed)
+            + 1
+    }
+}
+
+/// Weighted account age, follower ratio and posting rate signals.
+fn profile_score(profile: &BlueskyProfile, now: DateTime<Utc>) -> f32 {
+    let age_days = profile
+        .created_at
+        .map(|created| (now - created).num_hours() as f32 / 24.0);
+
+    let account_age = match age_days {
+        Some(days) if days < 1.0 => 1.0,
+        Some(days) if days < 7.0 => 0.6,
+        Some(days) if days < 30.0 => 0.3,
+        _ => 0.0,
+    };
+
+    // Following many accounts with almost no one following back
+    let follow_ratio = match (profile.followers_count, profile.follows_count) {
+        (Some(followers), Some(follows)) if follows >= 50 => {
+            let ratio = followers as f32 / follows as f32;
+            if ratio < 0.05 {
+                1.0
+            } else if ratio < 0.2 {
+                0.5
+            } else {
+                0.0
+            }
+        }
+        _ => 0.0,
+    };
+
+    let posting_rate = match (profile.posts_count, age_days) {
+        (Some(posts), Some(days)) => {
+            let per_day = posts as f32 / days.max(1.0);
+            if per_day > 200.0 {
+                1.0
+            } else if per_day > 50.0 {
+                0.5
+            } else {
+                0.0
+            }
+        }
+        _ => 0.0,
+    };
+
+    ACCOUNT_AGE_WEIGHT * account_age
+        + FOLLOW_RATIO_WEIGHT * follow_ratio
+        + POSTING_RATE_WEIGHT * posting_rate
+}
+
+fn duplicate_score(seen: u32) -> f32 {
+    match seen {
+        0..=2 => 0.0,
+        3..=4 => 0.5,
+        _ => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+    use chrono::Duration as ChronoDuration;
+
+    fn record_with_text(index: usize, text: &str) -> EnrichedRecord {
+        let mut message = create_post_message(index);
+        message.commit.as_mut().unwrap().record = Some(serde_json::json!({ "text": text }));
+        EnrichedRecord::new(message)
+    }
+
+    fn profile(age: ChronoDuration, followers: u64, follows: u64, posts: u64) -> BlueskyProfile {
+        let mut profile: BlueskyProfile = serde_json::from_value(serde_json::json!({
+            "did": "did:plc:user0001",
+            "handle": "user1.bsky.social"
+        }))
+        .unwrap();
+        profile.created_at = Some(Utc::now() - age);
+        profile.followers_count = Some(followers);
+        profile.follows_count = Some(follows);
+        profile.posts_count = Some(posts);
+        profile
+    }
+
+    #[test]
+    fn test_profile_signals_separate_new_mass_following_accounts() {
+        let now = Utc::now();
+        let spammy = profile(ChronoDuration::hours(6), 2, 2000, 900);
+        let established = profile(ChronoDuration::days(900), 300, 250, 4000);
+
+        assert!((profile_score(&spammy, now) - 0.7).abs() < 1e-3);
+        assert_eq!(profile_score(&established, now), 0.0);
+    }
+
+    #[test]
+    fn test_repeated_text_raises_score_until_dropped() {
+        let scorer = SpamScorer::new(Some(0.5));
+        let text = "Claim your free airdrop now at the link below!";
+
+        let mut records: Vec<EnrichedRecord> = (0..5).map(|i| record_with_text(i, text)).collect();
+        records.extend((0..3).map(|_| record_with_text(9, "gm")));
+        for record in &mut records[1..] {
+            // Scores 0.2 on its own: ten days old and following mostly strangers
+            record.hydrated_metadata.author_profile =
+                Some(Arc::new(profile(ChronoDuration::days(10), 5, 100, 100)));
+        }
+
+        assert_eq!(scorer.apply(&mut records), 1);
+        let scores: Vec<Option<f32>> = records
+            .iter()
+            .map(|record| record.hydrated_metadata.spam_score)
+            .collect();
+        // The fifth copy of the text pushes the score to the threshold
+        assert_eq!(
+            scores,
+            vec![
+                Some(0.0),
+                Some(0.2),
+                Some(0.35),
+                Some(0.35),
+                Some(0.2),
+                Some(0.2),
+                Some(0.2)
+            ]
+        );
+    }
+}
@@ -0,0 +1,570 @@
+//! Link previews. Posts that link out without an external embed card get the
+//! OpenGraph title, description and image of each linked page. Pages are
+//! fetched in the background by a dedicated client under its own rate limit
+//! and cached by URL, so a link shared many times is fetched once and no batch
+//! waits on a slow site.
+
+use crate::client::http::{client_builder, DEFAULT_USER_AGENT};
+use crate::models::enriched::{EnrichedRecord, UnfurledUrl, UrlEntry};
+use crate::models::TurboResult;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use metrics::counter;
+use moka::sync::Cache as MokaCache;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{header::CONTENT_TYPE, redirect, Client};
+use std::collections::{BTreeSet, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::trace;
+use url::{Host, Url};
+
+const UNFURL_TIMEOUT: Duration = Duration::from_secs(5);
+const UNFURL_CONCURRENCY: usize = 8;
+const UNFURL_MAX_REDIRECTS: usize = 3;
+const UNFURL_CACHE_SIZE: u64 = 50_000;
+/// Links past this many queued or fetching are left plain until a later batch
+const MAX_PENDING_UNFURLS: usize = 1_000;
+const UNFURL_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+/// OpenGraph tags live in the head, so pages are only read this far.
+const MAX_HTML_BYTES: usize = 256 * 1024;
+const MAX_FIELD_CHARS: usize = 300;
+const EXTERNAL_EMBED: &str = "app.bsky.embed.external";
+
+pub struct UrlUnfurler {
+    http_client: Client,
+    rate_limiter: DefaultDirectRateLimiter,
+    /// `None` for pages without metadata and failed fetches, so they aren't retried
+    cache: MokaCache<String, Option<UnfurledUrl>>,
+    allow_private_hosts: bool,
+    /// Links queued or fetching, so each is only queued once
+    pending: Mutex<HashSet<String>>,
+    fetches: Semaphore,
+}
+
+impl UrlUnfurler {
+    pub fn new(requests_per_second: u32) -> TurboResult<Self> {
+        Self::build(requests_per_second, false)
+    }
+
+    fn build(requests_per_second: u32, allow_private_hosts: bool) -> TurboResult<Self> {
+        Ok(Self {
+            http_client: Self::http_client(DEFAULT_USER_AGENT, allow_private_hosts)?,
+            rate_limiter: RateLimiter::direct(Quota::per_second(
+                NonZeroU32::new(requests_per_second.max(1)).expect("non-zero rate"),
+            )),
+            cache: MokaCache::builder()
+                .max_capacity(UNFURL_CACHE_SIZE)
+                .time_to_live(UNFURL_TTL)
+                .build(),
+            allow_private_hosts,
+            pending: Mutex::new(HashSet::new()),
+            fetches: Semaphore::new(UNFURL_CONCURRENCY),
+        })
+    }
+
+    /// Page fetches identify as `{user_agent} (link preview)`.
+    pub fn with_user_agent(mut self, user_agent: &str) -> TurboResult<Self> {
+        self.http_client = Self::http_client(user_agent, self.allow_private_hosts)?;
+        Ok(self)
+    }
+
+    fn http_client(user_agent: &str, allow_private_hosts: bool) -> TurboResult<Client> {
+        let redirect_policy = redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= UNFURL_MAX_REDIRECTS {
+                attempt.stop()
+            } else if allow_private_hosts || is_public_url(attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        });
+        let mut builder = client_builder(&format!("{user_agent} (link preview)"))
+            .timeout(UNFURL_TIMEOUT)
+            .redirect(redirect_policy);
+        if !allow_private_hosts {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Replaces plain links of posts without an external embed with their
+    /// previews, for pages fetched earlier. The others are queued for a
+    /// background fetch while the rate limit allows and previewed on records
+    /// seen after it finishes; links over the limit stay plain until a later
+    /// batch. Returns the number of links unfurled.
+    pub fn apply(self: &Arc<Self>, records: &mut [EnrichedRecord]) -> usize {
+        let uncached: BTreeSet<String> = records
+            .iter()
+            .filter(|record| !has_external_embed(record))
+            .flat_map(plain_links)
+            .filter(|url| !self.cache.contains_key(url))
+            .collect();
+        for url in uncached {
+            self.enqueue(url);
+        }
+
+        let mut unfurled = 0;
+        for record in records.iter_mut() {
+            if has_external_embed(record) {
+                continue;
+            }
+            for entry in &mut record.hydrated_metadata.urls {
+                if let UrlEntry::Link(url) = entry {
+                    if let Some(Some(preview)) = self.cache.get(url.as_str()) {
+                        *entry = UrlEntry::Unfurled(preview);
+                        unfurled += 1;
+                    }
+                }
+            }
+        }
+        if unfurled > 0 {
+            counter!("jetstream_turbo_urls_unfurled_total").increment(unfurled as u64);
+        }
+        unfurled
+    }
+
+    fn enqueue(self: &Arc<Self>, url: String) {
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if pending.contains(&url) {
+                return;
+            }
+            if pending.len() >= MAX_PENDING_UNFURLS {
+                counter!("jetstream_turbo_unfurl_skipped_total").increment(1);
+                return;
+            }
+            if self.rate_limiter.check().is_err() {
+                counter!("jetstream_turbo_unfurl_rate_limited_total").increment(1);
+                return;
+            }
+            pending.insert(url.clone());
+        }
+
+        let unfurler = Arc::clone(self);
+        tokio::spawn(async move {
+            let _fetch = unfurler.fetches.acquire().await;
+            let preview = match unfurler.fetch(&url).await {
+                Ok(preview) => preview,
+                Err(e) => {
+                    counter!("jetstream_turbo_unfurl_failures_total").increment(1);
+                    trace!("Failed to unfurl {}: {}", url, e);
+                    None
+                }
+            };
+            unfurler.cache.insert(url.clone(), preview);
+            unfurler
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&url);
+        });
+    }
+
+    /// Whether any link is still queued or fetching.
+    pub fn has_pending(&self) -> bool {
+        !self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+
+    async fn fetch(&self, url: &str) -> TurboResult<Option<UnfurledUrl>> {
+        let Ok(parsed) = Url::parse(url) else {
+            return Ok(None);
+        };
+        if !self.allow_private_hosts && !is_public_url(&parsed) {
+            return Ok(None);
+        }
+
+        let mut response = self
+            .http_client
+            .get(parsed)
+            .send()
+            .await?
+            .error_for_status()?;
+        let is_html = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| content_type.contains("text/html"));
+        if !is_html {
+            return Ok(None);
+        }
+
+        let mut html = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            html.extend_from_slice(&chunk);
+            if html.len() >= MAX_HTML_BYTES {
+                break;
+            }
+        }
+        Ok(parse_open_graph(url, &String::from_utf8_lossy(&html)))
+    }
+}
+
+fn plain_links(record: &EnrichedRecord) -> impl Iterator<Item = String> + '_ {
+    record
+        .hydrated_metadata
+        .urls
+        .iter()
+        .filter_map(|entry| match entry {
+            UrlEntry::Link(url) => Some(url.clone()),
+            UrlEntry::Unfurled(_) => None,
+        })
+}
+
+/// Posts with a link card already carry its title, description and thumbnail.
+fn has_external_embed(record: &EnrichedRecord) -> bool {
+    let Some(embed) = record
+        .message
+        .commit
+        .as_ref()
+        .and_then(|commit| commit.record.as_ref())
+        .and_then(|record| record.get("embed"))
+    else {
+        return false;
+    };
+    [Some(embed), embed.get("media")]
+        .into_iter()
+        .flatten()
+        .any(|embed| embed.get("$type").and_then(|t| t.as_str()) == Some(EXTERNAL_EMBED))
+}
+
+/// Rejects non-HTTP schemes, `localhost` and private, loopback and link-local
+/// addresses, so links in posts can't be used to probe the local network.
+fn is_public_url(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain != "localhost" && !domain.ends_with(".localhost")
+        }
+        Some(Host::Ipv4(ip)) => is_public_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => is_public_ip(IpAddr::V6(ip)),
+        None => false,
+    }
+}
+
+/// Resolves with the system resolver but keeps only public addresses, so a
+/// domain pointing into the local network can't reach it on any redirect hop.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast())
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local (fc00::/7) and link-local (fe80::/10)
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(|v4| !is_public_ip(IpAddr::V4(v4))))
+        }
+    }
+}
+
+/// OpenGraph title, description and image of a page, falling back to
+/// `<title>` and the plain description meta tag. `None` if there are none.
+fn parse_open_graph(url: &str, html: &str) -> Option<UnfurledUrl> {
+    // ASCII lowercasing keeps byte offsets, so tags found here index `html` too
+    let lower = html.to_ascii_lowercase();
+    let mut og_title = None;
+    let mut og_description = None;
+    let mut og_image = None;
+    let mut description = None;
+
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find("<meta") {
+        let start = pos + offset + "<meta".len();
+        let end = lower[start..].find('>').map_or(html.len(), |e| start + e);
+        pos = end;
+
+        let attributes = parse_attributes(&html[start..end]);
+        let attribute = |name: &str| {
+            attributes
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let (Some(key), Some(content)) = (
+            attribute("property").or_else(|| attribute("name")),
+            attribute("content"),
+        ) else {
+            continue;
+        };
+        let slot = match key.to_ascii_lowercase().as_str() {
+            "og:title" => &mut og_title,
+            "og:description" => &mut og_description,
+            "og:image" | "og:image:url" | "og:image:secure_url" => &mut og_image,
+            "description" => &mut description,
+            _ => continue,
+        };
+        if slot.is_none() {
+            *slot = clean_text(content);
+        }
+    }
+
+    let title = og_title.or_else(|| {
+        let start = lower.find("<title")?;
+        let start = start + lower[start..].find('>')? + 1;
+        let end = start + lower[start..].find("</title")?;
+        clean_text(&html[start..end])
+    });
+    // Images are often given relative to the page
+    let image = og_image.and_then(|image| {
+        Url::parse(url)
+            .and_then(|base| base.join(&image))
+            .ok()
+            .filter(|image| matches!(image.scheme(), "http" | "https"))
+            .map(String::from)
+    });
+    let description = og_description.or(description);
+
+    if title.is_none() && description.is_none() && image.is_none() {
+        return None;
+    }
+    Some(UnfurledUrl {
+        url: url.to_string(),
+        title,
+        description,
+        image,
+    })
+}
+
+/// `name=value` pairs of a tag, names lowercased. Values may be quoted with
+/// either quote or unquoted.
+fn parse_attributes(tag: &str) -> Vec<(String, String)> {
+    let bytes = tag.as_bytes();
+    let mut attributes = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        let name_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'=' {
+            i += 1;
+        }
+        let name = tag[name_start..i].to_ascii_lowercase();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= bytes.len() || bytes[i] != b'=' {
+            continue;
+        }
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let value = match bytes.get(i) {
+            Some(&quote @ (b'"' | b'\'')) => {
+                let value_start = i + 1;
+                let value_end = tag[value_start..]
+                    .find(quote as char)
+                    .map_or(tag.len(), |e| value_start + e);
+                i = value_end + 1;
+                &tag[value_start..value_end]
+            }
+            _ => {
+                let value_start = i;
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                &tag[value_start..i]
+            }
+        };
+        if !name.is_empty() {
+            attributes.push((name, value.to_string()));
+        }
+    }
+    attributes
+}
+
+/// Decodes entities, collapses whitespace and caps the length.
+fn clean_text(raw: &str) -> Option<String> {
+    let decoded = decode_entities(raw);
+    let collapsed = decoded.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        return None;
+    }
+    Some(collapsed.chars().take(MAX_FIELD_CHARS).collect())
+}
+
+fn decode_entities(raw: &str) -> String {
+    let mut decoded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest
+            .get(1..rest.len().min(12))
+            .and_then(|candidate| candidate.find(';'))
+            .and_then(|semicolon| {
+                let name = &rest[1..semicolon + 1];
+                let character = match name {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    _ => name
+                        .strip_prefix("#x")
+                        .or_else(|| name.strip_prefix("#X"))
+                        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                        .or_else(|| name.strip_prefix('#')?.parse().ok())
+                        .and_then(char::from_u32),
+                };
+                character.map(|character| (character, semicolon + 2))
+            });
+        match entity {
+            Some((character, length)) => {
+                decoded.push(character);
+                rest = &rest[length..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_open_graph_tags_are_parsed_with_fallbacks() {
+        let html = r#"<html><head>
+            <title>Fallback title</title>
+            <META property="og:title" content="Rust &amp; You &#8212; a guide">
+            <meta name='description' content='Plain   description'>
+            <meta property=og:image content=/img/card.png />
+        </head></html>"#;
+        let preview = parse_open_graph("https://example.com/posts/1", html).unwrap();
+        assert_eq!(preview.title.as_deref(), Some("Rust & You — a guide"));
+        assert_eq!(preview.description.as_deref(), Some("Plain description"));
+        assert_eq!(
+            preview.image.as_deref(),
+            Some("https://example.com/img/card.png")
+        );
+
+        let untagged = parse_open_graph("https://example.com", "<title>Only a title</title>");
+        assert_eq!(untagged.unwrap().title.as_deref(), Some("Only a title"));
+        assert!(parse_open_graph("https://example.com", "<p>nothing</p>").is_none());
+    }
+
+    #[test]
+    fn test_private_hosts_are_not_fetched() {
+        let public = |url: &str| is_public_url(&Url::parse(url).unwrap());
+        assert!(public("https://example.com/page"));
+        assert!(public("http://93.184.216.34/"));
+        assert!(!public("http://localhost:8080/admin"));
+        assert!(!public("http://127.0.0.1/"));
+        assert!(!public("http://10.0.0.5/"));
+        assert!(!public("http://169.254.169.254/latest/meta-data"));
+        assert!(!public("http://[::1]/"));
+        assert!(!public("file:///etc/passwd"));
+    }
+
+    #[tokio::test]
+    async fn test_domains_resolving_to_private_addresses_are_refused() {
+        assert!(PublicResolver
+            .resolve("localhost".parse().unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_links_without_a_card_are_unfurled_once() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/article"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"<meta property="og:title" content="An article">"#,
+                "text/html; charset=utf-8",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let link = format!("{}/article", server.uri());
+
+        let linked = |index: usize, embed: Option<serde_json::Value>| {
+            let mut message = create_post_message(index);
+            if let Some(embed) = embed {
+                message.commit.as_mut().unwrap().record.as_mut().unwrap()["embed"] = embed;
+            }
+            let mut record = EnrichedRecord::new(message);
+            record.hydrated_metadata.urls = vec![UrlEntry::Link(link.clone())];
+            record
+        };
+        let card = serde_json::json!({
+            "$type": "app.bsky.embed.external",
+            "external": {"uri": link, "title": "Card", "description": ""}
+        });
+        let mut records = vec![linked(1, None), linked(2, Some(card)), linked(3, None)];
+
+        let unfurler = Arc::new(UrlUnfurler::build(10, true).unwrap());
+        // The first sighting only queues the fetch
+        assert_eq!(unfurler.apply(&mut records), 0);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while unfurler.has_pending() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(unfurler.apply(&mut records), 2);
+        let expected = UrlEntry::Unfurled(UnfurledUrl {
+            url: link.clone(),
+            title: Some("An article".to_string()),
+            description: None,
+            image: None,
+        });
+        assert_eq!(records[0].hydrated_metadata.urls, vec![expected.clone()]);
+        assert_eq!(
+            records[1].hydrated_metadata.urls,
+            vec![UrlEntry::Link(link.clone())]
+        );
+
+        // Served from the cache; the mock expects a single request
+        let mut again = vec![linked(4, None)];
+        assert_eq!(unfurler.apply(&mut again), 1);
+        assert_eq!(again[0].hydrated_metadata.urls, vec![expected]);
+    }
+}
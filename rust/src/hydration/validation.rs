@@ -0,0 +1,443 @@
+use crate::models::at_uri::AtUri;
+use crate::models::enriched::EnrichedRecord;
+use chrono::DateTime;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, trace, warn};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Lexicon documents compiled into the binary. Collections without a schema here
+/// pass through unvalidated.
+const BUNDLED_LEXICONS: &[&str] = &[
+    include_str!("lexicons/app.bsky.feed.post.json"),
+    include_str!("lexicons/app.bsky.feed.like.json"),
+    include_str!("lexicons/app.bsky.feed.repost.json"),
+    include_str!("lexicons/app.bsky.graph.follow.json"),
+    include_str!("lexicons/app.bsky.richtext.facet.json"),
+    include_str!("lexicons/com.atproto.repo.strongRef.json"),
+];
+
+/// Violations reported per record are capped so one malformed array can't bloat the payload.
+const MAX_ERRORS_PER_RECORD: usize = 8;
+const MAX_SCHEMA_DEPTH: usize = 32;
+
+/// What to do with a record that does not match its lexicon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMode {
+    /// Skip validation entirely
+    #[default]
+    Off,
+    /// Keep the record and list the violations in `validation_errors`
+    Tag,
+    /// Remove the record before it reaches any sink
+    Drop,
+}
+
+/// Checks commit records against bundled lexicon schemas and counts how often
+/// upstream records drift from them.
+#[derive(Debug)]
+pub struct LexiconValidator {
+    lexicons: HashMap<String, Value>,
+    mode: ValidationMode,
+    schema_drift: AtomicU64,
+}
+
+impl LexiconValidator {
+    pub fn new(mode: ValidationMode) -> Self {
+        Self::with_lexicons(BUNDLED_LEXICONS.iter().copied(), mode)
+    }
+
+    /// Builds a validator from lexicon JSON documents. Documents that fail to parse
+    /// or have no `id` are skipped with a warning.
+    pub fn with_lexicons<'a>(
+        documents: impl IntoIterator<Item = &'a str>,
+        mode: ValidationMode,
+    ) -> Self {
+        let mut lexicons = HashMap::new();
+        for document in documents {
+            match serde_json::from_str::<Value>(document) {
+                Ok(doc) => match doc.get("id").and_then(Value::as_str) {
+                    Some(id) => {
+                        lexicons.insert(id.to_string(), doc);
+                    }
+                    None => warn!("Skipping lexicon without an id"),
+                },
+                Err(e) => warn!("Skipping unparsable lexicon: {}", e),
+            }
+        }
+
+        Self {
+            lexicons,
+            mode,
+            schema_drift: AtomicU64::new(0),
+        }
+    }
+
+    pub fn mode(&self) -> ValidationMode {
+        self.mode
+    }
+
+    pub fn has_schema(&self, collection: &str) -> bool {
+        self.lexicons.contains_key(collection)
+    }
+
+    /// Number of records that failed validation since startup.
+    pub fn schema_drift_count(&self) -> u64 {
+        self.schema_drift.load(Ordering::Relaxed)
+    }
+
+    /// Violations of `record` against the bundled schema for `collection`.
+    /// Returns nothing for collections without a bundled schema.
+    pub fn validate(&self, collection: &str, record: &Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let Some((nsid, main)) = self.resolve(collection, collection) {
+            let def = match main.get("type").and_then(Value::as_str) {
+                Some("record") => main.get("record").unwrap_or(&Value::Null),
+                _ => main,
+            };
+            self.check(nsid, def, record, "", &mut errors, 0);
+        }
+        errors
+    }
+
+    /// Tags or drops records that fail validation according to the mode.
+    /// Returns the number of invalid records.
+    pub fn apply(&self, records: &mut Vec<EnrichedRecord>) -> usize {
+        if self.mode == ValidationMode::Off {
+            return 0;
+        }
+
+        let mut invalid = 0;
+        records.retain_mut(|record| {
+            let errors = self.validate_record(record);
+            if errors.is_empty() {
+                return true;
+            }
+            invalid += 1;
+            match self.mode {
+                ValidationMode::Drop => {
+                    trace!(
+                        "Dropping invalid record: {:?}",
+                        record.get_at_uri().unwrap_or_default()
+                    );
+                    false
+                }
+                ValidationMode::Tag | ValidationMode::Off => {
+                    record.hydrated_metadata.validation_errors = errors;
+                    true
+                }
+            }
+        });
+        invalid
+    }
+
+    fn validate_record(&self, record: &EnrichedRecord) -> Vec<String> {
+        let Some(commit) = &record.message.commit else {
+            return Vec::new();
+        };
+        let (Some(collection), Some(value)) = (&commit.collection, &commit.record) else {
+            return Vec::new();
+        };
+
+        let errors = self.validate(collection, value);
+        if !errors.is_empty() {
+            self.schema_drift.fetch_add(1, Ordering::Relaxed);
+            counter!("jetstream_turbo_schema_drift_total", "collection" => collection.clone())
+                .increment(1);
+            debug!(
+                "Record {:?} does not match {}: {}",
+                record.get_at_uri().unwrap_or_default(),
+                collection,
+                errors.join("; ")
+            );
+        }
+        errors
+    }
+
+    /// Looks up a `ref` relative to the lexicon it appears in: `#name`, `nsid#name`, or `nsid`.
+    fn resolve<'a>(&'a self, context: &'a str, reference: &'a str) -> Option<(&'a str, &'a Value)> {
+        let (nsid, name) = split_ref(context, reference);
+        let (nsid, doc) = self.lexicons.get_key_value(nsid)?;
+        Some((nsid.as_str(), doc.get("defs")?.get(name)?))
+    }
+
+    fn check(
+        &self,
+        nsid: &str,
+        def: &Value,
+        value: &Value,
+        path: &str,
+        errors: &mut Vec<String>,
+        depth: usize,
+    ) {
+        if errors.len() >= MAX_ERRORS_PER_RECORD || depth > MAX_SCHEMA_DEPTH {
+            return;
+        }
+
+        match def.get("type").and_then(Value::as_str).unwrap_or_default() {
+            "object" => {
+                let Some(object) = value.as_object() else {
+                    return push_error(errors, path, "expected object");
+                };
+                for field in str_array(def, "required") {
+                    if !object.contains_key(field) {
+                        push_error(errors, &join_path(path, field), "required field missing");
+                    }
+                }
+                let nullable: Vec<&str> = str_array(def, "nullable").collect();
+                let properties = def.get("properties").and_then(Value::as_object);
+                for (name, property) in properties.into_iter().flatten() {
+                    match object.get(name) {
+                        Some(Value::Null) if nullable.contains(&name.as_str()) => {}
+                        Some(field) => self.check(
+                            nsid,
+                            property,
+                            field,
+                            &join_path(path, name),
+                            errors,
+                            depth + 1,
+                        ),
+                        None => {}
+                    }
+                }
+            }
+            "ref" => {
+                let target = def
+                    .get("ref")
+                    .and_then(Value::as_str)
+                    .and_then(|reference| self.resolve(nsid, reference));
+                if let Some((target_nsid, target)) = target {
+                    self.check(target_nsid, target, value, path, errors, depth + 1);
+                }
+            }
+            "union" => {
+                let Some(member_type) = value.get("$type").and_then(Value::as_str) else {
+                    return push_error(errors, path, "union member missing $type");
+                };
+                let member = str_array(def, "refs")
+                    .find(|reference| split_ref(nsid, reference) == split_ref("", member_type));
+                match member {
+                    Some(reference) => {
+                        if let Some((target_nsid, target)) = self.resolve(nsid, reference) {
+                            self.check(target_nsid, target, value, path, errors, depth + 1);
+                        }
+                    }
+                    None if def.get("closed").and_then(Value::as_bool) == Some(true) => {
+                        push_error(errors, path, &format!("unexpected $type {member_type}"));
+                    }
+                    None => {}
+                }
+            }
+            "string" => {
+                let Some(s) = value.as_str() else {
+                    return push_error(errors, path, "expected string");
+                };
+                if let Some(max) = u64_field(def, "maxLength").filter(|max| s.len() as u64 > *max) {
+                    push_error(errors, path, &format!("longer than {max} bytes"));
+                }
+                if let Some(min) = u64_field(def, "minLength").filter(|min| (s.len() as u64) < *min)
+                {
+                    push_error(errors, path, &format!("shorter than {min} bytes"));
+                }
+                if let Some(max) = u64_field(def, "maxGraphemes") {
+                    if s.graphemes(true).count() as u64 > max {
+                        push_error(errors, path, &format!("longer than {max} graphemes"));
+                    }
+                }
+                if let Some(format) = def.get("format").and_then(Value::as_str) {
+                    if !is_valid_format(format, s) {
+                        push_error(errors, path, &format!("invalid {format}"));
+                    }
+                }
+            }
+            "integer" => {
+                let Some(n) = value.as_i64() else {
+                    return push_error(errors, path, "expected integer");
+                };
+                if let Some(min) = def
+                    .get("minimum")
+                    .and_then(Value::as_i64)
+                    .filter(|m| n < *m)
+                {
+                    push_error(errors, path, &format!("less than {min}"));
+                }
+                if let Some(max) = def
+                    .get("maximum")
+                    .and_then(Value::as_i64)
+                    .filter(|m| n > *m)
+                {
+                    push_error(errors, path, &format!("greater than {max}"));
+                }
+            }
+            "boolean" if !value.is_boolean() => push_error(errors, path, "expected boolean"),
+            "array" => {
+                let Some(items) = value.as_array() else {
+                    return push_error(errors, path, "expected array");
+                };
+                if let Some(max) = u64_field(def, "maxLength").filter(|m| items.len() as u64 > *m) {
+                    push_error(errors, path, &format!("more than {max} items"));
+                }
+                if let Some(min) = u64_field(def, "minLength").filter(|m| (items.len() as u64) < *m)
+                {
+                    push_error(errors, path, &format!("fewer than {min} items"));
+                }
+                if let Some(item_def) = def.get("items") {
+                    for (index, item) in items.iter().enumerate() {
+                        let item_path = format!("{path}[{index}]");
+                        self.check(nsid, item_def, item, &item_path, errors, depth + 1);
+                    }
+                }
+            }
+            "blob" | "unknown" if !value.is_object() => {
+                push_error(errors, path, "expected object");
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Splits a lexicon reference into `(nsid, def name)`, defaulting to `main` and
+/// resolving local `#name` refs against `context`.
+fn split_ref<'a>(context: &'a str, reference: &'a str) -> (&'a str, &'a str) {
+    match reference.split_once('#') {
+        Some(("", name)) => (context, name),
+        Some((nsid, name)) => (nsid, name),
+        None => (reference, "main"),
+    }
+}
+
+fn str_array<'a>(def: &'a Value, key: &str) -> impl Iterator<Item = &'a str> {
+    def.get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+fn u64_field(def: &Value, key: &str) -> Option<u64> {
+    def.get(key).and_then(Value::as_u64)
+}
+
+fn join_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{path}.{field}")
+    }
+}
+
+fn push_error(errors: &mut Vec<String>, path: &str, message: &str) {
+    if errors.len() < MAX_ERRORS_PER_RECORD {
+        let path = if path.is_empty() { "record" } else { path };
+        errors.push(format!("{path}: {message}"));
+    }
+}
+
+fn is_valid_format(format: &str, s: &str) -> bool {
+    match format {
+        "datetime" => DateTime::parse_from_rfc3339(s).is_ok(),
+        "at-uri" => AtUri::is_valid(s),
+        "did" => s
+            .strip_prefix("did:")
+            .and_then(|rest| rest.split_once(':'))
+            .is_some_and(|(method, id)| !method.is_empty() && !id.is_empty()),
+        "cid" => !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric()),
+        "language" => !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+        "uri" => s
+            .split_once(':')
+            .is_some_and(|(scheme, rest)| !scheme.is_empty() && !rest.is_empty()),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+    use serde_json::json;
+
+    #[test]
+    fn test_bundled_post_schema_reports_violations() {
+        let validator = LexiconValidator::new(ValidationMode::Tag);
+        assert!(validator.has_schema("app.bsky.feed.post"));
+        assert!(validator.validate("app.bsky.feed.post", &json!({
+            "$type": "app.bsky.feed.post",
+            "text": "hello @alice.bsky.social",
+            "createdAt": "2024-01-01T00:00:00.000Z",
+            "langs": ["en"],
+            "facets": [{
+                "index": {"byteStart": 6, "byteEnd": 24},
+                "features": [{"$type": "app.bsky.richtext.facet#mention", "did": "did:plc:alice"}]
+            }],
+            "reply": {
+                "root": {"uri": "at://did:plc:root/app.bsky.feed.post/3k", "cid": "bafyroot"},
+                "parent": {"uri": "at://did:plc:root/app.bsky.feed.post/3k", "cid": "bafyroot"}
+            },
+            "embed": {"$type": "app.bsky.embed.images", "images": []}
+        }))
+        .is_empty());
+
+        let errors = validator.validate(
+            "app.bsky.feed.post",
+            &json!({
+                "text": "a".repeat(301),
+                "langs": ["en", "de", "fr", "ja"],
+                "facets": [{
+                    "index": {"byteStart": -1, "byteEnd": 4},
+                    "features": [{"$type": "app.bsky.richtext.facet#mention", "did": "alice"}]
+                }],
+                "reply": {"root": {"uri": "https://bsky.app", "cid": "bafyroot"}}
+            }),
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "createdAt: required field missing",
+                "facets[0].features[0].did: invalid did",
+                "facets[0].index.byteStart: less than 0",
+                "langs: more than 3 items",
+                "reply.parent: required field missing",
+                "reply.root.uri: invalid at-uri",
+                "text: longer than 300 graphemes",
+            ]
+        );
+
+        assert!(validator
+            .validate("app.bsky.actor.status", &json!({"anything": true}))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_apply_tags_or_drops_and_counts_drift() {
+        let invalid = || {
+            let mut message = create_post_message(1);
+            let commit = message.commit.as_mut().unwrap();
+            commit.record = Some(json!({"text": "missing createdAt"}));
+            EnrichedRecord::new(message)
+        };
+
+        let tag = LexiconValidator::new(ValidationMode::Tag);
+        let mut records = vec![EnrichedRecord::new(create_post_message(0)), invalid()];
+        assert_eq!(tag.apply(&mut records), 1);
+        assert_eq!(records.len(), 2);
+        assert!(records[0].hydrated_metadata.validation_errors.is_empty());
+        assert_eq!(
+            records[1].hydrated_metadata.validation_errors,
+            vec!["createdAt: required field missing"]
+        );
+        assert_eq!(tag.schema_drift_count(), 1);
+
+        let drop = LexiconValidator::new(ValidationMode::Drop);
+        assert_eq!(drop.apply(&mut records), 1);
+        assert_eq!(records.len(), 1);
+
+        let off = LexiconValidator::new(ValidationMode::Off);
+        let mut records = vec![invalid()];
+        assert_eq!(off.apply(&mut records), 0);
+        assert_eq!(off.schema_drift_count(), 0);
+    }
+}
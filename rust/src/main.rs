@@ -1,17 +1,35 @@
 use anyhow::Result;
+#[cfg(feature = "s3")]
+use chrono::NaiveDate;
+use chrono::{DateTime, Utc};
 use clap::Parser;
+use jetstream_turbo_rs::client::{BackfillClient, PlcClient};
 use jetstream_turbo_rs::config::Settings;
-use jetstream_turbo_rs::server::create_server;
-use jetstream_turbo_rs::telemetry::ErrorReporter;
-use jetstream_turbo_rs::turbocharger::ProductionTurboCharger as TurboCharger;
+use jetstream_turbo_rs::server::{create_server, drain::ConnectionDrain, ServerBinding};
+use jetstream_turbo_rs::storage::file_sink::STDOUT_PATH;
+use jetstream_turbo_rs::telemetry::{install_prometheus_recorder, ErrorReporter, LogFilterHandle};
+use jetstream_turbo_rs::turbocharger::{ShardAssignment, TurboChargerBuilder};
 use std::any::Any;
 use std::collections::HashMap;
 use std::env;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{filter::filter_fn, layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
+#[cfg(all(feature = "jemalloc", feature = "mimalloc"))]
+compile_error!("features `jemalloc` and `mimalloc` are mutually exclusive");
+
+#[cfg(all(feature = "jemalloc", not(feature = "mimalloc")))]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
 const BATCH_REPORT_LOG_TARGET: &str = "jetstream_turbo.batch_report";
 
 #[derive(Parser, Debug)]
@@ -32,6 +50,12 @@ EXAMPLES:
     cargo run
     cargo run -- --log-level debug
     cargo run -- --modulo 4 --shard 0
+    cargo run -- --backfill did:plc:abc,did:plc:def
+    cargo run -- --output stdout | jq .message.did
+    cargo run -- --serve-only
+    cargo run --features arrow -- --export-arrow records.arrow
+    cargo run -- export --since 2026-01-01T00:00:00Z --out snapshot.db
+    cargo run --features s3 -- restore --since 2026-01-01 --until 2026-01-08
 
 For more information, see README.md
 "#
@@ -48,6 +72,82 @@ struct Args {
     /// Log level: trace, debug, info, warn, error
     #[arg(long)]
     log_level: Option<String>,
+
+    /// Comma-separated DIDs whose existing records are loaded alongside the live stream
+    #[arg(long, value_delimiter = ',')]
+    backfill: Vec<String>,
+
+    /// Collections to backfill (defaults to WANTED_COLLECTIONS)
+    #[arg(long, value_delimiter = ',')]
+    backfill_collections: Vec<String>,
+
+    /// Where enriched records go (defaults to STANDALONE and STANDALONE_OUTPUT_FILE)
+    #[arg(long, value_enum)]
+    output: Option<Output>,
+
+    /// Serve existing SQLite data over HTTP/WebSocket without ingesting
+    /// (no Jetstream, Bluesky login or Redis; same as SERVE_ONLY=true)
+    #[arg(long)]
+    serve_only: bool,
+
+    /// Write every stored record to an Arrow IPC (feather) file and exit
+    #[cfg(feature = "arrow")]
+    #[arg(long, value_name = "PATH")]
+    export_arrow: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Args {
+    /// Whether this run only reads stored data and exits, so it needs no
+    /// Bluesky credentials.
+    fn runs_offline(&self) -> bool {
+        #[cfg(feature = "arrow")]
+        if self.export_arrow.is_some() {
+            return true;
+        }
+        match self.command {
+            Some(Command::Export { .. }) => true,
+            #[cfg(feature = "s3")]
+            Some(Command::Restore { .. }) => true,
+            None => false,
+        }
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Copy stored records into a new self-contained SQLite file and exit
+    Export {
+        /// Earliest event time to include (RFC 3339); open-ended if omitted
+        #[arg(long)]
+        since: Option<DateTime<Utc>>,
+        /// Event time to stop before (RFC 3339); open-ended if omitted
+        #[arg(long)]
+        until: Option<DateTime<Utc>>,
+        /// Snapshot file to create; must not exist yet
+        #[arg(long, value_name = "PATH")]
+        out: PathBuf,
+    },
+    /// Download archived days from ARCHIVE_BUCKET into DB_DIR for reading and exit
+    #[cfg(feature = "s3")]
+    Restore {
+        /// First UTC day to restore (YYYY-MM-DD); open-ended if omitted
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// UTC day to stop before (YYYY-MM-DD); open-ended if omitted
+        #[arg(long)]
+        until: Option<NaiveDate>,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Output {
+    /// The Redis stream
+    Redis,
+    /// NDJSON on stdout, without Redis; logs go to stderr
+    Stdout,
 }
 
 #[tokio::main]
@@ -60,7 +160,7 @@ async fn main() -> Result<()> {
     let args = Args::parse();
 
     // Default to warn in release mode, info in debug mode
-    let log_level = args.log_level.unwrap_or_else(|| {
+    let log_level = args.log_level.clone().unwrap_or_else(|| {
         if cfg!(debug_assertions) {
             "info".to_string()
         } else {
@@ -68,11 +168,46 @@ async fn main() -> Result<()> {
         }
     });
 
+    // Load configuration; nothing that only reads stored data logs in to Bluesky
+    let mut settings = if args.serve_only || args.runs_offline() {
+        Settings::from_env_serve_only()?
+    } else {
+        Settings::from_env()?
+    };
+    if settings.serve_only && !args.backfill.is_empty() {
+        anyhow::bail!("--backfill needs ingestion and can't be used with --serve-only");
+    }
+    match args.output {
+        Some(Output::Redis) => {
+            settings.standalone = false;
+            settings.standalone_output_file = None;
+        }
+        Some(Output::Stdout) => {
+            settings.standalone = true;
+            settings.standalone_output_file = Some(STDOUT_PATH.to_string());
+        }
+        None => {}
+    }
+    let stdout_output = settings.writes_records_to_stdout();
+
     // Initialize tracing
-    let _log_guards = init_tracing(&log_level)?;
+    let (log_filter, _log_guards) = init_tracing(&log_level, stdout_output)?;
+
+    #[cfg(feature = "arrow")]
+    if let Some(path) = args.export_arrow {
+        return export_arrow(&settings, &path).await;
+    }
 
-    // Load configuration
-    let settings = Settings::from_env()?;
+    match args.command {
+        Some(Command::Export { since, until, out }) => {
+            return export_snapshot(&settings, since, until, &out).await;
+        }
+        #[cfg(feature = "s3")]
+        Some(Command::Restore { since, until }) => {
+            return restore_archives(&settings, since, until).await;
+        }
+        None => {}
+    }
 
     // Initialize error reporter
     let error_reporter = ErrorReporter::new(
@@ -82,6 +217,9 @@ async fn main() -> Result<()> {
     .await;
     install_panic_hook(error_reporter.clone());
 
+    // Back the pipeline's counters and histograms for /api/v1/metrics
+    let recorder = install_prometheus_recorder()?;
+
     tracing::info!("Starting jetstream-turbo v{}", env!("CARGO_PKG_VERSION"));
     tracing::info!(
         "Configuration loaded: modulo={}, shard={}",
@@ -90,57 +228,133 @@ async fn main() -> Result<()> {
     );
 
     // Create turbocharger
-    let turbocharger = TurboCharger::new(
-        settings.clone(),
-        args.modulo,
-        args.shard,
-        error_reporter.clone(),
-    )
-    .await?;
-    let turbocharger = std::sync::Arc::new(turbocharger);
+    let turbocharger = TurboChargerBuilder::new(settings.clone())
+        .error_reporter(error_reporter.clone())
+        .shard_assignment(ShardAssignment::new(args.modulo, args.shard)?)
+        .build()
+        .await?;
+    let turbocharger = Arc::new(turbocharger);
+
+    // Serving read-only data leaves sessions, cleanup, rotation and compaction alone
+    if !settings.serve_only {
+        // Start background session refresh task
+        turbocharger.start_session_refresh_task();
+
+        // Start background hourly aggregate roll-up task
+        turbocharger.start_roll_up_task();
 
-    // Start background session refresh task
-    turbocharger.start_session_refresh_task();
+        // Start background database cleanup task
+        turbocharger.start_db_cleanup_task();
 
-    // Start background database cleanup task
-    turbocharger.start_db_cleanup_task();
+        // Start background database rotation task
+        turbocharger.start_rotation_task().await?;
 
-    // Run initial cleanup check on startup
-    if let Err(e) = turbocharger.check_and_cleanup_db().await {
-        tracing::warn!("Initial database cleanup check failed: {}", e);
+        // Start background archive compaction task
+        turbocharger.start_compaction_task()?;
+
+        // Run initial cleanup check on startup
+        if let Err(e) = turbocharger.check_and_cleanup_db().await {
+            tracing::warn!("Initial database cleanup check failed: {}", e);
+        }
+    }
+
+    if !args.backfill.is_empty() {
+        let collections = if args.backfill_collections.is_empty() {
+            settings
+                .wanted_collections
+                .split(',')
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect()
+        } else {
+            args.backfill_collections
+        };
+        let client = BackfillClient::new(settings.backfill_api_url.clone())?
+            .with_http_policy(settings.backfill_http_policy())?;
+        let client = match &settings.plc_directory_url {
+            Some(url) => client.with_pds_resolver(Arc::new(
+                PlcClient::new(url.clone())?.with_user_agent(&settings.user_agent())?,
+            )),
+            None => client,
+        };
+        let backfill_turbocharger = turbocharger.clone();
+        let dids = args.backfill;
+        tokio::spawn(async move {
+            tracing::info!("Backfilling {} repos: {:?}", dids.len(), collections);
+            let report = backfill_turbocharger
+                .backfill(&client, &dids, &collections)
+                .await;
+            tracing::info!(
+                "Backfill finished: {} records from {} repos, {} failed",
+                report.records,
+                report.repos,
+                report.failed_repos.len()
+            );
+        });
     }
 
     // Run both turbocharger and server
     let turbocharger_clone = turbocharger.clone();
+    let ingest = turbocharger.clone();
     let error_reporter_clone = error_reporter.clone();
-    let turbocharger_handle = tokio::spawn(async move {
-        let restart_delay = Duration::from_secs(5);
-
-        loop {
-            match turbocharger_clone.run().await {
-                Ok(()) => {
-                    tracing::warn!("Turbocharger run loop ended unexpectedly; restarting");
-                }
-                Err(e) => {
-                    tracing::error!("Turbocharger failed: {}", e);
-                    let mut ctx = HashMap::new();
-                    ctx.insert("component", "main");
-                    ctx.insert("operation", "turbocharger_run");
-                    error_reporter_clone.capture_error(&e, ctx);
+    let mut turbocharger_handle = if settings.serve_only {
+        tracing::info!(
+            "Serve-only mode: serving records stored in {}",
+            settings.db_dir
+        );
+        // Nothing to ingest; only the server or a shutdown signal ends the process
+        tokio::spawn(std::future::pending::<()>())
+    } else {
+        tokio::spawn(async move {
+            let restart_delay = Duration::from_secs(5);
+
+            loop {
+                match turbocharger_clone.run().await {
+                    // Only a shutdown ends a run cleanly
+                    Ok(()) => break,
+                    // The reader at the other end of the pipe went away (`| head`)
+                    Err(jetstream_turbo_rs::TurboError::Io(e))
+                        if stdout_output && e.kind() == std::io::ErrorKind::BrokenPipe =>
+                    {
+                        tracing::info!("Stdout closed; stopping");
+                        break;
+                    }
+                    Err(e) => {
+                        tracing::error!("Turbocharger failed: {}", e);
+                        let mut ctx = HashMap::new();
+                        ctx.insert("component", "main");
+                        ctx.insert("operation", "turbocharger_run");
+                        error_reporter_clone.capture_error(&e, ctx);
+                    }
                 }
-            }
 
-            tracing::warn!(
-                "Restarting turbocharger run loop in {} seconds",
-                restart_delay.as_secs()
-            );
-            tokio::time::sleep(restart_delay).await;
-        }
-    });
+                tracing::warn!(
+                    "Restarting turbocharger run loop in {} seconds",
+                    restart_delay.as_secs()
+                );
+                tokio::time::sleep(restart_delay).await;
+            }
+        })
+    };
 
     let server_error_reporter = error_reporter.clone();
+    let binding = ServerBinding::from_settings(&settings);
+    let drain = ConnectionDrain::new();
+    let drain_timeout = Duration::from_secs(settings.shutdown_drain_timeout_secs);
+    let server_drain = drain.clone();
+    let trusted_proxies = settings.trusted_proxies().map_err(anyhow::Error::msg)?;
     let server_handle = tokio::spawn(async move {
-        if let Err(e) = create_server(settings.http_port, turbocharger).await {
+        if let Err(e) = create_server(
+            binding,
+            turbocharger,
+            server_drain,
+            log_filter,
+            trusted_proxies,
+            recorder,
+        )
+        .await
+        {
             tracing::error!("Server failed: {}", e);
             let mut ctx = HashMap::new();
             ctx.insert("component", "main");
@@ -154,12 +368,32 @@ async fn main() -> Result<()> {
 
     // Wait for either task to complete, then make a bounded attempt to flush telemetry.
     let shutdown_reason = tokio::select! {
-        result = turbocharger_handle => {
+        result = &mut turbocharger_handle => {
             handle_task_exit("turbocharger", result, &error_reporter)
         }
         result = server_handle => {
             handle_task_exit("server", result, &error_reporter)
         }
+        _ = shutdown_signal() => {
+            tracing::info!("Shutdown signal received; flushing buffered records and draining connections");
+            ingest.shutdown();
+            let flush = async {
+                if settings.serve_only {
+                    return;
+                }
+                if tokio::time::timeout(drain_timeout, &mut turbocharger_handle)
+                    .await
+                    .is_err()
+                {
+                    tracing::warn!(
+                        "Buffered records were not flushed within {} seconds",
+                        drain_timeout.as_secs()
+                    );
+                }
+            };
+            tokio::join!(flush, drain.drain(drain_timeout));
+            "shutdown signal"
+        }
     };
 
     if error_reporter
@@ -180,6 +414,112 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "arrow")]
+async fn export_arrow(settings: &Settings, path: &std::path::Path) -> Result<()> {
+    use jetstream_turbo_rs::storage::{export_records, SQLitePragmaConfig, ShardedSQLiteStore};
+
+    let store = ShardedSQLiteStore::new(
+        format!("{}/jetstream.db", settings.db_dir),
+        settings.sqlite_shards,
+        SQLitePragmaConfig {
+            cache_size_kib: settings.sqlite_cache_size_kib,
+            mmap_size_mb: settings.sqlite_mmap_size_mb,
+            journal_size_limit_mb: settings.sqlite_journal_size_limit_mb,
+        },
+    )
+    .await?;
+    let rows = export_records(&store, path).await?;
+    store.close().await?;
+    println!("Exported {rows} records to {}", path.display());
+    Ok(())
+}
+
+async fn export_snapshot(
+    settings: &Settings,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    out: &std::path::Path,
+) -> Result<()> {
+    use jetstream_turbo_rs::storage::{
+        export_snapshot, PartitionedReader, SQLitePragmaConfig, ShardedSQLiteStore,
+    };
+
+    let pragma_config = SQLitePragmaConfig {
+        cache_size_kib: settings.sqlite_cache_size_kib,
+        mmap_size_mb: settings.sqlite_mmap_size_mb,
+        journal_size_limit_mb: settings.sqlite_journal_size_limit_mb,
+    };
+    let store = Arc::new(
+        ShardedSQLiteStore::new(
+            format!("{}/jetstream.db", settings.db_dir),
+            settings.sqlite_shards,
+            pragma_config,
+        )
+        .await?,
+    );
+    let reader = PartitionedReader::new(Arc::clone(&store), &settings.db_dir);
+    let rows = export_snapshot(
+        &reader,
+        out,
+        since.map(|t| t.timestamp_micros()),
+        until.map(|t| t.timestamp_micros()),
+        pragma_config,
+    )
+    .await?;
+    store.close().await?;
+    println!("Exported {rows} records to {}", out.display());
+    Ok(())
+}
+
+#[cfg(feature = "s3")]
+async fn restore_archives(
+    settings: &Settings,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Result<()> {
+    use jetstream_turbo_rs::storage::compaction::s3_archive_store;
+    use jetstream_turbo_rs::storage::{
+        restore_archives, PartitionedReader, SQLitePragmaConfig, ShardedSQLiteStore,
+    };
+
+    let Some(bucket) = &settings.archive_bucket else {
+        anyhow::bail!("restore needs ARCHIVE_BUCKET");
+    };
+    // Opening the live store creates it if missing, so --serve-only can start
+    // on a directory holding nothing but restored archives
+    let store = Arc::new(
+        ShardedSQLiteStore::new(
+            format!("{}/jetstream.db", settings.db_dir),
+            settings.sqlite_shards,
+            SQLitePragmaConfig {
+                cache_size_kib: settings.sqlite_cache_size_kib,
+                mmap_size_mb: settings.sqlite_mmap_size_mb,
+                journal_size_limit_mb: settings.sqlite_journal_size_limit_mb,
+            },
+        )
+        .await?,
+    );
+    let reader = PartitionedReader::new(store.clone(), &settings.db_dir);
+    let report = restore_archives(
+        s3_archive_store(bucket)?.as_ref(),
+        &settings.archive_prefix,
+        &reader,
+        since,
+        until,
+    )
+    .await?;
+    store.close().await?;
+    println!(
+        "Restored {} archives ({} bytes), skipped {} already present; {} databases readable in {}",
+        report.restored.len(),
+        report.bytes_downloaded,
+        report.skipped.len(),
+        report.partitions,
+        settings.db_dir
+    );
+    Ok(())
+}
+
 fn install_panic_hook(error_reporter: ErrorReporter) {
     let default_hook = std::panic::take_hook();
 
@@ -210,6 +550,30 @@ fn install_panic_hook(error_reporter: ErrorReporter) {
     }));
 }
 
+/// Resolves on Ctrl-C, or SIGTERM on unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(terminate) => terminate,
+                Err(e) => {
+                    tracing::warn!("Unable to listen for SIGTERM: {}", e);
+                    let _ = tokio::signal::ctrl_c().await;
+                    return;
+                }
+            };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
 fn handle_task_exit(
     task_name: &'static str,
     result: Result<(), tokio::task::JoinError>,
@@ -260,11 +624,24 @@ fn panic_payload_to_string(payload: &(dyn Any + Send)) -> String {
     }
 }
 
-fn init_tracing(log_level: &str) -> Result<Vec<WorkerGuard>> {
-    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(log_level));
+/// Console logs go to stdout unless records are written there.
+fn init_tracing(
+    log_level: &str,
+    log_to_stderr: bool,
+) -> Result<(LogFilterHandle, Vec<WorkerGuard>)> {
+    let (filter, log_filter) = LogFilterHandle::new(
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(log_level)),
+    );
 
-    let stdout_layer = tracing_subscriber::fmt::layer().json();
+    let console_writer = if log_to_stderr {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    let console_layer = tracing_subscriber::fmt::layer()
+        .json()
+        .with_writer(console_writer);
     let main_file_filter = filter_fn(|metadata| metadata.target() != BATCH_REPORT_LOG_TARGET);
     let batch_file_filter = filter_fn(|metadata| metadata.target() == BATCH_REPORT_LOG_TARGET);
 
@@ -287,14 +664,14 @@ fn init_tracing(log_level: &str) -> Result<Vec<WorkerGuard>> {
 
             tracing_subscriber::registry()
                 .with(filter)
-                .with(stdout_layer)
+                .with(console_layer)
                 .with(file_layer)
                 .with(batch_file_layer)
                 .init();
 
             tracing::info!(log_path = %main_log_path.display(), "File logging enabled");
             tracing::info!(batch_log_path = %batch_log_path.display(), "Batch file logging enabled");
-            Ok(vec![main_guard, batch_guard])
+            Ok((log_filter, vec![main_guard, batch_guard]))
         }
         (Some((file_writer, guard, log_path)), None) => {
             let file_layer = tracing_subscriber::fmt::layer()
@@ -304,12 +681,12 @@ fn init_tracing(log_level: &str) -> Result<Vec<WorkerGuard>> {
 
             tracing_subscriber::registry()
                 .with(filter)
-                .with(stdout_layer)
+                .with(console_layer)
                 .with(file_layer)
                 .init();
 
             tracing::info!(log_path = %log_path.display(), "File logging enabled");
-            Ok(vec![guard])
+            Ok((log_filter, vec![guard]))
         }
         (None, Some((batch_file_writer, batch_guard, batch_log_path))) => {
             let batch_file_layer = tracing_subscriber::fmt::layer()
@@ -319,20 +696,20 @@ fn init_tracing(log_level: &str) -> Result<Vec<WorkerGuard>> {
 
             tracing_subscriber::registry()
                 .with(filter)
-                .with(stdout_layer)
+                .with(console_layer)
                 .with(batch_file_layer)
                 .init();
 
             tracing::info!(batch_log_path = %batch_log_path.display(), "Batch file logging enabled");
-            Ok(vec![batch_guard])
+            Ok((log_filter, vec![batch_guard]))
         }
         (None, None) => {
             tracing_subscriber::registry()
                 .with(filter)
-                .with(stdout_layer)
+                .with(console_layer)
                 .init();
 
-            Ok(Vec::new())
+            Ok((log_filter, Vec::new()))
         }
     }
 }
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use thiserror::Error;
 
 pub type TurboResult<T> = Result<T, TurboError>;
@@ -15,8 +16,12 @@ pub enum TurboError {
     #[error("HTTP request failed: {0}")]
     HttpRequest(#[from] reqwest::Error),
 
-    #[error("API rate limit exceeded")]
-    RateLimitExceeded,
+    #[error("API rate limit exceeded for {endpoint}{}", format_retry_after(.retry_after))]
+    RateLimited {
+        endpoint: String,
+        /// Server-provided wait before the next attempt, if any
+        retry_after: Option<Duration>,
+    },
 
     #[error("Invalid response from API: {0}")]
     InvalidApiResponse(String),
@@ -32,6 +37,7 @@ pub enum TurboError {
     #[error("SQLite database error: {0}")]
     Database(#[from] sqlx::Error),
 
+    #[cfg(feature = "redis")]
     #[error("Redis operation failed: {0}")]
     RedisOperation(#[from] not_redis::RedisError),
 
@@ -78,20 +84,42 @@ pub enum TurboError {
 
     #[error("Session expired: {0}")]
     ExpiredToken(String),
+
+    #[cfg(feature = "arrow")]
+    #[error("Arrow export failed: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    #[cfg(feature = "s3")]
+    #[error("Object storage error: {0}")]
+    ObjectStore(#[from] object_store::Error),
+
+    /// A failure injected by the `chaos` feature, standing in for a transient one
+    #[cfg(feature = "chaos")]
+    #[error("Injected {0} fault")]
+    InjectedFault(&'static str),
+}
+
+impl From<crate::models::at_uri::InvalidAtUri> for TurboError {
+    fn from(error: crate::models::at_uri::InvalidAtUri) -> Self {
+        TurboError::InvalidMessage(error.to_string())
+    }
 }
 
 impl TurboError {
     pub fn is_retryable(&self) -> bool {
-        matches!(
-            self,
+        match self {
             TurboError::HttpRequest(_)
-                | TurboError::RateLimitExceeded
-                | TurboError::Database(_)
-                | TurboError::RedisOperation(_)
-                | TurboError::WebSocketConnection(_)
-                | TurboError::Timeout(_)
-                | TurboError::ExpiredToken(_)
-        )
+            | TurboError::RateLimited { .. }
+            | TurboError::Database(_)
+            | TurboError::WebSocketConnection(_)
+            | TurboError::Timeout(_)
+            | TurboError::ExpiredToken(_) => true,
+            #[cfg(feature = "redis")]
+            TurboError::RedisOperation(_) => true,
+            #[cfg(feature = "chaos")]
+            TurboError::InjectedFault(_) => true,
+            _ => false,
+        }
     }
 
     pub fn is_critical(&self) -> bool {
@@ -106,4 +134,41 @@ impl TurboError {
     pub fn is_expired_token(&self) -> bool {
         matches!(self, TurboError::ExpiredToken(_))
     }
+
+    /// How long the server asked callers to wait before retrying.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            TurboError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+fn format_retry_after(retry_after: &Option<Duration>) -> String {
+    retry_after
+        .map(|wait| format!(" (retry after {:.1}s)", wait.as_secs_f64()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::tungstenite;
+
+    #[test]
+    fn test_websocket_errors_convert_into_jetstream_connection() {
+        fn connect() -> TurboResult<()> {
+            Err(tungstenite::Error::ConnectionClosed)?
+        }
+
+        let err = connect().unwrap_err();
+        assert!(matches!(
+            err,
+            TurboError::JetstreamConnection(tungstenite::Error::ConnectionClosed)
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Jetstream connection failed: Connection closed normally"
+        );
+    }
 }
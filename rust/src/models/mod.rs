@@ -1,6 +1,6 @@
-pub mod bluesky;
-pub mod enriched;
 pub mod errors;
-pub mod jetstream;
 
+pub use jetstream_turbo_models::{at_uri, bluesky, enriched, jetstream, records, text};
+
+pub use at_uri::AtUri;
 pub use errors::{TurboError, TurboResult};
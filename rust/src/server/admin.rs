@@ -0,0 +1,294 @@
+//! Operator controls under `/api/v1/admin`: pausing ingestion, changing the
+//! log filter, editing the watchlist, moving the instance to another shard at
+//! runtime and counting stored records exactly. Every route requires
+//! `Authorization: Bearer <ADMIN_TOKEN>` and is refused outright when no token
+//! is configured.
+
+use super::error_response;
+use crate::models::errors::{TurboError, TurboResult};
+use crate::telemetry::LogFilterHandle;
+use crate::turbocharger::{ProductionTurboCharger, ShardAssignment};
+use axum::{
+    extract::{Extension, Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post, put, Router},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Serialize)]
+pub struct IngestionState {
+    pub paused: bool,
+}
+
+#[derive(Serialize)]
+pub struct IngestionResponse {
+    pub status: String,
+    pub data: IngestionState,
+}
+
+#[derive(Deserialize)]
+pub struct LogFilterRequest {
+    /// Directives in `RUST_LOG` syntax, e.g. `info,jetstream_turbo_rs=debug`
+    pub filter: String,
+}
+
+#[derive(Serialize)]
+pub struct LogFilterState {
+    pub filter: String,
+}
+
+#[derive(Serialize)]
+pub struct LogFilterResponse {
+    pub status: String,
+    pub data: LogFilterState,
+}
+
+#[derive(Serialize)]
+pub struct ShardResponse {
+    pub status: String,
+    pub data: ShardAssignment,
+}
+
+#[derive(Serialize)]
+pub struct RecordCount {
+    pub records: i64,
+}
+
+#[derive(Serialize)]
+pub struct RecordCountResponse {
+    pub status: String,
+    pub data: RecordCount,
+}
+
+#[derive(Serialize)]
+pub struct WatchlistState {
+    pub entries: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct WatchlistResponse {
+    pub status: String,
+    pub data: WatchlistState,
+}
+
+pub fn router(turbocharger: Arc<ProductionTurboCharger>) -> Router<Arc<ProductionTurboCharger>> {
+    Router::new()
+        .route("/ingestion", get(ingestion_state))
+        .route("/ingestion/pause", post(pause_ingestion))
+        .route("/ingestion/resume", post(resume_ingestion))
+        .route(
+            "/log-level",
+            get(get_log_filter)
+                .put(set_log_filter)
+                .delete(reset_log_filter),
+        )
+        .route("/shard", get(get_shard).put(reassign_shard))
+        .route("/records/count", get(count_records))
+        .route("/watchlist", get(get_watchlist))
+        .route(
+            "/watchlist/:entry",
+            put(watch_account).delete(unwatch_account),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            turbocharger,
+            require_admin_token,
+        ))
+}
+
+async fn require_admin_token(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = turbocharger.admin_token() else {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Admin routes are disabled; set ADMIN_TOKEN to enable them".to_string(),
+        );
+    };
+    if !bearer_token_matches(request.headers(), expected) {
+        return error_response(
+            StatusCode::UNAUTHORIZED,
+            "Missing or invalid admin token".to_string(),
+        );
+    }
+    next.run(request).await
+}
+
+fn bearer_token_matches(headers: &HeaderMap, expected: &str) -> bool {
+    let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    // Compare every byte so the response time doesn't reveal the matching prefix
+    token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn ingestion_state(State(turbocharger): State<Arc<ProductionTurboCharger>>) -> Response {
+    ingestion_response(turbocharger.is_ingestion_paused())
+}
+
+async fn pause_ingestion(State(turbocharger): State<Arc<ProductionTurboCharger>>) -> Response {
+    set_ingestion_paused(&turbocharger, true)
+}
+
+async fn resume_ingestion(State(turbocharger): State<Arc<ProductionTurboCharger>>) -> Response {
+    set_ingestion_paused(&turbocharger, false)
+}
+
+fn set_ingestion_paused(turbocharger: &ProductionTurboCharger, paused: bool) -> Response {
+    if !turbocharger.set_ingestion_paused(paused) {
+        return error_response(
+            StatusCode::CONFLICT,
+            "The configured ingest mode can't be paused".to_string(),
+        );
+    }
+    ingestion_response(paused)
+}
+
+fn ingestion_response(paused: bool) -> Response {
+    Json(IngestionResponse {
+        status: "success".to_string(),
+        data: IngestionState { paused },
+    })
+    .into_response()
+}
+
+async fn get_log_filter(Extension(log_filter): Extension<LogFilterHandle>) -> Response {
+    log_filter_response(log_filter.current())
+}
+
+async fn set_log_filter(
+    Extension(log_filter): Extension<LogFilterHandle>,
+    Json(request): Json<LogFilterRequest>,
+) -> Response {
+    log_filter_response(log_filter.set(&request.filter))
+}
+
+/// Back to the filter the process started with.
+async fn reset_log_filter(Extension(log_filter): Extension<LogFilterHandle>) -> Response {
+    log_filter_response(log_filter.reset())
+}
+
+fn log_filter_response(result: TurboResult<String>) -> Response {
+    match result {
+        Ok(filter) => Json(LogFilterResponse {
+            status: "success".to_string(),
+            data: LogFilterState { filter },
+        })
+        .into_response(),
+        Err(TurboError::InvalidMessage(e)) => error_response(StatusCode::BAD_REQUEST, e),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn get_shard(State(turbocharger): State<Arc<ProductionTurboCharger>>) -> Response {
+    shard_response(turbocharger.shard_assignment())
+}
+
+/// Takes `{"modulo": 4, "shard": 1}`, e.g. from a coordinator rebalancing the
+/// fleet after an instance joins or dies.
+async fn reassign_shard(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Json(request): Json<ShardAssignment>,
+) -> Response {
+    match ShardAssignment::new(request.modulo, request.shard) {
+        Ok(assignment) => {
+            turbocharger.reassign_shard(assignment);
+            shard_response(assignment)
+        }
+        Err(e) => error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    }
+}
+
+fn shard_response(assignment: ShardAssignment) -> Response {
+    Json(ShardResponse {
+        status: "success".to_string(),
+        data: assignment,
+    })
+    .into_response()
+}
+
+/// An exact count by table scan; `/stats` reports the maintained counts.
+async fn count_records(State(turbocharger): State<Arc<ProductionTurboCharger>>) -> Response {
+    match turbocharger.count_records_exact().await {
+        Ok(records) => Json(RecordCountResponse {
+            status: "success".to_string(),
+            data: RecordCount { records },
+        })
+        .into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn get_watchlist(State(turbocharger): State<Arc<ProductionTurboCharger>>) -> Response {
+    watchlist_response(&turbocharger)
+}
+
+/// Adds a DID or handle; adding one already watched is a no-op.
+async fn watch_account(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Path(entry): Path<String>,
+) -> Response {
+    let watchlist = turbocharger.watchlist();
+    if !watchlist.add(&entry) && !watchlist.contains(&entry) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("'{entry}' is not a DID or handle"),
+        );
+    }
+    watchlist_response(&turbocharger)
+}
+
+async fn unwatch_account(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Path(entry): Path<String>,
+) -> Response {
+    if !turbocharger.watchlist().remove(&entry) {
+        return error_response(
+            StatusCode::NOT_FOUND,
+            format!("'{entry}' is not on the watchlist"),
+        );
+    }
+    watchlist_response(&turbocharger)
+}
+
+fn watchlist_response(turbocharger: &ProductionTurboCharger) -> Response {
+    Json(WatchlistResponse {
+        status: "success".to_string(),
+        data: WatchlistState {
+            entries: turbocharger.watchlist().entries(),
+        },
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_token_must_match_exactly() {
+        let mut headers = HeaderMap::new();
+        assert!(!bearer_token_matches(&headers, "secret"));
+
+        headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        assert!(bearer_token_matches(&headers, "secret"));
+        assert!(!bearer_token_matches(&headers, "secret2"));
+        assert!(!bearer_token_matches(&headers, "secreT"));
+
+        headers.insert(header::AUTHORIZATION, "Basic secret".parse().unwrap());
+        assert!(!bearer_token_matches(&headers, "secret"));
+    }
+}
@@ -0,0 +1,85 @@
+//! Graceful shutdown for long-lived connections. On shutdown the listeners stop
+//! accepting, each WebSocket client is sent what's already queued for it and a
+//! going-away close frame, and shutdown waits a bounded time for them to finish.
+
+use std::time::Duration;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+use tokio_util::task::task_tracker::TaskTrackerToken;
+use tokio_util::task::TaskTracker;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionDrain {
+    shutdown: CancellationToken,
+    connections: TaskTracker,
+}
+
+impl ConnectionDrain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves once shutdown has started.
+    pub fn shutdown_started(&self) -> WaitForCancellationFutureOwned {
+        self.shutdown.clone().cancelled_owned()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    /// Held by a connection for as long as shutdown should wait on it.
+    pub fn track(&self) -> TaskTrackerToken {
+        self.connections.token()
+    }
+
+    /// Starts shutdown and waits up to `timeout` for tracked connections to
+    /// close. Returns whether they all did.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.shutdown.cancel();
+        self.connections.close();
+        let open = self.connections.len();
+        if open > 0 {
+            info!("Draining {} WebSocket connections", open);
+        }
+        let drained = tokio::time::timeout(timeout, self.connections.wait())
+            .await
+            .is_ok();
+        if !drained {
+            warn!(
+                "{} connections still open after the {:?} drain timeout",
+                self.connections.len(),
+                timeout
+            );
+        }
+        drained
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_tracked_connections() {
+        let drain = ConnectionDrain::new();
+        let connection = drain.track();
+        let connection_drain = drain.clone();
+        let handler = tokio::spawn(async move {
+            connection_drain.shutdown_started().await;
+            // Flushing and closing the socket
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(connection);
+        });
+
+        assert!(!drain.is_draining());
+        assert!(drain.drain(Duration::from_secs(5)).await);
+        assert!(drain.is_draining());
+        handler.await.unwrap();
+
+        // A connection that never closes is given up on at the timeout
+        let drain = ConnectionDrain::new();
+        let _stuck = drain.track();
+        assert!(!drain.drain(Duration::from_millis(20)).await);
+    }
+}
@@ -1,25 +1,193 @@
+pub mod admin;
+pub mod drain;
+pub mod route_metrics;
+pub mod tls;
+#[cfg(unix)]
+mod unix;
+
+pub use jetstream_turbo_access_log as access_log;
+
+use crate::config::Settings;
+use crate::models::at_uri::AtUri;
+use crate::models::bluesky::BlueskyProfile;
+use crate::models::enriched::{EnrichedRecord, OutputFormat};
 use crate::models::errors::{TurboError, TurboResult};
-use crate::turbocharger::{HealthDiagnostics, HealthStatus, ProductionTurboCharger, TurboStats};
+use crate::storage::{HourlyAggregate, Thread};
+use crate::telemetry::LogFilterHandle;
+use crate::turbocharger::{
+    HealthDiagnostics, HealthStatus, ProductionTurboCharger, ReadinessStatus, RecordSubscription,
+    ThroughputSeriesSnapshot, TurboStats,
+};
+use access_log::TrustedProxies;
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        Query, State,
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Extension, Path, Query, RawQuery, State,
     },
     http::StatusCode,
-    response::Json,
+    middleware,
+    response::{Html, IntoResponse, Json},
     routing::{get, Router},
 };
+use drain::ConnectionDrain;
 use futures::{SinkExt, StreamExt};
+use metrics_exporter_prometheus::PrometheusHandle;
+use route_metrics::RouteMetrics;
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use std::time::Duration;
+use tls::TlsPaths;
 use tracing::info;
 
+/// Single-page triage view over the stats, timeseries, health and WebSocket endpoints.
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+const DEFAULT_AGGREGATE_HOURS: i64 = 24;
+/// Aggregates outlive raw records, so this is well past the usual retention
+const MAX_AGGREGATE_HOURS: i64 = 24 * 90;
+const MAX_WS_BATCH_MS: u64 = 5_000;
+const DEFAULT_RECORDS_LIMIT: u32 = 100;
+const MAX_RECORDS_LIMIT: u32 = 1_000;
+/// Same limit Jetstream puts on `wantedCollections`
+const MAX_WANTED_COLLECTIONS: usize = 100;
+/// A coalesced frame is sent early once it holds this many records
+const MAX_WS_BATCH_RECORDS: usize = 1_000;
+
 #[derive(Deserialize)]
 pub struct StatsQuery {
     pub detailed: Option<bool>,
 }
 
+#[derive(Deserialize)]
+pub struct TimeseriesQuery {
+    /// Most recent minutes to return; all retained ones by default
+    pub minutes: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct AggregatesQuery {
+    /// Most recent hours to return, including the current one; 24 by default
+    pub hours: Option<i64>,
+    /// Hashtags listed per hour; 10 by default
+    pub top_hashtags: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct AggregatesResponse {
+    pub status: String,
+    pub data: Vec<HourlyAggregate>,
+}
+
+#[derive(Serialize)]
+pub struct TimeseriesResponse {
+    pub status: String,
+    pub data: ThroughputSeriesSnapshot,
+}
+
+#[derive(Deserialize)]
+pub struct WsQuery {
+    pub format: Option<OutputFormat>,
+    /// `event_id` of the last record a reconnecting client received
+    pub last_id: Option<u64>,
+    /// Send records as JSON arrays flushed every this many milliseconds
+    /// instead of one frame per record; 0 or unset disables coalescing
+    pub batch_ms: Option<u64>,
+}
+
+impl WsQuery {
+    fn batch_interval(&self) -> Result<Option<Duration>, String> {
+        match self.batch_ms {
+            None | Some(0) => Ok(None),
+            Some(ms) if ms > MAX_WS_BATCH_MS => {
+                Err(format!("batch_ms must be between 0 and {MAX_WS_BATCH_MS}"))
+            }
+            Some(ms) => Ok(Some(Duration::from_millis(ms))),
+        }
+    }
+}
+
+/// Collections a `/subscribe` client asked for through `wantedCollections`, given
+/// as repeated parameters or comma-separated. `app.bsky.graph.*` matches every
+/// collection under that prefix. Empty means every collection; identity and
+/// account events are sent regardless, as Jetstream does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WantedCollections {
+    exact: Vec<String>,
+    prefixes: Vec<String>,
+}
+
+impl WantedCollections {
+    fn from_query(query: Option<&str>) -> Result<Self, String> {
+        let mut wanted = Self::default();
+        let values = url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+            .filter(|(key, _)| key == "wantedCollections")
+            .map(|(_, value)| value.into_owned())
+            .collect::<Vec<_>>();
+        for collection in values
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+        {
+            if collection.is_empty() {
+                continue;
+            }
+            if wanted.exact.len() + wanted.prefixes.len() == MAX_WANTED_COLLECTIONS {
+                return Err(format!(
+                    "at most {MAX_WANTED_COLLECTIONS} wantedCollections are allowed"
+                ));
+            }
+            match collection.strip_suffix('*') {
+                Some(prefix) if prefix.ends_with('.') && !prefix.contains('*') => {
+                    wanted.prefixes.push(prefix.to_string())
+                }
+                None if !collection.contains('*') => wanted.exact.push(collection.to_string()),
+                _ => return Err(format!("invalid wantedCollections entry: {collection}")),
+            }
+        }
+        Ok(wanted)
+    }
+
+    fn matches(&self, record: &EnrichedRecord) -> bool {
+        if self.exact.is_empty() && self.prefixes.is_empty() {
+            return true;
+        }
+        let Some(commit) = &record.message.commit else {
+            return true;
+        };
+        commit.collection.as_deref().is_some_and(|collection| {
+            self.exact.iter().any(|want| want == collection)
+                || self
+                    .prefixes
+                    .iter()
+                    .any(|prefix| collection.starts_with(prefix.as_str()))
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RecordQuery {
+    /// Fetch and hydrate the record from its repo when it isn't stored
+    pub hydrate: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct RecordsQuery {
+    /// Earliest `time_us` to include
+    pub since_us: Option<i64>,
+    /// Latest `time_us` to include
+    pub until_us: Option<i64>,
+    /// Most records to return, newest first; 100 by default
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct RecordsResponse {
+    pub status: String,
+    pub data: Vec<EnrichedRecord>,
+}
+
 #[derive(Serialize)]
 pub struct StatsResponse {
     pub status: String,
@@ -32,6 +200,24 @@ pub struct HealthResponse {
     pub data: HealthStatus,
 }
 
+#[derive(Serialize)]
+pub struct RecordResponse {
+    pub status: String,
+    pub data: EnrichedRecord,
+}
+
+#[derive(Serialize)]
+pub struct ThreadResponse {
+    pub status: String,
+    pub data: Thread,
+}
+
+#[derive(Serialize)]
+pub struct ProfileResponse {
+    pub status: String,
+    pub data: Arc<BlueskyProfile>,
+}
+
 #[derive(Serialize)]
 pub struct ErrorResponse {
     pub status: String,
@@ -42,8 +228,16 @@ pub fn create_router(turbocharger: Arc<ProductionTurboCharger>) -> Router {
     Router::new()
         .route("/health", get(health_check))
         .route("/stats", get(get_stats))
+        .route("/stats/timeseries", get(get_stats_timeseries))
+        .route("/aggregates", get(get_aggregates))
         .route("/metrics", get(get_metrics))
+        .route("/records", get(get_records))
+        .route("/records/*at_uri", get(get_record))
+        .route("/threads/*root_uri", get(get_thread))
+        .route("/profiles/:did", get(get_profile))
         .route("/ws", get(ws_handler))
+        .route("/ws/:stream", get(stream_ws_handler))
+        .nest("/admin", admin::router(Arc::clone(&turbocharger)))
         .with_state(turbocharger)
 }
 
@@ -72,37 +266,331 @@ async fn get_stats(
     }
 }
 
-async fn get_metrics(State(turbocharger): State<Arc<ProductionTurboCharger>>) -> String {
+async fn get_stats_timeseries(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Query(query): Query<TimeseriesQuery>,
+) -> Json<TimeseriesResponse> {
+    Json(TimeseriesResponse {
+        status: "success".to_string(),
+        data: turbocharger.throughput_series(query.minutes),
+    })
+}
+
+async fn get_aggregates(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Query(query): Query<AggregatesQuery>,
+) -> axum::response::Response {
+    let hours = query.hours.unwrap_or(DEFAULT_AGGREGATE_HOURS);
+    if !(1..=MAX_AGGREGATE_HOURS).contains(&hours) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("hours must be between 1 and {MAX_AGGREGATE_HOURS}"),
+        );
+    }
+    match turbocharger
+        .hourly_aggregates(hours, query.top_hashtags.unwrap_or(10))
+        .await
+    {
+        Ok(aggregates) => Json(AggregatesResponse {
+            status: "success".to_string(),
+            data: aggregates,
+        })
+        .into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn get_metrics(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Extension(route_metrics): Extension<RouteMetrics>,
+    Extension(recorder): Extension<PrometheusHandle>,
+) -> String {
     let diagnostics = turbocharger.get_runtime_diagnostics().await;
-    prometheus_metrics_from_diagnostics(&diagnostics)
+    let mut output = prometheus_metrics_from_diagnostics(&diagnostics);
+    output.push_str(&route_metrics.render());
+    output.push_str(&recorder.render());
+    output
+}
+
+async fn get_records(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Query(query): Query<RecordsQuery>,
+) -> axum::response::Response {
+    let limit = query.limit.unwrap_or(DEFAULT_RECORDS_LIMIT);
+    if !(1..=MAX_RECORDS_LIMIT).contains(&limit) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("limit must be between 1 and {MAX_RECORDS_LIMIT}"),
+        );
+    }
+    match turbocharger
+        .records_in_time_range(query.since_us, query.until_us, limit)
+        .await
+    {
+        Ok(records) => Json(RecordsResponse {
+            status: "success".to_string(),
+            data: records,
+        })
+        .into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn get_record(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Path(at_uri): Path<String>,
+    Query(query): Query<RecordQuery>,
+) -> axum::response::Response {
+    if !AtUri::is_valid(&at_uri) {
+        return error_response(StatusCode::BAD_REQUEST, format!("Invalid AT-URI: {at_uri}"));
+    }
+    match turbocharger
+        .lookup_record(&at_uri, query.hydrate.unwrap_or(false))
+        .await
+    {
+        Ok(Some(record)) => Json(RecordResponse {
+            status: "success".to_string(),
+            data: record,
+        })
+        .into_response(),
+        Ok(None) => error_response(StatusCode::NOT_FOUND, format!("Record not found: {at_uri}")),
+        Err(e @ TurboError::RateLimited { .. }) => {
+            error_response(StatusCode::TOO_MANY_REQUESTS, e.to_string())
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn get_thread(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Path(root_uri): Path<String>,
+) -> axum::response::Response {
+    if !AtUri::is_valid(&root_uri) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid AT-URI: {root_uri}"),
+        );
+    }
+    match turbocharger.get_thread(&root_uri).await {
+        Ok(Some(thread)) => Json(ThreadResponse {
+            status: "success".to_string(),
+            data: thread,
+        })
+        .into_response(),
+        Ok(None) => error_response(
+            StatusCode::NOT_FOUND,
+            format!("No stored posts in thread: {root_uri}"),
+        ),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn get_profile(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Path(did): Path<String>,
+) -> axum::response::Response {
+    if !did.starts_with("did:") {
+        return error_response(StatusCode::BAD_REQUEST, format!("Invalid DID: {did}"));
+    }
+    match turbocharger.get_profile(&did).await {
+        Ok(Some(profile)) => Json(ProfileResponse {
+            status: "success".to_string(),
+            data: profile,
+        })
+        .into_response(),
+        Ok(None) => error_response(StatusCode::NOT_FOUND, format!("Profile not found: {did}")),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+fn error_response(status: StatusCode, error: String) -> axum::response::Response {
+    (
+        status,
+        Json(ErrorResponse {
+            status: "error".to_string(),
+            error,
+        }),
+    )
+        .into_response()
 }
 
 async fn ws_handler(
     State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Extension(drain): Extension<ConnectionDrain>,
+    Query(query): Query<WsQuery>,
     ws: WebSocketUpgrade,
 ) -> axum::response::Response {
-    ws.on_upgrade(move |socket| handle_websocket(socket, turbocharger.subscribe()))
+    let batch = match query.batch_interval() {
+        Ok(batch) => batch,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+    };
+    let format = query.format.unwrap_or_else(|| turbocharger.output_format());
+    let subscription = turbocharger.subscribe(query.last_id);
+    ws.on_upgrade(move |socket| {
+        handle_websocket(
+            socket,
+            subscription,
+            WantedCollections::default(),
+            format,
+            batch,
+            drain,
+        )
+    })
 }
 
+/// Jetstream's `/subscribe` endpoint, so existing Jetstream consumers can point at
+/// this server unchanged. Records are sent in the Jetstream shape unless the
+/// client asks for another `format`.
+async fn subscribe_handler(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Extension(drain): Extension<ConnectionDrain>,
+    Query(query): Query<WsQuery>,
+    RawQuery(raw_query): RawQuery,
+    ws: WebSocketUpgrade,
+) -> axum::response::Response {
+    let batch = match query.batch_interval() {
+        Ok(batch) => batch,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+    };
+    let collections = match WantedCollections::from_query(raw_query.as_deref()) {
+        Ok(collections) => collections,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+    };
+    let format = query.format.unwrap_or(OutputFormat::Jetstream);
+    let subscription = turbocharger.subscribe(query.last_id);
+    ws.on_upgrade(move |socket| {
+        handle_websocket(socket, subscription, collections, format, batch, drain)
+    })
+}
+
+async fn stream_ws_handler(
+    State(turbocharger): State<Arc<ProductionTurboCharger>>,
+    Extension(drain): Extension<ConnectionDrain>,
+    Path(stream): Path<String>,
+    Query(query): Query<WsQuery>,
+    ws: WebSocketUpgrade,
+) -> axum::response::Response {
+    let batch = match query.batch_interval() {
+        Ok(batch) => batch,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+    };
+    let Some(subscription) = turbocharger.subscribe_stream(&stream, query.last_id) else {
+        return error_response(
+            StatusCode::NOT_FOUND,
+            format!("Unknown output stream: {stream}"),
+        );
+    };
+    let format = query.format.unwrap_or_else(|| turbocharger.output_format());
+    ws.on_upgrade(move |socket| {
+        handle_websocket(
+            socket,
+            subscription,
+            WantedCollections::default(),
+            format,
+            batch,
+            drain,
+        )
+    })
+}
+
+/// Joins encoded records into one JSON array, emptying `pending`.
+fn coalesce(pending: &mut Vec<String>) -> Option<String> {
+    if pending.is_empty() {
+        return None;
+    }
+    let len = pending.iter().map(|json| json.len() + 1).sum::<usize>() + 1;
+    let mut frame = String::with_capacity(len);
+    frame.push('[');
+    for (i, json) in pending.drain(..).enumerate() {
+        if i > 0 {
+            frame.push(',');
+        }
+        frame.push_str(&json);
+    }
+    frame.push(']');
+    Some(frame)
+}
+
+/// Streams the records in `collections` to one client, one frame per record or,
+/// with `batch` set, as arrays flushed on that interval.
 async fn handle_websocket(
     socket: WebSocket,
-    mut broadcast_rx: broadcast::Receiver<crate::models::enriched::EnrichedRecord>,
+    mut subscription: RecordSubscription,
+    collections: WantedCollections,
+    format: OutputFormat,
+    batch: Option<Duration>,
+    drain: ConnectionDrain,
 ) {
+    let _connection = drain.track();
+    let mut shutdown_started = std::pin::pin!(drain.shutdown_started());
     let (mut sender, mut socket_rx) = socket.split();
+    let mut pending = Vec::new();
+    let mut flush = tokio::time::interval(batch.unwrap_or(Duration::from_secs(3600)));
+    flush.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
     loop {
         tokio::select! {
-            msg = broadcast_rx.recv() => {
+            _ = &mut shutdown_started => {
+                // Send what's already queued, then tell the client to reconnect elsewhere
+                while let Some(event) = subscription.try_recv() {
+                    if !collections.matches(&event.record) {
+                        continue;
+                    }
+                    if let Ok(json) = format.encode_event(&event.record, event.id) {
+                        if batch.is_some() {
+                            pending.push(json);
+                        } else if sender.send(Message::Text(json)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                if let Some(frame) = coalesce(&mut pending) {
+                    if sender.send(Message::Text(frame)).await.is_err() {
+                        return;
+                    }
+                }
+                let close = CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "server shutting down".into(),
+                };
+                let _ = sender.send(Message::Close(Some(close))).await;
+                break;
+            }
+            _ = flush.tick(), if batch.is_some() => {
+                if let Some(frame) = coalesce(&mut pending) {
+                    if sender.send(Message::Text(frame)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            msg = subscription.recv() => {
                 match msg {
-                    Ok(record) => {
-                        if let Ok(json) = serde_json::to_string(&record) {
-                            if sender.send(Message::Text(json)).await.is_err() {
-                                break;
+                    Some(event) => {
+                        if !collections.matches(&event.record) {
+                            continue;
+                        }
+                        let Ok(json) = format.encode_event(&event.record, event.id) else {
+                            continue;
+                        };
+                        let frame = if batch.is_some() {
+                            pending.push(json);
+                            if pending.len() < MAX_WS_BATCH_RECORDS {
+                                continue;
                             }
+                            coalesce(&mut pending).unwrap_or_default()
+                        } else {
+                            json
+                        };
+                        if sender.send(Message::Text(frame)).await.is_err() {
+                            break;
                         }
                     }
-                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
-                    Err(broadcast::error::RecvError::Closed) => break,
+                    None => {
+                        if let Some(frame) = coalesce(&mut pending) {
+                            let _ = sender.send(Message::Text(frame)).await;
+                        }
+                        break;
+                    }
                 }
             }
             msg = socket_rx.next() => {
@@ -115,27 +603,110 @@ async fn handle_websocket(
     }
 }
 
+/// Where the API server listens.
+#[derive(Debug, Clone)]
+pub enum ServerBinding {
+    /// All interfaces on `port`, over HTTPS when `tls` is given
+    Tcp { port: u16, tls: Option<TlsPaths> },
+    /// A unix socket at this path
+    Unix(PathBuf),
+}
+
+impl ServerBinding {
+    pub fn from_settings(settings: &Settings) -> Self {
+        if let Some(path) = &settings.http_unix_socket {
+            return Self::Unix(PathBuf::from(path));
+        }
+        let tls = settings
+            .tls_cert_path
+            .as_ref()
+            .zip(settings.tls_key_path.as_ref())
+            .map(|(cert_path, key_path)| TlsPaths::new(cert_path, key_path));
+        Self::Tcp {
+            port: settings.http_port,
+            tls,
+        }
+    }
+}
+
+/// Serves the API until `drain` starts shutdown.
 pub async fn create_server(
-    port: u16,
+    binding: ServerBinding,
     turbocharger: Arc<ProductionTurboCharger>,
+    drain: ConnectionDrain,
+    log_filter: LogFilterHandle,
+    trusted_proxies: TrustedProxies,
+    recorder: PrometheusHandle,
 ) -> TurboResult<()> {
     let readiness_turbocharger = Arc::clone(&turbocharger);
+    let subscribe = Router::new()
+        .route("/subscribe", get(subscribe_handler))
+        .with_state(Arc::clone(&turbocharger));
     let app = Router::new()
         .nest("/api/v1", create_router(turbocharger))
+        .merge(subscribe)
         .route("/", get(|| async { "jetstream-turbo API server" }))
+        .route("/dashboard", get(|| async { Html(DASHBOARD_HTML) }))
+        // Liveness only says the process is serving; restarts on stuck pipelines
+        // are driven by /api/v1/health
+        .route("/live", get(|| async { StatusCode::OK }))
         .route(
             "/ready",
             get(move || {
                 let turbocharger = Arc::clone(&readiness_turbocharger);
                 async move {
-                    match turbocharger.health_check().await {
-                        Ok(status) => readiness_http_status(&status),
-                        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
-                    }
+                    let status = turbocharger.readiness_check().await;
+                    (readiness_http_status(&status), Json(status))
                 }
             }),
         );
 
+    let route_metrics = RouteMetrics::default();
+    let app = app
+        .route_layer(middleware::from_fn_with_state(
+            route_metrics.clone(),
+            route_metrics::track,
+        ))
+        .layer(Extension(route_metrics))
+        .layer(Extension(recorder))
+        .layer(Extension(drain.clone()))
+        .layer(Extension(log_filter));
+    let app = access_log::with_access_log(app, trusted_proxies);
+
+    let (port, tls) = match binding {
+        ServerBinding::Tcp { port, tls } => (port, tls),
+        #[cfg(unix)]
+        ServerBinding::Unix(path) => {
+            return unix::serve(&path, app, drain.shutdown_started()).await
+        }
+        #[cfg(not(unix))]
+        ServerBinding::Unix(_) => {
+            return Err(TurboError::Internal(
+                "unix sockets are not supported on this platform".to_string(),
+            ))
+        }
+    };
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    if let Some(paths) = tls {
+        let config = tls::load_with_reload(paths)?;
+        info!("Starting HTTPS server on port {}", port);
+        let handle = axum_server::Handle::new();
+        let shutdown_handle = handle.clone();
+        let shutdown_started = drain.shutdown_started();
+        tokio::spawn(async move {
+            shutdown_started.await;
+            // Connection draining is bounded by ConnectionDrain, not here
+            shutdown_handle.graceful_shutdown(None);
+        });
+        axum_server::bind_rustls(SocketAddr::from(([0, 0, 0, 0], port)), config)
+            .handle(handle)
+            .serve(app)
+            .await
+            .map_err(TurboError::Io)?;
+        return Ok(());
+    }
+
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}"))
         .await
         .map_err(TurboError::Io)?;
@@ -143,14 +714,15 @@ pub async fn create_server(
     info!("Starting HTTP server on port {}", port);
 
     axum::serve(listener, app)
+        .with_graceful_shutdown(drain.shutdown_started())
         .await
         .map_err(|e| TurboError::Io(std::io::Error::other(e)))?;
 
     Ok(())
 }
 
-fn readiness_http_status(status: &HealthStatus) -> StatusCode {
-    if status.healthy {
+fn readiness_http_status(status: &ReadinessStatus) -> StatusCode {
+    if status.ready {
         StatusCode::OK
     } else {
         StatusCode::SERVICE_UNAVAILABLE
@@ -158,11 +730,10 @@ fn readiness_http_status(status: &HealthStatus) -> StatusCode {
 }
 
 fn health_http_response(status: HealthStatus) -> (StatusCode, HealthResponse) {
-    let status_code = readiness_http_status(&status);
-    let response_status = if status.healthy {
-        "healthy"
+    let (status_code, response_status) = if status.healthy {
+        (StatusCode::OK, "healthy")
     } else {
-        "unhealthy"
+        (StatusCode::SERVICE_UNAVAILABLE, "unhealthy")
     };
 
     (
@@ -296,24 +867,27 @@ fn prometheus_metrics_from_diagnostics(diagnostics: &HealthDiagnostics) -> Strin
         "Current SQLite WAL file size in bytes.",
         optional_i64_metric_value(diagnostics.sqlite_state.wal_size_bytes),
     );
-    append_gauge_metric(
-        &mut output,
-        "jetstream_turbo_not_redis_connected",
-        "Whether not_redis is currently reachable (1 = yes, 0 = no).",
-        bool_metric_value(diagnostics.not_redis_state.connected),
-    );
-    append_gauge_metric(
-        &mut output,
-        "jetstream_turbo_not_redis_stream_length",
-        "Current not_redis stream length.",
-        optional_usize_metric_value(diagnostics.not_redis_state.stream_length),
-    );
-    append_gauge_metric(
-        &mut output,
-        "jetstream_turbo_not_redis_configured_max_length",
-        "Configured not_redis stream trim max length.",
-        optional_usize_metric_value(diagnostics.not_redis_state.configured_max_length),
-    );
+    // Standalone mode has no Redis to report on
+    if let Some(not_redis_state) = &diagnostics.not_redis_state {
+        append_gauge_metric(
+            &mut output,
+            "jetstream_turbo_not_redis_connected",
+            "Whether not_redis is currently reachable (1 = yes, 0 = no).",
+            bool_metric_value(not_redis_state.connected),
+        );
+        append_gauge_metric(
+            &mut output,
+            "jetstream_turbo_not_redis_stream_length",
+            "Current not_redis stream length.",
+            optional_usize_metric_value(not_redis_state.stream_length),
+        );
+        append_gauge_metric(
+            &mut output,
+            "jetstream_turbo_not_redis_configured_max_length",
+            "Configured not_redis stream trim max length.",
+            optional_usize_metric_value(not_redis_state.configured_max_length),
+        );
+    }
 
     output
 }
@@ -361,13 +935,86 @@ fn optional_usize_metric_value(value: Option<usize>) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{health_http_response, prometheus_metrics_from_diagnostics, readiness_http_status};
+    use super::{
+        coalesce, create_router, handle_websocket, health_http_response,
+        prometheus_metrics_from_diagnostics, readiness_http_status, ConnectionDrain,
+        ProductionTurboCharger, RouteMetrics, WantedCollections, WsQuery,
+    };
+    use crate::client::BlueskyClient;
+    use crate::config::Settings;
+    use crate::models::enriched::{EnrichedRecord, OutputFormat};
+    use crate::storage::{RecordStore, SQLitePragmaConfig, ShardedSQLiteStore};
+    use crate::telemetry::{prometheus_recorder, DropCounters, DropReason};
+    use crate::testing::fixtures::create_post_message;
+    use crate::testing::get_profiles_response;
+    use crate::turbocharger::broadcast::RecordBroadcaster;
     use crate::turbocharger::{
-        CacheStateDiagnostics, HealthDiagnostics, HealthStatus, MemoryPeakDiagnostics,
-        NotRedisStateDiagnostics, ProcessMemoryDiagnostics, SQLiteStateDiagnostics,
+        CacheStateDiagnostics, HealthDiagnostics, HealthStatus, LivenessThresholds,
+        MemoryPeakDiagnostics, NotRedisStateDiagnostics, PipelineActivity,
+        ProcessMemoryDiagnostics, ReadinessStatus, SQLiteStateDiagnostics, TurboChargerBuilder,
     };
-    use axum::http::StatusCode;
+    use axum::body::Body;
+    use axum::extract::{ws::WebSocketUpgrade, Extension, RawQuery};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::{get, Router};
+    use futures::StreamExt;
+    use metrics_exporter_prometheus::PrometheusHandle;
     use serde_json::Value;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn record_in(collection: &str, index: usize) -> Arc<EnrichedRecord> {
+        let mut message = create_post_message(index);
+        if let Some(commit) = message.commit.as_mut() {
+            commit.collection = Some(collection.to_string());
+        }
+        Arc::new(EnrichedRecord::new(message))
+    }
+
+    fn temp_db_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("test_server_{}", uuid::Uuid::new_v4()))
+    }
+
+    /// A turbocharger over SQLite in `settings.db_dir`, hydrating through the
+    /// XRPC API at `api_url` and publishing to WebSocket clients only.
+    async fn test_turbocharger(settings: Settings, api_url: &str) -> Arc<ProductionTurboCharger> {
+        let client = Arc::new(
+            BlueskyClient::new(vec![format!("token:::{api_url}")], None, 25, 25, 10, 10).unwrap(),
+        );
+        let settings = Settings {
+            standalone: true,
+            ..settings
+        };
+        let turbocharger = TurboChargerBuilder::new(settings)
+            .fetchers(Arc::clone(&client), client)
+            .build()
+            .await
+            .unwrap();
+        Arc::new(turbocharger)
+    }
+
+    fn test_app(turbocharger: Arc<ProductionTurboCharger>, recorder: PrometheusHandle) -> Router {
+        create_router(turbocharger)
+            .layer(Extension(RouteMetrics::default()))
+            .layer(Extension(recorder))
+    }
+
+    async fn get_body(app: &Router, uri: &str) -> (StatusCode, String) {
+        let response = app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
 
     fn sample_diagnostics() -> HealthDiagnostics {
         HealthDiagnostics {
@@ -413,36 +1060,50 @@ mod tests {
                 journal_size_limit_bytes: Some(5368709120),
                 collection_error: None,
             },
-            not_redis_state: NotRedisStateDiagnostics {
+            not_redis_state: Some(NotRedisStateDiagnostics {
                 connected: true,
                 engine: "not_redis".to_string(),
                 stream_name: "hydrated_jetstream".to_string(),
                 stream_length: Some(7),
                 configured_max_length: Some(100),
                 collection_error: None,
-            },
+            }),
         }
     }
 
     fn sample_health(healthy: bool) -> HealthStatus {
         HealthStatus {
             healthy,
-            redis_connected: healthy,
+            redis_connected: Some(healthy),
             sqlite_available: healthy,
             session_count: if healthy { 1 } else { 0 },
+            liveness: LivenessThresholds {
+                max_message_age: Duration::ZERO,
+                max_disconnected: Duration::ZERO,
+                min_session_ttl: Duration::ZERO,
+                max_sink_error_rate: 1.0,
+            }
+            .evaluate(&PipelineActivity::new(), None, None),
             diagnostics: sample_diagnostics(),
         }
     }
 
+    fn sample_readiness(authenticated: bool) -> ReadinessStatus {
+        ReadinessStatus::new(authenticated, Some(true), true, Some(true), false, 6, false)
+    }
+
     #[test]
-    fn readiness_http_status_is_ok_when_healthy() {
-        assert_eq!(readiness_http_status(&sample_health(true)), StatusCode::OK);
+    fn readiness_http_status_is_ok_when_ready() {
+        assert_eq!(
+            readiness_http_status(&sample_readiness(true)),
+            StatusCode::OK
+        );
     }
 
     #[test]
-    fn readiness_http_status_is_503_when_unhealthy() {
+    fn readiness_http_status_is_503_while_authenticating() {
         assert_eq!(
-            readiness_http_status(&sample_health(false)),
+            readiness_http_status(&sample_readiness(false)),
             StatusCode::SERVICE_UNAVAILABLE
         );
     }
@@ -455,7 +1116,12 @@ mod tests {
         assert!(response.data.healthy);
         assert_eq!(response.data.diagnostics.cache_state.user_capacity, 10);
         assert_eq!(
-            response.data.diagnostics.not_redis_state.stream_name,
+            response
+                .data
+                .diagnostics
+                .not_redis_state
+                .unwrap()
+                .stream_name,
             "hydrated_jetstream"
         );
     }
@@ -521,8 +1187,9 @@ mod tests {
             .peaks_24h
             .virtual_memory_peak_unix_seconds = None;
         diagnostics.sqlite_state.db_size_bytes = None;
-        diagnostics.not_redis_state.stream_length = None;
-        diagnostics.not_redis_state.configured_max_length = None;
+        let not_redis_state = diagnostics.not_redis_state.as_mut().unwrap();
+        not_redis_state.stream_length = None;
+        not_redis_state.configured_max_length = None;
 
         let output = prometheus_metrics_from_diagnostics(&diagnostics);
         assert!(output.contains("jetstream_turbo_process_memory_rss_bytes NaN"));
@@ -533,4 +1200,242 @@ mod tests {
         assert!(output.contains("jetstream_turbo_not_redis_stream_length NaN"));
         assert!(output.contains("jetstream_turbo_not_redis_configured_max_length NaN"));
     }
+
+    #[tokio::test]
+    async fn metrics_route_renders_the_recorded_pipeline_metrics() {
+        let dir = temp_db_dir();
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let turbocharger = test_turbocharger(settings, "http://127.0.0.1:9").await;
+        let recorder = prometheus_recorder();
+        metrics::with_local_recorder(&recorder, || {
+            DropCounters::new().record(DropReason::Spam, 2);
+        });
+
+        let app = test_app(turbocharger, recorder.handle());
+        let (status, body) = get_body(&app, "/metrics").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("jetstream_turbo_process_memory_rss_bytes"));
+        assert!(body.contains("jetstream_turbo_dropped_total{reason=\"spam\"} 2"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// Stores `records` where a turbocharger over `settings` reads them.
+    async fn seed_store(settings: &Settings, records: &[Arc<EnrichedRecord>]) {
+        let store = ShardedSQLiteStore::new(
+            PathBuf::from(&settings.db_dir).join("jetstream.db"),
+            settings.sqlite_shards,
+            SQLitePragmaConfig {
+                cache_size_kib: settings.sqlite_cache_size_kib,
+                mmap_size_mb: settings.sqlite_mmap_size_mb,
+                journal_size_limit_mb: settings.sqlite_journal_size_limit_mb,
+            },
+        )
+        .await
+        .unwrap();
+        store.store_batch(records).await.unwrap();
+        store.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn record_route_serves_stored_records_and_rejects_malformed_uris() {
+        let dir = temp_db_dir();
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let stored = record_in("app.bsky.feed.post", 1);
+        seed_store(&settings, &[Arc::clone(&stored)]).await;
+        let turbocharger = test_turbocharger(settings, "http://127.0.0.1:9").await;
+        let app = test_app(turbocharger, prometheus_recorder().handle());
+
+        let uri = stored.get_at_uri().unwrap();
+        let (status, body) = get_body(&app, &format!("/records/{uri}")).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["status"], "success");
+        assert_eq!(body["data"]["message"]["did"], "did:plc:user0001");
+
+        let missing = "at://did:plc:user0001/app.bsky.feed.post/3missing";
+        let (status, body) = get_body(&app, &format!("/records/{missing}")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.contains("Record not found"));
+
+        let (status, body) = get_body(&app, "/records/not-an-at-uri").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("Invalid AT-URI"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn record_route_rate_limits_hydrate_on_miss() {
+        let pds = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&pds)
+            .await;
+        let dir = temp_db_dir();
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            backfill_api_url: pds.uri(),
+            hydrate_on_miss_per_minute: 1,
+            ..Default::default()
+        };
+        let turbocharger = test_turbocharger(settings, "http://127.0.0.1:9").await;
+        let app = test_app(turbocharger, prometheus_recorder().handle());
+
+        let missing = "/records/at://did:plc:user0001/app.bsky.feed.post/3missing?hydrate=true";
+        let (status, _) = get_body(&app, missing).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = get_body(&app, missing).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(body.contains("hydrate-on-miss"));
+        assert_eq!(pds.received_requests().await.unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn profile_route_serves_hydrated_profiles_and_rejects_invalid_dids() {
+        // The API leaves out actors it can't resolve
+        let api = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/app.bsky.actor.getProfiles"))
+            .and(query_param("actors", "did:plc:user0001"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(get_profiles_response(&["did:plc:user0001"])),
+            )
+            .mount(&api)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/app.bsky.actor.getProfiles"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(get_profiles_response::<&str>(&[])),
+            )
+            .mount(&api)
+            .await;
+        let dir = temp_db_dir();
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let turbocharger = test_turbocharger(settings, &api.uri()).await;
+        let app = test_app(turbocharger, prometheus_recorder().handle());
+
+        let (status, body) = get_body(&app, "/profiles/did:plc:user0001").await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["status"], "success");
+        assert_eq!(body["data"]["did"], "did:plc:user0001");
+
+        let (status, body) = get_body(&app, "/profiles/did:plc:user0002").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.contains("Profile not found"));
+
+        let (status, body) = get_body(&app, "/profiles/user0001.bsky.social").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("Invalid DID"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn coalesced_frames_are_json_arrays_of_the_pending_records() {
+        let mut pending = vec![
+            r#"{"event_id":1}"#.to_string(),
+            r#"{"event_id":2}"#.to_string(),
+        ];
+        let frame = coalesce(&mut pending).unwrap();
+        let parsed: Vec<Value> = serde_json::from_str(&frame).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1]["event_id"], 2);
+        assert!(pending.is_empty());
+        assert!(coalesce(&mut pending).is_none());
+
+        let query = |batch_ms| WsQuery {
+            format: None,
+            last_id: None,
+            batch_ms,
+        };
+        assert_eq!(query(None).batch_interval(), Ok(None));
+        assert_eq!(query(Some(0)).batch_interval(), Ok(None));
+        assert_eq!(
+            query(Some(250)).batch_interval(),
+            Ok(Some(Duration::from_millis(250)))
+        );
+        assert!(query(Some(60_000)).batch_interval().is_err());
+    }
+
+    #[test]
+    fn wanted_collections_accept_repeated_comma_separated_and_prefix_entries() {
+        let wanted = WantedCollections::from_query(Some(
+            "wantedCollections=app.bsky.feed.post,app.bsky.feed.like&wantedCollections=app.bsky.graph.*",
+        ))
+        .unwrap();
+        assert!(wanted.matches(&record_in("app.bsky.feed.like", 0)));
+        assert!(wanted.matches(&record_in("app.bsky.graph.follow", 1)));
+        assert!(!wanted.matches(&record_in("app.bsky.feed.repost", 2)));
+        assert!(!wanted.matches(&record_in("app.bsky.graphite", 3)));
+
+        let everything = WantedCollections::from_query(None).unwrap();
+        assert!(everything.matches(&record_in("app.bsky.feed.repost", 4)));
+
+        assert!(WantedCollections::from_query(Some("wantedCollections=app.*.post")).is_err());
+        assert!(WantedCollections::from_query(Some("wantedCollections=app.bsky*")).is_err());
+        let too_many = (0..=super::MAX_WANTED_COLLECTIONS)
+            .map(|i| format!("wantedCollections=app.example.c{i}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        assert!(WantedCollections::from_query(Some(&too_many)).is_err());
+    }
+
+    #[tokio::test]
+    async fn subscribe_sends_only_the_wanted_collections() {
+        let broadcaster = Arc::new(RecordBroadcaster::new(16));
+        let app = Router::new().route(
+            "/subscribe",
+            get({
+                let broadcaster = Arc::clone(&broadcaster);
+                move |RawQuery(query): RawQuery, ws: WebSocketUpgrade| async move {
+                    let collections = WantedCollections::from_query(query.as_deref()).unwrap();
+                    let subscription = broadcaster.subscribe();
+                    ws.on_upgrade(move |socket| {
+                        handle_websocket(
+                            socket,
+                            subscription,
+                            collections,
+                            OutputFormat::Jetstream,
+                            None,
+                            ConnectionDrain::new(),
+                        )
+                    })
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!(
+            "ws://{addr}/subscribe?wantedCollections=app.bsky.graph.*"
+        ))
+        .await
+        .unwrap();
+        broadcaster.send(record_in("app.bsky.feed.post", 0));
+        broadcaster.send(record_in("app.bsky.graph.follow", 1));
+
+        // Records arrive in order, so the follow being first means the post was skipped
+        let frame = tokio::time::timeout(Duration::from_secs(5), socket.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let event: Value = serde_json::from_str(frame.to_text().unwrap()).unwrap();
+        assert_eq!(event["commit"]["collection"], "app.bsky.graph.follow");
+    }
 }
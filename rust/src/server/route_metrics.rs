@@ -0,0 +1,189 @@
+//! Per-route request counts and latency histograms, rendered alongside the
+//! runtime diagnostics on `/api/v1/metrics`. Routes are labeled by their
+//! matched template (`/api/v1/profiles/:did`), never the raw path, so label
+//! cardinality stays bounded.
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Upper bounds in seconds of the latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Clone, Default)]
+pub struct RouteMetrics {
+    routes: Arc<Mutex<BTreeMap<(String, String), RouteStats>>>,
+}
+
+#[derive(Default)]
+struct RouteStats {
+    statuses: BTreeMap<u16, u64>,
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    latency_sum: f64,
+    count: u64,
+}
+
+impl RouteMetrics {
+    pub fn record(&self, method: &str, route: &str, status: u16, latency_secs: f64) {
+        let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = routes
+            .entry((method.to_string(), route.to_string()))
+            .or_default();
+        *stats.statuses.entry(status).or_default() += 1;
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| latency_secs <= *le) {
+            stats.buckets[bucket] += 1;
+        }
+        stats.latency_sum += latency_secs;
+        stats.count += 1;
+    }
+
+    /// Prometheus text exposition of every route seen so far. Error rates are
+    /// derived from the `status` label, e.g. 5xx over all requests per route.
+    pub fn render(&self) -> String {
+        let routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut output = String::new();
+
+        output.push_str(
+            "# HELP jetstream_turbo_http_requests_total HTTP requests by route and status.\n",
+        );
+        output.push_str("# TYPE jetstream_turbo_http_requests_total counter\n");
+        for ((method, route), stats) in routes.iter() {
+            for (status, count) in &stats.statuses {
+                let _ = writeln!(
+                    output,
+                    "jetstream_turbo_http_requests_total{{method=\"{method}\",route=\"{route}\",status=\"{status}\"}} {count}"
+                );
+            }
+        }
+
+        output.push_str(
+            "# HELP jetstream_turbo_http_request_duration_seconds HTTP request latency by route.\n",
+        );
+        output.push_str("# TYPE jetstream_turbo_http_request_duration_seconds histogram\n");
+        for ((method, route), stats) in routes.iter() {
+            let labels = format!("method=\"{method}\",route=\"{route}\"");
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    output,
+                    "jetstream_turbo_http_request_duration_seconds_bucket{{{labels},le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                output,
+                "jetstream_turbo_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+                stats.count
+            );
+            let _ = writeln!(
+                output,
+                "jetstream_turbo_http_request_duration_seconds_sum{{{labels}}} {}",
+                stats.latency_sum
+            );
+            let _ = writeln!(
+                output,
+                "jetstream_turbo_http_request_duration_seconds_count{{{labels}}} {}",
+                stats.count
+            );
+        }
+
+        output
+    }
+}
+
+/// Middleware recording each routed request; attach with `route_layer` so
+/// unmatched paths are not counted.
+pub async fn track(State(metrics): State<RouteMetrics>, request: Request, next: Next) -> Response {
+    let Some(route) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+    else {
+        return next.run(request).await;
+    };
+    let method = request.method().clone();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    metrics.record(
+        method.as_str(),
+        &route,
+        response.status().as_u16(),
+        started.elapsed().as_secs_f64(),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::middleware;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_requests_are_labeled_by_route_template_and_status() {
+        let metrics = RouteMetrics::default();
+        let api = Router::new()
+            .route("/items/:id", get(|| async { "ok" }))
+            .route("/fail", get(|| async { StatusCode::SERVICE_UNAVAILABLE }));
+        let app = Router::new()
+            .nest("/api/v1", api)
+            .route_layer(middleware::from_fn_with_state(metrics.clone(), track));
+
+        for uri in [
+            "/api/v1/items/1",
+            "/api/v1/items/2",
+            "/api/v1/fail",
+            "/missing",
+        ] {
+            app.clone()
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+        }
+
+        let output = metrics.render();
+        assert!(output.contains(
+            "jetstream_turbo_http_requests_total{method=\"GET\",route=\"/api/v1/items/:id\",status=\"200\"} 2"
+        ));
+        assert!(output.contains(
+            "jetstream_turbo_http_requests_total{method=\"GET\",route=\"/api/v1/fail\",status=\"503\"} 1"
+        ));
+        assert!(output.contains(
+            "jetstream_turbo_http_request_duration_seconds_count{method=\"GET\",route=\"/api/v1/items/:id\"} 2"
+        ));
+        assert!(!output.contains("/missing"));
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let metrics = RouteMetrics::default();
+        metrics.record("GET", "/health", 200, 0.003);
+        metrics.record("GET", "/health", 200, 0.2);
+        metrics.record("GET", "/health", 200, 30.0);
+
+        let output = metrics.render();
+        let labels = "method=\"GET\",route=\"/health\"";
+        assert!(output.contains(&format!(
+            "jetstream_turbo_http_request_duration_seconds_bucket{{{labels},le=\"0.005\"}} 1"
+        )));
+        assert!(output.contains(&format!(
+            "jetstream_turbo_http_request_duration_seconds_bucket{{{labels},le=\"0.25\"}} 2"
+        )));
+        assert!(output.contains(&format!(
+            "jetstream_turbo_http_request_duration_seconds_bucket{{{labels},le=\"10\"}} 2"
+        )));
+        assert!(output.contains(&format!(
+            "jetstream_turbo_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} 3"
+        )));
+    }
+}
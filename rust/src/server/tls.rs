@@ -0,0 +1,154 @@
+//! TLS termination for the API server from PEM files, re-read when they change
+//! so certificate renewals (e.g. certbot or cert-manager) apply without a restart.
+
+use crate::models::errors::{TurboError, TurboResult};
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// How often the certificate and key files are checked for changes.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct TlsPaths {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+impl TlsPaths {
+    pub fn new(cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+        }
+    }
+
+    /// Server config from the PEM certificate chain and private key.
+    pub fn load(&self) -> TurboResult<Arc<ServerConfig>> {
+        let certs = read_pem(&self.cert_path, |pem| {
+            rustls_pemfile::certs(pem).collect::<Result<Vec<CertificateDer<'static>>, _>>()
+        })?;
+        if certs.is_empty() {
+            return Err(invalid_pem(&self.cert_path, "no certificates found"));
+        }
+        let key = read_pem(&self.key_path, rustls_pemfile::private_key)?
+            .ok_or_else(|| invalid_pem(&self.key_path, "no private key found"))?;
+        build_config(certs, key).map(Arc::new)
+    }
+
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|meta| meta.modified());
+        Some((
+            modified(&self.cert_path).ok()?,
+            modified(&self.key_path).ok()?,
+        ))
+    }
+}
+
+/// Loads the config and starts a task that swaps in new certificates when the
+/// files change. A failed reload keeps serving the previous certificate.
+pub fn load_with_reload(paths: TlsPaths) -> TurboResult<RustlsConfig> {
+    let config = RustlsConfig::from_config(paths.load()?);
+    let reloading = config.clone();
+    tokio::spawn(async move {
+        let mut last_modified = paths.modified();
+        let mut interval = tokio::time::interval(RELOAD_CHECK_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let modified = paths.modified();
+            if modified.is_none() || modified == last_modified {
+                continue;
+            }
+            match paths.load() {
+                Ok(server_config) => {
+                    reloading.reload_from_config(server_config);
+                    last_modified = modified;
+                    info!(
+                        "Reloaded TLS certificate from {}",
+                        paths.cert_path.display()
+                    );
+                }
+                // Retried on the next check, e.g. if only one file has been replaced so far
+                Err(e) => warn!("TLS certificate reload failed: {}", e),
+            }
+        }
+    });
+    Ok(config)
+}
+
+fn build_config(
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> TurboResult<ServerConfig> {
+    // Pick the provider explicitly so the server works without a process-wide default
+    let mut config = ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(tls_error)?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(tls_error)?;
+    // WebSocket upgrades need HTTP/1.1, so HTTP/2 isn't offered
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn read_pem<T>(
+    path: &Path,
+    parse: impl FnOnce(&mut dyn std::io::BufRead) -> std::io::Result<T>,
+) -> TurboResult<T> {
+    let contents = std::fs::read(path)?;
+    parse(&mut contents.as_slice()).map_err(|e| invalid_pem(path, &e.to_string()))
+}
+
+fn invalid_pem(path: &Path, reason: &str) -> TurboError {
+    TurboError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), reason),
+    ))
+}
+
+fn tls_error(error: rustls::Error) -> TurboError {
+    TurboError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loads_pem_files_and_rejects_mismatched_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_cert = |name: &str| {
+            let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+            let cert_path = dir.path().join(format!("{name}.crt"));
+            let key_path = dir.path().join(format!("{name}.key"));
+            std::fs::write(&cert_path, cert.cert.pem()).unwrap();
+            std::fs::write(&key_path, cert.key_pair.serialize_pem()).unwrap();
+            TlsPaths::new(cert_path, key_path)
+        };
+        let first = write_cert("first");
+        let second = write_cert("second");
+
+        let config = first.load().unwrap();
+        assert_eq!(config.alpn_protocols, vec![b"http/1.1".to_vec()]);
+        assert!(first.modified().is_some());
+
+        // A certificate paired with another certificate's key
+        let mismatched = TlsPaths::new(&first.cert_path, &second.key_path);
+        assert!(mismatched.load().is_err());
+
+        let missing_key = TlsPaths::new(&first.cert_path, &first.cert_path);
+        assert!(missing_key
+            .load()
+            .unwrap_err()
+            .to_string()
+            .contains("no private key"));
+    }
+}
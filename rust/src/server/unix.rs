@@ -0,0 +1,97 @@
+//! Serves the API on a unix domain socket, for deployments behind a local
+//! reverse proxy that shouldn't open any network port.
+
+use crate::models::errors::{TurboError, TurboResult};
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use std::future::Future;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use tokio::net::UnixListener;
+use tracing::{debug, info};
+
+/// Accepts connections until `shutdown` resolves.
+pub async fn serve(
+    path: &Path,
+    app: Router,
+    shutdown: impl Future<Output = ()>,
+) -> TurboResult<()> {
+    let listener = bind(path)?;
+    info!("Starting HTTP server on unix socket {}", path.display());
+
+    let mut shutdown = std::pin::pin!(shutdown);
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted.map_err(TurboError::Io)?,
+            _ = &mut shutdown => return Ok(()),
+        };
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Unix socket connection ended with error: {}", e);
+            }
+        });
+    }
+}
+
+/// Binds `path`, replacing a socket left behind by a previous run. Any other
+/// file at the path is left alone and binding fails.
+fn bind(path: &Path) -> TurboResult<UnixListener> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(UnixListener::bind(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixStream;
+
+    #[tokio::test]
+    async fn test_serves_requests_and_replaces_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("turbo.sock");
+        // A socket file left behind by a previous run
+        drop(UnixListener::bind(&path).unwrap());
+
+        let app = Router::new().route("/live", get(|| async { "ok" }));
+        let server_path = path.clone();
+        let drain = crate::server::drain::ConnectionDrain::new();
+        let shutdown = drain.shutdown_started();
+        let server = tokio::spawn(async move { serve(&server_path, app, shutdown).await });
+
+        let mut stream = loop {
+            match UnixStream::connect(&path).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        };
+        stream
+            .write_all(b"GET /live HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with("ok"));
+
+        drain.drain(std::time::Duration::from_secs(1)).await;
+        server.await.unwrap().unwrap();
+
+        // Regular files are never removed
+        let file = dir.path().join("not-a-socket");
+        std::fs::write(&file, "data").unwrap();
+        assert!(bind(&file).is_err());
+        assert!(file.exists());
+    }
+}
@@ -0,0 +1,132 @@
+//! Hourly counts of created records by collection, language and hashtag. They
+//! are rolled up incrementally from the `records` table into `hourly_counts`,
+//! so they outlive raw rows removed by cleanup and can be queried without
+//! scanning them.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub const HOUR_SECONDS: i64 = 3600;
+/// Hashtags kept per hour once the hour is over; the long tail is pruned.
+pub const RETAINED_HASHTAGS_PER_HOUR: u32 = 100;
+
+/// One row of `hourly_counts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HourlyCount {
+    /// Unix seconds at the start of the hour
+    pub hour: i64,
+    /// `collection`, `language` or `hashtag`
+    pub dimension: String,
+    pub value: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HashtagCount {
+    pub tag: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HourlyAggregate {
+    /// Unix seconds at the start of the hour
+    pub timestamp: i64,
+    pub records: u64,
+    pub collections: BTreeMap<String, u64>,
+    pub languages: BTreeMap<String, u64>,
+    /// Most used first
+    pub hashtags: Vec<HashtagCount>,
+}
+
+/// Merges counts from any number of databases into one aggregate per hour,
+/// oldest first, keeping the `top_hashtags` most used hashtags of each.
+pub fn merge_hourly_counts(counts: Vec<HourlyCount>, top_hashtags: usize) -> Vec<HourlyAggregate> {
+    let mut hours: BTreeMap<i64, (HourlyAggregate, BTreeMap<String, u64>)> = BTreeMap::new();
+    for count in counts {
+        let (aggregate, hashtags) = hours.entry(count.hour).or_insert_with(|| {
+            (
+                HourlyAggregate {
+                    timestamp: count.hour,
+                    ..HourlyAggregate::default()
+                },
+                BTreeMap::new(),
+            )
+        });
+        let bucket = match count.dimension.as_str() {
+            "collection" => {
+                aggregate.records += count.count;
+                &mut aggregate.collections
+            }
+            "language" => &mut aggregate.languages,
+            "hashtag" => hashtags,
+            _ => continue,
+        };
+        *bucket.entry(count.value).or_default() += count.count;
+    }
+
+    hours
+        .into_values()
+        .map(|(mut aggregate, hashtags)| {
+            let mut hashtags: Vec<HashtagCount> = hashtags
+                .into_iter()
+                .map(|(tag, count)| HashtagCount { tag, count })
+                .collect();
+            hashtags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+            hashtags.truncate(top_hashtags);
+            aggregate.hashtags = hashtags;
+            aggregate
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(hour: i64, dimension: &str, value: &str, count: u64) -> HourlyCount {
+        HourlyCount {
+            hour,
+            dimension: dimension.to_string(),
+            value: value.to_string(),
+            count,
+        }
+    }
+
+    #[test]
+    fn test_counts_from_several_databases_are_summed_per_hour() {
+        let aggregates = merge_hourly_counts(
+            vec![
+                count(7200, "collection", "app.bsky.feed.post", 5),
+                count(3600, "collection", "app.bsky.feed.like", 2),
+                count(7200, "collection", "app.bsky.feed.post", 3),
+                count(7200, "language", "en", 4),
+                count(7200, "hashtag", "rust", 2),
+                count(7200, "hashtag", "art", 3),
+                count(7200, "hashtag", "rust", 2),
+                count(7200, "hashtag", "news", 1),
+            ],
+            2,
+        );
+
+        assert_eq!(aggregates.len(), 2);
+        assert_eq!(aggregates[0].timestamp, 3600);
+        assert_eq!(aggregates[0].records, 2);
+        let latest = &aggregates[1];
+        assert_eq!(latest.records, 8);
+        assert_eq!(latest.collections["app.bsky.feed.post"], 8);
+        assert_eq!(latest.languages["en"], 4);
+        assert_eq!(
+            latest.hashtags,
+            vec![
+                HashtagCount {
+                    tag: "rust".to_string(),
+                    count: 4
+                },
+                HashtagCount {
+                    tag: "art".to_string(),
+                    count: 3
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Arrow IPC (feather v2) export of stored records. Each record becomes one row
+//! of a flat, columnar schema that polars, pandas and DataFusion can open directly.
+
+use crate::models::{
+    enriched::{EnrichedRecord, UrlEntry},
+    TurboResult,
+};
+use crate::storage::ShardedSQLiteStore;
+use arrow_array::builder::{
+    BooleanBuilder, Int64Builder, ListBuilder, StringBuilder, TimestampMicrosecondBuilder,
+    UInt32Builder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+
+/// Rows read from SQLite and written per Arrow record batch.
+pub const EXPORT_BATCH_SIZE: u32 = 10_000;
+
+fn string_list(name: &str) -> Field {
+    Field::new(
+        name,
+        DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+        false,
+    )
+}
+
+pub fn record_schema() -> SchemaRef {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("at_uri", DataType::Utf8, true),
+        Field::new("did", DataType::Utf8, false),
+        Field::new("collection", DataType::Utf8, true),
+        Field::new("time_us", DataType::Int64, true),
+        Field::new("processed_at", timestamp, false),
+        Field::new("text", DataType::Utf8, true),
+        Field::new("language", DataType::Utf8, true),
+        Field::new("author_handle", DataType::Utf8, true),
+        Field::new("author_display_name", DataType::Utf8, true),
+        Field::new("author_followers_count", DataType::UInt64, true),
+        Field::new("author_follows_count", DataType::UInt64, true),
+        Field::new("author_posts_count", DataType::UInt64, true),
+        string_list("hashtags"),
+        string_list("urls"),
+        string_list("labels"),
+        Field::new("mention_count", DataType::UInt32, false),
+        Field::new("referenced_post_count", DataType::UInt32, false),
+        Field::new("backfill", DataType::Boolean, false),
+    ]))
+}
+
+fn append_list(
+    builder: &mut ListBuilder<StringBuilder>,
+    values: impl IntoIterator<Item = impl AsRef<str>>,
+) {
+    for value in values {
+        builder.values().append_value(value);
+    }
+    builder.append(true);
+}
+
+pub fn records_to_batch(records: &[EnrichedRecord]) -> TurboResult<RecordBatch> {
+    let rows = records.len();
+    let mut at_uri = StringBuilder::with_capacity(rows, rows * 64);
+    let mut did = StringBuilder::with_capacity(rows, rows * 32);
+    let mut collection = StringBuilder::with_capacity(rows, rows * 20);
+    let mut time_us = Int64Builder::with_capacity(rows);
+    let mut processed_at = TimestampMicrosecondBuilder::with_capacity(rows).with_timezone("UTC");
+    let mut text = StringBuilder::new();
+    let mut language = StringBuilder::new();
+    let mut author_handle = StringBuilder::new();
+    let mut author_display_name = StringBuilder::new();
+    let mut author_followers_count = UInt64Builder::with_capacity(rows);
+    let mut author_follows_count = UInt64Builder::with_capacity(rows);
+    let mut author_posts_count = UInt64Builder::with_capacity(rows);
+    let mut hashtags = ListBuilder::new(StringBuilder::new());
+    let mut urls = ListBuilder::new(StringBuilder::new());
+    let mut labels = ListBuilder::new(StringBuilder::new());
+    let mut mention_count = UInt32Builder::with_capacity(rows);
+    let mut referenced_post_count = UInt32Builder::with_capacity(rows);
+    let mut backfill = BooleanBuilder::with_capacity(rows);
+
+    for record in records {
+        let metadata = &record.hydrated_metadata;
+        let author = metadata.author_profile.as_deref();
+
+        at_uri.append_option(record.get_at_uri());
+        did.append_value(record.get_did());
+        collection.append_option(
+            record
+                .message
+                .commit
+                .as_ref()
+                .and_then(|commit| commit.collection.as_deref()),
+        );
+        time_us.append_option(record.message.time_us.map(|t| t as i64));
+        processed_at.append_value(record.processed_at.timestamp_micros());
+        text.append_option(record.get_text());
+        language.append_option(metadata.detected_language.as_deref());
+        author_handle.append_option(author.map(|profile| profile.handle.as_str()));
+        author_display_name
+            .append_option(author.and_then(|profile| profile.display_name.as_deref()));
+        author_followers_count.append_option(author.and_then(|profile| profile.followers_count));
+        author_follows_count.append_option(author.and_then(|profile| profile.follows_count));
+        author_posts_count.append_option(author.and_then(|profile| profile.posts_count));
+        append_list(&mut hashtags, &metadata.hashtags);
+        append_list(&mut urls, metadata.urls.iter().map(UrlEntry::url));
+        append_list(&mut labels, &metadata.labels);
+        mention_count.append_value(metadata.mentions.len() as u32);
+        referenced_post_count.append_value(metadata.referenced_posts.len() as u32);
+        backfill.append_value(metadata.backfill);
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(at_uri.finish()),
+        Arc::new(did.finish()),
+        Arc::new(collection.finish()),
+        Arc::new(time_us.finish()),
+        Arc::new(processed_at.finish()),
+        Arc::new(text.finish()),
+        Arc::new(language.finish()),
+        Arc::new(author_handle.finish()),
+        Arc::new(author_display_name.finish()),
+        Arc::new(author_followers_count.finish()),
+        Arc::new(author_follows_count.finish()),
+        Arc::new(author_posts_count.finish()),
+        Arc::new(hashtags.finish()),
+        Arc::new(urls.finish()),
+        Arc::new(labels.finish()),
+        Arc::new(mention_count.finish()),
+        Arc::new(referenced_post_count.finish()),
+        Arc::new(backfill.finish()),
+    ];
+    Ok(RecordBatch::try_new(record_schema(), columns)?)
+}
+
+pub struct ArrowIpcWriter {
+    writer: FileWriter<BufWriter<File>>,
+    rows: u64,
+}
+
+impl ArrowIpcWriter {
+    pub fn create<P: AsRef<Path>>(path: P) -> TurboResult<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Self {
+            writer: FileWriter::try_new(file, &record_schema())?,
+            rows: 0,
+        })
+    }
+
+    pub fn write_records(&mut self, records: &[EnrichedRecord]) -> TurboResult<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.writer.write(&records_to_batch(records)?)?;
+        self.rows += records.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the file footer and returns the number of rows exported.
+    pub fn finish(mut self) -> TurboResult<u64> {
+        self.writer.finish()?;
+        Ok(self.rows)
+    }
+}
+
+/// Exports every live record in every shard to a single Arrow IPC file.
+pub async fn export_records<P: AsRef<Path>>(
+    store: &ShardedSQLiteStore,
+    path: P,
+) -> TurboResult<u64> {
+    let mut writer = ArrowIpcWriter::create(path.as_ref())?;
+
+    for shard in store.shards() {
+        let mut after_id = 0;
+        loop {
+            let page = shard.records_after(after_id, EXPORT_BATCH_SIZE).await?;
+            let Some((last_id, _)) = page.last() else {
+                break;
+            };
+            after_id = *last_id;
+            let records: Vec<EnrichedRecord> = page.into_iter().map(|(_, record)| record).collect();
+            writer.write_records(&records)?;
+        }
+    }
+
+    let rows = writer.finish()?;
+    info!("Exported {} records to {}", rows, path.as_ref().display());
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{RecordStore, SQLitePragmaConfig};
+    use crate::testing::fixtures::{create_message_batch, create_profile};
+    use arrow_array::{Array, ListArray, StringArray};
+    use arrow_ipc::reader::FileReader;
+
+    #[tokio::test]
+    async fn test_export_writes_readable_ipc_file_across_shards() {
+        let dir = std::env::temp_dir().join(format!("test_arrow_{}", uuid::Uuid::new_v4()));
+        let store = ShardedSQLiteStore::new(
+            dir.join("jetstream.db"),
+            2,
+            SQLitePragmaConfig {
+                cache_size_kib: 1024,
+                mmap_size_mb: 0,
+                journal_size_limit_mb: 64,
+            },
+        )
+        .await
+        .unwrap();
+
+        let records: Vec<Arc<EnrichedRecord>> = create_message_batch(5)
+            .into_iter()
+            .map(|message| {
+                let mut record = EnrichedRecord::new(message);
+                record.hydrated_metadata.author_profile =
+                    Some(Arc::new(create_profile(record.get_did())));
+                record.hydrated_metadata.hashtags = vec!["rust".to_string()];
+                Arc::new(record)
+            })
+            .collect();
+        store.store_batch(&records).await.unwrap();
+
+        let path = dir.join("records.arrow");
+        assert_eq!(export_records(&store, &path).await.unwrap(), 5);
+
+        let reader = FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        assert_eq!(reader.schema(), record_schema());
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 5);
+
+        let batch = &batches[0];
+        let handles = batch
+            .column_by_name("author_handle")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(handles.null_count(), 0);
+        let hashtags = batch
+            .column_by_name("hashtags")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<ListArray>()
+            .unwrap();
+        assert_eq!(hashtags.value_length(0), 1);
+
+        store.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
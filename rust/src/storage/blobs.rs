@@ -0,0 +1,363 @@
+//! Mirrors avatars and post images referenced by hydrated records into object
+//! storage under content-addressed keys, so archived records keep their media
+//! after the original blobs are deleted. Downloads run in the background so
+//! hydration never waits on them; records are pointed at a blob once it has
+//! been mirrored.
+
+use crate::client::http::{client_builder, DEFAULT_USER_AGENT};
+use crate::models::{enriched::EnrichedRecord, errors::TurboError, TurboResult};
+use metrics::counter;
+use moka::sync::Cache as MokaCache;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::{Attribute, Attributes, ObjectStore, PutOptions, PutPayload};
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::{trace, warn};
+
+/// Blobs larger than this are left unmirrored.
+pub const MAX_BLOB_BYTES: usize = 10 * 1024 * 1024;
+const BLOB_CDN_URL: &str = "https://cdn.bsky.app/img/feed_fullsize/plain";
+const CONCURRENT_BLOB_DOWNLOADS: usize = 8;
+/// Blobs queued or downloading at once; new ones past this are skipped until a
+/// later record references them again.
+const MAX_PENDING_BLOBS: usize = 1_000;
+const MIRRORED_URL_CACHE_SIZE: u64 = 100_000;
+
+/// Where blobs go and how mirrored URLs are written.
+#[derive(Debug, Clone)]
+pub struct BlobMirrorConfig {
+    pub bucket: String,
+    pub region: Option<String>,
+    /// Custom S3-compatible endpoint, e.g. MinIO or R2
+    pub endpoint: Option<String>,
+    /// Base URL the bucket is served from; defaults to `s3://{bucket}`
+    pub public_url: Option<String>,
+    pub key_prefix: String,
+}
+
+pub struct BlobMirror {
+    store: Arc<dyn ObjectStore>,
+    http_client: Client,
+    public_url: String,
+    key_prefix: String,
+    /// Source URL -> mirrored URL, so repeat avatars aren't downloaded again
+    mirrored: MokaCache<String, Arc<str>>,
+    /// Source URLs queued or downloading, so each is only queued once
+    pending: Mutex<HashSet<String>>,
+    downloads: Semaphore,
+}
+
+impl BlobMirror {
+    /// An S3 mirror. Credentials come from the standard `AWS_*` environment variables.
+    pub fn s3(config: &BlobMirrorConfig) -> TurboResult<Self> {
+        let mut builder = AmazonS3Builder::from_env().with_bucket_name(&config.bucket);
+        if let Some(region) = &config.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+        let public_url = config
+            .public_url
+            .clone()
+            .unwrap_or_else(|| format!("s3://{}", config.bucket));
+
+        Self::new(
+            Arc::new(builder.build()?),
+            public_url,
+            config.key_prefix.clone(),
+        )
+    }
+
+    pub fn new(
+        store: Arc<dyn ObjectStore>,
+        public_url: String,
+        key_prefix: String,
+    ) -> TurboResult<Self> {
+        Ok(Self {
+            store,
+            http_client: Self::http_client(DEFAULT_USER_AGENT)?,
+            public_url: public_url.trim_end_matches('/').to_string(),
+            key_prefix: key_prefix.trim_matches('/').to_string(),
+            mirrored: MokaCache::builder()
+                .max_capacity(MIRRORED_URL_CACHE_SIZE)
+                .build(),
+            pending: Mutex::new(HashSet::new()),
+            downloads: Semaphore::new(CONCURRENT_BLOB_DOWNLOADS),
+        })
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> TurboResult<Self> {
+        self.http_client = Self::http_client(user_agent)?;
+        Ok(self)
+    }
+
+    fn http_client(user_agent: &str) -> TurboResult<Client> {
+        Ok(client_builder(user_agent)
+            .timeout(Duration::from_secs(30))
+            .build()?)
+    }
+
+    /// Content-addressed key for a blob: `{prefix}/{sha256[..2]}/{sha256}`.
+    pub fn blob_key(&self, bytes: &[u8]) -> String {
+        let digest = hex(&Sha256::digest(bytes));
+        let key = format!("{}/{}", &digest[..2], digest);
+        if self.key_prefix.is_empty() {
+            key
+        } else {
+            format!("{}/{}", self.key_prefix, key)
+        }
+    }
+
+    /// Downloads `url` and stores it, returning the mirrored URL.
+    pub async fn mirror(&self, url: &str) -> TurboResult<Arc<str>> {
+        if let Some(mirrored) = self.mirrored.get(url) {
+            return Ok(mirrored);
+        }
+
+        let response = self.http_client.get(url).send().await?.error_for_status()?;
+        if response
+            .content_length()
+            .is_some_and(|len| len > MAX_BLOB_BYTES as u64)
+        {
+            return Err(TurboError::InvalidApiResponse(format!(
+                "Blob at {url} exceeds {MAX_BLOB_BYTES} bytes"
+            )));
+        }
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let bytes = response.bytes().await?;
+        if bytes.len() > MAX_BLOB_BYTES {
+            return Err(TurboError::InvalidApiResponse(format!(
+                "Blob at {url} exceeds {MAX_BLOB_BYTES} bytes"
+            )));
+        }
+
+        let key = self.blob_key(&bytes);
+        let mut attributes = Attributes::new();
+        if let Some(content_type) = content_type {
+            attributes.insert(Attribute::ContentType, content_type.into());
+        }
+        self.store
+            .put_opts(
+                &ObjectPath::from(key.as_str()),
+                PutPayload::from_bytes(bytes),
+                PutOptions {
+                    attributes,
+                    ..PutOptions::default()
+                },
+            )
+            .await?;
+
+        let mirrored: Arc<str> = Arc::from(format!("{}/{}", self.public_url, key));
+        trace!("Mirrored {} to {}", url, mirrored);
+        self.mirrored.insert(url.to_string(), Arc::clone(&mirrored));
+        Ok(mirrored)
+    }
+
+    /// Rewrites profile avatars in `records` to their mirrored URLs and records
+    /// every mapping in `mirrored_blobs`, for blobs mirrored earlier. The others
+    /// are queued for a background download and mapped on records seen after it
+    /// finishes; failed downloads are logged and tried again on a later record.
+    /// Returns the number of blobs mapped.
+    pub fn mirror_records(self: &Arc<Self>, records: &mut [EnrichedRecord]) -> usize {
+        let urls: BTreeSet<String> = records.iter().flat_map(blob_urls).collect();
+        if urls.is_empty() {
+            return 0;
+        }
+
+        let mut mirrored: HashMap<String, Arc<str>> = HashMap::new();
+        for url in urls {
+            match self.mirrored.get(&url) {
+                Some(target) => {
+                    mirrored.insert(url, target);
+                }
+                None => self.enqueue(url),
+            }
+        }
+
+        for record in records.iter_mut() {
+            for url in blob_urls(record) {
+                if let Some(target) = mirrored.get(&url) {
+                    record
+                        .hydrated_metadata
+                        .mirrored_blobs
+                        .insert(url, target.to_string());
+                }
+            }
+
+            let metadata = &mut record.hydrated_metadata;
+            for profile in metadata
+                .author_profile
+                .iter_mut()
+                .chain(metadata.mentioned_profiles.iter_mut())
+            {
+                if let Some(target) = profile.avatar.as_ref().and_then(|url| mirrored.get(url)) {
+                    Arc::make_mut(profile).avatar = Some(target.to_string());
+                }
+            }
+        }
+
+        mirrored.len()
+    }
+
+    fn enqueue(self: &Arc<Self>, url: String) {
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if pending.contains(&url) {
+                return;
+            }
+            if pending.len() >= MAX_PENDING_BLOBS {
+                counter!("jetstream_turbo_blobs_mirrored_total", "outcome" => "skipped")
+                    .increment(1);
+                return;
+            }
+            pending.insert(url.clone());
+        }
+
+        let mirror = Arc::clone(self);
+        tokio::spawn(async move {
+            let _download = mirror.downloads.acquire().await;
+            match mirror.mirror(&url).await {
+                Ok(_) => {
+                    counter!("jetstream_turbo_blobs_mirrored_total", "outcome" => "success")
+                        .increment(1);
+                }
+                Err(e) => {
+                    counter!("jetstream_turbo_blobs_mirrored_total", "outcome" => "failure")
+                        .increment(1);
+                    warn!("Failed to mirror blob {}: {}", url, e);
+                }
+            }
+            mirror
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&url);
+        });
+    }
+
+    /// Whether any blob is still queued or downloading.
+    pub fn has_pending(&self) -> bool {
+        !self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+}
+
+/// Avatar and image URLs referenced by a record.
+fn blob_urls(record: &EnrichedRecord) -> Vec<String> {
+    let metadata = &record.hydrated_metadata;
+    let mut urls: Vec<String> = metadata
+        .author_profile
+        .iter()
+        .chain(&metadata.mentioned_profiles)
+        .filter_map(|profile| profile.avatar.clone())
+        .collect();
+
+    let did = record.get_did();
+    urls.extend(
+        record
+            .message
+            .extract_image_blob_cids()
+            .into_iter()
+            .map(|cid| format!("{BLOB_CDN_URL}/{did}/{cid}@jpeg")),
+    );
+    urls
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::{create_post_message, create_profile};
+    use object_store::memory::InMemory;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_mirrors_avatars_under_content_addressed_keys() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/avatar.jpg"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "image/jpeg")
+                    .set_body_bytes(b"jpeg bytes".to_vec()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let store = Arc::new(InMemory::new());
+        let mirror = Arc::new(
+            BlobMirror::new(
+                store.clone(),
+                "https://media.example.com/".to_string(),
+                "blobs".to_string(),
+            )
+            .unwrap(),
+        );
+
+        let avatar = format!("{}/avatar.jpg", server.uri());
+        let mut records: Vec<EnrichedRecord> = (1..=2)
+            .map(|i| {
+                let mut record = EnrichedRecord::new(create_post_message(i));
+                let mut profile = create_profile(record.get_did());
+                profile.avatar = Some(avatar.clone());
+                record.hydrated_metadata.author_profile = Some(Arc::new(profile));
+                record
+            })
+            .collect();
+
+        // The first sighting only queues the download
+        assert_eq!(mirror.mirror_records(&mut records), 0);
+        assert!(records[0].hydrated_metadata.mirrored_blobs.is_empty());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while mirror.has_pending() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(mirror.mirror_records(&mut records), 1);
+
+        let key = mirror.blob_key(b"jpeg bytes");
+        assert!(key.starts_with("blobs/"));
+        let expected = format!("https://media.example.com/{key}");
+        for record in &records {
+            let metadata = &record.hydrated_metadata;
+            assert_eq!(
+                metadata.author_profile.as_ref().unwrap().avatar.as_deref(),
+                Some(expected.as_str())
+            );
+            assert_eq!(metadata.mirrored_blobs[&avatar], expected);
+        }
+
+        let stored = store.get(&ObjectPath::from(key.as_str())).await.unwrap();
+        assert_eq!(
+            stored
+                .attributes
+                .get(&Attribute::ContentType)
+                .map(|v| v.as_ref()),
+            Some("image/jpeg")
+        );
+        assert_eq!(stored.bytes().await.unwrap().as_ref(), b"jpeg bytes");
+    }
+}
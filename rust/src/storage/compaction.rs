@@ -0,0 +1,444 @@
+//! Merges rotated `jetstream_{unix}.db` files into one archive per UTC day under
+//! `{db_dir}/archives`, deduplicating and vacuuming as it goes. Each archive is
+//! built as `<name>.part` and renamed into place once finished, so readers
+//! never open a half-merged one. Local archives
+//! stay uncompressed so they can be queried; with the `s3` feature they can be
+//! uploaded to object storage, optionally zstd-compressed, and restored from it
+//! (see `restore`).
+
+use crate::models::TurboResult;
+use crate::storage::partitions::partition_start_us;
+use crate::storage::sqlite::remove_database_and_sidecars;
+use crate::storage::{DatabaseRotator, PartitionedReader, SQLitePragmaConfig, SQLiteStore};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, trace};
+
+pub const ARCHIVE_DIR_NAME: &str = "archives";
+#[cfg(feature = "s3")]
+const ARCHIVE_ZSTD_LEVEL: i32 = 19;
+const ARCHIVE_FILE_PREFIX: &str = "jetstream_archive_";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompactionReport {
+    pub days: usize,
+    pub source_files: usize,
+    pub records_merged: u64,
+    pub archives: Vec<PathBuf>,
+}
+
+pub struct ArchiveCompactor {
+    db_dir: PathBuf,
+    archive_dir: PathBuf,
+    pragma_config: SQLitePragmaConfig,
+    reader: Option<Arc<PartitionedReader>>,
+    #[cfg(feature = "s3")]
+    compress: bool,
+    #[cfg(feature = "s3")]
+    upload: Option<(Arc<dyn object_store::ObjectStore>, String)>,
+}
+
+impl ArchiveCompactor {
+    pub fn new<P: AsRef<Path>>(db_dir: P, pragma_config: SQLitePragmaConfig) -> Self {
+        let db_dir = db_dir.as_ref().to_path_buf();
+        Self {
+            archive_dir: db_dir.join(ARCHIVE_DIR_NAME),
+            db_dir,
+            pragma_config,
+            reader: None,
+            #[cfg(feature = "s3")]
+            compress: false,
+            #[cfg(feature = "s3")]
+            upload: None,
+        }
+    }
+
+    /// Hands merged rotated files to `reader` for removal, so it never reads
+    /// one that is being deleted, and attaches each finished archive to it.
+    pub fn with_reader(mut self, reader: Arc<PartitionedReader>) -> Self {
+        self.reader = Some(reader);
+        self
+    }
+
+    /// Uploads each finished archive as a `.db.zst` copy.
+    #[cfg(feature = "s3")]
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Uploads each finished archive to `store` under `{prefix}/{file name}`.
+    #[cfg(feature = "s3")]
+    pub fn with_upload(
+        mut self,
+        store: Arc<dyn object_store::ObjectStore>,
+        prefix: String,
+    ) -> Self {
+        self.upload = Some((store, prefix.trim_matches('/').to_string()));
+        self
+    }
+
+    pub fn archive_dir(&self) -> &Path {
+        &self.archive_dir
+    }
+
+    /// Compacts every rotated file from days before `today`. Files from `today`
+    /// are left alone since rotation may still be adding to that day.
+    pub async fn compact(&self, today: NaiveDate) -> TurboResult<CompactionReport> {
+        let mut days: BTreeMap<NaiveDate, Vec<PathBuf>> = BTreeMap::new();
+        for (name, path) in DatabaseRotator::list_databases(&self.db_dir).await? {
+            let Some(day) = partition_start_us(&name)
+                .and_then(DateTime::<Utc>::from_timestamp_micros)
+                .map(|started_at| started_at.date_naive())
+            else {
+                continue;
+            };
+            if day < today {
+                days.entry(day).or_default().push(path);
+            }
+        }
+
+        let mut report = CompactionReport::default();
+        for (day, mut files) in days {
+            files.sort();
+            let (archive, merged) = self.compact_day(day, &files).await?;
+            report.days += 1;
+            report.source_files += files.len();
+            report.records_merged += merged;
+            report.archives.push(archive);
+        }
+        Ok(report)
+    }
+
+    async fn compact_day(&self, day: NaiveDate, files: &[PathBuf]) -> TurboResult<(PathBuf, u64)> {
+        tokio::fs::create_dir_all(&self.archive_dir).await?;
+        let archive_path = self.archive_dir.join(archive_file_name(day));
+        let compressed_path = compressed_path(&archive_path);
+        let part_path = part_path(&archive_path);
+        // Left behind by a run that stopped mid-merge
+        remove_database_and_sidecars(&part_path).await?;
+
+        // Files rotated in late for an already-archived day are merged into a
+        // copy of its archive
+        if tokio::fs::metadata(&archive_path).await.is_ok() {
+            tokio::fs::copy(&archive_path, &part_path).await?;
+        } else if tokio::fs::metadata(&compressed_path).await.is_ok() {
+            let (from, to) = (compressed_path.clone(), part_path.clone());
+            tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+                zstd::stream::copy_decode(File::open(from)?, File::create(to)?)
+            })
+            .await??;
+        }
+
+        let merged = match self.merge_into(&part_path, files).await {
+            Ok(merged) => merged,
+            Err(e) => {
+                let _ = remove_database_and_sidecars(&part_path).await;
+                return Err(e);
+            }
+        };
+
+        match &self.reader {
+            Some(reader) => {
+                reader
+                    .install_archive(&part_path, &archive_path, files)
+                    .await?
+            }
+            None => {
+                tokio::fs::rename(&part_path, &archive_path).await?;
+                for file in files {
+                    DatabaseRotator::remove_database_files(file).await?;
+                }
+            }
+        }
+        // Archives compressed locally by earlier releases are kept uncompressed
+        if tokio::fs::metadata(&compressed_path).await.is_ok() {
+            tokio::fs::remove_file(&compressed_path).await?;
+        }
+
+        #[cfg(feature = "s3")]
+        if let Some((store, prefix)) = &self.upload {
+            if self.compress {
+                let (from, to) = (archive_path.clone(), compressed_path.clone());
+                tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+                    zstd::stream::copy_encode(
+                        File::open(from)?,
+                        File::create(to)?,
+                        ARCHIVE_ZSTD_LEVEL,
+                    )
+                })
+                .await??;
+                let uploaded = upload_archive(store.as_ref(), prefix, &compressed_path).await;
+                tokio::fs::remove_file(&compressed_path).await?;
+                uploaded?;
+            } else {
+                upload_archive(store.as_ref(), prefix, &archive_path).await?;
+            }
+        }
+
+        info!(
+            "Compacted {} rotated databases for {} into {} ({} records)",
+            files.len(),
+            day,
+            archive_path.display(),
+            merged
+        );
+        Ok((archive_path, merged))
+    }
+
+    /// Merges `files` into the archive being built at `part_path`, then vacuums
+    /// and checkpoints it so the single file is the whole archive.
+    async fn merge_into(&self, part_path: &Path, files: &[PathBuf]) -> TurboResult<u64> {
+        let archive = SQLiteStore::new(part_path, self.pragma_config).await?;
+        let merged = async {
+            let mut merged = 0;
+            for file in files {
+                merged += archive.merge_from(file).await?;
+                trace!("Merged {} into {}", file.display(), part_path.display());
+            }
+            archive.vacuum().await?;
+            archive.checkpoint().await?;
+            Ok(merged)
+        }
+        .await;
+        archive.close().await?;
+        merged
+    }
+
+    /// Compacts once every `interval`, starting after the first interval.
+    pub fn start_compaction_task(
+        self: Arc<Self>,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        info!("Started archive compaction task (every {:?})", interval);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match self.compact(Utc::now().date_naive()).await {
+                    Ok(report) if report.days > 0 => info!(
+                        "Archive compaction: {} days, {} files, {} records",
+                        report.days, report.source_files, report.records_merged
+                    ),
+                    Ok(_) => trace!("Archive compaction: nothing to compact"),
+                    Err(e) => error!("Archive compaction failed: {}", e),
+                }
+            }
+        })
+    }
+}
+
+/// `jetstream_archive_2024-01-01.db`
+pub(crate) fn archive_file_name(day: NaiveDate) -> String {
+    format!("{ARCHIVE_FILE_PREFIX}{day}.db")
+}
+
+/// The UTC day an archive file holds, compressed or not.
+pub(crate) fn archive_day(file_name: &str) -> Option<NaiveDate> {
+    let rest = file_name.strip_prefix(ARCHIVE_FILE_PREFIX)?;
+    let (day, extension) = rest.split_once('.')?;
+    if !matches!(extension, "db" | "db.zst") {
+        return None;
+    }
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()
+}
+
+fn compressed_path(archive_path: &Path) -> PathBuf {
+    let mut path = archive_path.as_os_str().to_owned();
+    path.push(".zst");
+    PathBuf::from(path)
+}
+
+/// Where the archive is built before it's renamed into place.
+fn part_path(archive_path: &Path) -> PathBuf {
+    let mut path = archive_path.as_os_str().to_owned();
+    path.push(".part");
+    PathBuf::from(path)
+}
+
+/// The S3 bucket archives are uploaded to and restored from, with credentials
+/// and region from the standard `AWS_*` variables.
+#[cfg(feature = "s3")]
+pub fn s3_archive_store(bucket: &str) -> TurboResult<Arc<dyn object_store::ObjectStore>> {
+    Ok(Arc::new(
+        object_store::aws::AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()?,
+    ))
+}
+
+/// Where an archive named `file_name` is kept under `prefix`.
+#[cfg(feature = "s3")]
+pub(crate) fn archive_location(prefix: &str, file_name: &str) -> object_store::path::Path {
+    if prefix.is_empty() {
+        object_store::path::Path::from(file_name)
+    } else {
+        object_store::path::Path::from(format!("{prefix}/{file_name}"))
+    }
+}
+
+#[cfg(feature = "s3")]
+async fn upload_archive(
+    store: &dyn object_store::ObjectStore,
+    prefix: &str,
+    path: &Path,
+) -> TurboResult<()> {
+    use tokio::io::AsyncReadExt;
+
+    const UPLOAD_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let location = archive_location(prefix, &file_name);
+
+    let mut writer = object_store::WriteMultipart::new(store.put_multipart(&location).await?);
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = vec![0; UPLOAD_CHUNK_BYTES];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        writer.wait_for_capacity(4).await?;
+        writer.write(&buffer[..read]);
+    }
+    writer.finish().await?;
+
+    info!("Uploaded {} to {}", path.display(), location);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::enriched::EnrichedRecord;
+    use crate::storage::{RecordStore, ShardedSQLiteStore};
+    use crate::testing::fixtures::create_post_message;
+
+    const PRAGMAS: SQLitePragmaConfig = SQLitePragmaConfig {
+        cache_size_kib: 1024,
+        mmap_size_mb: 0,
+        journal_size_limit_mb: 64,
+    };
+
+    #[tokio::test]
+    async fn test_compacts_past_days_into_deduplicated_archives() {
+        let dir = std::env::temp_dir().join(format!("test_compaction_{}", uuid::Uuid::new_v4()));
+        let live = Arc::new(
+            ShardedSQLiteStore::new(dir.join("jetstream.db"), 1, PRAGMAS)
+                .await
+                .unwrap(),
+        );
+
+        // 2024-01-01 00:00 and 01:00 UTC overlap on one record; 2024-01-02 is "today"
+        let hourly = [
+            (1704067200, vec![1, 2]),
+            (1704070800, vec![2, 3]),
+            (1704153600, vec![4]),
+        ];
+        for (started_at, indices) in &hourly {
+            let store = SQLiteStore::new(dir.join(format!("jetstream_{started_at}.db")), PRAGMAS)
+                .await
+                .unwrap();
+            let records: Vec<Arc<EnrichedRecord>> = indices
+                .iter()
+                .map(|&i| Arc::new(EnrichedRecord::new(create_post_message(i))))
+                .collect();
+            store.store_batch(&records).await.unwrap();
+            store.close().await.unwrap();
+        }
+        // The first hour was rotated out by a release without the newer columns
+        let pool = sqlx::SqlitePool::connect(&format!(
+            "sqlite://{}",
+            dir.join("jetstream_1704067200.db").display()
+        ))
+        .await
+        .unwrap();
+        sqlx::query(
+            "ALTER TABLE records DROP COLUMN deleted_at;
+             ALTER TABLE records DROP COLUMN schema_version;
+             PRAGMA user_version = 0;",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+
+        let reader = Arc::new(PartitionedReader::new(live.clone(), &dir));
+        assert_eq!(reader.refresh().await.unwrap(), 3);
+
+        let compactor = ArchiveCompactor::new(&dir, PRAGMAS).with_reader(reader.clone());
+        // Left behind by a compaction that stopped mid-merge
+        std::fs::create_dir_all(compactor.archive_dir()).unwrap();
+        let part_path = compactor
+            .archive_dir()
+            .join("jetstream_archive_2024-01-01.db.part");
+        std::fs::write(&part_path, b"half-merged").unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let report = compactor.compact(today).await.unwrap();
+        assert_eq!(report.days, 1);
+        assert_eq!(report.source_files, 2);
+        assert_eq!(report.records_merged, 3);
+
+        let archive_path = compactor
+            .archive_dir()
+            .join("jetstream_archive_2024-01-01.db");
+        assert_eq!(report.archives, vec![archive_path.clone()]);
+        assert!(!part_path.exists());
+        assert!(!dir.join("jetstream_1704067200.db").exists());
+        assert!(dir.join("jetstream_1704153600.db").exists());
+
+        // The reader swapped the merged files for the archive
+        assert_eq!(reader.partition_count().await, 2);
+        for i in 1..=4 {
+            let message = create_post_message(i);
+            let uri = EnrichedRecord::new(message).get_at_uri().unwrap();
+            assert!(reader.get_record_by_uri(&uri).await.unwrap().is_some());
+        }
+
+        // A file from the archived day rotated out late hides the archive until
+        // it's merged in, since it could already be part of it
+        let late = SQLiteStore::new(dir.join("jetstream_1704074400.db"), PRAGMAS)
+            .await
+            .unwrap();
+        late.store_batch(&[Arc::new(EnrichedRecord::new(create_post_message(5)))])
+            .await
+            .unwrap();
+        late.close().await.unwrap();
+        assert_eq!(reader.refresh().await.unwrap(), 2);
+        let report = compactor.compact(today).await.unwrap();
+        assert_eq!((report.source_files, report.records_merged), (1, 1));
+        assert_eq!(reader.partition_count().await, 2);
+        for i in 1..=5 {
+            let message = create_post_message(i);
+            let uri = EnrichedRecord::new(message).get_at_uri().unwrap();
+            assert!(reader.get_record_by_uri(&uri).await.unwrap().is_some());
+        }
+
+        let archive = SQLiteStore::open_read_only(&archive_path).await.unwrap();
+        assert_eq!(archive.count_records().await.unwrap(), 4);
+        archive.close().await.unwrap();
+        live.close().await.unwrap();
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_archive_file_names_round_trip() {
+        let day = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert_eq!(archive_file_name(day), "jetstream_archive_2024-01-01.db");
+        assert_eq!(archive_day(&archive_file_name(day)), Some(day));
+        assert_eq!(
+            archive_day("jetstream_archive_2024-01-01.db.zst"),
+            Some(day)
+        );
+        assert_eq!(archive_day("jetstream_archive_2024-01-01.db.part"), None);
+        assert_eq!(archive_day("jetstream_1704067200.db"), None);
+    }
+}
@@ -0,0 +1,135 @@
+//! Appends enriched records to a newline-delimited JSON file. In standalone
+//! mode this takes the place of the Redis stream, so a local run leaves
+//! something to `tail -f` or load afterwards. With `--output stdout` the same
+//! lines go to stdout instead, for piping into `jq` and friends.
+
+use crate::models::{enriched::EnrichedRecord, errors::TurboResult};
+use crate::storage::publisher::EventPublisher;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tracing::{info, trace};
+
+/// The `STANDALONE_OUTPUT_FILE` value that writes to stdout.
+pub const STDOUT_PATH: &str = "-";
+
+type Writer = Box<dyn AsyncWrite + Send + Sync + Unpin>;
+
+pub struct FileSink {
+    path: PathBuf,
+    /// The file and the number of lines written to it by this process
+    file: Mutex<(Writer, u64)>,
+    publish_deletes: bool,
+}
+
+impl FileSink {
+    /// Opens `path` for appending, creating it and its directory if needed.
+    /// [`STDOUT_PATH`] writes to stdout.
+    pub fn open<P: AsRef<Path>>(path: P) -> TurboResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.as_os_str() == STDOUT_PATH {
+            return Ok(Self::stdout());
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        info!("Appending records to {}", path.display());
+        Ok(Self::with_writer(path, Box::new(File::from_std(file))))
+    }
+
+    pub fn stdout() -> Self {
+        info!("Writing records to stdout");
+        Self::with_writer(PathBuf::from(STDOUT_PATH), Box::new(tokio::io::stdout()))
+    }
+
+    fn with_writer(path: PathBuf, writer: Writer) -> Self {
+        Self {
+            path,
+            file: Mutex::new((writer, 0)),
+            publish_deletes: true,
+        }
+    }
+
+    /// Whether delete events are written. Each line's `event` field tells
+    /// readers whether it's a record or the deletion of one.
+    pub fn with_publish_deletes(mut self, publish_deletes: bool) -> Self {
+        self.publish_deletes = publish_deletes;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl EventPublisher for FileSink {
+    /// Returns the line number each record was written at, counted from this
+    /// process's first write.
+    async fn publish_batch(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<Vec<String>> {
+        let mut buffer = Vec::new();
+        let mut written = 0;
+        for record in records {
+            if !self.publish_deletes && record.is_delete() {
+                continue;
+            }
+            serde_json::to_writer(&mut buffer, record.as_ref())?;
+            buffer.push(b'\n');
+            written += 1;
+        }
+        if written == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut guard = self.file.lock().await;
+        let (file, lines) = &mut *guard;
+        file.write_all(&buffer).await?;
+        file.flush().await?;
+        let ids = (*lines + 1..=*lines + written)
+            .map(|line| line.to_string())
+            .collect();
+        *lines += written;
+        trace!("Appended {} records to {}", written, self.path.display());
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::{create_delete_message, create_post_message};
+
+    #[tokio::test]
+    async fn test_records_are_appended_as_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out").join("records.ndjson");
+        let sink = FileSink::open(&path).unwrap().with_publish_deletes(false);
+
+        let records = [
+            Arc::new(EnrichedRecord::new(create_post_message(1))),
+            Arc::new(EnrichedRecord::new(create_delete_message(1))),
+            Arc::new(EnrichedRecord::new(create_post_message(2))),
+        ];
+        assert_eq!(sink.publish_batch(&records[..2]).await.unwrap(), vec!["1"]);
+        assert_eq!(sink.publish_batch(&records[2..]).await.unwrap(), vec!["2"]);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<EnrichedRecord> = contents
+            .lines()
+            .map(|line| EnrichedRecord::from_json_value(serde_json::from_str(line).unwrap()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].get_text(), records[2].get_text());
+    }
+
+    #[test]
+    fn test_dash_writes_to_stdout_instead_of_a_file() {
+        let sink = FileSink::open(STDOUT_PATH).unwrap();
+        assert_eq!(sink.path(), Path::new(STDOUT_PATH));
+        assert!(!Path::new(STDOUT_PATH).exists());
+    }
+}
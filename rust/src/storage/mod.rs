@@ -1,7 +1,39 @@
+pub mod aggregates;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "s3")]
+pub mod blobs;
+pub mod compaction;
+pub mod file_sink;
+pub mod partitions;
+pub mod projection;
+pub mod publisher;
+#[cfg(feature = "redis")]
 pub mod redis;
+#[cfg(feature = "s3")]
+pub mod restore;
 pub mod rotation;
+pub mod sharded;
+pub mod snapshot;
 pub mod sqlite;
+pub mod threads;
 
-pub use redis::{EventPublisher, RedisStore};
+pub use aggregates::{merge_hourly_counts, HashtagCount, HourlyAggregate, HourlyCount};
+#[cfg(feature = "arrow")]
+pub use arrow::{export_records, ArrowIpcWriter};
+#[cfg(feature = "s3")]
+pub use blobs::{BlobMirror, BlobMirrorConfig};
+pub use compaction::{ArchiveCompactor, CompactionReport};
+pub use file_sink::FileSink;
+pub use partitions::PartitionedReader;
+pub use projection::{ProjectionPart, RecordProjection};
+pub use publisher::{EventPublisher, LiveSink, PayloadEncoding};
+#[cfg(feature = "redis")]
+pub use redis::{RedisStore, StreamEntry};
+#[cfg(feature = "s3")]
+pub use restore::{restore_archives, RestoreReport};
 pub use rotation::DatabaseRotator;
-pub use sqlite::{RecordStore, SQLitePragmaConfig, SQLiteStore};
+pub use sharded::ShardedSQLiteStore;
+pub use snapshot::export_snapshot;
+pub use sqlite::{DeleteMode, RecordStore, SQLitePragmaConfig, SQLiteStore};
+pub use threads::{Thread, ThreadEntry, ThreadNode, MAX_THREAD_POSTS};
@@ -0,0 +1,580 @@
+//! Reads across the live store and the rotated-out `jetstream_{unix}.db` files
+//! kept next to it, so lookups still find records written before the last
+//! rotation. Uncompressed daily archives in `archives/`, whether compacted here
+//! or restored from object storage, are read the same way, except while rotated
+//! files from the archive's day remain: those may already be merged into it,
+//! so the day is read from the rotated files until compaction removes them.
+//! Files are rescanned periodically and opened read-only; one is reopened
+//! writable only once a delete or account removal targets records stored in it.
+
+use crate::models::{enriched::EnrichedRecord, TurboResult};
+use crate::storage::aggregates::HourlyCount;
+use crate::storage::compaction::{archive_day, ARCHIVE_DIR_NAME};
+use crate::storage::sharded::newest_first;
+use crate::storage::threads::{Thread, MAX_THREAD_POSTS};
+use crate::storage::{DatabaseRotator, DeleteMode, SQLiteStore, ShardedSQLiteStore};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::future::try_join_all;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{OnceCell, RwLock};
+use tracing::{info, warn};
+
+/// How long a directory scan is trusted before lookups rescan for new rotations.
+const PARTITION_RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// One rotated-out database or daily archive. Rotation names files after the
+/// second they were started and archives after their UTC day, so a partition
+/// holds records from `started_at_us` until the next newer partition started.
+pub struct RotatedPartition {
+    path: PathBuf,
+    started_at_us: i64,
+    /// Read-only
+    store: SQLiteStore,
+    /// Opened the first time a delete or account removal targets this file
+    writable: OnceCell<SQLiteStore>,
+}
+
+impl RotatedPartition {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn started_at_us(&self) -> i64 {
+        self.started_at_us
+    }
+
+    async fn writable(&self, delete_mode: DeleteMode) -> TurboResult<&SQLiteStore> {
+        self.writable
+            .get_or_try_init(|| async {
+                info!(
+                    "Opening rotated database {} for deletes",
+                    self.path.display()
+                );
+                Ok(SQLiteStore::open_existing(&self.path)
+                    .await?
+                    .with_delete_mode(delete_mode))
+            })
+            .await
+    }
+
+    async fn close(&self) {
+        let _ = self.store.close().await;
+        if let Some(writable) = self.writable.get() {
+            let _ = writable.close().await;
+        }
+    }
+}
+
+/// `jetstream_1700000000.db` (or a shard of it) -> start time in microseconds.
+pub(crate) fn partition_start_us(file_name: &str) -> Option<i64> {
+    let digits: String = file_name
+        .strip_prefix("jetstream_")?
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse::<i64>().ok()?.checked_mul(1_000_000)
+}
+
+/// `jetstream_archive_2024-01-01.db` -> midnight UTC of that day in microseconds.
+fn archive_start_us(file_name: &str) -> Option<i64> {
+    let day = archive_day(file_name)?;
+    Some(
+        day.and_time(chrono::NaiveTime::MIN)
+            .and_utc()
+            .timestamp_micros(),
+    )
+}
+
+/// The `jetstream_*.db` files in `dir`; none if it doesn't exist.
+async fn list_partition_files(dir: &Path) -> TurboResult<Vec<(String, PathBuf)>> {
+    match DatabaseRotator::list_databases(dir).await {
+        Ok(databases) => Ok(databases),
+        Err(_) if !dir.exists() => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+struct PartitionSet {
+    /// Newest first
+    partitions: Vec<Arc<RotatedPartition>>,
+    scanned_at: Option<Instant>,
+}
+
+impl PartitionSet {
+    /// Drops and closes the partitions whose path matches.
+    async fn detach(&mut self, matches: impl Fn(&PathBuf) -> bool) {
+        let (removed, kept): (Vec<_>, Vec<_>) = self
+            .partitions
+            .drain(..)
+            .partition(|partition| matches(&partition.path));
+        self.partitions = kept;
+        for partition in removed {
+            partition.close().await;
+        }
+    }
+}
+
+pub struct PartitionedReader {
+    live: Arc<ShardedSQLiteStore>,
+    db_dir: PathBuf,
+    delete_mode: DeleteMode,
+    account_removal_mode: DeleteMode,
+    partitions: RwLock<PartitionSet>,
+}
+
+impl PartitionedReader {
+    pub fn new(live: Arc<ShardedSQLiteStore>, db_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            live,
+            db_dir: db_dir.into(),
+            delete_mode: DeleteMode::default(),
+            account_removal_mode: DeleteMode::default(),
+            partitions: RwLock::new(PartitionSet {
+                partitions: Vec::new(),
+                scanned_at: None,
+            }),
+        }
+    }
+
+    /// Deletes arriving after a rotation are applied to rotated files in this
+    /// mode as well.
+    pub fn with_delete_mode(mut self, delete_mode: DeleteMode) -> Self {
+        self.delete_mode = delete_mode;
+        self
+    }
+
+    /// How records of taken-down or deleted accounts are treated, here and
+    /// in the live store.
+    pub fn with_account_removal_mode(mut self, mode: DeleteMode) -> Self {
+        self.account_removal_mode = mode;
+        self
+    }
+
+    /// Rescans the directory and its archives, opening newly rotated or
+    /// restored files and dropping removed ones. Returns the number of
+    /// partitions now available.
+    pub async fn refresh(&self) -> TurboResult<usize> {
+        let mut databases = list_partition_files(&self.db_dir).await?;
+        let rotated_days: HashSet<NaiveDate> = databases
+            .iter()
+            .filter_map(|(name, _)| partition_start_us(name))
+            .filter_map(DateTime::<Utc>::from_timestamp_micros)
+            .map(|started_at| started_at.date_naive())
+            .collect();
+        databases.extend(
+            list_partition_files(&self.archive_dir())
+                .await?
+                .into_iter()
+                .filter(|(name, _)| {
+                    archive_day(name).is_none_or(|day| !rotated_days.contains(&day))
+                }),
+        );
+
+        let mut set = self.partitions.write().await;
+        let mut existing: HashMap<PathBuf, Arc<RotatedPartition>> = set
+            .partitions
+            .drain(..)
+            .map(|partition| (partition.path.clone(), partition))
+            .collect();
+
+        let mut partitions = Vec::with_capacity(databases.len());
+        for (name, path) in databases {
+            if let Some(partition) = existing.remove(&path) {
+                partitions.push(partition);
+                continue;
+            }
+            let Some(started_at_us) = partition_start_us(&name).or_else(|| archive_start_us(&name))
+            else {
+                continue;
+            };
+            match SQLiteStore::open_read_only(&path).await {
+                Ok(store) => {
+                    info!("Attached rotated database {}", path.display());
+                    partitions.push(Arc::new(RotatedPartition {
+                        path,
+                        started_at_us,
+                        store,
+                        writable: OnceCell::new(),
+                    }));
+                }
+                Err(e) => warn!("Skipping rotated database {}: {}", path.display(), e),
+            }
+        }
+        for removed in existing.into_values() {
+            removed.close().await;
+        }
+
+        partitions.sort_by_key(|partition| std::cmp::Reverse(partition.started_at_us));
+        set.partitions = partitions;
+        set.scanned_at = Some(Instant::now());
+        Ok(set.partitions.len())
+    }
+
+    /// Closes the partitions at `paths`, if attached, and deletes their files
+    /// while holding the set, so lookups never read a file being removed.
+    pub async fn remove_partitions(&self, paths: &[PathBuf]) -> TurboResult<()> {
+        let mut set = self.partitions.write().await;
+        set.detach(|path| paths.contains(path)).await;
+        for path in paths {
+            DatabaseRotator::remove_database_files(path).await?;
+        }
+        Ok(())
+    }
+
+    /// Renames the finished archive built at `part` to `archive` and deletes
+    /// the rotated `sources` merged into it while holding the set, so lookups
+    /// read either the sources or the whole archive, never both. Then rescans
+    /// to attach the archive.
+    pub async fn install_archive(
+        &self,
+        part: &Path,
+        archive: &Path,
+        sources: &[PathBuf],
+    ) -> TurboResult<()> {
+        {
+            let mut set = self.partitions.write().await;
+            set.detach(|path| path == archive || sources.contains(path))
+                .await;
+            tokio::fs::rename(part, archive).await?;
+            for path in sources {
+                DatabaseRotator::remove_database_files(path).await?;
+            }
+        }
+        self.refresh().await?;
+        Ok(())
+    }
+
+    /// Where daily archives are read from.
+    pub fn archive_dir(&self) -> PathBuf {
+        self.db_dir.join(ARCHIVE_DIR_NAME)
+    }
+
+    pub async fn partition_count(&self) -> usize {
+        self.partitions.read().await.partitions.len()
+    }
+
+    /// Attached partitions, newest first, rescanning first if the last scan is stale.
+    async fn current_partitions(&self) -> Vec<Arc<RotatedPartition>> {
+        let stale = self
+            .partitions
+            .read()
+            .await
+            .scanned_at
+            .is_none_or(|scanned_at| scanned_at.elapsed() >= PARTITION_RESCAN_INTERVAL);
+        if stale {
+            if let Err(e) = self.refresh().await {
+                warn!("Failed to rescan rotated databases: {}", e);
+            }
+        }
+        self.partitions.read().await.partitions.clone()
+    }
+
+    /// Looks in the live store first, then in rotated partitions, newest first.
+    pub async fn get_record_by_uri(&self, at_uri: &str) -> TurboResult<Option<EnrichedRecord>> {
+        if let Some(record) = self.live.get_record_by_uri(at_uri).await? {
+            return Ok(Some(record));
+        }
+        for partition in self.current_partitions().await {
+            if let Some(record) = partition.store.get_record_by_uri(at_uri).await? {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Tombstones or removes rows matching `at_uris` in the rotated partitions
+    /// that hold them, in one transaction per partition; the live store applies
+    /// deletes as they're stored. Returns the rows affected.
+    pub async fn apply_deletes(&self, at_uris: &[String]) -> TurboResult<u64> {
+        if at_uris.is_empty() || self.delete_mode == DeleteMode::Keep {
+            return Ok(0);
+        }
+        let partitions = self.current_partitions().await;
+        let affected = try_join_all(partitions.iter().map(|partition| async move {
+            let targeted = partition.store.stored_uris(at_uris).await?;
+            if targeted.is_empty() {
+                return Ok(0);
+            }
+            partition
+                .writable(self.delete_mode)
+                .await?
+                .apply_deletes(&targeted)
+                .await
+        }))
+        .await?;
+        Ok(affected.into_iter().sum())
+    }
+
+    /// Applies the account removal mode to every stored commit by `dids`, in the
+    /// live store and every rotated partition. Returns the rows affected.
+    pub async fn remove_accounts(&self, dids: &[String]) -> TurboResult<u64> {
+        let mode = self.account_removal_mode;
+        if dids.is_empty() || mode == DeleteMode::Keep {
+            return Ok(0);
+        }
+        let partitions = self.current_partitions().await;
+        let (live, rotated) = tokio::join!(
+            self.live.remove_account_records(dids, mode),
+            try_join_all(partitions.iter().map(|partition| async move {
+                let targeted = partition.store.stored_dids(dids).await?;
+                if targeted.is_empty() {
+                    return Ok(0);
+                }
+                partition
+                    .writable(self.delete_mode)
+                    .await?
+                    .remove_account_records(&targeted, mode)
+                    .await
+            }))
+        );
+        Ok(live? + rotated?.into_iter().sum::<u64>())
+    }
+
+    /// The stored reply tree under `root_uri`. A thread can outlive a rotation,
+    /// so every partition is searched.
+    pub async fn thread(&self, root_uri: &str) -> TurboResult<Option<Thread>> {
+        let partitions = self.current_partitions().await;
+        let (live, rotated) =
+            tokio::join!(
+                self.live.thread_entries(root_uri, MAX_THREAD_POSTS),
+                try_join_all(partitions.iter().map(|partition| {
+                    partition.store.thread_entries(root_uri, MAX_THREAD_POSTS)
+                }))
+            );
+
+        let mut entries = live?;
+        entries.extend(rotated?.into_iter().flatten());
+        // Keep the oldest posts, nearest the root, when the thread is too large
+        entries.sort_by_key(|entry| entry.record.message.time_us);
+        entries.truncate(MAX_THREAD_POSTS as usize);
+        Ok(Thread::build(root_uri, entries))
+    }
+
+    /// Hourly counts for hours starting in `[since, until)` from the live store
+    /// and every partition, unmerged. Partitions keep the counts rolled up
+    /// before they were rotated, so the whole history is searched.
+    pub async fn hourly_counts(&self, since: i64, until: i64) -> TurboResult<Vec<HourlyCount>> {
+        let partitions = self.current_partitions().await;
+        let (live, rotated) = tokio::join!(
+            self.live.hourly_counts(since, until),
+            try_join_all(
+                partitions
+                    .iter()
+                    .map(|partition| partition.store.hourly_counts(since, until))
+            )
+        );
+
+        let mut counts = live?;
+        counts.extend(rotated?.into_iter().flatten());
+        Ok(counts)
+    }
+
+    /// Records with `since_us <= time_us < until_us`, newest first, from the
+    /// live store and every partition whose time span overlaps the range.
+    pub async fn records_in_time_range(
+        &self,
+        since_us: Option<i64>,
+        until_us: Option<i64>,
+        limit: u32,
+    ) -> TurboResult<Vec<EnrichedRecord>> {
+        let partitions = self.current_partitions().await;
+        let (live, rotated) = tokio::join!(
+            self.live.records_in_time_range(since_us, until_us, limit),
+            try_join_all(
+                overlapping(&partitions, since_us, until_us).map(|partition| {
+                    partition
+                        .store
+                        .records_in_time_range(since_us, until_us, limit)
+                })
+            )
+        );
+
+        let mut records = live?;
+        records.extend(rotated?.into_iter().flatten());
+        Ok(newest_first(records, limit))
+    }
+
+    /// Files that may hold records with `since_us <= time_us < until_us`:
+    /// every live shard, then each overlapping partition after a fresh scan.
+    pub async fn database_paths(
+        &self,
+        since_us: Option<i64>,
+        until_us: Option<i64>,
+    ) -> TurboResult<Vec<PathBuf>> {
+        self.refresh().await?;
+        let mut paths = Vec::new();
+        for shard in self.live.shards() {
+            paths.push(PathBuf::from(shard.get_db_path().await));
+        }
+        let partitions = self.partitions.read().await.partitions.clone();
+        paths.extend(
+            overlapping(&partitions, since_us, until_us).map(|partition| partition.path.clone()),
+        );
+        Ok(paths)
+    }
+}
+
+/// The partitions, newest first, whose time span overlaps the range.
+fn overlapping(
+    partitions: &[Arc<RotatedPartition>],
+    since_us: Option<i64>,
+    until_us: Option<i64>,
+) -> impl Iterator<Item = &Arc<RotatedPartition>> {
+    partitions
+        .iter()
+        .enumerate()
+        .filter(move |(index, partition)| {
+            // Newest first, so the previous entry started when this one ended
+            let ended_at_us = index
+                .checked_sub(1)
+                .map(|newer| partitions[newer].started_at_us);
+            until_us.is_none_or(|until| partition.started_at_us < until)
+                && since_us
+                    .zip(ended_at_us)
+                    .is_none_or(|(since, ended)| ended > since)
+        })
+        .map(|(_, partition)| partition)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{RecordStore, SQLitePragmaConfig};
+    use crate::testing::fixtures::{create_delete_message, create_post_message};
+
+    const PRAGMAS: SQLitePragmaConfig = SQLitePragmaConfig {
+        cache_size_kib: 1024,
+        mmap_size_mb: 0,
+        journal_size_limit_mb: 64,
+    };
+
+    fn record(index: usize, time_us: u64) -> Arc<EnrichedRecord> {
+        let mut message = create_post_message(index);
+        message.time_us = Some(time_us);
+        Arc::new(EnrichedRecord::new(message))
+    }
+
+    #[tokio::test]
+    async fn test_reads_fan_out_to_rotated_databases() {
+        let dir = std::env::temp_dir().join(format!("test_partitions_{}", uuid::Uuid::new_v4()));
+
+        // Two rotated-out files, started at t=1000s and t=2000s
+        for (started_at, index) in [(1000u64, 1), (2000, 2)] {
+            let rotated = SQLiteStore::new(dir.join(format!("jetstream_{started_at}.db")), PRAGMAS)
+                .await
+                .unwrap();
+            rotated
+                .store_batch(&[record(index, (started_at + 10) * 1_000_000)])
+                .await
+                .unwrap();
+            rotated.close().await.unwrap();
+        }
+
+        let live = Arc::new(
+            ShardedSQLiteStore::new(dir.join("jetstream.db"), 1, PRAGMAS)
+                .await
+                .unwrap(),
+        );
+        live.store_batch(&[record(3, 3_000_000_000)]).await.unwrap();
+
+        let reader = PartitionedReader::new(live.clone(), &dir);
+        assert_eq!(reader.refresh().await.unwrap(), 2);
+
+        let old_uri = record(1, 0).get_at_uri().unwrap();
+        let found = reader.get_record_by_uri(&old_uri).await.unwrap().unwrap();
+        assert_eq!(found.get_did(), "did:plc:user0001");
+        assert!(live.get_record_by_uri(&old_uri).await.unwrap().is_none());
+
+        let all = reader.records_in_time_range(None, None, 10).await.unwrap();
+        let dids: Vec<&str> = all.iter().map(EnrichedRecord::get_did).collect();
+        assert_eq!(
+            dids,
+            vec!["did:plc:user0003", "did:plc:user0002", "did:plc:user0001"]
+        );
+
+        let windowed = reader
+            .records_in_time_range(Some(1_500_000_000), Some(2_500_000_000), 10)
+            .await
+            .unwrap();
+        assert_eq!(windowed.len(), 1);
+        assert_eq!(windowed[0].get_did(), "did:plc:user0002");
+
+        live.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_deletes_reach_rotated_databases() {
+        let dir = std::env::temp_dir().join(format!("test_partitions_{}", uuid::Uuid::new_v4()));
+        let rotated = SQLiteStore::new(dir.join("jetstream_1000.db"), PRAGMAS)
+            .await
+            .unwrap();
+        rotated
+            .store_batch(&[record(1, 1_010_000_000), record(2, 1_020_000_000)])
+            .await
+            .unwrap();
+        rotated.close().await.unwrap();
+
+        let live = Arc::new(
+            ShardedSQLiteStore::new(dir.join("jetstream.db"), 1, PRAGMAS)
+                .await
+                .unwrap()
+                .with_delete_mode(DeleteMode::Tombstone),
+        );
+        let deleted_uri = record(1, 0).get_at_uri().unwrap();
+        let delete = EnrichedRecord::new(create_delete_message(1));
+        assert_eq!(delete.get_at_uri().as_deref(), Some(deleted_uri.as_str()));
+
+        let keeping = PartitionedReader::new(live.clone(), &dir);
+        assert_eq!(
+            keeping
+                .apply_deletes(std::slice::from_ref(&deleted_uri))
+                .await
+                .unwrap(),
+            0
+        );
+
+        let reader =
+            PartitionedReader::new(live.clone(), &dir).with_delete_mode(DeleteMode::Tombstone);
+        live.store_batch(&[Arc::new(delete)]).await.unwrap();
+        // Deletes for records the partition doesn't hold leave it read-only
+        let unstored_uri = record(9, 0).get_at_uri().unwrap();
+        assert_eq!(
+            reader
+                .apply_deletes(std::slice::from_ref(&unstored_uri))
+                .await
+                .unwrap(),
+            0
+        );
+        assert!(reader.current_partitions().await[0]
+            .writable
+            .get()
+            .is_none());
+
+        assert_eq!(
+            reader
+                .apply_deletes(std::slice::from_ref(&deleted_uri))
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(reader
+            .get_record_by_uri(&deleted_uri)
+            .await
+            .unwrap()
+            .is_none());
+        let remaining = reader.records_in_time_range(None, None, 10).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].get_did(), "did:plc:user0002");
+        assert!(reader.current_partitions().await[0]
+            .writable
+            .get()
+            .is_some());
+
+        live.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
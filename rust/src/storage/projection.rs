@@ -0,0 +1,230 @@
+//! Trims records before they're persisted. Operators that only need the text,
+//! the author and a few counts can drop the bulky parts of each record from
+//! SQLite and the main Redis stream; WebSocket clients, output streams and the
+//! watchlist still see the full record.
+
+use crate::models::bluesky::BlueskyProfile;
+use crate::models::enriched::EnrichedRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// Fields of the raw record kept by `raw_record`. `reply` and `subject` stay
+/// so threads and interaction targets still resolve.
+const KEPT_RECORD_FIELDS: &[&str] = &["$type", "text", "createdAt", "langs", "reply", "subject"];
+
+/// A part of the enriched record that can be left out of storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectionPart {
+    /// Every part below
+    Compact,
+    /// Reduce the raw record to its text, timestamps, languages and reply/subject refs
+    RawRecord,
+    /// Keep only the author's DID, handle, display name, creation date and counts
+    AuthorProfile,
+    MentionedProfiles,
+    ReferencedPosts,
+    /// `hashtags`, `links` and `mentions`
+    Entities,
+    /// Extracted hashtags, which hourly hashtag aggregates are counted from
+    Hashtags,
+    /// Extracted links and their previews
+    Links,
+    Mentions,
+    Media,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordProjection {
+    parts: BTreeSet<ProjectionPart>,
+}
+
+impl RecordProjection {
+    pub fn new(parts: &[ProjectionPart]) -> Self {
+        let parts = parts
+            .iter()
+            .flat_map(|part| match part {
+                ProjectionPart::Compact => vec![
+                    ProjectionPart::RawRecord,
+                    ProjectionPart::AuthorProfile,
+                    ProjectionPart::MentionedProfiles,
+                    ProjectionPart::ReferencedPosts,
+                    ProjectionPart::Hashtags,
+                    ProjectionPart::Links,
+                    ProjectionPart::Mentions,
+                    ProjectionPart::Media,
+                ],
+                ProjectionPart::Entities => vec![
+                    ProjectionPart::Hashtags,
+                    ProjectionPart::Links,
+                    ProjectionPart::Mentions,
+                ],
+                part => vec![*part],
+            })
+            .collect();
+        Self { parts }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// The records to persist. Without any parts configured these are the
+    /// same records, not copies.
+    pub fn apply(&self, records: &[Arc<EnrichedRecord>]) -> Vec<Arc<EnrichedRecord>> {
+        if self.is_empty() {
+            return records.to_vec();
+        }
+        records
+            .iter()
+            .map(|record| {
+                // Deletes carry nothing to trim
+                if record.is_delete() {
+                    Arc::clone(record)
+                } else {
+                    Arc::new(self.project(record))
+                }
+            })
+            .collect()
+    }
+
+    fn project(&self, record: &EnrichedRecord) -> EnrichedRecord {
+        let mut projected = record.clone();
+        let metadata = &mut projected.hydrated_metadata;
+        for part in &self.parts {
+            match part {
+                // Expanded into the parts below by `new`
+                ProjectionPart::Compact | ProjectionPart::Entities => {}
+                ProjectionPart::RawRecord => {
+                    if let Some(serde_json::Value::Object(fields)) = projected
+                        .message
+                        .commit
+                        .as_mut()
+                        .and_then(|commit| commit.record.as_mut())
+                    {
+                        fields.retain(|key, _| KEPT_RECORD_FIELDS.contains(&key.as_str()));
+                    }
+                }
+                ProjectionPart::AuthorProfile => {
+                    if let Some(profile) = &metadata.author_profile {
+                        metadata.author_profile = Some(Arc::new(slim_profile(profile)));
+                    }
+                }
+                ProjectionPart::MentionedProfiles => metadata.mentioned_profiles.clear(),
+                ProjectionPart::ReferencedPosts => metadata.referenced_posts.clear(),
+                ProjectionPart::Hashtags => metadata.hashtags.clear(),
+                ProjectionPart::Links => metadata.urls.clear(),
+                ProjectionPart::Mentions => metadata.mentions.clear(),
+                ProjectionPart::Media => metadata.media = None,
+            }
+        }
+        projected
+    }
+}
+
+fn slim_profile(profile: &BlueskyProfile) -> BlueskyProfile {
+    BlueskyProfile {
+        did: Arc::clone(&profile.did),
+        handle: profile.handle.clone(),
+        display_name: profile.display_name.clone(),
+        description: None,
+        avatar: None,
+        banner: None,
+        followers_count: profile.followers_count,
+        follows_count: profile.follows_count,
+        posts_count: profile.posts_count,
+        indexed_at: None,
+        created_at: profile.created_at,
+        labels: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::enriched::{Mention, UrlEntry};
+    use crate::testing::fixtures::{create_delete_message, create_post_message};
+
+    #[test]
+    fn test_compact_projection_keeps_text_author_and_counts() {
+        let mut message = create_post_message(1);
+        let fields = message.commit.as_mut().unwrap().record.as_mut().unwrap();
+        fields["facets"] = serde_json::json!([{"features": []}]);
+        fields["embed"] = serde_json::json!({"$type": "app.bsky.embed.external"});
+        let mut record = EnrichedRecord::new(message);
+        let profile: BlueskyProfile = serde_json::from_value(serde_json::json!({
+            "did": "did:plc:user0001",
+            "handle": "user1.bsky.social",
+            "description": "A long bio",
+            "avatar": "https://cdn.example.com/avatar.jpg",
+            "followersCount": 12
+        }))
+        .unwrap();
+        record.hydrated_metadata.author_profile = Some(Arc::new(profile));
+        record.hydrated_metadata.hashtags = vec!["rust".to_string()];
+        record.hydrated_metadata.urls = vec![UrlEntry::Link("https://example.com".to_string())];
+        let records = vec![
+            Arc::new(record),
+            Arc::new(EnrichedRecord::new(create_delete_message(2))),
+        ];
+
+        let projected = RecordProjection::new(&[ProjectionPart::Compact]).apply(&records);
+        let post = &projected[0];
+        let fields = post
+            .message
+            .commit
+            .as_ref()
+            .unwrap()
+            .record
+            .as_ref()
+            .unwrap();
+        assert!(fields.get("facets").is_none() && fields.get("embed").is_none());
+        assert_eq!(post.get_text(), records[0].get_text());
+        let author = post.hydrated_metadata.author_profile.as_ref().unwrap();
+        assert_eq!(author.handle, "user1.bsky.social");
+        assert_eq!(author.followers_count, Some(12));
+        assert!(author.description.is_none() && author.avatar.is_none());
+        assert!(post.hydrated_metadata.hashtags.is_empty());
+        assert!(post.hydrated_metadata.urls.is_empty());
+        assert!(Arc::ptr_eq(&projected[1], &records[1]));
+
+        // The originals, still used for live consumers, are untouched
+        assert_eq!(records[0].hydrated_metadata.hashtags.len(), 1);
+
+        let unprojected = RecordProjection::default().apply(&records);
+        assert!(Arc::ptr_eq(&unprojected[0], &records[0]));
+    }
+
+    #[test]
+    fn test_entities_left_out_of_the_projection_are_kept() {
+        let mut record = EnrichedRecord::new(create_post_message(1));
+        record.hydrated_metadata.hashtags = vec!["rust".to_string()];
+        record.hydrated_metadata.urls = vec![UrlEntry::Link("https://example.com".to_string())];
+        record.hydrated_metadata.mentions = vec![Mention {
+            did: Arc::from("did:plc:user0002"),
+            handle: None,
+            display_name: None,
+            start_byte: 0,
+            end_byte: 6,
+        }];
+        let records = vec![Arc::new(record)];
+
+        let projected = RecordProjection::new(&[ProjectionPart::Links, ProjectionPart::Mentions])
+            .apply(&records);
+        let metadata = &projected[0].hydrated_metadata;
+        assert_eq!(metadata.hashtags, vec!["rust".to_string()]);
+        assert!(metadata.urls.is_empty() && metadata.mentions.is_empty());
+
+        let projected = RecordProjection::new(&[ProjectionPart::Hashtags]).apply(&records);
+        let metadata = &projected[0].hydrated_metadata;
+        assert!(metadata.hashtags.is_empty());
+        assert_eq!(metadata.urls.len(), 1);
+        assert_eq!(metadata.mentions.len(), 1);
+
+        let projected = RecordProjection::new(&[ProjectionPart::Entities]).apply(&records);
+        let metadata = &projected[0].hydrated_metadata;
+        assert!(metadata.hashtags.is_empty() && metadata.urls.is_empty());
+        assert!(metadata.mentions.is_empty());
+    }
+}
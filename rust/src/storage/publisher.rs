@@ -0,0 +1,96 @@
+//! The live event stream's sink interface, and the payload encodings its
+//! entries are written in.
+
+use crate::models::{
+    enriched::EnrichedRecord,
+    errors::{TurboError, TurboResult},
+};
+use crate::storage::FileSink;
+#[cfg(feature = "redis")]
+use crate::storage::RedisStore;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// zstd level used for compressed payloads; favours speed over ratio.
+const ZSTD_LEVEL: i32 = 3;
+
+/// How the enriched record is written to the `message` field of a stream entry.
+/// Every entry carries a `format` field so readers can decode either encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadEncoding {
+    /// Plain JSON text
+    #[default]
+    Json,
+    /// zstd-compressed JSON
+    Zstd,
+}
+
+impl PayloadEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadEncoding::Json => "json",
+            PayloadEncoding::Zstd => "zstd",
+        }
+    }
+
+    pub fn from_format(format: Option<&[u8]>) -> TurboResult<Self> {
+        match format {
+            None | Some(b"json") => Ok(PayloadEncoding::Json),
+            Some(b"zstd") => Ok(PayloadEncoding::Zstd),
+            Some(other) => Err(TurboError::InvalidMessage(format!(
+                "unknown stream payload format: {}",
+                String::from_utf8_lossy(other)
+            ))),
+        }
+    }
+
+    pub fn encode(&self, record: &EnrichedRecord) -> TurboResult<Vec<u8>> {
+        let json = serde_json::to_vec(record)?;
+        match self {
+            PayloadEncoding::Json => Ok(json),
+            PayloadEncoding::Zstd => zstd::encode_all(json.as_slice(), ZSTD_LEVEL)
+                .map_err(|e| TurboError::InvalidMessage(format!("zstd compression failed: {e}"))),
+        }
+    }
+
+    pub fn decode(&self, payload: &[u8]) -> TurboResult<EnrichedRecord> {
+        let value: serde_json::Value = match self {
+            PayloadEncoding::Json => serde_json::from_slice(payload)?,
+            PayloadEncoding::Zstd => {
+                let json = zstd::decode_all(payload).map_err(|e| {
+                    TurboError::InvalidMessage(format!("zstd decompression failed: {e}"))
+                })?;
+                serde_json::from_slice(&json)?
+            }
+        };
+        Ok(EnrichedRecord::from_json_value(value)?)
+    }
+}
+
+pub trait EventPublisher {
+    fn publish_batch(
+        &self,
+        records: &[Arc<EnrichedRecord>],
+    ) -> impl std::future::Future<Output = TurboResult<Vec<String>>> + Send;
+}
+
+/// The main stream's sink as `TurboCharger::new` builds it: the Redis stream,
+/// or in standalone mode or without the `redis` feature an optional NDJSON file. WebSocket clients are fed
+/// by the in-process broadcast either way.
+pub enum LiveSink {
+    #[cfg(feature = "redis")]
+    Redis(Arc<RedisStore>),
+    Standalone(Option<FileSink>),
+}
+
+impl EventPublisher for LiveSink {
+    async fn publish_batch(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<Vec<String>> {
+        match self {
+            #[cfg(feature = "redis")]
+            LiveSink::Redis(redis) => redis.publish_batch(records).await,
+            LiveSink::Standalone(Some(file)) => file.publish_batch(records).await,
+            LiveSink::Standalone(None) => Ok(vec![]),
+        }
+    }
+}
@@ -2,23 +2,38 @@ use crate::models::{
     enriched::EnrichedRecord,
     errors::{TurboError, TurboResult},
 };
-use not_redis::Client as NotRedisClient;
-use serde_json;
+use crate::storage::publisher::{EventPublisher, PayloadEncoding};
+#[cfg(feature = "chaos")]
+use crate::utils::chaos::FaultInjector;
+use metrics::counter;
+use not_redis::{Client as NotRedisClient, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tracing::{error, info, trace};
 
-pub trait EventPublisher {
-    fn publish_batch(
-        &self,
-        records: &[EnrichedRecord],
-    ) -> impl std::future::Future<Output = TurboResult<Vec<String>>> + Send;
-}
+/// How long a published event's idempotency key is remembered. Only needs to
+/// cover the cursor rewind on reconnect; restarts are caught by the SQLite sink.
+const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(10 * 60);
 
 pub struct RedisStore {
     client: Arc<Mutex<NotRedisClient>>,
     stream_name: String,
     max_length: Option<usize>,
+    payload_encoding: PayloadEncoding,
+    publish_deletes: bool,
+    /// Shared with the stores of the other streams, like the connection
+    duplicates_skipped: Arc<AtomicU64>,
+    #[cfg(feature = "chaos")]
+    chaos: FaultInjector,
+}
+
+/// A record read back from the stream together with its entry ID.
+#[derive(Debug, Clone)]
+pub struct StreamEntry {
+    pub id: String,
+    pub record: EnrichedRecord,
 }
 
 impl RedisStore {
@@ -38,28 +53,162 @@ impl RedisStore {
             client: Arc::new(Mutex::new(client)),
             stream_name,
             max_length,
+            payload_encoding: PayloadEncoding::default(),
+            publish_deletes: true,
+            duplicates_skipped: Arc::new(AtomicU64::new(0)),
+            #[cfg(feature = "chaos")]
+            chaos: FaultInjector::default(),
         })
     }
 
-    pub async fn publish_record(&self, record: &EnrichedRecord) -> TurboResult<String> {
-        let message_json = serde_json::to_string(record)?;
-        let message_id = generate_message_id(record);
+    pub fn with_payload_encoding(mut self, payload_encoding: PayloadEncoding) -> Self {
+        self.payload_encoding = payload_encoding;
+        self
+    }
+
+    /// Whether delete events are published. Each entry's `event` field tells
+    /// consumers whether it's a record or the deletion of one.
+    pub fn with_publish_deletes(mut self, publish_deletes: bool) -> Self {
+        self.publish_deletes = publish_deletes;
+        self
+    }
+
+    /// Injects faults into each publish before it reaches the stream.
+    #[cfg(feature = "chaos")]
+    pub fn with_chaos(mut self, injector: FaultInjector) -> Self {
+        self.chaos = injector;
+        self
+    }
+
+    /// Trims the stream to roughly `max_length` entries after each publish.
+    pub fn with_max_length(mut self, max_length: Option<usize>) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// A store publishing to `stream_name` over the same connection and settings.
+    /// It publishes deletes regardless; output streams filter them on their own.
+    pub fn for_stream(&self, stream_name: String) -> Self {
+        Self {
+            client: Arc::clone(&self.client),
+            stream_name,
+            max_length: self.max_length,
+            payload_encoding: self.payload_encoding,
+            publish_deletes: true,
+            duplicates_skipped: Arc::clone(&self.duplicates_skipped),
+            #[cfg(feature = "chaos")]
+            chaos: self.chaos.clone(),
+        }
+    }
+
+    /// Events skipped as published within `IDEMPOTENCY_KEY_TTL`, on any stream.
+    pub fn duplicates_skipped(&self) -> u64 {
+        self.duplicates_skipped.load(Ordering::Relaxed)
+    }
+
+    pub fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
+
+    fn entry_values(&self, record: &EnrichedRecord) -> TurboResult<Vec<(&'static str, Vec<u8>)>> {
+        let message = self.payload_encoding.encode(record)?;
         let at_uri = record.get_at_uri().unwrap_or_default();
         let did = record.get_did().to_string();
         let hydrated_at = record.processed_at.to_rfc3339();
 
-        let values = vec![
-            ("at_uri", at_uri),
-            ("did", did),
-            ("message", message_json),
-            ("hydrated_at", hydrated_at),
-        ];
+        Ok(vec![
+            ("at_uri", at_uri.into_bytes()),
+            ("did", did.into_bytes()),
+            (
+                "event",
+                if record.is_delete() {
+                    "delete"
+                } else {
+                    "record"
+                }
+                .as_bytes()
+                .to_vec(),
+            ),
+            ("format", self.payload_encoding.as_str().as_bytes().to_vec()),
+            ("message", message),
+            ("hydrated_at", hydrated_at.into_bytes()),
+            (
+                "schema_version",
+                record.schema_version.to_string().into_bytes(),
+            ),
+            (
+                "idempotency_key",
+                record.idempotency_key().unwrap_or_default().into_bytes(),
+            ),
+        ])
+    }
+
+    /// The key `record` is remembered under once published, if it has one.
+    fn published_key(&self, record: &EnrichedRecord) -> Option<String> {
+        record
+            .idempotency_key()
+            .map(|key| format!("{}:seen:{}", self.stream_name, key))
+    }
+
+    /// Whether the event under `key` was published within `IDEMPOTENCY_KEY_TTL`.
+    /// Callers hold the client lock until `remember_published`, so the check and
+    /// the publish in between are atomic.
+    async fn already_published(
+        &self,
+        client: &mut NotRedisClient,
+        key: Option<&str>,
+    ) -> TurboResult<bool> {
+        let Some(key) = key else {
+            return Ok(false);
+        };
+        let seen = client
+            .exists(key)
+            .await
+            .map_err(TurboError::RedisOperation)?;
+        if seen {
+            self.duplicates_skipped.fetch_add(1, Ordering::Relaxed);
+            counter!("jetstream_turbo_duplicates_skipped_total", "sink" => "redis").increment(1);
+        }
+        Ok(seen)
+    }
+
+    /// Remembers the event under `key` as published. Only called once its
+    /// `xadd` succeeded, so a failed publish is retried rather than skipped.
+    async fn remember_published(
+        &self,
+        client: &mut NotRedisClient,
+        key: Option<&str>,
+    ) -> TurboResult<()> {
+        let Some(key) = key else {
+            return Ok(());
+        };
+        client
+            .set(key, "1")
+            .await
+            .map_err(TurboError::RedisOperation)?;
+        client
+            .expire(key, IDEMPOTENCY_KEY_TTL.as_secs() as i64)
+            .await
+            .map_err(TurboError::RedisOperation)?;
+        Ok(())
+    }
+
+    /// Returns the entry ID, or `None` if the event was already published.
+    pub async fn publish_record(&self, record: &EnrichedRecord) -> TurboResult<Option<String>> {
+        let message_id = generate_message_id(record);
+        let values = self.entry_values(record)?;
+
+        let key = self.published_key(record);
 
         let mut client = self.client.lock().await;
+        if self.already_published(&mut client, key.as_deref()).await? {
+            return Ok(None);
+        }
         let id: String = client
             .xadd(self.stream_name.clone(), Some(&message_id), values)
             .await
             .map_err(TurboError::RedisOperation)?;
+        self.remember_published(&mut client, key.as_deref()).await?;
 
         if let Some(max_len) = self.max_length {
             let _: i64 = client
@@ -69,7 +218,25 @@ impl RedisStore {
         }
 
         trace!("Published record to not_redis stream with ID: {}", id);
-        Ok(id)
+        Ok(Some(id))
+    }
+
+    /// Reads up to `count` entries with IDs at or after `start` (`-` for the
+    /// beginning of the stream), decoding each payload according to its `format`.
+    /// Entries written before the field existed are treated as JSON.
+    pub async fn read_records(&self, start: &str, count: usize) -> TurboResult<Vec<StreamEntry>> {
+        let reply: Value = {
+            let mut client = self.client.lock().await;
+            client
+                .xrange(self.stream_name.clone(), start, "+", Some(count))
+                .await
+                .map_err(TurboError::RedisOperation)?
+        };
+
+        let Value::Array(entries) = reply else {
+            return Ok(vec![]);
+        };
+        entries.iter().map(decode_stream_entry).collect()
     }
 
     pub async fn get_stream_info(&self) -> TurboResult<StreamInfo> {
@@ -123,33 +290,34 @@ impl RedisStore {
 }
 
 impl EventPublisher for RedisStore {
-    async fn publish_batch(&self, records: &[EnrichedRecord]) -> TurboResult<Vec<String>> {
+    async fn publish_batch(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<Vec<String>> {
         if records.is_empty() {
             return Ok(vec![]);
         }
+        #[cfg(feature = "chaos")]
+        self.chaos.inject().await?;
 
         let mut client = self.client.lock().await;
         let mut message_ids = Vec::with_capacity(records.len());
 
         // Batch Redis operations - acquire lock once for all records
-        for record in records {
-            let message_json = serde_json::to_string(record)?;
+        let records: Vec<&Arc<EnrichedRecord>> = records
+            .iter()
+            .filter(|record| self.publish_deletes || !record.is_delete())
+            .collect();
+        for record in &records {
+            let key = self.published_key(record);
+            if self.already_published(&mut client, key.as_deref()).await? {
+                continue;
+            }
             let message_id = generate_message_id(record);
-            let at_uri = record.get_at_uri().unwrap_or_default();
-            let did = record.get_did().to_string();
-            let hydrated_at = record.processed_at.to_rfc3339();
-
-            let values = vec![
-                ("at_uri", at_uri),
-                ("did", did),
-                ("message", message_json),
-                ("hydrated_at", hydrated_at),
-            ];
+            let values = self.entry_values(record)?;
 
             let id: String = client
                 .xadd(self.stream_name.clone(), Some(&message_id), values)
                 .await
                 .map_err(TurboError::RedisOperation)?;
+            self.remember_published(&mut client, key.as_deref()).await?;
 
             message_ids.push(id);
         }
@@ -163,8 +331,9 @@ impl EventPublisher for RedisStore {
         }
 
         info!(
-            "Published batch of {} records to not_redis stream",
-            records.len()
+            "Published batch of {} records to not_redis stream ({} duplicates skipped)",
+            message_ids.len(),
+            records.len() - message_ids.len()
         );
         Ok(message_ids)
     }
@@ -186,6 +355,36 @@ fn generate_message_id(record: &EnrichedRecord) -> String {
     )
 }
 
+/// Decodes one `[id, field, value, field, value, ...]` entry from an XRANGE reply.
+fn decode_stream_entry(entry: &Value) -> TurboResult<StreamEntry> {
+    let malformed = || TurboError::InvalidMessage("malformed stream entry".to_string());
+    let Value::Array(parts) = entry else {
+        return Err(malformed());
+    };
+    let Some((Value::String(id), fields)) = parts.split_first() else {
+        return Err(malformed());
+    };
+
+    let mut format = None;
+    let mut message = None;
+    for pair in fields.chunks_exact(2) {
+        if let [Value::String(field), Value::String(value)] = pair {
+            match field.as_slice() {
+                b"format" => format = Some(value.as_slice()),
+                b"message" => message = Some(value.as_slice()),
+                _ => {}
+            }
+        }
+    }
+
+    let message = message.ok_or_else(malformed)?;
+    let record = PayloadEncoding::from_format(format)?.decode(message)?;
+    Ok(StreamEntry {
+        id: String::from_utf8_lossy(id).into_owned(),
+        record,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,6 +393,7 @@ mod tests {
     #[test]
     fn test_generate_message_id() {
         let record = EnrichedRecord {
+            schema_version: crate::models::enriched::ENRICHED_RECORD_SCHEMA_VERSION,
             message: crate::models::jetstream::JetstreamMessage {
                 did: "did:plc:test".to_string(),
                 seq: Some(12345),
@@ -206,8 +406,13 @@ mod tests {
                     rkey: Some("test".to_string()),
                     record: Some(serde_json::json!({"text": "Hello world"})),
                     cid: Some("bafyrei".to_string()),
+                    extra: Default::default(),
                 }),
+                identity: None,
+                account: None,
+                extra: Default::default(),
             },
+            event: crate::models::enriched::EnrichedEventKind::Record,
             hydrated_metadata: crate::models::enriched::HydratedMetadata::default(),
             processed_at: chrono::Utc::now(),
             metrics: ProcessingMetrics {
@@ -216,6 +421,7 @@ mod tests {
                 cache_hit_rate: 0.8,
                 cache_hits: 8,
                 cache_misses: 2,
+                ingest_lag_ms: 0,
             },
         };
 
@@ -223,4 +429,121 @@ mod tests {
         assert!(message_id.contains('-'));
         assert_eq!(message_id.split('-').count(), 2);
     }
+
+    #[tokio::test]
+    async fn test_read_records_decodes_both_payload_encodings() {
+        use crate::testing::fixtures::create_post_message;
+
+        let json_store = RedisStore::new("", "json_stream".to_string(), None)
+            .await
+            .unwrap();
+        let zstd_store = RedisStore::new("", "zstd_stream".to_string(), None)
+            .await
+            .unwrap()
+            .with_payload_encoding(PayloadEncoding::Zstd);
+
+        let records: Vec<Arc<EnrichedRecord>> = (1..=3)
+            .map(|seq| Arc::new(EnrichedRecord::new(create_post_message(seq))))
+            .collect();
+
+        for store in [&json_store, &zstd_store] {
+            store.publish_batch(&records).await.unwrap();
+            let entries = store.read_records("-", 10).await.unwrap();
+            assert_eq!(entries.len(), 3);
+            for (entry, record) in entries.iter().zip(&records) {
+                assert_eq!(entry.record.message.seq, record.message.seq);
+                assert_eq!(entry.record.get_text(), record.get_text());
+            }
+        }
+
+        let json_values = json_store.entry_values(&records[0]).unwrap();
+        let zstd_values = zstd_store.entry_values(&records[0]).unwrap();
+        let message_len = |values: &[(&str, Vec<u8>)]| {
+            values
+                .iter()
+                .find(|(field, _)| *field == "message")
+                .map(|(_, value)| value.len())
+                .unwrap()
+        };
+        assert!(message_len(&zstd_values) < message_len(&json_values));
+    }
+
+    #[tokio::test]
+    async fn test_replayed_events_are_published_once() {
+        use crate::testing::fixtures::create_post_message;
+
+        let store = RedisStore::new("", "dedup_stream".to_string(), None)
+            .await
+            .unwrap();
+        let records: Vec<Arc<EnrichedRecord>> = (1..=3)
+            .map(|seq| Arc::new(EnrichedRecord::new(create_post_message(seq))))
+            .collect();
+
+        assert_eq!(store.publish_batch(&records).await.unwrap().len(), 3);
+        assert!(store.publish_batch(&records[1..]).await.unwrap().is_empty());
+        assert!(store.publish_record(&records[0]).await.unwrap().is_none());
+        assert_eq!(store.get_stream_info().await.unwrap().stream_length, 3);
+        assert_eq!(store.duplicates_skipped(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_publishes_are_not_remembered() {
+        use crate::testing::fixtures::create_post_message;
+
+        let store = RedisStore::new("", "failed_stream".to_string(), None)
+            .await
+            .unwrap();
+        let record = EnrichedRecord::new(create_post_message(1));
+
+        // A key of the wrong type where the stream belongs makes XADD fail
+        store
+            .client
+            .lock()
+            .await
+            .set("failed_stream", "not a stream")
+            .await
+            .unwrap();
+        assert!(store.publish_record(&record).await.is_err());
+
+        store.clear_stream().await.unwrap();
+        assert!(store.publish_record(&record).await.unwrap().is_some());
+        assert!(store.publish_record(&record).await.unwrap().is_none());
+        assert_eq!(store.get_stream_info().await.unwrap().stream_length, 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_events_are_marked_or_left_out() {
+        use crate::testing::fixtures::{create_delete_message, create_post_message};
+
+        let records = vec![
+            Arc::new(EnrichedRecord::new(create_post_message(1))),
+            Arc::new(EnrichedRecord::new(create_delete_message(1))),
+        ];
+        let store = RedisStore::new("", "deletes_stream".to_string(), None)
+            .await
+            .unwrap();
+        let event = |record: &EnrichedRecord| {
+            store
+                .entry_values(record)
+                .unwrap()
+                .into_iter()
+                .find(|(field, _)| *field == "event")
+                .map(|(_, value)| value)
+                .unwrap()
+        };
+        assert_eq!(event(&records[0]), b"record");
+        assert_eq!(event(&records[1]), b"delete");
+        assert_eq!(store.publish_batch(&records).await.unwrap().len(), 2);
+
+        let without_deletes = RedisStore::new("", "no_deletes_stream".to_string(), None)
+            .await
+            .unwrap()
+            .with_publish_deletes(false);
+        assert_eq!(
+            without_deletes.publish_batch(&records).await.unwrap().len(),
+            1
+        );
+        let entries = without_deletes.read_records("-", 10).await.unwrap();
+        assert!(!entries[0].record.is_delete());
+    }
 }
@@ -0,0 +1,228 @@
+//! Brings daily archives uploaded by the compactor back from object storage,
+//! e.g. to seed a query replica. Archives are decompressed into the archive
+//! directory, where the `PartitionedReader` reads them like rotated files.
+
+use crate::models::TurboResult;
+use crate::storage::compaction::{archive_day, archive_file_name};
+use crate::storage::PartitionedReader;
+use chrono::NaiveDate;
+use futures::StreamExt;
+use object_store::{ObjectMeta, ObjectStore};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::info;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RestoreReport {
+    pub restored: Vec<PathBuf>,
+    /// Days whose uncompressed archive was already there, left untouched
+    pub skipped: Vec<NaiveDate>,
+    pub bytes_downloaded: u64,
+    /// Partitions the reader reads after the restore, restored ones included
+    pub partitions: usize,
+}
+
+/// Downloads the archives under `prefix` for days with `since <= day < until`
+/// into `reader`'s archive directory, decompressing `.db.zst` ones, then
+/// rescans so `reader` serves them. A missing bound leaves that side open.
+/// When a day was uploaded both compressed and not, the newer object wins.
+pub async fn restore_archives(
+    store: &dyn ObjectStore,
+    prefix: &str,
+    reader: &PartitionedReader,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> TurboResult<RestoreReport> {
+    let prefix = prefix.trim_matches('/');
+    let listed_prefix = (!prefix.is_empty()).then(|| object_store::path::Path::from(prefix));
+    let mut objects = store.list(listed_prefix.as_ref());
+    let mut days: BTreeMap<NaiveDate, ObjectMeta> = BTreeMap::new();
+    while let Some(meta) = objects.next().await {
+        let meta = meta?;
+        let Some(day) = meta.location.filename().and_then(archive_day) else {
+            continue;
+        };
+        if since.is_some_and(|since| day < since) || until.is_some_and(|until| day >= until) {
+            continue;
+        }
+        if days
+            .get(&day)
+            .is_none_or(|newest| meta.last_modified > newest.last_modified)
+        {
+            days.insert(day, meta);
+        }
+    }
+
+    let archive_dir = reader.archive_dir();
+    tokio::fs::create_dir_all(&archive_dir).await?;
+    let mut report = RestoreReport::default();
+    for (day, meta) in days {
+        let target = archive_dir.join(archive_file_name(day));
+        if tokio::fs::metadata(&target).await.is_ok() {
+            report.skipped.push(day);
+            continue;
+        }
+        report.bytes_downloaded += download_archive(store, &meta, &target).await?;
+        info!("Restored {} to {}", meta.location, target.display());
+        report.restored.push(target);
+    }
+
+    report.partitions = reader.refresh().await?;
+    Ok(report)
+}
+
+/// Writes the object to `target`, decompressing it if it's zstd. Files are
+/// written under a `.part` name first, so a rescan never opens half of one.
+/// Returns the bytes downloaded.
+async fn download_archive(
+    store: &dyn ObjectStore,
+    meta: &ObjectMeta,
+    target: &Path,
+) -> TurboResult<u64> {
+    let file_name = meta.location.filename().unwrap_or_default();
+    let downloaded = part_path(&target.with_file_name(file_name));
+    let mut file = tokio::fs::File::create(&downloaded).await?;
+    let mut chunks = store.get(&meta.location).await?.into_stream();
+    let mut bytes = 0;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk?;
+        bytes += chunk.len() as u64;
+        file.write_all(&chunk).await?;
+    }
+    file.sync_all().await?;
+    drop(file);
+
+    if file_name.ends_with(".zst") {
+        let decoded = part_path(target);
+        let (from, to) = (downloaded.clone(), decoded.clone());
+        tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+            zstd::stream::copy_decode(File::open(from)?, File::create(to)?)
+        })
+        .await??;
+        tokio::fs::remove_file(&downloaded).await?;
+        tokio::fs::rename(&decoded, target).await?;
+    } else {
+        tokio::fs::rename(&downloaded, target).await?;
+    }
+    Ok(bytes)
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::enriched::EnrichedRecord;
+    use crate::storage::{RecordStore, SQLitePragmaConfig, SQLiteStore, ShardedSQLiteStore};
+    use crate::testing::fixtures::create_post_message;
+    use object_store::memory::InMemory;
+    use object_store::PutPayload;
+    use std::sync::Arc;
+
+    const PRAGMAS: SQLitePragmaConfig = SQLitePragmaConfig {
+        cache_size_kib: 1024,
+        mmap_size_mb: 0,
+        journal_size_limit_mb: 64,
+    };
+
+    fn record(index: usize, time_us: u64) -> Arc<EnrichedRecord> {
+        let mut message = create_post_message(index);
+        message.time_us = Some(time_us);
+        Arc::new(EnrichedRecord::new(message))
+    }
+
+    /// An archive file holding `record`, as the compactor leaves it.
+    async fn archive_bytes(dir: &Path, record: Arc<EnrichedRecord>) -> Vec<u8> {
+        let path = dir.join(format!("source_{}.db", uuid::Uuid::new_v4()));
+        let store = SQLiteStore::new(&path, PRAGMAS).await.unwrap();
+        store.store_batch(&[record]).await.unwrap();
+        store.close().await.unwrap();
+        SQLiteStore::finalize_standalone(&path).await.unwrap();
+        std::fs::read(&path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_restores_archives_in_range_for_the_reader() {
+        let dir = std::env::temp_dir().join(format!("test_restore_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // 2024-01-01 compressed, 2024-01-02 not, 2024-01-05 out of range
+        let store = InMemory::new();
+        let first = archive_bytes(&dir, record(1, 1_704_070_000_000_000)).await;
+        let second = archive_bytes(&dir, record(2, 1_704_160_000_000_000)).await;
+        let later = archive_bytes(&dir, record(5, 1_704_420_000_000_000)).await;
+        for (name, bytes) in [
+            (
+                "jetstream_archive_2024-01-01.db.zst",
+                zstd::encode_all(first.as_slice(), 3).unwrap(),
+            ),
+            ("jetstream_archive_2024-01-02.db", second),
+            ("jetstream_archive_2024-01-05.db", later),
+            ("notes.txt", b"not an archive".to_vec()),
+        ] {
+            store
+                .put(
+                    &object_store::path::Path::from(format!("archives/{name}")),
+                    PutPayload::from(bytes),
+                )
+                .await
+                .unwrap();
+        }
+
+        let live = Arc::new(
+            ShardedSQLiteStore::new(dir.join("jetstream.db"), 1, PRAGMAS)
+                .await
+                .unwrap(),
+        );
+        let reader = PartitionedReader::new(live.clone(), &dir);
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let report = restore_archives(&store, "/archives/", &reader, Some(day(1)), Some(day(5)))
+            .await
+            .unwrap();
+        assert_eq!(report.restored.len(), 2);
+        assert!(report.skipped.is_empty());
+        assert!(report.bytes_downloaded > 0);
+        assert_eq!(report.partitions, 2);
+
+        let archive_dir = dir.join("archives");
+        assert!(archive_dir.join("jetstream_archive_2024-01-01.db").exists());
+        assert!(!archive_dir.join("jetstream_archive_2024-01-05.db").exists());
+        assert!(std::fs::read_dir(&archive_dir).unwrap().all(|entry| !entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .ends_with(".part")));
+
+        for index in [1, 2] {
+            let uri = record(index, 0).get_at_uri().unwrap();
+            assert!(reader.get_record_by_uri(&uri).await.unwrap().is_some());
+        }
+        let first_day = reader
+            .records_in_time_range(Some(1_704_067_200_000_000), Some(1_704_153_600_000_000), 10)
+            .await
+            .unwrap();
+        assert_eq!(first_day.len(), 1);
+        assert_eq!(first_day[0].get_did(), "did:plc:user0001");
+
+        // Days already restored are left alone
+        let again = restore_archives(&store, "archives", &reader, None, None)
+            .await
+            .unwrap();
+        assert_eq!(again.skipped, vec![day(1), day(2)]);
+        assert_eq!(
+            again.restored,
+            vec![archive_dir.join("jetstream_archive_2024-01-05.db")]
+        );
+        assert_eq!(again.partitions, 3);
+
+        live.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
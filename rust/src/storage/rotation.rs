@@ -1,5 +1,8 @@
 use crate::models::errors::TurboResult;
+use crate::storage::partitions::partition_start_us;
+use crate::storage::{SQLitePragmaConfig, ShardedSQLiteStore};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::time::interval;
 use tracing::{error, info, trace, warn};
@@ -26,7 +29,14 @@ impl DatabaseRotator {
         }
     }
 
-    pub async fn start_rotation_task(&self) -> TurboResult<tokio::task::JoinHandle<()>> {
+    /// Every `rotation_interval`, moves the rolled-up records of `store` into a
+    /// new `jetstream_{unix}.db` file in the directory, then removes rotated
+    /// files past `max_databases` or started more than `cleanup_age` ago.
+    pub async fn start_rotation_task(
+        &self,
+        store: Arc<ShardedSQLiteStore>,
+        pragma_config: SQLitePragmaConfig,
+    ) -> TurboResult<tokio::task::JoinHandle<()>> {
         let db_dir = self.db_dir.clone();
         let rotation_interval = self.rotation_interval;
         let max_databases = self.max_databases;
@@ -42,11 +52,19 @@ impl DatabaseRotator {
             interval.tick().await; // Skip first tick
 
             loop {
-                if let Err(e) = Self::rotate_databases(&db_dir, max_databases, cleanup_age).await {
+                interval.tick().await;
+
+                if let Err(e) = Self::rotate_databases(
+                    &db_dir,
+                    &store,
+                    pragma_config,
+                    max_databases,
+                    cleanup_age,
+                )
+                .await
+                {
                     error!("Database rotation failed: {}", e);
                 }
-
-                interval.tick().await;
             }
         });
 
@@ -55,40 +73,32 @@ impl DatabaseRotator {
 
     async fn rotate_databases(
         db_dir: &Path,
+        store: &ShardedSQLiteStore,
+        pragma_config: SQLitePragmaConfig,
         max_databases: usize,
         cleanup_age: Duration,
     ) -> TurboResult<()> {
         trace!("Starting database rotation");
-
-        // Create timestamped database name
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        let new_db_name = format!("jetstream_{timestamp}.db");
-        let new_db_path = db_dir.join(&new_db_name);
-
-        info!("Creating new database: {}", new_db_name);
-
-        // Create the new database (this will be handled by SQLiteStore)
-        // For now, just ensure the directory exists
         tokio::fs::create_dir_all(db_dir).await?;
 
-        // List existing databases
-        let mut databases = Self::list_databases(db_dir).await?;
-
-        // Add the new database to the list
-        databases.push((new_db_name, new_db_path));
+        let moved = store.rotate(db_dir, pragma_config).await?;
+        if moved > 0 {
+            info!("Rotated {} records out of the live database", moved);
+        }
 
-        // Sort by timestamp (newest first)
+        // List rotated databases (not daily archives), newest first
+        let mut databases: Vec<_> = Self::list_databases(db_dir)
+            .await?
+            .into_iter()
+            .filter_map(|(name, path)| Some((partition_start_us(&name)?, name, path)))
+            .collect();
         databases.sort_by(|a, b| b.0.cmp(&a.0));
 
         // Keep only the max_databases most recent
         if databases.len() > max_databases {
             let to_remove = databases.split_off(max_databases);
 
-            for (db_name, db_path) in to_remove {
+            for (_, db_name, db_path) in to_remove {
                 info!("Removing old database: {}", db_name);
 
                 // Also remove associated files (.wal, .shm, etc.)
@@ -102,14 +112,14 @@ impl DatabaseRotator {
             }
         }
 
-        // Clean up very old files
+        // Clean up very old rotated databases
         Self::cleanup_old_files(db_dir, cleanup_age).await?;
 
-        info!("Database rotation completed");
+        trace!("Database rotation completed");
         Ok(())
     }
 
-    async fn list_databases(db_dir: &Path) -> TurboResult<Vec<(String, PathBuf)>> {
+    pub(crate) async fn list_databases(db_dir: &Path) -> TurboResult<Vec<(String, PathBuf)>> {
         let mut entries = tokio::fs::read_dir(db_dir).await?;
         let mut databases = Vec::new();
 
@@ -127,7 +137,7 @@ impl DatabaseRotator {
         Ok(databases)
     }
 
-    async fn remove_database_files(db_path: &Path) -> TurboResult<()> {
+    pub(crate) async fn remove_database_files(db_path: &Path) -> TurboResult<()> {
         // Remove main database file
         if tokio::fs::metadata(db_path).await.is_ok() {
             tokio::fs::remove_file(db_path).await?;
@@ -149,32 +159,29 @@ impl DatabaseRotator {
         Ok(())
     }
 
+    /// Removes rotated databases that started more than `max_age` ago. Only
+    /// `jetstream_{unix}.db` files are considered, never the live database or
+    /// anything else sharing the directory.
     async fn cleanup_old_files(db_dir: &Path, max_age: Duration) -> TurboResult<()> {
-        let now = SystemTime::now();
-        let mut entries = tokio::fs::read_dir(db_dir).await?;
+        let cutoff_us = SystemTime::now()
+            .checked_sub(max_age)
+            .and_then(|cutoff| cutoff.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |cutoff| cutoff.as_micros() as i64);
         let mut removed_count = 0;
 
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-
-            if let Ok(metadata) = entry.metadata().await {
-                if let Ok(modified) = metadata.modified() {
-                    if let Ok(age) = now.duration_since(modified) {
-                        if age > max_age {
-                            info!("Removing old file: {}", path.display());
-                            if let Err(e) = tokio::fs::remove_file(&path).await {
-                                warn!("Failed to remove old file {}: {}", path.display(), e);
-                            } else {
-                                removed_count += 1;
-                            }
-                        }
-                    }
+        for (name, path) in Self::list_databases(db_dir).await? {
+            if partition_start_us(&name).is_some_and(|started_at_us| started_at_us < cutoff_us) {
+                info!("Removing old database: {}", path.display());
+                if let Err(e) = Self::remove_database_files(&path).await {
+                    warn!("Failed to remove old database {}: {}", path.display(), e);
+                } else {
+                    removed_count += 1;
                 }
             }
         }
 
         if removed_count > 0 {
-            info!("Cleaned up {} old files", removed_count);
+            info!("Cleaned up {} old databases", removed_count);
         }
 
         Ok(())
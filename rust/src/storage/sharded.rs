@@ -0,0 +1,541 @@
+//! Spreads record writes across several SQLite files so a full-firehose ingest
+//! isn't limited by a single writer. Records are routed by a stable hash of their
+//! DID, which keeps every record (and delete) for a repo in the same shard.
+
+use crate::models::{at_uri::AtUri, enriched::EnrichedRecord, TurboResult};
+use crate::storage::aggregates::HourlyCount;
+use crate::storage::sqlite::{
+    CleanupResult, DeleteMode, RecordStore, SQLitePragmaConfig, SQLiteStateSnapshot, SQLiteStore,
+};
+use crate::storage::threads::ThreadEntry;
+#[cfg(feature = "chaos")]
+use crate::utils::chaos::FaultInjector;
+use chrono::{DateTime, Utc};
+use futures::future::try_join_all;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+pub struct ShardedSQLiteStore {
+    shards: Vec<SQLiteStore>,
+    #[cfg(feature = "chaos")]
+    chaos: FaultInjector,
+}
+
+impl ShardedSQLiteStore {
+    /// Opens `shard_count` databases next to `db_path`. With a single shard the
+    /// path is used as-is, so existing single-file deployments keep their data.
+    pub async fn new<P: AsRef<Path>>(
+        db_path: P,
+        shard_count: usize,
+        pragma_config: SQLitePragmaConfig,
+    ) -> TurboResult<Self> {
+        let shard_count = shard_count.max(1);
+        let mut shards = Vec::with_capacity(shard_count);
+        for path in shard_paths(db_path.as_ref(), shard_count) {
+            shards.push(SQLiteStore::new(&path, pragma_config).await?);
+        }
+        if shard_count > 1 {
+            info!("Sharding SQLite writes across {} databases", shard_count);
+        }
+        Ok(Self {
+            shards,
+            #[cfg(feature = "chaos")]
+            chaos: FaultInjector::default(),
+        })
+    }
+
+    /// Opens the existing databases of a `shard_count` layout for queries only,
+    /// as `SQLiteStore::open_read_only` does. Fails if any shard is missing.
+    pub async fn open_read_only<P: AsRef<Path>>(
+        db_path: P,
+        shard_count: usize,
+    ) -> TurboResult<Self> {
+        let mut shards = Vec::with_capacity(shard_count.max(1));
+        for path in shard_paths(db_path.as_ref(), shard_count.max(1)) {
+            shards.push(SQLiteStore::open_read_only(&path).await?);
+        }
+        info!("Opened {} SQLite databases read-only", shards.len());
+        Ok(Self {
+            shards,
+            #[cfg(feature = "chaos")]
+            chaos: FaultInjector::default(),
+        })
+    }
+
+    pub fn with_delete_mode(mut self, delete_mode: DeleteMode) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|shard| shard.with_delete_mode(delete_mode))
+            .collect();
+        self
+    }
+
+    /// Injects faults into each batch write before it reaches a shard.
+    #[cfg(feature = "chaos")]
+    pub fn with_chaos(mut self, injector: FaultInjector) -> Self {
+        self.chaos = injector;
+        self
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn shards(&self) -> &[SQLiteStore] {
+        &self.shards
+    }
+
+    pub fn shard_for_did(&self, did: &str) -> usize {
+        shard_index(did, self.shards.len())
+    }
+
+    pub async fn get_record_by_uri(&self, at_uri: &str) -> TurboResult<Option<EnrichedRecord>> {
+        let Some((did, _, _)) = AtUri::components(at_uri) else {
+            return Ok(None);
+        };
+        self.shards[self.shard_for_did(did)]
+            .get_record_by_uri(at_uri)
+            .await
+    }
+
+    /// `SQLiteStore::remove_account_records` on each shard holding any of `dids`.
+    pub async fn remove_account_records(
+        &self,
+        dids: &[String],
+        mode: DeleteMode,
+    ) -> TurboResult<u64> {
+        let mut by_shard: Vec<Vec<String>> = vec![Vec::new(); self.shards.len()];
+        for did in dids {
+            by_shard[self.shard_for_did(did)].push(did.clone());
+        }
+        let affected = try_join_all(
+            self.shards
+                .iter()
+                .zip(&by_shard)
+                .map(|(shard, dids)| shard.remove_account_records(dids, mode)),
+        )
+        .await?;
+        Ok(affected.into_iter().sum())
+    }
+
+    /// `SQLiteStore::records_in_time_range` across every shard, merged newest first.
+    pub async fn records_in_time_range(
+        &self,
+        since_us: Option<i64>,
+        until_us: Option<i64>,
+        limit: u32,
+    ) -> TurboResult<Vec<EnrichedRecord>> {
+        let pages = try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.records_in_time_range(since_us, until_us, limit)),
+        )
+        .await?;
+        Ok(newest_first(pages.into_iter().flatten().collect(), limit))
+    }
+
+    /// `SQLiteStore::thread_entries` from every shard, since replies are
+    /// stored with their own author's shard.
+    pub async fn thread_entries(
+        &self,
+        root_uri: &str,
+        limit: u32,
+    ) -> TurboResult<Vec<ThreadEntry>> {
+        let entries = try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.thread_entries(root_uri, limit)),
+        )
+        .await?;
+        Ok(entries.into_iter().flatten().collect())
+    }
+
+    /// Rolls every shard up; returns the total number of records rolled up.
+    pub async fn roll_up_hourly(&self) -> TurboResult<u64> {
+        let rolled = try_join_all(self.shards.iter().map(SQLiteStore::roll_up_hourly)).await?;
+        Ok(rolled.into_iter().sum())
+    }
+
+    /// Rolls every shard up, then moves its rolled-up records into a
+    /// `jetstream_{unix}.db` file in `db_dir`, sharded like the live files and
+    /// named after the earliest record moved. Returns the number of records moved.
+    pub async fn rotate(
+        &self,
+        db_dir: &Path,
+        pragma_config: SQLitePragmaConfig,
+    ) -> TurboResult<u64> {
+        self.roll_up_hourly().await?;
+        let mut moved = 0;
+        for (index, shard) in self.shards.iter().enumerate() {
+            let Some((last_id, first_time_us)) = shard.rotation_bounds().await? else {
+                continue;
+            };
+            let started_at = first_time_us
+                .map(|time_us| time_us.div_euclid(1_000_000))
+                .unwrap_or_else(|| Utc::now().timestamp());
+            let rotated = db_dir.join(format!("jetstream_{started_at}.db"));
+            let target = &shard_paths(&rotated, self.shards.len())[index];
+            moved += shard
+                .move_records_into(target, last_id, pragma_config)
+                .await?;
+        }
+        Ok(moved)
+    }
+
+    /// `SQLiteStore::hourly_counts` from every shard, unmerged.
+    pub async fn hourly_counts(&self, since: i64, until: i64) -> TurboResult<Vec<HourlyCount>> {
+        let counts = try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.hourly_counts(since, until)),
+        )
+        .await?;
+        Ok(counts.into_iter().flatten().collect())
+    }
+
+    /// The oldest of the shards' newest `time_us`, so resuming from it can't skip
+    /// events a lagging shard never stored. Replays into the other shards are
+    /// dropped as duplicates.
+    pub async fn latest_time_us(&self) -> TurboResult<Option<i64>> {
+        let latest = try_join_all(self.shards.iter().map(SQLiteStore::latest_time_us)).await?;
+        Ok(latest.into_iter().flatten().min())
+    }
+
+    pub async fn count_records(&self) -> TurboResult<i64> {
+        let counts = try_join_all(self.shards.iter().map(SQLiteStore::count_records)).await?;
+        Ok(counts.into_iter().sum())
+    }
+
+    /// `None` if any shard lacks maintained counts.
+    pub async fn count_records_since(&self, since: Option<i64>) -> TurboResult<Option<i64>> {
+        let counts = try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.count_records_since(since)),
+        )
+        .await?;
+        Ok(counts.into_iter().sum())
+    }
+
+    pub async fn count_records_exact(&self) -> TurboResult<i64> {
+        let counts = try_join_all(self.shards.iter().map(SQLiteStore::count_records_exact)).await?;
+        Ok(counts.into_iter().sum())
+    }
+
+    pub async fn get_db_size(&self) -> TurboResult<i64> {
+        let sizes = try_join_all(self.shards.iter().map(SQLiteStore::get_db_size)).await?;
+        Ok(sizes.into_iter().sum())
+    }
+
+    /// Sizes and page counts are summed across shards; pragma settings are the
+    /// same on every shard and are reported from the first.
+    pub async fn get_state_snapshot(&self) -> TurboResult<SQLiteStateSnapshot> {
+        let snapshots =
+            try_join_all(self.shards.iter().map(SQLiteStore::get_state_snapshot)).await?;
+        let mut snapshots = snapshots.into_iter();
+        let mut total = snapshots
+            .next()
+            .expect("sharded store always has at least one shard");
+        for snapshot in snapshots {
+            total.db_size_bytes += snapshot.db_size_bytes;
+            total.wal_size_bytes = match (total.wal_size_bytes, snapshot.wal_size_bytes) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            };
+            total.page_count += snapshot.page_count;
+            total.freelist_count += snapshot.freelist_count;
+        }
+        Ok(total)
+    }
+
+    /// Cleans each shard in turn against an equal share of `max_size_bytes`, so
+    /// at most one shard is deleting or vacuuming at a time.
+    pub async fn cleanup_with_vacuum(
+        &self,
+        retention_days: u32,
+        max_size_bytes: i64,
+        vacuum_min_bytes_freed: u64,
+        vacuum_min_percent_freed: f64,
+        cleanup_chunk_size: u32,
+        cleanup_chunk_delay_ms: u64,
+    ) -> TurboResult<CleanupResult> {
+        let shard_max_size = max_size_bytes / self.shards.len() as i64;
+        let mut total = CleanupResult {
+            records_deleted: 0,
+            new_size_bytes: 0,
+            vacuum_pending: false,
+        };
+
+        for shard in &self.shards {
+            let result = shard
+                .cleanup_with_vacuum(
+                    retention_days,
+                    shard_max_size,
+                    vacuum_min_bytes_freed,
+                    vacuum_min_percent_freed,
+                    cleanup_chunk_size,
+                    cleanup_chunk_delay_ms,
+                )
+                .await?;
+            total.records_deleted += result.records_deleted;
+            total.new_size_bytes += result.new_size_bytes;
+            total.vacuum_pending |= result.vacuum_pending;
+        }
+
+        Ok(total)
+    }
+
+    /// Records skipped as already stored, across shards.
+    pub fn duplicates_skipped(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(SQLiteStore::duplicates_skipped)
+            .sum()
+    }
+
+    pub async fn close(&self) -> TurboResult<()> {
+        for shard in &self.shards {
+            shard.close().await?;
+        }
+        Ok(())
+    }
+}
+
+impl RecordStore for ShardedSQLiteStore {
+    /// Returned IDs are row IDs within each record's own shard, in input order,
+    /// for the records newly stored by this call.
+    async fn store_batch(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<Vec<i64>> {
+        #[cfg(feature = "chaos")]
+        self.chaos.inject().await?;
+        if self.shards.len() == 1 {
+            return self.shards[0].store_batch(records).await;
+        }
+        Ok(self
+            .insert_batch(records)
+            .await?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    async fn get_record_by_uri(&self, at_uri: &str) -> TurboResult<Option<EnrichedRecord>> {
+        ShardedSQLiteStore::get_record_by_uri(self, at_uri).await
+    }
+
+    async fn remove_older_than(&self, older_than: DateTime<Utc>) -> TurboResult<u64> {
+        let removed = try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| RecordStore::remove_older_than(shard, older_than)),
+        )
+        .await?;
+        Ok(removed.into_iter().sum())
+    }
+}
+
+impl ShardedSQLiteStore {
+    /// `SQLiteStore::insert_batch` across shards: one entry per input record.
+    async fn insert_batch(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<Vec<Option<i64>>> {
+        let mut batches: Vec<Vec<Arc<EnrichedRecord>>> = vec![Vec::new(); self.shards.len()];
+        let mut positions: Vec<Vec<usize>> = vec![Vec::new(); self.shards.len()];
+        for (position, record) in records.iter().enumerate() {
+            let shard = self.shard_for_did(record.get_did());
+            batches[shard].push(Arc::clone(record));
+            positions[shard].push(position);
+        }
+
+        let results = try_join_all(
+            self.shards
+                .iter()
+                .zip(&batches)
+                .filter(|(_, batch)| !batch.is_empty())
+                .map(|(shard, batch)| shard.insert_batch(batch)),
+        )
+        .await?;
+
+        let mut ids = vec![None; records.len()];
+        let shard_positions = positions.iter().filter(|positions| !positions.is_empty());
+        for (shard_ids, positions) in results.into_iter().zip(shard_positions) {
+            for (id, position) in shard_ids.into_iter().zip(positions) {
+                ids[*position] = id;
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// FNV-1a, chosen over the std hasher because shard placement must not change
+/// between runs or builds.
+fn shard_index(did: &str, shard_count: usize) -> usize {
+    let hash = did.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    (hash % shard_count as u64) as usize
+}
+
+/// Sorts `records` by `time_us`, newest first, and keeps the first `limit`.
+pub(crate) fn newest_first(mut records: Vec<EnrichedRecord>, limit: u32) -> Vec<EnrichedRecord> {
+    records.sort_by_key(|record| std::cmp::Reverse(record.message.time_us));
+    records.truncate(limit as usize);
+    records
+}
+
+/// `data/jetstream.db` becomes `data/jetstream.shard-0.db`, `data/jetstream.shard-1.db`, ...
+fn shard_paths(db_path: &Path, shard_count: usize) -> Vec<PathBuf> {
+    if shard_count == 1 {
+        return vec![db_path.to_path_buf()];
+    }
+    let stem = db_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = db_path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (0..shard_count)
+        .map(|index| db_path.with_file_name(format!("{stem}.shard-{index}{extension}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::PartitionedReader;
+    use crate::testing::fixtures::create_message_batch;
+
+    #[test]
+    fn test_shard_paths_and_index_are_stable() {
+        assert_eq!(
+            shard_paths(Path::new("data/jetstream.db"), 1),
+            vec![PathBuf::from("data/jetstream.db")]
+        );
+        assert_eq!(
+            shard_paths(Path::new("data/jetstream.db"), 2),
+            vec![
+                PathBuf::from("data/jetstream.shard-0.db"),
+                PathBuf::from("data/jetstream.shard-1.db")
+            ]
+        );
+        assert_eq!(shard_index("did:plc:abc", 4), shard_index("did:plc:abc", 4));
+        assert_eq!(shard_index("did:plc:abc", 1), 0);
+    }
+
+    #[tokio::test]
+    async fn test_records_are_routed_and_queried_across_shards() {
+        let dir = std::env::temp_dir().join(format!("test_sharded_{}", uuid::Uuid::new_v4()));
+        let store = ShardedSQLiteStore::new(
+            dir.join("jetstream.db"),
+            3,
+            SQLitePragmaConfig {
+                cache_size_kib: 1024,
+                mmap_size_mb: 0,
+                journal_size_limit_mb: 64,
+            },
+        )
+        .await
+        .unwrap();
+
+        let records: Vec<Arc<EnrichedRecord>> = create_message_batch(30)
+            .into_iter()
+            .map(|message| Arc::new(EnrichedRecord::new(message)))
+            .collect();
+        let ids = store.store_batch(&records).await.unwrap();
+        assert_eq!(ids.len(), 30);
+        assert_eq!(store.count_records().await.unwrap(), 30);
+
+        let mut used_shards = std::collections::HashSet::new();
+        for record in &records {
+            let uri = record.get_at_uri().unwrap();
+            let stored = store.get_record_by_uri(&uri).await.unwrap().unwrap();
+            assert_eq!(stored.get_did(), record.get_did());
+            used_shards.insert(store.shard_for_did(record.get_did()));
+        }
+        assert!(used_shards.len() > 1);
+
+        let snapshot = store.get_state_snapshot().await.unwrap();
+        assert_eq!(snapshot.db_size_bytes, store.get_db_size().await.unwrap());
+
+        store.close().await.unwrap();
+
+        let read_only = ShardedSQLiteStore::open_read_only(dir.join("jetstream.db"), 3)
+            .await
+            .unwrap();
+        assert_eq!(read_only.count_records().await.unwrap(), 30);
+        assert!(read_only
+            .get_record_by_uri(&records[7].get_at_uri().unwrap())
+            .await
+            .unwrap()
+            .is_some());
+        let unstored = Arc::new(EnrichedRecord::new(
+            crate::testing::fixtures::create_post_message(30),
+        ));
+        assert!(read_only.store_batch(&[unstored]).await.is_err());
+        read_only.close().await.unwrap();
+        // A layout with a shard that was never written isn't there to serve
+        assert!(
+            ShardedSQLiteStore::open_read_only(dir.join("jetstream.db"), 4)
+                .await
+                .is_err()
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_rotation_moves_rolled_up_records_into_rotated_files() {
+        let dir = std::env::temp_dir().join(format!("test_rotate_{}", uuid::Uuid::new_v4()));
+        let pragma_config = SQLitePragmaConfig {
+            cache_size_kib: 1024,
+            mmap_size_mb: 0,
+            journal_size_limit_mb: 64,
+        };
+        let store = Arc::new(
+            ShardedSQLiteStore::new(dir.join("jetstream.db"), 2, pragma_config)
+                .await
+                .unwrap(),
+        );
+        let records: Vec<Arc<EnrichedRecord>> = create_message_batch(10)
+            .into_iter()
+            .map(|message| Arc::new(EnrichedRecord::new(message)))
+            .collect();
+        store.store_batch(&records).await.unwrap();
+
+        assert_eq!(store.rotate(&dir, pragma_config).await.unwrap(), 10);
+        assert_eq!(store.count_records_exact().await.unwrap(), 0);
+        // The aggregates stay behind in the live files
+        assert_eq!(
+            store
+                .hourly_counts(0, i64::MAX)
+                .await
+                .unwrap()
+                .iter()
+                .filter(|count| count.dimension == "collection")
+                .map(|count| count.count)
+                .sum::<u64>(),
+            10
+        );
+        // Nothing left to move
+        assert_eq!(store.rotate(&dir, pragma_config).await.unwrap(), 0);
+
+        let reader = PartitionedReader::new(store.clone(), &dir);
+        assert_eq!(reader.refresh().await.unwrap(), 2);
+        for record in &records {
+            let uri = record.get_at_uri().unwrap();
+            assert!(reader.get_record_by_uri(&uri).await.unwrap().is_some());
+        }
+        assert_eq!(
+            reader
+                .records_in_time_range(None, None, 100)
+                .await
+                .unwrap()
+                .len(),
+            10
+        );
+
+        store.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
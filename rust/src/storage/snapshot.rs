@@ -0,0 +1,173 @@
+//! Portable slices of the record store: a fresh SQLite file with the full
+//! schema, holding the live records of a time range from every shard, rotated
+//! database and daily archive, that can be handed to someone without access to
+//! the running instance.
+
+use crate::models::{errors::TurboError, TurboResult};
+use crate::storage::partitions::PartitionedReader;
+use crate::storage::sqlite::{remove_database_and_sidecars, SQLitePragmaConfig, SQLiteStore};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Copies the live records with `since_us <= time_us < until_us` from every
+/// shard and every rotated or archived partition overlapping the range into a
+/// new database at `out`, then vacuums it and leaves WAL mode so the single
+/// file is the whole snapshot. A missing bound leaves that side of the range
+/// open. Refuses to overwrite an existing file, and removes what it wrote if
+/// the export fails. Returns the number of records copied.
+pub async fn export_snapshot<P: AsRef<Path>>(
+    reader: &PartitionedReader,
+    out: P,
+    since_us: Option<i64>,
+    until_us: Option<i64>,
+    pragma_config: SQLitePragmaConfig,
+) -> TurboResult<u64> {
+    let out = out.as_ref();
+    if out.exists() {
+        return Err(TurboError::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists", out.display()),
+        )));
+    }
+
+    let exported = async {
+        let sources = reader.database_paths(since_us, until_us).await?;
+        let snapshot = SQLiteStore::new(out, pragma_config).await?;
+        let mut rows = 0;
+        for source in &sources {
+            match snapshot.copy_records_from(source, since_us, until_us).await {
+                Ok(copied) => rows += copied,
+                Err(e) => {
+                    let _ = snapshot.close().await;
+                    return Err(e);
+                }
+            }
+        }
+        snapshot.close().await?;
+        SQLiteStore::finalize_standalone(out).await?;
+        Ok(rows)
+    }
+    .await;
+
+    match exported {
+        Ok(rows) => {
+            info!("Exported {} records to {}", rows, out.display());
+            Ok(rows)
+        }
+        Err(e) => {
+            if let Err(remove_error) = remove_database_and_sidecars(out).await {
+                warn!(
+                    "Failed to remove partial snapshot {}: {}",
+                    out.display(),
+                    remove_error
+                );
+            }
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::enriched::EnrichedRecord;
+    use crate::storage::{RecordStore, ShardedSQLiteStore};
+    use crate::testing::fixtures::create_message_batch;
+    use std::sync::Arc;
+
+    fn pragma_config() -> SQLitePragmaConfig {
+        SQLitePragmaConfig {
+            cache_size_kib: 1024,
+            mmap_size_mb: 0,
+            journal_size_limit_mb: 64,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_holds_the_time_range_from_every_shard_and_partition() {
+        let dir = std::env::temp_dir().join(format!("test_snapshot_{}", uuid::Uuid::new_v4()));
+        let records: Vec<Arc<EnrichedRecord>> = create_message_batch(5)
+            .into_iter()
+            .map(|message| Arc::new(EnrichedRecord::new(message)))
+            .collect();
+        let time_us = |i: usize| records[i].message.time_us.unwrap() as i64;
+
+        // The two oldest records were rotated out before the rest arrived
+        let rotated = SQLiteStore::new(dir.join("jetstream_1.db"), pragma_config())
+            .await
+            .unwrap();
+        rotated.store_batch(&records[..2]).await.unwrap();
+        rotated.close().await.unwrap();
+        let store = Arc::new(
+            ShardedSQLiteStore::new(dir.join("jetstream.db"), 2, pragma_config())
+                .await
+                .unwrap(),
+        );
+        store.store_batch(&records[2..]).await.unwrap();
+        let reader = PartitionedReader::new(Arc::clone(&store), &dir);
+
+        let out = dir.join("snapshot.db");
+        let rows = export_snapshot(
+            &reader,
+            &out,
+            Some(time_us(1)),
+            Some(time_us(4)),
+            pragma_config(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(rows, 3);
+        assert!(!dir.join("snapshot.db-wal").exists());
+        // Header bytes 18-19 are the file format versions: 1 is rollback journal, 2 is WAL
+        assert_eq!(&std::fs::read(&out).unwrap()[18..20], &[1, 1]);
+
+        let snapshot = SQLiteStore::open_read_only(&out).await.unwrap();
+        assert_eq!(snapshot.count_records_exact().await.unwrap(), 3);
+        for (index, exported) in [(0, false), (1, true), (2, true), (4, false)] {
+            let found = snapshot
+                .get_record_by_uri(&records[index].get_at_uri().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(found.is_some(), exported, "record {index}");
+        }
+        snapshot.close().await.unwrap();
+
+        assert!(export_snapshot(&reader, &out, None, None, pragma_config())
+            .await
+            .is_err());
+        store.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_failed_snapshot_leaves_no_file_behind() {
+        let dir = std::env::temp_dir().join(format!("test_snapshot_{}", uuid::Uuid::new_v4()));
+        let rotated_path = dir.join("jetstream_1.db");
+        SQLiteStore::new(&rotated_path, pragma_config())
+            .await
+            .unwrap()
+            .close()
+            .await
+            .unwrap();
+        let store = Arc::new(
+            ShardedSQLiteStore::new(dir.join("jetstream.db"), 1, pragma_config())
+                .await
+                .unwrap(),
+        );
+        let reader = PartitionedReader::new(Arc::clone(&store), &dir);
+        assert_eq!(reader.refresh().await.unwrap(), 1);
+        // Damaged after being attached, so the copy from it fails
+        std::fs::write(&rotated_path, b"not a database").unwrap();
+
+        let out = dir.join("snapshot.db");
+        assert!(export_snapshot(&reader, &out, None, None, pragma_config())
+            .await
+            .is_err());
+        assert!(!out.exists());
+        assert!(!dir.join("snapshot.db-wal").exists());
+        assert!(!dir.join("snapshot.db-shm").exists());
+
+        store.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
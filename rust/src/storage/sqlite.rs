@@ -1,16 +1,36 @@
-use crate::models::{enriched::EnrichedRecord, TurboResult};
+use crate::models::{
+    at_uri::AtUri,
+    enriched::{EnrichedEventKind, EnrichedRecord, LEGACY_SCHEMA_VERSION},
+    TurboResult,
+};
+use crate::storage::aggregates::{HourlyCount, HOUR_SECONDS, RETAINED_HASHTAGS_PER_HOUR};
+use crate::storage::threads::{ThreadEntry, ThreadLink};
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
 use simd_json::to_string as simd_json_to_string;
 use sqlx::{
-    sqlite::SqliteConnectOptions, sqlite::SqliteJournalMode, sqlite::SqlitePoolOptions, Row,
-    SqliteConnection, SqlitePool,
+    sqlite::SqliteConnectOptions, sqlite::SqliteJournalMode, sqlite::SqlitePoolOptions,
+    ConnectOptions, Connection, Row, SqliteConnection, SqlitePool,
 };
+use std::collections::HashSet;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Instant;
 use tokio::time::{sleep, Duration};
 use tracing::{error, info, instrument, trace, warn};
 
+/// Stored in `PRAGMA user_version` once `initialize_schema` has run, so files
+/// opened without migrating, like rotated ones, can tell they're behind. Bump it
+/// with every schema change.
+const DATABASE_SCHEMA_VERSION: i64 = 1;
+
+/// Every column of `records`, in a fixed order for copying rows between files.
+const RECORD_COLUMNS: &str = "id, at_uri, did, time_us, message, message_metadata, \
+    created_at, hydrated_at, hydration_time_ms, api_calls_count, cache_hit_rate, \
+    cache_hits, cache_misses, deleted_at, schema_version, idempotency_key";
+
 #[derive(Debug, Clone, Serialize)]
 pub struct CleanupResult {
     pub records_deleted: u64,
@@ -38,16 +58,68 @@ pub struct SQLitePragmaConfig {
     pub journal_size_limit_mb: u64,
 }
 
+/// How delete events are applied to previously stored records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteMode {
+    /// Store the delete event as its own row and leave earlier rows untouched
+    #[default]
+    Keep,
+    /// Mark matching rows with `deleted_at` instead of storing the delete event
+    Tombstone,
+    /// Remove matching rows instead of storing the delete event
+    Remove,
+}
+
+/// Chunking used by `RecordStore::remove_older_than`, the same as the
+/// `CLEANUP_CHUNK_SIZE` and `CLEANUP_CHUNK_DELAY_MS` defaults.
+const CLEANUP_CHUNK_SIZE: u32 = 1000;
+const CLEANUP_CHUNK_DELAY_MS: u64 = 50;
+/// Values bound per `IN (...)` list, well under SQLite's variable limit.
+const IN_LIST_CHUNK: usize = 500;
+
+/// Where enriched records are persisted. Implement it to keep records
+/// somewhere other than SQLite and inject it with `TurboChargerBuilder::sinks`.
 pub trait RecordStore {
+    /// Stores a batch, returning the IDs of the records newly stored. Records
+    /// already stored (by idempotency key) should be skipped, since batches are
+    /// retried.
     fn store_batch(
         &self,
-        records: &[EnrichedRecord],
+        records: &[Arc<EnrichedRecord>],
     ) -> impl std::future::Future<Output = TurboResult<Vec<i64>>> + Send;
+
+    fn store_record(
+        &self,
+        record: &Arc<EnrichedRecord>,
+    ) -> impl std::future::Future<Output = TurboResult<Option<i64>>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let ids = self.store_batch(std::slice::from_ref(record)).await?;
+            Ok(ids.into_iter().next())
+        }
+    }
+
+    /// The stored record with this AT-URI, if it hasn't been deleted.
+    fn get_record_by_uri(
+        &self,
+        at_uri: &str,
+    ) -> impl std::future::Future<Output = TurboResult<Option<EnrichedRecord>>> + Send;
+
+    /// Removes records stored before `older_than`, returning how many.
+    fn remove_older_than(
+        &self,
+        older_than: DateTime<Utc>,
+    ) -> impl std::future::Future<Output = TurboResult<u64>> + Send;
 }
 
 pub struct SQLiteStore {
     pool: SqlitePool,
     db_path: String,
+    delete_mode: DeleteMode,
+    duplicates_skipped: AtomicU64,
 }
 
 impl SQLiteStore {
@@ -95,9 +167,98 @@ impl SQLiteStore {
         Ok(Self {
             pool,
             db_path: db_path_str,
+            delete_mode: DeleteMode::default(),
+            duplicates_skipped: AtomicU64::new(0),
+        })
+    }
+
+    /// Opens an existing database, such as a rotated-out file, for queries only.
+    /// A file written by an older release is migrated first, which needs write
+    /// access once.
+    pub async fn open_read_only<P: AsRef<Path>>(db_path: P) -> TurboResult<Self> {
+        let db_path_str = db_path.as_ref().to_string_lossy().to_string();
+        Self::migrate_if_outdated(&db_path_str).await?;
+        let pool = SqlitePoolOptions::new()
+            .max_connections(2)
+            .connect_with(
+                SqliteConnectOptions::new()
+                    .filename(&db_path_str)
+                    .read_only(true),
+            )
+            .await?;
+
+        trace!("Opened {} read-only", db_path_str);
+        Ok(Self {
+            pool,
+            db_path: db_path_str,
+            delete_mode: DeleteMode::default(),
+            duplicates_skipped: AtomicU64::new(0),
+        })
+    }
+
+    /// Opens an existing database without creating it, e.g. a rotated-out file
+    /// that deletes still have to reach. Files from older releases are migrated.
+    pub async fn open_existing<P: AsRef<Path>>(db_path: P) -> TurboResult<Self> {
+        let db_path_str = db_path.as_ref().to_string_lossy().to_string();
+        Self::migrate_if_outdated(&db_path_str).await?;
+        let pool = SqlitePoolOptions::new()
+            .max_connections(2)
+            .connect_with(
+                SqliteConnectOptions::new()
+                    .filename(&db_path_str)
+                    .create_if_missing(false),
+            )
+            .await?;
+
+        trace!("Opened existing database {}", db_path_str);
+        Ok(Self {
+            pool,
+            db_path: db_path_str,
+            delete_mode: DeleteMode::default(),
+            duplicates_skipped: AtomicU64::new(0),
         })
     }
 
+    pub fn with_delete_mode(mut self, delete_mode: DeleteMode) -> Self {
+        self.delete_mode = delete_mode;
+        self
+    }
+
+    /// Records skipped because a row with their idempotency key was stored.
+    pub fn duplicates_skipped(&self) -> u64 {
+        self.duplicates_skipped.load(Ordering::Relaxed)
+    }
+
+    /// Brings the existing file at `db_path` to the current schema if an older
+    /// release wrote it.
+    async fn migrate_if_outdated(db_path: &str) -> TurboResult<()> {
+        let mut conn = SqliteConnectOptions::new()
+            .filename(db_path)
+            .read_only(true)
+            .connect()
+            .await?;
+        let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+            .fetch_one(&mut conn)
+            .await?;
+        conn.close().await?;
+        if version >= DATABASE_SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        info!("Migrating {} to the current schema", db_path);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(
+                SqliteConnectOptions::new()
+                    .filename(db_path)
+                    .create_if_missing(false),
+            )
+            .await?;
+        let migrated = Self::initialize_schema(&pool).await;
+        pool.close().await;
+        migrated
+    }
+
     async fn initialize_schema(pool: &SqlitePool) -> TurboResult<()> {
         sqlx::query(
             r#"
@@ -114,22 +275,125 @@ impl SQLiteStore {
                 api_calls_count INTEGER,
                 cache_hit_rate REAL,
                 cache_hits INTEGER,
-                cache_misses INTEGER
+                cache_misses INTEGER,
+                deleted_at TEXT,
+                schema_version INTEGER,
+                idempotency_key TEXT
             );
             
             CREATE INDEX IF NOT EXISTS idx_records_at_uri ON records(at_uri);
             CREATE INDEX IF NOT EXISTS idx_records_did ON records(did);
             CREATE INDEX IF NOT EXISTS idx_records_time_us ON records(time_us);
             CREATE INDEX IF NOT EXISTS idx_records_created_at ON records(created_at);
+
+            CREATE TABLE IF NOT EXISTS threads (
+                uri TEXT PRIMARY KEY,
+                root_uri TEXT NOT NULL,
+                parent_uri TEXT,
+                record_id INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_threads_root_uri ON threads(root_uri);
+
+            CREATE TABLE IF NOT EXISTS hourly_counts (
+                hour INTEGER NOT NULL,
+                dimension TEXT NOT NULL,
+                value TEXT NOT NULL,
+                count INTEGER NOT NULL,
+                PRIMARY KEY (hour, dimension, value)
+            );
+
+            CREATE TABLE IF NOT EXISTS aggregation_state (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                last_record_id INTEGER NOT NULL
+            );
             "#,
         )
         .execute(pool)
         .await?;
 
+        // Databases created by older releases lack these columns
+        Self::ensure_column(pool, "deleted_at", "TEXT").await?;
+        Self::ensure_column(pool, "schema_version", "INTEGER").await?;
+        Self::ensure_column(pool, "idempotency_key", "TEXT").await?;
+        // Created after the column exists; rows from older releases are all NULL, which never conflicts
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_records_idempotency_key ON records(idempotency_key)",
+        )
+        .execute(pool)
+        .await?;
+        Self::ensure_record_counts(pool).await?;
+        sqlx::query(&format!("PRAGMA user_version = {DATABASE_SCHEMA_VERSION}"))
+            .execute(pool)
+            .await?;
+
         trace!("SQLite schema initialized");
         Ok(())
     }
 
+    /// Keeps `record_counts` (rows per `time_us` hour) in step with `records`
+    /// through triggers, so every write and delete path updates it in the same
+    /// transaction. Databases from older releases are counted once here.
+    async fn ensure_record_counts(pool: &SqlitePool) -> TurboResult<()> {
+        let mut tx = pool.begin().await?;
+        let (tracked,): (bool,) = sqlx::query_as(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'record_counts'",
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if tracked {
+            return Ok(());
+        }
+
+        sqlx::query(&format!(
+            r#"
+            CREATE TABLE record_counts (
+                hour INTEGER PRIMARY KEY,
+                count INTEGER NOT NULL
+            );
+
+            CREATE TRIGGER records_count_insert AFTER INSERT ON records
+            BEGIN
+                INSERT INTO record_counts (hour, count)
+                VALUES (COALESCE(NEW.time_us / 3600000000 * {HOUR_SECONDS}, 0), 1)
+                ON CONFLICT (hour) DO UPDATE SET count = count + 1;
+            END;
+
+            CREATE TRIGGER records_count_delete AFTER DELETE ON records
+            BEGIN
+                UPDATE record_counts SET count = count - 1
+                WHERE hour = COALESCE(OLD.time_us / 3600000000 * {HOUR_SECONDS}, 0);
+            END;
+
+            INSERT INTO record_counts (hour, count)
+            SELECT COALESCE(time_us / 3600000000 * {HOUR_SECONDS}, 0), COUNT(*)
+            FROM records GROUP BY 1;
+            "#
+        ))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn ensure_column(pool: &SqlitePool, name: &str, definition: &str) -> TurboResult<()> {
+        let (exists,): (bool,) =
+            sqlx::query_as("SELECT COUNT(*) > 0 FROM pragma_table_info('records') WHERE name = ?")
+                .bind(name)
+                .fetch_one(pool)
+                .await?;
+
+        if !exists {
+            sqlx::query(&format!(
+                "ALTER TABLE records ADD COLUMN {name} {definition}"
+            ))
+            .execute(pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
     async fn apply_pragmas(
         conn: &mut SqliteConnection,
         pragma_config: SQLitePragmaConfig,
@@ -207,6 +471,7 @@ impl SQLiteStore {
         skip(self, record),
         fields(at_uri, duration_ms)
     )]
+    /// Returns the new row's ID, or the existing row's if the event was stored before.
     pub async fn store_record(&self, record: &EnrichedRecord) -> TurboResult<i64> {
         let start = Instant::now();
         let at_uri = record.get_at_uri().unwrap_or_default();
@@ -222,8 +487,11 @@ impl SQLiteStore {
             INSERT INTO records (
                 at_uri, did, time_us, message, message_metadata,
                 created_at, hydrated_at, hydration_time_ms,
-                api_calls_count, cache_hit_rate, cache_hits, cache_misses
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                api_calls_count, cache_hit_rate, cache_hits, cache_misses,
+                schema_version, idempotency_key
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(idempotency_key) DO UPDATE SET idempotency_key = excluded.idempotency_key
+            RETURNING id
             "#,
         )
         .bind(record.get_at_uri())
@@ -238,10 +506,17 @@ impl SQLiteStore {
         .bind(record.metrics.cache_hit_rate)
         .bind(record.metrics.cache_hits as i64)
         .bind(record.metrics.cache_misses as i64)
-        .execute(&self.pool)
+        .bind(record.schema_version as i64)
+        .bind(record.idempotency_key())
+        .fetch_one(&self.pool)
         .await?;
 
-        let id = result.last_insert_rowid();
+        // A replayed event resolves to the row already holding it
+        let id: i64 = result.try_get("id")?;
+        if let Some(link) = ThreadLink::of(record) {
+            let mut conn = self.pool.acquire().await?;
+            Self::insert_thread_links(&mut conn, &[(link, id)]).await?;
+        }
         let duration = start.elapsed().as_millis() as u64;
         tracing::Span::current().record("duration_ms", duration);
         trace!("Stored record with ID: {}", id);
@@ -249,13 +524,18 @@ impl SQLiteStore {
     }
 
     pub async fn get_record_by_uri(&self, at_uri: &str) -> TurboResult<Option<EnrichedRecord>> {
+        if !AtUri::is_valid(at_uri) {
+            return Ok(None);
+        }
+
         let row = sqlx::query(
             r#"
             SELECT at_uri, did, time_us, message, message_metadata,
                    created_at, hydrated_at, hydration_time_ms,
-                   api_calls_count, cache_hit_rate, cache_hits, cache_misses
+                   api_calls_count, cache_hit_rate, cache_hits, cache_misses,
+                   schema_version
             FROM records 
-            WHERE at_uri = ?
+            WHERE at_uri = ? AND deleted_at IS NULL
             LIMIT 1
             "#,
         )
@@ -279,8 +559,24 @@ impl SQLiteStore {
         let message: serde_json::Value = serde_json::from_str(&message_str)?;
         let hydrated_metadata: serde_json::Value = serde_json::from_str(&metadata_str)?;
 
-        let message = serde_json::from_value(message)?;
-        let hydrated_metadata = serde_json::from_value(hydrated_metadata)?;
+        let is_delete = message
+            .get("commit")
+            .and_then(|c| c.get("operation"))
+            .and_then(|o| o.as_str())
+            == Some("delete");
+        let event = if is_delete {
+            EnrichedEventKind::Delete
+        } else {
+            EnrichedEventKind::Record
+        };
+
+        // Rows written before versioning have no schema_version
+        let schema_version = row
+            .try_get::<Option<i64>, _>("schema_version")
+            .ok()
+            .flatten()
+            .map(|v| v as u32)
+            .unwrap_or(LEGACY_SCHEMA_VERSION);
 
         let hydrated_at: String = row.try_get("hydrated_at")?;
         let processed_at = DateTime::parse_from_rfc3339(&hydrated_at)
@@ -289,21 +585,518 @@ impl SQLiteStore {
             })?
             .with_timezone(&Utc);
 
-        Ok(EnrichedRecord {
-            message,
-            hydrated_metadata,
-            processed_at,
-            metrics: crate::models::enriched::ProcessingMetrics {
-                hydration_time_ms: row.try_get::<i64, _>("hydration_time_ms").unwrap_or(0) as u64,
-                api_calls_count: row.try_get::<i64, _>("api_calls_count").unwrap_or(0) as u32,
-                cache_hit_rate: row.try_get("cache_hit_rate").unwrap_or(0.0),
-                cache_hits: row.try_get::<i64, _>("cache_hits").unwrap_or(0) as u32,
-                cache_misses: row.try_get::<i64, _>("cache_misses").unwrap_or(0) as u32,
-            },
-        })
+        let metrics = crate::models::enriched::ProcessingMetrics {
+            hydration_time_ms: row.try_get::<i64, _>("hydration_time_ms").unwrap_or(0) as u64,
+            api_calls_count: row.try_get::<i64, _>("api_calls_count").unwrap_or(0) as u32,
+            cache_hit_rate: row.try_get("cache_hit_rate").unwrap_or(0.0),
+            cache_hits: row.try_get::<i64, _>("cache_hits").unwrap_or(0) as u32,
+            cache_misses: row.try_get::<i64, _>("cache_misses").unwrap_or(0) as u32,
+            // Not stored; the time from the event to its row being written is close enough
+            ingest_lag_ms: row
+                .try_get::<i64, _>("time_us")
+                .map(|time_us| (processed_at.timestamp_micros() - time_us).max(0) as u64 / 1000)
+                .unwrap_or(0),
+        };
+
+        let record = serde_json::json!({
+            "schema_version": schema_version,
+            "message": message,
+            "event": event,
+            "hydrated_metadata": hydrated_metadata,
+            "processed_at": processed_at,
+            "metrics": metrics,
+        });
+
+        Ok(EnrichedRecord::from_json_value(record)?)
+    }
+
+    /// Tombstones or removes stored rows matching the given AT-URIs, per the configured mode.
+    pub async fn apply_deletes(&self, at_uris: &[String]) -> TurboResult<u64> {
+        if at_uris.is_empty() || self.delete_mode == DeleteMode::Keep {
+            return Ok(0);
+        }
+
+        let deleted_at = Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
+        let mut affected = 0u64;
+
+        for at_uri in at_uris {
+            let result =
+                match self.delete_mode {
+                    DeleteMode::Tombstone => sqlx::query(
+                        "UPDATE records SET deleted_at = ? WHERE at_uri = ? AND deleted_at IS NULL",
+                    )
+                    .bind(&deleted_at)
+                    .bind(at_uri)
+                    .execute(&mut *tx)
+                    .await?,
+                    DeleteMode::Remove => {
+                        sqlx::query("DELETE FROM threads WHERE uri = ?")
+                            .bind(at_uri)
+                            .execute(&mut *tx)
+                            .await?;
+                        sqlx::query("DELETE FROM records WHERE at_uri = ?")
+                            .bind(at_uri)
+                            .execute(&mut *tx)
+                            .await?
+                    }
+                    DeleteMode::Keep => unreachable!("keep mode returns early"),
+                };
+            affected += result.rows_affected();
+        }
+
+        tx.commit().await?;
+        trace!(
+            "Applied {} delete events ({:?}), {} rows affected",
+            at_uris.len(),
+            self.delete_mode,
+            affected
+        );
+        Ok(affected)
+    }
+
+    /// Which of `at_uris` have stored rows that aren't tombstoned.
+    pub async fn stored_uris(&self, at_uris: &[String]) -> TurboResult<Vec<String>> {
+        self.stored_values("at_uri", at_uris).await
+    }
+
+    /// Which of `dids` have stored commits that aren't tombstoned.
+    pub async fn stored_dids(&self, dids: &[String]) -> TurboResult<Vec<String>> {
+        self.stored_values("did", dids).await
+    }
+
+    async fn stored_values(&self, column: &str, values: &[String]) -> TurboResult<Vec<String>> {
+        let mut stored = Vec::new();
+        for chunk in values.chunks(IN_LIST_CHUNK) {
+            let sql = format!(
+                "SELECT DISTINCT {column} FROM records \
+                 WHERE {column} IN ({}) AND at_uri IS NOT NULL AND deleted_at IS NULL",
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let mut query = sqlx::query_scalar::<_, String>(&sql);
+            for value in chunk {
+                query = query.bind(value);
+            }
+            stored.extend(query.fetch_all(&self.pool).await?);
+        }
+        Ok(stored)
+    }
+
+    /// Tombstones or removes every stored commit by `dids`, in one transaction.
+    /// Identity and account events are left in place as the record of what
+    /// happened.
+    pub async fn remove_account_records(
+        &self,
+        dids: &[String],
+        mode: DeleteMode,
+    ) -> TurboResult<u64> {
+        if dids.is_empty() || mode == DeleteMode::Keep {
+            return Ok(0);
+        }
+
+        let deleted_at = Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
+        let mut affected = 0u64;
+        for chunk in dids.chunks(IN_LIST_CHUNK) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let result = match mode {
+                DeleteMode::Keep => unreachable!("keep mode returns early"),
+                DeleteMode::Tombstone => {
+                    let sql = format!(
+                        "UPDATE records SET deleted_at = ? \
+                         WHERE did IN ({placeholders}) AND at_uri IS NOT NULL AND deleted_at IS NULL"
+                    );
+                    let mut query = sqlx::query(&sql).bind(&deleted_at);
+                    for did in chunk {
+                        query = query.bind(did);
+                    }
+                    query.execute(&mut *tx).await?
+                }
+                DeleteMode::Remove => {
+                    let sql = format!(
+                        "DELETE FROM threads WHERE uri IN \
+                         (SELECT at_uri FROM records WHERE did IN ({placeholders}) AND at_uri IS NOT NULL)"
+                    );
+                    let mut query = sqlx::query(&sql);
+                    for did in chunk {
+                        query = query.bind(did);
+                    }
+                    query.execute(&mut *tx).await?;
+
+                    let sql = format!(
+                        "DELETE FROM records WHERE did IN ({placeholders}) AND at_uri IS NOT NULL"
+                    );
+                    let mut query = sqlx::query(&sql);
+                    for did in chunk {
+                        query = query.bind(did);
+                    }
+                    query.execute(&mut *tx).await?
+                }
+            };
+            affected += result.rows_affected();
+        }
+        tx.commit().await?;
+        Ok(affected)
+    }
+
+    /// Live (not tombstoned) records with row ID greater than `after_id`, in ID
+    /// order, for paging through the whole table.
+    pub async fn records_after(
+        &self,
+        after_id: i64,
+        limit: u32,
+    ) -> TurboResult<Vec<(i64, EnrichedRecord)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, at_uri, did, time_us, message, message_metadata,
+                   created_at, hydrated_at, hydration_time_ms,
+                   api_calls_count, cache_hit_rate, cache_hits, cache_misses,
+                   schema_version
+            FROM records
+            WHERE id > ? AND deleted_at IS NULL
+            ORDER BY id
+            LIMIT ?
+            "#,
+        )
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut records = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id: i64 = row.try_get("id")?;
+            records.push((id, self.row_to_record(row).await?));
+        }
+        Ok(records)
+    }
+
+    /// The newest rolled-up record id and the earliest `time_us` up to it, or
+    /// `None` if no rolled-up records are left. Only these can be rotated out
+    /// without losing them from the hourly aggregates.
+    pub(crate) async fn rotation_bounds(&self) -> TurboResult<Option<(i64, Option<i64>)>> {
+        let (last_id, first_time_us): (Option<i64>, Option<i64>) = sqlx::query_as(
+            r#"
+            SELECT MAX(id), MIN(time_us) FROM records
+            WHERE id <= COALESCE((SELECT last_record_id FROM aggregation_state WHERE id = 1), 0)
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(last_id.map(|last_id| (last_id, first_time_us)))
+    }
+
+    /// Moves records with ids up to `last_id`, and their thread links, into the
+    /// database at `target`, creating it if needed. The two files commit
+    /// separately, so a crash in between leaves rows in both rather than in
+    /// neither; moving again skips the copies already there. Returns the number
+    /// of records moved.
+    pub(crate) async fn move_records_into(
+        &self,
+        target: &Path,
+        last_id: i64,
+        pragma_config: SQLitePragmaConfig,
+    ) -> TurboResult<u64> {
+        SQLiteStore::new(target, pragma_config)
+            .await?
+            .close()
+            .await?;
+
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("ATTACH DATABASE ?1 AS rotated")
+            .bind(target.to_string_lossy().into_owned())
+            .execute(&mut *conn)
+            .await?;
+        let moved = Self::move_into_attached(&mut conn, last_id).await;
+        let detached = sqlx::query("DETACH DATABASE rotated")
+            .execute(&mut *conn)
+            .await;
+        let moved = moved?;
+        detached?;
+        Ok(moved)
     }
 
+    async fn move_into_attached(conn: &mut SqliteConnection, last_id: i64) -> TurboResult<u64> {
+        let mut tx = conn.begin().await?;
+        sqlx::query(&format!(
+            "INSERT OR IGNORE INTO rotated.records ({RECORD_COLUMNS})
+             SELECT {RECORD_COLUMNS} FROM main.records WHERE id <= ?1"
+        ))
+        .bind(last_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT OR IGNORE INTO rotated.threads (uri, root_uri, parent_uri, record_id)
+             SELECT uri, root_uri, parent_uri, record_id FROM main.threads WHERE record_id <= ?1",
+        )
+        .bind(last_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM main.threads WHERE record_id <= ?1")
+            .bind(last_id)
+            .execute(&mut *tx)
+            .await?;
+        let moved = sqlx::query("DELETE FROM main.records WHERE id <= ?1")
+            .bind(last_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(moved)
+    }
+
+    /// Live records with `since_us <= time_us < until_us`, newest first. A
+    /// missing bound leaves that side of the range open.
+    pub async fn records_in_time_range(
+        &self,
+        since_us: Option<i64>,
+        until_us: Option<i64>,
+        limit: u32,
+    ) -> TurboResult<Vec<EnrichedRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT at_uri, did, time_us, message, message_metadata,
+                   created_at, hydrated_at, hydration_time_ms,
+                   api_calls_count, cache_hit_rate, cache_hits, cache_misses,
+                   schema_version
+            FROM records
+            WHERE deleted_at IS NULL
+              AND (?1 IS NULL OR time_us >= ?1)
+              AND (?2 IS NULL OR time_us < ?2)
+            ORDER BY time_us DESC
+            LIMIT ?3
+            "#,
+        )
+        .bind(since_us)
+        .bind(until_us)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut records = Vec::with_capacity(rows.len());
+        for row in rows {
+            records.push(self.row_to_record(row).await?);
+        }
+        Ok(records)
+    }
+
+    /// Stored, non-deleted posts of the thread rooted at `root_uri`, including
+    /// the root itself, oldest first. Databases written before threads were
+    /// tracked have none.
+    pub async fn thread_entries(
+        &self,
+        root_uri: &str,
+        limit: u32,
+    ) -> TurboResult<Vec<ThreadEntry>> {
+        let (tracked,): (bool,) = sqlx::query_as(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'threads'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if !tracked {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query(
+            r#"
+            SELECT t.parent_uri, r.at_uri, r.did, r.time_us, r.message, r.message_metadata,
+                   r.created_at, r.hydrated_at, r.hydration_time_ms,
+                   r.api_calls_count, r.cache_hit_rate, r.cache_hits, r.cache_misses,
+                   r.schema_version
+            FROM threads t
+            JOIN records r ON r.id = t.record_id
+            WHERE t.root_uri = ? AND r.deleted_at IS NULL
+            ORDER BY r.time_us
+            LIMIT ?
+            "#,
+        )
+        .bind(root_uri)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut entries = Vec::with_capacity(rows.len());
+        for row in rows {
+            let parent_uri: Option<String> = row.try_get("parent_uri")?;
+            entries.push(ThreadEntry {
+                parent_uri,
+                record: self.row_to_record(row).await?,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Adds records stored since the last roll-up to `hourly_counts`, then
+    /// prunes hashtags outside each finished hour's top ones. Records are
+    /// counted once by id, so cleanup deleting them later leaves the counts be.
+    /// Returns the number of records rolled up.
+    pub async fn roll_up_hourly(&self) -> TurboResult<u64> {
+        const CHUNK_RECORDS: i64 = 50_000;
+        let mut total = 0u64;
+
+        loop {
+            let mut tx = self.pool.begin().await?;
+            let (watermark,): (i64,) = sqlx::query_as(
+                "SELECT COALESCE((SELECT last_record_id FROM aggregation_state WHERE id = 1), 0)",
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            let (max_id,): (i64,) = sqlx::query_as("SELECT COALESCE(MAX(id), 0) FROM records")
+                .fetch_one(&mut *tx)
+                .await?;
+            if max_id <= watermark {
+                tx.commit().await?;
+                break;
+            }
+            let upper = max_id.min(watermark + CHUNK_RECORDS);
+
+            for (dimension, counts) in [
+                (
+                    "collection",
+                    "SELECT hour, json_extract(message, '$.commit.collection') AS bucket, COUNT(*) AS total
+                     FROM batch WHERE json_extract(message, '$.commit.collection') IS NOT NULL
+                     GROUP BY 1, 2",
+                ),
+                (
+                    "language",
+                    "SELECT hour, json_extract(message_metadata, '$.detected_language') AS bucket, COUNT(*) AS total
+                     FROM batch WHERE json_extract(message_metadata, '$.detected_language') IS NOT NULL
+                     GROUP BY 1, 2",
+                ),
+                (
+                    "hashtag",
+                    "SELECT hour, lower(ltrim(tag.value, '#')) AS bucket, COUNT(*) AS total
+                     FROM batch, json_each(batch.message_metadata, '$.hashtags') AS tag
+                     WHERE ltrim(tag.value, '#') != ''
+                     GROUP BY 1, 2",
+                ),
+            ] {
+                sqlx::query(&format!(
+                    r#"
+                    WITH batch AS (
+                        SELECT (time_us / 3600000000) * {HOUR_SECONDS} AS hour, message, message_metadata
+                        FROM records
+                        WHERE id > ?1 AND id <= ?2 AND time_us IS NOT NULL
+                          AND json_extract(message, '$.commit.operation') = 'create'
+                    )
+                    INSERT INTO hourly_counts (hour, dimension, value, count)
+                    SELECT hour, '{dimension}', bucket, total FROM ({counts}) WHERE true
+                    ON CONFLICT (hour, dimension, value) DO UPDATE SET count = count + excluded.count
+                    "#
+                ))
+                .bind(watermark)
+                .bind(upper)
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            sqlx::query(
+                "INSERT INTO aggregation_state (id, last_record_id) VALUES (1, ?) ON CONFLICT (id) DO UPDATE SET last_record_id = excluded.last_record_id",
+            )
+            .bind(upper)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            total += (upper - watermark) as u64;
+        }
+
+        // The current hour is still filling up, so only earlier ones are pruned
+        let current_hour = Utc::now().timestamp() / HOUR_SECONDS * HOUR_SECONDS;
+        sqlx::query(
+            r#"
+            DELETE FROM hourly_counts WHERE rowid IN (
+                SELECT rowid FROM (
+                    SELECT rowid, ROW_NUMBER() OVER (PARTITION BY hour ORDER BY count DESC, value) AS rank
+                    FROM hourly_counts
+                    WHERE dimension = 'hashtag' AND hour < ?1
+                ) WHERE rank > ?2
+            )
+            "#,
+        )
+        .bind(current_hour)
+        .bind(RETAINED_HASHTAGS_PER_HOUR)
+        .execute(&self.pool)
+        .await?;
+
+        if total > 0 {
+            trace!("Rolled up {} records into hourly counts", total);
+        }
+        Ok(total)
+    }
+
+    /// Hourly counts for hours starting in `[since, until)`, in unix seconds.
+    /// Databases written before aggregation existed have none.
+    pub async fn hourly_counts(&self, since: i64, until: i64) -> TurboResult<Vec<HourlyCount>> {
+        let (tracked,): (bool,) = sqlx::query_as(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'hourly_counts'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if !tracked {
+            return Ok(Vec::new());
+        }
+
+        let rows: Vec<(i64, String, String, i64)> = sqlx::query_as(
+            "SELECT hour, dimension, value, count FROM hourly_counts WHERE hour >= ? AND hour < ?",
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(hour, dimension, value, count)| HourlyCount {
+                hour,
+                dimension,
+                value,
+                count: count.max(0) as u64,
+            })
+            .collect())
+    }
+
+    /// `time_us` of the newest stored event, the point a Jetstream cursor resumes from.
+    pub async fn latest_time_us(&self) -> TurboResult<Option<i64>> {
+        let (latest,): (Option<i64>,) = sqlx::query_as("SELECT MAX(time_us) FROM records")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(latest)
+    }
+
+    /// Rows in `records`, tombstones included, from the maintained counts.
+    /// Databases opened without a schema upgrade fall back to a full scan.
     pub async fn count_records(&self) -> TurboResult<i64> {
+        match self.count_records_since(None).await? {
+            Some(count) => Ok(count),
+            None => self.count_records_exact().await,
+        }
+    }
+
+    /// Rows whose `time_us` falls in an hour starting at or after `since`
+    /// (unix seconds), or every row without a bound. `None` when the
+    /// database has no maintained counts.
+    pub async fn count_records_since(&self, since: Option<i64>) -> TurboResult<Option<i64>> {
+        let (tracked,): (bool,) = sqlx::query_as(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'record_counts'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if !tracked {
+            return Ok(None);
+        }
+
+        let since_hour = since.map(|since| since.div_euclid(HOUR_SECONDS) * HOUR_SECONDS);
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COALESCE(SUM(count), 0) FROM record_counts WHERE ?1 IS NULL OR hour >= ?1",
+        )
+        .bind(since_hour)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Some(count))
+    }
+
+    /// `SELECT COUNT(*)` over the whole table. Slow on large databases; meant
+    /// for checking the maintained counts, not for stats.
+    pub async fn count_records_exact(&self) -> TurboResult<i64> {
         let result = sqlx::query("SELECT COUNT(*) as count FROM records")
             .fetch_one(&self.pool)
             .await?;
@@ -342,6 +1135,14 @@ impl SQLiteStore {
             }
         }
 
+        if total_deleted > 0 {
+            sqlx::query(
+                "DELETE FROM threads WHERE NOT EXISTS (SELECT 1 FROM records WHERE records.id = threads.record_id)",
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
         info!("Cleaned up {} old records", total_deleted);
         Ok(total_deleted)
     }
@@ -457,7 +1258,7 @@ impl SQLiteStore {
             0.0
         };
 
-        let should_vacuum = bytes_freed as i64 >= vacuum_min_bytes_freed as i64
+        let should_vacuum = bytes_freed >= vacuum_min_bytes_freed as i64
             || percent_freed >= vacuum_min_percent_freed;
 
         let mut vacuum_pending = false;
@@ -496,6 +1297,134 @@ impl SQLiteStore {
         })
     }
 
+    /// Copies every row of the database at `source_path` into this one, skipping
+    /// records already present (by AT-URI, or DID and `time_us` for events
+    /// without one). A source written by an older release is migrated first.
+    /// Returns the number of rows copied.
+    pub async fn merge_from<P: AsRef<Path>>(&self, source_path: P) -> TurboResult<u64> {
+        Self::migrate_if_outdated(&source_path.as_ref().to_string_lossy()).await?;
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("ATTACH DATABASE ? AS source")
+            .bind(source_path.as_ref().to_string_lossy().to_string())
+            .execute(&mut *conn)
+            .await?;
+
+        let merged = sqlx::query(
+            r#"
+            INSERT INTO main.records (
+                at_uri, did, time_us, message, message_metadata,
+                created_at, hydrated_at, hydration_time_ms,
+                api_calls_count, cache_hit_rate, cache_hits, cache_misses,
+                deleted_at, schema_version
+            )
+            SELECT s.at_uri, s.did, s.time_us, s.message, s.message_metadata,
+                   s.created_at, s.hydrated_at, s.hydration_time_ms,
+                   s.api_calls_count, s.cache_hit_rate, s.cache_hits, s.cache_misses,
+                   s.deleted_at, s.schema_version
+            FROM source.records s
+            WHERE NOT EXISTS (
+                SELECT 1 FROM main.records r
+                WHERE r.at_uri IS s.at_uri
+                  AND (s.at_uri IS NOT NULL OR (r.did IS s.did AND r.time_us IS s.time_us))
+            )
+            ORDER BY s.id
+            "#,
+        )
+        .execute(&mut *conn)
+        .await;
+
+        // Detach even if the copy failed so the pooled connection stays usable
+        sqlx::query("DETACH DATABASE source")
+            .execute(&mut *conn)
+            .await?;
+        Ok(merged?.rows_affected())
+    }
+
+    /// Copies the live records of the database at `source_path` with
+    /// `since_us <= time_us < until_us` into this one, oldest row first. A
+    /// missing bound leaves that side of the range open. A source written by an
+    /// older release is migrated first. Returns the number of rows copied.
+    pub async fn copy_records_from<P: AsRef<Path>>(
+        &self,
+        source_path: P,
+        since_us: Option<i64>,
+        until_us: Option<i64>,
+    ) -> TurboResult<u64> {
+        let source_path = source_path.as_ref().to_string_lossy().to_string();
+        Self::migrate_if_outdated(&source_path).await?;
+
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("ATTACH DATABASE ? AS source")
+            .bind(source_path)
+            .execute(&mut *conn)
+            .await?;
+
+        let copied = sqlx::query(
+            r#"
+            INSERT INTO main.records (
+                at_uri, did, time_us, message, message_metadata,
+                created_at, hydrated_at, hydration_time_ms,
+                api_calls_count, cache_hit_rate, cache_hits, cache_misses,
+                schema_version, idempotency_key
+            )
+            SELECT at_uri, did, time_us, message, message_metadata,
+                   created_at, hydrated_at, hydration_time_ms,
+                   api_calls_count, cache_hit_rate, cache_hits, cache_misses,
+                   schema_version, idempotency_key
+            FROM source.records
+            WHERE deleted_at IS NULL
+              AND (?1 IS NULL OR time_us >= ?1)
+              AND (?2 IS NULL OR time_us < ?2)
+            ORDER BY id
+            "#,
+        )
+        .bind(since_us)
+        .bind(until_us)
+        .execute(&mut *conn)
+        .await;
+
+        // Detach even if the copy failed so the pooled connection stays usable
+        sqlx::query("DETACH DATABASE source")
+            .execute(&mut *conn)
+            .await?;
+        // Closed here rather than handed back on drop, which happens on a spawned
+        // task that `close` on the pool doesn't always wait for
+        conn.close().await?;
+        Ok(copied?.rows_affected())
+    }
+
+    pub async fn vacuum(&self) -> TurboResult<()> {
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Takes the closed database at `db_path` out of WAL mode and vacuums it, so
+    /// the file is complete on its own and opens anywhere, read-only media
+    /// included. Leaving WAL needs the only connection to the file, so close
+    /// any store over it first.
+    pub async fn finalize_standalone<P: AsRef<Path>>(db_path: P) -> TurboResult<()> {
+        use sqlx::Connection;
+
+        let mut conn = SqliteConnection::connect_with(
+            &SqliteConnectOptions::new()
+                .filename(db_path.as_ref())
+                .create_if_missing(false)
+                .journal_mode(SqliteJournalMode::Delete),
+        )
+        .await?;
+        sqlx::query("VACUUM").execute(&mut conn).await?;
+        conn.close().await?;
+        Ok(())
+    }
+
+    /// Moves everything in the WAL into the main file, so it can be copied on its own.
+    pub async fn checkpoint(&self) -> TurboResult<()> {
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     pub async fn get_db_path(&self) -> &str {
         &self.db_path
     }
@@ -507,13 +1436,19 @@ impl SQLiteStore {
     }
 }
 
-impl RecordStore for SQLiteStore {
+impl SQLiteStore {
+    /// Stores `records`, returning one entry per input record: the new row ID, or
+    /// `None` for delete events applied to earlier rows and for events already
+    /// stored (matched by `EnrichedRecord::idempotency_key`).
     #[instrument(
         name = "sqlite_store_batch",
         skip(self, records),
-        fields(count, duration_ms)
+        fields(count, duplicates, duration_ms)
     )]
-    async fn store_batch(&self, records: &[EnrichedRecord]) -> TurboResult<Vec<i64>> {
+    pub(crate) async fn insert_batch(
+        &self,
+        records: &[Arc<EnrichedRecord>],
+    ) -> TurboResult<Vec<Option<i64>>> {
         let start = Instant::now();
 
         if records.is_empty() {
@@ -523,22 +1458,58 @@ impl RecordStore for SQLiteStore {
         let count = records.len();
         tracing::Span::current().record("count", count);
 
+        // Outside keep mode, deletes modify earlier rows rather than being stored themselves
+        let mut delete_uris = Vec::new();
+        let inserts: Vec<(usize, &EnrichedRecord)> = records
+            .iter()
+            .map(Arc::as_ref)
+            .enumerate()
+            .filter(|(_, record)| {
+                if self.delete_mode != DeleteMode::Keep && record.is_delete() {
+                    delete_uris.extend(record.get_at_uri());
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect();
+
         let now = Utc::now();
         let now_str = now.to_rfc3339();
 
         const MAX_PARAMS: usize = 999;
-        const COLUMNS: usize = 12;
+        const COLUMNS: usize = 14;
         const MAX_ROWS_PER_INSERT: usize = MAX_PARAMS / COLUMNS;
 
-        static SINGLE_ROW_PLACEHOLDER: &str = "(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
-
-        let mut all_ids = Vec::with_capacity(count);
-
-        for chunk in records.chunks(MAX_ROWS_PER_INSERT) {
-            let mut tx = self.pool.begin().await?;
+        static SINGLE_ROW_PLACEHOLDER: &str = "(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+        let mut all_ids = vec![None; count];
+        let mut duplicates = 0;
+
+        for chunk in inserts.chunks(MAX_ROWS_PER_INSERT) {
+            // Take the write lock up front so no other writer can store a replayed
+            // event between the duplicate check and the insert
+            let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+            let keys: Vec<Option<String>> = chunk
+                .iter()
+                .map(|(_, record)| record.idempotency_key())
+                .collect();
+            let mut seen = Self::existing_idempotency_keys(&mut tx, &keys).await?;
+            let fresh: Vec<(usize, &EnrichedRecord, Option<String>)> = chunk
+                .iter()
+                .zip(keys)
+                .filter(|(_, key)| key.as_ref().is_none_or(|key| seen.insert(key.clone())))
+                .map(|(&(position, record), key)| (position, record, key))
+                .collect();
+            duplicates += chunk.len() - fresh.len();
+
+            if fresh.is_empty() {
+                tx.commit().await?;
+                continue;
+            }
 
-            let placeholders: String = std::iter::repeat(SINGLE_ROW_PLACEHOLDER)
-                .take(chunk.len())
+            let placeholders: String = std::iter::repeat_n(SINGLE_ROW_PLACEHOLDER, fresh.len())
                 .collect::<Vec<_>>()
                 .join(", ");
 
@@ -546,14 +1517,14 @@ impl RecordStore for SQLiteStore {
                 r#"INSERT INTO records (
                     at_uri, did, time_us, message, message_metadata,
                     created_at, hydrated_at, hydration_time_ms,
-                    api_calls_count, cache_hit_rate, cache_hits, cache_misses
-                ) VALUES {}"#,
-                placeholders
+                    api_calls_count, cache_hit_rate, cache_hits, cache_misses,
+                    schema_version, idempotency_key
+                ) VALUES {placeholders}"#
             );
 
             let mut query = sqlx::query(&insert_sql);
 
-            for record in chunk {
+            for (_, record, key) in &fresh {
                 query = query
                     .bind(record.get_at_uri())
                     .bind(record.get_did())
@@ -566,23 +1537,129 @@ impl RecordStore for SQLiteStore {
                     .bind(record.metrics.api_calls_count as i64)
                     .bind(record.metrics.cache_hit_rate)
                     .bind(record.metrics.cache_hits as i64)
-                    .bind(record.metrics.cache_misses as i64);
+                    .bind(record.metrics.cache_misses as i64)
+                    .bind(record.schema_version as i64)
+                    .bind(key.as_deref());
             }
 
             let result = query.execute(&mut *tx).await?;
-            tx.commit().await?;
 
             let base_id = result.last_insert_rowid();
-            for i in 0..chunk.len() {
-                all_ids.push(base_id - (chunk.len() - 1 - i) as i64);
+            let mut links = Vec::new();
+            for (i, (position, record, _)) in fresh.iter().enumerate() {
+                let id = base_id - (fresh.len() - 1 - i) as i64;
+                all_ids[*position] = Some(id);
+                links.extend(ThreadLink::of(record).map(|link| (link, id)));
             }
+            Self::insert_thread_links(&mut tx, &links).await?;
+            tx.commit().await?;
+        }
+
+        if duplicates > 0 {
+            self.duplicates_skipped
+                .fetch_add(duplicates as u64, Ordering::Relaxed);
+            counter!("jetstream_turbo_duplicates_skipped_total", "sink" => "sqlite")
+                .increment(duplicates as u64);
+            tracing::Span::current().record("duplicates", duplicates);
         }
 
+        self.apply_deletes(&delete_uris).await?;
+
         let duration = start.elapsed().as_millis() as u64;
         tracing::Span::current().record("duration_ms", duration);
-        trace!("Stored batch of {} records", count);
+        trace!(
+            "Stored batch of {} records ({} duplicates skipped)",
+            count,
+            duplicates
+        );
         Ok(all_ids)
     }
+
+    /// Points each post at its thread root and parent. A post URI written again,
+    /// e.g. recreated after a delete, moves to the new row.
+    async fn insert_thread_links(
+        conn: &mut SqliteConnection,
+        links: &[(ThreadLink, i64)],
+    ) -> TurboResult<()> {
+        if links.is_empty() {
+            return Ok(());
+        }
+        let sql = format!(
+            r#"INSERT INTO threads (uri, root_uri, parent_uri, record_id) VALUES {}
+            ON CONFLICT(uri) DO UPDATE SET
+                root_uri = excluded.root_uri,
+                parent_uri = excluded.parent_uri,
+                record_id = excluded.record_id"#,
+            vec!["(?, ?, ?, ?)"; links.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for (link, id) in links {
+            query = query
+                .bind(&link.uri)
+                .bind(&link.root_uri)
+                .bind(link.parent_uri.as_deref())
+                .bind(id);
+        }
+        query.execute(conn).await?;
+        Ok(())
+    }
+
+    /// Which of `keys` are already stored.
+    async fn existing_idempotency_keys(
+        conn: &mut SqliteConnection,
+        keys: &[Option<String>],
+    ) -> TurboResult<HashSet<String>> {
+        let keys: Vec<&str> = keys.iter().flatten().map(String::as_str).collect();
+        if keys.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let sql = format!(
+            "SELECT idempotency_key FROM records WHERE idempotency_key IN ({})",
+            vec!["?"; keys.len()].join(", ")
+        );
+        let mut query = sqlx::query_scalar::<_, String>(&sql);
+        for key in keys {
+            query = query.bind(key);
+        }
+        Ok(query.fetch_all(conn).await?.into_iter().collect())
+    }
+}
+
+impl RecordStore for SQLiteStore {
+    /// Returned IDs are those of newly stored rows, in input order.
+    async fn store_batch(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<Vec<i64>> {
+        Ok(self
+            .insert_batch(records)
+            .await?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    async fn get_record_by_uri(&self, at_uri: &str) -> TurboResult<Option<EnrichedRecord>> {
+        SQLiteStore::get_record_by_uri(self, at_uri).await
+    }
+
+    async fn remove_older_than(&self, older_than: DateTime<Utc>) -> TurboResult<u64> {
+        self.cleanup_old_records(older_than, CLEANUP_CHUNK_SIZE, CLEANUP_CHUNK_DELAY_MS)
+            .await
+    }
+}
+
+/// Removes the database at `path` and the `-wal` and `-shm` files SQLite
+/// keeps beside it, whichever exist.
+pub(crate) async fn remove_database_and_sidecars(path: &Path) -> std::io::Result<()> {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        match tokio::fs::remove_file(&file).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -741,6 +1818,333 @@ mod tests {
         store.close().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_delete_modes_apply_to_stored_records() {
+        use crate::testing::{create_delete_message, create_post_message};
+
+        let keep_store = create_test_db().await;
+        keep_store
+            .store_batch(&[
+                Arc::new(EnrichedRecord::new(create_post_message(1))),
+                Arc::new(EnrichedRecord::new(create_delete_message(1))),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(keep_store.count_records().await.unwrap(), 2);
+        keep_store.close().await.unwrap();
+
+        let tombstone_store = create_test_db()
+            .await
+            .with_delete_mode(DeleteMode::Tombstone);
+        let ids = tombstone_store
+            .store_batch(&[
+                Arc::new(EnrichedRecord::new(create_post_message(1))),
+                Arc::new(EnrichedRecord::new(create_post_message(2))),
+                Arc::new(EnrichedRecord::new(create_delete_message(1))),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(ids.len(), 2, "delete events are not stored as rows");
+        let (tombstoned,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM records WHERE deleted_at IS NOT NULL")
+                .fetch_one(&tombstone_store.pool)
+                .await
+                .unwrap();
+        assert_eq!(tombstoned, 1);
+        assert_eq!(tombstone_store.count_records().await.unwrap(), 2);
+        tombstone_store.close().await.unwrap();
+
+        let remove_store = create_test_db().await.with_delete_mode(DeleteMode::Remove);
+        remove_store
+            .store_batch(&[
+                Arc::new(EnrichedRecord::new(create_post_message(1))),
+                Arc::new(EnrichedRecord::new(create_post_message(2))),
+            ])
+            .await
+            .unwrap();
+        remove_store
+            .store_batch(&[Arc::new(EnrichedRecord::new(create_delete_message(1)))])
+            .await
+            .unwrap();
+        assert_eq!(remove_store.count_records().await.unwrap(), 1);
+        assert!(remove_store
+            .get_record_by_uri("at://did:plc:user0001/app.bsky.feed.post/3mepgzgia0001")
+            .await
+            .unwrap()
+            .is_none());
+        remove_store.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_posts_are_linked_to_their_thread() {
+        use crate::testing::{create_delete_message, create_post_message};
+
+        let store = create_test_db().await.with_delete_mode(DeleteMode::Remove);
+        let root = EnrichedRecord::new(create_post_message(1));
+        let root_uri = root.get_at_uri().unwrap();
+        let mut reply_message = create_post_message(2);
+        reply_message
+            .commit
+            .as_mut()
+            .unwrap()
+            .record
+            .as_mut()
+            .unwrap()["reply"] = serde_json::json!({
+            "root": {"uri": root_uri, "cid": "bafyroot"},
+            "parent": {"uri": root_uri, "cid": "bafyroot"},
+        });
+        store
+            .store_batch(&[
+                Arc::new(root),
+                Arc::new(EnrichedRecord::new(reply_message)),
+                Arc::new(EnrichedRecord::new(create_post_message(3))),
+            ])
+            .await
+            .unwrap();
+
+        let entries = store.thread_entries(&root_uri, 10).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].parent_uri, None);
+        assert_eq!(entries[1].record.get_did(), "did:plc:user0002");
+        assert_eq!(entries[1].parent_uri.as_deref(), Some(root_uri.as_str()));
+
+        store
+            .store_batch(&[Arc::new(EnrichedRecord::new(create_delete_message(2)))])
+            .await
+            .unwrap();
+        assert_eq!(store.thread_entries(&root_uri, 10).await.unwrap().len(), 1);
+        store.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_records_are_rolled_up_into_hourly_counts() {
+        use crate::testing::{create_delete_message, create_post_message};
+
+        let store = create_test_db().await;
+        let records: Vec<Arc<EnrichedRecord>> = (1..=3)
+            .map(|i| {
+                let mut record = EnrichedRecord::new(create_post_message(i));
+                record.hydrated_metadata.hashtags = vec!["rust".to_string()];
+                if i == 1 {
+                    record.hydrated_metadata.hashtags.push("#Art".to_string());
+                    record.hydrated_metadata.detected_language = Some("en".to_string());
+                }
+                Arc::new(record)
+            })
+            .chain(std::iter::once(Arc::new(EnrichedRecord::new(
+                create_delete_message(1),
+            ))))
+            .collect();
+        store.store_batch(&records).await.unwrap();
+
+        assert_eq!(store.roll_up_hourly().await.unwrap(), 4);
+        // Nothing new to count, and removing the raw rows keeps the counts
+        assert_eq!(store.roll_up_hourly().await.unwrap(), 0);
+        store
+            .cleanup_old_records(Utc::now() + chrono::Duration::days(1), 100, 0)
+            .await
+            .unwrap();
+        assert_eq!(store.count_records().await.unwrap(), 0);
+
+        let mut counts: Vec<(String, String, u64)> = store
+            .hourly_counts(0, i64::MAX)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|count| (count.dimension, count.value, count.count))
+            .collect();
+        counts.sort();
+        let expected = [
+            ("collection", "app.bsky.feed.post", 3),
+            ("hashtag", "art", 1),
+            ("hashtag", "rust", 3),
+            ("language", "en", 1),
+        ]
+        .map(|(dimension, value, count)| (dimension.to_string(), value.to_string(), count));
+        assert_eq!(counts, expected);
+        store.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replayed_events_are_stored_once() {
+        use crate::testing::create_post_message;
+
+        let store = create_test_db().await;
+        let batch: Vec<Arc<EnrichedRecord>> = (1..=3)
+            .map(|i| Arc::new(EnrichedRecord::new(create_post_message(i))))
+            .collect();
+        let ids = store.store_batch(&batch).await.unwrap();
+        assert_eq!(ids.len(), 3);
+
+        // A cursor rewind replays the tail of the stream, overlapping the last batch
+        let replay: Vec<Arc<EnrichedRecord>> = (2..=4)
+            .map(|i| Arc::new(EnrichedRecord::new(create_post_message(i))))
+            .collect();
+        let positions = store.insert_batch(&replay).await.unwrap();
+        assert!(positions[0].is_none() && positions[1].is_none());
+        assert!(positions[2].is_some());
+        assert_eq!(store.count_records().await.unwrap(), 4);
+        assert_eq!(store.duplicates_skipped(), 2);
+
+        let existing = store.store_record(&batch[0]).await.unwrap();
+        assert_eq!(existing, ids[0]);
+        assert_eq!(store.count_records().await.unwrap(), 4);
+        assert_eq!(
+            store.latest_time_us().await.unwrap(),
+            create_post_message(4).time_us.map(|t| t as i64)
+        );
+
+        store.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_record_counts_are_maintained_and_seeded() {
+        let store = create_test_db().await;
+        let now_str = Utc::now().to_rfc3339();
+        let hour_us = HOUR_SECONDS * 1_000_000;
+        for (i, time_us) in [
+            Some(hour_us),
+            Some(3 * hour_us + 5),
+            Some(3 * hour_us),
+            None,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            sqlx::query(
+                "INSERT INTO records (at_uri, did, time_us, message, created_at, hydrated_at) VALUES (?, 'did:plc:a', ?, '{}', ?, ?)",
+            )
+            .bind(format!("at://did:plc:a/app.bsky.feed.post/{i}"))
+            .bind(time_us)
+            .bind(&now_str)
+            .bind(&now_str)
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query("DELETE FROM records WHERE time_us = ?")
+            .bind(3 * hour_us)
+            .execute(&store.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(store.count_records().await.unwrap(), 3);
+        assert_eq!(
+            store
+                .count_records_since(Some(3 * HOUR_SECONDS + 10))
+                .await
+                .unwrap(),
+            Some(1)
+        );
+
+        // A database from before the counts existed is counted once on open
+        sqlx::query(
+            "DROP TRIGGER records_count_insert; DROP TRIGGER records_count_delete; DROP TABLE record_counts;",
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+        assert_eq!(store.count_records_since(None).await.unwrap(), None);
+        assert_eq!(store.count_records().await.unwrap(), 3);
+        SQLiteStore::initialize_schema(&store.pool).await.unwrap();
+        assert_eq!(store.count_records_since(None).await.unwrap(), Some(3));
+        assert_eq!(store.count_records_exact().await.unwrap(), 3);
+
+        store.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_legacy_rows_without_schema_version_are_upgraded() {
+        let store = create_test_db().await;
+        let now_str = Utc::now().to_rfc3339();
+        let at_uri = "at://did:plc:legacy/app.bsky.feed.post/1";
+
+        sqlx::query(
+            r#"INSERT INTO records (at_uri, did, time_us, message, message_metadata, created_at, hydrated_at, hydration_time_ms, api_calls_count, cache_hit_rate, cache_hits, cache_misses)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#
+        )
+        .bind(at_uri)
+        .bind("did:plc:legacy")
+        .bind(1000i64)
+        .bind(r#"{"did":"did:plc:legacy","kind":"commit","commit":{"operation":"create","collection":"app.bsky.feed.post","rkey":"1","record":{"text":"old"}}}"#)
+        .bind(r#"{"hashtags":["old"]}"#)
+        .bind(&now_str)
+        .bind(&now_str)
+        .bind(100i64)
+        .bind(1i64)
+        .bind(0.5)
+        .bind(1i64)
+        .bind(1i64)
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        let record = store.get_record_by_uri(at_uri).await.unwrap().unwrap();
+        assert_eq!(
+            record.schema_version,
+            crate::models::enriched::ENRICHED_RECORD_SCHEMA_VERSION
+        );
+        assert_eq!(record.get_text(), Some("old"));
+        assert_eq!(record.hydrated_metadata.hashtags, vec!["old"]);
+
+        store.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_old_files_are_migrated_when_opened_read_only() {
+        let db_path = std::env::temp_dir().join(format!("test_sqlite_{}.db", uuid::Uuid::new_v4()));
+        let at_uri = "at://did:plc:legacy/app.bsky.feed.post/1";
+        let now_str = Utc::now().to_rfc3339();
+
+        // A rotated file from before deletes, schema versions and threads
+        let pool = SqlitePool::connect_with(
+            SqliteConnectOptions::new()
+                .filename(&db_path)
+                .create_if_missing(true),
+        )
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"CREATE TABLE records (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, at_uri TEXT, did TEXT, time_us INTEGER,
+                message TEXT NOT NULL, message_metadata TEXT, created_at TEXT NOT NULL,
+                hydrated_at TEXT NOT NULL, hydration_time_ms INTEGER, api_calls_count INTEGER,
+                cache_hit_rate REAL, cache_hits INTEGER, cache_misses INTEGER
+            )"#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"INSERT INTO records (at_uri, did, time_us, message, message_metadata, created_at, hydrated_at)
+               VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(at_uri)
+        .bind("did:plc:legacy")
+        .bind(1000i64)
+        .bind(r#"{"did":"did:plc:legacy","kind":"commit","commit":{"operation":"create","collection":"app.bsky.feed.post","rkey":"1","record":{"text":"old"}}}"#)
+        .bind(r#"{}"#)
+        .bind(&now_str)
+        .bind(&now_str)
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+
+        let store = SQLiteStore::open_read_only(&db_path).await.unwrap();
+        let record = store.get_record_by_uri(at_uri).await.unwrap().unwrap();
+        assert_eq!(record.get_text(), Some("old"));
+        assert!(store.thread_entries(at_uri, 10).await.unwrap().is_empty());
+        let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(version, DATABASE_SCHEMA_VERSION);
+        store.close().await.unwrap();
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
     #[tokio::test]
     async fn test_cleanup_with_vacuum_size_based() {
         let store = create_test_db().await;
@@ -756,8 +2160,8 @@ mod tests {
                 r#"INSERT INTO records (at_uri, did, time_us, message, message_metadata, created_at, hydrated_at, hydration_time_ms, api_calls_count, cache_hit_rate, cache_hits, cache_misses)
                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#
             )
-            .bind(format!("at://test{}.bsky.social/app.bsky.feed.post/1", i))
-            .bind(format!("did:plc:test{}", i))
+            .bind(format!("at://test{i}.bsky.social/app.bsky.feed.post/1"))
+            .bind(format!("did:plc:test{i}"))
             .bind(1000i64 + i as i64)
             .bind(r#"{"foo":"bar","extra":"data"}"#)
             .bind(r#"{}"#)
@@ -802,8 +2206,8 @@ mod tests {
                 r#"INSERT INTO records (at_uri, did, time_us, message, message_metadata, created_at, hydrated_at, hydration_time_ms, api_calls_count, cache_hit_rate, cache_hits, cache_misses)
                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#
             )
-            .bind(format!("at://recent{}.bsky.social/app.bsky.feed.post/1", i))
-            .bind(format!("did:plc:recent{}", i))
+            .bind(format!("at://recent{i}.bsky.social/app.bsky.feed.post/1"))
+            .bind(format!("did:plc:recent{i}"))
             .bind(1000i64 + i as i64)
             .bind(r#"{"foo":"bar"}"#)
             .bind(r#"{}"#)
@@ -0,0 +1,242 @@
+//! Reply threads rebuilt from stored posts. Every stored post gets a row in the
+//! `threads` table pointing at its root and parent, so all stored replies to a
+//! root can be found with one indexed lookup per database.
+
+use crate::models::{at_uri::AtUri, enriched::EnrichedRecord};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+const POST_COLLECTION: &str = "app.bsky.feed.post";
+
+/// Upper bound on posts returned for one thread.
+pub const MAX_THREAD_POSTS: u32 = 1000;
+
+/// Where a post sits in its thread. Top-level posts are their own root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ThreadLink {
+    pub uri: String,
+    pub root_uri: String,
+    pub parent_uri: Option<String>,
+}
+
+impl ThreadLink {
+    /// `None` for anything but a post create.
+    pub fn of(record: &EnrichedRecord) -> Option<Self> {
+        let commit = record.message.commit.as_ref()?;
+        if record.is_delete() || commit.collection.as_deref() != Some(POST_COLLECTION) {
+            return None;
+        }
+        let uri = record.get_at_uri()?;
+        let reply = commit.record.as_ref()?.get("reply");
+        let reply_uri = |field: &str| {
+            reply
+                .and_then(|reply| reply.get(field)?.get("uri")?.as_str())
+                .filter(|uri| AtUri::is_valid(uri))
+                .map(str::to_string)
+        };
+        match (reply_uri("root"), reply_uri("parent")) {
+            (Some(root_uri), parent_uri) => Some(Self {
+                parent_uri: parent_uri.or_else(|| Some(root_uri.clone())),
+                root_uri,
+                uri,
+            }),
+            (None, _) => Some(Self {
+                root_uri: uri.clone(),
+                parent_uri: None,
+                uri,
+            }),
+        }
+    }
+}
+
+/// A stored post belonging to a thread, as read back from one database.
+#[derive(Debug, Clone)]
+pub struct ThreadEntry {
+    pub parent_uri: Option<String>,
+    pub record: EnrichedRecord,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreadNode {
+    pub uri: String,
+    pub record: EnrichedRecord,
+    /// Oldest first
+    pub replies: Vec<ThreadNode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Thread {
+    pub root_uri: String,
+    pub post_count: usize,
+    /// `None` if the root post itself isn't stored
+    pub root: Option<ThreadNode>,
+    /// Replies whose parent isn't stored, each with the replies below it
+    pub detached: Vec<ThreadNode>,
+}
+
+impl Thread {
+    /// Assembles the reply tree under `root_uri`, or `None` if no post of the
+    /// thread is stored. Entries may come from several databases in any order.
+    pub fn build(root_uri: &str, entries: Vec<ThreadEntry>) -> Option<Self> {
+        let mut seen = HashSet::new();
+        let mut entries: Vec<(String, ThreadEntry)> = entries
+            .into_iter()
+            .filter_map(|entry| Some((entry.record.get_at_uri()?, entry)))
+            .filter(|(uri, _)| seen.insert(uri.clone()))
+            .collect();
+        if entries.is_empty() {
+            return None;
+        }
+        entries.sort_by_key(|(_, entry)| entry.record.message.time_us);
+        let post_count = entries.len();
+
+        let mut root = None;
+        let mut detached = Vec::new();
+        let mut children: HashMap<String, Vec<(String, EnrichedRecord)>> = HashMap::new();
+        for (uri, entry) in entries {
+            if uri == root_uri {
+                root = Some((uri, entry.record));
+                continue;
+            }
+            match entry.parent_uri {
+                Some(parent) if seen.contains(&parent) => children
+                    .entry(parent)
+                    .or_default()
+                    .push((uri, entry.record)),
+                _ => detached.push((uri, entry.record)),
+            }
+        }
+
+        Some(Self {
+            root_uri: root_uri.to_string(),
+            post_count,
+            root: root.map(|(uri, record)| attach_replies(uri, record, &mut children)),
+            detached: detached
+                .into_iter()
+                .map(|(uri, record)| attach_replies(uri, record, &mut children))
+                .collect(),
+        })
+    }
+
+    /// The thread without the posts `keep` rejects, or `None` if none are
+    /// left. Replies to a removed post become detached.
+    pub fn retain(self, keep: impl Fn(&EnrichedRecord) -> bool) -> Option<Self> {
+        let mut entries = Vec::with_capacity(self.post_count);
+        let mut nodes: Vec<ThreadNode> = self.root.into_iter().chain(self.detached).collect();
+        while let Some(node) = nodes.pop() {
+            nodes.extend(node.replies);
+            if keep(&node.record) {
+                entries.push(ThreadEntry {
+                    parent_uri: ThreadLink::of(&node.record).and_then(|link| link.parent_uri),
+                    record: node.record,
+                });
+            }
+        }
+        Self::build(&self.root_uri, entries)
+    }
+}
+
+fn attach_replies(
+    uri: String,
+    record: EnrichedRecord,
+    children: &mut HashMap<String, Vec<(String, EnrichedRecord)>>,
+) -> ThreadNode {
+    // Removing each parent's list as it's visited also stops at reply cycles
+    let replies = children
+        .remove(&uri)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(uri, record)| attach_replies(uri, record, children))
+        .collect();
+    ThreadNode {
+        uri,
+        record,
+        replies,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+
+    /// Post `index`, replying to post `parent` in the thread rooted at post `root`.
+    fn post(index: usize, reply_to: Option<(usize, usize)>) -> EnrichedRecord {
+        let mut message = create_post_message(index);
+        message.time_us = Some(index as u64);
+        if let Some((root, parent)) = reply_to {
+            let uri = |i: usize| EnrichedRecord::new(create_post_message(i)).get_at_uri();
+            let record = message.commit.as_mut().unwrap().record.as_mut().unwrap();
+            record["reply"] = serde_json::json!({
+                "root": {"uri": uri(root), "cid": "bafyroot"},
+                "parent": {"uri": uri(parent), "cid": "bafyparent"},
+            });
+        }
+        EnrichedRecord::new(message)
+    }
+
+    fn entry(record: EnrichedRecord) -> ThreadEntry {
+        ThreadEntry {
+            parent_uri: ThreadLink::of(&record).unwrap().parent_uri,
+            record,
+        }
+    }
+
+    #[test]
+    fn test_links_point_at_root_and_parent() {
+        let root = ThreadLink::of(&post(1, None)).unwrap();
+        assert_eq!(root.root_uri, root.uri);
+        assert_eq!(root.parent_uri, None);
+
+        let reply = ThreadLink::of(&post(3, Some((1, 2)))).unwrap();
+        assert_eq!(reply.root_uri, root.uri);
+        assert_eq!(reply.parent_uri, post(2, None).get_at_uri());
+    }
+
+    #[test]
+    fn test_thread_nests_replies_and_keeps_orphans() {
+        let root_uri = post(1, None).get_at_uri().unwrap();
+        let entries = vec![
+            entry(post(4, Some((1, 2)))),
+            entry(post(2, Some((1, 1)))),
+            entry(post(1, None)),
+            entry(post(3, Some((1, 1)))),
+            // Its parent, post 5, was never stored
+            entry(post(6, Some((1, 5)))),
+            entry(post(2, Some((1, 1)))),
+        ];
+
+        let thread = Thread::build(&root_uri, entries).unwrap();
+        assert_eq!(thread.post_count, 5);
+        let root = thread.root.unwrap();
+        let reply_dids: Vec<&str> = root.replies.iter().map(|r| r.record.get_did()).collect();
+        assert_eq!(reply_dids, vec!["did:plc:user0002", "did:plc:user0003"]);
+        assert_eq!(
+            root.replies[0].replies[0].record.get_did(),
+            "did:plc:user0004"
+        );
+        assert_eq!(thread.detached.len(), 1);
+        assert_eq!(thread.detached[0].record.get_did(), "did:plc:user0006");
+
+        assert!(Thread::build(&root_uri, Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_retain_detaches_replies_to_removed_posts() {
+        let root_uri = post(1, None).get_at_uri().unwrap();
+        let entries = vec![
+            entry(post(1, None)),
+            entry(post(2, Some((1, 1)))),
+            entry(post(3, Some((1, 2)))),
+        ];
+        let thread = Thread::build(&root_uri, entries).unwrap();
+
+        let thread = thread
+            .retain(|record| record.get_did() != "did:plc:user0002")
+            .unwrap();
+        assert_eq!(thread.post_count, 2);
+        assert!(thread.root.unwrap().replies.is_empty());
+        assert_eq!(thread.detached.len(), 1);
+        assert_eq!(thread.detached[0].record.get_did(), "did:plc:user0003");
+    }
+}
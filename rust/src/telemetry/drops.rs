@@ -0,0 +1,132 @@
+use metrics::counter;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Why a message (or part of one) never reached the sinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DropReason {
+    /// An upstream frame that couldn't be decoded
+    ParseError,
+    /// A firehose op without a `collection/rkey` path
+    MissingCollection,
+    /// Dropped because the ingest channel was full
+    ChannelFull,
+    /// Left to the instance owning the author's shard
+    OtherShard,
+    /// Blocked, or not on the allowlist
+    DidFilter,
+    /// A referenced post URI that isn't a valid AT-URI. Counts references
+    /// skipped rather than fetched; the record itself still goes through.
+    InvalidUri,
+    HydrationFailed,
+    LabelPolicy,
+    InvalidRecord,
+    Spam,
+    /// From an author who opted out of logged-out visibility
+    Privacy,
+}
+
+impl DropReason {
+    pub const ALL: [DropReason; 11] = [
+        DropReason::ParseError,
+        DropReason::MissingCollection,
+        DropReason::ChannelFull,
+        DropReason::OtherShard,
+        DropReason::DidFilter,
+        DropReason::InvalidUri,
+        DropReason::HydrationFailed,
+        DropReason::LabelPolicy,
+        DropReason::InvalidRecord,
+        DropReason::Spam,
+        DropReason::Privacy,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DropReason::ParseError => "parse_error",
+            DropReason::MissingCollection => "missing_collection",
+            DropReason::ChannelFull => "channel_full",
+            DropReason::OtherShard => "other_shard",
+            DropReason::DidFilter => "did_filter",
+            DropReason::InvalidUri => "invalid_uri",
+            DropReason::HydrationFailed => "hydration_failed",
+            DropReason::LabelPolicy => "label_policy",
+            DropReason::InvalidRecord => "invalid_record",
+            DropReason::Spam => "spam",
+            DropReason::Privacy => "privacy",
+        }
+    }
+}
+
+/// Drop counts by reason, with every reason present so a zero is explicit.
+pub type DropStats = BTreeMap<&'static str, u64>;
+
+/// Counts drops per reason, for stats and as
+/// `jetstream_turbo_dropped_total{reason}`. Clones share their counts, so one
+/// set can be handed to every stage that discards messages.
+#[derive(Debug, Clone, Default)]
+pub struct DropCounters {
+    counts: Arc<[AtomicU64; DropReason::ALL.len()]>,
+}
+
+impl DropCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, reason: DropReason, count: usize) {
+        if count == 0 {
+            return;
+        }
+        self.counts[reason as usize].fetch_add(count as u64, Ordering::Relaxed);
+        counter!("jetstream_turbo_dropped_total", "reason" => reason.as_str())
+            .increment(count as u64);
+    }
+
+    pub fn get(&self, reason: DropReason) -> u64 {
+        self.counts[reason as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> DropStats {
+        DropReason::ALL
+            .iter()
+            .map(|&reason| (reason.as_str(), self.get(reason)))
+            .collect()
+    }
+
+    /// Adds `other`'s counts to a snapshot of this set.
+    pub fn merged_with(&self, other: &DropCounters) -> DropStats {
+        DropReason::ALL
+            .iter()
+            .map(|&reason| (reason.as_str(), self.get(reason) + other.get(reason)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_are_shared_between_clones_and_merged() {
+        let source = DropCounters::new();
+        let pipeline = DropCounters::new();
+        let handle = pipeline.clone();
+
+        source.record(DropReason::ParseError, 2);
+        handle.record(DropReason::DidFilter, 3);
+        pipeline.record(DropReason::Spam, 0);
+
+        assert_eq!(pipeline.get(DropReason::DidFilter), 3);
+        let stats = pipeline.merged_with(&source);
+        assert_eq!(stats.len(), DropReason::ALL.len());
+        assert_eq!(stats["parse_error"], 2);
+        assert_eq!(stats["did_filter"], 3);
+        assert_eq!(stats["spam"], 0);
+        assert!(DropReason::ALL
+            .iter()
+            .enumerate()
+            .all(|(i, &reason)| reason as usize == i));
+    }
+}
@@ -1,13 +1,20 @@
 use crate::models::errors::TurboError;
+#[cfg(feature = "posthog")]
 use serde_json::json;
 use std::collections::HashMap;
 use std::time::Duration;
 use tokio::sync::{mpsc, oneshot};
-use tokio::time::{interval, timeout, Instant};
+#[cfg(feature = "posthog")]
+use tokio::time::interval;
+use tokio::time::{timeout, Instant};
 
+#[cfg(feature = "posthog")]
 const DEFAULT_BATCH_SIZE: usize = 50;
+#[cfg(feature = "posthog")]
 const DEFAULT_FLUSH_INTERVAL_SECS: u64 = 60;
 
+// Only read by the PostHog flush loop
+#[cfg_attr(not(feature = "posthog"), allow(dead_code))]
 #[derive(Debug, Clone)]
 pub struct ErrorEvent {
     pub error_type: String,
@@ -24,11 +31,13 @@ pub struct ErrorReporter {
     enabled: bool,
 }
 
+#[cfg_attr(not(feature = "posthog"), allow(dead_code))]
 enum ReporterMessage {
     Event(ErrorEvent),
     Flush(oneshot::Sender<()>),
 }
 
+#[cfg(feature = "posthog")]
 fn mask_api_key(key: &str) -> String {
     if key.len() <= 8 {
         return "****".to_string();
@@ -45,6 +54,15 @@ impl ErrorReporter {
                 tracing::info!("PostHog error reporting disabled (no POSTHOG_API_KEY configured)");
                 Self { tx, enabled: false }
             }
+            #[cfg(not(feature = "posthog"))]
+            Some(_) => {
+                drop((rx, host));
+                tracing::warn!(
+                    "POSTHOG_API_KEY is set but error reporting requires the posthog feature"
+                );
+                Self { tx, enabled: false }
+            }
+            #[cfg(feature = "posthog")]
             Some(key) => {
                 let host = host.unwrap_or_else(|| "https://us.i.posthog.com".to_string());
                 tracing::info!(
@@ -126,6 +144,30 @@ impl ErrorReporter {
         }
     }
 
+    /// Reports a condition worth a look that isn't an error in itself, such as
+    /// a sink staying slow, as a handled, non-critical event.
+    pub fn capture_alert(&self, alert_type: &str, message: &str, context: HashMap<&str, &str>) {
+        if !self.enabled {
+            return;
+        }
+
+        let event = ErrorEvent {
+            is_critical: false,
+            handled: true,
+            ..Self::unhandled_failure_event(alert_type, message, context)
+        };
+
+        match self.tx.try_send(ReporterMessage::Event(event)) {
+            Ok(()) => {}
+            Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!("Error buffer full, dropping alert event");
+            }
+            Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => {
+                tracing::warn!("Error reporter unavailable, dropping alert event");
+            }
+        }
+    }
+
     pub async fn flush_with_timeout(&self, timeout_duration: Duration) -> bool {
         if !self.enabled {
             return false;
@@ -176,11 +218,12 @@ impl ErrorReporter {
             TurboError::JetstreamConnection(_) => "JetstreamConnection",
             TurboError::WebSocketConnection(_) => "WebSocketConnection",
             TurboError::HttpRequest(_) => "HttpRequest",
-            TurboError::RateLimitExceeded => "RateLimitExceeded",
+            TurboError::RateLimited { .. } => "RateLimited",
             TurboError::InvalidApiResponse(_) => "InvalidApiResponse",
             TurboError::Configuration(_) => "Configuration",
             TurboError::MissingEnvVar(_) => "MissingEnvVar",
             TurboError::Database(_) => "Database",
+            #[cfg(feature = "redis")]
             TurboError::RedisOperation(_) => "RedisOperation",
             TurboError::JsonSerialization(_) => "JsonSerialization",
             TurboError::JsonDeserialization(_) => "JsonDeserialization",
@@ -195,6 +238,12 @@ impl ErrorReporter {
             TurboError::NotFound(_) => "NotFound",
             TurboError::PermissionDenied(_) => "PermissionDenied",
             TurboError::ExpiredToken(_) => "ExpiredToken",
+            #[cfg(feature = "arrow")]
+            TurboError::Arrow(_) => "Arrow",
+            #[cfg(feature = "s3")]
+            TurboError::ObjectStore(_) => "ObjectStore",
+            #[cfg(feature = "chaos")]
+            TurboError::InjectedFault(_) => "InjectedFault",
         }
         .to_string()
     }
@@ -217,6 +266,7 @@ impl ErrorReporter {
         }
     }
 
+    #[cfg(feature = "posthog")]
     async fn validate_connection(client: &posthog_rs::Client, api_key: &str) -> Result<(), String> {
         let mut test_event =
             posthog_rs::Event::new("posthog_connectivity_check", "jetstream-turbo");
@@ -242,12 +292,13 @@ impl ErrorReporter {
                 } else if error_str.contains("timeout") || error_str.contains("connection") {
                     Err("Network error - unable to reach host".to_string())
                 } else {
-                    Err(format!("Connection failed: {}", e))
+                    Err(format!("Connection failed: {e}"))
                 }
             }
         }
     }
 
+    #[cfg(feature = "posthog")]
     async fn flush_loop(client: posthog_rs::Client, mut rx: mpsc::Receiver<ReporterMessage>) {
         let mut flush_interval = interval(Duration::from_secs(DEFAULT_FLUSH_INTERVAL_SECS));
         let mut batch: Vec<ErrorEvent> = Vec::with_capacity(DEFAULT_BATCH_SIZE);
@@ -287,6 +338,7 @@ impl ErrorReporter {
         }
     }
 
+    #[cfg(feature = "posthog")]
     async fn flush_batch(client: &posthog_rs::Client, batch: &[ErrorEvent]) {
         let event_count = batch.len();
         tracing::debug!("Sending {} error events to PostHog", event_count);
@@ -348,6 +400,7 @@ impl ErrorReporter {
         }
     }
 
+    #[cfg(feature = "posthog")]
     fn attach_exception_properties(
         event: &mut posthog_rs::Event,
         error_type: &str,
@@ -374,14 +427,20 @@ impl ErrorReporter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "posthog")]
     use serde_json::Value;
+    #[cfg(feature = "posthog")]
     use wiremock::matchers::{method, path};
+    #[cfg(feature = "posthog")]
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[test]
     fn test_error_type_name() {
-        let error = TurboError::RateLimitExceeded;
-        assert_eq!(ErrorReporter::error_type_name(&error), "RateLimitExceeded");
+        let error = TurboError::RateLimited {
+            endpoint: "app.bsky.actor.getProfiles".to_string(),
+            retry_after: None,
+        };
+        assert_eq!(ErrorReporter::error_type_name(&error), "RateLimited");
 
         let error = TurboError::InvalidApiResponse("test error".to_string());
         assert_eq!(ErrorReporter::error_type_name(&error), "InvalidApiResponse");
@@ -425,6 +484,7 @@ mod tests {
         assert!(!reporter.flush_with_timeout(Duration::from_millis(50)).await);
     }
 
+    #[cfg(feature = "posthog")]
     #[tokio::test]
     async fn startup_validation_and_flush_emit_expected_payloads() {
         let mock_server = MockServer::start().await;
@@ -524,6 +584,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "posthog")]
     fn assert_exception_event(
         event: &Value,
         error_type: &str,
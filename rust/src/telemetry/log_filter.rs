@@ -0,0 +1,76 @@
+use crate::models::errors::{TurboError, TurboResult};
+use tracing::warn;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Swaps the process-wide `EnvFilter` at runtime, so verbosity can be raised
+/// during an incident without a restart dropping the cursor and caches.
+#[derive(Clone)]
+pub struct LogFilterHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+    initial: String,
+}
+
+impl LogFilterHandle {
+    /// Wraps `filter` in a reloadable layer, which must be the first layer
+    /// added to the registry.
+    pub fn new(filter: EnvFilter) -> (reload::Layer<EnvFilter, Registry>, Self) {
+        let initial = filter.to_string();
+        let (layer, handle) = reload::Layer::new(filter);
+        (layer, Self { handle, initial })
+    }
+
+    pub fn current(&self) -> TurboResult<String> {
+        self.handle
+            .with_current(|filter| filter.to_string())
+            .map_err(|e| TurboError::Internal(format!("log filter unavailable: {e}")))
+    }
+
+    /// Replaces the filter with `directives` in `RUST_LOG` syntax, e.g.
+    /// `info,jetstream_turbo_rs=debug`.
+    pub fn set(&self, directives: &str) -> TurboResult<String> {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|e| TurboError::InvalidMessage(format!("invalid log filter: {e}")))?;
+        let applied = filter.to_string();
+        self.handle
+            .reload(filter)
+            .map_err(|e| TurboError::Internal(format!("failed to reload log filter: {e}")))?;
+        // Warn so the change is visible under the default release filter
+        warn!("Log filter changed to {}", applied);
+        Ok(applied)
+    }
+
+    /// Restores the filter the process started with.
+    pub fn reset(&self) -> TurboResult<String> {
+        let initial = self.initial.clone();
+        self.set(&initial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_filter_can_be_changed_and_reset() {
+        let (layer, handle) = LogFilterHandle::new(EnvFilter::new("warn"));
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(tracing::Level::DEBUG));
+
+            assert_eq!(handle.set("debug").unwrap(), "debug");
+            assert_eq!(handle.current().unwrap(), "debug");
+            assert!(tracing::enabled!(tracing::Level::DEBUG));
+
+            assert!(matches!(
+                handle.set("jetstream_turbo_rs=loud"),
+                Err(TurboError::InvalidMessage(_))
+            ));
+            assert_eq!(handle.current().unwrap(), "debug");
+
+            assert_eq!(handle.reset().unwrap(), "warn");
+            assert!(!tracing::enabled!(tracing::Level::DEBUG));
+        });
+    }
+}
@@ -1,3 +1,11 @@
+mod drops;
 mod error_reporter;
+mod log_filter;
+mod prometheus;
+mod schema_drift;
 
+pub use drops::{DropCounters, DropReason, DropStats};
 pub use error_reporter::ErrorReporter;
+pub use log_filter::LogFilterHandle;
+pub use prometheus::{install_prometheus_recorder, prometheus_recorder};
+pub use schema_drift::UnknownFieldTracker;
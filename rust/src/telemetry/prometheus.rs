@@ -0,0 +1,43 @@
+use crate::models::errors::{TurboError, TurboResult};
+use metrics_exporter_prometheus::{
+    Matcher, PrometheusBuilder, PrometheusHandle, PrometheusRecorder,
+};
+use std::time::Duration;
+
+/// How often histogram samples are folded in between scrapes, so they don't
+/// pile up when nothing reads `/metrics`.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Upper bounds of the buckets of every `*_seconds` histogram, matching the
+/// per-route latency histograms.
+const SECONDS_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Recorder for the counters, gauges and histograms recorded through the
+/// `metrics` macros, rendered on `/api/v1/metrics` by its handle.
+pub fn prometheus_recorder() -> PrometheusRecorder {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), &SECONDS_BUCKETS)
+        .expect("bucket list is not empty")
+        .build_recorder()
+}
+
+/// Installs `prometheus_recorder` as the process-wide recorder and returns the
+/// handle that renders it. Runs its upkeep on the current Tokio runtime.
+pub fn install_prometheus_recorder() -> TurboResult<PrometheusHandle> {
+    let recorder = prometheus_recorder();
+    let handle = recorder.handle();
+    metrics::set_global_recorder(recorder)
+        .map_err(|e| TurboError::Internal(format!("failed to install metrics recorder: {e}")))?;
+
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+    Ok(handle)
+}
@@ -0,0 +1,75 @@
+use crate::models::jetstream::JetstreamMessage;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use metrics::counter;
+use tracing::warn;
+
+/// Counts field names in upstream payloads that the models don't recognize, so
+/// Jetstream schema changes show up in logs and metrics before they matter.
+#[derive(Debug, Default)]
+pub struct UnknownFieldTracker {
+    seen: DashMap<String, u64>,
+}
+
+impl UnknownFieldTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the message's unknown fields, warning the first time each name appears.
+    pub fn observe(&self, message: &JetstreamMessage) {
+        for field in message.unknown_fields() {
+            counter!("jetstream_turbo_unknown_fields_total", "field" => field.clone()).increment(1);
+            match self.seen.entry(field) {
+                Entry::Occupied(mut seen) => *seen.get_mut() += 1,
+                Entry::Vacant(new) => {
+                    warn!(
+                        field = %new.key(),
+                        "New field in upstream payload; preserving it in `extra`"
+                    );
+                    new.insert(1);
+                }
+            }
+        }
+    }
+
+    /// Unknown field names and how often each was seen, sorted by name.
+    pub fn snapshot(&self) -> Vec<(String, u64)> {
+        let mut fields: Vec<(String, u64)> = self
+            .seen
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        fields.sort();
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+
+    #[test]
+    fn test_tracker_counts_unknown_fields() {
+        let tracker = UnknownFieldTracker::new();
+        tracker.observe(&create_post_message(0));
+        assert!(tracker.snapshot().is_empty());
+
+        let mut message = create_post_message(1);
+        message.extra.insert("region".to_string(), "us-east".into());
+        message
+            .commit
+            .as_mut()
+            .unwrap()
+            .extra
+            .insert("prev".to_string(), serde_json::Value::Null);
+        tracker.observe(&message);
+        tracker.observe(&message);
+
+        assert_eq!(
+            tracker.snapshot(),
+            vec![("commit.prev".to_string(), 2), ("region".to_string(), 2)]
+        );
+    }
+}
@@ -6,22 +6,38 @@ use crate::models::{
     jetstream::JetstreamMessage,
 };
 use crate::storage::{EventPublisher, RecordStore};
-use futures::Stream;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use tokio::sync::Mutex;
+use std::sync::Arc;
+use tokio::sync::{Mutex, Notify};
 
 /// Mock implementation of `MessageSource` that yields a fixed set of messages.
 pub struct MockMessageSource {
     messages: Mutex<Vec<JetstreamMessage>>,
+    stays_open: bool,
     pub stream_messages_call_count: AtomicUsize,
+    /// Notified once an open-ended stream has handed over its messages
+    pub delivered: Arc<Notify>,
 }
 
 impl MockMessageSource {
     pub fn new(messages: Vec<JetstreamMessage>) -> Self {
         Self {
             messages: Mutex::new(messages),
+            stays_open: false,
             stream_messages_call_count: AtomicUsize::new(0),
+            delivered: Arc::new(Notify::new()),
+        }
+    }
+
+    /// A source whose stream stays open after its messages, like a live
+    /// connection with nothing more to say.
+    pub fn open_ended(messages: Vec<JetstreamMessage>) -> Self {
+        Self {
+            stays_open: true,
+            ..Self::new(messages)
         }
     }
 }
@@ -34,7 +50,15 @@ impl MessageSource for MockMessageSource {
             .fetch_add(1, Ordering::SeqCst);
         let messages = self.messages.lock().await.drain(..).collect::<Vec<_>>();
         let stream = futures::stream::iter(messages.into_iter().map(Ok));
-        Ok(Box::pin(stream))
+        if !self.stays_open {
+            return Ok(Box::pin(stream));
+        }
+        let delivered = Arc::clone(&self.delivered);
+        let rest = futures::stream::once(async move {
+            delivered.notify_one();
+            futures::future::pending().await
+        });
+        Ok(Box::pin(stream.chain(rest)))
     }
 }
 
@@ -47,6 +71,7 @@ pub struct MockProfileFetcher {
 }
 
 impl MockProfileFetcher {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             profiles: Mutex::new(std::collections::HashMap::new()),
@@ -82,6 +107,7 @@ pub struct MockPostFetcher {
 }
 
 impl MockPostFetcher {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             posts: Mutex::new(std::collections::HashMap::new()),
@@ -106,14 +132,46 @@ impl PostFetcher for MockPostFetcher {
     }
 }
 
+/// Mock `DataFetcher`: a profile and a post fetcher behind one handle.
+pub struct MockDataFetcher {
+    pub profiles: MockProfileFetcher,
+    pub posts: MockPostFetcher,
+}
+
+impl MockDataFetcher {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            profiles: MockProfileFetcher::new(),
+            posts: MockPostFetcher::new(),
+        }
+    }
+}
+
+impl ProfileFetcher for MockDataFetcher {
+    async fn bulk_fetch_profiles(
+        &self,
+        dids: &[String],
+    ) -> TurboResult<Vec<Option<BlueskyProfile>>> {
+        self.profiles.bulk_fetch_profiles(dids).await
+    }
+}
+
+impl PostFetcher for MockDataFetcher {
+    async fn bulk_fetch_posts(&self, uris: &[String]) -> TurboResult<Vec<Option<BlueskyPost>>> {
+        self.posts.bulk_fetch_posts(uris).await
+    }
+}
+
 /// Mock implementation of `RecordStore` that stores records in memory.
 pub struct MockRecordStore {
-    pub stored_records: Mutex<Vec<EnrichedRecord>>,
+    pub stored_records: Mutex<Vec<Arc<EnrichedRecord>>>,
     pub call_count: AtomicUsize,
     next_id: AtomicUsize,
 }
 
 impl MockRecordStore {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             stored_records: Mutex::new(Vec::new()),
@@ -128,27 +186,43 @@ impl MockRecordStore {
 }
 
 impl RecordStore for MockRecordStore {
-    async fn store_batch(&self, records: &[EnrichedRecord]) -> TurboResult<Vec<i64>> {
+    async fn store_batch(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<Vec<i64>> {
         self.call_count.fetch_add(1, Ordering::SeqCst);
         let mut stored = self.stored_records.lock().await;
         let mut ids = Vec::with_capacity(records.len());
         for record in records {
             let id = self.next_id.fetch_add(1, Ordering::SeqCst) as i64;
-            stored.push(record.clone());
+            stored.push(Arc::clone(record));
             ids.push(id);
         }
         Ok(ids)
     }
+
+    async fn get_record_by_uri(&self, at_uri: &str) -> TurboResult<Option<EnrichedRecord>> {
+        let stored = self.stored_records.lock().await;
+        Ok(stored
+            .iter()
+            .find(|record| record.get_at_uri().as_deref() == Some(at_uri))
+            .map(|record| EnrichedRecord::clone(record)))
+    }
+
+    async fn remove_older_than(&self, older_than: DateTime<Utc>) -> TurboResult<u64> {
+        let mut stored = self.stored_records.lock().await;
+        let before = stored.len();
+        stored.retain(|record| record.processed_at >= older_than);
+        Ok((before - stored.len()) as u64)
+    }
 }
 
 /// Mock implementation of `EventPublisher` that records published events.
 pub struct MockEventPublisher {
-    pub published_records: Mutex<Vec<EnrichedRecord>>,
+    pub published_records: Mutex<Vec<Arc<EnrichedRecord>>>,
     pub call_count: AtomicUsize,
     next_id: AtomicUsize,
 }
 
 impl MockEventPublisher {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             published_records: Mutex::new(Vec::new()),
@@ -163,13 +237,13 @@ impl MockEventPublisher {
 }
 
 impl EventPublisher for MockEventPublisher {
-    async fn publish_batch(&self, records: &[EnrichedRecord]) -> TurboResult<Vec<String>> {
+    async fn publish_batch(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<Vec<String>> {
         self.call_count.fetch_add(1, Ordering::SeqCst);
         let mut published = self.published_records.lock().await;
         let mut ids = Vec::with_capacity(records.len());
         for record in records {
             let id = self.next_id.fetch_add(1, Ordering::SeqCst);
-            published.push(record.clone());
+            published.push(Arc::clone(record));
             ids.push(format!("{}-{}", record.processed_at.timestamp_millis(), id));
         }
         Ok(ids)
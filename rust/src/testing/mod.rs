@@ -1,5 +1,9 @@
 pub mod fixtures;
 pub mod mocks;
+pub mod simulation;
 
 pub use fixtures::*;
+pub use generator::{get_posts_response, get_profiles_response, EventMix, MessageGenerator};
+pub use jetstream_turbo_models::generator;
 pub use mocks::*;
+pub use simulation::{write_recording, SimulatedApi, Simulation, SimulationReport};
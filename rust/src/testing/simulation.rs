@@ -0,0 +1,282 @@
+//! Deterministic simulation: feeds a recorded session through the full
+//! pipeline with the Bluesky API answered from canned responses, and reduces
+//! what reached the sinks to a digest. Two runs over the same recording and
+//! settings produce the same digest, so CI can pin hydration and sink output
+//! without network access:
+//!
+//! ```ignore
+//! let report = Simulation::from_recording("tests/data/session.ndjson")?.run().await?;
+//! assert_eq!(report.digest, EXPECTED_DIGEST);
+//! ```
+//!
+//! Time is virtual: each record is stamped with its event's `time_us` instead
+//! of the wall clock, and per-record timing metrics are left out of the digest.
+//! Batches are hydrated concurrently, so records are digested in event order
+//! rather than the order they happened to reach the sinks.
+
+use crate::client::{read_capture, PostFetcher, ProfileFetcher};
+use crate::config::Settings;
+use crate::models::{
+    bluesky::{
+        BlueskyPost, BlueskyProfile, GetPostsBulkResponse, GetPostsResponse, GetProfilesResponse,
+    },
+    enriched::{EnrichedRecord, ProcessingMetrics},
+    errors::{TurboError, TurboResult},
+    jetstream::JetstreamMessage,
+};
+use crate::testing::generator::{get_posts_response, get_profiles_response};
+use crate::testing::mocks::{MockEventPublisher, MockMessageSource, MockRecordStore};
+use crate::turbocharger::orchestrator::STREAM_ENDED;
+use crate::turbocharger::TurboChargerBuilder;
+use chrono::{DateTime, Utc};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+/// What reached the sinks during a simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationReport {
+    /// Messages in the recording
+    pub messages: usize,
+    pub stored: usize,
+    pub published: usize,
+    /// blake3 of the stored then published records, hex encoded
+    pub digest: String,
+}
+
+pub struct Simulation {
+    messages: Vec<JetstreamMessage>,
+    settings: Settings,
+}
+
+impl Simulation {
+    pub fn new(messages: Vec<JetstreamMessage>) -> Self {
+        Self {
+            messages,
+            settings: Settings::default(),
+        }
+    }
+
+    /// Loads a recording: a `FrameCapture` file or directory, or a file of
+    /// Jetstream frames, one per line.
+    pub fn from_recording<P: AsRef<Path>>(path: P) -> TurboResult<Self> {
+        let path = path.as_ref();
+        let is_capture = path.is_dir()
+            || path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with(".zst"));
+        let messages = if is_capture {
+            read_capture(path)?
+        } else {
+            let reader = BufReader::new(std::fs::File::open(path)?);
+            let mut messages = Vec::new();
+            for line in reader.lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    messages.push(serde_json::from_str(&line)?);
+                }
+            }
+            messages
+        };
+        Ok(Self::new(messages))
+    }
+
+    /// Settings the pipeline is built from, for simulating filters, spam
+    /// scoring and the like. Storage goes to a scratch directory regardless.
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub async fn run(self) -> TurboResult<SimulationReport> {
+        let dir = std::env::temp_dir().join(format!("simulation_{}", uuid::Uuid::new_v4()));
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            redis_url: String::new(),
+            standalone: false,
+            standalone_output_file: None,
+            ..self.settings
+        };
+        let messages = self.messages.len();
+        let api = Arc::new(SimulatedApi);
+        let store = Arc::new(MockRecordStore::new());
+        let publisher = Arc::new(MockEventPublisher::new());
+
+        let result = async {
+            let turbocharger = TurboChargerBuilder::new(settings)
+                .message_source(MockMessageSource::new(self.messages))
+                .data_fetcher(Arc::clone(&api))
+                .sinks(Arc::clone(&store), Arc::clone(&publisher))
+                .build()
+                .await?;
+            // The pipeline treats the end of its source as an error once
+            // everything read has been processed
+            match turbocharger.run().await {
+                Ok(()) => Ok(()),
+                Err(TurboError::Internal(message)) if message == STREAM_ENDED => Ok(()),
+                Err(e) => Err(e),
+            }
+        }
+        .await;
+        let _ = std::fs::remove_dir_all(&dir);
+        result?;
+
+        let stored = store.stored_records.lock().await.clone();
+        let published = publisher.published_records.lock().await.clone();
+        let mut hasher = blake3::Hasher::new();
+        for (sink, records) in [("store", &stored), ("publish", &published)] {
+            hasher.update(sink.as_bytes());
+            hasher.update(b"\n");
+            for record in virtual_time_order(records) {
+                serde_json::to_writer(&mut hasher, &record)?;
+                hasher.update(b"\n");
+            }
+        }
+
+        Ok(SimulationReport {
+            messages,
+            stored: stored.len(),
+            published: published.len(),
+            digest: hasher.finalize().to_hex().to_string(),
+        })
+    }
+}
+
+/// Answers every profile and post lookup with the generator's canned API
+/// responses, decoded the way the real client decodes them.
+#[derive(Debug, Default)]
+pub struct SimulatedApi;
+
+impl ProfileFetcher for SimulatedApi {
+    async fn bulk_fetch_profiles(
+        &self,
+        dids: &[String],
+    ) -> TurboResult<Vec<Option<BlueskyProfile>>> {
+        let response: GetProfilesResponse = serde_json::from_value(get_profiles_response(dids))?;
+        Ok(response
+            .profiles
+            .into_iter()
+            .map(|profile| Some(profile.into()))
+            .collect())
+    }
+}
+
+impl PostFetcher for SimulatedApi {
+    async fn bulk_fetch_posts(&self, uris: &[String]) -> TurboResult<Vec<Option<BlueskyPost>>> {
+        let response: GetPostsBulkResponse = serde_json::from_value(get_posts_response(uris))?;
+        let mut posts = vec![None; uris.len()];
+        for post in response.posts {
+            if let Some(index) = uris.iter().position(|uri| uri == &post.uri) {
+                posts[index] = Some(simulated_post(post));
+            }
+        }
+        Ok(posts)
+    }
+}
+
+fn simulated_post(response: GetPostsResponse) -> BlueskyPost {
+    let created_at = response
+        .record
+        .get("createdAt")
+        .and_then(|value| value.as_str())
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map_or(DateTime::UNIX_EPOCH, |value| value.with_timezone(&Utc));
+    BlueskyPost {
+        uri: response.uri,
+        cid: response.cid,
+        author: response.author.into(),
+        text: response
+            .record
+            .get("text")
+            .and_then(|value| value.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        created_at,
+        embed: None,
+        reply: None,
+        facets: None,
+        labels: response.labels,
+        like_count: response.like_count,
+        repost_count: response.repost_count,
+        reply_count: response.reply_count,
+    }
+}
+
+/// Records restamped with their event time, in event order.
+fn virtual_time_order(records: &[Arc<EnrichedRecord>]) -> Vec<EnrichedRecord> {
+    let mut records: Vec<EnrichedRecord> = records
+        .iter()
+        .map(|record| {
+            let mut record = EnrichedRecord::clone(record);
+            record.processed_at = record
+                .message
+                .time_us
+                .and_then(|time_us| DateTime::from_timestamp_micros(time_us as i64))
+                .unwrap_or(DateTime::UNIX_EPOCH);
+            record.metrics = ProcessingMetrics::default();
+            record
+        })
+        .collect();
+    records.sort_by(|a, b| {
+        (
+            a.message.time_us,
+            a.message.seq,
+            a.get_at_uri(),
+            &a.message.did,
+        )
+            .cmp(&(
+                b.message.time_us,
+                b.message.seq,
+                b.get_at_uri(),
+                &b.message.did,
+            ))
+    });
+    records
+}
+
+/// Writes `messages` as a recording `Simulation::from_recording` reads.
+pub fn write_recording<P: AsRef<Path>>(path: P, messages: &[JetstreamMessage]) -> TurboResult<()> {
+    let mut contents = Vec::new();
+    for message in messages {
+        serde_json::to_writer(&mut contents, message)?;
+        contents.push(b'\n');
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MessageGenerator;
+
+    #[tokio::test]
+    async fn test_same_recording_gives_the_same_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let messages = MessageGenerator::new(5).with_users(25).batch(300);
+        let path = dir.path().join("session.ndjson");
+        write_recording(&path, &messages).unwrap();
+
+        let first = Simulation::from_recording(&path)
+            .unwrap()
+            .run()
+            .await
+            .unwrap();
+        let second = Simulation::from_recording(&path)
+            .unwrap()
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.messages, 300);
+        assert_eq!(first.stored, 300);
+        assert_eq!(first.published, 300);
+
+        let other = Simulation::new(MessageGenerator::new(6).with_users(25).batch(300))
+            .run()
+            .await
+            .unwrap();
+        assert_ne!(other.digest, first.digest);
+    }
+}
@@ -0,0 +1,204 @@
+//! Handles `account` events that take an account out of the network for good.
+//! The author's cached profile is evicted and, depending on the configured
+//! mode, everything stored for the account is tombstoned or removed. Every
+//! account in a batch is handled in one pass over the stores. Counts are kept
+//! per status so operators can show which removals were honoured.
+
+use crate::hydration::TurboCache;
+use crate::models::enriched::EnrichedRecord;
+use crate::models::TurboResult;
+use crate::storage::{DeleteMode, PartitionedReader};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// Account statuses after which the account's records shouldn't be kept.
+/// `deactivated` and `suspended` can be reversed, and `desynchronized` and
+/// `throttled` are transient, so they leave data alone.
+const REMOVAL_STATUSES: &[&str] = &["takendown", "deleted"];
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AccountRemovalStats {
+    pub mode: DeleteMode,
+    /// Account events handled, by status
+    pub accounts: BTreeMap<String, u64>,
+    /// Stored rows tombstoned or removed because of them
+    pub records_affected: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct AccountRemovals {
+    mode: DeleteMode,
+    stats: Mutex<AccountRemovalStats>,
+}
+
+impl AccountRemovals {
+    pub fn new(mode: DeleteMode) -> Self {
+        Self {
+            mode,
+            stats: Mutex::new(AccountRemovalStats {
+                mode,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// `(did, status)` of every account in `records` that was just taken down
+    /// or deleted.
+    pub fn removed_accounts(records: &[Arc<EnrichedRecord>]) -> Vec<(String, String)> {
+        records
+            .iter()
+            .filter_map(|record| record.message.account.as_ref())
+            .filter(|account| !account.active)
+            .filter_map(|account| {
+                let status = account.status.as_deref()?;
+                REMOVAL_STATUSES
+                    .contains(&status)
+                    .then(|| (account.did.clone(), status.to_string()))
+            })
+            .collect()
+    }
+
+    /// Evicts the accounts' profiles and applies the mode to their stored
+    /// records, in the live store and in rotated databases. With no `reader`,
+    /// as with an injected record store, only the profiles are evicted.
+    pub async fn apply(
+        &self,
+        accounts: &[(String, String)],
+        cache: &TurboCache,
+        reader: Option<&PartitionedReader>,
+    ) -> TurboResult<u64> {
+        if accounts.is_empty() {
+            return Ok(0);
+        }
+        for (did, _) in accounts {
+            cache.remove_user_profile(did);
+        }
+
+        let dids: Vec<String> = accounts.iter().map(|(did, _)| did.clone()).collect();
+        let affected = match reader {
+            Some(reader) => reader.remove_accounts(&dids).await?,
+            None => 0,
+        };
+        if affected > 0 {
+            info!(
+                "Applied {} account removals ({:?}), {} rows affected",
+                accounts.len(),
+                self.mode,
+                affected
+            );
+        }
+
+        let mut stats = self.lock();
+        for (_, status) in accounts {
+            *stats.accounts.entry(status.clone()).or_default() += 1;
+        }
+        stats.records_affected += affected;
+        Ok(affected)
+    }
+
+    pub fn stats(&self) -> AccountRemovalStats {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, AccountRemovalStats> {
+        self.stats
+            .lock()
+            .expect("account removal stats lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::jetstream::JetstreamMessage;
+    use crate::storage::{RecordStore, SQLitePragmaConfig, ShardedSQLiteStore};
+    use crate::testing::fixtures::{create_post_message, create_profile};
+
+    fn account_event(did: &str, active: bool, status: Option<&str>) -> Arc<EnrichedRecord> {
+        let message: JetstreamMessage = serde_json::from_value(serde_json::json!({
+            "did": did,
+            "time_us": 1770949213990196u64,
+            "kind": "account",
+            "account": {"did": did, "active": active, "status": status, "seq": 1}
+        }))
+        .unwrap();
+        Arc::new(EnrichedRecord::new(message))
+    }
+
+    #[tokio::test]
+    async fn test_taken_down_accounts_are_purged_and_counted() {
+        let dir = std::env::temp_dir().join(format!("test_accounts_{}", uuid::Uuid::new_v4()));
+        let live = Arc::new(
+            ShardedSQLiteStore::new(
+                dir.join("jetstream.db"),
+                2,
+                SQLitePragmaConfig {
+                    cache_size_kib: 1024,
+                    mmap_size_mb: 0,
+                    journal_size_limit_mb: 64,
+                },
+            )
+            .await
+            .unwrap(),
+        );
+        let taken_down = account_event("did:plc:user0001", false, Some("takendown"));
+        live.store_batch(&[
+            Arc::new(EnrichedRecord::new(create_post_message(1))),
+            Arc::new(EnrichedRecord::new(create_post_message(2))),
+            Arc::new(EnrichedRecord::new(create_post_message(5))),
+            Arc::clone(&taken_down),
+        ])
+        .await
+        .unwrap();
+
+        let batch = vec![
+            taken_down,
+            account_event("did:plc:user0002", false, Some("throttled")),
+            account_event("did:plc:user0004", false, Some("deactivated")),
+            account_event("did:plc:user0003", true, None),
+            account_event("did:plc:user0005", false, Some("deleted")),
+        ];
+        let accounts = AccountRemovals::removed_accounts(&batch);
+        assert_eq!(
+            accounts,
+            vec![
+                ("did:plc:user0001".to_string(), "takendown".to_string()),
+                ("did:plc:user0005".to_string(), "deleted".to_string()),
+            ]
+        );
+
+        let cache = TurboCache::new(10, 10);
+        cache.set_user_profile(
+            "did:plc:user0001".to_string(),
+            Arc::new(create_profile("did:plc:user0001")),
+        );
+        let reader = PartitionedReader::new(live.clone(), &dir)
+            .with_account_removal_mode(DeleteMode::Remove);
+        let removals = AccountRemovals::new(DeleteMode::Remove);
+        assert_eq!(
+            removals
+                .apply(&accounts, &cache, Some(&reader))
+                .await
+                .unwrap(),
+            2
+        );
+
+        assert!(cache.get_user_profile("did:plc:user0001").is_none());
+        let uri = EnrichedRecord::new(create_post_message(1))
+            .get_at_uri()
+            .unwrap();
+        assert!(reader.get_record_by_uri(&uri).await.unwrap().is_none());
+        // The account event itself stays as the audit trail
+        assert_eq!(live.count_records().await.unwrap(), 2);
+
+        let stats = removals.stats();
+        assert_eq!(stats.accounts.get("takendown"), Some(&1));
+        assert_eq!(stats.accounts.get("deleted"), Some(&1));
+        assert_eq!(stats.records_affected, 2);
+
+        live.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,270 @@
+//! Fan-out of enriched records to live subscribers (WebSocket clients) with
+//! accounting for the records each subscriber loses when it falls behind.
+//!
+//! Every record gets an event id, and the most recent records are kept in a
+//! ring buffer so a client that reconnects with the last id it saw can catch up
+//! on what it missed before switching to live delivery.
+
+use crate::models::enriched::EnrichedRecord;
+use dashmap::DashMap;
+use metrics::counter;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+pub const DEFAULT_BROADCAST_CAPACITY: usize = 1000;
+
+#[derive(Debug, Default)]
+struct LagTracker {
+    next_id: AtomicU64,
+    dropped_total: AtomicU64,
+    subscribers: DashMap<u64, u64>,
+}
+
+/// A broadcast record and the event id it was sent with.
+#[derive(Debug, Clone)]
+pub struct BroadcastRecord {
+    pub id: u64,
+    pub record: Arc<EnrichedRecord>,
+}
+
+#[derive(Debug)]
+struct ReplayBuffer {
+    next_event_id: u64,
+    records: VecDeque<BroadcastRecord>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordBroadcaster {
+    sender: broadcast::Sender<BroadcastRecord>,
+    capacity: usize,
+    lag: Arc<LagTracker>,
+    replay: Arc<Mutex<ReplayBuffer>>,
+}
+
+impl RecordBroadcaster {
+    /// `capacity` bounds both each subscriber's backlog and the replay window.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, _) = broadcast::channel(capacity);
+        // Seeding from the clock keeps ids increasing across restarts, so a
+        // client's last id from before a restart doesn't replay the new window
+        let first_event_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |elapsed| elapsed.as_micros() as u64);
+        Self {
+            sender,
+            capacity,
+            lag: Arc::default(),
+            replay: Arc::new(Mutex::new(ReplayBuffer {
+                next_event_id: first_event_id,
+                records: VecDeque::with_capacity(capacity),
+            })),
+        }
+    }
+
+    /// Sends to every current subscriber; a send with no subscribers is not an error.
+    pub fn send(&self, record: Arc<EnrichedRecord>) {
+        // Held across the send so ids reach the channel in order and a
+        // subscriber joining concurrently sees each record exactly once
+        let mut replay = self.replay.lock().expect("replay buffer lock poisoned");
+        let event = BroadcastRecord {
+            id: replay.next_event_id,
+            record,
+        };
+        replay.next_event_id += 1;
+        if replay.records.len() == self.capacity {
+            replay.records.pop_front();
+        }
+        replay.records.push_back(event.clone());
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> RecordSubscription {
+        self.subscribe_after(None)
+    }
+
+    /// Subscribes starting with the retained records after `last_id`, then live
+    /// records. Records older than the replay window are gone.
+    pub fn subscribe_after(&self, last_id: Option<u64>) -> RecordSubscription {
+        let id = self.lag.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lag.subscribers.insert(id, 0);
+
+        let replay = self.replay.lock().expect("replay buffer lock poisoned");
+        let receiver = self.sender.subscribe();
+        let backlog: VecDeque<BroadcastRecord> = match last_id {
+            Some(last_id) => replay
+                .records
+                .iter()
+                .filter(|event| event.id > last_id)
+                .cloned()
+                .collect(),
+            None => VecDeque::new(),
+        };
+        if let (Some(last_id), Some(oldest)) = (last_id, replay.records.front()) {
+            if oldest.id > last_id.saturating_add(1) {
+                debug!(
+                    "Subscriber {} resumed after {} but the replay window starts at {}",
+                    id, last_id, oldest.id
+                );
+            }
+        }
+        drop(replay);
+
+        RecordSubscription {
+            id,
+            backlog,
+            receiver,
+            lag: Arc::clone(&self.lag),
+        }
+    }
+
+    pub fn stats(&self) -> BroadcastStats {
+        BroadcastStats {
+            capacity: self.capacity,
+            subscribers: self.lag.subscribers.len(),
+            dropped_total: self.lag.dropped_total.load(Ordering::Relaxed),
+            dropped_by_subscriber: self
+                .lag
+                .subscribers
+                .iter()
+                .map(|entry| (*entry.key(), *entry.value()))
+                .collect(),
+        }
+    }
+}
+
+/// A subscriber's view of the broadcast. Records skipped because the subscriber
+/// lagged past the channel capacity are counted rather than surfaced as errors.
+pub struct RecordSubscription {
+    id: u64,
+    /// Replayed records still to deliver before the live ones
+    backlog: VecDeque<BroadcastRecord>,
+    receiver: broadcast::Receiver<BroadcastRecord>,
+    lag: Arc<LagTracker>,
+}
+
+impl RecordSubscription {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Next record, or `None` once the broadcaster is gone.
+    pub async fn recv(&mut self) -> Option<BroadcastRecord> {
+        if let Some(event) = self.backlog.pop_front() {
+            return Some(event);
+        }
+        loop {
+            match self.receiver.recv().await {
+                Ok(record) => return Some(record),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => self.record_drops(skipped),
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Next record if one is already queued, without waiting.
+    pub fn try_recv(&mut self) -> Option<BroadcastRecord> {
+        if let Some(event) = self.backlog.pop_front() {
+            return Some(event);
+        }
+        loop {
+            match self.receiver.try_recv() {
+                Ok(record) => return Some(record),
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => self.record_drops(skipped),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    fn record_drops(&self, skipped: u64) {
+        warn!(
+            "Broadcast subscriber {} lagged and dropped {} records",
+            self.id, skipped
+        );
+        self.lag.dropped_total.fetch_add(skipped, Ordering::Relaxed);
+        if let Some(mut dropped) = self.lag.subscribers.get_mut(&self.id) {
+            *dropped += skipped;
+        }
+        counter!("jetstream_turbo_broadcast_dropped_total").increment(skipped);
+    }
+}
+
+impl Drop for RecordSubscription {
+    fn drop(&mut self) {
+        self.lag.subscribers.remove(&self.id);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BroadcastStats {
+    pub capacity: usize,
+    pub subscribers: usize,
+    /// Records dropped across all subscribers since startup, including disconnected ones
+    pub dropped_total: u64,
+    /// Records dropped per connected subscriber, keyed by subscriber ID
+    pub dropped_by_subscriber: BTreeMap<u64, u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_drops_are_counted() {
+        let broadcaster = RecordBroadcaster::new(2);
+        let mut slow = broadcaster.subscribe();
+        let mut fast = broadcaster.subscribe();
+
+        for seq in 0..5 {
+            broadcaster.send(Arc::new(EnrichedRecord::new(create_post_message(seq))));
+            fast.recv().await.unwrap();
+        }
+
+        let event = slow.recv().await.unwrap();
+        assert_eq!(event.record.message.seq, create_post_message(3).seq);
+
+        let stats = broadcaster.stats();
+        assert_eq!(stats.capacity, 2);
+        assert_eq!(stats.subscribers, 2);
+        assert_eq!(stats.dropped_total, 3);
+        assert_eq!(stats.dropped_by_subscriber[&slow.id()], 3);
+        assert_eq!(stats.dropped_by_subscriber[&fast.id()], 0);
+
+        drop(slow);
+        let stats = broadcaster.stats();
+        assert_eq!(stats.subscribers, 1);
+        assert_eq!(stats.dropped_total, 3);
+    }
+
+    #[tokio::test]
+    async fn test_resumed_subscriber_catches_up_from_last_id() {
+        let broadcaster = RecordBroadcaster::new(3);
+        let mut live = broadcaster.subscribe();
+        let mut ids = Vec::new();
+        for seq in 0..5 {
+            broadcaster.send(Arc::new(EnrichedRecord::new(create_post_message(seq))));
+            ids.push(live.recv().await.unwrap().id);
+        }
+        assert!(ids.windows(2).all(|pair| pair[1] == pair[0] + 1));
+
+        // Resuming within the window replays only what came after last_id
+        let mut resumed = broadcaster.subscribe_after(Some(ids[2]));
+        // Resuming from before the window replays the whole window
+        let mut stale = broadcaster.subscribe_after(Some(ids[0] - 100));
+        broadcaster.send(Arc::new(EnrichedRecord::new(create_post_message(5))));
+
+        for expected in [ids[3], ids[4], ids[4] + 1] {
+            assert_eq!(resumed.recv().await.unwrap().id, expected);
+        }
+        for expected in [ids[2], ids[3], ids[4], ids[4] + 1] {
+            assert_eq!(stale.recv().await.unwrap().id, expected);
+        }
+        assert!(stale.try_recv().is_none());
+    }
+}
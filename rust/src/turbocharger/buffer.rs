@@ -62,10 +62,14 @@ mod tests {
                 rev: Some("test-rev".to_string()),
                 operation_type: OperationType::Create,
                 collection: Some("app.bsky.feed.post".to_string()),
-                rkey: Some(format!("test-rkey-{}", seq)),
+                rkey: Some(format!("test-rkey-{seq}")),
                 record: Some(serde_json::json!({"text": format!("Test message {}", seq)})),
                 cid: Some("bafyrei".to_string()),
+                extra: Default::default(),
             }),
+            identity: None,
+            account: None,
+            extra: Default::default(),
         }
     }
 
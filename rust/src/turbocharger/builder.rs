@@ -0,0 +1,850 @@
+//! Assembles a `TurboCharger` from injected components. Anything not injected
+//! is built from settings exactly as `TurboCharger::new` does, so embedding the
+//! pipeline with, say, a custom record store or mocked Bluesky fetchers only
+//! means swapping that one part:
+//!
+//! ```ignore
+//! let turbocharger = TurboChargerBuilder::new(settings)
+//!     .fetchers(Arc::new(profiles), Arc::new(posts))
+//!     .sinks(Arc::new(store), Arc::new(publisher))
+//!     .build()
+//!     .await?;
+//! ```
+
+use crate::client::{
+    BlueskyAuthClient, BlueskyClient, FirehoseClient, FrameCapture, IngestMode, IngestSource,
+    JetstreamClient, MessageSource, PostFetcher, ProfileFetcher, ReplaySource, RequestBudget,
+};
+use crate::config::Settings;
+use crate::hydration::{DataFetcher, TurboCache};
+use crate::models::TurboResult;
+#[cfg(feature = "redis")]
+use crate::storage::RedisStore;
+use crate::storage::{
+    EventPublisher, FileSink, LiveSink, RecordStore, SQLitePragmaConfig, ShardedSQLiteStore,
+};
+use crate::telemetry::ErrorReporter;
+use crate::turbocharger::orchestrator::TurboCharger;
+use crate::turbocharger::sharding::ShardAssignment;
+use crate::turbocharger::sink_latency::Sink;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Where the Bluesky request budget is kept between runs, inside `db_dir`.
+const REQUEST_BUDGET_FILE: &str = "bluesky_request_budget.json";
+
+/// A component the builder creates from settings because none was injected.
+pub struct FromSettings;
+
+/// An injected ingest source.
+pub struct InjectedSource<M>(M);
+
+/// Injected profile and post fetchers. They manage their own credentials, so
+/// no Bluesky session is opened or refreshed.
+pub struct InjectedFetchers<P, Po> {
+    profiles: Arc<P>,
+    posts: Arc<Po>,
+}
+
+/// An injected record store and event publisher. Neither SQLite nor Redis is
+/// opened, so lookups go to the injected store, and the SQLite-only read API
+/// (threads, aggregates, time ranges), rotation and compaction are unavailable.
+pub struct InjectedSinks<S, E> {
+    store: Arc<S>,
+    publisher: Arc<E>,
+}
+
+/// The sinks a `SinkComponents` resolved to, with the SQLite and Redis stores
+/// it opened for them, which the `TurboCharger` also reads and reports on.
+pub struct ResolvedSinks<S, E> {
+    pub store: Arc<S>,
+    pub publisher: Arc<E>,
+    pub sqlite: Option<Arc<ShardedSQLiteStore>>,
+    /// What `publisher` writes to, for its latency stats. Injected publishers
+    /// count as Redis, the main stream they stand in for.
+    pub publisher_sink: Sink,
+    /// `None` in standalone and serve-only mode too
+    #[cfg(feature = "redis")]
+    pub redis: Option<Arc<RedisStore>>,
+}
+
+pub trait SourceComponent {
+    type Source: MessageSource + Send + Sync + 'static;
+
+    /// `store` is where a Jetstream source resumes from when configured to;
+    /// `None` with injected sinks.
+    fn resolve(
+        self,
+        settings: &Settings,
+        store: Option<&ShardedSQLiteStore>,
+    ) -> impl Future<Output = TurboResult<Self::Source>> + Send;
+}
+
+pub trait FetcherComponents {
+    type Profiles: ProfileFetcher + Send + Sync + 'static;
+    type Posts: PostFetcher + Send + Sync + 'static;
+
+    /// The fetchers, plus the Bluesky client whose sessions need refreshing if
+    /// the builder created it. Rate-limit pauses of that client are reported
+    /// to `error_reporter`.
+    #[allow(clippy::type_complexity)]
+    fn resolve(
+        self,
+        settings: &Settings,
+        error_reporter: &ErrorReporter,
+    ) -> impl Future<
+        Output = TurboResult<(
+            Arc<Self::Profiles>,
+            Arc<Self::Posts>,
+            Option<Arc<BlueskyClient>>,
+        )>,
+    > + Send;
+}
+
+pub trait SinkComponents {
+    type Store: RecordStore + Send + Sync + 'static;
+    type Publisher: EventPublisher + Send + Sync + 'static;
+
+    /// The store counts as the SQLite one, read and cleaned up through the
+    /// database directly, exactly when `ResolvedSinks::sqlite` is set.
+    fn resolve(
+        self,
+        settings: &Settings,
+    ) -> impl Future<Output = TurboResult<ResolvedSinks<Self::Store, Self::Publisher>>> + Send;
+}
+
+impl SourceComponent for FromSettings {
+    type Source = IngestSource;
+
+    async fn resolve(
+        self,
+        settings: &Settings,
+        store: Option<&ShardedSQLiteStore>,
+    ) -> TurboResult<IngestSource> {
+        let source = match settings.ingest_mode {
+            IngestMode::Jetstream => {
+                let mut client = JetstreamClient::new(
+                    settings.jetstream_hosts.clone(),
+                    settings.wanted_collections.clone(),
+                )
+                .with_channel_capacity(settings.channel_capacity)
+                .with_parse_workers(settings.parse_workers)
+                .with_user_agent(&settings.user_agent())?;
+                if settings.jetstream_probe_interval_secs > 0 {
+                    client = client.with_endpoint_probing(Duration::from_secs(
+                        settings.jetstream_probe_interval_secs,
+                    ));
+                }
+                if let Some(capture_dir) = &settings.capture_dir {
+                    client = client.with_capture(FrameCapture::start(
+                        capture_dir,
+                        settings.capture_rotate_mb * 1024 * 1024,
+                    )?);
+                }
+                if let Some(store) = store.filter(|_| settings.jetstream_resume_from_store) {
+                    if let Some(time_us) = store.latest_time_us().await? {
+                        info!("Resuming Jetstream from newest stored event ({})", time_us);
+                        client.resume_from(time_us as u64);
+                    }
+                }
+                IngestSource::Jetstream(client)
+            }
+            IngestMode::Firehose => IngestSource::Firehose(
+                FirehoseClient::new(
+                    settings.firehose_hosts.clone(),
+                    settings.wanted_collections.clone(),
+                )
+                .with_channel_capacity(settings.channel_capacity)
+                .with_user_agent(&settings.user_agent())?,
+            ),
+            IngestMode::Replay => IngestSource::Replay(
+                ReplaySource::new(
+                    settings.replay_path.clone().unwrap_or_default(),
+                    settings.replay_speed,
+                )
+                .with_channel_capacity(settings.channel_capacity),
+            ),
+        };
+        Ok(source)
+    }
+}
+
+impl<M> SourceComponent for InjectedSource<M>
+where
+    M: MessageSource + Send + Sync + 'static,
+{
+    type Source = M;
+
+    async fn resolve(
+        self,
+        _settings: &Settings,
+        _store: Option<&ShardedSQLiteStore>,
+    ) -> TurboResult<M> {
+        Ok(self.0)
+    }
+}
+
+impl FetcherComponents for FromSettings {
+    type Profiles = BlueskyClient;
+    type Posts = BlueskyClient;
+
+    async fn resolve(
+        self,
+        settings: &Settings,
+        error_reporter: &ErrorReporter,
+    ) -> TurboResult<(
+        Arc<BlueskyClient>,
+        Arc<BlueskyClient>,
+        Option<Arc<BlueskyClient>>,
+    )> {
+        if settings.serve_only {
+            // Nothing is hydrated, so no session is opened or refreshed
+            let bluesky_client = Arc::new(
+                BlueskyClient::new(
+                    Vec::new(),
+                    None,
+                    settings.profile_batch_size,
+                    settings.post_batch_size,
+                    settings.profile_batch_wait_ms,
+                    settings.post_batch_wait_ms,
+                )?
+                .with_http_policy(settings.bluesky_http_policy())?,
+            );
+            return Ok((Arc::clone(&bluesky_client), bluesky_client, None));
+        }
+
+        // Authenticate directly with Bluesky
+        let auth_client = Arc::new(
+            BlueskyAuthClient::new(
+                settings.bluesky_handle.clone(),
+                settings.bluesky_app_password.clone(),
+            )?
+            .with_http_policy(settings.auth_http_policy())?,
+        );
+
+        let auth_response = auth_client.authenticate().await?;
+        info!(
+            "Successfully authenticated with Bluesky as {}",
+            settings.bluesky_handle
+        );
+        let bluesky_client = BlueskyClient::new(
+            vec![auth_response.access_jwt.clone()],
+            Some(auth_client.clone()),
+            settings.profile_batch_size,
+            settings.post_batch_size,
+            settings.profile_batch_wait_ms,
+            settings.post_batch_wait_ms,
+        )?
+        .with_http_policy(settings.bluesky_http_policy())?
+        .with_accepted_labelers(&settings.accepted_labelers)
+        .with_error_reporter(error_reporter.clone());
+        let bluesky_client = if settings.api_request_budget > 0 {
+            bluesky_client.with_request_budget(Arc::new(
+                RequestBudget::new(
+                    settings.api_request_budget,
+                    Duration::from_secs(settings.api_request_budget_window_secs),
+                )
+                .with_state_file(Path::new(&settings.db_dir).join(REQUEST_BUDGET_FILE)),
+            ))
+        } else {
+            bluesky_client
+        };
+        #[cfg(feature = "chaos")]
+        let bluesky_client = bluesky_client.with_chaos(settings.chaos.bluesky_injector());
+        let bluesky_client = Arc::new(bluesky_client);
+        bluesky_client
+            .refresh_sessions(
+                vec![auth_response.access_jwt],
+                Some(auth_response.refresh_jwt),
+                auth_response.expires_at,
+            )
+            .await;
+
+        Ok((
+            Arc::clone(&bluesky_client),
+            Arc::clone(&bluesky_client),
+            Some(bluesky_client),
+        ))
+    }
+}
+
+impl<P, Po> FetcherComponents for InjectedFetchers<P, Po>
+where
+    P: ProfileFetcher + Send + Sync + 'static,
+    Po: PostFetcher + Send + Sync + 'static,
+{
+    type Profiles = P;
+    type Posts = Po;
+
+    async fn resolve(
+        self,
+        _settings: &Settings,
+        _error_reporter: &ErrorReporter,
+    ) -> TurboResult<(Arc<P>, Arc<Po>, Option<Arc<BlueskyClient>>)> {
+        Ok((self.profiles, self.posts, None))
+    }
+}
+
+impl SinkComponents for FromSettings {
+    type Store = ShardedSQLiteStore;
+    type Publisher = LiveSink;
+
+    async fn resolve(
+        self,
+        settings: &Settings,
+    ) -> TurboResult<ResolvedSinks<ShardedSQLiteStore, LiveSink>> {
+        let db_path = format!("{}/jetstream.db", settings.db_dir);
+        let sqlite = if settings.serve_only {
+            ShardedSQLiteStore::open_read_only(&db_path, settings.sqlite_shards).await?
+        } else {
+            ShardedSQLiteStore::new(
+                &db_path,
+                settings.sqlite_shards,
+                SQLitePragmaConfig {
+                    cache_size_kib: settings.sqlite_cache_size_kib,
+                    mmap_size_mb: settings.sqlite_mmap_size_mb,
+                    journal_size_limit_mb: settings.sqlite_journal_size_limit_mb,
+                },
+            )
+            .await?
+        }
+        .with_delete_mode(settings.delete_mode);
+        #[cfg(feature = "chaos")]
+        let sqlite = sqlite.with_chaos(settings.chaos.sqlite_injector());
+        let sqlite = Arc::new(sqlite);
+
+        if settings.serve_only {
+            info!("Serve-only mode: serving stored records without ingesting");
+        } else if settings.standalone {
+            info!("Standalone mode: live records go to WebSocket clients only, not Redis");
+        } else if cfg!(not(feature = "redis")) {
+            warn!("Publishing to Redis requires the redis feature; live records go to WebSocket clients only");
+        }
+        #[cfg(feature = "redis")]
+        let redis = open_redis(settings).await?;
+        #[cfg(feature = "redis")]
+        let redis_sink = redis
+            .as_ref()
+            .map(|redis| LiveSink::Redis(Arc::clone(redis)));
+        #[cfg(not(feature = "redis"))]
+        let redis_sink = None;
+
+        let (publisher, publisher_sink) = match redis_sink {
+            Some(redis_sink) => (redis_sink, Sink::Redis),
+            None => (
+                LiveSink::Standalone(
+                    settings
+                        .standalone_output_file
+                        .as_ref()
+                        .map(|path| {
+                            FileSink::open(path).map(|sink| {
+                                sink.with_publish_deletes(settings.redis_publish_deletes)
+                            })
+                        })
+                        .transpose()?,
+                ),
+                Sink::File,
+            ),
+        };
+        Ok(ResolvedSinks {
+            store: Arc::clone(&sqlite),
+            publisher: Arc::new(publisher),
+            sqlite: Some(sqlite),
+            publisher_sink,
+            #[cfg(feature = "redis")]
+            redis,
+        })
+    }
+}
+
+/// The Redis stream the main stream is published to, unless serving only or
+/// running standalone.
+#[cfg(feature = "redis")]
+async fn open_redis(settings: &Settings) -> TurboResult<Option<Arc<RedisStore>>> {
+    if settings.serve_only || settings.standalone {
+        return Ok(None);
+    }
+    let redis = RedisStore::new(
+        &settings.redis_url,
+        settings.stream_name_redis.clone(),
+        settings.trim_maxlen,
+    )
+    .await?
+    .with_payload_encoding(settings.redis_payload_encoding)
+    .with_publish_deletes(settings.redis_publish_deletes);
+    #[cfg(feature = "chaos")]
+    let redis = redis.with_chaos(settings.chaos.redis_injector());
+    Ok(Some(Arc::new(redis)))
+}
+
+impl<S, E> SinkComponents for InjectedSinks<S, E>
+where
+    S: RecordStore + Send + Sync + 'static,
+    E: EventPublisher + Send + Sync + 'static,
+{
+    type Store = S;
+    type Publisher = E;
+
+    async fn resolve(self, _settings: &Settings) -> TurboResult<ResolvedSinks<S, E>> {
+        Ok(ResolvedSinks {
+            store: self.store,
+            publisher: self.publisher,
+            sqlite: None,
+            publisher_sink: Sink::Redis,
+            #[cfg(feature = "redis")]
+            redis: None,
+        })
+    }
+}
+
+pub struct TurboChargerBuilder<Src = FromSettings, F = FromSettings, Sk = FromSettings> {
+    pub(super) settings: Settings,
+    pub(super) source: Src,
+    pub(super) fetchers: F,
+    pub(super) sinks: Sk,
+    pub(super) cache: Option<TurboCache>,
+    pub(super) error_reporter: Option<ErrorReporter>,
+    pub(super) shard_assignment: ShardAssignment,
+}
+
+impl TurboChargerBuilder {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            source: FromSettings,
+            fetchers: FromSettings,
+            sinks: FromSettings,
+            cache: None,
+            error_reporter: None,
+            shard_assignment: ShardAssignment::default(),
+        }
+    }
+}
+
+impl<Src, F, Sk> TurboChargerBuilder<Src, F, Sk> {
+    pub fn message_source<M>(self, source: M) -> TurboChargerBuilder<InjectedSource<M>, F, Sk>
+    where
+        M: MessageSource + Send + Sync + 'static,
+    {
+        TurboChargerBuilder {
+            settings: self.settings,
+            source: InjectedSource(source),
+            fetchers: self.fetchers,
+            sinks: self.sinks,
+            cache: self.cache,
+            error_reporter: self.error_reporter,
+            shard_assignment: self.shard_assignment,
+        }
+    }
+
+    pub fn fetchers<P, Po>(
+        self,
+        profiles: Arc<P>,
+        posts: Arc<Po>,
+    ) -> TurboChargerBuilder<Src, InjectedFetchers<P, Po>, Sk>
+    where
+        P: ProfileFetcher + Send + Sync + 'static,
+        Po: PostFetcher + Send + Sync + 'static,
+    {
+        TurboChargerBuilder {
+            settings: self.settings,
+            source: self.source,
+            fetchers: InjectedFetchers { profiles, posts },
+            sinks: self.sinks,
+            cache: self.cache,
+            error_reporter: self.error_reporter,
+            shard_assignment: self.shard_assignment,
+        }
+    }
+
+    /// `fetchers` with one `DataFetcher` for both profiles and posts.
+    pub fn data_fetcher<D>(
+        self,
+        fetcher: Arc<D>,
+    ) -> TurboChargerBuilder<Src, InjectedFetchers<D, D>, Sk>
+    where
+        D: DataFetcher,
+    {
+        self.fetchers(Arc::clone(&fetcher), fetcher)
+    }
+
+    pub fn sinks<S, E>(
+        self,
+        store: Arc<S>,
+        publisher: Arc<E>,
+    ) -> TurboChargerBuilder<Src, F, InjectedSinks<S, E>>
+    where
+        S: RecordStore + Send + Sync + 'static,
+        E: EventPublisher + Send + Sync + 'static,
+    {
+        TurboChargerBuilder {
+            settings: self.settings,
+            source: self.source,
+            fetchers: self.fetchers,
+            sinks: InjectedSinks { store, publisher },
+            cache: self.cache,
+            error_reporter: self.error_reporter,
+            shard_assignment: self.shard_assignment,
+        }
+    }
+
+    /// Replaces the profile and post cache sized by `CACHE_SIZE_USERS` and
+    /// `CACHE_SIZE_POSTS`, e.g. with `TurboCache::with_backend` to keep
+    /// entries in a store shared between instances.
+    pub fn cache(mut self, cache: TurboCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Replaces the PostHog reporter configured by `POSTHOG_API_KEY`.
+    pub fn error_reporter(mut self, error_reporter: ErrorReporter) -> Self {
+        self.error_reporter = Some(error_reporter);
+        self
+    }
+
+    /// Handles only the DIDs of one shard of the stream; every DID by default.
+    pub fn shard_assignment(mut self, assignment: ShardAssignment) -> Self {
+        self.shard_assignment = assignment;
+        self
+    }
+}
+
+impl<Src, F, Sk> TurboChargerBuilder<Src, F, Sk>
+where
+    Src: SourceComponent + Send,
+    F: FetcherComponents + Send,
+    Sk: SinkComponents + Send,
+{
+    pub async fn build(
+        self,
+    ) -> TurboResult<TurboCharger<Src::Source, F::Profiles, F::Posts, Sk::Store, Sk::Publisher>>
+    {
+        TurboCharger::assemble(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TurboError;
+    use crate::testing::fixtures::{create_post_message, create_profile};
+    use crate::testing::mocks::{
+        MockEventPublisher, MockMessageSource, MockPostFetcher, MockProfileFetcher, MockRecordStore,
+    };
+
+    #[tokio::test]
+    async fn test_injected_components_replace_bluesky_and_sinks() {
+        let dir = std::env::temp_dir().join(format!("test_builder_{}", uuid::Uuid::new_v4()));
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            redis_url: String::new(),
+            ..Default::default()
+        };
+
+        let profiles = Arc::new(MockProfileFetcher::new());
+        profiles
+            .add_profile(create_profile("did:plc:user0001"))
+            .await;
+        let posts = Arc::new(MockPostFetcher::new());
+        let store = Arc::new(MockRecordStore::new());
+        let publisher = Arc::new(MockEventPublisher::new());
+
+        let turbocharger = TurboChargerBuilder::new(settings)
+            .message_source(MockMessageSource::new(vec![
+                create_post_message(1),
+                create_post_message(2),
+            ]))
+            .fetchers(Arc::clone(&profiles), posts)
+            .sinks(Arc::clone(&store), Arc::clone(&publisher))
+            .build()
+            .await
+            .unwrap();
+
+        // The mock source ends after its messages, which ends the run
+        assert!(turbocharger.run().await.is_err());
+        assert_eq!(store.get_stored_count().await, 2);
+        assert_eq!(publisher.get_published_count().await, 2);
+        assert!(
+            profiles
+                .call_count
+                .load(std::sync::atomic::Ordering::SeqCst)
+                > 0
+        );
+
+        // Lookups fall through to the injected store
+        let uri = crate::models::enriched::EnrichedRecord::new(create_post_message(1))
+            .get_at_uri()
+            .unwrap();
+        assert!(turbocharger
+            .get_record_by_uri(&uri)
+            .await
+            .unwrap()
+            .is_some());
+        // Cleanup holds the injected store to retention, which these are within
+        assert!(turbocharger.check_and_cleanup_db().await.unwrap().is_none());
+        assert_eq!(store.get_stored_count().await, 2);
+        // Injected sinks leave SQLite unopened, so its reads are unavailable
+        assert!(!dir.exists());
+        assert!(matches!(
+            turbocharger.get_thread(&uri).await,
+            Err(TurboError::NotFound(_))
+        ));
+        assert!(turbocharger.health_check().await.unwrap().sqlite_available);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_injected_cache_backend_holds_hydrated_profiles() {
+        use crate::hydration::CacheBackend;
+        use crate::models::bluesky::{BlueskyPost, BlueskyProfile};
+        use std::collections::HashMap;
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct MapBackend {
+            profiles: Mutex<HashMap<String, Arc<BlueskyProfile>>>,
+        }
+
+        impl CacheBackend for MapBackend {
+            fn get_profile(&self, did: &str) -> Option<Arc<BlueskyProfile>> {
+                self.profiles.lock().unwrap().get(did).cloned()
+            }
+
+            fn insert_profile(&self, did: String, profile: Arc<BlueskyProfile>) {
+                self.profiles.lock().unwrap().insert(did, profile);
+            }
+
+            fn remove_profile(&self, did: &str) {
+                self.profiles.lock().unwrap().remove(did);
+            }
+
+            fn get_post(&self, _uri: &str) -> Option<Arc<BlueskyPost>> {
+                None
+            }
+
+            fn insert_post(&self, _uri: String, _post: Arc<BlueskyPost>) {}
+
+            fn entry_counts(&self) -> (u64, u64) {
+                (self.profiles.lock().unwrap().len() as u64, 0)
+            }
+
+            fn capacity_limits(&self) -> (usize, usize) {
+                (usize::MAX, 0)
+            }
+
+            fn clear(&self) {
+                self.profiles.lock().unwrap().clear();
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("test_builder_{}", uuid::Uuid::new_v4()));
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let profiles = Arc::new(MockProfileFetcher::new());
+        profiles
+            .add_profile(create_profile("did:plc:user0001"))
+            .await;
+        let backend = Arc::new(MapBackend::default());
+
+        let turbocharger = TurboChargerBuilder::new(settings)
+            .message_source(MockMessageSource::new(vec![create_post_message(1)]))
+            .fetchers(profiles, Arc::new(MockPostFetcher::new()))
+            .sinks(
+                Arc::new(MockRecordStore::new()),
+                Arc::new(MockEventPublisher::new()),
+            )
+            .cache(TurboCache::with_backend(backend.clone()))
+            .build()
+            .await
+            .unwrap();
+
+        assert!(turbocharger.run().await.is_err());
+        assert!(backend
+            .profiles
+            .lock()
+            .unwrap()
+            .contains_key("did:plc:user0001"));
+        let diagnostics = turbocharger.get_runtime_diagnostics().await;
+        assert_eq!(diagnostics.cache_state.user_entries, 1);
+        assert_eq!(diagnostics.cache_state.user_capacity, usize::MAX);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_lookups_hydrate_misses_from_the_authors_pds_within_the_rate_limit() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let directory = MockServer::start().await;
+        let pds = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/did:plc:hosted/data"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "did": "did:plc:hosted",
+                "alsoKnownAs": ["at://hosted.example.com"],
+                "services": {
+                    "atproto_pds": {
+                        "type": "AtprotoPersonalDataServer",
+                        "endpoint": pds.uri()
+                    }
+                }
+            })))
+            .mount(&directory)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.repo.getRecord"))
+            .and(query_param("repo", "did:plc:hosted"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "uri": "at://did:plc:hosted/app.bsky.feed.post/3kabc",
+                "cid": "bafyreiabc",
+                "value": {
+                    "$type": "app.bsky.feed.post",
+                    "text": "fetched",
+                    "createdAt": "2024-01-01T00:00:00Z"
+                }
+            })))
+            .expect(1)
+            .mount(&pds)
+            .await;
+
+        let dir = std::env::temp_dir().join(format!("test_builder_{}", uuid::Uuid::new_v4()));
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            redis_url: String::new(),
+            plc_directory_url: Some(directory.uri()),
+            // Nothing else may answer for the record
+            backfill_api_url: "http://127.0.0.1:9/xrpc".to_string(),
+            hydrate_on_miss_per_minute: 1,
+            ..Default::default()
+        };
+        let turbocharger = TurboChargerBuilder::new(settings)
+            .message_source(MockMessageSource::new(vec![]))
+            .fetchers(
+                Arc::new(MockProfileFetcher::new()),
+                Arc::new(MockPostFetcher::new()),
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let uri = "at://did:plc:hosted/app.bsky.feed.post/3kabc";
+        assert!(turbocharger
+            .lookup_record(uri, false)
+            .await
+            .unwrap()
+            .is_none());
+        let record = turbocharger
+            .lookup_record(uri, true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.get_text(), Some("fetched"));
+        // Fetched records aren't stored, so the next miss needs another fetch
+        assert!(matches!(
+            turbocharger.lookup_record(uri, true).await,
+            Err(TurboError::RateLimited {
+                retry_after: Some(_),
+                ..
+            })
+        ));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_buffered_messages_and_ends_the_run() {
+        let dir = std::env::temp_dir().join(format!("test_builder_{}", uuid::Uuid::new_v4()));
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            redis_url: String::new(),
+            ..Default::default()
+        };
+        let source =
+            MockMessageSource::open_ended(vec![create_post_message(1), create_post_message(2)]);
+        let delivered = Arc::clone(&source.delivered);
+        let store = Arc::new(MockRecordStore::new());
+        let turbocharger = Arc::new(
+            TurboChargerBuilder::new(settings)
+                .message_source(source)
+                .fetchers(
+                    Arc::new(MockProfileFetcher::new()),
+                    Arc::new(MockPostFetcher::new()),
+                )
+                .sinks(Arc::clone(&store), Arc::new(MockEventPublisher::new()))
+                .build()
+                .await
+                .unwrap(),
+        );
+
+        let run = tokio::spawn({
+            let turbocharger = Arc::clone(&turbocharger);
+            async move { turbocharger.run().await }
+        });
+        delivered.notified().await;
+        turbocharger.shutdown();
+
+        assert!(run.await.unwrap().is_ok());
+        assert_eq!(store.get_stored_count().await, 2);
+        // A stopped turbocharger doesn't start again
+        assert!(turbocharger.run().await.is_ok());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_standalone_mode_writes_the_main_stream_to_a_file() {
+        let dir = std::env::temp_dir().join(format!("test_standalone_{}", uuid::Uuid::new_v4()));
+        let output_file = dir.join("records.ndjson");
+        let settings = Settings {
+            db_dir: dir.to_string_lossy().into_owned(),
+            standalone: true,
+            standalone_output_file: Some(output_file.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+
+        let turbocharger = TurboChargerBuilder::new(settings)
+            .message_source(MockMessageSource::new(vec![
+                create_post_message(1),
+                create_post_message(2),
+            ]))
+            .fetchers(
+                Arc::new(MockProfileFetcher::new()),
+                Arc::new(MockPostFetcher::new()),
+            )
+            .build()
+            .await
+            .unwrap();
+        let mut subscription = turbocharger.subscribe(None);
+
+        assert!(turbocharger.run().await.is_err());
+        assert_eq!(
+            std::fs::read_to_string(&output_file)
+                .unwrap()
+                .lines()
+                .count(),
+            2
+        );
+        assert!(subscription.recv().await.is_some());
+
+        let stats = turbocharger.get_stats().await.unwrap();
+        assert_eq!(stats.total_records_processed, 2);
+        assert_eq!(stats.redis_version, "standalone");
+        // The file's writes aren't reported as Redis
+        assert_eq!(stats.sink_latency.file.samples, 1);
+        assert_eq!(stats.sink_latency.redis.samples, 0);
+        let readiness = turbocharger.readiness_check().await;
+        assert_eq!(readiness.redis_connected, None);
+        assert!(!readiness
+            .not_ready
+            .contains(&"Redis unreachable".to_string()));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,154 @@
+//! Commit outcomes broken down by collection and operation. Identity and
+//! account events have no collection and aren't counted here.
+
+use crate::models::enriched::EnrichedRecord;
+use crate::models::jetstream::JetstreamMessage;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+type CounterKey = (String, &'static str);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CollectionCounts {
+    /// Hydrated and written to every sink
+    pub processed: u64,
+    /// Dropped before the sinks, e.g. by label or lexicon filtering
+    pub skipped: u64,
+    /// Part of a batch a sink failed to write
+    pub failed: u64,
+}
+
+/// Collection NSID, then operation (`create`, `update`, `delete`).
+pub type CollectionStats = BTreeMap<String, BTreeMap<String, CollectionCounts>>;
+
+#[derive(Debug, Default)]
+pub struct CollectionCounters {
+    counts: Mutex<BTreeMap<CounterKey, CollectionCounts>>,
+}
+
+/// Commits per collection and operation in a batch.
+#[derive(Debug, Default)]
+pub struct BatchTally(BTreeMap<CounterKey, u64>);
+
+impl BatchTally {
+    pub fn of_messages<'a>(messages: impl IntoIterator<Item = &'a JetstreamMessage>) -> Self {
+        let mut tally = BTreeMap::new();
+        for key in messages.into_iter().filter_map(counter_key) {
+            *tally.entry(key).or_default() += 1;
+        }
+        Self(tally)
+    }
+
+    pub fn of_records(records: &[Arc<EnrichedRecord>]) -> Self {
+        Self::of_messages(records.iter().map(|record| &record.message))
+    }
+}
+
+fn counter_key(message: &JetstreamMessage) -> Option<CounterKey> {
+    let commit = message.commit.as_ref()?;
+    Some((
+        commit.collection.clone().unwrap_or_default(),
+        commit.operation_type.as_str(),
+    ))
+}
+
+impl CollectionCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts everything in `received` that hydration didn't turn into a record as skipped.
+    pub fn record_hydrated(&self, received: &BatchTally, hydrated: &BatchTally) {
+        let mut counts = self.lock();
+        for (key, received_count) in &received.0 {
+            let hydrated_count = hydrated.0.get(key).copied().unwrap_or_default();
+            let skipped = received_count.saturating_sub(hydrated_count);
+            if skipped > 0 {
+                counts.entry(key.clone()).or_default().skipped += skipped;
+            }
+        }
+    }
+
+    pub fn record_written(&self, written: &BatchTally, succeeded: bool) {
+        let mut counts = self.lock();
+        for (key, count) in &written.0 {
+            let entry = counts.entry(key.clone()).or_default();
+            if succeeded {
+                entry.processed += count;
+            } else {
+                entry.failed += count;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CollectionStats {
+        let mut stats = CollectionStats::new();
+        for ((collection, operation), counts) in self.lock().iter() {
+            stats
+                .entry(collection.clone())
+                .or_default()
+                .insert(operation.to_string(), *counts);
+        }
+        stats
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<CounterKey, CollectionCounts>> {
+        self.counts
+            .lock()
+            .expect("collection counters lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(collection: &str, operation: &str) -> JetstreamMessage {
+        serde_json::from_value(serde_json::json!({
+            "did": "did:plc:test",
+            "time_us": 1,
+            "kind": "commit",
+            "commit": {"operation": operation, "collection": collection, "rkey": "a"}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_counts_are_split_by_collection_operation_and_outcome() {
+        let counters = CollectionCounters::new();
+        let post = commit("app.bsky.feed.post", "create");
+        let like = commit("app.bsky.feed.like", "create");
+        let unlike = commit("app.bsky.feed.like", "delete");
+        let identity: JetstreamMessage = serde_json::from_value(serde_json::json!({
+            "did": "did:plc:test", "time_us": 1, "kind": "identity"
+        }))
+        .unwrap();
+
+        let received = BatchTally::of_messages([&post, &post, &like, &unlike, &identity]);
+        let hydrated = BatchTally::of_messages([&post, &like, &unlike]);
+        counters.record_hydrated(&received, &hydrated);
+        counters.record_written(&hydrated, true);
+        counters.record_written(&BatchTally::of_messages([&like]), false);
+
+        let stats = counters.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            stats["app.bsky.feed.post"]["create"],
+            CollectionCounts {
+                processed: 1,
+                skipped: 1,
+                failed: 0
+            }
+        );
+        assert_eq!(
+            stats["app.bsky.feed.like"]["create"],
+            CollectionCounts {
+                processed: 1,
+                skipped: 0,
+                failed: 1
+            }
+        );
+        assert_eq!(stats["app.bsky.feed.like"]["delete"].processed, 1);
+    }
+}
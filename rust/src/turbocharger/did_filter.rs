@@ -0,0 +1,260 @@
+//! Per-DID allow and deny lists, applied to messages as they arrive so ignored
+//! accounts never cost a hydration request. DIDs come from comma-separated
+//! env lists and/or files of one DID per line (`#` starts a comment). Files
+//! are re-read when they change, so a list can be updated without a restart.
+//!
+//! A blocked DID is always dropped. Once any allowlist is configured, only
+//! allowed DIDs get through, for building curated datasets.
+
+use crate::config::Settings;
+use crate::models::errors::TurboResult;
+use crate::models::jetstream::JetstreamMessage;
+use metrics::counter;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DidFilterStats {
+    pub blocked_dids: usize,
+    /// Absent unless running in allowlist-only mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_dids: Option<usize>,
+    /// Messages dropped since startup
+    pub dropped_messages: u64,
+}
+
+/// One list: DIDs given inline plus those read from an optional file.
+#[derive(Debug, Default)]
+struct DidList {
+    inline: HashSet<String>,
+    path: Option<PathBuf>,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl DidList {
+    fn new(inline: &[String], path: Option<&str>) -> Self {
+        Self {
+            inline: inline.iter().map(|did| did.trim().to_string()).collect(),
+            path: path.map(PathBuf::from),
+            modified: Mutex::new(None),
+        }
+    }
+
+    fn is_configured(&self) -> bool {
+        !self.inline.is_empty() || self.path.is_some()
+    }
+
+    /// The full list, or `None` if the file hasn't changed since the last load.
+    fn load(&self, force: bool) -> TurboResult<Option<HashSet<String>>> {
+        let mut dids = self.inline.clone();
+        let Some(path) = &self.path else {
+            return Ok(force.then_some(dids));
+        };
+        let modified = std::fs::metadata(path).and_then(|meta| meta.modified())?;
+        let mut last_modified = self.modified.lock().expect("DID list lock poisoned");
+        if !force && *last_modified == Some(modified) {
+            return Ok(None);
+        }
+        dids.extend(read_did_file(path)?);
+        *last_modified = Some(modified);
+        Ok(Some(dids))
+    }
+}
+
+fn read_did_file(path: &Path) -> TurboResult<Vec<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+#[derive(Debug, Default)]
+pub struct DidFilter {
+    blocklist: DidList,
+    allowlist: DidList,
+    blocked: RwLock<HashSet<String>>,
+    /// `None` when no allowlist is configured
+    allowed: RwLock<Option<HashSet<String>>>,
+    dropped: AtomicU64,
+}
+
+impl DidFilter {
+    /// Loads the lists; a configured file that can't be read is an error.
+    pub fn new(
+        blocklist: &[String],
+        blocklist_file: Option<&str>,
+        allowlist: &[String],
+        allowlist_file: Option<&str>,
+    ) -> TurboResult<Self> {
+        let filter = Self {
+            blocklist: DidList::new(blocklist, blocklist_file),
+            allowlist: DidList::new(allowlist, allowlist_file),
+            ..Default::default()
+        };
+        filter.reload(true)?;
+        let stats = filter.stats();
+        if stats.blocked_dids > 0 {
+            info!("Ignoring {} blocked DIDs", stats.blocked_dids);
+        }
+        if let Some(allowed) = stats.allowed_dids {
+            info!("Allowlist-only mode: accepting {} DIDs", allowed);
+        }
+        Ok(filter)
+    }
+
+    pub fn from_settings(settings: &Settings) -> TurboResult<Self> {
+        Self::new(
+            &settings.did_blocklist,
+            settings.did_blocklist_file.as_deref(),
+            &settings.did_allowlist,
+            settings.did_allowlist_file.as_deref(),
+        )
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.blocklist.is_configured() || self.allowlist.is_configured()
+    }
+
+    /// Re-reads list files that changed since they were last loaded. A file
+    /// that can't be read keeps its previous list.
+    pub fn reload_if_changed(&self) {
+        if let Err(e) = self.reload(false) {
+            warn!("Failed to reload DID filter lists: {}", e);
+        }
+    }
+
+    fn reload(&self, force: bool) -> TurboResult<()> {
+        if let Some(blocked) = self.blocklist.load(force)? {
+            if !force {
+                info!("Reloaded DID blocklist: {} DIDs", blocked.len());
+            }
+            *self.blocked.write().expect("DID filter lock poisoned") = blocked;
+        }
+        if self.allowlist.is_configured() {
+            if let Some(allowed) = self.allowlist.load(force)? {
+                if !force {
+                    info!("Reloaded DID allowlist: {} DIDs", allowed.len());
+                }
+                *self.allowed.write().expect("DID filter lock poisoned") = Some(allowed);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn allows(&self, did: &str) -> bool {
+        if self
+            .blocked
+            .read()
+            .expect("DID filter lock poisoned")
+            .contains(did)
+        {
+            return false;
+        }
+        self.allowed
+            .read()
+            .expect("DID filter lock poisoned")
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(did))
+    }
+
+    /// Whether `message` gets through, counting it if not.
+    pub fn allows_message(&self, message: &JetstreamMessage) -> bool {
+        if !self.is_enabled() || self.allows(message.extract_did()) {
+            return true;
+        }
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        counter!("jetstream_turbo_did_filter_dropped_total").increment(1);
+        false
+    }
+
+    pub fn stats(&self) -> DidFilterStats {
+        DidFilterStats {
+            blocked_dids: self.blocked.read().expect("DID filter lock poisoned").len(),
+            allowed_dids: self
+                .allowed
+                .read()
+                .expect("DID filter lock poisoned")
+                .as_ref()
+                .map(HashSet::len),
+            dropped_messages: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+
+    #[test]
+    fn test_blocklist_wins_and_allowlist_restricts() {
+        let allowed = create_post_message(1);
+        let blocked = create_post_message(2);
+        let other = create_post_message(3);
+
+        let blocklist = [blocked.did.clone()];
+        let filter = DidFilter::new(&blocklist, None, &[], None).unwrap();
+        assert!(filter.allows_message(&allowed));
+        assert!(!filter.allows_message(&blocked));
+        assert!(filter.allows_message(&other));
+
+        let filter = DidFilter::new(
+            &blocklist,
+            None,
+            &[allowed.did.clone(), blocked.did.clone()],
+            None,
+        )
+        .unwrap();
+        assert!(filter.allows_message(&allowed));
+        assert!(!filter.allows_message(&blocked));
+        assert!(!filter.allows_message(&other));
+        assert_eq!(
+            filter.stats(),
+            DidFilterStats {
+                blocked_dids: 1,
+                allowed_dids: Some(2),
+                dropped_messages: 2,
+            }
+        );
+
+        assert!(!DidFilter::default().is_enabled());
+    }
+
+    #[test]
+    fn test_list_files_are_reloaded_when_they_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocklist.txt");
+        std::fs::write(
+            &path,
+            "# spam farm\ndid:plc:one\n\ndid:plc:two # and its twin\n",
+        )
+        .unwrap();
+
+        let filter = DidFilter::new(&[], path.to_str(), &[], None).unwrap();
+        assert!(!filter.allows("did:plc:one"));
+        assert!(!filter.allows("did:plc:two"));
+        assert!(filter.allows("did:plc:three"));
+
+        std::fs::write(&path, "did:plc:three\n").unwrap();
+        // Make sure the change is visible even on coarse-grained mtimes
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        filter.reload_if_changed();
+        assert!(filter.allows("did:plc:one"));
+        assert!(!filter.allows("did:plc:three"));
+
+        assert!(DidFilter::new(&[], dir.path().join("missing").to_str(), &[], None).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! Ingest lag of the enriched stream: how far behind real time each record is
+//! when it's hydrated. The most recent samples are kept for percentiles in
+//! stats, and every sample goes to a Prometheus histogram.
+
+use crate::models::enriched::EnrichedRecord;
+use metrics::histogram;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Samples kept for percentiles; a few seconds of the full firehose.
+pub const LAG_SAMPLES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct IngestLagStats {
+    /// Samples the percentiles are taken over
+    pub samples: usize,
+    pub latest_ms: u64,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct IngestLag {
+    samples: Mutex<VecDeque<u64>>,
+}
+
+impl IngestLag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the lag of each live record. Backfilled records are old by
+    /// design and would only drown out the live ones.
+    pub fn record(&self, records: &[Arc<EnrichedRecord>]) {
+        let lags = records
+            .iter()
+            .filter(|record| !record.hydrated_metadata.backfill && record.message.time_us.is_some())
+            .map(|record| record.metrics.ingest_lag_ms);
+        let mut samples = self.lock();
+        for lag_ms in lags {
+            histogram!("jetstream_turbo_ingest_lag_seconds").record(lag_ms as f64 / 1000.0);
+            if samples.len() == LAG_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(lag_ms);
+        }
+    }
+
+    pub fn stats(&self) -> IngestLagStats {
+        let samples = self.lock();
+        let Some(&latest_ms) = samples.back() else {
+            return IngestLagStats::default();
+        };
+        let mut sorted: Vec<u64> = samples.iter().copied().collect();
+        drop(samples);
+        sorted.sort_unstable();
+        // Nearest-rank percentile
+        let percentile = |p: usize| sorted[(sorted.len() * p).div_ceil(100).max(1) - 1];
+        IngestLagStats {
+            samples: sorted.len(),
+            latest_ms,
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+            max_ms: sorted[sorted.len() - 1],
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<u64>> {
+        self.samples.lock().expect("ingest lag lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+
+    fn record(lag_ms: u64, backfill: bool) -> Arc<EnrichedRecord> {
+        let mut record = EnrichedRecord::new(create_post_message(1));
+        record.metrics.ingest_lag_ms = lag_ms;
+        record.hydrated_metadata.backfill = backfill;
+        Arc::new(record)
+    }
+
+    #[test]
+    fn test_percentiles_cover_recent_live_records() {
+        let lag = IngestLag::new();
+        assert_eq!(lag.stats(), IngestLagStats::default());
+
+        let mut records: Vec<_> = (1..=100).map(|ms| record(ms, false)).collect();
+        records.push(record(60_000, true));
+        lag.record(&records);
+
+        let stats = lag.stats();
+        assert_eq!(stats.samples, 100);
+        assert_eq!(stats.latest_ms, 100);
+        assert_eq!((stats.p50_ms, stats.p90_ms, stats.p99_ms), (50, 90, 99));
+        assert_eq!(stats.max_ms, 100);
+
+        lag.record(&vec![record(7, false); LAG_SAMPLES]);
+        let stats = lag.stats();
+        assert_eq!(stats.samples, LAG_SAMPLES);
+        assert_eq!(stats.max_ms, 7);
+    }
+
+    #[test]
+    fn test_hydration_time_sets_lag_from_time_us() {
+        let mut message = create_post_message(1);
+        message.time_us = Some(chrono::Utc::now().timestamp_micros() as u64 - 2_000_000);
+        let mut record = EnrichedRecord::new(message);
+        record.record_ingest_lag();
+        assert!((2_000..10_000).contains(&record.metrics.ingest_lag_ms));
+    }
+}
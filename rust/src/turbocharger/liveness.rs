@@ -0,0 +1,367 @@
+//! Signals that tell a stuck pipeline from a healthy one: how long since the last
+//! upstream message, whether the stream is connected, how close the Bluesky
+//! session is to expiring and how often the sinks are failing. Each has a
+//! threshold; crossing any of them makes `/health` report unhealthy so an
+//! orchestrator restarts the instance.
+//!
+//! Readiness is separate: an instance that is still authenticating, draining or
+//! backed up isn't broken, it just shouldn't receive traffic yet.
+
+use crate::client::ConnectionState;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Sink error rates are measured over the current and previous window of this length.
+const SINK_ERROR_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Default)]
+struct ErrorWindow {
+    started_at: Option<Instant>,
+    attempts: u64,
+    failures: u64,
+    previous_attempts: u64,
+    previous_failures: u64,
+}
+
+impl ErrorWindow {
+    fn roll(&mut self, now: Instant) {
+        let started_at = *self.started_at.get_or_insert(now);
+        let elapsed = now.duration_since(started_at);
+        if elapsed >= SINK_ERROR_WINDOW {
+            // A gap longer than two windows leaves nothing recent to carry over
+            let stale = elapsed >= SINK_ERROR_WINDOW * 2;
+            self.previous_attempts = if stale { 0 } else { self.attempts };
+            self.previous_failures = if stale { 0 } else { self.failures };
+            self.attempts = 0;
+            self.failures = 0;
+            self.started_at = Some(now);
+        }
+    }
+
+    fn rate(&self) -> Option<f64> {
+        let attempts = self.attempts + self.previous_attempts;
+        (attempts > 0).then(|| (self.failures + self.previous_failures) as f64 / attempts as f64)
+    }
+}
+
+/// Activity recorded by the main loop and batch tasks.
+#[derive(Debug)]
+pub struct PipelineActivity {
+    started_at: Instant,
+    /// Milliseconds after `started_at` the last message arrived, 0 if none has
+    last_message_ms: AtomicU64,
+    sinks: Mutex<BTreeMap<&'static str, ErrorWindow>>,
+    /// Set while the main loop flushes its last batches after the stream ends
+    draining: AtomicBool,
+}
+
+impl Default for PipelineActivity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PipelineActivity {
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            last_message_ms: AtomicU64::new(0),
+            sinks: Mutex::new(BTreeMap::new()),
+            draining: AtomicBool::new(false),
+        }
+    }
+
+    pub fn record_message(&self) {
+        let elapsed_ms = self.started_at.elapsed().as_millis().max(1) as u64;
+        self.last_message_ms.store(elapsed_ms, Ordering::Relaxed);
+    }
+
+    /// Time since the last message, or since startup if none has arrived yet.
+    pub fn last_message_age(&self) -> Duration {
+        let last_ms = self.last_message_ms.load(Ordering::Relaxed);
+        self.started_at
+            .elapsed()
+            .saturating_sub(Duration::from_millis(last_ms))
+    }
+
+    pub fn has_received_messages(&self) -> bool {
+        self.last_message_ms.load(Ordering::Relaxed) > 0
+    }
+
+    pub fn set_draining(&self, draining: bool) {
+        self.draining.store(draining, Ordering::Relaxed);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    pub fn record_sink_result(&self, sink: &'static str, succeeded: bool) {
+        let mut sinks = self.sinks.lock().expect("sink error window lock poisoned");
+        let window = sinks.entry(sink).or_default();
+        window.roll(Instant::now());
+        window.attempts += 1;
+        if !succeeded {
+            window.failures += 1;
+        }
+    }
+
+    /// Failed fraction of recent writes, per sink that has been written to.
+    pub fn sink_error_rates(&self) -> BTreeMap<String, f64> {
+        let now = Instant::now();
+        let mut sinks = self.sinks.lock().expect("sink error window lock poisoned");
+        sinks
+            .iter_mut()
+            .filter_map(|(sink, window)| {
+                window.roll(now);
+                window.rate().map(|rate| (sink.to_string(), rate))
+            })
+            .collect()
+    }
+}
+
+/// Limits past which the instance is reported unhealthy. A zero duration
+/// disables that check.
+#[derive(Debug, Clone, Copy)]
+pub struct LivenessThresholds {
+    pub max_message_age: Duration,
+    pub max_disconnected: Duration,
+    pub min_session_ttl: Duration,
+    /// Ratio in `0.0..=1.0`; at 1.0 sink errors never fail the check
+    pub max_sink_error_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamLiveness {
+    pub last_message_age_seconds: u64,
+    pub messages_received: bool,
+    /// `None` for sources without a connection, such as replay
+    pub stream_connected: Option<bool>,
+    pub disconnected_for_seconds: Option<u64>,
+    /// Paused by an operator; message age isn't checked meanwhile
+    pub ingestion_paused: bool,
+    /// `None` when the session's expiry isn't known
+    pub session_expires_in_seconds: Option<i64>,
+    pub sink_error_rates: BTreeMap<String, f64>,
+    /// Checks over their threshold; any entry makes the instance unhealthy
+    pub failing_checks: Vec<String>,
+}
+
+impl StreamLiveness {
+    pub fn is_healthy(&self) -> bool {
+        self.failing_checks.is_empty()
+    }
+}
+
+impl LivenessThresholds {
+    /// `connection` is `None` for sources without a connection.
+    pub fn evaluate(
+        &self,
+        activity: &PipelineActivity,
+        connection: Option<ConnectionState>,
+        session_expires_in: Option<chrono::Duration>,
+    ) -> StreamLiveness {
+        let last_message_age = activity.last_message_age();
+        let sink_error_rates = activity.sink_error_rates();
+        let stream_connected = connection.map(|state| state == ConnectionState::Connected);
+        let disconnected_for = match connection {
+            Some(ConnectionState::Disconnected(down_for)) => Some(down_for),
+            _ => None,
+        };
+        let ingestion_paused = connection == Some(ConnectionState::Paused);
+        let mut failing_checks = Vec::new();
+
+        if !ingestion_paused
+            && !self.max_message_age.is_zero()
+            && last_message_age > self.max_message_age
+        {
+            failing_checks.push(format!(
+                "no message for {}s (limit {}s)",
+                last_message_age.as_secs(),
+                self.max_message_age.as_secs()
+            ));
+        }
+        if let Some(disconnected) = disconnected_for {
+            if !self.max_disconnected.is_zero() && disconnected > self.max_disconnected {
+                failing_checks.push(format!(
+                    "stream disconnected for {}s (limit {}s)",
+                    disconnected.as_secs(),
+                    self.max_disconnected.as_secs()
+                ));
+            }
+        }
+        if let Some(expires_in) = session_expires_in {
+            let min_ttl = chrono::Duration::from_std(self.min_session_ttl).unwrap_or_default();
+            if !self.min_session_ttl.is_zero() && expires_in < min_ttl {
+                failing_checks.push(format!(
+                    "session expires in {}s (limit {}s)",
+                    expires_in.num_seconds(),
+                    min_ttl.num_seconds()
+                ));
+            }
+        }
+        for (sink, rate) in &sink_error_rates {
+            if *rate > self.max_sink_error_rate {
+                failing_checks.push(format!(
+                    "{sink} error rate {:.0}% (limit {:.0}%)",
+                    rate * 100.0,
+                    self.max_sink_error_rate * 100.0
+                ));
+            }
+        }
+
+        StreamLiveness {
+            last_message_age_seconds: last_message_age.as_secs(),
+            messages_received: activity.has_received_messages(),
+            stream_connected,
+            disconnected_for_seconds: disconnected_for.map(|d| d.as_secs()),
+            ingestion_paused,
+            session_expires_in_seconds: session_expires_in.map(|d| d.num_seconds()),
+            sink_error_rates,
+            failing_checks,
+        }
+    }
+}
+
+/// Whether the instance should receive traffic, as reported by `/ready`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessStatus {
+    pub ready: bool,
+    pub authenticated: bool,
+    /// `None` in standalone mode
+    pub redis_connected: Option<bool>,
+    pub sqlite_available: bool,
+    /// `None` for sources without a connection, such as replay
+    pub stream_connected: Option<bool>,
+    pub draining: bool,
+    /// Free batch processing slots; at 0 the main loop is waiting on the sinks
+    pub batch_slots_available: usize,
+    pub memory_shedding: bool,
+    /// Reasons the instance isn't ready; empty when it is
+    pub not_ready: Vec<String>,
+}
+
+impl ReadinessStatus {
+    pub fn new(
+        authenticated: bool,
+        redis_connected: Option<bool>,
+        sqlite_available: bool,
+        stream_connected: Option<bool>,
+        draining: bool,
+        batch_slots_available: usize,
+        memory_shedding: bool,
+    ) -> Self {
+        let not_ready: Vec<String> = [
+            (!authenticated, "no authenticated Bluesky session"),
+            (redis_connected == Some(false), "Redis unreachable"),
+            (!sqlite_available, "SQLite unavailable"),
+            (stream_connected == Some(false), "stream disconnected"),
+            (draining, "draining"),
+            (batch_slots_available == 0, "batch processing saturated"),
+            (memory_shedding, "over memory soft limit"),
+        ]
+        .into_iter()
+        .filter(|(failing, _)| *failing)
+        .map(|(_, reason)| reason.to_string())
+        .collect();
+
+        Self {
+            ready: not_ready.is_empty(),
+            authenticated,
+            redis_connected,
+            sqlite_available,
+            stream_connected,
+            draining,
+            batch_slots_available,
+            memory_shedding,
+            not_ready,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: LivenessThresholds = LivenessThresholds {
+        max_message_age: Duration::from_secs(120),
+        max_disconnected: Duration::from_secs(60),
+        min_session_ttl: Duration::from_secs(300),
+        max_sink_error_rate: 0.5,
+    };
+
+    #[test]
+    fn test_thresholds_flag_stuck_pipelines() {
+        let activity = PipelineActivity::new();
+        activity.record_message();
+        let liveness = THRESHOLDS.evaluate(
+            &activity,
+            Some(ConnectionState::Connected),
+            Some(chrono::Duration::hours(1)),
+        );
+        assert!(liveness.is_healthy(), "{:?}", liveness.failing_checks);
+        assert_eq!(liveness.stream_connected, Some(true));
+
+        activity.record_sink_result("sqlite", true);
+        activity.record_sink_result("redis", false);
+        activity.record_sink_result("redis", false);
+        activity.record_sink_result("redis", true);
+        let liveness = THRESHOLDS.evaluate(
+            &activity,
+            Some(ConnectionState::Disconnected(Duration::from_secs(90))),
+            Some(chrono::Duration::seconds(30)),
+        );
+        assert_eq!(liveness.stream_connected, Some(false));
+        assert_eq!(liveness.sink_error_rates["sqlite"], 0.0);
+        assert!((liveness.sink_error_rates["redis"] - 2.0 / 3.0).abs() < f64::EPSILON);
+        assert_eq!(liveness.failing_checks.len(), 3);
+        assert!(liveness.failing_checks[0].starts_with("stream disconnected"));
+        assert!(liveness.failing_checks[1].starts_with("session expires"));
+        assert!(liveness.failing_checks[2].starts_with("redis error rate"));
+
+        // Replay sources have no connection or session to report
+        let liveness = THRESHOLDS.evaluate(&PipelineActivity::new(), None, None);
+        assert_eq!(liveness.stream_connected, None);
+    }
+
+    #[test]
+    fn test_paused_ingestion_is_not_a_stalled_stream() {
+        let thresholds = LivenessThresholds {
+            max_message_age: Duration::from_millis(1),
+            ..THRESHOLDS
+        };
+        let activity = PipelineActivity::new();
+        activity.record_message();
+        std::thread::sleep(Duration::from_millis(5));
+
+        let liveness = thresholds.evaluate(&activity, Some(ConnectionState::Paused), None);
+        assert!(liveness.is_healthy(), "{:?}", liveness.failing_checks);
+        assert!(liveness.ingestion_paused);
+        assert_eq!(liveness.stream_connected, Some(false));
+
+        let liveness = thresholds.evaluate(&activity, Some(ConnectionState::Connected), None);
+        assert!(liveness.failing_checks[0].starts_with("no message"));
+    }
+
+    #[test]
+    fn test_readiness_lists_every_blocking_reason() {
+        let status = ReadinessStatus::new(true, Some(true), true, None, false, 4, false);
+        assert!(status.ready);
+        assert!(status.not_ready.is_empty());
+
+        let status = ReadinessStatus::new(false, Some(true), true, Some(false), true, 0, false);
+        assert!(!status.ready);
+        assert_eq!(
+            status.not_ready,
+            vec![
+                "no authenticated Bluesky session",
+                "stream disconnected",
+                "draining",
+                "batch processing saturated",
+            ]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Soft memory limit for the pipeline. Usage is sampled periodically; while it is
+//! over budget the orchestrator sheds load (smaller batches, no post fetches)
+//! instead of letting the process grow until it is OOM-killed.
+
+use metrics::{counter, gauge};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+// Rough per-entry footprints used when RSS is unavailable. Profiles and posts are
+// stored as parsed structs behind an Arc, plus the key and cache bookkeeping.
+const APPROX_PROFILE_BYTES: u64 = 1024;
+const APPROX_POST_BYTES: u64 = 2048;
+const APPROX_MESSAGE_BYTES: u64 = 1024;
+/// Shedding stops once usage falls below this fraction of the limit, so a process
+/// hovering around the limit doesn't flap between modes every sample.
+const RECOVERY_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct MemoryUsage {
+    pub rss_bytes: Option<u64>,
+    pub cache_bytes: u64,
+    pub buffered_bytes: u64,
+    pub in_flight_batches: u64,
+}
+
+impl MemoryUsage {
+    pub fn new(
+        rss_bytes: Option<u64>,
+        cache_entries: (u64, u64),
+        buffered_messages: usize,
+        in_flight_batches: usize,
+    ) -> Self {
+        let (user_entries, post_entries) = cache_entries;
+        Self {
+            rss_bytes,
+            cache_bytes: user_entries * APPROX_PROFILE_BYTES + post_entries * APPROX_POST_BYTES,
+            buffered_bytes: buffered_messages as u64 * APPROX_MESSAGE_BYTES,
+            in_flight_batches: in_flight_batches as u64,
+        }
+    }
+
+    /// Bytes compared against the soft limit: RSS when the platform reports it,
+    /// otherwise the sum of the tracked estimates.
+    pub fn accounted_bytes(&self) -> u64 {
+        self.rss_bytes
+            .unwrap_or(self.cache_bytes + self.buffered_bytes)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryGuard {
+    soft_limit_bytes: Option<u64>,
+    shedding: AtomicBool,
+    shed_episodes: AtomicU64,
+    last_usage: Mutex<MemoryUsage>,
+}
+
+impl MemoryGuard {
+    pub fn new(soft_limit_mb: Option<u64>) -> Self {
+        Self {
+            soft_limit_bytes: soft_limit_mb
+                .filter(|mb| *mb > 0)
+                .map(|mb| mb * 1024 * 1024),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.soft_limit_bytes.is_some()
+    }
+
+    pub fn is_shedding(&self) -> bool {
+        self.shedding.load(Ordering::Relaxed)
+    }
+
+    /// Records a usage sample and returns whether load should be shed.
+    pub fn observe(&self, usage: MemoryUsage) -> bool {
+        *self
+            .last_usage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = usage;
+
+        let accounted = usage.accounted_bytes();
+        gauge!("jetstream_turbo_memory_accounted_bytes").set(accounted as f64);
+        gauge!("jetstream_turbo_memory_cache_bytes").set(usage.cache_bytes as f64);
+        gauge!("jetstream_turbo_memory_buffered_bytes").set(usage.buffered_bytes as f64);
+        gauge!("jetstream_turbo_memory_in_flight_batches").set(usage.in_flight_batches as f64);
+
+        let Some(limit) = self.soft_limit_bytes else {
+            return false;
+        };
+
+        let was_shedding = self.is_shedding();
+        let shedding = if was_shedding {
+            accounted as f64 >= limit as f64 * RECOVERY_RATIO
+        } else {
+            accounted > limit
+        };
+
+        if shedding && !was_shedding {
+            self.shed_episodes.fetch_add(1, Ordering::Relaxed);
+            counter!("jetstream_turbo_memory_shed_episodes_total").increment(1);
+            warn!(
+                "Memory usage {} bytes is over the soft limit of {} bytes; shedding load",
+                accounted, limit
+            );
+        } else if !shedding && was_shedding {
+            info!(
+                "Memory usage {} bytes is back under the soft limit; resuming normal processing",
+                accounted
+            );
+        }
+
+        self.shedding.store(shedding, Ordering::Relaxed);
+        gauge!("jetstream_turbo_memory_shedding").set(if shedding { 1.0 } else { 0.0 });
+        shedding
+    }
+
+    pub fn stats(&self) -> MemoryBudgetStats {
+        MemoryBudgetStats {
+            soft_limit_bytes: self.soft_limit_bytes,
+            shedding: self.is_shedding(),
+            shed_episodes: self.shed_episodes.load(Ordering::Relaxed),
+            usage: *self
+                .last_usage
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryBudgetStats {
+    pub soft_limit_bytes: Option<u64>,
+    pub shedding: bool,
+    pub shed_episodes: u64,
+    pub usage: MemoryUsage,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rss(mb: u64) -> MemoryUsage {
+        MemoryUsage::new(Some(mb * 1024 * 1024), (0, 0), 0, 0)
+    }
+
+    #[test]
+    fn test_guard_sheds_over_limit_with_hysteresis() {
+        let guard = MemoryGuard::new(Some(100));
+
+        assert!(!guard.observe(rss(80)));
+        assert!(guard.observe(rss(120)));
+        // Still above the recovery threshold (90 MB)
+        assert!(guard.observe(rss(95)));
+        assert!(!guard.observe(rss(85)));
+        assert!(guard.observe(rss(101)));
+
+        let stats = guard.stats();
+        assert_eq!(stats.shed_episodes, 2);
+        assert!(stats.shedding);
+        assert_eq!(stats.usage.rss_bytes, Some(101 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_disabled_guard_never_sheds_and_estimates_without_rss() {
+        let guard = MemoryGuard::new(None);
+        assert!(!guard.is_enabled());
+        assert!(!guard.observe(rss(10_000)));
+
+        let usage = MemoryUsage::new(None, (2, 1), 3, 1);
+        assert_eq!(
+            usage.accounted_bytes(),
+            2 * APPROX_PROFILE_BYTES + APPROX_POST_BYTES + 3 * APPROX_MESSAGE_BYTES
+        );
+    }
+}
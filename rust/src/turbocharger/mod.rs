@@ -1,9 +1,45 @@
+pub mod accounts;
+pub mod broadcast;
 pub mod buffer;
+pub mod builder;
+pub mod collections;
 pub mod coordinator;
+pub mod did_filter;
+pub mod lag;
+pub mod liveness;
+pub mod memory;
 pub mod orchestrator;
+pub mod session;
+pub mod sharding;
+pub mod sink_latency;
+pub mod streams;
+pub mod timeseries;
+pub mod watchlist;
 
+pub use accounts::{AccountRemovalStats, AccountRemovals};
+pub use broadcast::{BroadcastRecord, BroadcastStats, RecordBroadcaster, RecordSubscription};
+pub use builder::{
+    FetcherComponents, FromSettings, InjectedFetchers, InjectedSinks, InjectedSource,
+    ResolvedSinks, SinkComponents, SourceComponent, TurboChargerBuilder,
+};
+pub use collections::{CollectionCounters, CollectionCounts, CollectionStats};
+pub use did_filter::{DidFilter, DidFilterStats};
+pub use lag::{IngestLag, IngestLagStats};
+pub use liveness::{LivenessThresholds, PipelineActivity, ReadinessStatus, StreamLiveness};
+pub use memory::{MemoryBudgetStats, MemoryGuard, MemoryUsage};
 pub use orchestrator::{
-    CacheStateDiagnostics, HealthDiagnostics, HealthStatus, MemoryPeakDiagnostics,
-    NotRedisStateDiagnostics, ProcessMemoryDiagnostics, ProductionTurboCharger,
-    SQLiteStateDiagnostics, TurboCharger, TurboStats,
+    BackfillReport, CacheStateDiagnostics, DuplicateStats, HealthDiagnostics, HealthStatus,
+    MemoryPeakDiagnostics, NotRedisStateDiagnostics, ProcessMemoryDiagnostics,
+    ProductionTurboCharger, SQLiteStateDiagnostics, TurboCharger, TurboStats,
+};
+pub use session::{SessionRefreshStats, SessionRefreshTracker};
+pub use sharding::{ShardAssignment, ShardFilter, ShardStats};
+pub use sink_latency::{
+    Sink, SinkLatency, SinkLatencyStats, SlowWriteThresholds, WriteLatencyStats,
+};
+pub use streams::{
+    CompiledStreamFilter, OutputStream, OutputStreamConfig, OutputStreamStats, OutputStreams,
+    StreamFilter,
 };
+pub use timeseries::{ThroughputPoint, ThroughputSeries, ThroughputSeriesSnapshot};
+pub use watchlist::{Watchlist, WatchlistAlert, WatchlistStats, WATCHLIST_STREAM};
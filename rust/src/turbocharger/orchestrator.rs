@@ -1,28 +1,76 @@
 use crate::client::{
-    BlueskyAuthClient, BlueskyClient, JetstreamClient, MessageSource, PostFetcher, ProfileFetcher,
+    BackfillClient, BatchFillStats, BlueskyClient, ConnectionState, IngestSource, MessageSource,
+    PlcClient, PostFetcher, ProfileFetcher, SESSION_REFRESH_MARGIN,
 };
 use crate::config::Settings;
-use crate::hydration::{Hydrator, TurboCache};
-use crate::models::enriched::EnrichedRecord;
+use crate::hydration::{
+    AuthorPrivacy, Degradation, DegradationConfig, DegradationStats, Hydrator, LabelPolicy,
+    LexiconValidator, PrivacyStats, Pseudonymizer, SpamScorer, TurboCache, UrlUnfurler,
+};
+use crate::models::enriched::{EnrichedRecord, OutputFormat};
 use crate::models::{
+    at_uri::AtUri,
+    bluesky::BlueskyProfile,
     errors::{TurboError, TurboResult},
     jetstream::JetstreamMessage,
 };
-use crate::storage::{EventPublisher, RecordStore, RedisStore, SQLitePragmaConfig, SQLiteStore};
-use crate::telemetry::ErrorReporter;
+#[cfg(feature = "redis")]
+use crate::storage::RedisStore;
+use crate::storage::{
+    aggregates::HOUR_SECONDS, merge_hourly_counts, ArchiveCompactor, DatabaseRotator,
+    EventPublisher, HourlyAggregate, LiveSink, PartitionedReader, RecordProjection, RecordStore,
+    SQLitePragmaConfig, ShardedSQLiteStore, Thread,
+};
+#[cfg(feature = "s3")]
+use crate::storage::{BlobMirror, BlobMirrorConfig};
+use crate::telemetry::{DropCounters, DropReason, DropStats, ErrorReporter};
+use crate::turbocharger::accounts::{AccountRemovalStats, AccountRemovals};
+use crate::turbocharger::broadcast::{BroadcastStats, RecordBroadcaster, RecordSubscription};
+use crate::turbocharger::builder::{
+    FetcherComponents, ResolvedSinks, SinkComponents, SourceComponent, TurboChargerBuilder,
+};
+use crate::turbocharger::collections::{BatchTally, CollectionCounters, CollectionStats};
+use crate::turbocharger::did_filter::{DidFilter, DidFilterStats};
+use crate::turbocharger::lag::{IngestLag, IngestLagStats};
+use crate::turbocharger::liveness::{
+    LivenessThresholds, PipelineActivity, ReadinessStatus, StreamLiveness,
+};
+use crate::turbocharger::memory::{MemoryBudgetStats, MemoryGuard, MemoryUsage};
+use crate::turbocharger::session::{
+    SessionRefreshStats, SessionRefreshTracker, SESSION_REFRESH_INTERVAL,
+};
+use crate::turbocharger::sharding::{ShardAssignment, ShardFilter, ShardStats};
+use crate::turbocharger::sink_latency::{Sink, SinkLatency, SinkLatencyStats, SlowWriteThresholds};
+use crate::turbocharger::streams::{OutputStreamStats, OutputStreams};
+use crate::turbocharger::timeseries::{ThroughputSeries, ThroughputSeriesSnapshot};
+use crate::turbocharger::watchlist::{Watchlist, WatchlistStats, WATCHLIST_STREAM};
 use futures::StreamExt;
+use governor::clock::{Clock, DefaultClock};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
 use serde::Serialize;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::num::NonZeroU32;
 use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::{broadcast, Semaphore};
+use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 use tokio::time::{interval, sleep};
-use tracing::{error, info, trace};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, trace, warn};
 
 const BATCH_SIZE: usize = 25;
+/// Batch size while shedding load under memory pressure.
+const SHED_BATCH_SIZE: usize = 5;
+const MEMORY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How often DID list files are checked for changes.
+const DID_FILTER_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+/// How stale the current hour of `/api/v1/aggregates` may get.
+const HOURLY_ROLL_UP_INTERVAL: Duration = Duration::from_secs(60);
 const BATCH_REPORT_LOG_TARGET: &str = "jetstream_turbo.batch_report";
+/// `TurboError::Internal` message `run` returns when its source runs out.
+pub(crate) const STREAM_ENDED: &str = "Jetstream stream ended";
 // The hydrator can consume up to one profile batch and one post batch per flush.
 // At 200ms, the time-based path can generate 5 flushes/sec, which maps to 10 API
 // requests/sec in the worst case and fully consumes the shared Bluesky limit.
@@ -30,24 +78,108 @@ const BATCH_REPORT_LOG_TARGET: &str = "jetstream_turbo.batch_report";
 // longer to fill without changing the API-imposed batch size of 25.
 const MAX_WAIT_TIME_MS: u64 = 250;
 const BATCH_REPORT_INTERVAL_SECS: u64 = 5 * 60;
+const RATE_LIMIT_RETRIES: u32 = 2;
+const RATE_LIMIT_FALLBACK_DELAY: Duration = Duration::from_secs(1);
+const RATE_LIMIT_MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// Runs `attempt` again after each `RateLimited` error, waiting its `Retry-After`
+/// (capped at `RATE_LIMIT_MAX_WAIT`), for up to `RATE_LIMIT_RETRIES` retries.
+/// Any other error is returned at once.
+async fn retry_rate_limited<F, Fut>(mut attempt: F) -> TurboResult<()>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = TurboResult<()>>,
+{
+    let mut retries = 0;
+    loop {
+        match attempt().await {
+            Err(e @ TurboError::RateLimited { .. }) if retries < RATE_LIMIT_RETRIES => {
+                let wait = e
+                    .retry_after()
+                    .unwrap_or(RATE_LIMIT_FALLBACK_DELAY)
+                    .min(RATE_LIMIT_MAX_WAIT);
+                warn!("{}; retrying batch prefetch in {:?}", e, wait);
+                sleep(wait).await;
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
 const MEMORY_PEAK_WINDOW_SECS: u64 = 24 * 60 * 60;
+/// `redis_version` in stats when running without Redis.
+const STANDALONE_ENGINE: &str = "standalone";
+/// What reads needing SQLite report with injected sinks.
+const INJECTED_SINKS: &str = "SQLite-backed reads; injected sinks don't open SQLite";
 
 pub struct TurboCharger<M, P, Po, S, E> {
     settings: Settings,
     message_source: M,
-    bluesky_client: Arc<BlueskyClient>,
+    /// The client `TurboChargerBuilder` authenticated, if it built the fetchers
+    bluesky_client: Option<Arc<BlueskyClient>>,
     hydrator: Hydrator<P, Po>,
     record_store: Arc<S>,
     event_publisher: Arc<E>,
-    sqlite_store: Arc<SQLiteStore>,
-    redis_store: Arc<RedisStore>,
+    /// `None` with injected sinks, which open neither SQLite nor Redis, so
+    /// `record_store` then needs its own lookups and retention cleanup
+    sqlite_store: Option<Arc<ShardedSQLiteStore>>,
+    record_reader: Option<Arc<PartitionedReader>>,
+    /// `None` in standalone mode
+    #[cfg(feature = "redis")]
+    redis_store: Option<Arc<RedisStore>>,
     semaphore: Arc<Semaphore>,
-    broadcast_sender: broadcast::Sender<EnrichedRecord>,
+    broadcaster: RecordBroadcaster,
+    output_streams: OutputStreams,
+    watchlist: Arc<Watchlist>,
+    projection: Arc<RecordProjection>,
+    delete_events: Arc<AtomicU64>,
+    account_removals: Arc<AccountRemovals>,
+    /// Keeps authors asking for privacy out of public feeds and lookups
+    privacy: Arc<AuthorPrivacy>,
+    did_filter: DidFilter,
+    sharding: ShardFilter,
+    /// Messages discarded past the source, shared with the hydrator
+    drops: DropCounters,
+    collection_counters: Arc<CollectionCounters>,
+    throughput: Arc<ThroughputSeries>,
+    ingest_lag: Arc<IngestLag>,
+    sink_latency: Arc<SinkLatency>,
     error_reporter: ErrorReporter,
     memory_peak_window: Mutex<MemoryPeakWindow>,
+    memory_guard: MemoryGuard,
+    session_refresh: SessionRefreshTracker,
+    activity: Arc<PipelineActivity>,
+    liveness_thresholds: LivenessThresholds,
+    /// Fetches records that aren't stored for hydrate-on-miss lookups
+    record_fetcher: BackfillClient,
+    /// Caps those fetches; `None` when hydrate-on-miss is off
+    hydrate_on_miss_limiter: Option<DefaultDirectRateLimiter>,
+    /// Cancelled by `shutdown` to stop the run loop after a final flush
+    shutdown: CancellationToken,
 }
 
-impl TurboCharger<JetstreamClient, BlueskyClient, BlueskyClient, SQLiteStore, RedisStore> {
+/// Everything a batch needs from the `TurboCharger`, cloned out so the batch can
+/// run on its own task.
+struct BatchContext<P, Po, S, E> {
+    hydrator: Hydrator<P, Po>,
+    record_store: Arc<S>,
+    event_publisher: Arc<E>,
+    broadcaster: RecordBroadcaster,
+    output_streams: OutputStreams,
+    watchlist: Arc<Watchlist>,
+    projection: Arc<RecordProjection>,
+    record_reader: Option<Arc<PartitionedReader>>,
+    delete_events: Arc<AtomicU64>,
+    account_removals: Arc<AccountRemovals>,
+    privacy: Arc<AuthorPrivacy>,
+    collection_counters: Arc<CollectionCounters>,
+    throughput: Arc<ThroughputSeries>,
+    ingest_lag: Arc<IngestLag>,
+    sink_latency: Arc<SinkLatency>,
+    activity: Arc<PipelineActivity>,
+}
+
+impl ProductionTurboCharger {
     pub async fn new(
         settings: Settings,
         modulo: u32,
@@ -58,111 +190,280 @@ impl TurboCharger<JetstreamClient, BlueskyClient, BlueskyClient, SQLiteStore, Re
             "Initializing TurboCharger with modulo={}, shard={}",
             modulo, shard
         );
+        TurboChargerBuilder::new(settings)
+            .error_reporter(error_reporter)
+            .shard_assignment(ShardAssignment::new(modulo, shard)?)
+            .build()
+            .await
+    }
+}
 
-        // Initialize Jetstream client
-        let jetstream_client = JetstreamClient::new(
-            settings.jetstream_hosts.clone(),
-            settings.wanted_collections.clone(),
-        )
-        .with_channel_capacity(settings.channel_capacity);
-
-        // Authenticate directly with Bluesky
-        let auth_client = Arc::new(BlueskyAuthClient::new(
-            settings.bluesky_handle.clone(),
-            settings.bluesky_app_password.clone(),
-        )?);
+impl<M, P, Po, S, E> TurboCharger<M, P, Po, S, E>
+where
+    M: MessageSource + Send + Sync + 'static,
+    P: ProfileFetcher + Send + Sync + 'static,
+    Po: PostFetcher + Send + Sync + 'static,
+    S: RecordStore + Send + Sync + 'static,
+    E: EventPublisher + Send + Sync + 'static,
+{
+    /// Builds whatever `builder` wasn't given from its settings; see `TurboChargerBuilder`.
+    pub(super) async fn assemble<Src, F, Sk>(
+        builder: TurboChargerBuilder<Src, F, Sk>,
+    ) -> TurboResult<Self>
+    where
+        Src: SourceComponent<Source = M>,
+        F: FetcherComponents<Profiles = P, Posts = Po>,
+        Sk: SinkComponents<Store = S, Publisher = E>,
+    {
+        let TurboChargerBuilder {
+            settings,
+            source,
+            fetchers,
+            sinks,
+            cache,
+            error_reporter,
+            shard_assignment,
+        } = builder;
+        let error_reporter = match error_reporter {
+            Some(error_reporter) => error_reporter,
+            None => {
+                ErrorReporter::new(
+                    settings.posthog_api_key.clone(),
+                    settings.posthog_host.clone(),
+                )
+                .await
+            }
+        };
 
-        let auth_response = auth_client.authenticate().await?;
-        info!(
-            "Successfully authenticated with Bluesky as {}",
-            settings.bluesky_handle
-        );
-        let bluesky_client = Arc::new(BlueskyClient::new(
-            vec![auth_response.access_jwt.clone()],
-            Some(auth_client.clone()),
-            settings.profile_batch_size,
-            settings.post_batch_size,
-            settings.profile_batch_wait_ms,
-            settings.post_batch_wait_ms,
-        )?);
-        bluesky_client
-            .refresh_sessions(
-                vec![auth_response.access_jwt],
-                Some(auth_response.refresh_jwt),
-                auth_response.expires_at,
-            )
-            .await;
+        let (profile_fetcher, post_fetcher, bluesky_client) =
+            fetchers.resolve(&settings, &error_reporter).await?;
 
         // Initialize cache
-        let cache = TurboCache::new(settings.cache_size_users, settings.cache_size_posts);
+        let cache = cache.unwrap_or_else(|| {
+            TurboCache::new(settings.cache_size_users, settings.cache_size_posts)
+        });
 
         // Initialize hydrator
-        let hydrator = Hydrator::new(cache, bluesky_client.clone(), bluesky_client.clone());
-
-        // Initialize storage
-        let db_path = format!("{}/jetstream.db", settings.db_dir);
-        let sqlite_store = Arc::new(
-            SQLiteStore::new(
-                &db_path,
-                SQLitePragmaConfig {
-                    cache_size_kib: settings.sqlite_cache_size_kib,
-                    mmap_size_mb: settings.sqlite_mmap_size_mb,
-                    journal_size_limit_mb: settings.sqlite_journal_size_limit_mb,
-                },
+        let drops = DropCounters::new();
+        let user_agent = settings.user_agent();
+        let hydrator = Hydrator::new(cache, profile_fetcher, post_fetcher)
+            .with_drop_counters(drops.clone())
+            .with_label_policy(LabelPolicy::new(
+                settings.filtered_labels.clone(),
+                settings.label_filter_mode,
+            ))
+            .with_validator(LexiconValidator::new(settings.record_validation));
+        let hydrator = if settings.spam_scoring || settings.spam_drop_threshold.is_some() {
+            hydrator.with_spam_scorer(SpamScorer::new(settings.spam_drop_threshold))
+        } else {
+            hydrator
+        };
+        let hydrator = if settings.url_unfurling {
+            hydrator.with_url_unfurler(
+                UrlUnfurler::new(settings.unfurl_requests_per_second)?
+                    .with_user_agent(&user_agent)?,
             )
-            .await?,
-        );
+        } else {
+            hydrator
+        };
+        let hydrator = match &settings.pseudonymize_key {
+            Some(key) => {
+                info!("Pseudonymizing DIDs and handles before records reach any sink");
+                hydrator.with_pseudonymizer(Pseudonymizer::new(key))
+            }
+            None => hydrator,
+        };
+        let did_resolver = match &settings.plc_directory_url {
+            Some(url) => Some(Arc::new(
+                PlcClient::new(url.clone())?.with_user_agent(&user_agent)?,
+            )),
+            None => None,
+        };
+        let hydrator = match &did_resolver {
+            Some(resolver) => hydrator.with_did_resolver(Arc::clone(resolver)),
+            None => hydrator,
+        };
+        let hydrator = if settings.adaptive_degradation {
+            hydrator.with_degradation(Arc::new(Degradation::new(DegradationConfig {
+                latency_threshold: Duration::from_millis(settings.degradation_latency_ms),
+                recovery_period: Duration::from_secs(settings.degradation_recovery_secs),
+                ..Default::default()
+            })))
+        } else {
+            hydrator
+        };
+        #[cfg(feature = "s3")]
+        let hydrator = match &settings.blob_mirror_bucket {
+            Some(bucket) => hydrator.with_blob_mirror(Arc::new(
+                BlobMirror::s3(&BlobMirrorConfig {
+                    bucket: bucket.clone(),
+                    region: settings.blob_mirror_region.clone(),
+                    endpoint: settings.blob_mirror_endpoint.clone(),
+                    public_url: settings.blob_mirror_public_url.clone(),
+                    key_prefix: settings.blob_mirror_prefix.clone(),
+                })?
+                .with_user_agent(&user_agent)?,
+            )),
+            None => hydrator,
+        };
+        #[cfg(not(feature = "s3"))]
+        if settings.blob_mirror_bucket.is_some() {
+            warn!("BLOB_MIRROR_BUCKET is set but blob mirroring requires the s3 feature");
+        }
 
-        let redis_store = Arc::new(
-            RedisStore::new(
-                &settings.redis_url,
-                settings.stream_name_redis.clone(),
-                settings.trim_maxlen,
-            )
-            .await?,
-        );
+        // Initialize storage
+        let ResolvedSinks {
+            store: record_store,
+            publisher: event_publisher,
+            sqlite: sqlite_store,
+            publisher_sink,
+            #[cfg(feature = "redis")]
+                redis: redis_store,
+        } = sinks.resolve(&settings).await?;
+
+        let message_source = source.resolve(&settings, sqlite_store.as_deref()).await?;
+
+        let record_reader = match &sqlite_store {
+            Some(sqlite_store) => {
+                let record_reader = PartitionedReader::new(sqlite_store.clone(), &settings.db_dir)
+                    .with_delete_mode(settings.delete_mode)
+                    .with_account_removal_mode(settings.account_removal_mode);
+                let partition_count = record_reader.refresh().await?;
+                if partition_count > 0 {
+                    info!("Reading across {} rotated databases", partition_count);
+                }
+                Some(Arc::new(record_reader))
+            }
+            None => None,
+        };
 
         // Initialize semaphore for concurrency control
-        let semaphore = Arc::new(Semaphore::new(
-            settings.max_concurrent_requests.max(1) as usize
-        ));
-
-        // Initialize broadcast channel
-        let (broadcast_sender, _) = broadcast::channel(1000);
+        let semaphore = Arc::new(Semaphore::new(settings.max_concurrent_requests.max(1)));
+
+        let broadcaster = RecordBroadcaster::new(settings.broadcast_capacity);
+        #[cfg(feature = "redis")]
+        let output_streams = match &redis_store {
+            Some(redis) => OutputStreams::with_redis(
+                &settings.output_streams,
+                redis,
+                settings.broadcast_capacity,
+            ),
+            None => OutputStreams::new(&settings.output_streams, settings.broadcast_capacity),
+        };
+        #[cfg(not(feature = "redis"))]
+        let output_streams =
+            OutputStreams::new(&settings.output_streams, settings.broadcast_capacity);
+        for stream in &settings.output_streams {
+            info!("Output stream '{}' enabled", stream.name);
+        }
+        let watchlist = Watchlist::new(
+            &settings.watchlist,
+            settings.broadcast_capacity,
+            settings.watchlist_webhook_url.clone(),
+        )?
+        .with_user_agent(&user_agent)?;
+        #[cfg(feature = "redis")]
+        let watchlist =
+            match &redis_store {
+                Some(redis) => watchlist.with_publisher(
+                    redis.for_stream(settings.watchlist_redis_stream.clone().unwrap_or_else(
+                        || format!("{}:{}", redis.stream_name(), WATCHLIST_STREAM),
+                    )),
+                ),
+                None => watchlist,
+            };
+        if !settings.watchlist.is_empty() {
+            info!("Watching {} accounts", watchlist.entries().len());
+        }
+        let memory_guard = MemoryGuard::new(settings.memory_soft_limit_mb);
+        let liveness_thresholds = LivenessThresholds {
+            // Nothing arrives when serving only stored records
+            max_message_age: if settings.serve_only {
+                Duration::ZERO
+            } else {
+                Duration::from_secs(settings.health_max_message_age_secs)
+            },
+            max_disconnected: Duration::from_secs(settings.health_max_disconnected_secs),
+            min_session_ttl: Duration::from_secs(settings.health_min_session_ttl_secs),
+            max_sink_error_rate: settings.health_max_sink_error_rate,
+        };
+        let record_fetcher = BackfillClient::new(settings.backfill_api_url.clone())?
+            .with_http_policy(settings.backfill_http_policy())?;
+        let record_fetcher = match did_resolver {
+            Some(resolver) => record_fetcher.with_pds_resolver(resolver),
+            None => record_fetcher,
+        };
+        let hydrate_on_miss_limiter = NonZeroU32::new(settings.hydrate_on_miss_per_minute)
+            .map(|per_minute| RateLimiter::direct(Quota::per_minute(per_minute)));
+        let projection = RecordProjection::new(&settings.record_projection);
+        let account_removals = AccountRemovals::new(settings.account_removal_mode);
+        let privacy = AuthorPrivacy::new(settings.author_privacy);
+        let did_filter = DidFilter::from_settings(&settings)?;
+        let sink_latency = SinkLatency::new(
+            SlowWriteThresholds {
+                threshold: Duration::from_millis(settings.slow_write_threshold_ms),
+                consecutive_batches: settings.slow_write_batches,
+            },
+            error_reporter.clone(),
+        )
+        .with_publisher(publisher_sink);
 
         info!("TurboCharger initialized successfully");
 
         Ok(Self {
             settings,
-            message_source: jetstream_client,
+            message_source,
             bluesky_client,
             hydrator,
-            record_store: sqlite_store.clone(),
-            event_publisher: redis_store.clone(),
+            record_store,
+            event_publisher,
             sqlite_store,
+            record_reader,
+            #[cfg(feature = "redis")]
             redis_store,
             semaphore,
-            broadcast_sender,
+            broadcaster,
+            output_streams,
+            watchlist: Arc::new(watchlist),
+            projection: Arc::new(projection),
+            delete_events: Arc::new(AtomicU64::new(0)),
+            account_removals: Arc::new(account_removals),
+            privacy: Arc::new(privacy),
+            did_filter,
+            sharding: ShardFilter::new(shard_assignment),
+            drops,
+            collection_counters: Arc::new(CollectionCounters::new()),
+            throughput: Arc::new(ThroughputSeries::new()),
+            ingest_lag: Arc::new(IngestLag::new()),
+            sink_latency: Arc::new(sink_latency),
             error_reporter,
             memory_peak_window: Mutex::new(MemoryPeakWindow::new(MEMORY_PEAK_WINDOW_SECS)),
+            memory_guard,
+            session_refresh: SessionRefreshTracker::default(),
+            activity: Arc::new(PipelineActivity::new()),
+            liveness_thresholds,
+            record_fetcher,
+            hydrate_on_miss_limiter,
+            shutdown: CancellationToken::new(),
         })
     }
-}
 
-impl<M, P, Po, S, E> TurboCharger<M, P, Po, S, E>
-where
-    M: MessageSource + Send + Sync + 'static,
-    P: ProfileFetcher + Send + Sync + 'static,
-    Po: PostFetcher + Send + Sync + 'static,
-    S: RecordStore + Send + Sync + 'static,
-    E: EventPublisher + Send + Sync + 'static,
-{
+    /// Runs until the stream ends, which is an error, or until `shutdown` is
+    /// called, which returns `Ok` once buffered messages reach the sinks.
     pub async fn run(&self) -> TurboResult<()> {
+        if self.shutdown.is_cancelled() {
+            return Ok(());
+        }
         info!("Starting TurboCharger main loop");
 
         let message_stream = self.message_source.stream_messages().await?;
+        self.activity.set_draining(false);
 
         let mut last_stats = std::time::Instant::now();
+        let mut last_memory_check = std::time::Instant::now();
+        let mut last_did_filter_check = std::time::Instant::now();
+        let mut shard_generation = self.sharding.generation();
+        let mut batch_size = BATCH_SIZE;
         let mut batch_reporter = BatchReporter::new(BATCH_SIZE);
         let mut buffer: Vec<JetstreamMessage> = Vec::with_capacity(BATCH_SIZE);
         let mut flush_interval = interval(Duration::from_millis(MAX_WAIT_TIME_MS));
@@ -176,15 +477,16 @@ where
                 result = message_stream.next() => {
                     match result {
                         Some(Ok(message)) => {
+                            self.activity.record_message();
                             if self.should_process_message(&message) {
                                 buffer.push(message);
                             }
 
-                            if buffer.len() >= BATCH_SIZE {
+                            if buffer.len() >= batch_size {
                                 batch_reporter.record(BatchFlushReason::Full, buffer.len());
                                 // Reuse batch_buffer to avoid allocation
                                 batch_buffer.clear();
-                                batch_buffer.extend(buffer.drain(..));
+                                batch_buffer.append(&mut buffer);
                                 self.spawn_batch_processing(
                                     std::mem::take(&mut batch_buffer),
                                     &mut batch_tasks,
@@ -198,9 +500,10 @@ where
                         None => break,
                     }
                 }
+                _ = self.shutdown.cancelled() => break,
                 _ = flush_interval.tick() => {
                     if !buffer.is_empty() {
-                        let flush_reason = if buffer.len() >= BATCH_SIZE {
+                        let flush_reason = if buffer.len() >= batch_size {
                             BatchFlushReason::Full
                         } else {
                             BatchFlushReason::Timer
@@ -208,7 +511,7 @@ where
                         batch_reporter.record(flush_reason, buffer.len());
                         // Reuse batch_buffer to avoid allocation
                         batch_buffer.clear();
-                        batch_buffer.extend(buffer.drain(..));
+                        batch_buffer.append(&mut buffer);
                         self.spawn_batch_processing(
                             std::mem::take(&mut batch_buffer),
                             &mut batch_tasks,
@@ -222,6 +525,38 @@ where
                 self.handle_batch_task_result(task_result)?;
             }
 
+            if self.sharding.generation() != shard_generation {
+                shard_generation = self.sharding.generation();
+                // Messages buffered under the old assignment may belong to
+                // another instance now, and batches in flight finish before
+                // any record is handled under the new one
+                let buffered = buffer.len();
+                buffer.retain(|message| self.sharding.owns(message.extract_did()));
+                self.drops
+                    .record(DropReason::OtherShard, buffered - buffer.len());
+                self.drain_batch_tasks(&mut batch_tasks).await?;
+                info!(
+                    "Applied shard assignment {:?}; dropped {} of {} buffered messages",
+                    self.sharding.assignment(),
+                    buffered - buffer.len(),
+                    buffered
+                );
+            }
+
+            if self.memory_guard.is_enabled()
+                && last_memory_check.elapsed() >= MEMORY_CHECK_INTERVAL
+            {
+                batch_size = self.check_memory_budget(buffer.len(), batch_tasks.len());
+                last_memory_check = std::time::Instant::now();
+            }
+
+            if self.did_filter.is_enabled()
+                && last_did_filter_check.elapsed() >= DID_FILTER_RELOAD_INTERVAL
+            {
+                self.did_filter.reload_if_changed();
+                last_did_filter_check = std::time::Instant::now();
+            }
+
             if last_stats.elapsed() >= Duration::from_secs(30) {
                 let process_memory = collect_process_memory_diagnostics();
                 let _ = self.observe_memory_sample(&process_memory);
@@ -237,17 +572,23 @@ where
             }
         }
 
+        self.activity.set_draining(true);
         if !buffer.is_empty() {
             batch_reporter.record(BatchFlushReason::Shutdown, buffer.len());
-            self.process_batch(buffer).await?;
+            self.process_batch(buffer, false).await?;
         }
 
         batch_reporter.log_if_window_has_data();
 
         self.drain_batch_tasks(&mut batch_tasks).await?;
 
+        if self.shutdown.is_cancelled() {
+            info!("TurboCharger main loop stopped after flushing buffered messages");
+            return Ok(());
+        }
+
         error!("Jetstream stream ended unexpectedly");
-        Err(TurboError::Internal("Jetstream stream ended".to_string()))
+        Err(TurboError::Internal(STREAM_ENDED.to_string()))
     }
 
     async fn spawn_batch_processing(
@@ -255,24 +596,14 @@ where
         batch: Vec<JetstreamMessage>,
         batch_tasks: &mut JoinSet<TurboResult<usize>>,
     ) -> TurboResult<()> {
-        let hydrator = self.hydrator.clone();
-        let record_store = Arc::clone(&self.record_store);
-        let event_publisher = Arc::clone(&self.event_publisher);
-        let broadcast_sender = self.broadcast_sender.clone();
+        let context = self.batch_context();
         let permit = self.semaphore.clone().acquire_owned().await.map_err(|e| {
             TurboError::Internal(format!("Batch semaphore closed unexpectedly: {e}"))
         })?;
 
         batch_tasks.spawn(async move {
             let _permit = permit;
-            Self::process_batch_internal(
-                hydrator,
-                record_store,
-                event_publisher,
-                broadcast_sender,
-                batch,
-            )
-            .await
+            Self::process_batch_internal(context, batch, false).await
         });
 
         Ok(())
@@ -318,67 +649,375 @@ where
         Ok(())
     }
 
-    async fn process_batch(&self, batch: Vec<JetstreamMessage>) -> TurboResult<usize> {
+    async fn process_batch(
+        &self,
+        batch: Vec<JetstreamMessage>,
+        backfill: bool,
+    ) -> TurboResult<usize> {
         let permit = self.semaphore.acquire().await.map_err(|e| {
             TurboError::Internal(format!("Batch semaphore closed unexpectedly: {e}"))
         })?;
-        let count = Self::process_batch_internal(
-            self.hydrator.clone(),
-            Arc::clone(&self.record_store),
-            Arc::clone(&self.event_publisher),
-            self.broadcast_sender.clone(),
-            batch,
-        )
-        .await?;
+        let count = Self::process_batch_internal(self.batch_context(), batch, backfill).await?;
         drop(permit);
         Ok(count)
     }
 
+    fn batch_context(&self) -> BatchContext<P, Po, S, E> {
+        BatchContext {
+            hydrator: self.hydrator.clone(),
+            record_store: Arc::clone(&self.record_store),
+            event_publisher: Arc::clone(&self.event_publisher),
+            broadcaster: self.broadcaster.clone(),
+            output_streams: self.output_streams.clone(),
+            watchlist: Arc::clone(&self.watchlist),
+            projection: Arc::clone(&self.projection),
+            record_reader: self.record_reader.clone(),
+            delete_events: Arc::clone(&self.delete_events),
+            account_removals: Arc::clone(&self.account_removals),
+            privacy: Arc::clone(&self.privacy),
+            collection_counters: Arc::clone(&self.collection_counters),
+            throughput: Arc::clone(&self.throughput),
+            ingest_lag: Arc::clone(&self.ingest_lag),
+            sink_latency: Arc::clone(&self.sink_latency),
+            activity: Arc::clone(&self.activity),
+        }
+    }
+
+    /// Prefetches profiles and posts for `batch`, waiting out rate limits for up to
+    /// `RATE_LIMIT_RETRIES` attempts. Whatever could not be fetched is left for
+    /// per-record hydration so the batch still makes progress.
+    async fn prefetch_with_rate_limit_retries(
+        hydrator: &Hydrator<P, Po>,
+        batch: &[JetstreamMessage],
+    ) {
+        if let Err(e) = retry_rate_limited(|| hydrator.prefetch_batch(batch)).await {
+            warn!("{}; hydrating batch from cache only", e);
+        }
+    }
+
     async fn process_batch_internal(
-        hydrator: Hydrator<P, Po>,
-        record_store: Arc<S>,
-        event_publisher: Arc<E>,
-        broadcast_sender: broadcast::Sender<EnrichedRecord>,
+        context: BatchContext<P, Po, S, E>,
         batch: Vec<JetstreamMessage>,
+        backfill: bool,
     ) -> TurboResult<usize> {
-        let enriched_records = hydrator.hydrate_batch(batch).await?;
+        let BatchContext {
+            hydrator,
+            record_store,
+            event_publisher,
+            broadcaster,
+            output_streams,
+            watchlist,
+            projection,
+            record_reader,
+            delete_events,
+            account_removals,
+            privacy,
+            collection_counters,
+            throughput,
+            ingest_lag,
+            sink_latency,
+            activity,
+        } = context;
+        let hydration_started = std::time::Instant::now();
+        let received_len = batch.len();
+        Self::prefetch_with_rate_limit_retries(&hydrator, &batch).await;
+        let received = BatchTally::of_messages(&batch);
+        let mut hydrated_records = hydrator.hydrate_prefetched(batch).await;
+        let suppressed = privacy.apply_internal(&mut hydrated_records);
+        hydrator
+            .drop_counters()
+            .record(DropReason::Privacy, suppressed);
+        // Records are shared by every sink, so wrap them once instead of cloning per sink
+        let enriched_records: Vec<Arc<EnrichedRecord>> = hydrated_records
+            .into_iter()
+            .map(|mut record| {
+                record.hydrated_metadata.backfill = backfill;
+                Arc::new(record)
+            })
+            .collect();
         let count = enriched_records.len();
+        let hydrated = BatchTally::of_records(&enriched_records);
+        collection_counters.record_hydrated(&received, &hydrated);
+        let hydration_elapsed = hydration_started.elapsed();
+        ingest_lag.record(&enriched_records);
 
         if count == 0 {
+            throughput.record_batch(received_len, 0, hydration_elapsed, Duration::ZERO);
             return Ok(0);
         }
 
-        // Parallelize record store and event publisher operations
-        let store_records = enriched_records.clone();
-        let publish_records = enriched_records.clone();
-
-        let store_future = async { record_store.store_batch(&store_records).await };
+        let delete_uris: Vec<String> = enriched_records
+            .iter()
+            .filter(|r| r.is_delete())
+            .filter_map(|r| r.get_at_uri())
+            .collect();
+        if !delete_uris.is_empty() {
+            delete_events.fetch_add(delete_uris.len() as u64, Ordering::Relaxed);
+        }
 
-        let publish_future = async { event_publisher.publish_batch(&publish_records).await };
+        // Parallelize record store and event publisher operations. Only these
+        // persist the projected records; live consumers get the full ones.
+        let persisted = projection.apply(&enriched_records);
+        let store_future = timed(record_store.store_batch(&persisted));
+        let publish_future = timed(event_publisher.publish_batch(&persisted));
+
+        // Output streams and the watchlist feed `/ws/:stream`, so they only get
+        // what may be served publicly
+        let public_records = privacy.public_records(&enriched_records);
+        let streams_future = output_streams.publish(&public_records);
+        let watchlist_future = watchlist.publish(&public_records);
+        // Records stored before the last rotation live in rotated databases
+        let rotated_deletes_future = async {
+            match &record_reader {
+                Some(record_reader) => record_reader.apply_deletes(&delete_uris).await,
+                None => Ok(0),
+            }
+        };
 
         // Run store and publish operations concurrently
-        let (store_result, publish_result) = tokio::join!(store_future, publish_future);
+        let sink_started = std::time::Instant::now();
+        let (
+            (store_result, store_elapsed),
+            (publish_result, publish_elapsed),
+            streams_result,
+            watchlist_result,
+            rotated_deletes,
+        ) = tokio::join!(
+            store_future,
+            publish_future,
+            streams_future,
+            watchlist_future,
+            rotated_deletes_future
+        );
+        if let Err(e) = rotated_deletes {
+            warn!("Failed to apply deletes to rotated databases: {}", e);
+        }
+        let sink_elapsed = sink_started.elapsed();
+        sink_latency.record(Sink::Sqlite, store_elapsed);
+        sink_latency.record(sink_latency.publisher(), publish_elapsed);
+
+        activity.record_sink_result(Sink::Sqlite.as_str(), store_result.is_ok());
+        activity.record_sink_result(sink_latency.publisher().as_str(), publish_result.is_ok());
+        let written = store_result.is_ok()
+            && publish_result.is_ok()
+            && streams_result.is_ok()
+            && watchlist_result.is_ok();
+        collection_counters.record_written(&hydrated, written);
+        throughput.record_batch(
+            received_len,
+            if written { count } else { 0 },
+            hydration_elapsed,
+            sink_elapsed,
+        );
 
         // Check results
         let _store_ids = store_result?;
         let _publish_ids = publish_result?;
+        streams_result?;
+        watchlist_result?;
+
+        // After the batch is stored, so the account's last records are caught too
+        let removed_accounts = AccountRemovals::removed_accounts(&enriched_records);
+        account_removals
+            .apply(
+                &removed_accounts,
+                hydrator.get_cache(),
+                record_reader.as_deref(),
+            )
+            .await?;
 
         // Broadcast records (fire and forget)
-        for enriched in enriched_records {
-            let _ = broadcast_sender.send(enriched);
+        for enriched in public_records {
+            broadcaster.send(enriched);
         }
 
         Ok(count)
     }
 
-    fn should_process_message(&self, _message: &JetstreamMessage) -> bool {
-        // Apply modulo-based sharding if specified
-        // For now, just return true
+    /// Loads existing `collections` records from each repo in `dids` through the
+    /// regular hydration and sink path, marked as backfill. A repo that fails is
+    /// logged and skipped so one bad repo doesn't stop the rest.
+    pub async fn backfill(
+        &self,
+        client: &BackfillClient,
+        dids: &[String],
+        collections: &[String],
+    ) -> BackfillReport {
+        let mut report = BackfillReport::default();
+
+        for did in dids {
+            match self.backfill_repo(client, did, collections).await {
+                Ok(records) => {
+                    info!("Backfilled {} records from {}", records, did);
+                    report.repos += 1;
+                    report.records += records;
+                }
+                Err(e) => {
+                    warn!("Backfill of {} failed: {}", did, e);
+                    report.failed_repos.push(did.clone());
+                }
+            }
+        }
+
+        report
+    }
+
+    async fn backfill_repo(
+        &self,
+        client: &BackfillClient,
+        did: &str,
+        collections: &[String],
+    ) -> TurboResult<usize> {
+        let mut records = 0;
+        for collection in collections {
+            let mut cursor: Option<String> = None;
+            loop {
+                let page = client
+                    .list_records(did, collection, cursor.as_deref())
+                    .await?;
+                let page_len = page.records.len();
+                let messages: Vec<JetstreamMessage> = page
+                    .records
+                    .into_iter()
+                    .filter_map(|record| record.into_message())
+                    .collect();
+                for batch in messages.chunks(BATCH_SIZE) {
+                    records += self.process_batch(batch.to_vec(), true).await?;
+                }
+
+                match page.cursor {
+                    Some(next) if page_len > 0 => cursor = Some(next),
+                    _ => break,
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    fn should_process_message(&self, message: &JetstreamMessage) -> bool {
+        if !self.sharding.owns(message.extract_did()) {
+            self.drops.record(DropReason::OtherShard, 1);
+            return false;
+        }
+        if !self.did_filter.allows_message(message) {
+            self.drops.record(DropReason::DidFilter, 1);
+            return false;
+        }
         true
     }
 
-    pub fn subscribe(&self) -> broadcast::Receiver<EnrichedRecord> {
-        self.broadcast_sender.subscribe()
+    /// Switches this instance to another shard of the stream without a
+    /// restart. Takes effect before the next message is handled.
+    pub fn reassign_shard(&self, assignment: ShardAssignment) -> bool {
+        self.sharding.reassign(assignment)
+    }
+
+    pub fn shard_assignment(&self) -> ShardAssignment {
+        self.sharding.assignment()
+    }
+
+    /// Drops by reason, from the message source and the pipeline.
+    pub fn drop_stats(&self) -> DropStats {
+        match self.message_source.drop_counters() {
+            Some(source) => self.drops.merged_with(source),
+            None => self.drops.snapshot(),
+        }
+    }
+
+    /// Subscribes to every record, first replaying retained records after
+    /// `last_id` when a reconnecting client supplies one.
+    pub fn subscribe(&self, last_id: Option<u64>) -> RecordSubscription {
+        self.broadcaster.subscribe_after(last_id)
+    }
+
+    /// Subscribes to a named output stream, if one is configured, or to the
+    /// watchlist.
+    pub fn subscribe_stream(&self, name: &str, last_id: Option<u64>) -> Option<RecordSubscription> {
+        if name == WATCHLIST_STREAM {
+            return Some(self.watchlist.subscribe_after(last_id));
+        }
+        self.output_streams
+            .get(name)
+            .map(|stream| stream.subscribe_after(last_id))
+    }
+
+    /// Stops or restarts consuming upstream. Buffered messages keep flowing to
+    /// the sinks while paused, and resuming reconnects from the saved cursor.
+    /// Returns false if the message source can't pause.
+    pub fn set_ingestion_paused(&self, paused: bool) -> bool {
+        let supported = self.message_source.set_paused(paused);
+        if supported {
+            info!(
+                "Ingestion {} by operator",
+                if paused { "paused" } else { "resumed" }
+            );
+        }
+        supported
+    }
+
+    /// Stops the run loop once the messages it has buffered are flushed.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    pub fn is_ingestion_paused(&self) -> bool {
+        self.message_source.connection_state() == Some(ConnectionState::Paused)
+    }
+
+    pub fn admin_token(&self) -> Option<&str> {
+        self.settings.admin_token.as_deref()
+    }
+
+    /// Counts stored records with a full table scan, to check the maintained
+    /// counts `get_stats` reports.
+    pub async fn count_records_exact(&self) -> TurboResult<i64> {
+        self.sqlite()?.count_records_exact().await
+    }
+
+    /// The SQLite store, for what only it can answer.
+    fn sqlite(&self) -> TurboResult<&ShardedSQLiteStore> {
+        self.sqlite_store
+            .as_deref()
+            .ok_or_else(|| TurboError::NotFound(INJECTED_SINKS.to_string()))
+    }
+
+    /// Reads across the live and rotated SQLite databases.
+    fn record_reader(&self) -> TurboResult<&PartitionedReader> {
+        self.record_reader
+            .as_deref()
+            .ok_or_else(|| TurboError::NotFound(INJECTED_SINKS.to_string()))
+    }
+
+    pub fn watchlist(&self) -> &Watchlist {
+        &self.watchlist
+    }
+
+    /// Per-minute throughput and latency, the last `minutes` of them or all retained.
+    pub fn throughput_series(&self, minutes: Option<usize>) -> ThroughputSeriesSnapshot {
+        self.throughput.snapshot(minutes)
+    }
+
+    /// Record shape for WebSocket subscribers that don't ask for one.
+    pub fn output_format(&self) -> OutputFormat {
+        self.settings.output_format
+    }
+
+    /// Samples memory against the soft limit, toggles post-fetch shedding on the
+    /// hydrator and returns the batch size to use until the next check.
+    fn check_memory_budget(&self, buffered_messages: usize, in_flight_batches: usize) -> usize {
+        let usage = MemoryUsage::new(
+            collect_process_memory_diagnostics().rss_bytes,
+            self.hydrator.get_cache().get_entry_counts(),
+            buffered_messages,
+            in_flight_batches,
+        );
+        let shedding = self.memory_guard.observe(usage);
+        self.hydrator.set_skip_post_fetches(shedding);
+        if shedding {
+            SHED_BATCH_SIZE
+        } else {
+            BATCH_SIZE
+        }
     }
 
     fn observe_memory_sample(
@@ -404,19 +1043,20 @@ where
 
         peak_window.snapshot(now_unix_seconds)
     }
-}
 
-// Production-specific methods that require concrete SQLiteStore and RedisStore
-impl<M, P, Po> TurboCharger<M, P, Po, SQLiteStore, RedisStore>
-where
-    M: MessageSource + Send + Sync + 'static,
-    P: ProfileFetcher + Send + Sync + 'static,
-    Po: PostFetcher + Send + Sync + 'static,
-{
     pub async fn refresh_sessions(&self) -> TurboResult<()> {
+        let Some(bluesky_client) = &self.bluesky_client else {
+            return Ok(());
+        };
         info!("Refreshing Bluesky session");
 
-        self.bluesky_client.refresh_session_with_fallback().await?;
+        // The client swaps in the new credentials only once they have been issued,
+        // so in-flight requests keep using the old session until then
+        if let Err(e) = bluesky_client.refresh_session_with_fallback().await {
+            self.session_refresh.record_failure();
+            return Err(e);
+        }
+        self.session_refresh.record_success();
 
         info!(
             "Refreshed session credentials for {}",
@@ -426,18 +1066,27 @@ where
     }
 
     pub fn start_session_refresh_task(self: &Arc<Self>) {
+        let Some(bluesky_client) = self.bluesky_client.clone() else {
+            return;
+        };
         let this = self.clone();
         tokio::spawn(async move {
-            let mut refresh_interval = interval(Duration::from_secs(60 * 60));
-            refresh_interval.tick().await;
-
             loop {
-                refresh_interval.tick().await;
-
-                if this.bluesky_client.should_refresh().await {
+                // A session already past its expiry is checked after the shortest delay
+                let expires_in = bluesky_client
+                    .session_expires_in()
+                    .await
+                    .map(|left| left.to_std().unwrap_or_default());
+                sleep(this.session_refresh.next_delay(expires_in)).await;
+
+                if bluesky_client.should_refresh().await {
                     info!("Session expiring soon, refreshing proactively");
                     if let Err(e) = this.refresh_sessions().await {
-                        error!("Proactive session refresh failed: {}", e);
+                        error!(
+                            "Proactive session refresh failed: {}; retrying in {:?}",
+                            e,
+                            this.session_refresh.next_delay(expires_in)
+                        );
                         let mut ctx = HashMap::new();
                         ctx.insert("component", "turbocharger");
                         ctx.insert("operation", "proactive_session_refresh");
@@ -446,67 +1095,296 @@ where
                 }
             }
         });
-        info!("Started session refresh task (every 1 hour)");
+        info!(
+            "Started session refresh task ({:?} before expiry, every {:?} when unknown, backing off on failure)",
+            SESSION_REFRESH_MARGIN, SESSION_REFRESH_INTERVAL
+        );
     }
 
+    /// Stored records aren't counted with injected sinks.
     pub async fn get_stats(&self) -> TurboResult<TurboStats> {
-        let record_count = self.sqlite_store.count_records().await?;
+        let (record_count, records_last_24h) = match &self.sqlite_store {
+            Some(sqlite_store) => (
+                sqlite_store.count_records().await?,
+                sqlite_store
+                    .count_records_since(Some(chrono::Utc::now().timestamp() - 24 * 3600))
+                    .await?,
+            ),
+            None => (0, None),
+        };
         let cache_metrics = self.hydrator.get_cache().get_metrics();
         let (user_hit_rate, post_hit_rate) = self.hydrator.get_cache().get_hit_rates();
-        let redis_info = self.redis_store.get_stream_info().await?;
+        #[cfg(feature = "redis")]
+        let (redis_stream_length, redis_version) = match &self.redis_store {
+            Some(redis) => {
+                let info = redis.get_stream_info().await?;
+                (info.stream_length, info.redis_version)
+            }
+            None => (0, STANDALONE_ENGINE.to_string()),
+        };
+        #[cfg(not(feature = "redis"))]
+        let (redis_stream_length, redis_version) = (0, STANDALONE_ENGINE.to_string());
+        let duplicates_skipped = DuplicateStats {
+            sqlite: self
+                .sqlite_store
+                .as_ref()
+                .map_or(0, |sqlite| sqlite.duplicates_skipped()),
+            #[cfg(feature = "redis")]
+            redis: self
+                .redis_store
+                .as_ref()
+                .map_or(0, |redis| redis.duplicates_skipped()),
+            #[cfg(not(feature = "redis"))]
+            redis: 0,
+        };
 
         Ok(TurboStats {
             total_records_processed: record_count,
+            records_last_24h,
+            delete_events_processed: self.delete_events.load(Ordering::Relaxed),
+            account_removals: self.account_removals.stats(),
+            ingest_lag: self.ingest_lag.stats(),
+            sink_latency: self.sink_latency.stats(),
+            collections: self.collection_counters.stats(),
+            schema_drift_records: self.hydrator.schema_drift_count(),
+            degradation: self.hydrator.degradation_stats(),
+            privacy: self.privacy.stats(),
+            did_filter: self.did_filter.stats(),
+            sharding: self.sharding.stats(),
+            drops: self.drop_stats(),
+            duplicates_skipped,
             cache_user_hits: cache_metrics.user_hits,
             cache_user_misses: cache_metrics.user_misses,
             cache_post_hits: cache_metrics.post_hits,
             cache_post_misses: cache_metrics.post_misses,
             cache_user_hit_rate: user_hit_rate,
             cache_post_hit_rate: post_hit_rate,
-            redis_stream_length: redis_info.stream_length,
-            redis_version: redis_info.redis_version,
+            redis_stream_length,
+            redis_version,
+            broadcast: self.broadcaster.stats(),
+            output_streams: self.output_streams.stats(),
+            watchlist: self.watchlist.stats(),
+            memory: self.memory_guard.stats(),
+            session_refresh: self.session_refresh.stats(),
+            collector_batches: self
+                .bluesky_client
+                .as_ref()
+                .map(|client| client.batch_fill_stats()),
         })
     }
 
-    pub async fn health_check(&self) -> TurboResult<HealthStatus> {
-        let redis_healthy = self.redis_store.health_check().await?;
-        let sqlite_available = match self.sqlite_store.count_records().await {
-            Ok(_) => true,
+    /// Looks up a stored record in the live database or any retained rotated
+    /// one, then in an injected record store.
+    pub async fn get_record_by_uri(&self, at_uri: &str) -> TurboResult<Option<EnrichedRecord>> {
+        match &self.record_reader {
+            Some(record_reader) => record_reader.get_record_by_uri(at_uri).await,
+            None => self.record_store.get_record_by_uri(at_uri).await,
+        }
+    }
+
+    /// Stored posts of the thread rooted at `root_uri`, nested by reply.
+    pub async fn get_thread(&self, root_uri: &str) -> TurboResult<Option<Thread>> {
+        let thread = self.record_reader()?.thread(root_uri).await?;
+        Ok(thread.and_then(|thread| thread.retain(|record| self.privacy.allows_record(record))))
+    }
+
+    /// Aggregates for the last `hours` hours, including the current one, oldest
+    /// first, as of the last roll-up.
+    pub async fn hourly_aggregates(
+        &self,
+        hours: i64,
+        top_hashtags: usize,
+    ) -> TurboResult<Vec<HourlyAggregate>> {
+        let until = (unix_timestamp_seconds() as i64 / HOUR_SECONDS + 1) * HOUR_SECONDS;
+        let counts = self
+            .record_reader()?
+            .hourly_counts(until - hours * HOUR_SECONDS, until)
+            .await?;
+        Ok(merge_hourly_counts(counts, top_hashtags))
+    }
+
+    /// Profile for `did` from the hydration cache, fetching it on a miss.
+    /// `None` in serve-only mode, which has no session to fetch with.
+    pub async fn get_profile(&self, did: &str) -> TurboResult<Option<Arc<BlueskyProfile>>> {
+        if self.settings.serve_only {
+            return Ok(None);
+        }
+        let profile = self.hydrator.get_profile(did).await?;
+        Ok(profile.filter(|profile| self.privacy.allows_profile(profile)))
+    }
+
+    /// `get_record_by_uri`, falling back to fetching the record from its repo
+    /// and hydrating it when it isn't stored. Fetched records are returned
+    /// without being stored or published. Nothing is fetched in serve-only mode,
+    /// and fetches past `hydrate_on_miss_per_minute` fail with `RateLimited`.
+    pub async fn lookup_record(
+        &self,
+        at_uri: &str,
+        hydrate_on_miss: bool,
+    ) -> TurboResult<Option<EnrichedRecord>> {
+        if let Some(record) = self.get_record_by_uri(at_uri).await? {
+            return Ok(Some(record).filter(|record| self.privacy.allows_record(record)));
+        }
+        let Some(uri) =
+            AtUri::parse(at_uri).filter(|_| hydrate_on_miss && !self.settings.serve_only)
+        else {
+            return Ok(None);
+        };
+        let Some(limiter) = &self.hydrate_on_miss_limiter else {
+            return Ok(None);
+        };
+        if let Err(not_until) = limiter.check() {
+            return Err(TurboError::RateLimited {
+                endpoint: "hydrate-on-miss".to_string(),
+                retry_after: Some(not_until.wait_time_from(DefaultClock::default().now())),
+            });
+        }
+        let Some(message) = self
+            .record_fetcher
+            .get_record(&uri)
+            .await?
+            .and_then(|record| record.into_message())
+        else {
+            return Ok(None);
+        };
+        let record = self.hydrator.hydrate_message(message).await?;
+        Ok(Some(record).filter(|record| self.privacy.allows_record(record)))
+    }
+
+    /// Stored records in a `time_us` range, newest first, across the live and
+    /// retained rotated databases.
+    pub async fn records_in_time_range(
+        &self,
+        since_us: Option<i64>,
+        until_us: Option<i64>,
+        limit: u32,
+    ) -> TurboResult<Vec<EnrichedRecord>> {
+        let records = self
+            .record_reader()?
+            .records_in_time_range(since_us, until_us, limit)
+            .await?;
+        Ok(records
+            .into_iter()
+            .filter(|record| self.privacy.allows_record(record))
+            .collect())
+    }
+
+    /// Sessions of the built-in Bluesky client; `None` with injected fetchers,
+    /// which hold their own credentials.
+    async fn session_count(&self) -> Option<usize> {
+        match &self.bluesky_client {
+            Some(client) => Some(client.get_session_count().await),
+            None => None,
+        }
+    }
+
+    /// The ingest stream's state; `None` in serve-only mode, which never connects.
+    fn stream_state(&self) -> Option<ConnectionState> {
+        if self.settings.serve_only {
+            return None;
+        }
+        self.message_source.connection_state()
+    }
+
+    /// Whether Redis answers a ping; `None` in standalone mode.
+    #[cfg(feature = "redis")]
+    async fn redis_connected(&self, probe: &str) -> Option<bool> {
+        let redis = self.redis_store.as_ref()?;
+        Some(match redis.health_check().await {
+            Ok(connected) => connected,
             Err(e) => {
-                error!("SQLite health check failed: {}", e);
+                error!("not_redis {} probe failed: {}", probe, e);
                 false
             }
+        })
+    }
+
+    /// Always `None`: there's no Redis without the `redis` feature.
+    #[cfg(not(feature = "redis"))]
+    async fn redis_connected(&self, _probe: &str) -> Option<bool> {
+        None
+    }
+
+    /// Whether SQLite answers a count; `true` with injected sinks, which have
+    /// no SQLite to lose. `probe` names the check when logging a failure.
+    async fn sqlite_available(&self, probe: &str) -> bool {
+        let Some(sqlite_store) = &self.sqlite_store else {
+            return true;
         };
-        let session_count = self.bluesky_client.get_session_count().await;
+        match sqlite_store.count_records().await {
+            Ok(_) => true,
+            Err(e) => {
+                error!("SQLite {} failed: {}", probe, e);
+                false
+            }
+        }
+    }
+
+    pub async fn health_check(&self) -> TurboResult<HealthStatus> {
+        let redis_connected = self.redis_connected("health").await;
+        let sqlite_available = self.sqlite_available("health check").await;
+        let session_count = self.session_count().await;
         let diagnostics = self
-            .collect_health_diagnostics(redis_healthy, sqlite_available)
+            .collect_health_diagnostics(redis_connected, sqlite_available)
             .await;
+        let liveness = self.liveness_thresholds.evaluate(
+            &self.activity,
+            self.stream_state(),
+            match &self.bluesky_client {
+                Some(client) => client.session_expires_in().await,
+                None => None,
+            },
+        );
+        if !liveness.is_healthy() {
+            warn!(
+                "Liveness checks failing: {}",
+                liveness.failing_checks.join("; ")
+            );
+        }
 
         Ok(HealthStatus {
-            healthy: derive_health(redis_healthy, sqlite_available, session_count),
-            redis_connected: redis_healthy,
+            // Without a built-in client there are no sessions to lose
+            healthy: derive_health(
+                redis_connected != Some(false),
+                sqlite_available,
+                session_count.unwrap_or(1),
+            ) && liveness.is_healthy(),
+            redis_connected,
             sqlite_available,
-            session_count,
+            session_count: session_count.unwrap_or(0),
+            liveness,
             diagnostics,
         })
     }
 
+    /// Cheaper than `health_check`: only what decides whether to route traffic here.
+    pub async fn readiness_check(&self) -> ReadinessStatus {
+        let authenticated = self.session_count().await.is_none_or(|count| count > 0);
+        let redis_connected = self.redis_connected("readiness").await;
+        let sqlite_available = self.sqlite_available("readiness probe").await;
+        // A paused stream is down on purpose and still serves stored data
+        let stream_connected = self
+            .stream_state()
+            .filter(|state| *state != ConnectionState::Paused)
+            .map(|state| state == ConnectionState::Connected);
+
+        ReadinessStatus::new(
+            authenticated,
+            redis_connected,
+            sqlite_available,
+            stream_connected,
+            self.activity.is_draining(),
+            self.semaphore.available_permits(),
+            self.memory_guard.is_shedding(),
+        )
+    }
+
     pub async fn get_runtime_diagnostics(&self) -> HealthDiagnostics {
-        let redis_connected = match self.redis_store.health_check().await {
-            Ok(connected) => connected,
-            Err(e) => {
-                error!("not_redis diagnostics health probe failed: {}", e);
-                false
-            }
-        };
+        let redis_connected = self.redis_connected("diagnostics").await;
 
-        let sqlite_available = match self.sqlite_store.count_records().await {
-            Ok(_) => true,
-            Err(e) => {
-                error!("SQLite diagnostics availability probe failed: {}", e);
-                false
-            }
-        };
+        let sqlite_available = self
+            .sqlite_available("diagnostics availability probe")
+            .await;
 
         self.collect_health_diagnostics(redis_connected, sqlite_available)
             .await
@@ -514,7 +1392,7 @@ where
 
     async fn collect_health_diagnostics(
         &self,
-        redis_connected: bool,
+        redis_connected: Option<bool>,
         sqlite_available: bool,
     ) -> HealthDiagnostics {
         let cache = self.hydrator.get_cache();
@@ -522,7 +1400,11 @@ where
         let (user_entries, post_entries) = cache.get_entry_counts();
         let (user_capacity, post_capacity) = cache.get_capacity_limits();
 
-        let sqlite_state = match self.sqlite_store.get_state_snapshot().await {
+        let snapshot = match self.sqlite() {
+            Ok(sqlite_store) => sqlite_store.get_state_snapshot().await,
+            Err(e) => Err(e),
+        };
+        let sqlite_state = match snapshot {
             Ok(snapshot) => SQLiteStateDiagnostics {
                 available: sqlite_available,
                 db_size_bytes: Some(snapshot.db_size_bytes),
@@ -551,24 +1433,7 @@ where
             },
         };
 
-        let not_redis_state = match self.redis_store.get_stream_info().await {
-            Ok(info) => NotRedisStateDiagnostics {
-                connected: redis_connected,
-                engine: info.redis_version,
-                stream_name: info.stream_name,
-                stream_length: Some(info.stream_length),
-                configured_max_length: info.max_length,
-                collection_error: None,
-            },
-            Err(e) => NotRedisStateDiagnostics {
-                connected: redis_connected,
-                engine: "not_redis".to_string(),
-                stream_name: self.redis_store.get_stream_name().to_string(),
-                stream_length: None,
-                configured_max_length: self.redis_store.get_max_length(),
-                collection_error: Some(e.to_string()),
-            },
-        };
+        let not_redis_state = self.not_redis_state(redis_connected).await;
 
         let mut process_memory = collect_process_memory_diagnostics();
         process_memory.peaks_24h = self.observe_memory_sample(&process_memory);
@@ -592,11 +1457,65 @@ where
         }
     }
 
+    /// The Redis stream's state; `None` in standalone mode.
+    #[cfg(feature = "redis")]
+    async fn not_redis_state(
+        &self,
+        redis_connected: Option<bool>,
+    ) -> Option<NotRedisStateDiagnostics> {
+        match (&self.redis_store, redis_connected) {
+            (Some(redis), Some(connected)) => Some(match redis.get_stream_info().await {
+                Ok(info) => NotRedisStateDiagnostics {
+                    connected,
+                    engine: info.redis_version,
+                    stream_name: info.stream_name,
+                    stream_length: Some(info.stream_length),
+                    configured_max_length: info.max_length,
+                    collection_error: None,
+                },
+                Err(e) => NotRedisStateDiagnostics {
+                    connected,
+                    engine: "not_redis".to_string(),
+                    stream_name: redis.get_stream_name().to_string(),
+                    stream_length: None,
+                    configured_max_length: redis.get_max_length(),
+                    collection_error: Some(e.to_string()),
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn not_redis_state(
+        &self,
+        _redis_connected: Option<bool>,
+    ) -> Option<NotRedisStateDiagnostics> {
+        None
+    }
+
+    /// Rolls records into the hourly aggregates first, so cleanup never
+    /// deletes a record before it's counted.
     pub async fn check_and_cleanup_db(
         &self,
     ) -> TurboResult<Option<crate::storage::sqlite::CleanupResult>> {
+        // An injected store has no size to check, so it's held to retention alone
+        let Some(sqlite_store) = &self.sqlite_store else {
+            let cutoff =
+                chrono::Utc::now() - chrono::Duration::days(self.settings.db_retention_days as i64);
+            let removed = self.record_store.remove_older_than(cutoff).await?;
+            if removed > 0 {
+                info!(
+                    "Removed {} records past retention from the record store",
+                    removed
+                );
+            }
+            return Ok(None);
+        };
+        sqlite_store.roll_up_hourly().await?;
+
         let max_size_bytes = (self.settings.max_db_size_mb as i64) * 1024 * 1024;
-        let current_size = self.sqlite_store.get_db_size().await?;
+        let current_size = sqlite_store.get_db_size().await?;
 
         if current_size > max_size_bytes {
             info!(
@@ -604,8 +1523,7 @@ where
                 current_size / (1024 * 1024),
                 self.settings.max_db_size_mb
             );
-            let result = self
-                .sqlite_store
+            let result = sqlite_store
                 .cleanup_with_vacuum(
                     self.settings.db_retention_days,
                     max_size_bytes,
@@ -627,6 +1545,98 @@ where
         Ok(None)
     }
 
+    /// Starts moving rolled-up records out of the live database into rotated
+    /// `jetstream_<unix>.db` files, if configured and SQLite is open.
+    pub async fn start_rotation_task(&self) -> TurboResult<()> {
+        let settings = &self.settings;
+        let Some(sqlite_store) = self
+            .sqlite_store
+            .clone()
+            .filter(|_| settings.rotation_minutes > 0)
+        else {
+            return Ok(());
+        };
+
+        // Retention is by age, so the count limit is left open
+        let rotator = DatabaseRotator::new(
+            &settings.db_dir,
+            Duration::from_secs(settings.rotation_minutes * 60),
+            usize::MAX,
+            Duration::from_secs(u64::from(settings.db_retention_days) * 24 * 60 * 60),
+        );
+        rotator
+            .start_rotation_task(
+                sqlite_store,
+                SQLitePragmaConfig {
+                    cache_size_kib: settings.sqlite_cache_size_kib,
+                    mmap_size_mb: settings.sqlite_mmap_size_mb,
+                    journal_size_limit_mb: settings.sqlite_journal_size_limit_mb,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Starts merging rotated databases into daily archives, if configured and
+    /// SQLite is open.
+    pub fn start_compaction_task(&self) -> TurboResult<()> {
+        let settings = &self.settings;
+        let Some(record_reader) = self
+            .record_reader
+            .clone()
+            .filter(|_| settings.compaction_interval_minutes > 0)
+        else {
+            return Ok(());
+        };
+
+        let compactor = ArchiveCompactor::new(
+            &settings.db_dir,
+            SQLitePragmaConfig {
+                cache_size_kib: settings.sqlite_cache_size_kib,
+                mmap_size_mb: settings.sqlite_mmap_size_mb,
+                journal_size_limit_mb: settings.sqlite_journal_size_limit_mb,
+            },
+        )
+        .with_reader(record_reader);
+        #[cfg(feature = "s3")]
+        let compactor = match &settings.archive_bucket {
+            Some(bucket) => compactor
+                .with_upload(
+                    crate::storage::compaction::s3_archive_store(bucket)?,
+                    settings.archive_prefix.clone(),
+                )
+                .with_compression(settings.compaction_compress),
+            None => compactor,
+        };
+        #[cfg(not(feature = "s3"))]
+        if settings.archive_bucket.is_some() {
+            warn!("ARCHIVE_BUCKET is set but archive upload requires the s3 feature");
+        }
+
+        Arc::new(compactor).start_compaction_task(Duration::from_secs(
+            settings.compaction_interval_minutes * 60,
+        ));
+        Ok(())
+    }
+
+    /// Rolls stored records into the hourly aggregates every
+    /// `HOURLY_ROLL_UP_INTERVAL`, so reading them never writes. Nothing to
+    /// roll up with injected sinks.
+    pub fn start_roll_up_task(self: &Arc<Self>) {
+        let Some(sqlite_store) = self.sqlite_store.clone() else {
+            return;
+        };
+        tokio::spawn(async move {
+            let mut ticker = interval(HOURLY_ROLL_UP_INTERVAL);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = sqlite_store.roll_up_hourly().await {
+                    warn!("Hourly roll-up failed: {}", e);
+                }
+            }
+        });
+    }
+
     pub fn start_db_cleanup_task(self: &Arc<Self>) {
         let this = self.clone();
         let base_interval_minutes = this.settings.cleanup_check_interval_minutes;
@@ -682,6 +1692,30 @@ where
 #[derive(Debug, Clone, Serialize)]
 pub struct TurboStats {
     pub total_records_processed: i64,
+    /// Records with an event time in the last 24 hours, to the hour
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub records_last_24h: Option<i64>,
+    pub delete_events_processed: u64,
+    /// Taken-down and deleted accounts
+    pub account_removals: AccountRemovalStats,
+    /// How far behind real time recently hydrated records were
+    pub ingest_lag: IngestLagStats,
+    /// Per-batch SQLite and Redis write latency, and slow-write alerts
+    pub sink_latency: SinkLatencyStats,
+    /// Commit outcomes by collection, then operation
+    pub collections: CollectionStats,
+    pub schema_drift_records: u64,
+    /// Absent when adaptive degradation is off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub degradation: Option<DegradationStats>,
+    /// Records held back for authors asking not to be shown to logged-out viewers
+    pub privacy: PrivacyStats,
+    pub did_filter: DidFilterStats,
+    pub sharding: ShardStats,
+    /// Messages discarded before the sinks, by reason
+    pub drops: DropStats,
+    /// Replayed events the sinks recognized by idempotency key and skipped
+    pub duplicates_skipped: DuplicateStats,
     pub cache_user_hits: u64,
     pub cache_user_misses: u64,
     pub cache_post_hits: u64,
@@ -690,14 +1724,39 @@ pub struct TurboStats {
     pub cache_post_hit_rate: f64,
     pub redis_stream_length: usize,
     pub redis_version: String,
+    pub broadcast: BroadcastStats,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub output_streams: BTreeMap<String, OutputStreamStats>,
+    pub watchlist: WatchlistStats,
+    pub memory: MemoryBudgetStats,
+    pub session_refresh: SessionRefreshStats,
+    /// Absent when the Bluesky fetchers were injected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collector_batches: Option<BatchFillStats>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DuplicateStats {
+    pub sqlite: u64,
+    /// Counted across the main stream and the output streams
+    pub redis: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BackfillReport {
+    pub repos: usize,
+    pub records: usize,
+    pub failed_repos: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct HealthStatus {
     pub healthy: bool,
-    pub redis_connected: bool,
+    /// `None` in standalone mode
+    pub redis_connected: Option<bool>,
     pub sqlite_available: bool,
     pub session_count: usize,
+    pub liveness: StreamLiveness,
     pub diagnostics: HealthDiagnostics,
 }
 
@@ -706,7 +1765,9 @@ pub struct HealthDiagnostics {
     pub process_memory: ProcessMemoryDiagnostics,
     pub cache_state: CacheStateDiagnostics,
     pub sqlite_state: SQLiteStateDiagnostics,
-    pub not_redis_state: NotRedisStateDiagnostics,
+    /// Absent in standalone mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_redis_state: Option<NotRedisStateDiagnostics>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -787,12 +1848,19 @@ pub struct NotRedisStateDiagnostics {
 
 /// Concrete type alias for the production TurboCharger
 pub type ProductionTurboCharger =
-    TurboCharger<JetstreamClient, BlueskyClient, BlueskyClient, SQLiteStore, RedisStore>;
+    TurboCharger<IngestSource, BlueskyClient, BlueskyClient, ShardedSQLiteStore, LiveSink>;
 
 fn derive_health(redis_connected: bool, sqlite_available: bool, session_count: usize) -> bool {
     redis_connected && sqlite_available && session_count > 0
 }
 
+/// Runs `future`, returning its output with how long it took.
+async fn timed<F: std::future::Future>(future: F) -> (F::Output, Duration) {
+    let started = std::time::Instant::now();
+    let output = future.await;
+    (output, started.elapsed())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum BatchFlushReason {
     Full,
@@ -1250,6 +2318,33 @@ VmRSS:\t  1024 kB\n";
         assert!(matches!(result, Err(TurboError::TaskJoin(_))));
     }
 
+    #[tokio::test]
+    async fn retry_rate_limited_retries_only_rate_limits() {
+        let attempts = AtomicU64::new(0);
+        let result = retry_rate_limited(|| async {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(TurboError::RateLimited {
+                endpoint: "app.bsky.actor.getProfiles".to_string(),
+                retry_after: Some(Duration::from_millis(1)),
+            })
+        })
+        .await;
+        assert!(matches!(result, Err(TurboError::RateLimited { .. })));
+        assert_eq!(
+            attempts.load(Ordering::Relaxed),
+            u64::from(RATE_LIMIT_RETRIES) + 1
+        );
+
+        let attempts = AtomicU64::new(0);
+        let result = retry_rate_limited(|| async {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(TurboError::InvalidApiResponse("bad gateway".to_string()))
+        })
+        .await;
+        assert!(matches!(result, Err(TurboError::InvalidApiResponse(_))));
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    }
+
     #[test]
     fn batch_flush_counters_capture_mix_of_full_and_partial_batches() {
         let mut counters = BatchFlushCounters::default();
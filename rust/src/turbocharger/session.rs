@@ -0,0 +1,132 @@
+//! Outcome tracking and retry backoff for the background session refresh task.
+
+use crate::client::SESSION_REFRESH_MARGIN;
+use metrics::{counter, gauge};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often the session is refreshed while its expiry is unknown.
+pub const SESSION_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Shortest wait between checks, so a session issued already inside the
+/// refresh margin isn't refreshed in a tight loop.
+const SESSION_REFRESH_MIN_DELAY: Duration = Duration::from_secs(30);
+const SESSION_REFRESH_RETRY_BASE: Duration = Duration::from_secs(30);
+const SESSION_REFRESH_RETRY_MAX: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Default)]
+pub struct SessionRefreshTracker {
+    successes: AtomicU64,
+    failures: AtomicU64,
+    consecutive_failures: AtomicU32,
+    last_success_unix: AtomicU64,
+    last_failure_unix: AtomicU64,
+}
+
+impl SessionRefreshTracker {
+    pub fn record_success(&self) {
+        self.successes.fetch_add(1, Ordering::Relaxed);
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        self.last_success_unix.store(unix_now(), Ordering::Relaxed);
+        counter!("jetstream_turbo_session_refresh_total", "outcome" => "success").increment(1);
+        gauge!("jetstream_turbo_session_refresh_consecutive_failures").set(0.0);
+    }
+
+    pub fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        let consecutive = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        self.last_failure_unix.store(unix_now(), Ordering::Relaxed);
+        counter!("jetstream_turbo_session_refresh_total", "outcome" => "failure").increment(1);
+        gauge!("jetstream_turbo_session_refresh_consecutive_failures").set(consecutive as f64);
+    }
+
+    /// Delay before the next check. While healthy that's until the refresh
+    /// margin before a session expiring in `expires_in`, or the regular interval
+    /// when the expiry is unknown; otherwise an exponential backoff so a failed
+    /// refresh is retried well before expiry.
+    pub fn next_delay(&self, expires_in: Option<Duration>) -> Duration {
+        match self.consecutive_failures.load(Ordering::Relaxed) {
+            0 => expires_in.map_or(SESSION_REFRESH_INTERVAL, |expires_in| {
+                expires_in
+                    .saturating_sub(SESSION_REFRESH_MARGIN)
+                    .max(SESSION_REFRESH_MIN_DELAY)
+            }),
+            failures => SESSION_REFRESH_RETRY_BASE
+                .saturating_mul(1 << (failures - 1).min(16))
+                .min(SESSION_REFRESH_RETRY_MAX),
+        }
+    }
+
+    pub fn stats(&self) -> SessionRefreshStats {
+        let timestamp = |value: &AtomicU64| Some(value.load(Ordering::Relaxed)).filter(|t| *t > 0);
+        SessionRefreshStats {
+            successes: self.successes.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            last_success_unix: timestamp(&self.last_success_unix),
+            last_failure_unix: timestamp(&self.last_failure_unix),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionRefreshStats {
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub last_success_unix: Option<u64>,
+    pub last_failure_unix: Option<u64>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_back_off_and_success_resets() {
+        let tracker = SessionRefreshTracker::default();
+        assert_eq!(tracker.next_delay(None), SESSION_REFRESH_INTERVAL);
+
+        tracker.record_failure();
+        assert_eq!(tracker.next_delay(None), Duration::from_secs(30));
+        tracker.record_failure();
+        assert_eq!(tracker.next_delay(None), Duration::from_secs(60));
+        for _ in 0..10 {
+            tracker.record_failure();
+        }
+        assert_eq!(tracker.next_delay(None), SESSION_REFRESH_RETRY_MAX);
+
+        tracker.record_success();
+        assert_eq!(tracker.next_delay(None), SESSION_REFRESH_INTERVAL);
+        let stats = tracker.stats();
+        assert_eq!((stats.successes, stats.failures), (1, 12));
+        assert_eq!(stats.consecutive_failures, 0);
+        assert!(stats.last_success_unix.is_some());
+    }
+
+    #[test]
+    fn test_healthy_refresh_is_scheduled_ahead_of_expiry() {
+        let tracker = SessionRefreshTracker::default();
+        let two_hours = Duration::from_secs(2 * 60 * 60);
+        assert_eq!(
+            tracker.next_delay(Some(two_hours)),
+            two_hours - SESSION_REFRESH_MARGIN
+        );
+        assert_eq!(
+            tracker.next_delay(Some(Duration::ZERO)),
+            SESSION_REFRESH_MIN_DELAY
+        );
+
+        tracker.record_failure();
+        assert_eq!(
+            tracker.next_delay(Some(two_hours)),
+            SESSION_REFRESH_RETRY_BASE
+        );
+    }
+}
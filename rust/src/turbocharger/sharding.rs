@@ -0,0 +1,168 @@
+//! Splits the firehose between instances by DID. Every instance reads the full
+//! stream and keeps the DIDs whose hash lands on its shard, so all events for
+//! a repo are handled by one instance. The assignment starts from `--modulo`
+//! and `--shard` and can be replaced at runtime (e.g. by a fleet coordinator
+//! through the admin API) when instances join or leave; the run loop then
+//! re-filters what it has buffered and drains in-flight batches before
+//! continuing under the new assignment.
+
+use crate::models::errors::{TurboError, TurboResult};
+use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardAssignment {
+    /// Number of instances sharing the stream; 0 or 1 keeps every DID
+    pub modulo: u32,
+    /// This instance's shard, below `modulo`
+    pub shard: u32,
+}
+
+impl ShardAssignment {
+    pub fn new(modulo: u32, shard: u32) -> TurboResult<Self> {
+        if modulo > 1 && shard >= modulo {
+            return Err(TurboError::InvalidMessage(format!(
+                "shard {shard} is out of range for modulo {modulo}"
+            )));
+        }
+        Ok(Self { modulo, shard })
+    }
+
+    pub fn is_sharded(&self) -> bool {
+        self.modulo > 1
+    }
+
+    pub fn owns(&self, did: &str) -> bool {
+        !self.is_sharded() || did_hash(did) % u64::from(self.modulo) == u64::from(self.shard)
+    }
+}
+
+/// FNV-1a with a final mix, so instance shards don't line up with the FNV
+/// placement of `SQLITE_SHARDS` when both use the same count.
+fn did_hash(did: &str) -> u64 {
+    let mut hash = did.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    hash = (hash ^ (hash >> 33)).wrapping_mul(0xff51afd7ed558ccd);
+    hash ^ (hash >> 33)
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ShardStats {
+    #[serde(flatten)]
+    pub assignment: ShardAssignment,
+    /// Assignments received at runtime since startup
+    pub reassignments: u64,
+    /// Messages left to other shards
+    pub skipped_messages: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct ShardFilter {
+    assignment: RwLock<ShardAssignment>,
+    /// Bumped on every reassignment, so the run loop can tell it happened
+    generation: AtomicU64,
+    skipped: AtomicU64,
+}
+
+impl ShardFilter {
+    pub fn new(assignment: ShardAssignment) -> Self {
+        let filter = Self::default();
+        filter.set(assignment);
+        filter
+    }
+
+    pub fn assignment(&self) -> ShardAssignment {
+        *self
+            .assignment
+            .read()
+            .expect("shard assignment lock poisoned")
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Replaces the assignment. Returns false if it was already in effect.
+    pub fn reassign(&self, assignment: ShardAssignment) -> bool {
+        let previous = self.assignment();
+        if previous == assignment {
+            return false;
+        }
+        self.set(assignment);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        counter!("jetstream_turbo_shard_reassignments_total").increment(1);
+        info!(
+            "Shard reassigned from {}/{} to {}/{}",
+            previous.shard, previous.modulo, assignment.shard, assignment.modulo
+        );
+        true
+    }
+
+    /// Whether this instance handles `did`, counting it if not.
+    pub fn owns(&self, did: &str) -> bool {
+        let owned = self.assignment().owns(did);
+        if !owned {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+        }
+        owned
+    }
+
+    pub fn stats(&self) -> ShardStats {
+        ShardStats {
+            assignment: self.assignment(),
+            reassignments: self.generation(),
+            skipped_messages: self.skipped.load(Ordering::Relaxed),
+        }
+    }
+
+    fn set(&self, assignment: ShardAssignment) {
+        *self
+            .assignment
+            .write()
+            .expect("shard assignment lock poisoned") = assignment;
+        gauge!("jetstream_turbo_shard_modulo").set(f64::from(assignment.modulo));
+        gauge!("jetstream_turbo_shard_index").set(f64::from(assignment.shard));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_did_has_exactly_one_owner_after_reassignment() {
+        let dids: Vec<String> = (0..1000).map(|i| format!("did:plc:user{i:04}")).collect();
+        let owners = |modulo: u32| {
+            let filters: Vec<ShardFilter> = (0..modulo)
+                .map(|shard| ShardFilter::new(ShardAssignment::new(modulo, shard).unwrap()))
+                .collect();
+            let mut per_shard = vec![0; modulo as usize];
+            for did in &dids {
+                let owning: Vec<usize> = (0..filters.len())
+                    .filter(|&i| filters[i].owns(did))
+                    .collect();
+                assert_eq!(owning.len(), 1, "{did} owned by {owning:?}");
+                per_shard[owning[0]] += 1;
+            }
+            per_shard
+        };
+        assert!(owners(3).iter().all(|&count| count > 250));
+        assert!(owners(4).iter().all(|&count| count > 180));
+
+        let filter = ShardFilter::new(ShardAssignment::default());
+        assert!(dids.iter().all(|did| filter.owns(did)));
+        assert!(filter.reassign(ShardAssignment::new(2, 1).unwrap()));
+        assert!(!filter.reassign(ShardAssignment::new(2, 1).unwrap()));
+        assert_eq!(filter.generation(), 1);
+        let owned = dids.iter().filter(|did| filter.owns(did)).count();
+        assert!((400..600).contains(&owned));
+        assert_eq!(filter.stats().skipped_messages, 1000 - owned as u64);
+
+        assert!(ShardAssignment::new(4, 4).is_err());
+        assert!(ShardAssignment::new(0, 0).is_ok());
+    }
+}
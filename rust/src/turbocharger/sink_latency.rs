@@ -0,0 +1,274 @@
+//! Per-batch write latency of the SQLite store and of the publisher, which is
+//! the Redis stream or, standalone, the NDJSON file. Recent samples are
+//! kept for percentiles in stats and every sample goes to a Prometheus
+//! histogram. A sink that stays slower than the threshold for a run of batches
+//! raises a telemetry alert, since a slow disk otherwise only shows up as lag.
+
+use crate::telemetry::ErrorReporter;
+use metrics::{counter, histogram};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+/// Batches kept per sink for percentiles.
+pub const SINK_LATENCY_SAMPLES: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sink {
+    Sqlite,
+    Redis,
+    /// The standalone NDJSON output file
+    File,
+}
+
+impl Sink {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Sink::Sqlite => "sqlite",
+            Sink::Redis => "redis",
+            Sink::File => "file",
+        }
+    }
+}
+
+/// When a sink counts as slow. A `threshold` of zero disables alerts.
+#[derive(Debug, Clone, Copy)]
+pub struct SlowWriteThresholds {
+    pub threshold: Duration,
+    /// Consecutive slow batches before alerting
+    pub consecutive_batches: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct WriteLatencyStats {
+    /// Batches the percentiles are taken over
+    pub samples: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    /// Batches in a row over the slow-write threshold, up to now
+    pub consecutive_slow: u32,
+    /// Runs of slow batches that raised an alert
+    pub slow_alerts: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct SinkLatencyStats {
+    pub sqlite: WriteLatencyStats,
+    pub redis: WriteLatencyStats,
+    pub file: WriteLatencyStats,
+}
+
+#[derive(Debug, Default)]
+struct SinkSamples {
+    samples: Mutex<VecDeque<u64>>,
+    consecutive_slow: AtomicU32,
+    slow_alerts: AtomicU64,
+}
+
+pub struct SinkLatency {
+    sqlite: SinkSamples,
+    redis: SinkSamples,
+    file: SinkSamples,
+    /// Where published batches go
+    publisher: Sink,
+    thresholds: SlowWriteThresholds,
+    error_reporter: ErrorReporter,
+}
+
+impl SinkLatency {
+    pub fn new(thresholds: SlowWriteThresholds, error_reporter: ErrorReporter) -> Self {
+        Self {
+            sqlite: SinkSamples::default(),
+            redis: SinkSamples::default(),
+            file: SinkSamples::default(),
+            publisher: Sink::Redis,
+            thresholds,
+            error_reporter,
+        }
+    }
+
+    /// Which sink published batches are recorded under; Redis by default.
+    pub fn with_publisher(mut self, publisher: Sink) -> Self {
+        self.publisher = publisher;
+        self
+    }
+
+    pub fn publisher(&self) -> Sink {
+        self.publisher
+    }
+
+    /// Records how long `sink` took to write one batch. Returns true when this
+    /// batch makes the run of slow ones long enough to raise an alert. A sink
+    /// that stays slow alerts once; the next alert needs a fast batch first.
+    pub fn record(&self, sink: Sink, elapsed: Duration) -> bool {
+        histogram!("jetstream_turbo_sink_write_seconds", "sink" => sink.as_str())
+            .record(elapsed.as_secs_f64());
+        let state = self.samples(sink);
+        {
+            let mut samples = state.samples.lock().unwrap_or_else(|e| e.into_inner());
+            if samples.len() == SINK_LATENCY_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(elapsed.as_millis() as u64);
+        }
+
+        let threshold = self.thresholds.threshold;
+        if threshold.is_zero() || elapsed <= threshold {
+            state.consecutive_slow.store(0, Ordering::Relaxed);
+            return false;
+        }
+        let consecutive = state.consecutive_slow.fetch_add(1, Ordering::Relaxed) + 1;
+        if consecutive != self.thresholds.consecutive_batches.max(1) {
+            return false;
+        }
+
+        state.slow_alerts.fetch_add(1, Ordering::Relaxed);
+        counter!("jetstream_turbo_slow_sink_alerts_total", "sink" => sink.as_str()).increment(1);
+        let message = format!(
+            "{} writes over {:?} for {} consecutive batches (last {:?})",
+            sink.as_str(),
+            threshold,
+            consecutive,
+            elapsed
+        );
+        warn!("{}", message);
+        let mut ctx = HashMap::new();
+        ctx.insert("component", "storage");
+        ctx.insert("operation", "sink_write");
+        ctx.insert("sink", sink.as_str());
+        self.error_reporter
+            .capture_alert("SlowSinkWrite", &message, ctx);
+        true
+    }
+
+    pub fn stats(&self) -> SinkLatencyStats {
+        SinkLatencyStats {
+            sqlite: self.sink_stats(Sink::Sqlite),
+            redis: self.sink_stats(Sink::Redis),
+            file: self.sink_stats(Sink::File),
+        }
+    }
+
+    fn sink_stats(&self, sink: Sink) -> WriteLatencyStats {
+        let state = self.samples(sink);
+        let mut sorted: Vec<u64> = state
+            .samples
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .copied()
+            .collect();
+        let counters = WriteLatencyStats {
+            consecutive_slow: state.consecutive_slow.load(Ordering::Relaxed),
+            slow_alerts: state.slow_alerts.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        if sorted.is_empty() {
+            return counters;
+        }
+        sorted.sort_unstable();
+        // Nearest-rank percentile
+        let percentile = |p: usize| sorted[(sorted.len() * p).div_ceil(100).max(1) - 1];
+        WriteLatencyStats {
+            samples: sorted.len(),
+            p50_ms: percentile(50),
+            p95_ms: percentile(95),
+            p99_ms: percentile(99),
+            max_ms: sorted[sorted.len() - 1],
+            ..counters
+        }
+    }
+
+    fn samples(&self, sink: Sink) -> &SinkSamples {
+        match sink {
+            Sink::Sqlite => &self.sqlite,
+            Sink::Redis => &self.redis,
+            Sink::File => &self.file,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn latency(threshold_ms: u64, consecutive_batches: u32) -> SinkLatency {
+        SinkLatency::new(
+            SlowWriteThresholds {
+                threshold: Duration::from_millis(threshold_ms),
+                consecutive_batches,
+            },
+            ErrorReporter::new(None, None).await,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_percentiles_are_kept_per_sink() {
+        let latency = latency(0, 3).await;
+        assert_eq!(latency.stats(), SinkLatencyStats::default());
+
+        for ms in 1..=100 {
+            assert!(!latency.record(Sink::Sqlite, Duration::from_millis(ms)));
+        }
+        latency.record(Sink::Redis, Duration::from_millis(4));
+
+        let stats = latency.stats();
+        assert_eq!(stats.sqlite.samples, 100);
+        assert_eq!(
+            (
+                stats.sqlite.p50_ms,
+                stats.sqlite.p95_ms,
+                stats.sqlite.p99_ms
+            ),
+            (50, 95, 99)
+        );
+        assert_eq!(stats.sqlite.max_ms, 100);
+        assert_eq!((stats.redis.samples, stats.redis.max_ms), (1, 4));
+        assert_eq!(stats.file, WriteLatencyStats::default());
+        assert_eq!(stats.sqlite.consecutive_slow, 0);
+    }
+
+    #[tokio::test]
+    async fn test_writes_and_alerts_are_rendered_for_prometheus() {
+        let latency = latency(100, 1).await;
+        let recorder = crate::telemetry::prometheus_recorder();
+        metrics::with_local_recorder(&recorder, || {
+            assert!(latency.record(Sink::Sqlite, Duration::from_millis(250)));
+        });
+
+        let output = recorder.handle().render();
+        assert!(output
+            .contains("jetstream_turbo_sink_write_seconds_bucket{sink=\"sqlite\",le=\"0.25\"} 1"));
+        assert!(output.contains("jetstream_turbo_sink_write_seconds_count{sink=\"sqlite\"} 1"));
+        assert!(output.contains("jetstream_turbo_slow_sink_alerts_total{sink=\"sqlite\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_alerts_once_per_run_of_slow_batches() {
+        let latency = latency(100, 3).await;
+        let slow = Duration::from_millis(250);
+        let fast = Duration::from_millis(20);
+
+        assert!(!latency.record(Sink::Sqlite, slow));
+        assert!(!latency.record(Sink::Sqlite, slow));
+        // A fast batch breaks the run
+        assert!(!latency.record(Sink::Sqlite, fast));
+        assert!(!latency.record(Sink::Sqlite, slow));
+        assert!(!latency.record(Sink::Sqlite, slow));
+        assert!(latency.record(Sink::Sqlite, slow));
+        assert!(!latency.record(Sink::Sqlite, slow));
+        // Redis has its own run
+        assert!(!latency.record(Sink::Redis, slow));
+
+        let stats = latency.stats();
+        assert_eq!(stats.sqlite.slow_alerts, 1);
+        assert_eq!(stats.sqlite.consecutive_slow, 4);
+        assert_eq!(stats.redis.consecutive_slow, 1);
+        assert_eq!(stats.redis.slow_alerts, 0);
+    }
+}
@@ -0,0 +1,419 @@
+//! Named output streams for serving several downstream apps from one instance.
+//! Each stream has its own filter, Redis stream and WebSocket channel; records
+//! are routed to every stream whose filter they match, in addition to the
+//! default outputs. In standalone mode, or without the `redis` feature,
+//! streams only have the WebSocket channel.
+
+use crate::models::{
+    enriched::EnrichedRecord,
+    text::{contains_words, normalize, normalize_hashtag, words},
+    TurboResult,
+};
+#[cfg(feature = "redis")]
+use crate::storage::{EventPublisher, RedisStore};
+use crate::turbocharger::broadcast::{BroadcastStats, RecordBroadcaster, RecordSubscription};
+use futures::future::try_join_all;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Which records a stream receives. Every non-empty list must match; an empty
+/// list matches anything. `keywords` and `hashtags` are a single topic check,
+/// met by a post with any of either.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct StreamFilter {
+    pub collections: Vec<String>,
+    pub dids: Vec<String>,
+    /// Detected languages, e.g. `en`
+    pub languages: Vec<String>,
+    /// Hashtags, with or without the leading `#`, matched after Unicode
+    /// normalization and case folding
+    pub hashtags: Vec<String>,
+    /// Words or phrases matched against whole words of the post text after the
+    /// same normalization, so `rust` doesn't match `trust`. CJK text splits
+    /// into words per ideograph, so phrases match without spaces around them.
+    pub keywords: Vec<String>,
+    pub include_deletes: bool,
+}
+
+impl Default for StreamFilter {
+    fn default() -> Self {
+        Self {
+            collections: Vec::new(),
+            dids: Vec::new(),
+            languages: Vec::new(),
+            hashtags: Vec::new(),
+            keywords: Vec::new(),
+            include_deletes: true,
+        }
+    }
+}
+
+impl StreamFilter {
+    /// Normalizes the filter's hashtags and keywords once. For matching one
+    /// record; output streams compile their filter when they're created.
+    pub fn matches(&self, record: &EnrichedRecord) -> bool {
+        self.compile().matches(record)
+    }
+
+    pub fn compile(&self) -> CompiledStreamFilter {
+        CompiledStreamFilter {
+            hashtags: self
+                .hashtags
+                .iter()
+                .filter_map(|want| normalize_hashtag(want))
+                .collect(),
+            keywords: self
+                .keywords
+                .iter()
+                .map(|keyword| {
+                    words(&normalize(keyword))
+                        .into_iter()
+                        .map(str::to_owned)
+                        .collect()
+                })
+                .collect(),
+            filter: self.clone(),
+        }
+    }
+}
+
+/// A `StreamFilter` with its hashtags and keywords already normalized, so
+/// matching a record only normalizes the record.
+#[derive(Debug, Clone)]
+pub struct CompiledStreamFilter {
+    filter: StreamFilter,
+    hashtags: Vec<String>,
+    /// Words of each keyword
+    keywords: Vec<Vec<String>>,
+}
+
+impl CompiledStreamFilter {
+    pub fn filter(&self) -> &StreamFilter {
+        &self.filter
+    }
+
+    pub fn matches(&self, record: &EnrichedRecord) -> bool {
+        let filter = &self.filter;
+        if record.is_delete() && !filter.include_deletes {
+            return false;
+        }
+
+        let metadata = &record.hydrated_metadata;
+        let collection = record
+            .message
+            .commit
+            .as_ref()
+            .and_then(|commit| commit.collection.as_deref());
+
+        (filter.collections.is_empty()
+            || collection.is_some_and(|c| filter.collections.iter().any(|want| want == c)))
+            && (filter.dids.is_empty() || filter.dids.iter().any(|did| did == record.get_did()))
+            && (filter.languages.is_empty()
+                || metadata
+                    .detected_language
+                    .as_deref()
+                    .is_some_and(|lang| filter.languages.iter().any(|want| want == lang)))
+            && self.matches_topic(record)
+    }
+
+    fn matches_topic(&self, record: &EnrichedRecord) -> bool {
+        if self.filter.hashtags.is_empty() && self.filter.keywords.is_empty() {
+            return true;
+        }
+        let tagged = record
+            .hydrated_metadata
+            .hashtags
+            .iter()
+            .filter_map(|tag| normalize_hashtag(tag))
+            .any(|tag| self.hashtags.contains(&tag));
+        tagged
+            || (!self.keywords.is_empty()
+                && record.get_text().is_some_and(|text| {
+                    let text = normalize(text);
+                    let words = words(&text);
+                    self.keywords
+                        .iter()
+                        .any(|keyword| contains_words(&words, keyword))
+                }))
+    }
+}
+
+/// One entry of `OUTPUT_STREAMS`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct OutputStreamConfig {
+    /// Stream name; also the WebSocket channel at `/api/v1/ws/{name}`
+    pub name: String,
+    /// Redis stream to publish to; defaults to `{STREAM_NAME_REDIS}:{name}`
+    #[serde(default)]
+    pub redis_stream: Option<String>,
+    /// Entries kept in this stream's Redis stream; defaults to `TRIM_MAXLEN`
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    #[serde(flatten)]
+    pub filter: StreamFilter,
+}
+
+pub struct OutputStream {
+    name: String,
+    filter: CompiledStreamFilter,
+    /// `None` in standalone mode
+    #[cfg(feature = "redis")]
+    publisher: Option<RedisStore>,
+    broadcaster: RecordBroadcaster,
+}
+
+impl OutputStream {
+    fn new(config: &OutputStreamConfig, capacity: usize) -> Self {
+        Self {
+            name: config.name.clone(),
+            filter: config.filter.compile(),
+            #[cfg(feature = "redis")]
+            publisher: None,
+            broadcaster: RecordBroadcaster::new(capacity),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn filter(&self) -> &StreamFilter {
+        self.filter.filter()
+    }
+
+    pub fn subscribe(&self) -> RecordSubscription {
+        self.broadcaster.subscribe()
+    }
+
+    /// See `RecordBroadcaster::subscribe_after`.
+    pub fn subscribe_after(&self, last_id: Option<u64>) -> RecordSubscription {
+        self.broadcaster.subscribe_after(last_id)
+    }
+
+    async fn publish(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<usize> {
+        let matching: Vec<Arc<EnrichedRecord>> = records
+            .iter()
+            .filter(|record| self.filter.matches(record))
+            .cloned()
+            .collect();
+        if matching.is_empty() {
+            return Ok(0);
+        }
+
+        #[cfg(feature = "redis")]
+        if let Some(publisher) = &self.publisher {
+            publisher.publish_batch(&matching).await?;
+        }
+        counter!("jetstream_turbo_output_stream_records_total", "stream" => self.name.clone())
+            .increment(matching.len() as u64);
+        let count = matching.len();
+        for record in matching {
+            self.broadcaster.send(record);
+        }
+        Ok(count)
+    }
+
+    /// The Redis stream published to and its max length, if any.
+    fn redis_stream(&self) -> (Option<String>, Option<usize>) {
+        #[cfg(feature = "redis")]
+        if let Some(publisher) = &self.publisher {
+            return (
+                Some(publisher.stream_name().to_string()),
+                publisher.get_max_length(),
+            );
+        }
+        (None, None)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputStreamStats {
+    /// Absent in standalone mode and without the `redis` feature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redis_stream: Option<String>,
+    pub max_length: Option<usize>,
+    pub broadcast: BroadcastStats,
+}
+
+/// The configured named streams; cheap to clone into batch tasks.
+#[derive(Clone, Default)]
+pub struct OutputStreams {
+    streams: Arc<Vec<OutputStream>>,
+}
+
+impl OutputStreams {
+    /// Builds one stream per config, publishing only to WebSocket clients.
+    pub fn new(configs: &[OutputStreamConfig], capacity: usize) -> Self {
+        Self::from_streams(
+            configs
+                .iter()
+                .map(|config| OutputStream::new(config, capacity)),
+        )
+    }
+
+    /// `new`, with each stream also published through `redis` under the
+    /// stream's own name.
+    #[cfg(feature = "redis")]
+    pub fn with_redis(configs: &[OutputStreamConfig], redis: &RedisStore, capacity: usize) -> Self {
+        Self::from_streams(configs.iter().map(|config| {
+            OutputStream {
+                publisher: Some(
+                    redis
+                        .for_stream(
+                            config.redis_stream.clone().unwrap_or_else(|| {
+                                format!("{}:{}", redis.stream_name(), config.name)
+                            }),
+                        )
+                        .with_max_length(config.max_length.or(redis.get_max_length())),
+                ),
+                ..OutputStream::new(config, capacity)
+            }
+        }))
+    }
+
+    fn from_streams(streams: impl Iterator<Item = OutputStream>) -> Self {
+        Self {
+            streams: Arc::new(streams.collect()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OutputStream> {
+        self.streams.iter().find(|stream| stream.name == name)
+    }
+
+    /// Sends each record to every stream whose filter it matches.
+    pub async fn publish(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<()> {
+        if self.streams.is_empty() {
+            return Ok(());
+        }
+        try_join_all(self.streams.iter().map(|stream| stream.publish(records))).await?;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> BTreeMap<String, OutputStreamStats> {
+        self.streams
+            .iter()
+            .map(|stream| {
+                let (redis_stream, max_length) = stream.redis_stream();
+                (
+                    stream.name.clone(),
+                    OutputStreamStats {
+                        redis_stream,
+                        max_length,
+                        broadcast: stream.broadcaster.stats(),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::create_post_message;
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    async fn test_records_are_routed_to_matching_streams_only() {
+        let configs: Vec<OutputStreamConfig> = serde_json::from_str(
+            r##"[
+                {"name": "rust", "hashtags": ["#Rust"], "max_length": 100},
+                {"name": "user2", "dids": ["did:plc:user0002"], "redis_stream": "user2_posts"}
+            ]"##,
+        )
+        .unwrap();
+        let redis = RedisStore::new("", "hydrated".to_string(), None)
+            .await
+            .unwrap();
+        let streams = OutputStreams::with_redis(&configs, &redis, 16);
+        let mut rust_subscription = streams.get("rust").unwrap().subscribe();
+        let mut user2_subscription = streams.get("user2").unwrap().subscribe();
+
+        let mut tagged = EnrichedRecord::new(create_post_message(1));
+        tagged.hydrated_metadata.hashtags = vec!["rust".to_string()];
+        let untagged = EnrichedRecord::new(create_post_message(2));
+        streams
+            .publish(&[Arc::new(tagged), Arc::new(untagged)])
+            .await
+            .unwrap();
+
+        let received = rust_subscription.recv().await.unwrap();
+        assert_eq!(received.record.get_did(), "did:plc:user0001");
+        let received = user2_subscription.recv().await.unwrap();
+        assert_eq!(received.record.get_did(), "did:plc:user0002");
+
+        let stats = streams.stats();
+        assert_eq!(stats["rust"].redis_stream.as_deref(), Some("hydrated:rust"));
+        assert_eq!(stats["user2"].redis_stream.as_deref(), Some("user2_posts"));
+        assert_eq!(stats["rust"].max_length, Some(100));
+        assert_eq!(stats["user2"].max_length, None);
+        for (stream, did) in [
+            ("hydrated:rust", "did:plc:user0001"),
+            ("user2_posts", "did:plc:user0002"),
+        ] {
+            let entries = redis
+                .for_stream(stream.to_string())
+                .read_records("-", 10)
+                .await
+                .unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].record.get_did(), did);
+        }
+    }
+
+    fn post_with_text(text: &str) -> EnrichedRecord {
+        let mut message = create_post_message(1);
+        message.commit.as_mut().unwrap().record = Some(serde_json::json!({ "text": text }));
+        EnrichedRecord::new(message)
+    }
+
+    #[test]
+    fn test_keywords_match_whole_words_or_any_hashtag() {
+        let filter: StreamFilter = serde_json::from_str(
+            r#"{"keywords": ["Rust", "borrow checker"], "hashtags": ["rustlang"]}"#,
+        )
+        .unwrap();
+
+        assert!(filter.matches(&post_with_text("Learning rust today")));
+        assert!(filter.matches(&post_with_text("The BORROW CHECKER, again.")));
+        assert!(!filter.matches(&post_with_text("In crust we trust")));
+        assert!(!filter.matches(&post_with_text("borrow checkers")));
+
+        let mut tagged = post_with_text("no keywords here");
+        tagged.hydrated_metadata.hashtags = vec!["#RustLang".to_string()];
+        assert!(filter.matches(&tagged));
+
+        let keywords_only: StreamFilter =
+            serde_json::from_str(r#"{"keywords": ["rust"]}"#).unwrap();
+        assert!(!keywords_only.matches(&EnrichedRecord::new(create_post_message(1))));
+    }
+
+    #[test]
+    fn test_topics_match_across_scripts_and_widths() {
+        let filter: StreamFilter =
+            serde_json::from_str(r#"{"keywords": ["東京", "Straße"], "hashtags": ["＃ＲＵＳＴ"]}"#)
+                .unwrap();
+
+        assert!(filter.matches(&post_with_text("明日は東京に行きます")));
+        assert!(filter.matches(&post_with_text("Die STRASSE ist lang")));
+        assert!(!filter.matches(&post_with_text("京都に行きます")));
+
+        // Terms are normalized once, when the filter is compiled
+        let compiled = filter.compile();
+        assert_eq!(compiled.hashtags, ["rust"]);
+        assert_eq!(compiled.keywords, [vec!["東", "京"], vec!["strasse"]]);
+        assert!(compiled.matches(&post_with_text("明日は東京に行きます")));
+
+        let mut tagged = post_with_text("no keywords here");
+        tagged.hydrated_metadata.hashtags = vec!["rust".to_string()];
+        assert!(filter.matches(&tagged));
+    }
+}
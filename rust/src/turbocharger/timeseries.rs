@@ -0,0 +1,217 @@
+//! Per-minute throughput and latency for the last few hours, kept in memory so
+//! recent behavior can be charted without Prometheus.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const BUCKET_SECONDS: u64 = 60;
+/// Three hours of one-minute buckets.
+pub const RETAINED_BUCKETS: usize = 180;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ThroughputPoint {
+    /// Unix seconds at the start of the minute
+    pub timestamp: u64,
+    pub batches: u64,
+    pub messages_received: u64,
+    pub records_processed: u64,
+    pub avg_hydration_ms: f64,
+    pub max_hydration_ms: f64,
+    pub avg_sink_ms: f64,
+    pub max_sink_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThroughputSeriesSnapshot {
+    pub bucket_seconds: u64,
+    /// Oldest first, one per minute including idle ones, ending with the current minute
+    pub points: Vec<ThroughputPoint>,
+}
+
+#[derive(Debug, Default)]
+pub struct ThroughputSeries {
+    buckets: Mutex<VecDeque<ThroughputPoint>>,
+}
+
+impl ThroughputSeries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_batch(
+        &self,
+        messages_received: usize,
+        records_processed: usize,
+        hydration: Duration,
+        sink: Duration,
+    ) {
+        self.record_batch_at(
+            unix_now(),
+            messages_received,
+            records_processed,
+            hydration,
+            sink,
+        );
+    }
+
+    /// The last `minutes` buckets, or every retained one.
+    pub fn snapshot(&self, minutes: Option<usize>) -> ThroughputSeriesSnapshot {
+        self.snapshot_at(unix_now(), minutes)
+    }
+
+    fn record_batch_at(
+        &self,
+        now: u64,
+        messages_received: usize,
+        records_processed: usize,
+        hydration: Duration,
+        sink: Duration,
+    ) {
+        let mut buckets = self
+            .buckets
+            .lock()
+            .expect("throughput series lock poisoned");
+        let bucket = current_bucket(&mut buckets, now);
+        let hydration_ms = hydration.as_secs_f64() * 1000.0;
+        let sink_ms = sink.as_secs_f64() * 1000.0;
+        let previous_batches = bucket.batches as f64;
+
+        bucket.batches += 1;
+        bucket.messages_received += messages_received as u64;
+        bucket.records_processed += records_processed as u64;
+        bucket.avg_hydration_ms =
+            (bucket.avg_hydration_ms * previous_batches + hydration_ms) / bucket.batches as f64;
+        bucket.max_hydration_ms = bucket.max_hydration_ms.max(hydration_ms);
+        bucket.avg_sink_ms =
+            (bucket.avg_sink_ms * previous_batches + sink_ms) / bucket.batches as f64;
+        bucket.max_sink_ms = bucket.max_sink_ms.max(sink_ms);
+    }
+
+    fn snapshot_at(&self, now: u64, minutes: Option<usize>) -> ThroughputSeriesSnapshot {
+        let mut buckets = self
+            .buckets
+            .lock()
+            .expect("throughput series lock poisoned");
+        // Pad up to the current minute so an idle pipeline shows as zeros
+        current_bucket(&mut buckets, now);
+        let limit = minutes.unwrap_or(RETAINED_BUCKETS).min(buckets.len());
+        ThroughputSeriesSnapshot {
+            bucket_seconds: BUCKET_SECONDS,
+            points: buckets
+                .iter()
+                .skip(buckets.len() - limit)
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+/// The bucket for `now`, appending empty buckets for any minutes skipped since
+/// the last one and dropping those past retention.
+fn current_bucket(buckets: &mut VecDeque<ThroughputPoint>, now: u64) -> &mut ThroughputPoint {
+    let minute = now - now % BUCKET_SECONDS;
+    let next = match buckets.back() {
+        Some(last) if last.timestamp >= minute => None,
+        Some(last) => {
+            // Past a full retention window there is nothing left to pad from
+            let oldest = minute.saturating_sub((RETAINED_BUCKETS as u64 - 1) * BUCKET_SECONDS);
+            Some((last.timestamp + BUCKET_SECONDS).max(oldest))
+        }
+        None => Some(minute),
+    };
+    if let Some(first_new) = next {
+        for timestamp in (first_new..=minute).step_by(BUCKET_SECONDS as usize) {
+            buckets.push_back(ThroughputPoint {
+                timestamp,
+                ..ThroughputPoint::default()
+            });
+        }
+        while buckets.len() > RETAINED_BUCKETS {
+            buckets.pop_front();
+        }
+    }
+    buckets.back_mut().expect("current bucket was just ensured")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_700_000_040;
+
+    #[test]
+    fn test_batches_aggregate_per_minute_with_idle_minutes_padded() {
+        let series = ThroughputSeries::new();
+        series.record_batch_at(
+            START,
+            10,
+            8,
+            Duration::from_millis(100),
+            Duration::from_millis(20),
+        );
+        series.record_batch_at(
+            START + 30,
+            10,
+            10,
+            Duration::from_millis(300),
+            Duration::from_millis(40),
+        );
+        series.record_batch_at(
+            START + 3 * BUCKET_SECONDS,
+            5,
+            5,
+            Duration::from_millis(50),
+            Duration::from_millis(10),
+        );
+
+        let snapshot = series.snapshot_at(START + 4 * BUCKET_SECONDS, None);
+        let timestamps: Vec<u64> = snapshot.points.iter().map(|p| p.timestamp).collect();
+        assert_eq!(
+            timestamps,
+            (0..5)
+                .map(|m| START + m * BUCKET_SECONDS)
+                .collect::<Vec<_>>()
+        );
+
+        let first = snapshot.points[0];
+        assert_eq!(first.batches, 2);
+        assert_eq!(first.messages_received, 20);
+        assert_eq!(first.records_processed, 18);
+        assert!((first.avg_hydration_ms - 200.0).abs() < 1e-6);
+        assert!((first.max_hydration_ms - 300.0).abs() < 1e-6);
+        assert!((first.avg_sink_ms - 30.0).abs() < 1e-6);
+        assert_eq!(
+            snapshot.points[1],
+            ThroughputPoint {
+                timestamp: START + BUCKET_SECONDS,
+                ..ThroughputPoint::default()
+            }
+        );
+        assert_eq!(snapshot.points[3].records_processed, 5);
+
+        let recent = series.snapshot_at(START + 4 * BUCKET_SECONDS, Some(2));
+        assert_eq!(recent.points.len(), 2);
+        assert_eq!(recent.points[0].records_processed, 5);
+    }
+
+    #[test]
+    fn test_retention_is_bounded() {
+        let series = ThroughputSeries::new();
+        series.record_batch_at(START, 1, 1, Duration::ZERO, Duration::ZERO);
+
+        let later = START + 1000 * BUCKET_SECONDS;
+        let snapshot = series.snapshot_at(later, None);
+        assert_eq!(snapshot.points.len(), RETAINED_BUCKETS);
+        assert_eq!(snapshot.points.last().unwrap().timestamp, later);
+        assert!(snapshot.points.iter().all(|p| p.batches == 0));
+    }
+}
@@ -0,0 +1,419 @@
+//! Watched accounts. Posts by a DID or handle on the watchlist are published
+//! to their own Redis stream and WebSocket channel (`/api/v1/ws/watchlist`),
+//! and optionally POSTed to a webhook. Entries can be added and removed at
+//! runtime through the admin API; `WATCHLIST` only seeds the list at startup.
+
+use crate::client::http::{client_builder, DEFAULT_USER_AGENT};
+use crate::models::{enriched::EnrichedRecord, jetstream::OperationType, TurboResult};
+#[cfg(feature = "redis")]
+use crate::storage::{EventPublisher, RedisStore};
+use crate::turbocharger::broadcast::{BroadcastStats, RecordBroadcaster, RecordSubscription};
+use metrics::counter;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+/// Channel name of the watchlist under `/api/v1/ws/`; not usable by `OUTPUT_STREAMS`.
+pub const WATCHLIST_STREAM: &str = "watchlist";
+const POST_COLLECTION: &str = "app.bsky.feed.post";
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+const CONCURRENT_WEBHOOKS: usize = 4;
+/// Alerts past this many queued or sending are dropped rather than piling up
+/// tasks behind a slow webhook
+const MAX_PENDING_WEBHOOKS: usize = 1_000;
+
+/// Body POSTed to the webhook for each post by a watched account.
+#[derive(Debug, Serialize)]
+pub struct WatchlistAlert<'a> {
+    pub event: &'static str,
+    /// The watchlist entry that matched, a DID or handle
+    pub matched: &'a str,
+    pub did: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handle: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<&'a str>,
+    pub record: &'a EnrichedRecord,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchlistStats {
+    pub entries: usize,
+    /// Absent in standalone mode and without the `redis` feature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redis_stream: Option<String>,
+    pub broadcast: BroadcastStats,
+}
+
+pub struct Watchlist {
+    /// DIDs and lowercased handles
+    entries: RwLock<BTreeSet<String>>,
+    /// `None` in standalone mode
+    #[cfg(feature = "redis")]
+    publisher: Option<RedisStore>,
+    broadcaster: RecordBroadcaster,
+    webhook: Option<Webhook>,
+}
+
+struct Webhook {
+    client: Client,
+    url: String,
+    pending: Arc<Semaphore>,
+    sending: Arc<Semaphore>,
+}
+
+impl Watchlist {
+    pub fn new(
+        entries: &[String],
+        capacity: usize,
+        webhook_url: Option<String>,
+    ) -> TurboResult<Self> {
+        let webhook = match webhook_url {
+            Some(url) => Some(Webhook {
+                client: Self::webhook_client(DEFAULT_USER_AGENT)?,
+                url,
+                pending: Arc::new(Semaphore::new(MAX_PENDING_WEBHOOKS)),
+                sending: Arc::new(Semaphore::new(CONCURRENT_WEBHOOKS)),
+            }),
+            None => None,
+        };
+        Ok(Self {
+            entries: RwLock::new(entries.iter().filter_map(|e| normalize_entry(e)).collect()),
+            #[cfg(feature = "redis")]
+            publisher: None,
+            broadcaster: RecordBroadcaster::new(capacity),
+            webhook,
+        })
+    }
+
+    /// Also publishes watched posts to `publisher`'s Redis stream.
+    #[cfg(feature = "redis")]
+    pub fn with_publisher(mut self, publisher: RedisStore) -> Self {
+        self.publisher = Some(publisher);
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> TurboResult<Self> {
+        if let Some(webhook) = &mut self.webhook {
+            webhook.client = Self::webhook_client(user_agent)?;
+        }
+        Ok(self)
+    }
+
+    fn webhook_client(user_agent: &str) -> TurboResult<Client> {
+        Ok(client_builder(user_agent)
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()?)
+    }
+
+    pub fn entries(&self) -> Vec<String> {
+        self.read().iter().cloned().collect()
+    }
+
+    pub fn contains(&self, entry: &str) -> bool {
+        normalize_entry(entry).is_some_and(|entry| self.read().contains(&entry))
+    }
+
+    /// Returns false if `entry` was already watched or isn't a DID or handle.
+    pub fn add(&self, entry: &str) -> bool {
+        let Some(entry) = normalize_entry(entry) else {
+            return false;
+        };
+        let added = self.write().insert(entry.clone());
+        if added {
+            info!("Watching {}", entry);
+        }
+        added
+    }
+
+    /// Returns false if `entry` wasn't watched.
+    pub fn remove(&self, entry: &str) -> bool {
+        let Some(entry) = normalize_entry(entry) else {
+            return false;
+        };
+        let removed = self.write().remove(&entry);
+        if removed {
+            info!("No longer watching {}", entry);
+        }
+        removed
+    }
+
+    pub fn subscribe_after(&self, last_id: Option<u64>) -> RecordSubscription {
+        self.broadcaster.subscribe_after(last_id)
+    }
+
+    pub fn stats(&self) -> WatchlistStats {
+        WatchlistStats {
+            entries: self.read().len(),
+            #[cfg(feature = "redis")]
+            redis_stream: self
+                .publisher
+                .as_ref()
+                .map(|publisher| publisher.stream_name().to_string()),
+            #[cfg(not(feature = "redis"))]
+            redis_stream: None,
+            broadcast: self.broadcaster.stats(),
+        }
+    }
+
+    /// The entry `record` matches if it's a new post by a watched account.
+    fn matching_entry(&self, record: &EnrichedRecord) -> Option<String> {
+        let commit = record.message.commit.as_ref()?;
+        if commit.operation_type != OperationType::Create
+            || commit.collection.as_deref() != Some(POST_COLLECTION)
+        {
+            return None;
+        }
+        let entries = self.read();
+        if entries.contains(record.get_did()) {
+            return Some(record.get_did().to_string());
+        }
+        let handle = record
+            .hydrated_metadata
+            .author_profile
+            .as_ref()?
+            .handle
+            .to_ascii_lowercase();
+        entries.contains(&handle).then_some(handle)
+    }
+
+    pub async fn publish(&self, records: &[Arc<EnrichedRecord>]) -> TurboResult<usize> {
+        if self.read().is_empty() {
+            return Ok(0);
+        }
+        let matching: Vec<(String, Arc<EnrichedRecord>)> = records
+            .iter()
+            .filter_map(|record| Some((self.matching_entry(record)?, Arc::clone(record))))
+            .collect();
+        if matching.is_empty() {
+            return Ok(0);
+        }
+
+        #[cfg(feature = "redis")]
+        if let Some(publisher) = &self.publisher {
+            let matched_records: Vec<Arc<EnrichedRecord>> = matching
+                .iter()
+                .map(|(_, record)| Arc::clone(record))
+                .collect();
+            publisher.publish_batch(&matched_records).await?;
+        }
+        counter!("jetstream_turbo_watchlist_alerts_total").increment(matching.len() as u64);
+
+        let count = matching.len();
+        for (entry, record) in matching {
+            if let Some(webhook) = &self.webhook {
+                webhook.enqueue(entry, Arc::clone(&record));
+            }
+            self.broadcaster.send(record);
+        }
+        Ok(count)
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeSet<String>> {
+        self.entries.read().expect("watchlist lock poisoned")
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeSet<String>> {
+        self.entries.write().expect("watchlist lock poisoned")
+    }
+}
+
+impl Webhook {
+    /// Webhooks are best effort and must not hold up the batch, so alerts are
+    /// sent in the background and dropped once too many are waiting.
+    fn enqueue(&self, matched: String, record: Arc<EnrichedRecord>) {
+        let Ok(queued) = Arc::clone(&self.pending).try_acquire_owned() else {
+            counter!("jetstream_turbo_watchlist_webhook_dropped_total").increment(1);
+            return;
+        };
+        let client = self.client.clone();
+        let url = self.url.clone();
+        let sending = Arc::clone(&self.sending);
+        tokio::spawn(async move {
+            let _queued = queued;
+            let Ok(_sending) = sending.acquire().await else {
+                return;
+            };
+            send_webhook(&client, &url, &matched, &record).await;
+        });
+    }
+}
+
+/// DIDs are kept as-is; handles are lowercased and lose any leading `@`.
+fn normalize_entry(entry: &str) -> Option<String> {
+    let entry = entry.trim().trim_start_matches('@');
+    if entry.is_empty() {
+        None
+    } else if entry.starts_with("did:") {
+        Some(entry.to_string())
+    } else if entry.contains('.') {
+        Some(entry.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+async fn send_webhook(client: &Client, url: &str, matched: &str, record: &EnrichedRecord) {
+    let alert = WatchlistAlert {
+        event: "watchlist.post",
+        matched,
+        did: record.get_did(),
+        handle: record
+            .hydrated_metadata
+            .author_profile
+            .as_ref()
+            .map(|profile| profile.handle.as_str()),
+        uri: record.get_at_uri(),
+        text: record.get_text(),
+        record,
+    };
+    let result = client
+        .post(url)
+        .json(&alert)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    if let Err(e) = result {
+        counter!("jetstream_turbo_watchlist_webhook_failures_total").increment(1);
+        warn!("Watchlist webhook for {} failed: {}", matched, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bluesky::BlueskyProfile;
+    use crate::testing::fixtures::create_post_message;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_entries_are_normalized() {
+        assert_eq!(
+            normalize_entry(" @Alice.BSKY.social"),
+            Some("alice.bsky.social".to_string())
+        );
+        assert_eq!(
+            normalize_entry("did:plc:AbC"),
+            Some("did:plc:AbC".to_string())
+        );
+        assert_eq!(normalize_entry("alice"), None);
+        assert_eq!(normalize_entry(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_posts_by_watched_accounts_are_published_and_sent_to_webhook() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_partial_json(serde_json::json!({
+                "event": "watchlist.post",
+                "matched": "user2.bsky.social",
+                "did": "did:plc:user0002",
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let watchlist = Watchlist::new(
+            &["did:plc:user0001".to_string()],
+            16,
+            Some(format!("{}/hook", server.uri())),
+        )
+        .unwrap();
+        #[cfg(feature = "redis")]
+        let redis = RedisStore::new("", "hydrated".to_string(), None)
+            .await
+            .unwrap();
+        #[cfg(feature = "redis")]
+        let watchlist =
+            watchlist.with_publisher(redis.for_stream("hydrated:watchlist".to_string()));
+        assert!(watchlist.add("@User2.bsky.social"));
+        assert!(!watchlist.add("user2.bsky.social"));
+        assert!(watchlist.contains("USER2.bsky.social"));
+        let mut subscription = watchlist.subscribe_after(None);
+
+        let by_did = EnrichedRecord::new(create_post_message(1));
+        let mut by_handle = EnrichedRecord::new(create_post_message(2));
+        let profile: BlueskyProfile = serde_json::from_value(serde_json::json!({
+            "did": "did:plc:user0002",
+            "handle": "User2.bsky.social"
+        }))
+        .unwrap();
+        by_handle.hydrated_metadata.author_profile = Some(Arc::new(profile));
+        let unwatched = EnrichedRecord::new(create_post_message(3));
+
+        let published = watchlist
+            .publish(&[Arc::new(by_did), Arc::new(by_handle), Arc::new(unwatched)])
+            .await
+            .unwrap();
+        assert_eq!(published, 2);
+        assert_eq!(
+            subscription.recv().await.unwrap().record.get_did(),
+            "did:plc:user0001"
+        );
+        assert_eq!(
+            subscription.recv().await.unwrap().record.get_did(),
+            "did:plc:user0002"
+        );
+        #[cfg(feature = "redis")]
+        {
+            let stored = redis
+                .for_stream("hydrated:watchlist".to_string())
+                .read_records("-", 10)
+                .await
+                .unwrap();
+            assert_eq!(stored.len(), 2);
+        }
+
+        assert!(watchlist.remove("user2.bsky.social"));
+        assert_eq!(watchlist.entries(), vec!["did:plc:user0001".to_string()]);
+
+        // Let the spawned webhook call land before the mock verifies
+        for _ in 0..50 {
+            if !server.received_requests().await.unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhook_alerts_past_the_pending_limit_are_dropped() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(200)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let webhook = Webhook {
+            client: Client::new(),
+            url: format!("{}/hook", server.uri()),
+            pending: Arc::new(Semaphore::new(1)),
+            sending: Arc::new(Semaphore::new(1)),
+        };
+        let record = Arc::new(EnrichedRecord::new(create_post_message(1)));
+        for _ in 0..3 {
+            webhook.enqueue("did:plc:user0001".to_string(), Arc::clone(&record));
+        }
+        assert_eq!(webhook.pending.available_permits(), 0);
+
+        // The one alert let through frees its slot once sent
+        for _ in 0..50 {
+            if webhook.pending.available_permits() == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(webhook.pending.available_permits(), 1);
+    }
+}
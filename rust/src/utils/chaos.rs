@@ -0,0 +1,206 @@
+//! Fault injection for resilience testing, built only with the `chaos`
+//! feature. Each of the Redis, SQLite and Bluesky client paths holds a
+//! `FaultInjector` that, on every call, may add latency and may fail the call
+//! before it does any work, at the rates set in `CHAOS`:
+//!
+//! ```text
+//! CHAOS={"sqlite":{"failure_rate":0.05},"bluesky":{"latency_ms":250,"latency_rate":0.5}}
+//! ```
+//!
+//! Injected failures surface as `TurboError::InjectedFault` through the same
+//! error paths as real ones, so retries and sink error accounting can be
+//! watched under a known failure rate.
+
+use crate::models::errors::{TurboError, TurboResult};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::trace;
+
+/// Faults for one client path. Rates are probabilities per call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FaultConfig {
+    pub failure_rate: f64,
+    pub latency_ms: u64,
+    pub latency_rate: f64,
+}
+
+impl FaultConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.failure_rate > 0.0 || (self.latency_ms > 0 && self.latency_rate > 0.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    pub redis: FaultConfig,
+    pub sqlite: FaultConfig,
+    pub bluesky: FaultConfig,
+    /// Makes the injected sequence repeatable; random per process otherwise
+    pub seed: Option<u64>,
+}
+
+impl ChaosConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.redis.is_enabled() || self.sqlite.is_enabled() || self.bluesky.is_enabled()
+    }
+
+    pub fn redis_injector(&self) -> FaultInjector {
+        self.injector("redis", self.redis, 1)
+    }
+
+    pub fn sqlite_injector(&self) -> FaultInjector {
+        self.injector("sqlite", self.sqlite, 2)
+    }
+
+    pub fn bluesky_injector(&self) -> FaultInjector {
+        self.injector("bluesky", self.bluesky, 3)
+    }
+
+    /// Each path gets its own stream so one path's call volume doesn't shift
+    /// another's faults.
+    fn injector(&self, target: &'static str, config: FaultConfig, stream: u64) -> FaultInjector {
+        let seed = self.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+        FaultInjector::new(
+            target,
+            config,
+            seed ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15),
+        )
+    }
+}
+
+/// Injects one path's faults. Clones share their random sequence.
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    target: &'static str,
+    config: FaultConfig,
+    state: Arc<AtomicU64>,
+}
+
+impl Default for FaultInjector {
+    /// Never injects anything.
+    fn default() -> Self {
+        Self::new("none", FaultConfig::default(), 0)
+    }
+}
+
+impl FaultInjector {
+    pub fn new(target: &'static str, config: FaultConfig, seed: u64) -> Self {
+        Self {
+            target,
+            config,
+            state: Arc::new(AtomicU64::new(seed)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_enabled()
+    }
+
+    /// Sleeps for the configured latency and/or fails, each at its rate.
+    pub async fn inject(&self) -> TurboResult<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if self.config.latency_ms > 0 && self.roll(self.config.latency_rate) {
+            counter!("jetstream_turbo_chaos_injected_total", "target" => self.target, "fault" => "latency")
+                .increment(1);
+            trace!(
+                "Injecting {}ms of latency into {}",
+                self.config.latency_ms,
+                self.target
+            );
+            tokio::time::sleep(Duration::from_millis(self.config.latency_ms)).await;
+        }
+        if self.roll(self.config.failure_rate) {
+            counter!("jetstream_turbo_chaos_injected_total", "target" => self.target, "fault" => "failure")
+                .increment(1);
+            return Err(TurboError::InjectedFault(self.target));
+        }
+        Ok(())
+    }
+
+    fn roll(&self, rate: f64) -> bool {
+        if rate <= 0.0 {
+            return false;
+        }
+        // splitmix64
+        let mut z = self
+            .state
+            .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        ((z >> 11) as f64 / (1u64 << 53) as f64) < rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_failures_are_injected_at_the_configured_rate() {
+        let config = ChaosConfig {
+            sqlite: FaultConfig {
+                failure_rate: 0.25,
+                ..Default::default()
+            },
+            seed: Some(7),
+            ..Default::default()
+        };
+        assert!(config.is_enabled());
+
+        let injector = config.sqlite_injector();
+        let mut failures = 0;
+        for _ in 0..2_000 {
+            if let Err(e) = injector.inject().await {
+                assert!(matches!(e, TurboError::InjectedFault("sqlite")));
+                failures += 1;
+            }
+        }
+        assert!((400..600).contains(&failures), "{failures} failures");
+
+        // The same seed gives the same sequence
+        let outcomes = |injector: FaultInjector| async move {
+            let mut outcomes = Vec::new();
+            for _ in 0..50 {
+                outcomes.push(injector.inject().await.is_ok());
+            }
+            outcomes
+        };
+        assert_eq!(
+            outcomes(config.sqlite_injector()).await,
+            outcomes(config.sqlite_injector()).await
+        );
+
+        for _ in 0..100 {
+            assert!(config.redis_injector().inject().await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_latency_is_added_before_the_call() {
+        let injector = FaultInjector::new(
+            "redis",
+            FaultConfig {
+                latency_ms: 20,
+                latency_rate: 1.0,
+                ..Default::default()
+            },
+            1,
+        );
+        let started = std::time::Instant::now();
+        injector.inject().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,411 @@
+//! Minimal DAG-CBOR and CARv1 decoding for the `com.atproto.sync.subscribeRepos` firehose.
+//!
+//! Only the subset of CBOR that DAG-CBOR allows is accepted: definite lengths, text map
+//! keys, 64-bit floats and tag 42 for CID links.
+use crate::models::errors::{TurboError, TurboResult};
+use data_encoding::{BASE32_NOPAD, BASE64_NOPAD};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+const CID_TAG: u64 = 42;
+const MAX_DEPTH: usize = 64;
+
+/// A decoded DAG-CBOR item that borrows strings and byte strings from the input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CborValue<'a> {
+    Unsigned(u64),
+    Negative(i128),
+    Float(f64),
+    Bool(bool),
+    Null,
+    Bytes(&'a [u8]),
+    Text(&'a str),
+    Array(Vec<CborValue<'a>>),
+    Map(Vec<(&'a str, CborValue<'a>)>),
+    /// CID link, rendered in its canonical base32 string form
+    Link(String),
+}
+
+impl<'a> CborValue<'a> {
+    pub fn get(&self, key: &str) -> Option<&CborValue<'a>> {
+        match self {
+            CborValue::Map(entries) => entries.iter().find(|(k, _)| *k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            CborValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            CborValue::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            CborValue::Unsigned(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            CborValue::Unsigned(n) => i64::try_from(*n).ok(),
+            CborValue::Negative(n) => i64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            CborValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[CborValue<'a>]> {
+        match self {
+            CborValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_link(&self) -> Option<&str> {
+        match self {
+            CborValue::Link(cid) => Some(cid),
+            _ => None,
+        }
+    }
+
+    /// Converts to the atproto JSON data model: links become `{"$link": cid}` and
+    /// byte strings become `{"$bytes": base64}`.
+    pub fn to_json(&self) -> Value {
+        match self {
+            CborValue::Unsigned(n) => Value::from(*n),
+            CborValue::Negative(n) => i64::try_from(*n)
+                .map(Value::from)
+                .unwrap_or_else(|_| Value::from(*n as f64)),
+            CborValue::Float(f) => serde_json::Number::from_f64(*f)
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            CborValue::Bool(b) => Value::Bool(*b),
+            CborValue::Null => Value::Null,
+            CborValue::Bytes(bytes) => {
+                let mut map = Map::with_capacity(1);
+                map.insert("$bytes".to_string(), BASE64_NOPAD.encode(bytes).into());
+                Value::Object(map)
+            }
+            CborValue::Text(text) => Value::String((*text).to_string()),
+            CborValue::Array(items) => Value::Array(items.iter().map(Self::to_json).collect()),
+            CborValue::Map(entries) => Value::Object(
+                entries
+                    .iter()
+                    .map(|(k, v)| ((*k).to_string(), v.to_json()))
+                    .collect(),
+            ),
+            CborValue::Link(cid) => {
+                let mut map = Map::with_capacity(1);
+                map.insert("$link".to_string(), Value::String(cid.clone()));
+                Value::Object(map)
+            }
+        }
+    }
+}
+
+/// Sequential decoder over a buffer holding one or more concatenated CBOR items.
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    pub fn decode(&mut self) -> TurboResult<CborValue<'a>> {
+        self.decode_at_depth(0)
+    }
+
+    fn take(&mut self, len: usize) -> TurboResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| cbor_error("unexpected end of input"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Returns the major type, the additional-info bits and the argument.
+    fn read_head(&mut self) -> TurboResult<(u8, u8, u64)> {
+        let initial = self.take(1)?[0];
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+        let arg = match info {
+            info @ 0..=23 => u64::from(info),
+            24 => u64::from(self.take(1)?[0]),
+            25 => u64::from(u16::from_be_bytes(self.take(2)?.try_into().unwrap())),
+            26 => u64::from(u32::from_be_bytes(self.take(4)?.try_into().unwrap())),
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            info => {
+                return Err(cbor_error(&format!(
+                    "unsupported additional info {info} (indefinite lengths are not DAG-CBOR)"
+                )))
+            }
+        };
+        Ok((major, info, arg))
+    }
+
+    fn read_len(&mut self, arg: u64) -> TurboResult<usize> {
+        let len = usize::try_from(arg).map_err(|_| cbor_error("length overflow"))?;
+        // Every element takes at least one byte, which bounds hostile lengths
+        if len > self.buf.len() - self.pos {
+            return Err(cbor_error("length exceeds input"));
+        }
+        Ok(len)
+    }
+
+    fn decode_at_depth(&mut self, depth: usize) -> TurboResult<CborValue<'a>> {
+        if depth > MAX_DEPTH {
+            return Err(cbor_error("nesting too deep"));
+        }
+
+        let (major, info, arg) = self.read_head()?;
+        match major {
+            0 => Ok(CborValue::Unsigned(arg)),
+            1 => Ok(CborValue::Negative(-1 - i128::from(arg))),
+            2 => {
+                let len = self.read_len(arg)?;
+                Ok(CborValue::Bytes(self.take(len)?))
+            }
+            3 => {
+                let len = self.read_len(arg)?;
+                let text = std::str::from_utf8(self.take(len)?)
+                    .map_err(|_| cbor_error("invalid UTF-8 in text string"))?;
+                Ok(CborValue::Text(text))
+            }
+            4 => {
+                let len = self.read_len(arg)?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.decode_at_depth(depth + 1)?);
+                }
+                Ok(CborValue::Array(items))
+            }
+            5 => {
+                let len = self.read_len(arg)?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let key = match self.decode_at_depth(depth + 1)? {
+                        CborValue::Text(key) => key,
+                        _ => return Err(cbor_error("map keys must be text strings")),
+                    };
+                    entries.push((key, self.decode_at_depth(depth + 1)?));
+                }
+                Ok(CborValue::Map(entries))
+            }
+            6 if arg == CID_TAG => match self.decode_at_depth(depth + 1)? {
+                // Tag 42 payloads carry a leading 0x00 multibase "identity" prefix
+                CborValue::Bytes([0, cid @ ..]) => Ok(CborValue::Link(cid_to_string(cid))),
+                _ => Err(cbor_error("malformed CID link")),
+            },
+            6 => Err(cbor_error(&format!("unsupported tag {arg}"))),
+            7 => match info {
+                20 => Ok(CborValue::Bool(false)),
+                21 => Ok(CborValue::Bool(true)),
+                22 => Ok(CborValue::Null),
+                27 => Ok(CborValue::Float(f64::from_bits(arg))),
+                _ => Err(cbor_error("unsupported simple value")),
+            },
+            _ => unreachable!("major type is three bits"),
+        }
+    }
+}
+
+/// Canonical string form of a binary CID: base32 multibase for v1, base58btc for v0.
+pub fn cid_to_string(cid: &[u8]) -> String {
+    if is_cid_v0(cid) {
+        return base58btc(cid);
+    }
+    let mut out = String::with_capacity(1 + BASE32_NOPAD.encode_len(cid.len()));
+    out.push('b');
+    out.push_str(&BASE32_NOPAD.encode(cid).to_ascii_lowercase());
+    out
+}
+
+fn is_cid_v0(cid: &[u8]) -> bool {
+    cid.len() == 34 && cid[0] == 0x12 && cid[1] == 0x20
+}
+
+fn base58btc(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for &byte in bytes {
+        let mut carry = u32::from(byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    std::iter::repeat_n('1', zeros)
+        .chain(digits.iter().rev().map(|d| ALPHABET[*d as usize] as char))
+        .collect()
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> TurboResult<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf
+            .get(*pos)
+            .ok_or_else(|| cbor_error("truncated varint"))?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(cbor_error("varint too long"))
+}
+
+/// Length in bytes of the binary CID at the start of `buf`.
+fn cid_len(buf: &[u8]) -> TurboResult<usize> {
+    if buf.len() >= 34 && is_cid_v0(&buf[..34]) {
+        return Ok(34);
+    }
+    let mut pos = 0;
+    let _version = read_varint(buf, &mut pos)?;
+    let _codec = read_varint(buf, &mut pos)?;
+    let _hash = read_varint(buf, &mut pos)?;
+    let digest_len = read_varint(buf, &mut pos)? as usize;
+    let len = pos
+        .checked_add(digest_len)
+        .filter(|len| *len <= buf.len())
+        .ok_or_else(|| cbor_error("truncated CID"))?;
+    Ok(len)
+}
+
+/// Reads the blocks of a CARv1 archive, keyed by CID string.
+pub fn read_car_blocks(car: &[u8]) -> TurboResult<HashMap<String, &[u8]>> {
+    let mut pos = 0;
+    let header_len = read_varint(car, &mut pos)? as usize;
+    pos = pos
+        .checked_add(header_len)
+        .filter(|end| *end <= car.len())
+        .ok_or_else(|| cbor_error("truncated CAR header"))?;
+
+    let mut blocks = HashMap::new();
+    while pos < car.len() {
+        let section_len = read_varint(car, &mut pos)? as usize;
+        let section = pos
+            .checked_add(section_len)
+            .and_then(|end| car.get(pos..end))
+            .ok_or_else(|| cbor_error("truncated CAR block"))?;
+        let cid_len = cid_len(section)?;
+        blocks.insert(cid_to_string(&section[..cid_len]), &section[cid_len..]);
+        pos += section_len;
+    }
+    Ok(blocks)
+}
+
+fn cbor_error(message: &str) -> TurboError {
+    TurboError::InvalidMessage(format!("DAG-CBOR: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sha2-256 digest of nothing in particular; only the layout matters here
+    const CID_V1: [u8; 36] = {
+        let mut cid = [0x11u8; 36];
+        cid[0] = 0x01; // version
+        cid[1] = 0x71; // dag-cbor
+        cid[2] = 0x12; // sha2-256
+        cid[3] = 0x20; // 32-byte digest
+        cid
+    };
+
+    fn text(out: &mut Vec<u8>, s: &str) {
+        out.push(0x60 | s.len() as u8);
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    #[test]
+    fn test_decode_map_with_link_and_bytes() {
+        // {"a": 1, "b": -2, "c": [true, null], "d": h'0102', "e": 42(h'00' || CID)}
+        let mut buf = vec![0xa5];
+        text(&mut buf, "a");
+        buf.push(0x01);
+        text(&mut buf, "b");
+        buf.push(0x21);
+        text(&mut buf, "c");
+        buf.extend_from_slice(&[0x82, 0xf5, 0xf6]);
+        text(&mut buf, "d");
+        buf.extend_from_slice(&[0x42, 0x01, 0x02]);
+        text(&mut buf, "e");
+        buf.extend_from_slice(&[0xd8, 42, 0x58, 37, 0x00]);
+        buf.extend_from_slice(&CID_V1);
+
+        let value = Decoder::new(&buf).decode().unwrap();
+        assert_eq!(value.get("a").and_then(CborValue::as_u64), Some(1));
+        assert_eq!(value.get("b").and_then(CborValue::as_i64), Some(-2));
+
+        let json = value.to_json();
+        assert_eq!(json["c"], serde_json::json!([true, null]));
+        assert_eq!(json["d"]["$bytes"], "AQI");
+        let link = json["e"]["$link"].as_str().unwrap();
+        assert!(link.starts_with("bafyrei"), "unexpected CID string {link}");
+    }
+
+    #[test]
+    fn test_rejects_non_dag_cbor() {
+        // indefinite-length array
+        assert!(Decoder::new(&[0x9f, 0x01, 0xff]).decode().is_err());
+        // integer map key
+        assert!(Decoder::new(&[0xa1, 0x01, 0x01]).decode().is_err());
+        // truncated text
+        assert!(Decoder::new(&[0x65, b'a']).decode().is_err());
+        // hostile length
+        assert!(
+            Decoder::new(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff])
+                .decode()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_read_car_blocks() {
+        let block = [0xa1, 0x61, b'x', 0x07];
+        let mut car = vec![0x01, 0xa0]; // header length 1, empty map
+        car.push((CID_V1.len() + block.len()) as u8);
+        car.extend_from_slice(&CID_V1);
+        car.extend_from_slice(&block);
+
+        let blocks = read_car_blocks(&car).unwrap();
+        let data = blocks.get(&cid_to_string(&CID_V1)).unwrap();
+        let value = Decoder::new(data).decode().unwrap();
+        assert_eq!(value.get("x").and_then(CborValue::as_u64), Some(7));
+
+        car.pop();
+        assert!(read_car_blocks(&car).is_err());
+    }
+}
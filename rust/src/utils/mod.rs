@@ -1,3 +1,6 @@
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod dagcbor;
 pub mod interned_string;
 pub mod logging;
 pub mod metrics;
@@ -22,9 +22,7 @@ pub mod json_utils {
 pub mod string_utils {
     /// Extract DID from AT URI (e.g., "at://did:plc:test/app.bsky.feed.post/abc" -> Some("did:plc:test"))
     pub fn extract_did_from_at_uri(at_uri: &str) -> Option<&str> {
-        at_uri
-            .strip_prefix("at://")
-            .and_then(|s| s.split('/').next())
+        crate::models::AtUri::components(at_uri).map(|(did, _, _)| did)
     }
 
     /// Check if string is a valid DID
@@ -34,16 +32,7 @@ pub mod string_utils {
 
     /// Check if string is a valid AT-URI
     pub fn is_valid_at_uri(uri: &str) -> bool {
-        let trimmed = uri.trim();
-        if !trimmed.starts_with("at://") {
-            return false;
-        }
-        let parts: Vec<&str> = trimmed.split('/').collect();
-        if parts.len() < 4 {
-            return false;
-        }
-        let did_part = parts[2];
-        did_part.starts_with("did:plc:")
+        crate::models::AtUri::is_valid(uri)
     }
 
     /// Truncate string with ellipsis
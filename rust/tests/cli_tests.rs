@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("test_cli_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs the binary in `dir` with only `PATH` and `STREAM_NAME` set, so no
+/// Bluesky credentials are available, from the environment or a `.env`.
+fn run_without_credentials(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jetstream-turbo"))
+        .args(args)
+        .current_dir(dir)
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("STREAM_NAME", "test_stream")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_export_runs_without_bluesky_credentials() {
+    let dir = temp_dir();
+    let output = run_without_credentials(&dir, &["export", "--out", "snapshot.db"]);
+    assert!(
+        output.status.success(),
+        "export failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(dir.join("snapshot.db").exists());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn test_arrow_export_runs_without_bluesky_credentials() {
+    let dir = temp_dir();
+    let output = run_without_credentials(&dir, &["--export-arrow", "records.arrow"]);
+    assert!(
+        output.status.success(),
+        "export failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(dir.join("records.arrow").exists());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[cfg(feature = "s3")]
+#[test]
+fn test_restore_runs_without_bluesky_credentials() {
+    let dir = temp_dir();
+    // Gets as far as the archive settings, which aren't set here
+    let output = run_without_credentials(&dir, &["restore"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("ARCHIVE_BUCKET"),
+        "unexpected error: {stderr}"
+    );
+    assert!(!stderr.contains("BLUESKY"), "unexpected error: {stderr}");
+
+    let _ = std::fs::remove_dir_all(dir);
+}
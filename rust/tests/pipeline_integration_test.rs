@@ -1,11 +1,17 @@
-use jetstream_turbo_rs::hydration::{Hydrator, TurboCache};
+use jetstream_turbo_rs::hydration::{
+    Degradation, DegradationConfig, DegradationLevel, Hydrator, LabelFilterMode, LabelPolicy,
+    TurboCache,
+};
+use jetstream_turbo_rs::models::bluesky::Label;
 use jetstream_turbo_rs::storage::{EventPublisher, RecordStore};
 use jetstream_turbo_rs::testing::{
-    create_message_batch, create_post_message, create_profile, create_reply_message,
-    MockEventPublisher, MockPostFetcher, MockProfileFetcher, MockRecordStore,
+    create_delete_message, create_message_batch, create_post_message, create_profile,
+    create_reply_message, MessageGenerator, MockDataFetcher, MockEventPublisher, MockPostFetcher,
+    MockProfileFetcher, MockRecordStore,
 };
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
 
 /// Helper to build the full mock pipeline components.
@@ -16,7 +22,7 @@ struct TestPipeline {
     post_fetcher: Arc<MockPostFetcher>,
     record_store: Arc<MockRecordStore>,
     event_publisher: Arc<MockEventPublisher>,
-    broadcast_sender: broadcast::Sender<jetstream_turbo_rs::models::enriched::EnrichedRecord>,
+    broadcast_sender: broadcast::Sender<Arc<jetstream_turbo_rs::models::enriched::EnrichedRecord>>,
 }
 
 impl TestPipeline {
@@ -47,24 +53,24 @@ impl TestPipeline {
     async fn process_batch(
         &self,
         messages: Vec<jetstream_turbo_rs::models::jetstream::JetstreamMessage>,
-    ) -> Vec<jetstream_turbo_rs::models::enriched::EnrichedRecord> {
+    ) -> Vec<Arc<jetstream_turbo_rs::models::enriched::EnrichedRecord>> {
         // Hydrate
-        let enriched = self
+        let enriched: Vec<_> = self
             .hydrator
             .hydrate_batch(messages)
             .await
-            .expect("hydration should succeed");
+            .expect("hydration should succeed")
+            .into_iter()
+            .map(Arc::new)
+            .collect();
 
         if enriched.is_empty() {
             return enriched;
         }
 
         // Store and publish concurrently (mirrors process_batch_internal)
-        let store_records = enriched.clone();
-        let publish_records = enriched.clone();
-
-        let store_future = self.record_store.store_batch(&store_records);
-        let publish_future = self.event_publisher.publish_batch(&publish_records);
+        let store_future = self.record_store.store_batch(&enriched);
+        let publish_future = self.event_publisher.publish_batch(&enriched);
 
         let (store_result, publish_result) = tokio::join!(store_future, publish_future);
         store_result.expect("store should succeed");
@@ -72,7 +78,7 @@ impl TestPipeline {
 
         // Broadcast
         for record in &enriched {
-            let _ = self.broadcast_sender.send(record.clone());
+            let _ = self.broadcast_sender.send(Arc::clone(record));
         }
 
         enriched
@@ -252,6 +258,31 @@ async fn test_profile_fetcher_tracks_requested_dids() {
     );
 }
 
+#[tokio::test]
+async fn test_get_profile_fetches_once_then_serves_from_cache() {
+    let pipeline = TestPipeline::new();
+    let did = "did:plc:lookup";
+    pipeline
+        .profile_fetcher
+        .add_profile(create_profile(did))
+        .await;
+
+    let profile = pipeline.hydrator.get_profile(did).await.unwrap().unwrap();
+    assert_eq!(profile.handle, "lookup.bsky.social");
+    pipeline.hydrator.get_profile(did).await.unwrap().unwrap();
+    assert_eq!(
+        pipeline.profile_fetcher.call_count.load(Ordering::SeqCst),
+        1
+    );
+
+    assert!(pipeline
+        .hydrator
+        .get_profile("did:plc:unknown")
+        .await
+        .unwrap()
+        .is_none());
+}
+
 #[tokio::test]
 async fn test_multiple_batches_accumulate() {
     let pipeline = TestPipeline::new();
@@ -288,3 +319,182 @@ async fn test_multiple_batches_accumulate() {
         "publisher should have accumulated 8 records"
     );
 }
+
+#[tokio::test]
+async fn test_delete_events_pass_through_without_hydration() {
+    let pipeline = TestPipeline::new();
+
+    let results = pipeline.process_batch(vec![create_delete_message(3)]).await;
+
+    assert_eq!(results.len(), 1, "delete should still reach the sinks");
+    assert!(results[0].is_delete());
+    assert!(results[0].hydrated_metadata.author_profile.is_none());
+    assert_eq!(
+        pipeline.profile_fetcher.call_count.load(Ordering::SeqCst),
+        0,
+        "deletes should not trigger profile fetches"
+    );
+    assert_eq!(pipeline.record_store.get_stored_count().await, 1);
+}
+
+#[tokio::test]
+async fn test_label_policy_drops_labeled_authors_before_sinks() {
+    let mut pipeline = TestPipeline::new();
+    pipeline.hydrator = pipeline
+        .hydrator
+        .clone()
+        .with_label_policy(LabelPolicy::new(
+            vec!["porn".to_string()],
+            LabelFilterMode::Drop,
+        ));
+
+    let batch = create_message_batch(3);
+    for msg in &batch {
+        let mut profile = create_profile(&msg.did);
+        if msg.did == batch[1].did {
+            profile.labels = Some(vec![Label {
+                src: "did:plc:ar7c4by46qjdydhdevvrndac".to_string(),
+                uri: msg.did.clone(),
+                val: "porn".to_string(),
+                cts: chrono::Utc::now(),
+                neg: None,
+            }]);
+        }
+        pipeline.profile_fetcher.add_profile(profile).await;
+    }
+
+    let results = pipeline.process_batch(batch.clone()).await;
+
+    assert_eq!(results.len(), 2, "labeled record should be dropped");
+    assert!(results.iter().all(|r| r.message.did != batch[1].did));
+    assert_eq!(pipeline.record_store.get_stored_count().await, 2);
+}
+
+#[tokio::test]
+async fn test_skip_post_fetches_hydrates_posts_from_cache_only() {
+    let pipeline = TestPipeline::new();
+    let reply = create_reply_message(1, "did:plc:parentuser", "3mepgzgia0001");
+    pipeline
+        .profile_fetcher
+        .add_profile(create_profile(&reply.did))
+        .await;
+
+    pipeline.hydrator.clone().set_skip_post_fetches(true);
+    let results = pipeline.process_batch(vec![reply.clone()]).await;
+    assert_eq!(results.len(), 1);
+    assert_eq!(pipeline.post_fetcher.call_count.load(Ordering::SeqCst), 0);
+    assert!(pipeline.profile_fetcher.call_count.load(Ordering::SeqCst) > 0);
+
+    pipeline.hydrator.set_skip_post_fetches(false);
+    pipeline.process_batch(vec![reply]).await;
+    assert_eq!(pipeline.post_fetcher.call_count.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_degraded_hydration_skips_fetches_and_marks_records_partial() {
+    let mut pipeline = TestPipeline::new();
+    let degradation = Arc::new(Degradation::new(DegradationConfig {
+        escalation_interval: Duration::ZERO,
+        ..Default::default()
+    }));
+    pipeline.hydrator = pipeline
+        .hydrator
+        .clone()
+        .with_degradation(Arc::clone(&degradation));
+    let reply = create_reply_message(1, "did:plc:parentuser", "3mepgzgia0001");
+    pipeline
+        .profile_fetcher
+        .add_profile(create_profile(&reply.did))
+        .await;
+
+    let results = pipeline.process_batch(vec![reply.clone()]).await;
+    assert!(!results[0].hydrated_metadata.partial);
+    assert_eq!(pipeline.post_fetcher.call_count.load(Ordering::SeqCst), 1);
+
+    // A rate-limited fetch stops post fetches
+    degradation.observe_fetch(Duration::ZERO, true);
+    assert_eq!(degradation.level(), DegradationLevel::SkipPosts);
+    let results = pipeline.process_batch(vec![reply]).await;
+    assert!(results[0].hydrated_metadata.partial);
+    assert!(results[0].hydrated_metadata.author_profile.is_some());
+    assert_eq!(pipeline.post_fetcher.call_count.load(Ordering::SeqCst), 1);
+
+    // Another stops profile fetches too
+    degradation.observe_fetch(Duration::ZERO, true);
+    let profile_calls = pipeline.profile_fetcher.call_count.load(Ordering::SeqCst);
+    let other = create_post_message(2);
+    let results = pipeline.process_batch(vec![other]).await;
+    assert!(results[0].hydrated_metadata.partial);
+    assert!(results[0].hydrated_metadata.author_profile.is_none());
+    assert_eq!(
+        pipeline.profile_fetcher.call_count.load(Ordering::SeqCst),
+        profile_calls
+    );
+
+    let stats = pipeline.hydrator.degradation_stats().unwrap();
+    assert_eq!(stats.level, DegradationLevel::CacheOnly);
+    assert_eq!(stats.escalations, 2);
+    assert_eq!(stats.partial_records, 2);
+}
+
+#[tokio::test]
+async fn test_hydrator_runs_against_a_single_data_fetcher() {
+    let fetcher = Arc::new(MockDataFetcher::new());
+    let reply = create_reply_message(1, "did:plc:parentuser", "3mepgzgia0001");
+    fetcher
+        .profiles
+        .add_profile(create_profile(&reply.did))
+        .await;
+
+    let hydrator = Hydrator::from_fetcher(TurboCache::new(100, 100), Arc::clone(&fetcher));
+    let results = hydrator.hydrate_batch(vec![reply]).await.unwrap();
+
+    assert_eq!(results.len(), 1);
+    assert!(results[0].hydrated_metadata.author_profile.is_some());
+    assert!(fetcher.profiles.call_count.load(Ordering::SeqCst) > 0);
+    assert_eq!(fetcher.posts.call_count.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_generated_traffic_hydrates_hashtags_and_mentions() {
+    let pipeline = TestPipeline::new();
+    for index in 0..20 {
+        pipeline
+            .profile_fetcher
+            .add_profile(create_profile(&MessageGenerator::user_did(index)))
+            .await;
+    }
+
+    let messages = MessageGenerator::new(11).with_users(20).batch(200);
+    let posts = messages
+        .iter()
+        .filter(|message| {
+            message.commit.as_ref().is_some_and(|commit| {
+                commit.collection.as_deref() == Some("app.bsky.feed.post")
+                    && commit.record.is_some()
+            })
+        })
+        .count();
+    let results = pipeline.process_batch(messages).await;
+
+    let hydrated_posts: Vec<_> = results
+        .iter()
+        .filter(|record| record.get_text().is_some())
+        .collect();
+    assert_eq!(hydrated_posts.len(), posts);
+    for record in &hydrated_posts {
+        assert_eq!(record.hydrated_metadata.hashtags.len(), 1);
+        assert!(record.hydrated_metadata.author_profile.is_some());
+        // Every mentioned account was resolved; reply parents are added too
+        for mention in &record.hydrated_metadata.mentions {
+            assert!(record
+                .hydrated_metadata
+                .mentioned_profiles
+                .iter()
+                .any(|profile| profile.did == mention.did));
+        }
+    }
+    assert!(hydrated_posts
+        .iter()
+        .any(|record| !record.hydrated_metadata.mentions.is_empty()));
+}